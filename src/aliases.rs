@@ -0,0 +1,177 @@
+use std::collections::BTreeMap;
+
+// Command aliases defined via `:alias NAME COMMAND`, e.g., `:alias tl timeline`.
+// An alias replaces the first word of a command, so any arguments are kept:
+// `:tl ts` runs `:timeline ts`.
+#[derive(Default)]
+pub struct Aliases(BTreeMap<String, String>);
+
+impl Aliases {
+    // Parse the argument to :alias, which is the name of the alias followed
+    // by the command it should run.
+    pub fn define(&mut self, definition: &str) -> Result<String, String> {
+        let Some((name, command)) = definition.trim().split_once(char::is_whitespace) else {
+            return Err("Usage: :alias NAME COMMAND".to_string());
+        };
+        let command = alias_command(name, command)?;
+        let message = format!("Defined alias {name} for :{command}");
+        self.0.insert(name.to_string(), command.to_string());
+        Ok(message)
+    }
+
+    // Define the aliases from the config file, e.g., `aliases.tl = "timeline"`,
+    // replacing any with the same names.
+    pub fn define_all(&mut self, aliases: &[(String, String)]) {
+        for (name, command) in aliases {
+            self.0.insert(name.clone(), command.clone());
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.0.remove(name).is_some()
+    }
+
+    // Expand any aliases at the start of a command, failing if an alias
+    // (eventually) expands to itself. Aliases can expand to other aliases,
+    // so we keep track of which ones we've already expanded.
+    pub fn resolve(&self, command: &str) -> Result<String, String> {
+        let mut command = command.to_string();
+        let mut expanded: Vec<&str> = vec![];
+
+        while let Some((name, expansion)) = self.alias_at_start_of(&command) {
+            if expanded.contains(&name) {
+                expanded.push(name);
+                return Err(format!("Recursive alias: {}", expanded.join(" -> ")));
+            }
+            expanded.push(name);
+
+            let args = &command[name.len()..];
+            command = format!("{expansion}{args}");
+        }
+
+        Ok(command)
+    }
+
+    fn alias_at_start_of(&self, command: &str) -> Option<(&str, &str)> {
+        let name = command.split(char::is_whitespace).next()?;
+        self.0
+            .get_key_value(name)
+            .map(|(name, expansion)| (name.as_str(), expansion.as_str()))
+    }
+
+    pub fn description(&self) -> String {
+        if self.0.is_empty() {
+            return "No aliases defined; use :alias NAME COMMAND".to_string();
+        }
+
+        let aliases: Vec<String> = self
+            .0
+            .iter()
+            .map(|(name, command)| format!("{name} = :{command}"))
+            .collect();
+        format!("Aliases: {}", aliases.join(", "))
+    }
+}
+
+// Parse an alias given with --alias NAME=COMMAND, which is how aliases in
+// the config file are passed along.
+pub fn parse_alias(definition: &str) -> Result<(String, String), String> {
+    let Some((name, command)) = definition.split_once('=') else {
+        return Err("Expected NAME=COMMAND".to_string());
+    };
+    let name = name.trim();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(format!("Invalid alias name: {name:?}"));
+    }
+    let command = alias_command(name, command)?;
+    Ok((name.to_string(), command.to_string()))
+}
+
+// The command an alias runs, which may start with a ':', as if it were
+// typed at the prompt.
+fn alias_command<'a>(name: &str, command: &'a str) -> Result<&'a str, String> {
+    let command = command.trim();
+    let command = command.strip_prefix(':').unwrap_or(command).trim_start();
+    if command.is_empty() {
+        return Err(format!("Missing command for alias {name}"));
+    }
+    Ok(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aliases() {
+        let mut aliases = Aliases::default();
+        assert!(aliases.define("tl").is_err());
+        assert_eq!(
+            Ok("Defined alias tl for :timeline".to_string()),
+            aliases.define("tl timeline")
+        );
+        aliases.define("ts  tl ts").unwrap();
+        // The ':' is optional.
+        assert_eq!(
+            Ok("Defined alias f for :filter .x".to_string()),
+            aliases.define("f :filter .x")
+        );
+        assert_eq!(Ok("filter .x y".to_string()), aliases.resolve("f y"));
+        assert_eq!(
+            Err("Missing command for alias f".to_string()),
+            aliases.define("f :")
+        );
+        aliases.define("d set dense!").unwrap();
+
+        assert_eq!(Ok("timeline".to_string()), aliases.resolve("tl"));
+        assert_eq!(
+            Ok("timeline field".to_string()),
+            aliases.resolve("tl field")
+        );
+        assert_eq!(Ok("timeline ts".to_string()), aliases.resolve("ts"));
+        assert_eq!(Ok("set dense!".to_string()), aliases.resolve("d"));
+        // Only whole words are expanded.
+        assert_eq!(Ok("tls".to_string()), aliases.resolve("tls"));
+        assert_eq!(Ok("largest".to_string()), aliases.resolve("largest"));
+
+        assert_eq!(
+            "Aliases: d = :set dense!, f = :filter .x, tl = :timeline, ts = :tl ts",
+            aliases.description()
+        );
+
+        aliases.define("a b").unwrap();
+        aliases.define("b c x").unwrap();
+        aliases.define("c a").unwrap();
+        assert_eq!(
+            Err("Recursive alias: a -> b -> c -> a".to_string()),
+            aliases.resolve("a")
+        );
+        aliases.define("self self").unwrap();
+        assert_eq!(
+            Err("Recursive alias: self -> self".to_string()),
+            aliases.resolve("self")
+        );
+
+        assert!(aliases.remove("a"));
+        assert!(!aliases.remove("a"));
+        assert_eq!(Ok("a x".to_string()), aliases.resolve("b"));
+
+        aliases.define_all(&[("d".to_string(), "set dense".to_string())]);
+        assert_eq!(Ok("set dense".to_string()), aliases.resolve("d"));
+    }
+
+    #[test]
+    fn test_parse_alias() {
+        assert_eq!(
+            Ok(("tl".to_string(), "timeline ts".to_string())),
+            parse_alias("tl = :timeline ts")
+        );
+        assert_eq!(
+            Ok(("e".to_string(), "set a=b".to_string())),
+            parse_alias("e=set a=b")
+        );
+        assert!(parse_alias("tl").is_err());
+        assert!(parse_alias("t l=timeline").is_err());
+        assert!(parse_alias("tl=:").is_err());
+    }
+}
@@ -1,36 +1,244 @@
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
 use std::io;
 use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Instant, SystemTime};
 
 use clipboard::{ClipboardContext, ClipboardProvider};
+use regex::Regex;
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
 use termion::event::Key;
 use termion::event::MouseButton::{Left, WheelDown, WheelUp};
 use termion::event::MouseEvent::Press;
-use termion::raw::RawTerminal;
 use termion::screen::{ToAlternateScreen, ToMainScreen};
 
-use crate::flatjson;
-use crate::input::TuiEvent;
+use crate::aliases::Aliases;
+use crate::capabilities::{self, Capabilities};
+use crate::chunks::{self, ChunkStrategy};
+use crate::config;
+use crate::control::ControlCommand;
+use crate::diff;
+use crate::flatjson::{self, OptionIndex, PathType, ValueType};
+use crate::highlighting::Background;
+use crate::history::ValueHistory;
 use crate::input::TuiEvent::{KeyEvent, MouseEvent, WinChEvent};
-use crate::jsonstringunescaper::unescape_json_string;
-use crate::lineprinter::JS_IDENTIFIER;
-use crate::options::{DataFormat, Opt};
-use crate::screenwriter::{MessageSeverity, ScreenWriter};
-use crate::search::{JumpDirection, SearchDirection, SearchState};
+use crate::input::{self, TuiEvent};
+use crate::jq;
+use crate::jsonstringunescaper::{unescape_json_string, ControlCharacters};
+use crate::jwt;
+use crate::keycompleter::{Completion, KeyCompleter};
+use crate::keymap::Keymap;
+use crate::lineprinter::{format_size, NumberBase, NumberMode, PreviewOrder, JS_IDENTIFIER};
+use crate::ndjson::Records;
+use crate::options::{DataFormat, Opt, PickTarget};
+use crate::outline::{Outline, OutlineKind};
+use crate::pager::Pager;
+use crate::position::{self, SavedPosition};
+use crate::resultbuffer::ResultBuffer;
+use crate::rng::Rng;
+use crate::screenwriter::{MessageSeverity, Output, ScreenWriter};
+use crate::script::Script;
+use crate::search::{
+    HiddenMatches, JumpDirection, SearchDefaults, SearchDirection, SearchIndex, SearchOffset,
+    SearchState,
+};
+use crate::sha256;
+use crate::smoothscroll::{SmoothScroll, WheelDirection};
+use crate::table::Table;
+use crate::timeline::Timeline;
+use crate::tutor::Tutor;
 use crate::types::TTYDimensions;
-use crate::viewer::{Action, JsonViewer, Mode};
+use crate::undo::{Change, UndoHistory};
+use crate::viewer::{Action, JsonViewer, Mode, SubFocus};
 
 pub struct App {
     viewer: JsonViewer,
     screen_writer: ScreenWriter,
     input_state: InputState,
     input_buffer: Vec<u8>,
+    // Only present while content printed with p is shown a page at a time.
+    pager: Option<Pager>,
     input_filename: String,
+    // None when the input was read from STDIN.
+    input_path: Option<PathBuf>,
+    data_format: DataFormat,
+    // Whether NaN and Infinity are accepted in JSON input (--allow-nan).
+    allow_nan: bool,
+    // Used by :sample; seeded with --seed if given.
+    rng: Rng,
+    // Whether JSON input that fails to parse is parsed line by line,
+    // skipping bad lines (--tolerant).
+    tolerant: bool,
+    // Set when running with --pick, in which case pressing Enter prints
+    // the focused path or value and exits.
+    pick: Option<PickTarget>,
+    picked: Option<String>,
+    // Lines of newline-delimited JSON input that failed to parse and were
+    // skipped.
+    parse_errors: Vec<flatjson::LineError>,
+    // Only present while keys are hidden via `:hide key=PATTERN`.
+    hidden_keys: Option<HiddenKeys>,
+    // Only present while a string is split into chunks via `:chunk`.
+    chunked_string: Option<ChunkedString>,
+    // Only present while the document is filtered through jq via `:filter`.
+    filter: Option<Filter>,
+    // Set while newline-delimited JSON input is still being read.
+    loading_records: bool,
+    // With --follow, whether the last record stays focused as more records
+    // are appended, which stops once the user moves.
+    following: bool,
+    // Records that were read while the document was a modified copy of the
+    // input, e.g., while keys were hidden, which get appended once it isn't.
+    pending_records: Vec<Records>,
+    // Markers for rows that changed when the input was last reloaded,
+    // including objects whose keys were only reordered, which are only
+    // shown in the gutter when show_key_order_changes is set.
+    diff_markers: HashMap<flatjson::Index, diff::DiffMarker>,
+    show_key_order_changes: bool,
+    // Changes made with :edit and :delete, which u undoes and ctrl-r
+    // redoes.
+    undo_history: UndoHistory,
+    // Whether the document was changed, e.g., with :edit, since the input
+    // was loaded or the document was last written with :w.
+    modified: bool,
     search_state: SearchState,
+    // Only present when started with `--search-index`.
+    search_index: Option<SearchIndex>,
+    // Where the focus was before the last jump to a search match, which ''
+    // goes back to.
+    jump_origin: Option<flatjson::Index>,
+    // How to handle search matches inside collapsed containers.
+    hidden_matches: HiddenMatches,
+    // Whether to preview matches while a search is being typed.
+    incsearch: bool,
+    // Whether searches match whole words or fixed strings by default.
+    search_defaults: SearchDefaults,
+    // Only present while a search is being typed with incsearch enabled.
+    incremental_search: Option<IncrementalSearch>,
+    smooth_scroll: SmoothScroll,
     message: Option<(String, MessageSeverity)>,
+    // Only present when running `jless --tutor`.
+    tutor: Option<Tutor>,
+    // Only present while the outline panel is open.
+    outline: Option<Outline>,
+    // Only present while the timeline is shown.
+    timeline: Option<Timeline>,
+    // Only present while an Array is shown as a table, in place of the
+    // viewer.
+    table: Option<Table>,
+    // Rows pinned via :pin, in the order they were pinned.
+    pinned_rows: Vec<flatjson::Index>,
+    // Only present while the screen is split via :split.
+    split: Option<Split>,
+    // Result buffers shown in place of the document, most recently opened
+    // last, each with the document it replaced.
+    result_buffers: Vec<(ResultBuffer, SavedDocument)>,
+    // Whether the two views of a split screen share which containers are
+    // collapsed.
+    shared_split_collapse: bool,
+    aliases: Aliases,
+    // Filters defined in the config file, which `:filter @NAME` runs.
+    named_filters: BTreeMap<String, String>,
+    keymap: Keymap,
+    value_history: ValueHistory,
+    // When the input was last (re)loaded.
+    loaded_at: SystemTime,
+    // Commands from `--cmd` that haven't been run yet.
+    script: Script,
+    capabilities: Capabilities,
     clipboard_context: Result<ClipboardContext, Box<dyn Error>>,
+    // The config file that options were loaded from, which gets reloaded
+    // when it changes.
+    config_path: Option<PathBuf>,
+    // The background the theme was chosen for, which may have been
+    // detected rather than set by an option.
+    background: Option<Background>,
+}
+
+// While object entries with keys matching a pattern are hidden, the
+// displayed document is a filtered copy of the original one.
+struct HiddenKeys {
+    pattern: Regex,
+    unfiltered: flatjson::FlatJson,
+    // The index in the unfiltered document of each row in the filtered one.
+    kept_rows: Vec<flatjson::Index>,
+}
+
+impl HiddenKeys {
+    fn unfiltered_index(&self, index: flatjson::Index) -> flatjson::Index {
+        // The filtered document may have been replaced since, e.g., by :jwt.
+        self.kept_rows.get(index).copied().unwrap_or(0)
+    }
+}
+
+// While a string is split into an array of chunks, the displayed document
+// is a modified copy of the original one, which is restored by :unchunk.
+struct ChunkedString {
+    original: flatjson::FlatJson,
+    // The row of the string in the original document, which the array of
+    // chunks replaces.
+    string_row: flatjson::Index,
+    // How many more rows the array has than the string did.
+    added_rows: usize,
+}
+
+// While the document is replaced with the output of a jq filter, the
+// document that isn't shown is kept, so that ^^ can switch between the
+// filtered document and the original one.
+struct Filter {
+    expression: String,
+    showing_original: bool,
+    other: flatjson::FlatJson,
+    other_parse_errors: Vec<flatjson::LineError>,
+    other_focused_row: flatjson::Index,
+}
+
+// A search being typed when incsearch is enabled. The first match of
+// what's been typed so far is previewed; pressing Enter runs the search as
+// usual, and pressing Escape goes back to where the search started.
+struct IncrementalSearch {
+    direction: SearchDirection,
+    jumps: usize,
+    search_input: String,
+    start_row: flatjson::Index,
+    start_top_row: flatjson::Index,
+    // The search from before this one started, restored if it's cancelled.
+    previous_search_state: SearchState,
+    // Collapsed containers that were expanded to preview a match, which
+    // get collapsed again before previewing the next one.
+    expanded_rows: Vec<flatjson::Index>,
+}
+
+// A second view of the document, shown when the screen is split. The
+// document itself always belongs to the focused view, so the other view
+// holds an empty placeholder until focus is toggled with Ctrl-W.
+struct Split {
+    other: JsonViewer,
+    other_is_top: bool,
+    // The containers collapsed in the other view, when the two views don't
+    // share their collapsed state.
+    other_collapsed: Option<Vec<flatjson::Index>>,
+}
+
+// A document that was replaced by a result buffer, along with everything
+// that refers to its rows, which is restored when the buffer is closed.
+struct SavedDocument {
+    viewer: JsonViewer,
+    input_filename: String,
+    parse_errors: Vec<flatjson::LineError>,
+    hidden_keys: Option<HiddenKeys>,
+    chunked_string: Option<ChunkedString>,
+    filter: Option<Filter>,
+    diff_markers: HashMap<flatjson::Index, diff::DiffMarker>,
+    search_state: SearchState,
+    search_index: Option<SearchIndex>,
+    jump_origin: Option<flatjson::Index>,
+    pinned_rows: Vec<flatjson::Index>,
+    outline: Option<Outline>,
+    timeline: Option<Timeline>,
+    following: bool,
 }
 
 // State to determine how to process the next event input.
@@ -48,7 +256,11 @@ enum InputState {
     PendingPCommand,
     PendingYCommand,
     PendingZCommand,
+    PendingBracketCommand,
+    PendingTypeahead,
+    IncrementalSearch,
     WaitingForAnyKeyPress,
+    Paging,
 }
 
 // Various things that can be copied/printed.
@@ -56,18 +268,120 @@ enum InputState {
 enum ContentTarget {
     PrettyPrintedValue,
     OneLineValue,
+    YamlValue,
+    // Mostly useful for YAML input, where the keys and numbers need to be
+    // converted to their JSON equivalents.
+    JsonValue,
+    // Only supported for objects.
+    TomlValue,
     String,
+    // String contents with control characters unescaped too; only
+    // supported for copying, since printing them would mess with the
+    // terminal.
+    RawString,
     Key,
     DotPath,
     BracketPath,
     QueryPath,
+    // A shell command that runs jq on the input file to extract the
+    // focused value.
+    JqCommand,
+    // The first capture group of the focused search match, or of every
+    // search match.
+    CaptureGroup,
+    AllCaptureGroups,
+    // The values of all the rows selected with 'x', as an array.
+    SelectedValues,
+    // The SHA-256 of the canonical form of the focused value.
+    Checksum,
+    // The path from the row marked with 'M' to the focused row.
+    RelativePath,
 }
 
 enum Command {
     Quit,
     Help,
+    Reload,
+    ShowErrors,
+    Sample,
+    Reverse,
+    Largest,
+    Longest,
+    FindType(String),
+    // Count the matches of a search within the focused value.
+    Count(String),
+    DecodeJwt,
+    Chunk(String),
+    Unchunk,
+    // Edit the focused value, prompting for the new value if not given.
+    Edit(Option<String>),
+    Delete,
+    Undo,
+    Redo,
+    // Toggle the timeline, or show it using the given timestamp field.
+    Timeline(Option<String>),
+    Table,
+    HideKeys(String),
+    UnhideKeys,
+    // Filter the document, or just the focused value, through jq.
+    Filter {
+        expression: String,
+        focused_value: bool,
+    },
+    Unfilter,
+    Follow,
+    Pin,
+    Unpin,
+    Alias(String),
+    Unalias(String),
+    ShowAliases,
+    ShowHistory,
+    Checksum,
+    ShowMatches,
+    SavePosition(String),
+    // Write the document to a file, or else back to the input file,
+    // overwriting existing files if forced.
+    Write {
+        path: Option<String>,
+        force: bool,
+    },
+    // Open another file in place of the input, discarding unwritten
+    // changes if forced.
+    Open {
+        path: String,
+        force: bool,
+    },
+    Split,
+    // Close the split, keeping either the focused view (:only) or the
+    // other one (:close).
+    CloseSplit {
+        keep_focused: bool,
+    },
     SetShowLineNumber(Option<bool>),
     SetShowRelativeLineNumber(Option<bool>),
+    SetNumberMode(NumberMode),
+    SetShowPreviews(Option<bool>),
+    SetPreviewOrder(PreviewOrder),
+    SetAccordionMode(Option<bool>),
+    SetDense(Option<bool>),
+    SetFoldRuns(Option<bool>),
+    SetShowInvisibles(Option<bool>),
+    SetShowContainerGauge(Option<bool>),
+    SetShowSizes(Option<bool>),
+    SetShowNumberTypes(Option<bool>),
+    SetNumberBase(NumberBase),
+    SetShowDates(Option<bool>),
+    SetHighlightFocusedLine(Option<bool>),
+    SetIncrementalSearch(Option<bool>),
+    SetWholeWord(Option<bool>),
+    SetFixedStrings(Option<bool>),
+    SetSmoothScroll(Option<bool>),
+    SetAlignKeys(Option<bool>),
+    SetShowIndentGuides(Option<bool>),
+    SetHiddenMatches(HiddenMatches),
+    SetSharedSplitCollapse(bool),
+    SetRememberLastChild(Option<bool>),
+    SetShowKeyOrderChanges(Option<bool>),
     Unknown,
 }
 
@@ -75,6 +389,16 @@ enum Command {
 const HELP: &str = std::include_str!("./jless.help");
 
 pub const MAX_BUFFER_SIZE: usize = 9;
+
+// Pinning more rows than this unpins the oldest ones.
+const MAX_PINNED_ROWS: usize = 5;
+
+// Each view of a split screen needs at least one row, plus the separator.
+const MIN_SPLIT_HEIGHT: u16 = 3;
+
+// Copying values larger than this to the clipboard requires confirmation,
+// since the clipboard provider may take a long time to handle them.
+const LARGE_CLIPBOARD_PAYLOAD_BYTES: usize = 32 * 1024 * 1024;
 const BELL: &str = "\x07";
 
 // https://docs.rs/termion/2.0.1/src/termion/input.rs.html#176-180
@@ -103,46 +427,249 @@ impl App {
         data: String,
         data_format: DataFormat,
         input_filename: String,
-        stdout: RawTerminal<Box<dyn Write>>,
+        stdout: Box<dyn Output>,
     ) -> Result<App, String> {
-        let flatjson = match Self::parse_input(data, data_format) {
-            Ok(flatjson) => flatjson,
-            Err(err) => return Err(format!("Unable to parse input: {err:?}")),
+        let capabilities = Capabilities::from_options(opt);
+        // Don't even connect to the clipboard if it's not allowed.
+        let clipboard_context = match Capabilities::check(capabilities.clipboard, "Copying") {
+            Ok(()) => ClipboardProvider::new(),
+            Err(err) => Err(err.into()),
         };
 
+        let (flatjson, parse_errors) =
+            match Self::parse_input(data, data_format, opt.allow_nan, opt.tolerant) {
+                Ok(parsed) => parsed,
+                Err(err) => return Err(format!("Unable to parse input: {err:?}")),
+            };
+
         let mut viewer = JsonViewer::new(flatjson, opt.mode);
         viewer.scrolloff_setting = opt.scrolloff;
+        viewer.accordion_mode = opt.accordion;
+        viewer.dense = opt.dense;
+        viewer.remember_last_child = opt.remember_last_child;
+        viewer.set_fold_runs(opt.fold_runs);
+        if opt.expand_spine {
+            let end_of_spine = viewer.flatjson.expand_spine();
+            viewer.perform_action(Action::JumpTo {
+                line: end_of_spine,
+                make_visible: false,
+            });
+        }
+
+        let mut restore_warning = None;
+        if let Some(path) = &opt.restore_position {
+            let position = std::fs::read_to_string(path)
+                .map_err(|err| err.to_string())
+                .and_then(|contents| SavedPosition::parse(&contents))
+                .map_err(|err| {
+                    format!("Unable to restore position from {}: {err}", path.display())
+                })?;
+            let (focused_row, num_missing) = position.restore(&mut viewer.flatjson);
+            viewer.perform_action(Action::JumpTo {
+                line: focused_row,
+                make_visible: false,
+            });
+            if num_missing > 0 {
+                let plural = if num_missing == 1 { "" } else { "s" };
+                restore_warning = Some(format!(
+                    "{num_missing} saved path{plural} not found in the input"
+                ));
+            }
+        }
+
+        // The older version is assumed to be in the same format.
+        let mut diff_message = None;
+        let diff_markers = match &opt.diff {
+            Some(path) => {
+                let (old, _) = std::fs::read_to_string(path)
+                    .map_err(|err| err.to_string())
+                    .and_then(|data| {
+                        Self::parse_input(data, data_format, opt.allow_nan, opt.tolerant)
+                    })
+                    .map_err(|err| format!("Unable to read {}: {err}", path.display()))?;
+                let diff_markers = diff::diff_markers(&old, &viewer.flatjson);
+                diff_message = Some(if diff_markers.is_empty() {
+                    format!("No differences from {}", path.display())
+                } else {
+                    format!(
+                        "Showing changes from {}; use ]c and [c to jump between them",
+                        path.display()
+                    )
+                });
+                diff_markers
+            }
+            None => HashMap::new(),
+        };
 
-        let screen_writer =
-            ScreenWriter::init(opt, stdout, Editor::<()>::new(), TTYDimensions::default());
+        let mut aliases = Aliases::default();
+        aliases.define_all(&opt.aliases);
+
+        let mut command_editor = Editor::<KeyCompleter>::new();
+        let named_filters: BTreeMap<String, String> = opt.named_filters.iter().cloned().collect();
+        let mut key_completer = KeyCompleter::new(&viewer.flatjson);
+        key_completer.filter_names = named_filters.keys().cloned().collect();
+        command_editor.set_helper(Some(key_completer));
+
+        let mut screen_writer =
+            ScreenWriter::init(opt, stdout, command_editor, TTYDimensions::default());
+        screen_writer.diff_markers = diff_markers.clone();
+
+        let search_index = if opt.search_index {
+            Some(SearchIndex::new(&viewer.flatjson.1))
+        } else {
+            None
+        };
+
+        let tutor = if opt.tutor { Some(Tutor::new()) } else { None };
+
+        let message = if let Some(tutor) = &tutor {
+            Some((tutor.welcome_message(), MessageSeverity::Info))
+        } else if !parse_errors.is_empty() {
+            Some((
+                Self::skipped_lines_message(&parse_errors),
+                MessageSeverity::Warn,
+            ))
+        } else if viewer.flatjson.has_non_finite_numbers() {
+            Some((
+                "Input contains NaN or Infinity, so it isn't strictly valid JSON".to_string(),
+                MessageSeverity::Warn,
+            ))
+        } else if let Some(warning) = restore_warning {
+            Some((warning, MessageSeverity::Warn))
+        } else if let Some(message) = diff_message {
+            Some((message, MessageSeverity::Info))
+        } else if let Some(pick) = opt.pick {
+            let target = match pick {
+                PickTarget::Path => "path",
+                PickTarget::Value => "value",
+            };
+            Some((
+                format!("Press Enter to pick the focused {target}, or q to quit"),
+                MessageSeverity::Info,
+            ))
+        } else {
+            None
+        };
 
         Ok(App {
             viewer,
             screen_writer,
             input_state: InputState::Default,
             input_buffer: vec![],
+            pager: None,
             input_filename,
+            input_path: opt.input.clone().filter(|path| path.as_os_str() != "-"),
+            data_format,
+            allow_nan: opt.allow_nan,
+            rng: opt.seed.map_or_else(Rng::from_time, Rng::new),
+            tolerant: opt.tolerant,
+            pick: opt.pick,
+            picked: None,
+            parse_errors,
+            hidden_keys: None,
+            chunked_string: None,
+            filter: None,
+            loading_records: false,
+            following: opt.follow,
+            pending_records: vec![],
+            diff_markers,
+            show_key_order_changes: true,
+            undo_history: UndoHistory::default(),
+            modified: false,
             search_state: SearchState::empty(),
-            message: None,
-            clipboard_context: ClipboardProvider::new(),
+            search_index,
+            jump_origin: None,
+            hidden_matches: HiddenMatches::Collapsed,
+            incsearch: opt.incsearch,
+            search_defaults: SearchDefaults {
+                whole_word: opt.whole_word,
+                fixed_string: opt.fixed_strings,
+            },
+            incremental_search: None,
+            smooth_scroll: SmoothScroll::new(opt),
+            message,
+            tutor,
+            outline: None,
+            timeline: None,
+            table: None,
+            pinned_rows: vec![],
+            split: None,
+            result_buffers: vec![],
+            shared_split_collapse: true,
+            aliases,
+            named_filters,
+            keymap: Keymap::new(&opt.key_mappings),
+            value_history: ValueHistory::default(),
+            loaded_at: SystemTime::now(),
+            script: Script::parse(&opt.startup_commands),
+            capabilities,
+            clipboard_context,
+            config_path: None,
+            background: opt.background,
         })
     }
 
-    fn parse_input(data: String, data_format: DataFormat) -> Result<flatjson::FlatJson, String> {
+    fn parse_input(
+        data: String,
+        data_format: DataFormat,
+        allow_nan: bool,
+        tolerant: bool,
+    ) -> Result<(flatjson::FlatJson, Vec<flatjson::LineError>), String> {
         match data_format {
-            DataFormat::Json => flatjson::parse_top_level_json(data),
-            DataFormat::Yaml => flatjson::parse_top_level_yaml(data),
+            DataFormat::Json if tolerant => {
+                flatjson::parse_top_level_json_tolerating_bad_lines(data, allow_nan)
+            }
+            DataFormat::Json => Ok((
+                flatjson::parse_top_level_json_allowing_nan(data, allow_nan)?,
+                vec![],
+            )),
+            DataFormat::Yaml => Ok((flatjson::parse_top_level_yaml(data)?, vec![])),
+            DataFormat::JsonSeq => flatjson::parse_top_level_json_seq(data, allow_nan),
+            DataFormat::Ndjson => flatjson::parse_top_level_ndjson(data, allow_nan),
+            DataFormat::Hjson => Ok((flatjson::parse_top_level_hjson(data)?, vec![])),
         }
     }
 
-    pub fn run(&mut self, input: Box<dyn Iterator<Item = io::Result<TuiEvent>>>) {
-        let dimensions = TTYDimensions::from_size(termion::terminal_size().unwrap());
-        self.viewer.dimensions = dimensions.without_status_bar();
+    fn skipped_lines_message(parse_errors: &[flatjson::LineError]) -> String {
+        let num_errors = parse_errors.len();
+        let plural = if num_errors == 1 { "" } else { "s" };
+        format!("Skipped {num_errors} line{plural} that failed to parse; type :errors to view")
+    }
+
+    pub fn run(
+        &mut self,
+        input: Box<dyn Iterator<Item = io::Result<TuiEvent>>>,
+        dimensions: TTYDimensions,
+    ) {
         self.screen_writer.dimensions = dimensions;
+        self.viewer.dimensions = self.viewer_dimensions();
+        // Where to start (e.g., via --expand-spine or --restorepos) is decided
+        // before we know the size of the screen, so show it with some context
+        // now that we do.
+        if self.following {
+            self.viewer.perform_action(Action::FocusBottom);
+        }
+        if self.viewer.focused_row != 0 {
+            self.viewer.top_row = 0;
+            self.viewer
+                .perform_action(Action::Reveal(self.viewer.focused_row));
+        }
         self.draw_screen();
 
+        let script_events = std::mem::take(&mut self.script.events);
+        let input = script_events.into_iter().map(Ok).chain(input);
+
         for event in input {
             let event = match event {
+                // These aren't input from the user, so they shouldn't change
+                // the input state, or clear the current message.
+                Ok(TuiEvent::Records(records)) => {
+                    self.receive_records(records);
+                    if self.input_state != InputState::WaitingForAnyKeyPress {
+                        self.draw_screen();
+                    }
+                    continue;
+                }
                 Ok(event) => event,
                 Err(io_error) => {
                     self.set_error_message(format!("Error: {io_error}"));
@@ -156,15 +683,44 @@ impl App {
             // state. (We ignore the actual value of the key they press.)
             if self.input_state == InputState::WaitingForAnyKeyPress {
                 if matches!(event, KeyEvent(_)) {
-                    let _ = write!(self.screen_writer.stdout, "{ToAlternateScreen}");
-                    let _ = write!(self.screen_writer.stdout, "{ENABLE_MOUSE_BUTTON_TRACKING}");
-                    self.input_state = InputState::Default;
-                    self.draw_screen();
-                    self.message = None;
+                    self.return_from_printed_content();
+                }
+                continue;
+            }
+
+            // Similarly, while printed content is being paged through, keys
+            // only move between pages, until the user is done with it.
+            if self.input_state == InputState::Paging {
+                if let KeyEvent(key) = event {
+                    let pager = self.pager.as_mut().unwrap();
+                    match key {
+                        Key::Char('b') | Key::Ctrl('b') | Key::PageUp => {
+                            pager.prev_page();
+                            self.draw_page();
+                        }
+                        Key::Char('q') | Key::Esc | Key::Ctrl('c') => {
+                            self.return_from_printed_content()
+                        }
+                        _ if pager.is_last_page() => self.return_from_printed_content(),
+                        Key::Char(' ') | Key::Char('f') | Key::Ctrl('f') | Key::PageDown => {
+                            pager.next_page();
+                            self.draw_page();
+                        }
+                        _ => {}
+                    }
                 }
                 continue;
             }
 
+            // Remapped keys are handled as if the key they're mapped to was
+            // pressed, but only when they start a new command.
+            let event = match event {
+                KeyEvent(key) if self.input_state == InputState::Default => {
+                    KeyEvent(self.keymap.get(key))
+                }
+                event => event,
+            };
+
             // If the user hits Ctrl-z, we don't modify state at all, just send SIGSTOP to
             // ourself, then loop around and process the next input.
             if matches!(event, KeyEvent(Key::Ctrl('z'))) {
@@ -183,6 +739,7 @@ impl App {
                 let _ = write!(self.screen_writer.stdout, "{ENABLE_MOUSE_BUTTON_TRACKING}");
                 let _ = self.screen_writer.stdout.activate_raw_mode();
                 // I'm not exactly sure why we have to do this.
+                self.screen_writer.invalidate_last_frame();
                 self.draw_screen();
                 continue;
             }
@@ -203,6 +760,9 @@ impl App {
             let focused_row_before = self.viewer.focused_row;
             let previous_collapsed_state_of_focused_row =
                 self.viewer.flatjson[focused_row_before].is_collapsed();
+            let top_row_before = self.viewer.top_row;
+            let following_before = self.following;
+            let resized = matches!(event, WinChEvent);
 
             let action = match event {
                 // Put this first so the current input state doesn't get reset
@@ -210,21 +770,70 @@ impl App {
                 WinChEvent => {
                     let dimensions = TTYDimensions::from_size(termion::terminal_size().unwrap());
                     self.screen_writer.dimensions = dimensions;
-                    Some(Action::ResizeViewerDimensions(
-                        dimensions.without_status_bar(),
-                    ))
+                    self.screen_writer.invalidate_last_frame();
+                    if self.split.is_some() && !self.has_room_to_split() {
+                        self.close_split(true);
+                    }
+                    Some(Action::ResizeViewerDimensions(self.viewer_dimensions()))
+                }
+                // Commands from the control socket cancel any pending
+                // multi-key command, but don't interrupt an incremental search.
+                TuiEvent::Control(command) if self.input_state != InputState::IncrementalSearch => {
+                    self.input_state = InputState::Default;
+                    self.input_buffer.clear();
+
+                    // Paths and searches refer to the input, not a result buffer,
+                    // and jump to rows that would be hidden behind a table.
+                    if matches!(command, ControlCommand::Goto(_) | ControlCommand::Search(_)) {
+                        self.close_result_buffers();
+                        self.table = None;
+                    }
+
+                    match command {
+                        ControlCommand::Goto(path) => self.jump_to_path(&path),
+                        ControlCommand::Search(pattern) => {
+                            let action = self.start_search(SearchDirection::Forward, 1, pattern);
+                            jumped_to_search_match = action.is_some();
+                            action
+                        }
+                        ControlCommand::Reload => {
+                            self.reload_input();
+                            None
+                        }
+                        ControlCommand::ReloadConfig => {
+                            self.reload_config();
+                            None
+                        }
+                    }
                 }
                 // Handle special input states:
+                event if self.input_state == InputState::IncrementalSearch => {
+                    let action = match event {
+                        KeyEvent(key) => self.handle_incremental_search_key(key),
+                        _ => None,
+                    };
+                    jumped_to_search_match = action.is_some();
+                    action
+                }
                 // p commands:
                 event if self.input_state == InputState::PendingPCommand => {
                     let content_target = match event {
                         KeyEvent(Key::Char('p')) => Some(ContentTarget::PrettyPrintedValue),
                         KeyEvent(Key::Char('v')) => Some(ContentTarget::OneLineValue),
+                        KeyEvent(Key::Char('Y')) => Some(ContentTarget::YamlValue),
+                        KeyEvent(Key::Char('J')) => Some(ContentTarget::JsonValue),
+                        KeyEvent(Key::Char('T')) => Some(ContentTarget::TomlValue),
                         KeyEvent(Key::Char('s')) => Some(ContentTarget::String),
                         KeyEvent(Key::Char('k')) => Some(ContentTarget::Key),
                         KeyEvent(Key::Char('P')) => Some(ContentTarget::DotPath),
                         KeyEvent(Key::Char('b')) => Some(ContentTarget::BracketPath),
                         KeyEvent(Key::Char('q')) => Some(ContentTarget::QueryPath),
+                        KeyEvent(Key::Char('j')) => Some(ContentTarget::JqCommand),
+                        KeyEvent(Key::Char('c')) => Some(ContentTarget::CaptureGroup),
+                        KeyEvent(Key::Char('C')) => Some(ContentTarget::AllCaptureGroups),
+                        KeyEvent(Key::Char('a')) => Some(ContentTarget::SelectedValues),
+                        KeyEvent(Key::Char('h')) => Some(ContentTarget::Checksum),
+                        KeyEvent(Key::Char('r')) => Some(ContentTarget::RelativePath),
                         _ => None,
                     };
 
@@ -232,7 +841,11 @@ impl App {
 
                     if let Some(content_target) = content_target {
                         if self.print_content(content_target) {
-                            self.input_state = InputState::WaitingForAnyKeyPress;
+                            self.input_state = if self.pager.is_some() {
+                                InputState::Paging
+                            } else {
+                                InputState::WaitingForAnyKeyPress
+                            };
                             continue;
                         }
                     }
@@ -246,11 +859,21 @@ impl App {
                     let content_target = match event {
                         KeyEvent(Key::Char('y')) => Some(ContentTarget::PrettyPrintedValue),
                         KeyEvent(Key::Char('v')) => Some(ContentTarget::OneLineValue),
+                        KeyEvent(Key::Char('Y')) => Some(ContentTarget::YamlValue),
+                        KeyEvent(Key::Char('J')) => Some(ContentTarget::JsonValue),
+                        KeyEvent(Key::Char('T')) => Some(ContentTarget::TomlValue),
                         KeyEvent(Key::Char('s')) => Some(ContentTarget::String),
+                        KeyEvent(Key::Char('S')) => Some(ContentTarget::RawString),
                         KeyEvent(Key::Char('k')) => Some(ContentTarget::Key),
                         KeyEvent(Key::Char('p')) => Some(ContentTarget::DotPath),
                         KeyEvent(Key::Char('b')) => Some(ContentTarget::BracketPath),
                         KeyEvent(Key::Char('q')) => Some(ContentTarget::QueryPath),
+                        KeyEvent(Key::Char('j')) => Some(ContentTarget::JqCommand),
+                        KeyEvent(Key::Char('c')) => Some(ContentTarget::CaptureGroup),
+                        KeyEvent(Key::Char('C')) => Some(ContentTarget::AllCaptureGroups),
+                        KeyEvent(Key::Char('a')) => Some(ContentTarget::SelectedValues),
+                        KeyEvent(Key::Char('h')) => Some(ContentTarget::Checksum),
+                        KeyEvent(Key::Char('r')) => Some(ContentTarget::RelativePath),
                         _ => None,
                     };
 
@@ -269,16 +892,104 @@ impl App {
                         KeyEvent(Key::Char('t')) => Some(Action::MoveFocusedLineToTop),
                         KeyEvent(Key::Char('z')) => Some(Action::MoveFocusedLineToCenter),
                         KeyEvent(Key::Char('b')) => Some(Action::MoveFocusedLineToBottom),
+                        KeyEvent(Key::Char('c')) => Some(Action::CollapseSelected),
+                        KeyEvent(Key::Char('o')) => Some(Action::ExpandSelected),
                         _ => None,
                     };
 
+                    if matches!(
+                        z_action,
+                        Some(Action::CollapseSelected | Action::ExpandSelected)
+                    ) && self.viewer.selected_rows.is_empty()
+                    {
+                        self.set_warning_message(
+                            "No rows are selected; use 'x' to select rows".to_string(),
+                        );
+                    }
+
                     self.input_state = InputState::Default;
                     self.input_buffer.clear();
 
                     z_action
                 }
+                // ]c and [c jump to the next and previous changed value.
+                event if self.input_state == InputState::PendingBracketCommand => {
+                    let forwards = self.input_buffer == b"]";
+                    self.input_state = InputState::Default;
+                    self.input_buffer.clear();
+
+                    match event {
+                        KeyEvent(Key::Char('c')) => self.jump_to_change(forwards),
+                        KeyEvent(Key::Char('t')) => self.step_along_timeline(forwards),
+                        _ => None,
+                    }
+                }
+                // ' followed by a character jumps to the next sibling whose
+                // key starts with that character, except for '', which goes
+                // back to where the last search jump started.
+                event if self.input_state == InputState::PendingTypeahead => {
+                    self.input_state = InputState::Default;
+                    self.input_buffer.clear();
+
+                    match event {
+                        KeyEvent(Key::Char('\'')) => self.jump_back_to_jump_origin(),
+                        KeyEvent(Key::Char(ch)) => {
+                            match self.viewer.next_sibling_with_key_starting_with(ch) {
+                                Some(line) => Some(Action::JumpTo {
+                                    line,
+                                    make_visible: false,
+                                }),
+                                None => {
+                                    self.set_warning_message(format!(
+                                        "No key starting with '{ch}'"
+                                    ));
+                                    None
+                                }
+                            }
+                        }
+                        _ => None,
+                    }
+                }
+                // While a table is shown, it handles all key presses other
+                // than ^c, and mouse events are ignored.
+                KeyEvent(key) if self.table.is_some() && key != Key::Ctrl('c') => {
+                    self.input_buffer.clear();
+                    self.handle_table_key(key)
+                }
+                MouseEvent(_) if self.table.is_some() => None,
+                KeyEvent(Key::Char('q')) if !self.result_buffers.is_empty() => {
+                    self.input_buffer.clear();
+                    self.close_result_buffer();
+                    None
+                }
                 // These inputs quit.
                 KeyEvent(Key::Ctrl('c') | Key::Char('q')) => break,
+                // When the outline is focused, it handles all other key presses.
+                KeyEvent(key) if matches!(&self.outline, Some(outline) if outline.focused) => {
+                    self.input_buffer.clear();
+                    self.handle_outline_key(key)
+                }
+                KeyEvent(Key::Char('\n')) if self.pick.is_some() => {
+                    match self.picked_content() {
+                        Ok(content) => {
+                            self.picked = Some(content);
+                            break;
+                        }
+                        Err(err) => self.set_warning_message(err),
+                    }
+                    None
+                }
+                // In a result buffer, Enter jumps to the row of the document
+                // that the focused entry refers to.
+                KeyEvent(Key::Char('\n')) if self.focused_result_target().is_some() => {
+                    let line = self.focused_result_target().unwrap();
+                    self.input_buffer.clear();
+                    self.close_result_buffer();
+                    Some(Action::JumpTo {
+                        line,
+                        make_visible: true,
+                    })
+                }
                 // Show the help page
                 KeyEvent(Key::F(1)) => {
                     self.show_help();
@@ -286,7 +997,11 @@ impl App {
                 }
                 KeyEvent(Key::Esc) => {
                     self.input_buffer.clear();
+                    self.viewer.sub_focus = None;
+                    self.viewer.selected_rows.clear();
                     self.search_state.set_no_longer_actively_searching();
+                    self.diff_markers.clear();
+                    self.screen_writer.diff_markers.clear();
                     None
                 }
                 // These inputs may be buffered.
@@ -306,11 +1021,23 @@ impl App {
                 }
                 KeyEvent(Key::Char('y')) => {
                     match &self.clipboard_context {
+                        // When only the key or value of the focused row is
+                        // highlighted, copy it right away.
+                        Ok(_) if self.viewer.sub_focus.is_some() => {
+                            self.input_buffer.clear();
+                            self.copy_content(match self.viewer.sub_focus.unwrap() {
+                                SubFocus::Key => ContentTarget::Key,
+                                SubFocus::Value => ContentTarget::PrettyPrintedValue,
+                            });
+                        }
                         Ok(_) => {
                             self.input_state = InputState::PendingYCommand;
                             self.input_buffer.clear();
                             self.buffer_input(b'y');
                         }
+                        Err(err) if !self.capabilities.clipboard => {
+                            self.set_warning_message(err.to_string());
+                        }
                         Err(err) => {
                             let msg = format!("Unable to access clipboard: {err}");
                             self.set_error_message(msg);
@@ -319,12 +1046,24 @@ impl App {
 
                     None
                 }
+                KeyEvent(Key::Char('\'')) => {
+                    self.input_state = InputState::PendingTypeahead;
+                    self.input_buffer.clear();
+                    self.buffer_input(b'\'');
+                    None
+                }
                 KeyEvent(Key::Char('z')) => {
                     self.input_state = InputState::PendingZCommand;
                     self.input_buffer.clear();
                     self.buffer_input(b'z');
                     None
                 }
+                KeyEvent(Key::Char(ch @ ('[' | ']'))) => {
+                    self.input_state = InputState::PendingBracketCommand;
+                    self.input_buffer.clear();
+                    self.buffer_input(ch as u8);
+                    None
+                }
                 // These inputs always clear the input_buffer (but may use its current contents).
                 KeyEvent(key) => {
                     let action = match key {
@@ -407,17 +1146,31 @@ impl App {
                         }
                         Key::Char('/') => {
                             let count = self.parse_input_buffer_as_number();
-                            let action = self
-                                .get_search_input_and_start_search(SearchDirection::Forward, count);
-                            jumped_to_search_match = action.is_some();
-                            action
+                            if self.incsearch && self.script.prompt_input.is_empty() {
+                                self.start_incremental_search(SearchDirection::Forward, count);
+                                None
+                            } else {
+                                let action = self.get_search_input_and_start_search(
+                                    SearchDirection::Forward,
+                                    count,
+                                );
+                                jumped_to_search_match = action.is_some();
+                                action
+                            }
                         }
                         Key::Char('?') => {
                             let count = self.parse_input_buffer_as_number();
-                            let action = self
-                                .get_search_input_and_start_search(SearchDirection::Reverse, count);
-                            jumped_to_search_match = action.is_some();
-                            action
+                            if self.incsearch && self.script.prompt_input.is_empty() {
+                                self.start_incremental_search(SearchDirection::Reverse, count);
+                                None
+                            } else {
+                                let action = self.get_search_input_and_start_search(
+                                    SearchDirection::Reverse,
+                                    count,
+                                );
+                                jumped_to_search_match = action.is_some();
+                                action
+                            }
                         }
                         Key::Char('*') => {
                             let count = self.parse_input_buffer_as_number();
@@ -441,6 +1194,7 @@ impl App {
                         Key::Char('H') => Some(Action::FocusParent),
                         Key::Char('c') => Some(Action::CollapseNodeAndSiblings),
                         Key::Char('C') => Some(Action::DeepCollapseNodeAndSiblings),
+                        Key::Char('x') => Some(Action::ToggleSelected),
                         Key::Char('e') => Some(Action::ExpandNodeAndSiblings),
                         Key::Char('E') => Some(Action::DeepExpandNodeAndSiblings),
                         Key::Char(' ') => Some(Action::ToggleCollapsed),
@@ -450,6 +1204,41 @@ impl App {
                         Key::End => Some(Action::FocusBottom),
                         Key::Char('%') => Some(Action::FocusMatchingPair),
                         Key::Char('m') => Some(Action::ToggleMode),
+                        Key::Char('M') => {
+                            self.toggle_mark();
+                            None
+                        }
+                        // Like less +F.
+                        Key::Char('F') => self.follow_input(),
+                        // Like switching to the alternate file in vim.
+                        Key::Ctrl('6') => {
+                            self.toggle_filtered_document();
+                            None
+                        }
+                        Key::Char('i') => {
+                            self.edit_focused_value(None);
+                            None
+                        }
+                        Key::Char('d') => self.delete_focused_value(),
+                        Key::Char('u') => self.undo_change(false),
+                        Key::Ctrl('r') => self.undo_change(true),
+                        Key::Char('o') => {
+                            self.toggle_outline();
+                            None
+                        }
+                        Key::Char('T') => {
+                            self.toggle_table();
+                            None
+                        }
+                        Key::Char('O') => {
+                            self.focus_outline();
+                            None
+                        }
+                        Key::Char('\t') => Some(Action::ToggleSubFocus),
+                        Key::Ctrl('w') => {
+                            self.toggle_split_focus();
+                            None
+                        }
                         Key::Char('<') => {
                             self.screen_writer
                                 .decrease_indentation_level(self.viewer.flatjson.2 as u16);
@@ -465,10 +1254,76 @@ impl App {
                             None
                         }
                         Key::Char(':') => {
-                            if let Some(command) = self.readline(":", "command") {
+                            let mut action = None;
+                            let command = self
+                                .readline(":", "command", Completion::CommandPaths)
+                                .and_then(|command| self.resolve_aliases(command));
+                            if let Some(command) = command {
                                 match Self::parse_command(&command) {
+                                    Command::Quit if !self.result_buffers.is_empty() => {
+                                        self.close_result_buffer()
+                                    }
                                     Command::Quit => break,
                                     Command::Help => self.show_help(),
+                                    Command::Reload => self.reload_input(),
+                                    Command::ShowErrors => self.show_parse_errors(),
+                                    Command::Sample => action = self.sample_array_element(),
+                                    Command::Reverse => action = self.reverse_focused_array(),
+                                    Command::Largest => action = self.jump_to_largest_child(),
+                                    Command::Longest => action = self.jump_to_longest_string(),
+                                    Command::FindType(type_name) => {
+                                        action = self.jump_to_next_of_type(&type_name)
+                                    }
+                                    Command::Count(pattern) => self.count_matches(pattern),
+                                    Command::DecodeJwt => self.decode_focused_jwt(),
+                                    Command::Chunk(strategy) => {
+                                        self.chunk_focused_string(&strategy)
+                                    }
+                                    Command::Unchunk => self.unchunk_string(),
+                                    Command::Edit(token) => self.edit_focused_value(token),
+                                    Command::Delete => action = self.delete_focused_value(),
+                                    Command::Undo => action = self.undo_change(false),
+                                    Command::Redo => action = self.undo_change(true),
+                                    Command::Timeline(field) => {
+                                        action = self.toggle_timeline(field.as_deref())
+                                    }
+                                    Command::Table => self.toggle_table(),
+                                    Command::HideKeys(pattern) => self.hide_keys(&pattern),
+                                    Command::UnhideKeys => self.unhide_keys(),
+                                    Command::Filter {
+                                        expression,
+                                        focused_value,
+                                    } => self.filter_document(&expression, focused_value),
+                                    Command::Unfilter => self.unfilter_document(),
+                                    Command::Follow => action = self.follow_input(),
+                                    Command::Pin => action = self.toggle_pinned_row(),
+                                    Command::Alias(definition) => {
+                                        match self.aliases.define(&definition) {
+                                            Ok(message) => self.set_info_message(message),
+                                            Err(err) => self.set_warning_message(err),
+                                        }
+                                    }
+                                    Command::Unalias(name) => {
+                                        if !self.aliases.remove(&name) {
+                                            self.set_warning_message(format!("No alias {name}"));
+                                        }
+                                    }
+                                    Command::ShowAliases => {
+                                        self.set_info_message(self.aliases.description())
+                                    }
+                                    Command::ShowHistory => self.show_value_history(),
+                                    Command::Checksum => self.show_checksum(),
+                                    Command::ShowMatches => self.show_search_matches(),
+                                    Command::SavePosition(path) => self.save_position(&path),
+                                    Command::Write { path, force } => {
+                                        self.write_document(path, force)
+                                    }
+                                    Command::Open { path, force } => self.open_file(path, force),
+                                    Command::Split => action = self.split_screen(),
+                                    Command::CloseSplit { keep_focused } => {
+                                        action = self.close_split(keep_focused)
+                                    }
+                                    Command::Unpin => action = self.unpin_all_rows(),
                                     Command::SetShowLineNumber(Some(new_val)) => {
                                         self.screen_writer.show_line_numbers = new_val
                                     }
@@ -483,6 +1338,122 @@ impl App {
                                         self.screen_writer.show_relative_line_numbers =
                                             !self.screen_writer.show_relative_line_numbers
                                     }
+                                    Command::SetNumberMode(mode) => {
+                                        self.screen_writer.number_mode = mode
+                                    }
+                                    Command::SetShowPreviews(Some(new_val)) => {
+                                        self.screen_writer.show_previews = new_val
+                                    }
+                                    Command::SetShowPreviews(None) => {
+                                        self.screen_writer.show_previews =
+                                            !self.screen_writer.show_previews
+                                    }
+                                    Command::SetPreviewOrder(order) => {
+                                        self.screen_writer.preview_order = order
+                                    }
+                                    Command::SetAccordionMode(Some(new_val)) => {
+                                        self.viewer.accordion_mode = new_val
+                                    }
+                                    Command::SetAccordionMode(None) => {
+                                        self.viewer.accordion_mode = !self.viewer.accordion_mode
+                                    }
+                                    Command::SetDense(Some(new_val)) => self.viewer.dense = new_val,
+                                    Command::SetDense(None) => {
+                                        self.viewer.dense = !self.viewer.dense
+                                    }
+                                    Command::SetFoldRuns(fold_runs) => {
+                                        let fold_runs = fold_runs.unwrap_or(!self.viewer.fold_runs);
+                                        self.viewer.set_fold_runs(fold_runs);
+                                    }
+                                    Command::SetShowInvisibles(Some(new_val)) => {
+                                        self.screen_writer.show_invisibles = new_val
+                                    }
+                                    Command::SetShowInvisibles(None) => {
+                                        self.screen_writer.show_invisibles =
+                                            !self.screen_writer.show_invisibles
+                                    }
+                                    Command::SetShowContainerGauge(Some(new_val)) => {
+                                        self.screen_writer.show_container_gauge = new_val
+                                    }
+                                    Command::SetShowContainerGauge(None) => {
+                                        self.screen_writer.show_container_gauge =
+                                            !self.screen_writer.show_container_gauge
+                                    }
+                                    Command::SetShowSizes(Some(new_val)) => {
+                                        self.screen_writer.show_sizes = new_val
+                                    }
+                                    Command::SetShowSizes(None) => {
+                                        self.screen_writer.show_sizes =
+                                            !self.screen_writer.show_sizes
+                                    }
+                                    Command::SetShowNumberTypes(Some(new_val)) => {
+                                        self.screen_writer.show_number_types = new_val
+                                    }
+                                    Command::SetShowNumberTypes(None) => {
+                                        self.screen_writer.show_number_types =
+                                            !self.screen_writer.show_number_types
+                                    }
+                                    Command::SetNumberBase(base) => {
+                                        self.screen_writer.number_base = base
+                                    }
+                                    Command::SetShowDates(Some(new_val)) => {
+                                        self.screen_writer.show_dates = new_val
+                                    }
+                                    Command::SetShowDates(None) => {
+                                        self.screen_writer.show_dates =
+                                            !self.screen_writer.show_dates
+                                    }
+                                    Command::SetHighlightFocusedLine(Some(new_val)) => {
+                                        self.screen_writer.highlight_focused_line = new_val
+                                    }
+                                    Command::SetHighlightFocusedLine(None) => {
+                                        self.screen_writer.highlight_focused_line =
+                                            !self.screen_writer.highlight_focused_line
+                                    }
+                                    Command::SetAlignKeys(new_val) => {
+                                        self.screen_writer.align_keys =
+                                            new_val.unwrap_or(!self.screen_writer.align_keys)
+                                    }
+                                    Command::SetShowIndentGuides(new_val) => {
+                                        self.screen_writer.show_indent_guides = new_val
+                                            .unwrap_or(!self.screen_writer.show_indent_guides)
+                                    }
+                                    Command::SetIncrementalSearch(new_val) => {
+                                        self.incsearch = new_val.unwrap_or(!self.incsearch)
+                                    }
+                                    Command::SetWholeWord(new_val) => {
+                                        let defaults = &mut self.search_defaults;
+                                        defaults.whole_word =
+                                            new_val.unwrap_or(!defaults.whole_word)
+                                    }
+                                    Command::SetFixedStrings(new_val) => {
+                                        let defaults = &mut self.search_defaults;
+                                        defaults.fixed_string =
+                                            new_val.unwrap_or(!defaults.fixed_string)
+                                    }
+                                    Command::SetSmoothScroll(new_val) => {
+                                        self.smooth_scroll.enabled =
+                                            new_val.unwrap_or(!self.smooth_scroll.enabled)
+                                    }
+                                    Command::SetRememberLastChild(Some(new_val)) => {
+                                        self.viewer.remember_last_child = new_val
+                                    }
+                                    Command::SetRememberLastChild(None) => {
+                                        self.viewer.remember_last_child =
+                                            !self.viewer.remember_last_child
+                                    }
+                                    Command::SetShowKeyOrderChanges(new_val) => {
+                                        self.show_key_order_changes =
+                                            new_val.unwrap_or(!self.show_key_order_changes);
+                                        self.screen_writer.diff_markers =
+                                            self.displayed_diff_markers();
+                                    }
+                                    Command::SetHiddenMatches(hidden_matches) => {
+                                        self.hidden_matches = hidden_matches
+                                    }
+                                    Command::SetSharedSplitCollapse(shared) => {
+                                        self.set_shared_split_collapse(shared)
+                                    }
                                     Command::Unknown => {
                                         self.set_warning_message(format!(
                                             "Unknown command: {command}"
@@ -491,7 +1462,7 @@ impl App {
                                 }
                             }
 
-                            None
+                            action
                         }
                         _ => {
                             eprint!("{BELL}\r");
@@ -507,16 +1478,44 @@ impl App {
                     self.input_buffer.clear();
 
                     match me {
-                        Press(Left, _, h) => {
-                            // Ignore clicks on status bar or below.
-                            if h > self.screen_writer.dimensions.without_status_bar().height {
-                                continue;
-                            } else {
-                                Some(Action::Click(h))
+                        Press(Left, x, h) => {
+                            let viewer_height = self.viewer_area_dimensions().height;
+                            match &self.timeline {
+                                // Jump to the record closest to the clicked time.
+                                Some(timeline) if h == viewer_height + 1 => {
+                                    let width = self.screen_writer.dimensions.width as usize;
+                                    Some(Action::JumpTo {
+                                        line: timeline.record_nearest_column(x as usize - 1, width),
+                                        make_visible: false,
+                                    })
+                                }
+                                // Jump to a pinned row when it's clicked.
+                                _ if h > viewer_height => {
+                                    let first_pinned_line = viewer_height
+                                        + 1
+                                        + if self.timeline.is_some() { 1 } else { 0 };
+                                    match h
+                                        .checked_sub(first_pinned_line)
+                                        .and_then(|i| self.pinned_rows.get(i as usize))
+                                    {
+                                        Some(pinned_row) => Some(Action::JumpTo {
+                                            line: *pinned_row,
+                                            make_visible: true,
+                                        }),
+                                        // Ignore clicks on status bar or below.
+                                        None => continue,
+                                    }
+                                }
+                                _ => self.click_in_view(h),
                             }
                         }
-                        Press(WheelUp, _, _) => Some(Action::ScrollUp(3)),
-                        Press(WheelDown, _, _) => Some(Action::ScrollDown(3)),
+                        Press(WheelUp, _, _) => {
+                            Some(self.smooth_scroll.wheel(WheelDirection::Up, Instant::now()))
+                        }
+                        Press(WheelDown, _, _) => Some(
+                            self.smooth_scroll
+                                .wheel(WheelDirection::Down, Instant::now()),
+                        ),
                         // Ignore all other mouse events and don't redraw the screen.
                         _ => {
                             continue;
@@ -527,17 +1526,54 @@ impl App {
                     self.set_error_message(format!("Unknown byte sequence: {bytes:?}"));
                     None
                 }
+                // Already handled above, except during an incremental search,
+                // which ignores them.
+                TuiEvent::Control(_) => None,
+                // Already handled above.
+                TuiEvent::Records(_) => None,
             };
 
             if let Some(action) = action {
-                self.viewer.perform_action(action);
+                let height = self.viewer.dimensions.height as usize;
+                match self.smooth_scroll.page_steps(action, height) {
+                    Some((first_step, second_step)) => {
+                        self.viewer.perform_action(first_step);
+                        self.draw_screen();
+                        if self.screen_writer.stdout.is_interactive() {
+                            std::thread::sleep(self.smooth_scroll.frame_delay);
+                        }
+                        self.viewer.perform_action(second_step);
+                    }
+                    None => self.viewer.perform_action(action),
+                }
+
+                if let Some(tutor) = &mut self.tutor {
+                    if let Some(message) = tutor.record_action(&action) {
+                        self.set_info_message(message);
+                    }
+                }
+            }
+
+            // Moving or scrolling stops following the input.
+            if following_before
+                && !resized
+                && (focused_row_before != self.viewer.focused_row
+                    || top_row_before != self.viewer.top_row)
+            {
+                self.following = false;
             }
 
             if jumped_to_search_match {
                 self.screen_writer.scroll_line_to_search_match(
                     &self.viewer,
-                    self.search_state.current_match_range(),
+                    self.search_state.current_match_range_to_show(),
                 );
+                // Previewed matches while typing a search don't count.
+                if self.input_state != InputState::IncrementalSearch
+                    && focused_row_before != self.viewer.focused_row
+                {
+                    self.record_jump_origin(focused_row_before);
+                }
             } else {
                 // Check whether we're still actively searching. If the cursor moves,
                 // we're no longer actively searching. If the focused row was expanded
@@ -553,36 +1589,109 @@ impl App {
                 }
             }
 
+            // E.g., keys were just shown again.
+            self.append_pending_records();
+
             self.draw_screen();
             self.message = None;
         }
     }
 
+    // What was picked when running with --pick, if anything.
+    pub fn picked(&self) -> Option<&str> {
+        self.picked.as_deref()
+    }
+
+    // The path of the focused value, e.g., to print with --on-exit-print-path.
+    pub fn focused_path(&self) -> String {
+        self.viewer
+            .flatjson
+            .build_path_to_node(flatjson::PathType::Jq, self.viewer.focused_row)
+            .unwrap_or_else(|_| ".".to_string())
+    }
+
+    fn picked_content(&self) -> Result<String, String> {
+        match self.pick {
+            Some(PickTarget::Path) => self
+                .viewer
+                .flatjson
+                .build_path_to_node(flatjson::PathType::Jq, self.viewer.focused_row),
+            // Print strings without quotes, like `jq -r`, so they can be
+            // used directly by the shell.
+            Some(PickTarget::Value)
+                if self.viewer.flatjson[self.viewer.focused_row].is_string() =>
+            {
+                self.get_content_target_data(ContentTarget::String)
+            }
+            _ => self.get_content_target_data(ContentTarget::PrettyPrintedValue),
+        }
+    }
+
     fn draw_screen(&mut self) {
+        if self.split.is_some() {
+            self.draw_unfocused_view();
+        }
+
+        let filename = self.status_bar_filename();
         self.screen_writer.print(
             &self.viewer,
             &self.input_buffer,
-            &self.input_filename,
+            &filename,
             &self.search_state,
             &self.message,
+            self.outline.as_mut(),
+            self.timeline.as_ref(),
+            self.table.as_mut(),
+            &self.pinned_rows,
         );
     }
 
     fn draw_status_bar(&mut self) {
+        let filename = self.status_bar_filename();
         self.screen_writer.print_status_bar(
             &self.viewer,
             &self.input_buffer,
-            &self.input_filename,
+            &filename,
             &self.search_state,
             &self.message,
         );
     }
 
+    // The filename shown in the status bar, along with a count of how
+    // many lines of the input were skipped because they failed to parse.
+    fn status_bar_filename(&self) -> String {
+        let mut filename = match self.parse_errors.len() {
+            0 => self.input_filename.clone(),
+            1 => format!("{} [1 error]", self.input_filename),
+            n => format!("{} [{n} errors]", self.input_filename),
+        };
+        if self.modified {
+            filename.push_str(" [modified]");
+        }
+        if let Some(hidden_keys) = &self.hidden_keys {
+            filename.push_str(&format!(" [hiding key={}]", hidden_keys.pattern));
+        }
+        if self.chunked_string.is_some() {
+            filename.push_str(" [chunked]");
+        }
+        match &self.filter {
+            Some(filter) if filter.showing_original => filename.push_str(" [unfiltered]"),
+            Some(filter) => filename.push_str(&format!(" [filter: {}]", filter.expression)),
+            None => {}
+        }
+        if self.following {
+            filename.push_str(" [following]");
+        } else if self.loading_records {
+            filename.push_str(" [loading]");
+        }
+        filename
+    }
+
     fn set_info_message(&mut self, s: String) {
         self.message = Some((s, MessageSeverity::Info));
     }
 
-    fn set_warning_message(&mut self, s: String) {
+    pub fn set_warning_message(&mut self, s: String) {
         self.message = Some((s, MessageSeverity::Warn));
     }
 
@@ -593,8 +1702,28 @@ impl App {
     // Get user input via a readline prompt. May fail to return input if
     // the user deliberately cancels the prompt via Ctrl-C or Ctrl-D, or
     // if an actual error occurs, in which case an error message is set.
-    fn readline(&mut self, prompt: &str, purpose: &str) -> Option<String> {
-        match self.screen_writer.get_command(prompt) {
+    // What can be tab-completed, if anything, is given by completion.
+    fn readline(&mut self, prompt: &str, purpose: &str, completion: Completion) -> Option<String> {
+        self.readline_with_initial(prompt, "", purpose, completion)
+    }
+
+    // Like readline, but the prompt starts out with the given text entered.
+    fn readline_with_initial(
+        &mut self,
+        prompt: &str,
+        initial: &str,
+        purpose: &str,
+        completion: Completion,
+    ) -> Option<String> {
+        if let Some(input) = self.script.prompt_input.pop_front() {
+            return Some(input);
+        }
+
+        if !self.screen_writer.stdout.is_interactive() {
+            return None;
+        }
+
+        match self.screen_writer.get_command(prompt, initial, completion) {
             Ok(s) => Some(s),
             // User hit Ctrl-C or Ctrl-D to cancel prompt
             Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => None,
@@ -638,8 +1767,16 @@ impl App {
             SearchDirection::Reverse => "?",
         };
 
-        let search_term = self.readline(prompt_str, "search input")?;
+        let search_term = self.readline(prompt_str, "search input", Completion::Keys)?;
+        self.start_search(direction, jumps, search_term)
+    }
 
+    fn start_search(
+        &mut self,
+        direction: SearchDirection,
+        jumps: usize,
+        search_term: String,
+    ) -> Option<Action> {
         // In vim, /<CR> or ?<CR> is a longcut for repeating the previous search.
         if search_term.is_empty() {
             // This will actually set the direction of a search going forward.
@@ -659,22 +1796,159 @@ impl App {
         }
     }
 
-    fn initialize_search(&mut self, direction: SearchDirection, search_term: String) -> bool {
-        match SearchState::initialize_search(search_term, &self.viewer.flatjson.1, direction) {
-            Ok(ss) => {
-                self.search_state = ss;
-                true
+    fn start_incremental_search(&mut self, direction: SearchDirection, jumps: usize) {
+        let previous_search_state = std::mem::replace(&mut self.search_state, SearchState::empty());
+        self.incremental_search = Some(IncrementalSearch {
+            direction,
+            jumps,
+            search_input: String::new(),
+            start_row: self.viewer.focused_row,
+            start_top_row: self.viewer.top_row,
+            previous_search_state,
+            expanded_rows: vec![],
+        });
+        self.input_state = InputState::IncrementalSearch;
+        self.screen_writer.search_prompt = Some(direction.prompt_char().to_string());
+        let _ = write!(self.screen_writer.stdout, "{}", termion::cursor::Show);
+    }
+
+    fn handle_incremental_search_key(&mut self, key: Key) -> Option<Action> {
+        let search = self.incremental_search.as_mut()?;
+        match key {
+            Key::Char('\n') => return self.finish_incremental_search(true),
+            Key::Esc | Key::Ctrl('c') => return self.finish_incremental_search(false),
+            Key::Backspace if search.search_input.is_empty() => {
+                return self.finish_incremental_search(false)
             }
-            Err(err_message) => {
-                self.set_error_message(err_message);
-                false
+            Key::Backspace => {
+                search.search_input.pop();
             }
+            Key::Ctrl('u') => search.search_input.clear(),
+            Key::Char(ch) => search.search_input.push(ch),
+            _ => return None,
         }
+
+        self.screen_writer.search_prompt = Some(format!(
+            "{}{}",
+            search.direction.prompt_char(),
+            search.search_input
+        ));
+        self.preview_incremental_search()
     }
 
-    fn start_object_key_search(
-        &mut self,
-        direction: SearchDirection,
+    // Go back to where the search started, then jump to the first match of
+    // what's been typed so far. Incomplete regexes, e.g., "a(", just don't
+    // match anything until they're finished.
+    fn preview_incremental_search(&mut self) -> Option<Action> {
+        self.undo_incremental_search_preview();
+        let search = self.incremental_search.as_mut()?;
+
+        self.search_state = SearchState::initialize_search_using_index(
+            search.search_input.clone(),
+            &self.viewer.flatjson.1,
+            self.search_index.as_ref(),
+            search.direction,
+            self.search_defaults,
+        )
+        .unwrap_or_else(|_| SearchState::empty());
+        if !self.search_state.any_matches() {
+            return None;
+        }
+
+        self.search_state.hidden_matches = self.hidden_matches;
+        let destination = self.search_state.jump_to_match(
+            search.start_row,
+            &self.viewer.flatjson,
+            JumpDirection::Next,
+            search.jumps,
+        );
+        if self.hidden_matches == HiddenMatches::Expand {
+            let mut curr = destination;
+            while let OptionIndex::Index(parent) = self.viewer.flatjson[curr].parent {
+                if self.viewer.flatjson[parent].is_collapsed() {
+                    self.viewer.flatjson.expand(parent);
+                    search.expanded_rows.push(parent);
+                }
+                curr = parent;
+            }
+        }
+
+        Some(Action::JumpTo {
+            line: destination,
+            make_visible: false,
+        })
+    }
+
+    fn undo_incremental_search_preview(&mut self) {
+        let Some(search) = self.incremental_search.as_mut() else {
+            return;
+        };
+        for row in search.expanded_rows.drain(..) {
+            self.viewer.flatjson.collapse(row);
+        }
+        self.viewer.focused_row = search.start_row;
+        self.viewer.top_row = search.start_top_row;
+    }
+
+    // When the search is committed, it gets run from where it started,
+    // exactly as if it had been entered at a regular prompt, so that
+    // offsets and repeating the previous search with an empty input work
+    // the same way.
+    fn finish_incremental_search(&mut self, commit: bool) -> Option<Action> {
+        self.undo_incremental_search_preview();
+        let search = self.incremental_search.take()?;
+        self.search_state = search.previous_search_state;
+        self.input_state = InputState::Default;
+        self.screen_writer.search_prompt = None;
+        let _ = write!(self.screen_writer.stdout, "{}", termion::cursor::Hide);
+
+        if commit {
+            self.start_search(search.direction, search.jumps, search.search_input)
+        } else {
+            None
+        }
+    }
+
+    fn rebuild_search_index(&mut self) {
+        if self.search_index.is_some() {
+            self.search_index = Some(SearchIndex::new(&self.viewer.flatjson.1));
+        }
+    }
+
+    fn initialize_search(&mut self, direction: SearchDirection, search_term: String) -> bool {
+        match SearchState::initialize_search_using_index(
+            search_term,
+            &self.viewer.flatjson.1,
+            self.search_index.as_ref(),
+            direction,
+            self.search_defaults,
+        ) {
+            Ok(ss) => {
+                self.search_state = ss;
+                // Keep the list of search matches up to date.
+                if let Some(outline) = &self.outline {
+                    if outline.kind == OutlineKind::SearchMatches {
+                        let focused = outline.focused;
+                        self.outline = self.search_state.any_matches().then(|| {
+                            let mut outline =
+                                Outline::search_matches(&self.viewer.flatjson, &self.search_state);
+                            outline.focused = focused;
+                            outline
+                        });
+                    }
+                }
+                true
+            }
+            Err(err_message) => {
+                self.set_error_message(err_message);
+                false
+            }
+        }
+    }
+
+    fn start_object_key_search(
+        &mut self,
+        direction: SearchDirection,
         jumps: usize,
     ) -> Option<Action> {
         if self.initialize_object_key_search(direction) {
@@ -689,56 +1963,2276 @@ impl App {
         }
     }
 
-    fn initialize_object_key_search(&mut self, direction: SearchDirection) -> bool {
-        if let Some(key_range) = &self.viewer.flatjson[self.viewer.focused_row].key_range {
-            // Note key_range already includes quotes around key.
-            let object_key = format!("{}: ", &self.viewer.flatjson.1[key_range.clone()]);
-            self.initialize_search(direction, object_key)
+    fn initialize_object_key_search(&mut self, direction: SearchDirection) -> bool {
+        if let Some(key_range) = &self.viewer.flatjson[self.viewer.focused_row].key_range {
+            // Note key_range already includes quotes around key.
+            let object_key = format!("{}: ", &self.viewer.flatjson.1[key_range.clone()]);
+            self.initialize_search(direction, object_key)
+        } else {
+            false
+        }
+    }
+
+    fn jump_to_search_match(
+        &mut self,
+        jump_direction: JumpDirection,
+        jumps: usize,
+    ) -> Option<Action> {
+        if !self.search_state.ever_searched {
+            self.set_info_message("Type / to search".to_string());
+            return None;
+        } else if !self.search_state.any_matches() {
+            self.set_warning_message(self.search_state.no_matches_message());
+            return None;
+        }
+
+        self.search_state.hidden_matches = self.hidden_matches;
+        let destination = self.search_state.jump_to_match(
+            self.viewer.focused_row,
+            &self.viewer.flatjson,
+            jump_direction,
+            jumps,
+        );
+        // Unless we're expanding containers to show hidden matches, go to the
+        // collapsed container that the match is in.
+        let destination = if self.hidden_matches == HiddenMatches::Expand {
+            destination
+        } else {
+            self.viewer.flatjson.first_visible_ancestor(destination)
+        };
+        let jump = Action::Reveal(destination);
+
+        match self.search_state.offset {
+            // Jump to the match first, so that we count lines from it after
+            // any containers have been expanded to reveal it.
+            SearchOffset::Lines(lines) if lines > 0 => {
+                self.viewer.perform_action(jump);
+                Some(Action::MoveDown(lines as usize))
+            }
+            SearchOffset::Lines(lines) if lines < 0 => {
+                self.viewer.perform_action(jump);
+                Some(Action::MoveUp(lines.unsigned_abs()))
+            }
+            _ => Some(jump),
+        }
+    }
+
+    // Remember where a jump to a search match started, so that '' can go
+    // back to it. If it's no longer on the screen, briefly show where it
+    // was, so it's clear how far the jump went.
+    fn record_jump_origin(&mut self, origin: flatjson::Index) {
+        self.jump_origin = Some(origin);
+
+        if self.message.is_none() && !self.viewer.is_row_on_screen(origin) {
+            if let Ok(path) = self
+                .viewer
+                .flatjson
+                .build_path_to_node(PathType::StatusBar, origin)
+            {
+                self.set_info_message(format!("Jumped from input{path}; '' to go back"));
+            }
+        }
+    }
+
+    // Like vim's '', going back also remembers where we came from, so that
+    // repeating it bounces between the two positions.
+    fn jump_back_to_jump_origin(&mut self) -> Option<Action> {
+        let Some(origin) = self.jump_origin else {
+            self.set_info_message("No search jump to go back from".to_string());
+            return None;
+        };
+
+        self.jump_origin = Some(self.viewer.focused_row);
+        Some(Action::JumpTo {
+            line: origin,
+            make_visible: false,
+        })
+    }
+
+    fn jump_to_change(&mut self, forwards: bool) -> Option<Action> {
+        if self.diff_markers.is_empty() {
+            self.set_info_message(
+                "No changes are marked; use :reload or --diff to mark them".to_string(),
+            );
+            return None;
+        }
+
+        let markers = self.displayed_diff_markers();
+        let next_change = diff::next_change(
+            &self.viewer.flatjson,
+            &markers,
+            self.viewer.focused_row,
+            forwards,
+        );
+        match next_change {
+            Some(line) => Some(Action::Reveal(line)),
+            None => {
+                let direction = if forwards { "below" } else { "above" };
+                self.set_warning_message(format!("No more changes {direction}"));
+                None
+            }
+        }
+    }
+
+    // Jump to the closest record in the next (or previous) column of the
+    // timeline that has any.
+    fn step_along_timeline(&mut self, forwards: bool) -> Option<Action> {
+        let Some(timeline) = &self.timeline else {
+            self.set_info_message("No timeline; use :timeline".to_string());
+            return None;
+        };
+
+        let width = self.screen_writer.dimensions.width as usize;
+        let record = timeline.step(
+            &self.viewer.flatjson,
+            self.viewer.focused_row,
+            forwards,
+            width,
+        );
+        record.map(|line| Action::JumpTo {
+            line,
+            make_visible: false,
+        })
+    }
+
+    fn jump_to_path(&mut self, path: &str) -> Option<Action> {
+        match position::find_node(&self.viewer.flatjson, path) {
+            Some(line) => Some(Action::Reveal(line)),
+            None => {
+                self.set_warning_message(format!("No value at path {path}"));
+                None
+            }
+        }
+    }
+
+    fn parse_command(command: &str) -> Command {
+        match command {
+            "h" | "he" | "hel" | "help" => Command::Help,
+            "q" | "qu" | "qui" | "quit" | "quit()" | "exit" | "exit()" => Command::Quit,
+            "e" | "reload" => Command::Reload,
+            "errors" => Command::ShowErrors,
+            "sample" => Command::Sample,
+            "reverse" => Command::Reverse,
+            "largest" => Command::Largest,
+            "longest" => Command::Longest,
+            _ if command.starts_with("findtype ") => {
+                Command::FindType(command["findtype ".len()..].trim().to_string())
+            }
+            "count" => Command::Count(String::new()),
+            _ if command.starts_with("count ") => {
+                Command::Count(command["count ".len()..].to_string())
+            }
+            "jwt" => Command::DecodeJwt,
+            "chunk" => Command::Chunk(String::new()),
+            _ if command.starts_with("chunk ") => {
+                Command::Chunk(command["chunk ".len()..].to_string())
+            }
+            "unchunk" => Command::Unchunk,
+            "edit" => Command::Edit(None),
+            "delete" => Command::Delete,
+            "undo" => Command::Undo,
+            "redo" => Command::Redo,
+            _ if command.starts_with("edit ") => {
+                Command::Edit(Some(command["edit ".len()..].to_string()))
+            }
+            "timeline" => Command::Timeline(None),
+            "table" => Command::Table,
+            _ if command.starts_with("timeline ") => {
+                Command::Timeline(Some(command["timeline ".len()..].trim().to_string()))
+            }
+            _ if command.starts_with("hide key=") => {
+                Command::HideKeys(command["hide key=".len()..].to_string())
+            }
+            "unhide" => Command::UnhideKeys,
+            _ if command.starts_with("filter ") => Command::Filter {
+                expression: command["filter ".len()..].trim().to_string(),
+                focused_value: false,
+            },
+            _ if command.starts_with("filter! ") => Command::Filter {
+                expression: command["filter! ".len()..].trim().to_string(),
+                focused_value: true,
+            },
+            "filter" => Command::Filter {
+                expression: String::new(),
+                focused_value: false,
+            },
+            "unfilter" => Command::Unfilter,
+            "follow" => Command::Follow,
+            "pin" => Command::Pin,
+            "aliases" => Command::ShowAliases,
+            "history" => Command::ShowHistory,
+            "checksum" => Command::Checksum,
+            "matches" => Command::ShowMatches,
+            "savepos" => Command::SavePosition(String::new()),
+            "w" => Command::Write {
+                path: None,
+                force: false,
+            },
+            "w!" => Command::Write {
+                path: None,
+                force: true,
+            },
+            _ if command.starts_with("w ") => Command::Write {
+                path: Some(command["w ".len()..].trim().to_string()),
+                force: false,
+            },
+            _ if command.starts_with("w! ") => Command::Write {
+                path: Some(command["w! ".len()..].trim().to_string()),
+                force: true,
+            },
+            "open" => Command::Open {
+                path: String::new(),
+                force: false,
+            },
+            "open!" => Command::Open {
+                path: String::new(),
+                force: true,
+            },
+            _ if command.starts_with("open ") => Command::Open {
+                path: command["open ".len()..].trim().to_string(),
+                force: false,
+            },
+            _ if command.starts_with("open! ") => Command::Open {
+                path: command["open! ".len()..].trim().to_string(),
+                force: true,
+            },
+            _ if command.starts_with("e ") => Command::Open {
+                path: command["e ".len()..].trim().to_string(),
+                force: false,
+            },
+            _ if command.starts_with("e! ") => Command::Open {
+                path: command["e! ".len()..].trim().to_string(),
+                force: true,
+            },
+            _ if command.starts_with("savepos ") => {
+                Command::SavePosition(command["savepos ".len()..].trim().to_string())
+            }
+            "sp" | "split" => Command::Split,
+            "on" | "only" => Command::CloseSplit { keep_focused: true },
+            "clo" | "close" => Command::CloseSplit {
+                keep_focused: false,
+            },
+            "alias" => Command::Alias(String::new()),
+            _ if command.starts_with("alias ") => {
+                Command::Alias(command["alias ".len()..].to_string())
+            }
+            _ if command.starts_with("unalias ") => {
+                Command::Unalias(command["unalias ".len()..].trim().to_string())
+            }
+            "unpin" => Command::Unpin,
+            "set number" => Command::SetShowLineNumber(Some(true)),
+            "set number!" => Command::SetShowLineNumber(None),
+            "set nonumber" => Command::SetShowLineNumber(Some(false)),
+            "set relativenumber" => Command::SetShowRelativeLineNumber(Some(true)),
+            "set relativenumber!" => Command::SetShowRelativeLineNumber(None),
+            "set norelativenumber" => Command::SetShowRelativeLineNumber(Some(false)),
+            "set numbermode=line" => Command::SetNumberMode(NumberMode::Line),
+            "set numbermode=index" => Command::SetNumberMode(NumberMode::Index),
+            "set previews" | "set previews=on" => Command::SetShowPreviews(Some(true)),
+            "set previews!" => Command::SetShowPreviews(None),
+            "set nopreviews" | "set previews=off" => Command::SetShowPreviews(Some(false)),
+            "set previeworder=document" => Command::SetPreviewOrder(PreviewOrder::Document),
+            "set previeworder=key" => Command::SetPreviewOrder(PreviewOrder::Key),
+            "set previeworder=size" => Command::SetPreviewOrder(PreviewOrder::Size),
+            "set accordion" => Command::SetAccordionMode(Some(true)),
+            "set accordion!" => Command::SetAccordionMode(None),
+            "set noaccordion" => Command::SetAccordionMode(Some(false)),
+            "set dense" => Command::SetDense(Some(true)),
+            "set dense!" => Command::SetDense(None),
+            "set nodense" => Command::SetDense(Some(false)),
+            "set foldruns" => Command::SetFoldRuns(Some(true)),
+            "set foldruns!" => Command::SetFoldRuns(None),
+            "set nofoldruns" => Command::SetFoldRuns(Some(false)),
+            "set invisibles" => Command::SetShowInvisibles(Some(true)),
+            "set invisibles!" => Command::SetShowInvisibles(None),
+            "set noinvisibles" => Command::SetShowInvisibles(Some(false)),
+            "set sizes" => Command::SetShowSizes(Some(true)),
+            "set sizes!" => Command::SetShowSizes(None),
+            "set nosizes" => Command::SetShowSizes(Some(false)),
+            "set numbertypes" => Command::SetShowNumberTypes(Some(true)),
+            "set numbertypes!" => Command::SetShowNumberTypes(None),
+            "set nonumbertypes" => Command::SetShowNumberTypes(Some(false)),
+            "set numberbase=decimal" => Command::SetNumberBase(NumberBase::Decimal),
+            "set numberbase=hex" => Command::SetNumberBase(NumberBase::Hex),
+            "set numberbase=binary" => Command::SetNumberBase(NumberBase::Binary),
+            "set dates" => Command::SetShowDates(Some(true)),
+            "set dates!" => Command::SetShowDates(None),
+            "set nodates" => Command::SetShowDates(Some(false)),
+            "set cursorline" => Command::SetHighlightFocusedLine(Some(true)),
+            "set cursorline!" => Command::SetHighlightFocusedLine(None),
+            "set nocursorline" => Command::SetHighlightFocusedLine(Some(false)),
+            "set incsearch" => Command::SetIncrementalSearch(Some(true)),
+            "set incsearch!" => Command::SetIncrementalSearch(None),
+            "set noincsearch" => Command::SetIncrementalSearch(Some(false)),
+            "set alignkeys" => Command::SetAlignKeys(Some(true)),
+            "set alignkeys!" => Command::SetAlignKeys(None),
+            "set noalignkeys" => Command::SetAlignKeys(Some(false)),
+            "set indentguides" => Command::SetShowIndentGuides(Some(true)),
+            "set indentguides!" => Command::SetShowIndentGuides(None),
+            "set noindentguides" => Command::SetShowIndentGuides(Some(false)),
+            "set wholeword" => Command::SetWholeWord(Some(true)),
+            "set nowholeword" => Command::SetWholeWord(Some(false)),
+            "set wholeword!" => Command::SetWholeWord(None),
+            "set fixedstrings" => Command::SetFixedStrings(Some(true)),
+            "set nofixedstrings" => Command::SetFixedStrings(Some(false)),
+            "set fixedstrings!" => Command::SetFixedStrings(None),
+            "set smoothscroll" => Command::SetSmoothScroll(Some(true)),
+            "set smoothscroll!" => Command::SetSmoothScroll(None),
+            "set nosmoothscroll" => Command::SetSmoothScroll(Some(false)),
+            "set gauge" => Command::SetShowContainerGauge(Some(true)),
+            "set gauge!" => Command::SetShowContainerGauge(None),
+            "set nogauge" => Command::SetShowContainerGauge(Some(false)),
+            "set rememberchild" => Command::SetRememberLastChild(Some(true)),
+            "set rememberchild!" => Command::SetRememberLastChild(None),
+            "set norememberchild" => Command::SetRememberLastChild(Some(false)),
+            "set keyorder" => Command::SetShowKeyOrderChanges(Some(true)),
+            "set keyorder!" => Command::SetShowKeyOrderChanges(None),
+            "set nokeyorder" => Command::SetShowKeyOrderChanges(Some(false)),
+            "set hiddenmatches=collapsed" => Command::SetHiddenMatches(HiddenMatches::Collapsed),
+            "set hiddenmatches=expand" => Command::SetHiddenMatches(HiddenMatches::Expand),
+            "set hiddenmatches=skip" => Command::SetHiddenMatches(HiddenMatches::Skip),
+            "set splitcollapse=shared" => Command::SetSharedSplitCollapse(true),
+            "set splitcollapse=independent" => Command::SetSharedSplitCollapse(false),
+            _ => Command::Unknown,
+        }
+    }
+
+    // Expand any alias at the start of a command. Built-in commands can't
+    // be overridden by aliases.
+    fn resolve_aliases(&mut self, command: String) -> Option<String> {
+        if !matches!(Self::parse_command(&command), Command::Unknown) {
+            return Some(command);
+        }
+
+        match self.aliases.resolve(&command) {
+            Ok(command) => Some(command),
+            Err(err) => {
+                self.set_error_message(err);
+                None
+            }
+        }
+    }
+
+    // The dimensions of the area the viewer is drawn in, which excludes the
+    // status bar, and the timeline and pinned rows when they're shown. On
+    // tiny screens, only the viewer is drawn, so it gets the whole screen.
+    fn viewer_area_dimensions(&self) -> TTYDimensions {
+        let screen = self.screen_writer.dimensions;
+        if screen.is_below_minimum_layout() {
+            return screen;
+        }
+
+        let mut dimensions = screen.without_status_bar();
+        if self.timeline.is_some() {
+            dimensions.height = dimensions.height.saturating_sub(1);
+        }
+        dimensions.height = dimensions
+            .height
+            .saturating_sub(self.pinned_rows.len() as u16)
+            .max(1);
+        dimensions
+    }
+
+    // The dimensions of the focused view, which only takes up part of the
+    // viewer area when the screen is split.
+    fn viewer_dimensions(&self) -> TTYDimensions {
+        let mut dimensions = self.viewer_area_dimensions();
+        if let Some(split) = &self.split {
+            let (top_height, bottom_height) = Self::split_heights(dimensions.height);
+            dimensions.height = if split.other_is_top {
+                bottom_height
+            } else {
+                top_height
+            };
+        }
+        dimensions
+    }
+
+    // The heights of the top and bottom views of a split screen, which are
+    // separated by a line showing the path of the top view.
+    fn split_heights(viewer_area_height: u16) -> (u16, u16) {
+        let available_height = viewer_area_height.saturating_sub(1);
+        let top_height = available_height / 2;
+        (top_height, available_height - top_height)
+    }
+
+    // The screen row (0-based) the focused view starts on.
+    fn focused_view_top(&self) -> u16 {
+        match &self.split {
+            Some(split) if split.other_is_top => {
+                Self::split_heights(self.viewer_area_dimensions().height).0 + 1
+            }
+            _ => 0,
+        }
+    }
+
+    fn has_room_to_split(&self) -> bool {
+        !self.screen_writer.dimensions.is_below_minimum_layout()
+            && self.viewer_area_dimensions().height >= MIN_SPLIT_HEIGHT
+    }
+
+    fn split_screen(&mut self) -> Option<Action> {
+        if self.split.is_some() {
+            self.set_info_message("The screen is already split".to_string());
+            return None;
+        }
+        if !self.has_room_to_split() {
+            self.set_warning_message("The screen is too small to split".to_string());
+            return None;
+        }
+
+        let placeholder = flatjson::FlatJson::new(vec![], String::new(), 0);
+        let mut other = JsonViewer::new(placeholder, self.viewer.mode);
+        other.scrolloff_setting = self.viewer.scrolloff_setting;
+        other.accordion_mode = self.viewer.accordion_mode;
+        other.dense = self.viewer.dense;
+        other.remember_last_child = self.viewer.remember_last_child;
+        other.fold_runs = self.viewer.fold_runs;
+        other.dimensions = self.viewer.dimensions;
+        other.top_row = self.viewer.top_row;
+        other.focused_row = self.viewer.focused_row;
+
+        let other_collapsed = if self.shared_split_collapse {
+            None
+        } else {
+            Some(self.viewer.flatjson.collapsed_containers())
+        };
+
+        // Like in vim, the focused view ends up on top.
+        self.split = Some(Split {
+            other,
+            other_is_top: false,
+            other_collapsed,
+        });
+        Some(Action::ResizeViewerDimensions(self.viewer_dimensions()))
+    }
+
+    fn close_split(&mut self, keep_focused: bool) -> Option<Action> {
+        if self.split.is_none() {
+            self.set_info_message("The screen isn't split".to_string());
+            return None;
+        }
+
+        if !keep_focused {
+            self.swap_views();
+        }
+        self.split = None;
+        self.screen_writer.viewer_top = 0;
+        Some(Action::ResizeViewerDimensions(self.viewer_dimensions()))
+    }
+
+    fn toggle_split_focus(&mut self) {
+        if self.split.is_none() {
+            return;
+        }
+
+        self.swap_views();
+        let split = self.split.as_mut().unwrap();
+        split.other_is_top = !split.other_is_top;
+        self.screen_writer.viewer_top = self.focused_view_top();
+    }
+
+    // Swap the focused view with the other one, handing the document over
+    // to the newly focused view.
+    fn swap_views(&mut self) {
+        let Some(split) = &mut self.split else {
+            return;
+        };
+
+        std::mem::swap(&mut self.viewer, &mut split.other);
+        std::mem::swap(&mut self.viewer.flatjson, &mut split.other.flatjson);
+        if let Some(other_collapsed) = &mut split.other_collapsed {
+            let collapsed = self.viewer.flatjson.collapsed_containers();
+            self.viewer
+                .flatjson
+                .set_collapsed_containers(other_collapsed);
+            *other_collapsed = collapsed;
+        }
+
+        // When sharing collapsed state, the other view may have collapsed
+        // the containers this view was positioned inside of.
+        let viewer = &mut self.viewer;
+        let mut top_row = viewer.flatjson.first_visible_ancestor(viewer.top_row);
+        let row = &viewer.flatjson[top_row];
+        if row.is_closing_of_container() && row.is_collapsed() {
+            top_row = row.pair_index().unwrap();
+        }
+        viewer.top_row = top_row;
+        viewer.perform_action(Action::JumpTo {
+            line: viewer.focused_row,
+            make_visible: false,
+        });
+    }
+
+    // Draw the view that isn't focused, along with the line separating the
+    // two views, into the frame that draw_screen is about to print.
+    fn draw_unfocused_view(&mut self) {
+        let area = self.viewer_area_dimensions();
+        let (top_height, bottom_height) = Self::split_heights(area.height);
+        let other_is_top = self.split.as_ref().unwrap().other_is_top;
+
+        self.swap_views();
+        let mut dimensions = area;
+        dimensions.height = if other_is_top {
+            top_height
+        } else {
+            bottom_height
+        };
+        self.viewer
+            .perform_action(Action::ResizeViewerDimensions(dimensions));
+        self.screen_writer.viewer_top = if other_is_top { 0 } else { top_height + 1 };
+        self.screen_writer
+            .print_unfocused_view(&self.viewer, &self.search_state);
+        if other_is_top {
+            self.screen_writer
+                .print_split_separator(&self.viewer, top_height, false);
+        }
+        self.swap_views();
+
+        if !other_is_top {
+            self.screen_writer
+                .print_split_separator(&self.viewer, top_height, true);
+        }
+        self.screen_writer.viewer_top = self.focused_view_top();
+    }
+
+    // Clicks are relative to the focused view. When the screen is split,
+    // clicking on the other view focuses it first.
+    fn click_in_view(&mut self, row: u16) -> Option<Action> {
+        if self.split.is_none() {
+            return Some(Action::Click(row));
+        }
+
+        let separator_row = Self::split_heights(self.viewer_area_dimensions().height).0 + 1;
+        if row == separator_row {
+            return None;
+        }
+        let top = self.focused_view_top();
+        if row <= top || row > top + self.viewer.dimensions.height {
+            self.toggle_split_focus();
+        }
+        Some(Action::Click(row - self.focused_view_top()))
+    }
+
+    fn set_shared_split_collapse(&mut self, shared: bool) {
+        self.shared_split_collapse = shared;
+        if let Some(split) = &mut self.split {
+            split.other_collapsed = if shared {
+                None
+            } else {
+                Some(self.viewer.flatjson.collapsed_containers())
+            };
+        }
+    }
+
+    fn toggle_timeline(&mut self, field: Option<&str>) -> Option<Action> {
+        if field.is_none() && self.timeline.take().is_some() {
+            return Some(Action::ResizeViewerDimensions(self.viewer_dimensions()));
+        }
+
+        match Timeline::new(&self.viewer.flatjson, field) {
+            Ok(timeline) => {
+                self.set_info_message(format!("Showing timeline of \"{}\"", timeline.field));
+                self.timeline = Some(timeline);
+            }
+            Err(err) => {
+                self.set_warning_message(err);
+                self.timeline = None;
+            }
+        }
+
+        Some(Action::ResizeViewerDimensions(self.viewer_dimensions()))
+    }
+
+    fn toggle_outline(&mut self) {
+        // If the panel is listing search matches, switch to the outline.
+        match self.outline.take() {
+            Some(outline) if outline.kind == OutlineKind::Document => {}
+            _ => {
+                let mut outline = Outline::new(&self.viewer.flatjson);
+                outline.select_entry_containing(self.viewer.focused_row);
+                self.outline = Some(outline);
+            }
+        }
+    }
+
+    // Move the focus to the outline panel, opening it if it isn't already.
+    fn focus_outline(&mut self) {
+        if self.outline.is_none() {
+            self.toggle_outline();
+        }
+        let outline = self.outline.as_mut().unwrap();
+        outline.select_entry_containing(self.viewer.focused_row);
+        outline.focused = true;
+    }
+
+    // List the rows with matches for the current search in the outline
+    // panel.
+    fn show_search_matches(&mut self) {
+        if !self.search_state.any_matches() {
+            let message = if self.search_state.search_term.is_empty() {
+                "No current search".to_string()
+            } else {
+                self.search_state.no_matches_message()
+            };
+            self.set_warning_message(message);
+            return;
+        }
+
+        let mut outline = Outline::search_matches(&self.viewer.flatjson, &self.search_state);
+        outline.select_entry_containing(self.viewer.focused_row);
+        self.outline = Some(outline);
+    }
+
+    fn handle_outline_key(&mut self, key: Key) -> Option<Action> {
+        let outline = self.outline.as_mut().unwrap();
+
+        match key {
+            Key::Down | Key::Char('j') | Key::Ctrl('n') => outline.move_selection_down(1),
+            Key::Up | Key::Char('k') | Key::Ctrl('p') => outline.move_selection_up(1),
+            Key::Ctrl('d') => {
+                let half_page = self.viewer.dimensions.height as usize / 2;
+                outline.move_selection_down(half_page);
+            }
+            Key::Ctrl('u') => {
+                let half_page = self.viewer.dimensions.height as usize / 2;
+                outline.move_selection_up(half_page);
+            }
+            Key::Home | Key::Char('g') => outline.select_first(),
+            Key::End | Key::Char('G') => outline.select_last(),
+            Key::Char('O') => outline.focused = false,
+            Key::Char('\n') => {
+                outline.focused = false;
+                return outline.selected_row().map(|line| match outline.kind {
+                    OutlineKind::Document => Action::JumpTo {
+                        line,
+                        make_visible: true,
+                    },
+                    OutlineKind::SearchMatches => Action::Reveal(line),
+                });
+            }
+            Key::Esc | Key::Char('o') => self.outline = None,
+            _ => eprint!("{BELL}\r"),
+        }
+
+        None
+    }
+
+    // Show the focused Array of Objects (or the Array containing the focused
+    // element) as a table, starting on the focused element, or go back to
+    // the viewer if a table is already shown.
+    fn toggle_table(&mut self) {
+        if self.table.take().is_some() {
+            return;
+        }
+
+        let Some(array_index) = self.focused_array("table") else {
+            return;
+        };
+        match Table::new(&self.viewer.flatjson, array_index) {
+            Ok(mut table) => {
+                table.select_element_containing(&self.viewer.flatjson, self.viewer.focused_row);
+                self.set_info_message(format!(
+                    "Showing {} elements as a table; Enter jumps to the selected one, T goes back",
+                    table.num_elements()
+                ));
+                self.table = Some(table);
+            }
+            Err(err) => self.set_warning_message(err),
+        }
+    }
+
+    fn handle_table_key(&mut self, key: Key) -> Option<Action> {
+        let table = self.table.as_mut().unwrap();
+        // The first line of the table shows the keys of the columns.
+        let height = (self.viewer.dimensions.height as usize).saturating_sub(1);
+
+        match key {
+            Key::Down | Key::Char('j') | Key::Ctrl('n') => table.move_selection_down(1),
+            Key::Up | Key::Char('k') | Key::Ctrl('p') => table.move_selection_up(1),
+            Key::Ctrl('d') => table.move_selection_down(height / 2),
+            Key::Ctrl('u') => table.move_selection_up(height / 2),
+            Key::Ctrl('f') | Key::PageDown => table.move_selection_down(height),
+            Key::Ctrl('b') | Key::PageUp => table.move_selection_up(height),
+            Key::Home | Key::Char('g') => table.select_first(),
+            Key::End | Key::Char('G') => table.select_last(),
+            Key::Right | Key::Char('l') => table.scroll_right(1),
+            Key::Left | Key::Char('h') => table.scroll_left(1),
+            Key::Char('0') | Key::Char('^') => table.left = 0,
+            Key::Char('$') => table.scroll_to_last_column(),
+            Key::Char('/') => self.search_table(SearchDirection::Forward),
+            Key::Char('?') => self.search_table(SearchDirection::Reverse),
+            Key::Char('n') => self.jump_to_table_match(JumpDirection::Next),
+            Key::Char('N') => self.jump_to_table_match(JumpDirection::Prev),
+            Key::Char('\n') => {
+                let element = table.selected_element();
+                self.table = None;
+                return element.map(Action::Reveal);
+            }
+            Key::Esc | Key::Char('q') | Key::Char('T') => self.table = None,
+            _ => eprint!("{BELL}\r"),
+        }
+
+        None
+    }
+
+    // Search within a single column of the table: `/name:foo` searches the
+    // values in the "name" column for foo, and anything without the name
+    // of a column in front of it searches the leftmost visible column.
+    fn search_table(&mut self, direction: SearchDirection) {
+        let prompt = direction.prompt_char().to_string();
+        let Some(input) = self.readline(&prompt, "search input", Completion::Keys) else {
+            return;
+        };
+
+        let table = self.table.as_ref().unwrap();
+        if table.columns.is_empty() {
+            self.set_warning_message("The table has no columns to search".to_string());
+            return;
+        }
+        let (column, pattern) = input
+            .split_once(':')
+            .and_then(|(key, pattern)| Some((table.column_named(key)?, pattern)))
+            .unwrap_or((table.left, input.as_str()));
+        let key = table.columns[column].key.clone();
+
+        let search_state = match SearchState::initialize_search(
+            pattern.to_owned(),
+            &self.viewer.flatjson.1,
+            direction,
+            self.search_defaults,
+        ) {
+            Ok(search_state) => search_state,
+            Err(err_message) => {
+                self.set_error_message(err_message);
+                return;
+            }
+        };
+
+        let table = self.table.as_mut().unwrap();
+        // Scroll to the column if it isn't visible, so the matches are.
+        let width = self.screen_writer.dimensions.width as usize;
+        if !table
+            .visible_columns(width)
+            .iter()
+            .any(|(c, _)| *c == column)
+        {
+            table.left = column;
+        }
+        match table.search_column(&self.viewer.flatjson, column, &search_state) {
+            0 => self.set_warning_message(format!(
+                "Pattern not found in {key}: {}",
+                search_state.search_term
+            )),
+            _ => self.jump_to_table_match(JumpDirection::Next),
+        }
+    }
+
+    fn jump_to_table_match(&mut self, jump_direction: JumpDirection) {
+        let table = self.table.as_mut().unwrap();
+        match (table.jump_to_match(jump_direction), &table.search) {
+            (Some((position, wrapped)), Some(search)) => {
+                let message = format!(
+                    "Match {} of {} in {}{}",
+                    position + 1,
+                    table.num_matching_elements(),
+                    table.columns[search.column].key,
+                    if wrapped { " (wrapped)" } else { "" },
+                );
+                self.set_info_message(message);
+            }
+            (None, Some(_)) => self.set_warning_message("No matches in the column".to_string()),
+            _ => self.set_info_message("Type / to search a column".to_string()),
+        }
+    }
+
+    // Mark the focused row as the start of relative paths, or clear the
+    // mark if it's already marked.
+    fn toggle_mark(&mut self) {
+        let flatjson = &self.viewer.flatjson;
+        let focused_row = &flatjson[self.viewer.focused_row];
+        let row = if focused_row.is_closing_of_container() {
+            focused_row.pair_index().unwrap()
+        } else {
+            self.viewer.focused_row
+        };
+
+        if self.viewer.mark == Some(row) {
+            self.viewer.mark = None;
+            self.set_info_message("Mark cleared".to_string());
+            return;
+        }
+
+        self.viewer.mark = Some(row);
+        let message = match flatjson.build_path_to_node(flatjson::PathType::StatusBar, row) {
+            Ok(path) if !path.is_empty() => format!("Marked {path}"),
+            _ => "Marked top-level value".to_string(),
+        };
+        self.set_info_message(message);
+    }
+
+    pub fn set_config_path(&mut self, config_path: PathBuf) {
+        self.config_path = Some(config_path);
+    }
+
+    // Re-read the config file and apply the display settings and key
+    // mappings from it.
+    // Options that only matter at startup, like --mode or --cmd, are
+    // ignored.
+    fn reload_config(&mut self) {
+        let Some(config_path) = &self.config_path else {
+            return;
+        };
+        let mut opt = match config::reload_options(config_path) {
+            Ok(opt) => opt,
+            Err(err) => {
+                self.set_error_message(format!("Unable to reload config: {err}"));
+                return;
+            }
+        };
+        if opt.background.is_none() {
+            opt.background = self.background;
+        }
+
+        self.screen_writer.apply_options(&opt);
+        self.keymap = Keymap::new(&opt.key_mappings);
+        self.named_filters = opt.named_filters.iter().cloned().collect();
+        self.aliases.define_all(&opt.aliases);
+        if let Some(key_completer) = self.screen_writer.command_editor.helper_mut() {
+            key_completer.filter_names = self.named_filters.keys().cloned().collect();
+        }
+        self.incsearch = opt.incsearch;
+        self.search_defaults = SearchDefaults {
+            whole_word: opt.whole_word,
+            fixed_string: opt.fixed_strings,
+        };
+        self.smooth_scroll.apply_options(&opt);
+        let mut viewers = vec![&mut self.viewer];
+        if let Some(split) = &mut self.split {
+            viewers.push(&mut split.other);
+        }
+        for viewer in viewers {
+            viewer.scrolloff_setting = opt.scrolloff;
+            viewer.accordion_mode = opt.accordion;
+            viewer.dense = opt.dense;
+            viewer.remember_last_child = opt.remember_last_child;
+            viewer.set_fold_runs(opt.fold_runs);
+        }
+        self.set_info_message("Reloaded config".to_string());
+    }
+
+    // Newline-delimited JSON input is still being read, and more records
+    // will arrive as TuiEvent::Records.
+    pub fn start_loading_records(&mut self) {
+        self.loading_records = true;
+    }
+
+    fn receive_records(&mut self, records: Records) {
+        if records.last {
+            self.loading_records = false;
+            self.following = false;
+            for (_, saved) in &mut self.result_buffers {
+                saved.following = false;
+            }
+            if let Some(read_error) = &records.read_error {
+                self.set_warning_message(read_error.clone());
+            }
+        } else {
+            // There are more records to come.
+            self.loading_records = true;
+        }
+        self.pending_records.push(records);
+        self.append_pending_records();
+    }
+
+    // Append the records that have been read since the document was shown
+    // to it, unless it's currently a modified copy of the input, which
+    // would get replaced by the original again later.
+    fn append_pending_records(&mut self) {
+        if self.pending_records.is_empty()
+            || !self.result_buffers.is_empty()
+            || self.hidden_keys.is_some()
+            || self.chunked_string.is_some()
+            || self.filter.is_some()
+        {
+            return;
+        }
+
+        let first_new_row = self.viewer.flatjson.0.len();
+        let text_start = self.viewer.flatjson.1.len();
+        for records in std::mem::take(&mut self.pending_records) {
+            self.parse_errors.extend(records.errors);
+            if !records.rows.is_empty() {
+                self.viewer
+                    .flatjson
+                    .append(records.rows, &records.pretty, records.depth);
+            }
+        }
+        if first_new_row == self.viewer.flatjson.0.len() {
+            return;
+        }
+
+        if self.viewer.fold_runs {
+            self.viewer.flatjson.fold_new_runs(first_new_row);
+        }
+
+        if let Some(search_index) = &mut self.search_index {
+            search_index.add(&self.viewer.flatjson.1[text_start..], text_start);
+        }
+        if let Some(key_completer) = self.screen_writer.command_editor.helper_mut() {
+            key_completer.add_keys(&self.viewer.flatjson, first_new_row);
+        }
+        if let Some(outline) = &mut self.outline {
+            outline.add_rows(&self.viewer.flatjson, first_new_row);
+        }
+        if let Some(timeline) = &mut self.timeline {
+            timeline.add_records(&self.viewer.flatjson, first_new_row);
+        }
+        if self.following {
+            self.viewer.perform_action(Action::FocusBottom);
+        }
+    }
+
+    // Keep the last record focused as more are appended to the input,
+    // until the user moves again.
+    fn follow_input(&mut self) -> Option<Action> {
+        if !self.loading_records {
+            self.set_warning_message("Input has already been read completely".to_string());
+            return None;
+        }
+        self.following = true;
+        Some(Action::FocusBottom)
+    }
+
+    // Re-read the input file and replace the current document with it. Rows
+    // that changed will be marked in the gutter until the user hits Escape.
+    fn reload_input(&mut self) {
+        let Some(path) = &self.input_path else {
+            self.set_warning_message("Cannot reload input read from STDIN".to_string());
+            return;
+        };
+        if self.loading_records {
+            self.set_warning_message("Cannot reload input that is still being read".to_string());
+            return;
+        }
+
+        let data = match std::fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(err) => {
+                self.set_error_message(format!("Unable to reload input: {err}"));
+                return;
+            }
+        };
+
+        // Result buffers and the filtered document are derived from the
+        // input, which is about to change, so go back to the original.
+        self.close_result_buffers();
+        if self.filter.is_some() {
+            self.unfilter_document();
+        }
+
+        let (mut flatjson, parse_errors) =
+            match Self::parse_input(data, self.data_format, self.allow_nan, self.tolerant) {
+                Ok(parsed) => parsed,
+                Err(err) => {
+                    self.set_error_message(format!("Unable to parse reloaded input: {err}"));
+                    return;
+                }
+            };
+
+        // Keep hiding the same keys in the reloaded input.
+        if let Some(hidden_keys) = self.hidden_keys.take() {
+            match Self::filter_keys(&flatjson, &hidden_keys.pattern) {
+                Ok((filtered, kept_rows, _)) => {
+                    let unfiltered = std::mem::replace(&mut flatjson, filtered);
+                    self.hidden_keys = Some(HiddenKeys {
+                        pattern: hidden_keys.pattern,
+                        unfiltered,
+                        kept_rows,
+                    });
+                }
+                Err(err) => {
+                    self.hidden_keys = Some(hidden_keys);
+                    self.set_error_message(format!("Unable to hide keys in reloaded input: {err}"));
+                    return;
+                }
+            }
+        }
+
+        // Remember the value that was focused before reloading, as well as
+        // the new value, so that :history can show how it changed.
+        self.record_focused_value();
+        let num_changed = self.replace_document(flatjson, parse_errors);
+        self.modified = false;
+        self.loaded_at = SystemTime::now();
+        self.record_focused_value();
+
+        let plural = if num_changed == 1 { "" } else { "s" };
+        let mut message = format!(
+            "Reloaded {}; {num_changed} value{plural} changed",
+            self.input_filename
+        );
+
+        let num_reordered = self
+            .diff_markers
+            .values()
+            .filter(|marker| **marker == diff::DiffMarker::Reordered)
+            .count();
+        if num_reordered > 0 && self.show_key_order_changes {
+            let plural = if num_reordered == 1 { "" } else { "s" };
+            message.push_str(&format!(
+                ", keys of {num_reordered} object{plural} reordered"
+            ));
+        }
+
+        self.set_info_message(message);
+    }
+
+    // The path of the focused value, identifying it across reloads, and the
+    // value itself.
+    fn focused_path_and_value(&self) -> Option<(String, String)> {
+        let flatjson = &self.viewer.flatjson;
+        let mut index = self.viewer.focused_row;
+        if flatjson[index].is_closing_of_container() {
+            index = flatjson[index].pair_index().unwrap();
+        }
+
+        let path = flatjson
+            .build_path_to_node(PathType::DotWithTopLevelIndex, index)
+            .ok()?;
+        let value = flatjson.1[flatjson[index].range.clone()].to_string();
+        Some((path, value))
+    }
+
+    fn record_focused_value(&mut self) {
+        if let Some((path, value)) = self.focused_path_and_value() {
+            self.value_history.record(path, self.loaded_at, &value);
+        }
+    }
+
+    fn save_position(&mut self, path: &str) {
+        if path.is_empty() {
+            self.set_warning_message("Usage: :savepos FILE".to_string());
+            return;
+        }
+
+        if let Err(err) = Capabilities::check(self.capabilities.write_files, "Saving positions") {
+            self.set_warning_message(err);
+            return;
+        }
+
+        let position = SavedPosition::of(&self.viewer.flatjson, self.viewer.focused_row);
+        match std::fs::write(path, position.to_string()) {
+            Ok(()) => self.set_info_message(format!(
+                "Saved position to {path}; open it with --restorepos {path}"
+            )),
+            Err(err) => self.set_error_message(format!("Unable to save position: {err}")),
+        }
+    }
+
+    // Write the document, including any changes made to it, as pretty
+    // printed JSON to the given file, or else back to the input file. Other
+    // existing files are only overwritten when forced, like with :w! in vim.
+    fn write_document(&mut self, path: Option<String>, force: bool) {
+        if let Err(err) = Capabilities::check(self.capabilities.write_files, "Writing files") {
+            self.set_warning_message(err);
+            return;
+        }
+        // Only the input itself gets written, not a copy of it that's
+        // derived from it.
+        if !self.result_buffers.is_empty() {
+            self.set_warning_message("Close result buffers before writing".to_string());
+            return;
+        }
+        let filtered = matches!(&self.filter, Some(filter) if !filter.showing_original);
+        if self.hidden_keys.is_some() || self.chunked_string.is_some() || filtered {
+            self.set_warning_message(
+                "Use :unhide, :unchunk and :unfilter before writing".to_string(),
+            );
+            return;
+        }
+
+        let path = match path {
+            Some(path) if !path.is_empty() => PathBuf::from(path),
+            _ => {
+                let Some(input_path) = &self.input_path else {
+                    self.set_warning_message(
+                        "Input was read from STDIN; use :w FILE to write it".to_string(),
+                    );
+                    return;
+                };
+                // The document is always written as JSON.
+                if matches!(self.data_format, DataFormat::Yaml | DataFormat::Hjson) && !force {
+                    self.set_warning_message(
+                        "Input isn't JSON; use :w FILE, or :w! to overwrite it with JSON"
+                            .to_string(),
+                    );
+                    return;
+                }
+                input_path.clone()
+            }
+        };
+
+        let is_input = self.input_path.as_ref() == Some(&path);
+        if !is_input && !force && path.exists() {
+            self.set_warning_message(format!(
+                "{} already exists; use :w! to overwrite it",
+                path.display()
+            ));
+            return;
+        }
+
+        let json = match self.viewer.flatjson.pretty_printed() {
+            Ok(json) => json,
+            Err(err) => {
+                self.set_error_message(format!("Unable to write document: {err}"));
+                return;
+            }
+        };
+        match std::fs::write(&path, &json) {
+            Ok(()) => {
+                self.modified = false;
+                self.set_info_message(format!(
+                    "Wrote {} ({})",
+                    path.display(),
+                    format_size(json.len())
+                ));
+            }
+            Err(err) => {
+                self.set_error_message(format!("Unable to write {}: {err}", path.display()))
+            }
+        }
+    }
+
+    // Open another file in place of the input, as if jless had been
+    // started with it, but keeping the current settings. Its format is
+    // determined by its extension.
+    fn open_file(&mut self, path: String, force: bool) {
+        if path.is_empty() {
+            self.set_warning_message("Usage: :open FILE".to_string());
+            return;
+        }
+        if self.loading_records {
+            self.set_warning_message(
+                "Cannot open a file while the input is still being read".to_string(),
+            );
+            return;
+        }
+        if self.modified && !force {
+            self.set_warning_message(
+                "Document has unwritten changes; use :w to write them, or :open! to discard them"
+                    .to_string(),
+            );
+            return;
+        }
+
+        let path = PathBuf::from(path);
+        let data = match std::fs::read_to_string(&path) {
+            Ok(data) => data,
+            Err(err) => {
+                self.set_error_message(format!("Unable to open {}: {err}", path.display()));
+                return;
+            }
+        };
+
+        let filename = path
+            .file_name()
+            .unwrap_or(path.as_os_str())
+            .to_string_lossy()
+            .to_string();
+        let data_format = crate::determine_data_format(None, &filename);
+        let (flatjson, parse_errors) =
+            match Self::parse_input(data, data_format, self.allow_nan, self.tolerant) {
+                Ok(parsed) => parsed,
+                Err(err) => {
+                    self.set_error_message(format!("Unable to parse {}: {err}", path.display()));
+                    return;
+                }
+            };
+
+        // Nothing derived from the previous input applies to this one.
+        self.close_result_buffers();
+        self.filter = None;
+        self.hidden_keys = None;
+        self.diff_markers.clear();
+        self.value_history = ValueHistory::default();
+        self.set_document(flatjson, parse_errors, 0);
+        self.input_filename = filename;
+        self.input_path = Some(path);
+        self.data_format = data_format;
+        self.modified = false;
+        self.following = false;
+        self.loaded_at = SystemTime::now();
+
+        if self.parse_errors.is_empty() {
+            self.set_info_message(format!("Opened {}", self.input_filename));
+        } else {
+            self.set_warning_message(Self::skipped_lines_message(&self.parse_errors));
+        }
+    }
+
+    fn show_checksum(&mut self) {
+        match self.get_content_target_data(ContentTarget::Checksum) {
+            Ok(checksum) => self.set_info_message(format!("SHA-256: {checksum}")),
+            Err(err) => self.set_warning_message(err),
+        }
+    }
+
+    fn show_value_history(&mut self) {
+        let Some((path, _)) = self.focused_path_and_value() else {
+            return;
+        };
+
+        let Some(values) = self.value_history.values(&path) else {
+            self.set_info_message(
+                "No history for focused value; use :reload to record it".to_string(),
+            );
+            return;
+        };
+
+        let mut focused_row = self.viewer.focused_row;
+        if self.viewer.flatjson[focused_row].is_closing_of_container() {
+            focused_row = self.viewer.flatjson[focused_row].pair_index().unwrap();
+        }
+        let mut buffer = ResultBuffer::new(format!("[history of {path}]"));
+        for (loaded_at, value) in values {
+            buffer.push_value(loaded_at, value, Some(focused_row));
+        }
+        self.open_result_buffer(buffer);
+    }
+
+    // Replace the current document with a new one, keeping the focus on the
+    // same row, and marking rows that changed in the gutter. Returns the
+    // number of values that changed, not counting key order changes.
+    fn replace_document(
+        &mut self,
+        flatjson: flatjson::FlatJson,
+        parse_errors: Vec<flatjson::LineError>,
+    ) -> usize {
+        self.diff_markers = diff::diff_markers(&self.viewer.flatjson, &flatjson);
+        let num_changed = self
+            .diff_markers
+            .values()
+            .filter(|marker| matches!(marker, diff::DiffMarker::Added | diff::DiffMarker::Modified))
+            .count();
+
+        let focused_row = self.viewer.focused_row;
+        self.set_document(flatjson, parse_errors, focused_row);
+
+        num_changed
+    }
+
+    // Show a different document, focusing on the given row in it, and
+    // return the document that was previously shown.
+    fn set_document(
+        &mut self,
+        flatjson: flatjson::FlatJson,
+        parse_errors: Vec<flatjson::LineError>,
+        focused_row: flatjson::Index,
+    ) -> flatjson::FlatJson {
+        // Changes to the previous document can't be undone in this one.
+        self.undo_history.clear();
+
+        // The other view of a split screen refers to rows of the previous
+        // document, so go back to a single view.
+        if self.split.take().is_some() {
+            self.screen_writer.viewer_top = 0;
+        }
+
+        let viewer = self.new_viewer(flatjson, focused_row);
+        let previous = std::mem::replace(&mut self.viewer, viewer).flatjson;
+        self.parse_errors = parse_errors;
+        self.chunked_string = None;
+        self.table = None;
+        self.search_state = SearchState::empty();
+        self.rebuild_search_index();
+        self.jump_origin = None;
+        self.reset_screen_for_document();
+        // The search was cleared, so there are no matches to list anymore.
+        if matches!(&self.outline, Some(outline) if outline.kind == OutlineKind::SearchMatches) {
+            self.outline = None;
+        }
+        if let Some(outline) = &mut self.outline {
+            let focused = outline.focused;
+            *outline = Outline::new(&self.viewer.flatjson);
+            outline.select_entry_containing(self.viewer.focused_row);
+            outline.focused = focused;
+        }
+        if let Some(timeline) = &self.timeline {
+            let field = timeline.field.clone();
+            self.timeline = Timeline::new(&self.viewer.flatjson, Some(&field)).ok();
+            if self.timeline.is_none() {
+                let dimensions = self.viewer_dimensions();
+                self.viewer
+                    .perform_action(Action::ResizeViewerDimensions(dimensions));
+            }
+        }
+        // Pinned rows may not exist in the new document.
+        if !self.pinned_rows.is_empty() {
+            self.pinned_rows.clear();
+            let dimensions = self.viewer_dimensions();
+            self.viewer
+                .perform_action(Action::ResizeViewerDimensions(dimensions));
+        }
+
+        previous
+    }
+
+    // Create a viewer for a different document with the same settings as
+    // the current one.
+    fn new_viewer(&self, flatjson: flatjson::FlatJson, focused_row: flatjson::Index) -> JsonViewer {
+        let mut viewer = JsonViewer::new(flatjson, self.viewer.mode);
+        viewer.scrolloff_setting = self.viewer.scrolloff_setting;
+        viewer.accordion_mode = self.viewer.accordion_mode;
+        viewer.dense = self.viewer.dense;
+        viewer.remember_last_child = self.viewer.remember_last_child;
+        viewer.set_fold_runs(self.viewer.fold_runs);
+        viewer.dimensions = self.viewer_dimensions();
+        viewer.perform_action(Action::JumpTo {
+            line: focused_row,
+            make_visible: false,
+        });
+        viewer
+    }
+
+    // Show the output of an analysis command in place of the document,
+    // which is put aside until the buffer is closed with q.
+    fn open_result_buffer(&mut self, buffer: ResultBuffer) {
+        let flatjson = match buffer.to_flatjson() {
+            Ok(flatjson) => flatjson,
+            Err(err) => {
+                self.set_error_message(format!("Unable to show {}: {err}", buffer.name));
+                return;
+            }
+        };
+
+        // The other view of a split screen refers to rows of the document.
+        if self.split.take().is_some() {
+            self.screen_writer.viewer_top = 0;
+        }
+
+        // Take the panels first, so that the buffer is sized without them.
+        let pinned_rows = std::mem::take(&mut self.pinned_rows);
+        let outline = self.outline.take();
+        let timeline = self.timeline.take();
+        self.table = None;
+        let viewer = self.new_viewer(flatjson, 0);
+
+        let saved = SavedDocument {
+            viewer: std::mem::replace(&mut self.viewer, viewer),
+            input_filename: std::mem::replace(&mut self.input_filename, buffer.name.clone()),
+            parse_errors: std::mem::take(&mut self.parse_errors),
+            hidden_keys: self.hidden_keys.take(),
+            chunked_string: self.chunked_string.take(),
+            filter: self.filter.take(),
+            diff_markers: std::mem::take(&mut self.diff_markers),
+            search_state: std::mem::replace(&mut self.search_state, SearchState::empty()),
+            search_index: self.search_index.take(),
+            jump_origin: self.jump_origin.take(),
+            pinned_rows,
+            outline,
+            timeline,
+            following: std::mem::replace(&mut self.following, false),
+        };
+
+        self.result_buffers.push((buffer, saved));
+        self.reset_screen_for_document();
+    }
+
+    // Go back to the document that the most recent result buffer replaced.
+    fn close_result_buffer(&mut self) {
+        let Some((_, saved)) = self.result_buffers.pop() else {
+            return;
+        };
+
+        if self.split.take().is_some() {
+            self.screen_writer.viewer_top = 0;
+        }
+
+        self.viewer = saved.viewer;
+        self.input_filename = saved.input_filename;
+        self.parse_errors = saved.parse_errors;
+        self.hidden_keys = saved.hidden_keys;
+        self.chunked_string = saved.chunked_string;
+        self.filter = saved.filter;
+        self.diff_markers = saved.diff_markers;
+        self.search_state = saved.search_state;
+        self.search_index = saved.search_index;
+        self.jump_origin = saved.jump_origin;
+        self.pinned_rows = saved.pinned_rows;
+        self.outline = saved.outline;
+        self.timeline = saved.timeline;
+        self.following = saved.following;
+
+        // The screen may have been resized while the buffer was shown.
+        let dimensions = self.viewer_dimensions();
+        self.viewer
+            .perform_action(Action::ResizeViewerDimensions(dimensions));
+        self.reset_screen_for_document();
+        self.append_pending_records();
+    }
+
+    fn close_result_buffers(&mut self) {
+        while !self.result_buffers.is_empty() {
+            self.close_result_buffer();
+        }
+    }
+
+    // The row of the document that the focused entry of a result buffer
+    // refers to.
+    fn focused_result_target(&self) -> Option<flatjson::Index> {
+        let (buffer, _) = self.result_buffers.last()?;
+        buffer.target(&self.viewer.flatjson, self.viewer.focused_row)
+    }
+
+    fn reset_screen_for_document(&mut self) {
+        self.screen_writer
+            .reset_for_new_document(self.displayed_diff_markers());
+        let mut key_completer = KeyCompleter::new(&self.viewer.flatjson);
+        key_completer.filter_names = self.named_filters.keys().cloned().collect();
+        self.screen_writer
+            .command_editor
+            .set_helper(Some(key_completer));
+    }
+
+    // Hide object entries whose keys match a pattern, by replacing the
+    // document with a copy that doesn't contain them. The original document
+    // is kept, so that they can be shown again.
+    fn hide_keys(&mut self, pattern: &str) {
+        let pattern = match Regex::new(pattern) {
+            Ok(pattern) => pattern,
+            Err(err) => {
+                // The default Display implementation for these errors spills
+                // onto multiple lines.
+                self.set_error_message(format!("{err}").replace('\n', " "));
+                return;
+            }
+        };
+
+        // A new pattern replaces the old one, rather than hiding even more.
+        let (unfiltered, focused_row) = match &self.hidden_keys {
+            Some(hidden_keys) => (
+                &hidden_keys.unfiltered,
+                hidden_keys.unfiltered_index(self.viewer.focused_row),
+            ),
+            None => (&self.viewer.flatjson, self.viewer.focused_row),
+        };
+        let (filtered, kept_rows, num_hidden) = match Self::filter_keys(unfiltered, &pattern) {
+            Ok(filtered) => filtered,
+            Err(err) => {
+                self.set_error_message(format!("Unable to hide keys: {err}"));
+                return;
+            }
+        };
+        // Focus the closest row before the focused one that's still shown.
+        let focused_row = kept_rows.partition_point(|row| *row <= focused_row) - 1;
+
+        self.diff_markers.clear();
+        let parse_errors = std::mem::take(&mut self.parse_errors);
+        let previous = self.set_document(filtered, parse_errors, focused_row);
+        let unfiltered = match self.hidden_keys.take() {
+            Some(hidden_keys) => hidden_keys.unfiltered,
+            None => previous,
+        };
+
+        let plural = if num_hidden == 1 { "y" } else { "ies" };
+        self.set_info_message(format!(
+            "Hiding {num_hidden} entr{plural} with keys matching {pattern}; use :unhide to show them"
+        ));
+        self.hidden_keys = Some(HiddenKeys {
+            pattern,
+            unfiltered,
+            kept_rows,
+        });
+    }
+
+    fn filter_keys(
+        flatjson: &flatjson::FlatJson,
+        pattern: &Regex,
+    ) -> Result<(flatjson::FlatJson, Vec<flatjson::Index>, usize), String> {
+        let (json, kept_rows, num_hidden) = flatjson.without_keys_matching(pattern)?;
+        let filtered = flatjson::parse_top_level_json(json)?;
+        Ok((filtered, kept_rows, num_hidden))
+    }
+
+    fn unhide_keys(&mut self) {
+        let Some(hidden_keys) = self.hidden_keys.take() else {
+            self.set_info_message("No keys are hidden".to_string());
+            return;
+        };
+
+        let focused_row = hidden_keys.unfiltered_index(self.viewer.focused_row);
+        self.diff_markers.clear();
+        let parse_errors = std::mem::take(&mut self.parse_errors);
+        self.set_document(hidden_keys.unfiltered, parse_errors, focused_row);
+        self.set_info_message("Showing all keys".to_string());
+    }
+
+    // Replace the document with the output of a jq filter, or with just the
+    // focused value replaced by it, e.g., to narrow down huge API responses.
+    // Filtering again filters the filtered document further.
+    fn filter_document(&mut self, expression: &str, focused_value: bool) {
+        // Without an expression, switch between the filtered document and
+        // the original, like ^^.
+        if expression.is_empty() && !focused_value && self.filter.is_some() {
+            self.toggle_filtered_document();
+            return;
+        }
+        if expression.is_empty() {
+            self.set_warning_message("Usage: :filter JQ-EXPRESSION".to_string());
+            return;
+        }
+        // Filters defined in the config file are run by name.
+        let expression = match expression.strip_prefix('@') {
+            Some(name) => match self.named_filters.get(name) {
+                Some(expression) => expression.clone(),
+                None => {
+                    self.set_warning_message(format!("No filter named {name}"));
+                    return;
+                }
+            },
+            None => expression.to_string(),
+        };
+        let expression = expression.as_str();
+        if self.hidden_keys.is_some() || self.chunked_string.is_some() {
+            self.set_warning_message("Use :unhide and :unchunk before filtering".to_string());
+            return;
+        }
+        if let Err(err) = Capabilities::check(self.capabilities.run_programs, "Running jq") {
+            self.set_warning_message(err);
+            return;
+        }
+
+        let flatjson = &self.viewer.flatjson;
+        let mut value_index = self.viewer.focused_row;
+        if flatjson[value_index].is_closing_of_container() {
+            value_index = flatjson[value_index].pair_index().unwrap();
+        }
+        let input = if focused_value {
+            flatjson.pretty_printed_value_as_json(value_index)
+        } else {
+            let mut input = String::new();
+            let mut top_level = OptionIndex::Index(0);
+            while let OptionIndex::Index(index) = top_level {
+                match flatjson.pretty_printed_value_as_json(index) {
+                    Ok(value) => input.push_str(&value),
+                    Err(err) => {
+                        self.set_error_message(format!("Unable to filter document: {err}"));
+                        return;
+                    }
+                }
+                input.push('\n');
+                top_level = flatjson[index].next_sibling;
+            }
+            Ok(input)
+        };
+
+        let values = match input.and_then(|input| jq::run(expression, input)) {
+            Ok(values) if values.is_empty() => {
+                self.set_warning_message(format!("{expression} didn't output anything"));
+                return;
+            }
+            Ok(values) => values,
+            Err(err) => {
+                self.set_error_message(err);
+                return;
+            }
+        };
+
+        let (document, focused_row) = if focused_value {
+            // Multiple outputs replace the value with an array of them.
+            let replacement = if values.len() == 1 {
+                values[0].clone()
+            } else {
+                format!("[{}]", values.join(","))
+            };
+            let range = flatjson[value_index].range.clone();
+            let document = [
+                &flatjson.1[..range.start],
+                &replacement,
+                &flatjson.1[range.end..],
+            ]
+            .concat();
+            (document, value_index)
+        } else {
+            (values.join("\n"), 0)
+        };
+        let filtered = match flatjson::parse_top_level_json(document) {
+            Ok(filtered) => filtered,
+            Err(err) => {
+                self.set_error_message(format!("Unable to parse output of jq: {err}"));
+                return;
+            }
+        };
+
+        let other_focused_row = self.viewer.focused_row;
+        self.diff_markers.clear();
+        let parse_errors = std::mem::take(&mut self.parse_errors);
+        let previous = self.set_document(filtered, vec![], focused_row);
+        // Keep the original document, and switch back to it with F.
+        let filter = match self.filter.take() {
+            Some(filter) if !filter.showing_original => Filter {
+                expression: format!("{} | {expression}", filter.expression),
+                ..filter
+            },
+            _ => Filter {
+                expression: expression.to_string(),
+                showing_original: false,
+                other: previous,
+                other_parse_errors: parse_errors,
+                other_focused_row,
+            },
+        };
+        self.set_info_message(format!(
+            "Filtered through {}; press ^^ to switch to the original",
+            filter.expression
+        ));
+        self.filter = Some(filter);
+    }
+
+    // Switch between the filtered document and the original one.
+    fn toggle_filtered_document(&mut self) {
+        if self.filter.is_none() {
+            self.set_info_message("No filter; use :filter JQ-EXPRESSION".to_string());
+            return;
+        }
+        if self.hidden_keys.is_some() || self.chunked_string.is_some() {
+            self.set_warning_message("Use :unhide and :unchunk before switching".to_string());
+            return;
+        }
+
+        let mut filter = self.filter.take().unwrap();
+        let focused_row = self.viewer.focused_row;
+        let other = std::mem::replace(
+            &mut filter.other,
+            flatjson::FlatJson::new(vec![], String::new(), 0),
+        );
+        let other_parse_errors = std::mem::take(&mut filter.other_parse_errors);
+        filter.other_parse_errors = std::mem::take(&mut self.parse_errors);
+        self.diff_markers.clear();
+        filter.other = self.set_document(other, other_parse_errors, filter.other_focused_row);
+        filter.other_focused_row = focused_row;
+        filter.showing_original = !filter.showing_original;
+        self.filter = Some(filter);
+    }
+
+    fn unfilter_document(&mut self) {
+        match &self.filter {
+            None => self.set_info_message("The document isn't filtered".to_string()),
+            Some(filter) if filter.showing_original => self.filter = None,
+            Some(_) => {
+                self.toggle_filtered_document();
+                self.filter = None;
+            }
+        }
+    }
+
+    fn displayed_diff_markers(&self) -> HashMap<flatjson::Index, diff::DiffMarker> {
+        if self.show_key_order_changes {
+            self.diff_markers.clone()
+        } else {
+            diff::ignoring_key_order(&self.diff_markers, &self.viewer.flatjson)
+        }
+    }
+
+    // Replace the focused string, if it's a JSON Web Token, with an object
+    // containing its decoded header and payload, so that they can be
+    // navigated like the rest of the input.
+    fn decode_focused_jwt(&mut self) {
+        let flatjson = &self.viewer.flatjson;
+        let focused_row = &flatjson[self.viewer.focused_row];
+        if !focused_row.is_string() {
+            self.set_warning_message("Must be focused on a string to use :jwt".to_string());
+            return;
+        }
+
+        let range = focused_row.range.clone();
+        let decoded = match jwt::decode_jwt(&flatjson.1[range.start + 1..range.end - 1]) {
+            Ok(decoded) => decoded,
+            Err(err) => {
+                self.set_warning_message(err);
+                return;
+            }
+        };
+
+        let document = [
+            &flatjson.1[..range.start],
+            &decoded,
+            &flatjson.1[range.end..],
+        ]
+        .concat();
+        let flatjson = match flatjson::parse_top_level_json(document) {
+            Ok(flatjson) => flatjson,
+            Err(err) => {
+                self.set_error_message(format!("Unable to decode JWT: {err}"));
+                return;
+            }
+        };
+
+        let parse_errors = std::mem::take(&mut self.parse_errors);
+        self.replace_document(flatjson, parse_errors);
+        self.set_info_message("Decoded JWT header and payload".to_string());
+    }
+
+    // Temporarily replace the focused string with an array of chunks of it,
+    // so that very long strings can be navigated, searched and copied in
+    // parts.
+    fn chunk_focused_string(&mut self, strategy: &str) {
+        if self.chunked_string.is_some() {
+            self.set_warning_message("A string is already chunked; use :unchunk first".to_string());
+            return;
+        }
+        let string_row = self.viewer.focused_row;
+        let range = self.viewer.flatjson[string_row].range.clone();
+        if !self.viewer.flatjson[string_row].is_string() {
+            self.set_warning_message("Must be focused on a string to use :chunk".to_string());
+            return;
+        }
+
+        let contents = match self.get_content_target_data(ContentTarget::RawString) {
+            Ok(contents) => contents,
+            Err(err) => {
+                self.set_warning_message(err);
+                return;
+            }
+        };
+        let chunks = match ChunkStrategy::parse(strategy, &contents) {
+            Ok(strategy) => strategy.split(&contents),
+            Err(err) => {
+                self.set_warning_message(err);
+                return;
+            }
+        };
+
+        let json = &self.viewer.flatjson.1;
+        let document = [
+            &json[..range.start],
+            &chunks::to_json_array(&chunks),
+            &json[range.end..],
+        ]
+        .concat();
+        let chunked = match flatjson::parse_top_level_json(document) {
+            Ok(flatjson) => flatjson,
+            Err(err) => {
+                self.set_error_message(format!("Unable to chunk string: {err}"));
+                return;
+            }
+        };
+
+        let parse_errors = std::mem::take(&mut self.parse_errors);
+        let original = self.set_document(chunked, parse_errors, string_row);
+        // An empty array has just one row, like the string.
+        let added_rows = if chunks.is_empty() {
+            0
+        } else {
+            chunks.len() + 1
+        };
+        self.chunked_string = Some(ChunkedString {
+            original,
+            string_row,
+            added_rows,
+        });
+
+        let plural = if chunks.len() == 1 { "" } else { "s" };
+        self.set_info_message(format!(
+            "Split string into {} chunk{plural}; use :unchunk to restore it",
+            chunks.len()
+        ));
+    }
+
+    fn unchunk_string(&mut self) {
+        let Some(chunked) = self.chunked_string.take() else {
+            self.set_info_message("No string is chunked".to_string());
+            return;
+        };
+
+        // Focus the string if a chunk was focused, and otherwise stay on
+        // the same row.
+        let focused_row = self.viewer.focused_row;
+        let focused_row = if focused_row < chunked.string_row {
+            focused_row
+        } else if focused_row <= chunked.string_row + chunked.added_rows {
+            chunked.string_row
+        } else {
+            focused_row - chunked.added_rows
+        };
+
+        let parse_errors = std::mem::take(&mut self.parse_errors);
+        self.set_document(chunked.original, parse_errors, focused_row);
+    }
+
+    // The opening row of the focused non-empty array, or of the array
+    // containing the focused element.
+    fn focused_array(&mut self, command: &str) -> Option<flatjson::Index> {
+        let flatjson = &self.viewer.flatjson;
+        let focused_row = &flatjson[self.viewer.focused_row];
+
+        let array_index = if focused_row.is_opening_of_container() && focused_row.is_array() {
+            Some(self.viewer.focused_row)
+        } else if focused_row.is_closing_of_container() && focused_row.is_array() {
+            Some(focused_row.pair_index().unwrap())
+        } else {
+            match focused_row.parent {
+                OptionIndex::Index(parent) if flatjson[parent].is_array() => Some(parent),
+                _ => None,
+            }
+        };
+
+        if array_index.is_none() {
+            self.set_warning_message(format!("Must be focused on an Array to use :{command}"));
+        }
+        array_index
+    }
+
+    // Jump to a random element of the focused array, or of the array
+    // containing the focused element.
+    fn sample_array_element(&mut self) -> Option<Action> {
+        let array_index = self.focused_array("sample")?;
+        let flatjson = &self.viewer.flatjson;
+
+        let num_elements = flatjson.num_children(array_index);
+        let n = self.rng.below(num_elements);
+
+        let element = flatjson.nth_child(array_index, n).unwrap();
+        let n = flatjson[element].index_in_parent;
+        self.set_info_message(format!("Sampled element {n} of {num_elements}"));
+
+        Some(Action::JumpTo {
+            line: element,
+            make_visible: true,
+        })
+    }
+
+    // Show how many matches of a search there are within the focused value,
+    // e.g., how many errors there are under it, without starting a search.
+    fn count_matches(&mut self, pattern: String) {
+        if pattern.is_empty() {
+            self.set_warning_message("Usage: :count PATTERN".to_string());
+            return;
+        }
+
+        let flatjson = &self.viewer.flatjson;
+        let mut row = self.viewer.focused_row;
+        if flatjson[row].is_closing_of_container() {
+            row = flatjson[row].pair_index().unwrap();
+        }
+        let path = flatjson
+            .build_path_to_node(PathType::StatusBar, row)
+            .unwrap_or_default();
+
+        match SearchState::count_matches_in_subtree(pattern, flatjson, row, self.search_defaults) {
+            Ok((num_matches, num_rows)) => {
+                let plural = if num_matches == 1 { "" } else { "es" };
+                let rows_plural = if num_rows == 1 { "" } else { "s" };
+                self.set_info_message(format!(
+                    "{num_matches} match{plural} in {num_rows} row{rows_plural} under input{path}"
+                ));
+            }
+            Err(err) => self.set_error_message(err),
+        }
+    }
+
+    // Reverse the order the elements of the focused array (or the array
+    // containing the focused element) are displayed in. Doing it again
+    // restores the original order.
+    fn reverse_focused_array(&mut self) -> Option<Action> {
+        let array_index = self.focused_array("reverse")?;
+
+        if self.viewer.toggle_reversed(array_index) {
+            self.set_info_message("Reversed array; use :reverse again to undo".to_string());
         } else {
-            false
+            self.set_info_message("Restored original order of array".to_string());
         }
+
+        Some(Action::JumpTo {
+            line: array_index,
+            make_visible: false,
+        })
     }
 
-    fn jump_to_search_match(
-        &mut self,
-        jump_direction: JumpDirection,
-        jumps: usize,
-    ) -> Option<Action> {
-        if !self.search_state.ever_searched {
-            self.set_info_message("Type / to search".to_string());
-            return None;
-        } else if !self.search_state.any_matches() {
-            self.set_warning_message(self.search_state.no_matches_message());
-            return None;
+    // Update everything derived from the pretty printed document after it
+    // was modified in place, e.g., by :delete or :edit, selecting the
+    // given row in the outline.
+    fn refresh_after_document_change(&mut self, row: flatjson::Index) {
+        // Search matches are ranges in the pretty printed document, which
+        // may no longer line up with it.
+        self.search_state = SearchState::empty();
+        self.rebuild_search_index();
+        self.screen_writer
+            .reset_for_new_document(self.displayed_diff_markers());
+        if matches!(&self.outline, Some(outline) if outline.kind == OutlineKind::SearchMatches) {
+            self.outline = None;
+        }
+        if let Some(outline) = &mut self.outline {
+            let focused = outline.focused;
+            *outline = Outline::new(&self.viewer.flatjson);
+            outline.select_entry_containing(row);
+            outline.focused = focused;
+        }
+        if let Some(timeline) = &self.timeline {
+            let field = timeline.field.clone();
+            self.timeline = Timeline::new(&self.viewer.flatjson, Some(&field)).ok();
         }
+    }
 
-        let destination = self.search_state.jump_to_match(
-            self.viewer.focused_row,
+    // Replace the focused primitive value with a new one, which is prompted
+    // for (starting with the current value) unless it's given.
+    fn edit_focused_value(&mut self, token: Option<String>) {
+        if !self.result_buffers.is_empty() {
+            self.set_warning_message("Result buffers can't be edited".to_string());
+            return;
+        }
+        if self.hidden_keys.is_some() || self.chunked_string.is_some() {
+            self.set_warning_message("Use :unhide and :unchunk before editing".to_string());
+            return;
+        }
+        // Changes to a filtered document would be lost, and changes to the
+        // original wouldn't show up in the filtered one.
+        if self.filter.is_some() {
+            self.set_warning_message("Use :unfilter before editing".to_string());
+            return;
+        }
+        let row = self.viewer.focused_row;
+        if !self.viewer.flatjson[row].is_primitive() {
+            self.set_warning_message("Must be focused on a primitive value to edit it".to_string());
+            return;
+        }
+
+        let current = self.viewer.flatjson.1[self.viewer.flatjson[row].range.clone()].to_string();
+        let token = match token {
+            Some(token) => token,
+            None => match self.readline_with_initial(
+                "edit: ",
+                &current,
+                "new value",
+                Completion::Nothing,
+            ) {
+                Some(token) => token,
+                None => return,
+            },
+        };
+        if token.trim() == current {
+            return;
+        }
+
+        if let Err(err) = self.viewer.flatjson.replace_value(row, &token) {
+            self.set_warning_message(err);
+            return;
+        }
+        let revert = Change::Replace {
+            index: row,
+            token: current,
+        };
+        self.undo_history.record(revert, self.diff_markers.clone());
+        self.modified = true;
+        diff::mark_with_ancestors(
             &self.viewer.flatjson,
-            jump_direction,
-            jumps,
+            &mut self.diff_markers,
+            row,
+            diff::DiffMarker::Modified,
         );
+        self.refresh_after_document_change(row);
+        self.set_info_message("Value updated; use u to undo".to_string());
+    }
+
+    // Delete the focused value, along with its key, e.g., to prune a huge
+    // document before writing it out.
+    fn delete_focused_value(&mut self) -> Option<Action> {
+        if !self.result_buffers.is_empty() {
+            self.set_warning_message("Result buffers can't be edited".to_string());
+            return None;
+        }
+        if self.hidden_keys.is_some() || self.chunked_string.is_some() {
+            self.set_warning_message("Use :unhide and :unchunk before deleting".to_string());
+            return None;
+        }
+        if self.filter.is_some() {
+            self.set_warning_message("Use :unfilter before deleting".to_string());
+            return None;
+        }
+        let flatjson = &self.viewer.flatjson;
+        let mut row = self.viewer.focused_row;
+        if flatjson[row].is_closing_of_container() {
+            row = flatjson[row].pair_index().unwrap();
+        }
+        let parent = flatjson[row].parent;
+        let path = flatjson
+            .build_path_to_node(PathType::StatusBar, row)
+            .unwrap_or_default();
+
+        let (remap, deleted) = match self.viewer.delete_value(row) {
+            Ok(deleted) => deleted,
+            Err(err) => {
+                self.set_warning_message(err);
+                return None;
+            }
+        };
+        self.undo_history
+            .record(Change::Restore(deleted), self.diff_markers.clone());
+        self.modified = true;
+        self.remap_pinned_rows(remap);
+        self.diff_markers = self
+            .diff_markers
+            .iter()
+            .filter_map(|(index, marker)| Some((remap(*index)?, *marker)))
+            .collect();
+        if let OptionIndex::Index(parent) = parent {
+            diff::mark_with_ancestors(
+                &self.viewer.flatjson,
+                &mut self.diff_markers,
+                parent,
+                diff::DiffMarker::Modified,
+            );
+        }
+        let focused_row = self.viewer.focused_row;
+        self.refresh_after_document_change(focused_row);
+        self.set_info_message(format!("Deleted input{path}; use u to undo"));
+
         Some(Action::JumpTo {
-            line: destination,
+            line: focused_row,
             make_visible: false,
         })
     }
 
-    fn parse_command(command: &str) -> Command {
-        match command {
-            "h" | "he" | "hel" | "help" => Command::Help,
-            "q" | "qu" | "qui" | "quit" | "quit()" | "exit" | "exit()" => Command::Quit,
-            "set number" => Command::SetShowLineNumber(Some(true)),
-            "set number!" => Command::SetShowLineNumber(None),
-            "set nonumber" => Command::SetShowLineNumber(Some(false)),
-            "set relativenumber" => Command::SetShowRelativeLineNumber(Some(true)),
-            "set relativenumber!" => Command::SetShowRelativeLineNumber(None),
-            "set norelativenumber" => Command::SetShowRelativeLineNumber(Some(false)),
-            _ => Command::Unknown,
+    // Revert the last change made to the document, or, when redoing, the
+    // last change that was undone.
+    fn undo_change(&mut self, redo: bool) -> Option<Action> {
+        let verb = if redo { "redo" } else { "undo" };
+        if !self.result_buffers.is_empty() {
+            self.set_warning_message(format!("Close result buffers before using {verb}"));
+            return None;
+        }
+        if self.hidden_keys.is_some() || self.chunked_string.is_some() {
+            self.set_warning_message(format!("Use :unhide and :unchunk before using {verb}"));
+            return None;
+        }
+        if self.filter.is_some() {
+            self.set_warning_message(format!("Use :unfilter before using {verb}"));
+            return None;
+        }
+
+        let entry = if redo {
+            self.undo_history.pop_redo()
+        } else {
+            self.undo_history.pop_undo()
+        };
+        let Some((change, diff_markers)) = entry else {
+            let newest_or_oldest = if redo { "newest" } else { "oldest" };
+            self.set_info_message(format!("Already at {newest_or_oldest} change"));
+            return None;
+        };
+        let command = change.command();
+
+        // Changes are reverted in the opposite order they were made in, so
+        // reverting them can't fail.
+        let (revert, row) = match change {
+            Change::Replace { index, token } => {
+                let flatjson = &mut self.viewer.flatjson;
+                let current = flatjson.1[flatjson[index].range.clone()].to_string();
+                flatjson.replace_value(index, &token).unwrap();
+                let revert = Change::Replace {
+                    index,
+                    token: current,
+                };
+                (revert, index)
+            }
+            Change::Delete { index } => {
+                let (remap, deleted) = self.viewer.delete_value(index).unwrap();
+                self.remap_pinned_rows(remap);
+                (Change::Restore(deleted), self.viewer.focused_row)
+            }
+            Change::Restore(deleted) => {
+                let index = deleted.index;
+                let remap = self.viewer.restore_value(deleted);
+                self.remap_pinned_rows(|pinned| Some(remap(pinned)));
+                (Change::Delete { index }, index)
+            }
+        };
+
+        self.modified = true;
+
+        // The diff markers from before the change was reverted are kept,
+        // rather than being recomputed.
+        let diff_markers = std::mem::replace(&mut self.diff_markers, diff_markers);
+        if redo {
+            self.undo_history.push_undo(revert, diff_markers);
+            self.set_info_message(format!("Redid :{command}"));
+        } else {
+            self.undo_history.push_redo(revert, diff_markers);
+            self.set_info_message(format!("Undid :{command}; use ctrl-r to redo"));
+        }
+        self.refresh_after_document_change(row);
+
+        Some(Action::JumpTo {
+            line: row,
+            make_visible: true,
+        })
+    }
+
+    // Update pinned rows after rows of the document moved, unpinning
+    // those that were removed.
+    fn remap_pinned_rows(&mut self, remap: impl Fn(flatjson::Index) -> Option<flatjson::Index>) {
+        self.pinned_rows = self
+            .pinned_rows
+            .iter()
+            .filter_map(|pinned| remap(*pinned))
+            .collect();
+    }
+
+    // Pin the focused row, so that its path and value are shown above the
+    // status bar, or unpin it if it's already pinned.
+    fn toggle_pinned_row(&mut self) -> Option<Action> {
+        let focused_row = &self.viewer.flatjson[self.viewer.focused_row];
+        // Containers are always pinned using their opening row.
+        let row = if focused_row.is_closing_of_container() {
+            focused_row.pair_index().unwrap()
+        } else {
+            self.viewer.focused_row
+        };
+
+        if let Some(position) = self.pinned_rows.iter().position(|pinned| *pinned == row) {
+            self.pinned_rows.remove(position);
+            self.set_info_message("Unpinned focused row".to_string());
+        } else {
+            self.pinned_rows.push(row);
+            if self.pinned_rows.len() > MAX_PINNED_ROWS {
+                self.pinned_rows.remove(0);
+                self.set_info_message(format!(
+                    "Pinned focused row; only {MAX_PINNED_ROWS} rows can be pinned, so the oldest was unpinned"
+                ));
+            } else {
+                self.set_info_message("Pinned focused row; use :pin again to unpin it".to_string());
+            }
+        }
+
+        Some(Action::ResizeViewerDimensions(self.viewer_dimensions()))
+    }
+
+    fn unpin_all_rows(&mut self) -> Option<Action> {
+        self.pinned_rows.clear();
+        Some(Action::ResizeViewerDimensions(self.viewer_dimensions()))
+    }
+
+    // The opening row of the focused non-empty container, or of the
+    // container containing the focused element.
+    fn focused_container(&mut self, command: &str) -> Option<flatjson::Index> {
+        let focused_row = &self.viewer.flatjson[self.viewer.focused_row];
+
+        let container_index = if focused_row.is_opening_of_container() {
+            Some(self.viewer.focused_row)
+        } else if focused_row.is_closing_of_container() {
+            Some(focused_row.pair_index().unwrap())
+        } else {
+            match focused_row.parent {
+                OptionIndex::Index(parent) => Some(parent),
+                OptionIndex::Nil => None,
+            }
+        };
+
+        if container_index.is_none() {
+            self.set_warning_message(format!(
+                "Must be focused on an Object or Array to use :{command}"
+            ));
+        }
+        container_index
+    }
+
+    // Jump to the child of the focused container with the most descendants.
+    fn jump_to_largest_child(&mut self) -> Option<Action> {
+        let container_index = self.focused_container("largest")?;
+        let flatjson = &self.viewer.flatjson;
+
+        let child = flatjson.largest_child(container_index).unwrap();
+        let num_descendants = flatjson.num_descendants(child);
+        self.set_info_message(format!(
+            "Largest child contains {num_descendants} nested value{}",
+            if num_descendants == 1 { "" } else { "s" }
+        ));
+
+        Some(Action::JumpTo {
+            line: child,
+            make_visible: true,
+        })
+    }
+
+    // Jump to the longest string anywhere inside the focused container.
+    fn jump_to_longest_string(&mut self) -> Option<Action> {
+        let container_index = self.focused_container("longest")?;
+        let flatjson = &self.viewer.flatjson;
+
+        let OptionIndex::Index(string) = flatjson.longest_string(container_index) else {
+            self.set_info_message("No strings in focused container".to_string());
+            return None;
+        };
+        // Don't count the quotes.
+        let len = flatjson[string].range.len() - 2;
+        self.set_info_message(format!("Longest string is {len} bytes long"));
+
+        Some(Action::JumpTo {
+            line: string,
+            make_visible: true,
+        })
+    }
+
+    // Jump to the next value of the given type (e.g., "int" or "object")
+    // after the focused row.
+    fn jump_to_next_of_type(&mut self, type_name: &str) -> Option<Action> {
+        let Some(value_type) = ValueType::parse(type_name) else {
+            self.set_warning_message(format!(
+                "Unknown type \"{type_name}\"; expected null, bool, number, int, float, \
+                 string, array or object"
+            ));
+            return None;
+        };
+
+        match self
+            .viewer
+            .flatjson
+            .next_of_type(value_type, self.viewer.focused_row)
+        {
+            OptionIndex::Index(line) => Some(Action::JumpTo {
+                line,
+                make_visible: true,
+            }),
+            OptionIndex::Nil => {
+                self.set_info_message(format!("No {type_name} values found"));
+                None
+            }
         }
     }
 
     fn show_help(&mut self) {
+        self.show_in_pager(HELP, "help documentation");
+    }
+
+    fn show_parse_errors(&mut self) {
+        if self.parse_errors.is_empty() {
+            self.set_info_message("No lines failed to parse".to_string());
+            return;
+        }
+
+        let mut buffer = ResultBuffer::new("[parse errors]".to_string());
+        for line_error in self.parse_errors.iter() {
+            buffer.push_text(
+                format!("Line {}", line_error.line_number),
+                &line_error.error,
+                None,
+            );
+        }
+        self.open_result_buffer(buffer);
+    }
+
+    fn show_in_pager(&mut self, contents: &str, description: &str) {
+        // There's no one to page through the contents when running headlessly.
+        if !self.screen_writer.stdout.is_interactive() {
+            return;
+        }
+        let what = format!("Showing {description} in a pager");
+        if let Err(err) = Capabilities::check(self.capabilities.run_programs, &what) {
+            self.set_warning_message(err);
+            return;
+        }
+
         let _ = write!(self.screen_writer.stdout, "{ToMainScreen}");
         let child = std::process::Command::new("less")
             .arg("-r")
@@ -749,17 +4243,18 @@ impl App {
         match child {
             Ok(mut child) => {
                 if let Some(ref mut stdin) = child.stdin {
-                    let _ = stdin.write(HELP.as_bytes());
+                    let _ = stdin.write(contents.as_bytes());
                     let _ = stdin.flush();
                 }
                 let _ = child.wait();
             }
             Err(err) => {
-                self.set_error_message(format!("Error piping help documentation to less: {err}"));
+                self.set_error_message(format!("Error piping {description} to less: {err}"));
             }
         }
 
         let _ = write!(self.screen_writer.stdout, "{ToAlternateScreen}");
+        self.screen_writer.invalidate_last_frame();
     }
 
     fn get_content_target_data(&self, content_target: ContentTarget) -> Result<String, String> {
@@ -777,7 +4272,19 @@ impl App {
                 let range = focused_row.range.clone();
                 json[range].to_string()
             }
-            ContentTarget::String => {
+            ContentTarget::YamlValue => self
+                .viewer
+                .flatjson
+                .pretty_printed_value_as_yaml(focused_row_index)?,
+            ContentTarget::JsonValue => self
+                .viewer
+                .flatjson
+                .pretty_printed_value_as_json(focused_row_index)?,
+            ContentTarget::TomlValue => self
+                .viewer
+                .flatjson
+                .pretty_printed_value_as_toml(focused_row_index)?,
+            ct @ (ContentTarget::String | ContentTarget::RawString) => {
                 if !focused_row.is_string() {
                     return Err("Current value is not a string".to_string());
                 }
@@ -786,7 +4293,12 @@ impl App {
                 let quoteless_range = (range.start + 1)..(range.end - 1);
                 let string_value = &json[quoteless_range];
 
-                match unescape_json_string(string_value) {
+                let control_characters = match ct {
+                    ContentTarget::RawString => ControlCharacters::Raw,
+                    _ => ControlCharacters::Escaped,
+                };
+
+                match unescape_json_string(string_value, control_characters) {
                     Ok(unescaped) => unescaped,
                     Err(err) => {
                         return Err(format!("{err}"));
@@ -819,14 +4331,78 @@ impl App {
                     _ => unreachable!(),
                 };
 
-                match self
+                self.viewer
+                    .flatjson
+                    .build_path_to_node(path_type, focused_row_index)?
+            }
+            ContentTarget::JqCommand => {
+                // jq can't read YAML, but yq takes the same paths.
+                let program = match self.data_format {
+                    DataFormat::Yaml => "yq",
+                    DataFormat::Hjson => {
+                        return Err("jq can't read Hjson input; use yq to copy the path".to_string())
+                    }
+                    _ => "jq",
+                };
+                let path = self
                     .viewer
                     .flatjson
-                    .build_path_to_node(path_type, focused_row_index)
-                {
-                    Ok(path) => path,
-                    Err(err) => return Err(err),
+                    .build_path_to_node(flatjson::PathType::Jq, focused_row_index)?;
+
+                match &self.input_path {
+                    Some(input_path) => format!(
+                        "{program} {} {}",
+                        shell_quote(&path),
+                        shell_quote_if_necessary(&input_path.to_string_lossy())
+                    ),
+                    None => format!("{program} {}", shell_quote(&path)),
+                }
+            }
+            ct @ (ContentTarget::CaptureGroup | ContentTarget::AllCaptureGroups) => {
+                if !self.search_state.has_capture_groups() {
+                    return Err("Last search pattern has no capture groups".to_string());
+                }
+
+                if matches!(ct, ContentTarget::CaptureGroup) {
+                    let range = focused_row.range_represented_by_row();
+                    match self.search_state.focused_match_capture(&range) {
+                        Some(capture) => json[capture].to_string(),
+                        None => return Err("No captured search match on focused line".to_string()),
+                    }
+                } else {
+                    let captures: Vec<&str> = self
+                        .search_state
+                        .all_match_captures()
+                        .map(|capture| &json[capture.clone()])
+                        .collect();
+                    if captures.is_empty() {
+                        return Err("No captured search matches".to_string());
+                    }
+                    captures.join("\n")
+                }
+            }
+            ContentTarget::Checksum => {
+                let canonical = self.viewer.flatjson.canonical_json(focused_row_index)?;
+                sha256::hex_digest(canonical.as_bytes())
+            }
+            ContentTarget::SelectedValues => {
+                if self.viewer.selected_rows.is_empty() {
+                    return Err("No rows are selected; use 'x' to select rows".to_string());
                 }
+
+                self.viewer
+                    .flatjson
+                    .pretty_printed_array(self.viewer.selected_rows.iter().copied())
+                    .unwrap()
+            }
+            ContentTarget::RelativePath => {
+                let Some(mark) = self.viewer.mark else {
+                    return Err("No node is marked; use 'M' to mark one".to_string());
+                };
+
+                self.viewer
+                    .flatjson
+                    .build_relative_path(mark, focused_row_index)?
             }
         };
 
@@ -834,8 +4410,39 @@ impl App {
     }
 
     fn copy_content(&mut self, content_target: ContentTarget) {
-        match self.get_content_target_data(content_target) {
+        if !self.confirm_large_copy(content_target) {
+            return;
+        }
+
+        let focused_row = &self.viewer.flatjson[self.viewer.focused_row];
+        let content = match content_target {
+            ContentTarget::PrettyPrintedValue
+                if focused_row.is_container()
+                    && focused_row.range.len() > LARGE_CLIPBOARD_PAYLOAD_BYTES =>
+            {
+                match self.pretty_print_focused_value_with_progress() {
+                    Some(content) => Ok(content),
+                    None => {
+                        self.set_info_message("Copy cancelled".to_string());
+                        return;
+                    }
+                }
+            }
+            _ => self.get_content_target_data(content_target),
+        };
+
+        match content {
             Ok(content) => {
+                if content.len() > LARGE_CLIPBOARD_PAYLOAD_BYTES {
+                    // The clipboard provider may block for a while, so let the
+                    // user know what's happening before it does.
+                    self.set_info_message(format!(
+                        "Copying {} to clipboard...",
+                        format_size(content.len())
+                    ));
+                    self.draw_status_bar();
+                }
+
                 // Checked when the user first hits 'y'.
                 let clipboard = self.clipboard_context.as_mut().unwrap();
 
@@ -846,11 +4453,24 @@ impl App {
                         "pretty-printed value"
                     }
                     ContentTarget::PrettyPrintedValue | ContentTarget::OneLineValue => "value",
+                    ContentTarget::YamlValue => "YAML value",
+                    ContentTarget::JsonValue => "JSON value",
+                    ContentTarget::TomlValue => "TOML value",
                     ContentTarget::String => "string contents",
+                    ContentTarget::RawString => "raw string contents",
                     ContentTarget::Key => "key",
                     ContentTarget::DotPath => "path",
                     ContentTarget::BracketPath => "bracketed path",
                     ContentTarget::QueryPath => "query path",
+                    ContentTarget::JqCommand if self.data_format == DataFormat::Yaml => {
+                        "yq command"
+                    }
+                    ContentTarget::JqCommand => "jq command",
+                    ContentTarget::CaptureGroup => "capture group",
+                    ContentTarget::AllCaptureGroups => "capture groups",
+                    ContentTarget::SelectedValues => "selected values",
+                    ContentTarget::Checksum => "checksum",
+                    ContentTarget::RelativePath => "relative path",
                 };
 
                 if let Err(err) = clipboard.set_contents(content) {
@@ -865,9 +4485,112 @@ impl App {
         }
     }
 
+    // Pretty print a huge focused value, showing how far along we are in
+    // the status bar, and stopping if the user presses any key, in which
+    // case this returns None.
+    fn pretty_print_focused_value_with_progress(&mut self) -> Option<String> {
+        let filename = self.status_bar_filename();
+        let screen_writer = &mut self.screen_writer;
+        let viewer = &self.viewer;
+        let input_buffer = &self.input_buffer;
+        let search_state = &self.search_state;
+
+        viewer
+            .flatjson
+            .pretty_printed_value_with_progress(viewer.focused_row, |printed, total| {
+                let message = Some((
+                    format!(
+                        "Copying: {}%; press any key to cancel",
+                        printed * 100 / total
+                    ),
+                    MessageSeverity::Info,
+                ));
+                screen_writer.print_status_bar(
+                    viewer,
+                    input_buffer,
+                    &filename,
+                    search_state,
+                    &message,
+                );
+                !input::key_pressed()
+            })
+            .unwrap()
+    }
+
+    // Before copying a huge value, which may hang while the clipboard
+    // provider processes it, ask the user to confirm. Returns whether
+    // we should go ahead with the copy.
+    fn confirm_large_copy(&mut self, content_target: ContentTarget) -> bool {
+        let is_value = matches!(
+            content_target,
+            ContentTarget::PrettyPrintedValue
+                | ContentTarget::OneLineValue
+                | ContentTarget::YamlValue
+                | ContentTarget::JsonValue
+                | ContentTarget::TomlValue
+                | ContentTarget::String
+                | ContentTarget::RawString
+        );
+        if !is_value {
+            return true;
+        }
+
+        // The single-line representation is a cheap lower bound on the size
+        // of what will actually be copied.
+        let size = self.viewer.flatjson[self.viewer.focused_row].range.len();
+        if size <= LARGE_CLIPBOARD_PAYLOAD_BYTES {
+            return true;
+        }
+
+        let prompt = format!(
+            "Value is at least {}; copy to clipboard anyway? [y/N] ",
+            format_size(size)
+        );
+        let confirmed = matches!(
+            self.readline(&prompt, "confirmation", Completion::Nothing)
+                .as_deref(),
+            Some("y" | "Y" | "yes")
+        );
+        if !confirmed {
+            self.set_info_message("Copy cancelled".to_string());
+        }
+        confirmed
+    }
+
+    // Show the current page of printed content, followed by a prompt on the
+    // last line of the screen. Rows are separated by "\r\n", since this is
+    // also done in raw mode.
+    fn draw_page(&mut self) {
+        let pager = self.pager.as_ref().unwrap();
+        let _ = write!(
+            self.screen_writer.stdout,
+            "{}{}{}{}{}",
+            termion::clear::All,
+            termion::cursor::Goto(1, 1),
+            pager.page().join("\r\n"),
+            termion::cursor::Goto(1, self.screen_writer.dimensions.height),
+            pager.prompt(),
+        );
+        let _ = self.screen_writer.stdout.flush();
+    }
+
+    // Go back to the viewer after content was printed with p.
+    fn return_from_printed_content(&mut self) {
+        let _ = write!(self.screen_writer.stdout, "{ToAlternateScreen}");
+        let _ = write!(self.screen_writer.stdout, "{ENABLE_MOUSE_BUTTON_TRACKING}");
+        self.pager = None;
+        self.input_state = InputState::Default;
+        self.screen_writer.invalidate_last_frame();
+        self.draw_screen();
+        self.message = None;
+    }
+
     fn print_content(&mut self, content_target: ContentTarget) -> bool {
         match self.get_content_target_data(content_target) {
-            Ok(content) => {
+            Ok(mut content) => {
+                if !self.capabilities.raw_terminal_output {
+                    content = capabilities::escape_control_characters(&content);
+                }
                 // Exit raw mode so that the terminal interprets newlines as usual.
                 let _ = self.screen_writer.stdout.suspend_raw_mode();
                 // Go to the main screen so that the text will persist after exiting.
@@ -875,13 +4598,20 @@ impl App {
                 // Disable mouse button tracking so that the user can use their mouse
                 // to highlight the text.
                 let _ = write!(self.screen_writer.stdout, "{DISABLE_MOUSE_BUTTON_TRACKING}");
-                let _ = write!(
-                    self.screen_writer.stdout,
-                    "{}{}{}\n\nPress any key to continue.",
-                    termion::clear::All,
-                    termion::cursor::Goto(1, 1),
-                    content
-                );
+                // Content that doesn't fit on the screen is shown a page at a
+                // time, so that the start of it isn't scrolled out of view.
+                self.pager = Pager::new(&content, self.screen_writer.dimensions);
+                if self.pager.is_some() {
+                    self.draw_page();
+                } else {
+                    let _ = write!(
+                        self.screen_writer.stdout,
+                        "{}{}{}\n\nPress any key to continue.",
+                        termion::clear::All,
+                        termion::cursor::Goto(1, 1),
+                        content
+                    );
+                }
                 let _ = self.screen_writer.stdout.flush();
                 // Go back to raw mode so we can immediately get key presses.
                 let _ = self.screen_writer.stdout.activate_raw_mode();
@@ -894,3 +4624,63 @@ impl App {
         }
     }
 }
+
+// Quote a string so that it is interpreted literally by a POSIX shell.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+// Like shell_quote, but leaves strings that don't need quoting as is.
+fn shell_quote_if_necessary(s: &str) -> String {
+    let is_safe = |ch: char| ch.is_ascii_alphanumeric() || "_-./:@%+=,".contains(ch);
+    if !s.is_empty() && s.chars().all(is_safe) {
+        s.to_string()
+    } else {
+        shell_quote(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::headless::screen_after;
+
+    #[test]
+    fn test_pin() {
+        const JSON: &str = r#"{"a": 1, "b": [10, 20, 30, 40, 50, 60, 70, 80], "c": "x"}"#;
+        let screen = |commands: &str| {
+            let commands = format!(":set nonumber | :set nogauge | {commands}");
+            screen_after("40x8", JSON, &commands)
+        };
+
+        // Pinned rows stay above the status bar while scrolling past them.
+        let expected = [
+            "      [4]: 50",
+            "      [5]: 60",
+            "    ▶ [6]: 70",
+            "      [7]: 80",
+            "    c: \"x\"",
+            ".a: 1",
+            "input.b[6/8]                   test.json",
+            ":",
+        ];
+        assert_eq!(expected.join("\n"), screen("j | :pin | j | l | 6j"));
+
+        // Pinning a pinned row unpins it.
+        let expected = [
+            "▽ (3) {a: 1, b: […], c: \"x\"}",
+            "    a: 1",
+            "  ▼ b: (8) [10, 20, 30, 40, 50, 60, …]",
+            "      [0]: 10",
+            "      [1]: 20",
+            ".a: 1",
+            "input.b                        test.json",
+            "Unpinned focused row",
+        ];
+        assert_eq!(expected.join("\n"), screen("j | :pin | j | :pin | :pin"));
+
+        // :unpin unpins everything, giving the rows back to the viewer.
+        let screen = screen("j | :pin | j | :pin | :unpin");
+        assert!(!screen.contains(".a: 1"), "{:?}", screen);
+        assert!(screen.contains("      [2]: 30\n"), "{:?}", screen);
+    }
+}
@@ -0,0 +1,92 @@
+use crate::options::Opt;
+
+// What jless may do besides reading its input and drawing the viewer.
+// Everything is allowed by default; `--secure`, for inspecting untrusted
+// documents in restricted environments, turns all of it off.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Capabilities {
+    pub clipboard: bool,
+    // Running other programs, e.g., `less` to show :help.
+    pub run_programs: bool,
+    // Accepting commands over a socket via `--control`.
+    pub control_socket: bool,
+    // Writing files, e.g., for :savepos, or spilling huge documents to a
+    // temporary file.
+    pub write_files: bool,
+    // Writing values to the terminal exactly as they are, control
+    // characters included.
+    pub raw_terminal_output: bool,
+}
+
+impl Capabilities {
+    pub fn from_options(opt: &Opt) -> Capabilities {
+        let allowed = !opt.secure;
+        Capabilities {
+            clipboard: allowed,
+            run_programs: allowed,
+            control_socket: allowed,
+            write_files: allowed,
+            raw_terminal_output: allowed,
+        }
+    }
+
+    // Returns an error describing what's disabled if it isn't allowed.
+    pub fn check(allowed: bool, what: &str) -> Result<(), String> {
+        if allowed {
+            Ok(())
+        } else {
+            Err(format!("{what} is disabled by --secure"))
+        }
+    }
+}
+
+// Replace control characters (other than newlines and tabs), which could
+// be used to manipulate the terminal, with JSON-style escapes.
+pub fn escape_control_characters(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for ch in s.chars() {
+        if ch.is_control() && ch != '\n' && ch != '\t' {
+            escaped.push_str(&format!("\\u{:04x}", ch as u32));
+        } else {
+            escaped.push(ch);
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    #[test]
+    fn test_capabilities() {
+        let capabilities = Capabilities::from_options(&Opt::parse_from(["jless"]));
+        assert!(capabilities.clipboard && capabilities.write_files);
+
+        let capabilities = Capabilities::from_options(&Opt::parse_from(["jless", "--secure"]));
+        assert_eq!(
+            Capabilities {
+                clipboard: false,
+                run_programs: false,
+                control_socket: false,
+                write_files: false,
+                raw_terminal_output: false,
+            },
+            capabilities
+        );
+        assert_eq!(
+            Err("Saving positions is disabled by --secure".to_string()),
+            Capabilities::check(capabilities.write_files, "Saving positions")
+        );
+    }
+
+    #[test]
+    fn test_escape_control_characters() {
+        assert_eq!(
+            "a\\u001b[31mb\\u0007\n\tc é",
+            escape_control_characters("a\x1b[31mb\x07\n\tc é")
+        );
+    }
+}
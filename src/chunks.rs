@@ -0,0 +1,104 @@
+use crate::jsonstringunescaper::escape_json_string;
+
+// How many characters are in each chunk by default, when a string
+// doesn't contain any newlines.
+const DEFAULT_CHUNK_CHARS: usize = 1000;
+
+// How `:chunk` splits a long string into an array of smaller strings, so
+// that its parts can be navigated, searched and copied separately.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ChunkStrategy {
+    // One chunk per line, including the newline.
+    Lines,
+    // Chunks of (at most) this many characters.
+    Chars(usize),
+}
+
+impl ChunkStrategy {
+    // Parse the argument to `:chunk`: `lines`, a number of characters, or
+    // nothing, which splits strings containing newlines into lines, and
+    // other strings into chunks of DEFAULT_CHUNK_CHARS.
+    pub fn parse(arg: &str, s: &str) -> Result<ChunkStrategy, String> {
+        match arg.trim() {
+            "" if s.contains('\n') => Ok(ChunkStrategy::Lines),
+            "" => Ok(ChunkStrategy::Chars(DEFAULT_CHUNK_CHARS)),
+            "lines" => Ok(ChunkStrategy::Lines),
+            n => match n.parse() {
+                Ok(0) | Err(_) => Err(format!(
+                    "Invalid chunk size: {n}; expected a number of characters or \"lines\""
+                )),
+                Ok(n) => Ok(ChunkStrategy::Chars(n)),
+            },
+        }
+    }
+
+    // Split a string into chunks, which join back together into the
+    // original string.
+    pub fn split<'a>(&self, s: &'a str) -> Vec<&'a str> {
+        match self {
+            ChunkStrategy::Lines => s.split_inclusive('\n').collect(),
+            ChunkStrategy::Chars(n) => {
+                let mut chunks = vec![];
+                let mut rest = s;
+                while !rest.is_empty() {
+                    let end = rest.char_indices().nth(*n).map_or(rest.len(), |(i, _)| i);
+                    let (chunk, remaining) = rest.split_at(end);
+                    chunks.push(chunk);
+                    rest = remaining;
+                }
+                chunks
+            }
+        }
+    }
+}
+
+// The JSON array of chunks that a string is replaced with.
+pub fn to_json_array(chunks: &[&str]) -> String {
+    let escaped: Vec<String> = chunks
+        .iter()
+        .map(|chunk| format!("\"{}\"", escape_json_string(chunk)))
+        .collect();
+    format!("[{}]", escaped.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_strategy() {
+        assert_eq!(Ok(ChunkStrategy::Lines), ChunkStrategy::parse("", "a\nb"));
+        assert_eq!(
+            Ok(ChunkStrategy::Chars(DEFAULT_CHUNK_CHARS)),
+            ChunkStrategy::parse("", "ab")
+        );
+        assert_eq!(
+            Ok(ChunkStrategy::Lines),
+            ChunkStrategy::parse("lines", "ab")
+        );
+        assert_eq!(
+            Ok(ChunkStrategy::Chars(80)),
+            ChunkStrategy::parse(" 80", "a\nb")
+        );
+        assert!(ChunkStrategy::parse("0", "ab").is_err());
+        assert!(ChunkStrategy::parse("words", "ab").is_err());
+    }
+
+    #[test]
+    fn test_split() {
+        assert_eq!(
+            vec!["ab", "cé", "d"],
+            ChunkStrategy::Chars(2).split("abcéd")
+        );
+        assert_eq!(
+            vec!["one\n", "\n", "two"],
+            ChunkStrategy::Lines.split("one\n\ntwo")
+        );
+        assert!(ChunkStrategy::Chars(2).split("").is_empty());
+
+        assert_eq!(
+            r#"["one\n","\"two\""]"#,
+            to_json_array(&["one\n", "\"two\""])
+        );
+    }
+}
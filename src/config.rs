@@ -0,0 +1,517 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use clap::{CommandFactory, Parser};
+
+use crate::control::{ControlCommand, ControlSender};
+use crate::options::Opt;
+
+// Defaults for command line options can be set in a config file, e.g.,
+// ~/.config/jless/config.toml:
+//
+//     mode = "line"
+//     scrolloff = 5
+//     line-numbers = false
+//     indent-guide-colors = ["red", "yellow", "light-blue"]
+//     map = ["J=<C-d>", "K=<C-u>"]
+//
+// Keys are the long names of options, and the values get turned into the
+// equivalent command line arguments, which are inserted before the actual
+// ones, so that the latter take precedence. YAML (config.yaml) works too.
+//
+// Named jq filters, run with `:filter @NAME`, are defined in a section:
+//
+//     [filters]
+//     images = ".items[].spec.containers[].image"
+//
+// or with dotted keys, e.g., `filters.images = "..."`. Aliases for commands
+// are defined the same way:
+//
+//     [aliases]
+//     tl = "timeline"
+//
+// The config file is watched while jless is running, and display settings
+// and key mappings are re-applied when it changes.
+
+const CONFIG_FILE_NAMES: [&str; 3] = ["config.toml", "config.yaml", "config.yml"];
+
+// Sections of the config file, where each entry defines something by name,
+// and the option they become, e.g., `filters.images = ".items[].image"`
+// becomes `--define-filter=images=.items[].image`.
+const SECTIONS: [(&str, &str); 2] = [("filters", "define-filter"), ("aliases", "alias")];
+
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, PartialEq, Eq)]
+enum ConfigValue {
+    Bool(bool),
+    // Strings, numbers, and lists of them, which are joined with commas.
+    Text(String),
+}
+
+// Return the command line arguments, starting with the name of the
+// program, with the options from the config file inserted before the
+// ones that were actually given.
+pub fn args_with_config(config_path: Option<&Path>) -> Result<Vec<OsString>, String> {
+    let mut args: Vec<OsString> = env::args_os().collect();
+    if let Some(path) = config_path {
+        let config_args = read_config_args(path)?;
+        args.splice(1..1, config_args.into_iter().map(OsString::from));
+    }
+    Ok(args)
+}
+
+// Re-read the config file, and parse it along with the original command
+// line arguments. Errors are kept to a single line so that they can be
+// shown in the status bar.
+pub fn reload_options(config_path: &Path) -> Result<Opt, String> {
+    let args = args_with_config(Some(config_path))
+        .map_err(|err| err.lines().collect::<Vec<&str>>().join(" "))?;
+    Opt::try_parse_from(args).map_err(|err| {
+        let message = err.to_string();
+        let first_line = message.lines().next().unwrap_or_default();
+        first_line
+            .strip_prefix("error: ")
+            .unwrap_or(first_line)
+            .to_string()
+    })
+}
+
+// Poll the modification time of the config file, and tell the main loop
+// to reload it whenever it changes. This also picks up the file being
+// created or deleted.
+pub fn watch(config_path: PathBuf, mut sender: ControlSender) {
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+
+    thread::spawn(move || {
+        let mut last_modified: Option<SystemTime> = modified(&config_path);
+        loop {
+            thread::sleep(WATCH_INTERVAL);
+            let current = modified(&config_path);
+            if current != last_modified {
+                last_modified = current;
+                if !sender.send(ControlCommand::ReloadConfig) {
+                    return;
+                }
+            }
+        }
+    });
+}
+
+// $JLESS_CONFIG if it's set (where an empty value disables loading a
+// config file), or the first config file that exists in
+// $XDG_CONFIG_HOME/jless or ~/.config/jless.
+pub fn config_file_path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("JLESS_CONFIG") {
+        return if path.is_empty() {
+            None
+        } else {
+            Some(PathBuf::from(path))
+        };
+    }
+
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    let dir = config_home.join("jless");
+    CONFIG_FILE_NAMES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+fn read_config_args(path: &Path) -> Result<Vec<String>, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|err| format!("Unable to read config file {}: {err}", path.display()))?;
+    let is_yaml = matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some("yaml" | "yml")
+    );
+    let entries = if is_yaml {
+        parse_yaml(&contents)
+    } else {
+        parse_toml(&contents)
+    };
+    let args = entries
+        .and_then(config_args)
+        .map_err(|err| format!("Invalid config file {}: {err}", path.display()))?;
+
+    // Check the options on their own, so that errors mention the config
+    // file, rather than looking like they came from the command line.
+    let mut check_args = vec!["jless".to_string()];
+    check_args.extend(args.iter().cloned());
+    if let Err(err) = Opt::try_parse_from(check_args) {
+        return Err(format!("Invalid config file {}:\n{err}", path.display()));
+    }
+
+    Ok(args)
+}
+
+// Turn the entries of a config file into command line arguments. Boolean
+// options can be set to true or false, e.g., `line-numbers = false` becomes
+// `--no-line-numbers`, and setting a flag to false just leaves it out.
+fn config_args(entries: Vec<(String, ConfigValue)>) -> Result<Vec<String>, String> {
+    let command = Opt::command();
+    let has_option = |name: &str| {
+        command
+            .get_arguments()
+            .any(|arg| arg.get_long() == Some(name))
+    };
+
+    let mut args = vec![];
+    for (key, value) in entries {
+        if let Some((section, name)) = key.split_once('.') {
+            let Some((_, option)) = SECTIONS.iter().find(|(s, _)| *s == section) else {
+                return Err(format!("Unknown section: {section}"));
+            };
+            let ConfigValue::Text(text) = value else {
+                return Err(format!("{key}: Expected a string"));
+            };
+            args.push(format!("--{option}={name}={text}"));
+            continue;
+        }
+
+        let negated = format!("no-{key}");
+        if !has_option(&key) && !has_option(&negated) {
+            return Err(format!("Unknown option: {key}"));
+        }
+
+        match value {
+            ConfigValue::Bool(true) if has_option(&key) => args.push(format!("--{key}")),
+            ConfigValue::Bool(false) if has_option(&negated) => args.push(format!("--{negated}")),
+            // E.g., `previews = true` when only --no-previews exists.
+            ConfigValue::Bool(_) => {}
+            ConfigValue::Text(text) => args.push(format!("--{key}={text}")),
+        }
+    }
+    Ok(args)
+}
+
+// Parse the subset of TOML that's needed for setting options: `key = value`
+// pairs, where values are strings, numbers, booleans, or arrays of them,
+// and comments. Keys in one of the SECTIONS, e.g., under `[filters]`, are
+// returned with the name of the section in front of them, like dotted keys.
+fn parse_toml(toml: &str) -> Result<Vec<(String, ConfigValue)>, String> {
+    let mut entries = vec![];
+    let mut section: Option<&str> = None;
+
+    for (i, line) in toml.lines().enumerate() {
+        let line = strip_toml_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let error = |message: &str| format!("line {}: {message}", i + 1);
+        let Some((key, value)) = line.split_once('=') else {
+            if let Some(table) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                let table = table.trim();
+                match SECTIONS.iter().find(|(name, _)| *name == table) {
+                    Some((name, _)) => section = Some(name),
+                    None => return Err(error(&format!("Unknown table: [{table}]"))),
+                }
+                continue;
+            }
+            return Err(error("Expected `key = value`"));
+        };
+
+        let key = unquote_toml_string(key.trim()).unwrap_or_else(|| key.trim().to_string());
+        let key = match section {
+            Some(section) => format!("{section}.{key}"),
+            None => key,
+        };
+        let value = value.trim();
+        let value = if let Some(items) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            let items: Option<Vec<String>> = split_toml_array(items)
+                .into_iter()
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(parse_toml_scalar)
+                .collect();
+            ConfigValue::Text(items.ok_or_else(|| error("Invalid array"))?.join(","))
+        } else {
+            match value {
+                "true" => ConfigValue::Bool(true),
+                "false" => ConfigValue::Bool(false),
+                _ => ConfigValue::Text(
+                    parse_toml_scalar(value).ok_or_else(|| error("Invalid value"))?,
+                ),
+            }
+        };
+        entries.push((key, value));
+    }
+
+    Ok(entries)
+}
+
+// Remove a trailing comment, ignoring '#'s inside of strings.
+fn strip_toml_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (i, ch) in line.char_indices() {
+        match (quote, ch) {
+            (None, '#') => return &line[..i],
+            (None, '"' | '\'') => quote = Some(ch),
+            (Some(q), _) if q == ch => quote = None,
+            _ => {}
+        }
+    }
+    line
+}
+
+// Split the items of an array on commas, ignoring ','s inside of strings,
+// e.g., for key mappings like ",=;".
+fn split_toml_array(items: &str) -> Vec<&str> {
+    let mut split = vec![];
+    let mut start = 0;
+    let mut quote = None;
+    for (i, ch) in items.char_indices() {
+        match (quote, ch) {
+            (None, ',') => {
+                split.push(&items[start..i]);
+                start = i + 1;
+            }
+            (None, '"' | '\'') => quote = Some(ch),
+            (Some(q), _) if q == ch => quote = None,
+            _ => {}
+        }
+    }
+    split.push(&items[start..]);
+    split
+}
+
+fn parse_toml_scalar(value: &str) -> Option<String> {
+    if let Some(s) = unquote_toml_string(value) {
+        return Some(s);
+    }
+    let is_bare = !value.is_empty()
+        && value
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '-' | '+' | '.' | '_'));
+    is_bare.then(|| value.to_string())
+}
+
+// Strings in config files don't need escape sequences, so only basic
+// ("...") and literal ('...') strings without them are supported.
+fn unquote_toml_string(s: &str) -> Option<String> {
+    for quote in ['"', '\''] {
+        if let Some(inner) = s.strip_prefix(quote).and_then(|s| s.strip_suffix(quote)) {
+            if !inner.contains(quote) && !inner.contains('\\') {
+                return Some(inner.to_string());
+            }
+        }
+    }
+    None
+}
+
+fn parse_yaml(yaml: &str) -> Result<Vec<(String, ConfigValue)>, String> {
+    use yaml_rust::{Yaml, YamlLoader};
+
+    let docs = YamlLoader::load_from_str(yaml).map_err(|err| err.to_string())?;
+    let hash = match docs.into_iter().next() {
+        None => return Ok(vec![]),
+        Some(Yaml::Hash(hash)) => hash,
+        Some(_) => return Err("Expected a mapping of options to values".to_string()),
+    };
+
+    let scalar = |value: Yaml| match value {
+        Yaml::String(s) | Yaml::Real(s) => Some(s),
+        Yaml::Integer(i) => Some(i.to_string()),
+        _ => None,
+    };
+
+    let mut entries = vec![];
+    for (key, value) in hash {
+        let Some(key) = scalar(key) else {
+            return Err("Option names must be strings".to_string());
+        };
+        // Sections are mappings of names to values.
+        if let Yaml::Hash(section) = value {
+            for (name, value) in section {
+                let (Some(name), Some(value)) = (scalar(name), scalar(value)) else {
+                    return Err(format!("{key}: Expected names mapped to strings"));
+                };
+                entries.push((format!("{key}.{name}"), ConfigValue::Text(value)));
+            }
+            continue;
+        }
+
+        let value = match value {
+            Yaml::Boolean(b) => ConfigValue::Bool(b),
+            Yaml::Array(items) => {
+                let items: Option<Vec<String>> = items.into_iter().map(scalar).collect();
+                let items = items.ok_or_else(|| format!("{key}: Invalid array"))?;
+                ConfigValue::Text(items.join(","))
+            }
+            value => {
+                ConfigValue::Text(scalar(value).ok_or_else(|| format!("{key}: Invalid value"))?)
+            }
+        };
+        entries.push((key, value));
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ConfigValue::*;
+
+    #[test]
+    fn test_parse_toml() {
+        let toml = r##"
+            # Defaults for jless
+            mode = "line"   # Or "data"
+            scrolloff = 5
+            line-numbers = false
+            "align-keys" = true
+            indent-guide-colors = ['red', "#fff", 3]
+            map = ["J=<C-d>", ',=;']
+        "##;
+        assert_eq!(
+            Ok(vec![
+                ("mode".to_string(), Text("line".to_string())),
+                ("scrolloff".to_string(), Text("5".to_string())),
+                ("line-numbers".to_string(), Bool(false)),
+                ("align-keys".to_string(), Bool(true)),
+                (
+                    "indent-guide-colors".to_string(),
+                    Text("red,#fff,3".to_string())
+                ),
+                ("map".to_string(), Text("J=<C-d>,,=;".to_string())),
+            ]),
+            parse_toml(toml)
+        );
+
+        assert_eq!(
+            Err("line 1: Unknown table: [jless]".to_string()),
+            parse_toml("[jless]")
+        );
+        assert_eq!(
+            Err("line 2: Invalid value".to_string()),
+            parse_toml("mode = 'line'\nscrolloff = \"5")
+        );
+    }
+
+    #[test]
+    fn test_parse_sections() {
+        let toml = r#"
+            filters.ids = ".items[].id"
+            [filters]
+            "images" = '.items[] | .image'  # Comment
+        "#;
+        assert_eq!(
+            Ok(vec![
+                ("filters.ids".to_string(), Text(".items[].id".to_string())),
+                (
+                    "filters.images".to_string(),
+                    Text(".items[] | .image".to_string())
+                ),
+            ]),
+            parse_toml(toml)
+        );
+
+        let yaml = "mode: line\nfilters:\n  ids: .items[].id\n";
+        assert_eq!(
+            Ok(vec![
+                ("mode".to_string(), Text("line".to_string())),
+                ("filters.ids".to_string(), Text(".items[].id".to_string())),
+            ]),
+            parse_yaml(yaml)
+        );
+
+        assert_eq!(
+            Ok(vec![
+                "--define-filter=ids=.items[].id".to_string(),
+                "--mode=line".to_string(),
+            ]),
+            config_args(parse_yaml("filters:\n  ids: .items[].id\nmode: line").unwrap())
+        );
+        assert_eq!(
+            Ok(vec!["--alias=tl=timeline ts".to_string()]),
+            config_args(parse_toml("[aliases]\ntl = \"timeline ts\"").unwrap())
+        );
+        assert_eq!(
+            Err("Unknown section: filter".to_string()),
+            config_args(vec![("filter.ids".to_string(), Text(".".to_string()))])
+        );
+    }
+
+    #[test]
+    fn test_parse_yaml() {
+        let yaml = "mode: line\nscrolloff: 5\nline-numbers: false\nindent-guide-colors: [red, 3]\n";
+        assert_eq!(
+            Ok(vec![
+                ("mode".to_string(), Text("line".to_string())),
+                ("scrolloff".to_string(), Text("5".to_string())),
+                ("line-numbers".to_string(), Bool(false)),
+                ("indent-guide-colors".to_string(), Text("red,3".to_string())),
+            ]),
+            parse_yaml(yaml)
+        );
+        assert!(parse_yaml("- mode").is_err());
+    }
+
+    #[test]
+    fn test_config_args() {
+        let entries = vec![
+            ("mode".to_string(), Text("line".to_string())),
+            ("line-numbers".to_string(), Bool(false)),
+            ("previews".to_string(), Bool(true)),
+            ("cursorline".to_string(), Bool(true)),
+            ("dense".to_string(), Bool(false)),
+        ];
+        assert_eq!(
+            Ok(vec![
+                "--mode=line".to_string(),
+                "--no-line-numbers".to_string(),
+                "--cursorline".to_string(),
+            ]),
+            config_args(entries)
+        );
+
+        assert_eq!(
+            Err("Unknown option: colour".to_string()),
+            config_args(vec![("colour".to_string(), Bool(true))])
+        );
+    }
+
+    #[test]
+    fn test_reload_errors_are_one_line() {
+        let path = std::env::temp_dir().join(format!("jless-test-{}.toml", std::process::id()));
+
+        fs::write(&path, "scrolloff = \"lots\"\n").unwrap();
+        let err = reload_options(&path).unwrap_err();
+        assert!(!err.contains('\n'), "{:?}", err);
+        assert!(err.starts_with("Invalid config file"), "{:?}", err);
+
+        fs::remove_file(&path).unwrap();
+        let err = reload_options(&path).unwrap_err();
+        assert!(err.starts_with("Unable to read config file"), "{:?}", err);
+    }
+
+    #[test]
+    fn test_command_line_overrides_config() {
+        use crate::viewer::Mode;
+
+        let opt = Opt::parse_from([
+            "jless",
+            // From the config file.
+            "--mode=line",
+            "--scrolloff=5",
+            "--cmd=:set cursorline",
+            // From the command line.
+            "--mode",
+            "data",
+            "--cmd",
+            "j",
+        ]);
+        assert_eq!(Mode::Data, opt.mode);
+        assert_eq!(5, opt.scrolloff);
+        assert_eq!(vec![":set cursorline", "j"], opt.startup_commands);
+    }
+}
@@ -0,0 +1,253 @@
+use std::fs;
+use std::io;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+// Commands sent to a running jless by other processes via the socket
+// passed to `--control`, one per line:
+//
+//   goto .data.items[3]
+//   search error
+//   reload
+//
+// Each line gets a reply of "ok" or "error: <reason>". Since it's just
+// a Unix socket, it can be driven by `nc -U` or `socat`, and forwarded
+// over SSH with `ssh -R`.
+//
+// The same channel is also used to tell the main loop that the config file
+// changed.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ControlCommand {
+    // Focus the node at a path, as copied with `yp`.
+    Goto(String),
+    // Start a forward search, like `/`.
+    Search(String),
+    Reload,
+    // Sent when the config file is modified, not over the socket.
+    ReloadConfig,
+}
+
+impl ControlCommand {
+    pub fn parse(line: &str) -> Result<ControlCommand, String> {
+        let line = line.trim();
+        let (command, arg) = line.split_once(' ').unwrap_or((line, ""));
+        let arg = arg.trim();
+
+        match command {
+            "goto" => Ok(ControlCommand::Goto(arg.to_string())),
+            "search" if arg.is_empty() => Err("search needs a pattern".to_string()),
+            "search" => Ok(ControlCommand::Search(arg.to_string())),
+            "reload" if arg.is_empty() => Ok(ControlCommand::Reload),
+            "reload" => Err("reload doesn't take an argument".to_string()),
+            _ => Err(format!("unknown command: {command}")),
+        }
+    }
+}
+
+// The end of the channel that the main loop reads commands from. Each
+// command is also signalled by writing a byte to a wake-up pipe, so that
+// it can be polled along with STDIN.
+pub struct ControlReceiver {
+    commands: Receiver<ControlCommand>,
+    wake_pipe: UnixStream,
+}
+
+impl ControlReceiver {
+    pub fn try_recv(&self) -> Option<ControlCommand> {
+        self.commands.try_recv().ok()
+    }
+
+    pub fn clear_wake_pipe(&mut self) {
+        // Just make this big enough to absorb a bunch of commands at once.
+        let mut buf = [0; 32];
+        let _ = self.wake_pipe.read(&mut buf);
+    }
+}
+
+impl AsRawFd for ControlReceiver {
+    fn as_raw_fd(&self) -> RawFd {
+        self.wake_pipe.as_raw_fd()
+    }
+}
+
+pub struct ControlSender {
+    commands: Sender<ControlCommand>,
+    wake_pipe: UnixStream,
+}
+
+impl ControlSender {
+    // Returns false if jless has exited.
+    pub fn send(&mut self, command: ControlCommand) -> bool {
+        if self.commands.send(command).is_err() {
+            return false;
+        }
+        let _ = self.wake_pipe.write_all(b"!");
+        true
+    }
+
+    pub fn try_clone(&self) -> io::Result<ControlSender> {
+        Ok(ControlSender {
+            commands: self.commands.clone(),
+            wake_pipe: self.wake_pipe.try_clone()?,
+        })
+    }
+}
+
+pub fn channel() -> io::Result<(ControlSender, ControlReceiver)> {
+    let (wake_pipe, wake_pipe_write) = UnixStream::pair()?;
+    let (sender, commands) = mpsc::channel();
+    Ok((
+        ControlSender {
+            commands: sender,
+            wake_pipe: wake_pipe_write,
+        },
+        ControlReceiver {
+            commands,
+            wake_pipe,
+        },
+    ))
+}
+
+// Removes the socket file when jless exits.
+pub struct ControlSocket {
+    path: PathBuf,
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// Start listening on a socket at the given path, handling each connection
+// on its own thread, which sends the commands it reads to the main loop.
+pub fn listen(path: &Path, sender: ControlSender) -> Result<ControlSocket, String> {
+    let listener = bind(path).map_err(|err| {
+        format!(
+            "Unable to listen on control socket {}: {err}",
+            path.display()
+        )
+    })?;
+    let socket = ControlSocket {
+        path: path.to_path_buf(),
+    };
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let Ok(sender) = sender.try_clone() else {
+                continue;
+            };
+            thread::spawn(move || handle_connection(stream, sender));
+        }
+    });
+
+    Ok(socket)
+}
+
+fn bind(path: &Path) -> io::Result<UnixListener> {
+    match UnixListener::bind(path) {
+        Err(err) if err.kind() == io::ErrorKind::AddrInUse => {
+            // A socket left behind by a jless that didn't exit cleanly
+            // can be replaced, but don't clobber one that's still in use,
+            // or a file that isn't a socket.
+            let is_socket = fs::metadata(path)?.file_type().is_socket();
+            if !is_socket || UnixStream::connect(path).is_ok() {
+                return Err(err);
+            }
+            fs::remove_file(path)?;
+            UnixListener::bind(path)
+        }
+        result => result,
+    }
+}
+
+fn handle_connection(stream: UnixStream, mut sender: ControlSender) {
+    let Ok(mut reply) = stream.try_clone() else {
+        return;
+    };
+
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match ControlCommand::parse(&line) {
+            Ok(command) => {
+                // jless has exited.
+                if !sender.send(command) {
+                    return;
+                }
+                "ok".to_string()
+            }
+            Err(err) => format!("error: {err}"),
+        };
+
+        if writeln!(reply, "{response}").is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_control_commands() {
+        assert_eq!(
+            Ok(ControlCommand::Goto(".a[1].b".to_string())),
+            ControlCommand::parse("goto .a[1].b\n")
+        );
+        assert_eq!(
+            Ok(ControlCommand::Goto(String::new())),
+            ControlCommand::parse("goto")
+        );
+        assert_eq!(
+            Ok(ControlCommand::Search("two words".to_string())),
+            ControlCommand::parse("search  two words ")
+        );
+        assert_eq!(Ok(ControlCommand::Reload), ControlCommand::parse("reload"));
+
+        assert!(ControlCommand::parse("search").is_err());
+        assert!(ControlCommand::parse("reload now").is_err());
+        assert!(ControlCommand::parse("quit").is_err());
+    }
+
+    #[test]
+    fn test_control_socket() {
+        let path = std::env::temp_dir().join(format!("jless-test-{}.sock", std::process::id()));
+        let (sender, mut receiver) = channel().unwrap();
+        let socket = listen(&path, sender.try_clone().unwrap()).unwrap();
+        // Can't listen on a socket that's in use.
+        assert!(listen(&path, sender).is_err());
+
+        let mut client = UnixStream::connect(&path).unwrap();
+        client.write_all(b"goto .a\nbogus\nreload\n").unwrap();
+        let mut replies = BufReader::new(client.try_clone().unwrap()).lines();
+        assert_eq!("ok", replies.next().unwrap().unwrap());
+        assert_eq!(
+            "error: unknown command: bogus",
+            replies.next().unwrap().unwrap()
+        );
+        assert_eq!("ok", replies.next().unwrap().unwrap());
+
+        receiver.clear_wake_pipe();
+        assert_eq!(
+            Some(ControlCommand::Goto(".a".to_string())),
+            receiver.try_recv()
+        );
+        assert_eq!(Some(ControlCommand::Reload), receiver.try_recv());
+        assert_eq!(None, receiver.try_recv());
+
+        drop(socket);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,282 @@
+use std::ffi::CStr;
+
+use clap::ValueEnum;
+use regex::Regex;
+
+use crate::options::Opt;
+use crate::timeline::{days_from_civil, parse_timestamp};
+
+// Timestamps can be annotated with the date and time they stand for, e.g.,
+// `created_at: 1680674828 (2023-04-05 06:07:08 UTC)`, which is enabled
+// with --date-format (or :set dates). Only the values of keys matching a
+// pattern are treated as timestamps, since plenty of other numbers, like
+// ids and sizes, would otherwise be "dates" too.
+
+pub const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S %Z";
+
+// Keys like `time`, `startTime`, `created_date`, `@timestamp`, `ts` and
+// `updated_at`.
+const DEFAULT_DATE_KEYS: &str = "(?i:time|date|timestamp)$|^(?i:ts)$|_at$|[a-z]At$";
+
+// Numbers before this (2001-09-09) are more likely to be durations or
+// counts than timestamps, e.g., `runtime: 300`.
+const MIN_NUMERIC_TIMESTAMP_MILLIS: i64 = 1_000_000_000_000;
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+const WEEKDAYS: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+// The conversion specifiers supported in date formats, as in strftime.
+const SPECIFIERS: &str = "YymdeHIMSpjbBaAFTzZs%";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum DateZone {
+    // The time zone of the machine jless is running on.
+    Local,
+    Utc,
+}
+
+#[derive(Clone, Debug)]
+pub struct DateDisplay {
+    pub format: String,
+    pub zone: DateZone,
+    // The keys whose values are shown as dates.
+    pub keys: Regex,
+}
+
+impl DateDisplay {
+    pub fn from_options(opt: &Opt) -> DateDisplay {
+        DateDisplay {
+            format: opt
+                .date_format
+                .clone()
+                .unwrap_or_else(|| DEFAULT_DATE_FORMAT.to_string()),
+            zone: opt.date_timezone,
+            keys: opt
+                .date_keys
+                .clone()
+                .unwrap_or_else(|| Regex::new(DEFAULT_DATE_KEYS).unwrap()),
+        }
+    }
+
+    // The note shown after a value, given as it appears in the pretty
+    // printed document, if it's a timestamp under one of the date keys.
+    pub fn note(&self, key: Option<&str>, value: &str) -> Option<String> {
+        if !self.keys.is_match(key?) {
+            return None;
+        }
+        let millis = parse_timestamp(value)?;
+        if !value.starts_with('"') && millis < MIN_NUMERIC_TIMESTAMP_MILLIS {
+            return None;
+        }
+        Some(format!(" ({})", self.format(millis)))
+    }
+
+    // Format milliseconds since the epoch according to the date format.
+    pub fn format(&self, millis: i64) -> String {
+        let secs = millis.div_euclid(1000);
+        let (offset, zone_name) = match self.zone {
+            DateZone::Utc => (0, "UTC".to_string()),
+            DateZone::Local => local_offset(secs),
+        };
+        let local_secs = secs + offset;
+        let days = local_secs.div_euclid(86_400);
+        let secs_of_day = local_secs.rem_euclid(86_400);
+        let (year, month, day) = civil_from_days(days);
+        let (hour, minute, second) = (secs_of_day / 3600, secs_of_day / 60 % 60, secs_of_day % 60);
+        // 1970-01-01 was a Thursday.
+        let weekday = (days + 4).rem_euclid(7) as usize;
+        let month_name = MONTHS[month as usize - 1];
+
+        let mut formatted = String::new();
+        let mut chars = self.format.chars();
+        while let Some(ch) = chars.next() {
+            if ch != '%' {
+                formatted.push(ch);
+                continue;
+            }
+            let part = match chars.next() {
+                Some('Y') => year.to_string(),
+                Some('y') => format!("{:02}", year.rem_euclid(100)),
+                Some('m') => format!("{month:02}"),
+                Some('d') => format!("{day:02}"),
+                Some('e') => format!("{day:2}"),
+                Some('H') => format!("{hour:02}"),
+                Some('I') => format!("{:02}", (hour + 11) % 12 + 1),
+                Some('M') => format!("{minute:02}"),
+                Some('S') => format!("{second:02}"),
+                Some('p') => if hour < 12 { "AM" } else { "PM" }.to_string(),
+                Some('j') => format!("{:03}", days - days_from_civil(year, 1, 1) + 1),
+                Some('b') => month_name[..3].to_string(),
+                Some('B') => month_name.to_string(),
+                Some('a') => WEEKDAYS[weekday][..3].to_string(),
+                Some('A') => WEEKDAYS[weekday].to_string(),
+                Some('F') => format!("{year}-{month:02}-{day:02}"),
+                Some('T') => format!("{hour:02}:{minute:02}:{second:02}"),
+                Some('z') => {
+                    let sign = if offset < 0 { '-' } else { '+' };
+                    let minutes = offset.abs() / 60;
+                    format!("{sign}{:02}{:02}", minutes / 60, minutes % 60)
+                }
+                Some('Z') => zone_name.clone(),
+                Some('s') => secs.to_string(),
+                // Formats are validated when they're parsed.
+                _ => "%".to_string(),
+            };
+            formatted.push_str(&part);
+        }
+        formatted
+    }
+}
+
+// Check that a date format only uses supported conversion specifiers.
+pub fn parse_date_format(format: &str) -> Result<String, String> {
+    let mut chars = format.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '%' {
+            continue;
+        }
+        match chars.next() {
+            Some(specifier) if SPECIFIERS.contains(specifier) => {}
+            Some(specifier) => {
+                return Err(format!(
+                    "Unsupported specifier in date format: %{specifier}"
+                ))
+            }
+            None => return Err("Date format can't end with a lone %".to_string()),
+        }
+    }
+    Ok(format.to_string())
+}
+
+pub fn parse_date_keys(pattern: &str) -> Result<Regex, String> {
+    Regex::new(pattern).map_err(|err| format!("Invalid date key pattern: {err}"))
+}
+
+// The offset from UTC, in seconds, and the abbreviated name of the local
+// time zone at the given time.
+fn local_offset(secs: i64) -> (i64, String) {
+    let time = secs as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return (0, "UTC".to_string());
+    }
+
+    let name = if tm.tm_zone.is_null() {
+        String::new()
+    } else {
+        unsafe { CStr::from_ptr(tm.tm_zone) }
+            .to_string_lossy()
+            .into_owned()
+    };
+    (tm.tm_gmtoff as i64, name)
+}
+
+// The (year, month, day) of the given number of days since 1970-01-01 in
+// the proleptic Gregorian calendar; the inverse of days_from_civil.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc_display(format: &str) -> DateDisplay {
+        DateDisplay {
+            format: format.to_string(),
+            zone: DateZone::Utc,
+            keys: Regex::new(DEFAULT_DATE_KEYS).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_civil_from_days() {
+        for (year, month, day) in [(1970, 1, 1), (2000, 2, 29), (2023, 12, 31), (1969, 7, 20)] {
+            let days = days_from_civil(year, month, day);
+            assert_eq!((year, month, day), civil_from_days(days));
+        }
+    }
+
+    #[test]
+    fn test_format() {
+        let dates = utc_display(DEFAULT_DATE_FORMAT);
+        assert_eq!("2023-04-05 06:07:08 UTC", dates.format(1_680_674_828_500));
+        assert_eq!("1969-12-31 23:59:59 UTC", dates.format(-1));
+
+        let dates = utc_display("%a %e %b %y, %I:%M %p (day %j, %s) %z %%");
+        assert_eq!(
+            "Wed  5 Apr 23, 06:07 AM (day 095, 1680674828) +0000 %",
+            dates.format(1_680_674_828_000)
+        );
+        let dates = utc_display("%A, %B %d %F %T");
+        assert_eq!(
+            "Sunday, December 31 2023-12-31 23:00:00",
+            dates.format(1_704_063_600_000)
+        );
+    }
+
+    #[test]
+    fn test_note() {
+        let dates = utc_display("%F");
+        assert_eq!(
+            Some(" (2023-04-05)".to_string()),
+            dates.note(Some("created_at"), "1680674828")
+        );
+        assert_eq!(
+            Some(" (2023-04-05)".to_string()),
+            dates.note(Some("startTime"), "\"2023-04-05T10:00:00Z\"")
+        );
+        assert_eq!(
+            Some(" (2023-04-06)".to_string()),
+            dates.note(Some("@timestamp"), "\"2023-04-05T23:00:00-02:00\"")
+        );
+        assert_eq!(None, dates.note(Some("ts"), "300"));
+        assert_eq!(None, dates.note(Some("id"), "1680674828"));
+        assert_eq!(None, dates.note(Some("counts"), "1680674828"));
+        assert_eq!(None, dates.note(Some("date"), "\"soon\""));
+        assert_eq!(None, dates.note(None, "1680674828"));
+    }
+
+    #[test]
+    fn test_parse_date_format() {
+        assert!(parse_date_format(DEFAULT_DATE_FORMAT).is_ok());
+        assert!(parse_date_format("%Y-%q").is_err());
+        assert!(parse_date_format("100%").is_err());
+    }
+}
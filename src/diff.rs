@@ -0,0 +1,405 @@
+use std::collections::HashMap;
+
+use crate::flatjson::{FlatJson, Index, OptionIndex, PathType};
+
+// This module is responsible for comparing two versions of a document,
+// for example, before and after the input file is reloaded, and
+// determining which rows in the new version should be marked as having
+// changed.
+//
+// Rows are matched up by their path from the root of the document,
+// rather than by their position, so that inserting an element near
+// the start of a file doesn't cause every row after it to be marked
+// as modified. (Array elements are still matched by index though, so
+// inserting an element into the middle of an array will cause every
+// element after it to be considered modified.)
+//
+// Since matching by path ignores the order of object keys, objects whose
+// keys were reordered are detected separately, and marked distinctly from
+// value changes, since some consumers care about key order and others
+// don't.
+//
+// Values that were removed have no row in the new version to mark, so the
+// container they were removed from is marked instead.
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DiffMarker {
+    // A node whose path didn't exist in the previous version.
+    Added,
+    // A node whose path existed, but whose value is different.
+    Modified,
+    // An object whose keys are in a different order, but whose values
+    // are otherwise the same (or whose values changed as well).
+    Reordered,
+    // A container that a value was removed from.
+    ChildRemoved,
+    // A container that has an added, modified or reordered descendant.
+    DescendantChanged,
+}
+
+impl DiffMarker {
+    pub fn gutter_char(&self) -> char {
+        match self {
+            DiffMarker::Added => '+',
+            DiffMarker::Modified => '~',
+            DiffMarker::Reordered => '↕',
+            DiffMarker::ChildRemoved => '-',
+            DiffMarker::DescendantChanged => '·',
+        }
+    }
+}
+
+// Compare two documents, returning markers for the rows in `new` that
+// differ from `old`. Closing rows of containers are never marked.
+pub fn diff_markers(old: &FlatJson, new: &FlatJson) -> HashMap<Index, DiffMarker> {
+    let old_paths = paths(old);
+    // The value of each path, and its position within its parent.
+    let mut old_values: HashMap<&str, (&str, usize)> = HashMap::new();
+    for (index, path) in old_paths.iter().enumerate() {
+        if let Some(path) = path {
+            old_values.insert(
+                path,
+                (comparable_value(old, index), old[index].index_in_parent),
+            );
+        }
+    }
+
+    let mut markers = HashMap::new();
+    // The position of each row in the new document in its parent in the
+    // old document, if it existed.
+    let mut old_positions: Vec<Option<usize>> = vec![None; new.0.len()];
+    let mut new_indexes: HashMap<&str, Index> = HashMap::new();
+
+    let new_paths = paths(new);
+    for (index, path) in new_paths.iter().enumerate() {
+        let Some(path) = path else {
+            continue;
+        };
+        new_indexes.insert(path, index);
+
+        let marker = match old_values.get(path.as_str()) {
+            None => DiffMarker::Added,
+            Some((old_value, old_position)) => {
+                old_positions[index] = Some(*old_position);
+                if *old_value == comparable_value(new, index) {
+                    continue;
+                }
+                DiffMarker::Modified
+            }
+        };
+
+        mark_with_ancestors(new, &mut markers, index, marker);
+    }
+
+    // Only the outermost removed values are considered, since the parents
+    // of the ones inside of them are gone too. Removed top-level values
+    // have no parent to mark.
+    for (index, path) in old_paths.iter().enumerate() {
+        let Some(path) = path else {
+            continue;
+        };
+        if new_indexes.contains_key(path.as_str()) {
+            continue;
+        }
+        let OptionIndex::Index(old_parent) = old[index].parent else {
+            continue;
+        };
+        let parent_path = old_paths[old_parent].as_deref();
+        if let Some(&parent) = parent_path.and_then(|path| new_indexes.get(path)) {
+            mark_with_ancestors(new, &mut markers, parent, DiffMarker::ChildRemoved);
+        }
+    }
+
+    for index in 0..new.0.len() {
+        let row = &new[index];
+        if row.is_opening_of_container()
+            && !row.is_array()
+            && old_positions[index].is_some()
+            && keys_reordered(new, index, &old_positions)
+        {
+            mark_with_ancestors(new, &mut markers, index, DiffMarker::Reordered);
+        }
+    }
+
+    markers
+}
+
+// The same markers, but as if key order changes weren't detected.
+pub fn ignoring_key_order(
+    markers: &HashMap<Index, DiffMarker>,
+    flatjson: &FlatJson,
+) -> HashMap<Index, DiffMarker> {
+    let mut without_reorders = HashMap::new();
+    for (index, marker) in markers {
+        if matches!(
+            marker,
+            DiffMarker::Added | DiffMarker::Modified | DiffMarker::ChildRemoved
+        ) {
+            mark_with_ancestors(flatjson, &mut without_reorders, *index, *marker);
+        }
+    }
+    without_reorders
+}
+
+pub fn mark_with_ancestors(
+    flatjson: &FlatJson,
+    markers: &mut HashMap<Index, DiffMarker>,
+    index: Index,
+    marker: DiffMarker,
+) {
+    // A reordered object may already be marked as having changed
+    // descendants; being reordered is more specific.
+    let existing = markers.entry(index).or_insert(marker);
+    if *existing == DiffMarker::DescendantChanged {
+        *existing = marker;
+    }
+
+    // Mark all the ancestors so that changes inside collapsed
+    // containers are still noticeable.
+    let mut ancestor = flatjson[index].parent;
+    while let OptionIndex::Index(parent) = ancestor {
+        markers
+            .entry(parent)
+            .or_insert(DiffMarker::DescendantChanged);
+        ancestor = flatjson[parent].parent;
+    }
+}
+
+// The first row of the next (or, going backwards, previous) change
+// relative to `from`, for jumping between changes like vim's ]c and [c.
+// The rows inside an added or modified container are part of the same
+// change as it, so they're skipped over.
+pub fn next_change(
+    flatjson: &FlatJson,
+    markers: &HashMap<Index, DiffMarker>,
+    from: Index,
+    forwards: bool,
+) -> Option<Index> {
+    let starts_change = |index: &Index| {
+        if !matches!(markers.get(index), Some(marker) if *marker != DiffMarker::DescendantChanged) {
+            return false;
+        }
+        match flatjson[*index].parent {
+            OptionIndex::Index(parent) => !matches!(
+                markers.get(&parent),
+                Some(DiffMarker::Added | DiffMarker::Modified)
+            ),
+            OptionIndex::Nil => true,
+        }
+    };
+
+    if forwards {
+        (from + 1..flatjson.0.len()).find(starts_change)
+    } else {
+        (0..from).rev().find(starts_change)
+    }
+}
+
+// The path of every row, except for the closing rows of containers.
+fn paths(flatjson: &FlatJson) -> Vec<Option<String>> {
+    (0..flatjson.0.len())
+        .map(|index| {
+            if flatjson[index].is_closing_of_container() {
+                return None;
+            }
+            flatjson
+                .build_path_to_node(PathType::DotWithTopLevelIndex, index)
+                .ok()
+        })
+        .collect()
+}
+
+// Whether the keys of an object that existed in the old document appear
+// in a different order than they used to. Added keys are ignored.
+fn keys_reordered(flatjson: &FlatJson, open_index: Index, old_positions: &[Option<usize>]) -> bool {
+    let mut prev_position = None;
+    let mut next_child = flatjson[open_index].first_child();
+
+    while let OptionIndex::Index(child) = next_child {
+        if let Some(position) = old_positions[child] {
+            if prev_position.map_or(false, |prev| prev > position) {
+                return true;
+            }
+            prev_position = Some(position);
+        }
+        next_child = flatjson[child].next_sibling;
+    }
+
+    false
+}
+
+// For primitives we compare the actual values, but for containers we
+// only compare their types; whether their contents changed is handled
+// by comparing their children.
+fn comparable_value(flatjson: &FlatJson, index: Index) -> &str {
+    let row = &flatjson[index];
+    match row.value.container_type() {
+        Some(container_type) => container_type.open_str(),
+        None => &flatjson.1[row.range.clone()],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flatjson::parse_top_level_json;
+
+    #[test]
+    fn test_diff_markers() {
+        const OLD: &str = r#"{
+            "a": 1,
+            "b": [2, 3],
+            "c": { "d": true },
+            "e": "same"
+        }"#;
+        const NEW: &str = r#"{
+            "a": 10,
+            "b": [2, 3, 4],
+            "c": ["d"],
+            "e": "same",
+            "f": null
+        }"#;
+
+        let old = parse_top_level_json(OLD.to_owned()).unwrap();
+        let new = parse_top_level_json(NEW.to_owned()).unwrap();
+        let markers = diff_markers(&old, &new);
+
+        use DiffMarker::*;
+        let expected: HashMap<Index, DiffMarker> = vec![
+            // Root object
+            (0, DescendantChanged),
+            // "a": 10
+            (1, Modified),
+            // "b": [2, 3, 4]
+            (2, DescendantChanged),
+            // 4
+            (5, Added),
+            // "c": ["d"]
+            (7, Modified),
+            // "d"
+            (8, Added),
+            // "f": null
+            (11, Added),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(expected, markers);
+    }
+
+    #[test]
+    fn test_diff_markers_key_order() {
+        const OLD: &str = r#"{
+            "a": {"x": 1, "y": 2, "z": 3},
+            "b": {"x": 1, "y": 2},
+            "c": [{"x": 1, "y": 2}]
+        }"#;
+        const NEW: &str = r#"{
+            "a": {"z": 3, "x": 1, "y": 2},
+            "b": {"x": 10, "w": 0, "y": 2},
+            "c": [{"y": 2, "x": 5}]
+        }"#;
+
+        let old = parse_top_level_json(OLD.to_owned()).unwrap();
+        let new = parse_top_level_json(NEW.to_owned()).unwrap();
+        let markers = diff_markers(&old, &new);
+
+        use DiffMarker::*;
+        let expected: HashMap<Index, DiffMarker> = vec![
+            // Root object
+            (0, DescendantChanged),
+            // "a": keys reordered, but values are the same.
+            (1, Reordered),
+            // "b": a key was added between existing keys, which isn't
+            // considered reordering.
+            (6, DescendantChanged),
+            (7, Modified),
+            (8, Added),
+            // "c"
+            (11, DescendantChanged),
+            // Reordered, with a modified value too.
+            (12, Reordered),
+            (14, Modified),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(expected, markers);
+
+        let expected: HashMap<Index, DiffMarker> = vec![
+            (0, DescendantChanged),
+            (6, DescendantChanged),
+            (7, Modified),
+            (8, Added),
+            (11, DescendantChanged),
+            (12, DescendantChanged),
+            (14, Modified),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(expected, ignoring_key_order(&markers, &new));
+    }
+
+    #[test]
+    fn test_diff_markers_removed_values() {
+        const OLD: &str = r#"{
+            "a": {"x": 1, "y": {"z": 2}},
+            "b": [1, 2, 3],
+            "c": {"d": 4}
+        }"#;
+        const NEW: &str = r#"{
+            "a": {"x": 1},
+            "b": [1, 2],
+            "c": [5]
+        }"#;
+
+        let old = parse_top_level_json(OLD.to_owned()).unwrap();
+        let new = parse_top_level_json(NEW.to_owned()).unwrap();
+
+        use DiffMarker::*;
+        let expected: HashMap<Index, DiffMarker> = vec![
+            (0, DescendantChanged),
+            // "a": only "y" is considered removed, not "y.z".
+            (1, ChildRemoved),
+            // "b": the last element was removed.
+            (4, ChildRemoved),
+            // "c": removing "d" doesn't override the container being modified.
+            (8, Modified),
+            (9, Added),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(expected, diff_markers(&old, &new));
+    }
+
+    #[test]
+    fn test_next_change() {
+        const OLD: &str = r#"{"a": 1, "b": {"c": 2}, "d": [3], "e": 4}"#;
+        const NEW: &str = r#"{"a": 1, "b": {"c": 20}, "d": {"x": [1]}, "e": 40}"#;
+
+        let old = parse_top_level_json(OLD.to_owned()).unwrap();
+        let new = parse_top_level_json(NEW.to_owned()).unwrap();
+        let markers = diff_markers(&old, &new);
+
+        // Rows: 0 {, 1 a, 2 b {, 3 c, 4 }, 5 d {, 6 x [, 7 1, 8 ], 9 }, 10 e
+        let next = |from, forwards| next_change(&new, &markers, from, forwards);
+        assert_eq!(Some(3), next(0, true));
+        // The contents of the modified "d" are part of the same change.
+        assert_eq!(Some(5), next(3, true));
+        assert_eq!(Some(10), next(5, true));
+        assert_eq!(Some(10), next(7, true));
+        assert_eq!(None, next(10, true));
+
+        assert_eq!(Some(5), next(10, false));
+        assert_eq!(Some(5), next(7, false));
+        assert_eq!(Some(3), next(5, false));
+        assert_eq!(None, next(3, false));
+    }
+
+    #[test]
+    fn test_no_diff_markers_for_identical_documents() {
+        const JSON: &str = r#"[{"a": [1, 2]}, "b", null]"#;
+        let old = parse_top_level_json(JSON.to_owned()).unwrap();
+        let new = parse_top_level_json(JSON.to_owned()).unwrap();
+        assert!(diff_markers(&old, &new).is_empty());
+    }
+}
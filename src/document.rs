@@ -0,0 +1,439 @@
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::Write;
+use std::ops::Deref;
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::rng::Rng;
+
+// Documents containing a leaf string at least this long are spilled to a
+// file, rather than kept in memory.
+pub const SPILL_THRESHOLD: usize = 1024 * 1024;
+
+// Set when writing files isn't allowed, i.e., with --secure.
+static SPILLING_DISABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn disable_spilling() {
+    SPILLING_DISABLED.store(true, Ordering::Relaxed);
+}
+
+lazy_static::lazy_static! {
+    // Where spill files are created, set by --spill-dir. Defaults to the
+    // system's temporary directory, i.e., $TMPDIR or /tmp.
+    static ref SPILL_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+}
+
+pub fn set_spill_dir(dir: PathBuf) {
+    *SPILL_DIR.lock().unwrap() = Some(dir);
+}
+
+// Spilled documents are written in chunks of at least this size while
+// they're being pretty printed.
+const WRITE_BUFFER_SIZE: usize = 64 * 1024;
+
+// The pretty printed text of a document, which rows contain ranges into.
+//
+// This is normally kept in memory, but when the document contains giant
+// leaf strings (e.g., log dumps with enormous embedded payloads), it is
+// written out to a temporary file that is then memory mapped. The kernel
+// only loads the parts of the file that are actually accessed, i.e., when
+// a giant string is displayed, searched or copied, and can evict them again
+// under memory pressure, which keeps the resident set small. Parsed JSON
+// is written to the file as it's pretty printed (see DocumentWriter), so
+// the whole text is never in memory at once.
+//
+// Editing, deleting or restoring a value rebuilds the whole text, and so
+// writes out (and maps) a new file when the document is spilled.
+//
+// Either way it derefs to a str, so rows' ranges can be used to access
+// the document without caring where it's stored.
+pub enum Document {
+    InMemory(String),
+    Spilled(MappedFile),
+}
+
+impl Document {
+    pub fn new(text: String, spill: bool) -> Document {
+        if !spill || SPILLING_DISABLED.load(Ordering::Relaxed) {
+            return Document::InMemory(text);
+        }
+
+        // If we fail to spill the document for some reason, e.g., the
+        // spill directory isn't writable, just keep it in memory.
+        match MappedFile::create(&[text.as_str()]) {
+            Ok(mapped) => Document::Spilled(mapped),
+            Err(_) => Document::InMemory(text),
+        }
+    }
+
+    pub fn into_string(self) -> String {
+        match self {
+            Document::InMemory(text) => text,
+            Document::Spilled(mapped) => mapped.to_string(),
+        }
+    }
+
+    // Add text to the end of the document. A spilled document grows its
+    // file and maps it again, so previously appended text is never copied.
+    pub fn append(&mut self, text: &str, spill: bool) {
+        let spill = spill && !SPILLING_DISABLED.load(Ordering::Relaxed);
+        match self {
+            Document::InMemory(existing) if !spill => existing.push_str(text),
+            // The first giant string moves the document to a file, which
+            // everything after it is appended to.
+            Document::InMemory(existing) => match MappedFile::create(&[existing.as_str(), text]) {
+                Ok(mapped) => *self = Document::Spilled(mapped),
+                Err(_) => existing.push_str(text),
+            },
+            Document::Spilled(mapped) => {
+                // If the file can't grow, e.g., the disk is full, move the
+                // document back into memory.
+                if mapped.append(text).is_err() {
+                    let mut in_memory = String::with_capacity(mapped.len + text.len());
+                    in_memory.push_str(mapped);
+                    in_memory.push_str(text);
+                    *self = Document::InMemory(in_memory);
+                }
+            }
+        }
+    }
+}
+
+impl Deref for Document {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        match self {
+            Document::InMemory(text) => text,
+            Document::Spilled(mapped) => mapped,
+        }
+    }
+}
+
+impl fmt::Debug for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+// Builds a document as it's pretty printed. It's kept in memory until a
+// giant string is written, at which point everything so far is written to
+// a spill file, and so is everything after it, in WRITE_BUFFER_SIZE chunks.
+pub struct DocumentWriter {
+    // The text that hasn't been written to the spill file yet, i.e., all
+    // of it, unless the document is being spilled.
+    buffer: String,
+    spill_file: Option<File>,
+    // How much of the text has been written to the spill file.
+    written: usize,
+    spill: bool,
+    // The first error writing to the spill file; the rest of the document
+    // is still measured, but not written.
+    error: Option<io::Error>,
+}
+
+impl DocumentWriter {
+    pub fn new(spill: bool) -> DocumentWriter {
+        DocumentWriter {
+            buffer: String::new(),
+            spill_file: None,
+            written: 0,
+            spill: spill && !SPILLING_DISABLED.load(Ordering::Relaxed),
+            error: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.written + self.buffer.len()
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.push_str(c.encode_utf8(&mut [0; 4]));
+    }
+
+    pub fn push_str(&mut self, text: &str) {
+        if self.spill && self.spill_file.is_none() && text.len() >= SPILL_THRESHOLD {
+            // If we can't create the file, e.g., the spill directory isn't
+            // writable, just keep the document in memory.
+            self.spill_file = create_spill_file().ok();
+        }
+
+        let Some(file) = &self.spill_file else {
+            self.buffer.push_str(text);
+            return;
+        };
+        if self.buffer.len() + text.len() < WRITE_BUFFER_SIZE {
+            self.buffer.push_str(text);
+            return;
+        }
+
+        // Giant strings are written straight from the input, rather than
+        // being copied into the buffer first.
+        let offset = self.written + self.buffer.len();
+        let result = file
+            .write_all_at(self.buffer.as_bytes(), self.written as u64)
+            .and_then(|_| file.write_all_at(text.as_bytes(), offset as u64));
+        if let Err(err) = result {
+            self.error.get_or_insert(err);
+        }
+        self.written = offset + text.len();
+        self.buffer.clear();
+    }
+
+    pub fn finish(self) -> io::Result<Document> {
+        let len = self.len();
+        let Some(file) = self.spill_file else {
+            return Ok(Document::InMemory(self.buffer));
+        };
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+
+        file.write_all_at(self.buffer.as_bytes(), self.written as u64)?;
+        Ok(Document::Spilled(MappedFile::map(file, len)?))
+    }
+}
+
+pub struct MappedFile {
+    file: File,
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+impl MappedFile {
+    // Write the concatenation of the given parts to a new file and map it.
+    fn create(parts: &[&str]) -> io::Result<MappedFile> {
+        let len = parts.iter().map(|part| part.len()).sum();
+        if len == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot map an empty file",
+            ));
+        }
+
+        let mut file = create_spill_file()?;
+        for part in parts.iter() {
+            file.write_all(part.as_bytes())?;
+        }
+        MappedFile::map(file, len)
+    }
+
+    // Map the first len bytes of the file, which must be a valid str.
+    fn map(file: File, len: usize) -> io::Result<MappedFile> {
+        let ptr = Self::mmap(&file, len)?;
+        let mapped = MappedFile { file, ptr, len };
+        Self::check_utf8(mapped.bytes())?;
+        Ok(mapped)
+    }
+
+    // Write the text to the end of the file, then replace the mapping with
+    // one that includes it.
+    fn append(&mut self, text: &str) -> io::Result<()> {
+        if text.is_empty() {
+            return Ok(());
+        }
+
+        // Write at an explicit offset, so that if a previous append failed
+        // partway through, its leftovers are overwritten.
+        self.file.write_all_at(text.as_bytes(), self.len as u64)?;
+        let len = self.len + text.len();
+        let ptr = Self::mmap(&self.file, len)?;
+        // The text before it was already checked, and a valid str followed
+        // by another one is still valid.
+        let appended = unsafe { std::slice::from_raw_parts(ptr as *const u8, len) };
+        if let Err(err) = Self::check_utf8(&appended[self.len..]) {
+            unsafe {
+                libc::munmap(ptr, len);
+            }
+            return Err(err);
+        }
+
+        unsafe {
+            libc::munmap(self.ptr, self.len);
+        }
+        self.ptr = ptr;
+        self.len = len;
+        Ok(())
+    }
+
+    fn bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+
+    // We only ever write valid strs to the file, but its contents are
+    // checked anyway when they're mapped, since they're read back as a str.
+    fn check_utf8(bytes: &[u8]) -> io::Result<()> {
+        std::str::from_utf8(bytes)
+            .map(|_| ())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    fn mmap(file: &File, len: usize) -> io::Result<*mut libc::c_void> {
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(ptr)
+    }
+}
+
+// Create a file in the spill directory to write a document to, which only
+// we can read or write. On Linux, the file never has a name at all, so no
+// other process can open it. Elsewhere, it gets a random name, and is
+// unlinked right away; either way, it stays around until it's closed and
+// unmapped, but is cleaned up automatically when we exit, even if we crash.
+fn create_spill_file() -> io::Result<File> {
+    let dir = SPILL_DIR
+        .lock()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(std::env::temp_dir);
+
+    // Not every filesystem supports O_TMPFILE, so fall back to a named file
+    // if it fails.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        let unnamed = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .mode(0o600)
+            .custom_flags(libc::O_TMPFILE)
+            .open(&dir);
+        if let Ok(file) = unnamed {
+            return Ok(file);
+        }
+    }
+
+    let mut rng = Rng::from_time();
+    let mut attempt = 0;
+    loop {
+        let path = dir.join(format!("jless-{:016x}.json", rng.next()));
+        match OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+        {
+            Ok(file) => {
+                std::fs::remove_file(path)?;
+                return Ok(file);
+            }
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists && attempt < 100 => {
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+impl Deref for MappedFile {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        // The mapping is private, the file can only be opened by us, and
+        // its contents were checked to be valid UTF-8 when they were mapped.
+        unsafe { std::str::from_utf8_unchecked(self.bytes()) }
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr, self.len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spilled_document() {
+        let text = format!("{{\"payload\": \"{}\"}}", "é".repeat(100));
+
+        let document = Document::new(text.clone(), true);
+        assert!(matches!(document, Document::Spilled(_)));
+        assert_eq!(text, &*document);
+        assert_eq!("\"payload\"", &document[1..10]);
+
+        let document = Document::new(text.clone(), false);
+        assert!(matches!(document, Document::InMemory(_)));
+        assert_eq!(text, &*document);
+
+        // Empty documents can't be mapped, so they're kept in memory.
+        assert!(matches!(
+            Document::new(String::new(), true),
+            Document::InMemory(_)
+        ));
+    }
+
+    #[test]
+    fn test_document_writer() {
+        let payload = format!("\"{}\"", "é".repeat(SPILL_THRESHOLD));
+
+        let mut writer = DocumentWriter::new(true);
+        writer.push('[');
+        writer.push_str("1, ");
+        assert_eq!(4, writer.len());
+        writer.push_str(&payload);
+        // Enough small writes after the giant string to fill the buffer.
+        for _ in 0..WRITE_BUFFER_SIZE / 3 {
+            writer.push_str(", 2");
+        }
+        writer.push(']');
+        let len = writer.len();
+
+        let document = writer.finish().unwrap();
+        assert!(matches!(document, Document::Spilled(_)));
+        assert_eq!(len, document.len());
+        let expected = format!("[1, {payload}{}]", ", 2".repeat(WRITE_BUFFER_SIZE / 3));
+        assert!(expected == *document);
+
+        let mut writer = DocumentWriter::new(false);
+        writer.push_str(&payload);
+        assert!(matches!(writer.finish().unwrap(), Document::InMemory(_)));
+    }
+
+    #[test]
+    fn test_spill_file_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let file = create_spill_file().unwrap();
+        let mode = file.metadata().unwrap().permissions().mode();
+        assert_eq!(0o600, mode & 0o777);
+    }
+
+    #[test]
+    fn test_append_to_document() {
+        let mut document = Document::new("[1]".to_owned(), false);
+        document.append("\n[2]", false);
+        assert!(matches!(document, Document::InMemory(_)));
+
+        // Appending a giant string spills the whole document.
+        let payload = format!("\n\"{}\"", "é".repeat(100));
+        document.append(&payload, true);
+        assert!(matches!(document, Document::Spilled(_)));
+        assert_eq!(format!("[1]\n[2]{payload}"), &*document);
+
+        // And once it's spilled, everything is appended to the file.
+        document.append("\n[3]", false);
+        assert!(matches!(document, Document::Spilled(_)));
+        assert_eq!(format!("[1]\n[2]{payload}\n[3]"), &*document);
+    }
+}
@@ -1,12 +1,41 @@
+use std::collections::BTreeMap;
 use std::fmt::{Debug, Write};
 use std::ops::Range;
 
+use regex::Regex;
+
+use crate::document::{Document, SPILL_THRESHOLD};
+use crate::hjsonparser;
 use crate::jsonparser;
+use crate::jsonstringunescaper::{escape_json_string, unescape_json_string, ControlCharacters};
 use crate::lineprinter;
+use crate::ndjson;
 use crate::yamlparser;
 
 pub type Index = usize;
 
+// Runs of at least this many identical elements can be folded.
+const MIN_FOLDED_RUN_LENGTH: usize = 4;
+
+// How many rows to pretty print between reporting progress.
+const PROGRESS_INTERVAL_ROWS: usize = 64 * 1024;
+
+lazy_static::lazy_static! {
+    // Strings that can be written as plain (unquoted) YAML scalars. This is
+    // intentionally much more conservative than what YAML actually allows.
+    static ref YAML_PLAIN_SCALAR: Regex = Regex::new("^[a-zA-Z_][a-zA-Z0-9_ ./-]*$").unwrap();
+    // Plain scalars that YAML parsers would interpret as something other
+    // than a string.
+    static ref YAML_NON_STRING_SCALAR: Regex =
+        Regex::new("^(?i:true|false|yes|no|on|off|y|n|null)$").unwrap();
+    // Numbers that are valid in JSON; YAML also allows things like `.inf`,
+    // `+1` and `1.`.
+    pub static ref JSON_NUMBER: Regex =
+        Regex::new(r"^-?(0|[1-9][0-9]*)(\.[0-9]+)?([eE][+-]?[0-9]+)?$").unwrap();
+    // Keys that don't need to be quoted in TOML.
+    static ref TOML_BARE_KEY: Regex = Regex::new("^[a-zA-Z0-9_-]+$").unwrap();
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum OptionIndex {
     Nil,
@@ -47,8 +76,15 @@ pub enum PathType {
     Dot,
     Bracket,
     Query,
-    // Just used for the status bar.
+    // Like Query, but refers to specific array elements, e.g.,
+    // `.items[3].name`, so that it can be passed to jq.
+    Jq,
+    // Used to uniquely identify nodes across multiple documents, e.g.,
+    // when diffing a reloaded file.
     DotWithTopLevelIndex,
+    // Just used for the status bar. Like DotWithTopLevelIndex, but array
+    // indexes also include the length of the array, e.g., `items[42/1204]`.
+    StatusBar,
 }
 
 #[derive(Debug)]
@@ -56,12 +92,48 @@ pub struct FlatJson(
     pub Vec<Row>,
     // Single-line pretty printed version of the JSON.
     // Rows will contain references into this.
-    pub String,
+    pub Document,
     // Max nesting depth.
     pub usize,
+    // Runs of identical elements of Arrays, when they're being folded.
+    Runs,
 );
 
+// Runs of identical primitive elements of Arrays, e.g., 500 `0`s in a row,
+// keyed by the index of their first element. A folded run is shown as a
+// single row, e.g., `0 ×500`, and the rest of its elements are skipped
+// over, like the contents of a collapsed container.
+#[derive(Debug, Default)]
+struct Runs {
+    by_first: BTreeMap<Index, Run>,
+}
+
+#[derive(Debug)]
+struct Run {
+    last: Index,
+    folded: bool,
+}
+
 impl FlatJson {
+    pub fn new(rows: Vec<Row>, pretty: String, depth: usize) -> FlatJson {
+        let spill = has_string_at_least(&rows, SPILL_THRESHOLD);
+        FlatJson::from_document(rows, Document::new(pretty, spill), depth)
+    }
+
+    pub fn from_document(rows: Vec<Row>, document: Document, depth: usize) -> FlatJson {
+        FlatJson(rows, document, depth, Runs::default())
+    }
+
+    // Add more top-level values to the end of the document, e.g., records
+    // of newline-delimited JSON that were read after it was first shown.
+    pub fn append(&mut self, rows: Vec<Row>, pretty: &str, depth: usize) {
+        let spill = has_string_at_least(&rows, SPILL_THRESHOLD);
+        let text_offset = self.1.len() + 1;
+        append_top_level_values(&mut self.0, rows, text_offset);
+        self.1.append(&format!("\n{pretty}"), spill);
+        self.2 = self.2.max(depth);
+    }
+
     pub fn last_visible_index(&self) -> Index {
         let last_index = self.0.len() - 1;
 
@@ -81,7 +153,7 @@ impl FlatJson {
             let row = &self.0[last_index];
 
             if row.is_primitive() {
-                return last_index;
+                return self.start_of_folded_run(last_index).unwrap_or(last_index);
             }
 
             if row.is_closing_of_container() && row.is_collapsed() {
@@ -101,6 +173,8 @@ impl FlatJson {
 
         if row.is_closing_of_container() && row.is_collapsed() {
             row.pair_index()
+        } else if let Some(start) = self.start_of_folded_run(index - 1) {
+            OptionIndex::Index(start)
         } else {
             OptionIndex::Index(index - 1)
         }
@@ -111,6 +185,10 @@ impl FlatJson {
         if self.0[index].is_opening_of_container() && self.0[index].is_collapsed() {
             index = self.0[index].pair_index().unwrap();
         }
+        // Likewise for the start of a folded run.
+        if let Some(run) = self.3.by_first.get(&index).filter(|run| run.folded) {
+            index = run.last;
+        }
 
         // We can always go to the next row, unless we're at the end of the file.
         if index == self.0.len() - 1 {
@@ -166,7 +244,7 @@ impl FlatJson {
     }
 
     pub fn first_visible_ancestor(&self, mut index: Index) -> Index {
-        let mut visible_ancestor = index;
+        let mut visible_ancestor = self.start_of_folded_run(index).unwrap_or(index);
         while let OptionIndex::Index(parent) = self[index].parent {
             if self[parent].is_collapsed() {
                 visible_ancestor = parent;
@@ -176,6 +254,88 @@ impl FlatJson {
         visible_ancestor
     }
 
+    // Find runs of identical primitive elements in Arrays, and fold them.
+    pub fn fold_runs(&mut self) {
+        self.3 = Runs::default();
+        self.fold_new_runs(0);
+    }
+
+    // Fold runs of identical elements starting at or after the given row,
+    // e.g., in records that were just appended.
+    pub fn fold_new_runs(&mut self, first_row: Index) {
+        let mut runs = vec![];
+        let mut index = first_row;
+        while index < self.0.len() {
+            let first = index;
+            let row = &self.0[first];
+            let in_array =
+                matches!(row.parent, OptionIndex::Index(parent) if self.0[parent].is_array());
+            if row.is_primitive() && in_array {
+                let value = &self.1[row.range.clone()];
+                while let OptionIndex::Index(next) = self.0[index].next_sibling {
+                    let next_row = &self.0[next];
+                    if next != index + 1
+                        || !next_row.is_primitive()
+                        || &self.1[next_row.range.clone()] != value
+                    {
+                        break;
+                    }
+                    index = next;
+                }
+                if index + 1 - first >= MIN_FOLDED_RUN_LENGTH {
+                    runs.push((first, index));
+                }
+            }
+            index += 1;
+        }
+
+        for (first, last) in runs {
+            self.3.by_first.insert(first, Run { last, folded: true });
+        }
+    }
+
+    pub fn unfold_runs(&mut self) {
+        self.3 = Runs::default();
+    }
+
+    // If the row is the start of a folded run, the number of elements in it.
+    pub fn folded_run_length(&self, index: Index) -> Option<usize> {
+        self.3
+            .by_first
+            .get(&index)
+            .filter(|run| run.folded)
+            .map(|run| run.last - index + 1)
+    }
+
+    // If the row is hidden inside of a folded run, the start of the run.
+    pub fn start_of_folded_run(&self, index: Index) -> Option<Index> {
+        let (&first, run) = self.3.by_first.range(..=index).next_back()?;
+        if run.folded && first < index && index <= run.last {
+            Some(first)
+        } else {
+            None
+        }
+    }
+
+    pub fn unfold_run_containing(&mut self, index: Index) {
+        if let Some((_, run)) = self.3.by_first.range_mut(..=index).next_back() {
+            if index <= run.last {
+                run.folded = false;
+            }
+        }
+    }
+
+    // Fold or unfold the run that the row is a part of, if it is, and
+    // return the start of the run.
+    pub fn toggle_run_folded(&mut self, index: Index) -> Option<Index> {
+        let (&first, run) = self.3.by_first.range_mut(..=index).next_back()?;
+        if index > run.last {
+            return None;
+        }
+        run.folded = !run.folded;
+        Some(first)
+    }
+
     pub fn build_path_to_node(&self, path_type: PathType, index: Index) -> Result<String, String> {
         let mut buf = String::new();
 
@@ -185,40 +345,115 @@ impl FlatJson {
                 PathType::Dot | PathType::Bracket => {
                     return Err("Cannot build path to top-level element".to_string());
                 }
-                PathType::Query => {
+                PathType::Query | PathType::Jq => {
                     return Ok(".".to_string());
                 }
-                PathType::DotWithTopLevelIndex => { /* Handled in impl */ }
+                PathType::DotWithTopLevelIndex | PathType::StatusBar => { /* Handled in impl */ }
+            }
+        }
+
+        self.build_path_to_node_impl(path_type, index, OptionIndex::Nil, &mut buf)?;
+        Ok(buf)
+    }
+
+    // Build a Dot path from a node to one of its descendants, e.g.,
+    // `.items[3].config` from `.data` to `.data.items[3].config`.
+    pub fn build_relative_path(&self, ancestor: Index, index: Index) -> Result<String, String> {
+        let ancestor = self.opening_index(ancestor);
+        let index = self.opening_index(index);
+        if ancestor == index {
+            return Err("Focused node is the marked node".to_string());
+        }
+
+        match self.common_ancestor(ancestor, index) {
+            OptionIndex::Index(common_ancestor) if common_ancestor == ancestor => {}
+            OptionIndex::Index(common_ancestor) if self[common_ancestor].parent.is_some() => {
+                return Err(format!(
+                    "Focused node isn't inside the marked node; both are inside {}",
+                    self.build_path_to_node(PathType::Dot, common_ancestor)?
+                ));
             }
+            _ => return Err("Focused node isn't inside the marked node".to_string()),
         }
 
-        self.build_path_to_node_impl(path_type, index, &mut buf)?;
+        let mut buf = String::new();
+        self.build_path_to_node_impl(PathType::Dot, index, OptionIndex::Index(ancestor), &mut buf)?;
         Ok(buf)
     }
 
+    // The closest node that both nodes are inside of (or are themselves),
+    // or Nil if they're in different top-level values.
+    pub fn common_ancestor(&self, a: Index, b: Index) -> OptionIndex {
+        let mut a = self.opening_index(a);
+        let mut b = self.opening_index(b);
+
+        while self[a].depth > self[b].depth {
+            a = self[a].parent.unwrap();
+        }
+        while self[b].depth > self[a].depth {
+            b = self[b].parent.unwrap();
+        }
+
+        while a != b {
+            match (self[a].parent, self[b].parent) {
+                (OptionIndex::Index(a_parent), OptionIndex::Index(b_parent)) => {
+                    a = a_parent;
+                    b = b_parent;
+                }
+                _ => return OptionIndex::Nil,
+            }
+        }
+
+        OptionIndex::Index(a)
+    }
+
+    fn opening_index(&self, index: Index) -> Index {
+        if self[index].is_closing_of_container() {
+            self[index].pair_index().unwrap()
+        } else {
+            index
+        }
+    }
+
+    // Writes the path to a node, starting from the top-level value, or, if
+    // given, from one of the node's ancestors.
     fn build_path_to_node_impl(
         &self,
         path_type: PathType,
         index: Index,
+        ancestor: OptionIndex,
         buf: &mut String,
     ) -> Result<(), String> {
         let row = &self[index];
 
         if row.is_closing_of_container() {
-            return self.build_path_to_node_impl(path_type, row.pair_index().unwrap(), buf);
+            return self.build_path_to_node_impl(
+                path_type,
+                row.pair_index().unwrap(),
+                ancestor,
+                buf,
+            );
         }
 
         if let OptionIndex::Index(parent_index) = row.parent {
-            self.build_path_to_node_impl(path_type, parent_index, buf)?;
+            if row.parent != ancestor {
+                self.build_path_to_node_impl(path_type, parent_index, ancestor, buf)?;
+            }
         }
 
         let res = if let Some(key_range) = &row.key_range {
             let key_open_delimiter = &self.1[key_range.start..key_range.start + 1];
             let key = &self.1[key_range.start + 1..key_range.end - 1];
 
-            // For non-string keys in YAML.
-            if key_open_delimiter == "[" {
-                if path_type == PathType::Query {
+            // Scalar YAML keys, i.e., booleans, numbers and null, become
+            // strings when converted to JSON, and yq looks keys up by their
+            // string form too, so they're always written as quoted strings,
+            // e.g., `["1"]` or `["true"]`.
+            let is_yaml_scalar_key = yaml_scalar_key(&self.1[key_range.clone()]).is_some();
+
+            // For other non-string keys in YAML, e.g., `[1, 2]`.
+            if key_open_delimiter == "[" && !is_yaml_scalar_key {
+                if matches!(path_type, PathType::Query | PathType::Jq) {
                     return Err(
                         "Path to node contains non-string keys not supported in JSON".to_string(),
                     );
@@ -226,10 +461,15 @@ impl FlatJson {
 
                 write!(buf, "[{key}]")
             } else {
-                if path_type != PathType::Bracket && lineprinter::JS_IDENTIFIER.is_match(key) {
+                // Unlike JavaScript, jq doesn't allow '$' in identifiers.
+                let is_identifier = lineprinter::JS_IDENTIFIER.is_match(key)
+                    && !(path_type == PathType::Jq && key.contains('$'))
+                    && !is_yaml_scalar_key;
+
+                if path_type != PathType::Bracket && is_identifier {
                     write!(buf, ".{key}")
                 } else {
-                    if path_type == PathType::Query && row.depth == 1 {
+                    if matches!(path_type, PathType::Query | PathType::Jq) && row.depth == 1 {
                         // Handle square brackets as the first part of the path.
                         write!(buf, ".[\"{key}\"]")
                     } else {
@@ -242,8 +482,10 @@ impl FlatJson {
                 // We only print the top level index for this PathType,
                 // but we don't print it out if there's only a single
                 // top-level element.
-                if path_type == PathType::DotWithTopLevelIndex
-                    && (index != 0 || row.next_sibling.is_some())
+                if matches!(
+                    path_type,
+                    PathType::DotWithTopLevelIndex | PathType::StatusBar
+                ) && (index != 0 || row.next_sibling.is_some())
                 {
                     write!(buf, "[{}]", row.index_in_parent)
                 } else {
@@ -259,6 +501,14 @@ impl FlatJson {
                             write!(buf, "[]")
                         }
                     }
+                    PathType::Jq if row.depth == 1 => {
+                        // Handle square brackets as the first part of the path.
+                        write!(buf, ".[{}]", row.index_in_parent)
+                    }
+                    PathType::StatusBar => {
+                        let array_len = self.num_children(row.parent.unwrap());
+                        write!(buf, "[{}/{array_len}]", row.index_in_parent)
+                    }
                     _ => write!(buf, "[{}]", row.index_in_parent),
                 }
             }
@@ -267,6 +517,526 @@ impl FlatJson {
         res.map_err(|e| e.to_string())
     }
 
+    // The number of children in a non-empty container, given the index of
+    // either its opening or closing row.
+    pub fn num_children(&self, index: Index) -> usize {
+        let row = &self[index];
+        let close_index = if row.is_opening_of_container() {
+            row.pair_index().unwrap()
+        } else {
+            index
+        };
+        let last_child = self[close_index].last_child().unwrap();
+        self[last_child].index_in_parent + 1
+    }
+
+    // The number of rows nested inside a container, given the index of its
+    // opening row.
+    pub fn num_descendants(&self, index: Index) -> usize {
+        match self[index].pair_index() {
+            OptionIndex::Index(close_index) => close_index - index - 1,
+            OptionIndex::Nil => 0,
+        }
+    }
+
+    // The child of a container with the most descendants, given the index
+    // of its opening row. Ties go to the earliest child.
+    pub fn largest_child(&self, index: Index) -> OptionIndex {
+        let mut largest = OptionIndex::Nil;
+        let mut largest_size = 0;
+        let mut child = self[index].first_child();
+        while let OptionIndex::Index(i) = child {
+            let size = self.num_descendants(i);
+            if largest.is_nil() || size > largest_size {
+                largest = OptionIndex::Index(i);
+                largest_size = size;
+            }
+            child = self[i].next_sibling;
+        }
+        largest
+    }
+
+    // The longest string nested anywhere inside a container, given the index
+    // of its opening row. Ties go to the earliest string.
+    pub fn longest_string(&self, index: Index) -> OptionIndex {
+        let mut longest = OptionIndex::Nil;
+        let mut longest_len = 0;
+        for i in index + 1..index + 1 + self.num_descendants(index) {
+            let row = &self[i];
+            if row.is_string() && (longest.is_nil() || row.range.len() > longest_len) {
+                longest = OptionIndex::Index(i);
+                longest_len = row.range.len();
+            }
+        }
+        longest
+    }
+
+    // Collapse every container except those along the "spine" of each
+    // top-level value: the chain of containers that each have a single
+    // child, e.g., { "data": { "result": { "a": 1, "b": 2 } } }, down to
+    // the first one with more than one child. Returns where the spine of
+    // the first top-level value ends.
+    pub fn expand_spine(&mut self) -> Index {
+        for i in 0..self.0.len() {
+            if self[i].is_opening_of_container() {
+                self.collapse(i);
+            }
+        }
+
+        let end_of_first_spine = self.expand_spine_from(0);
+        let mut top_level_value = self[0].next_sibling;
+        while let OptionIndex::Index(i) = top_level_value {
+            self.expand_spine_from(i);
+            top_level_value = self[i].next_sibling;
+        }
+        end_of_first_spine
+    }
+
+    fn expand_spine_from(&mut self, mut index: Index) -> Index {
+        while self[index].is_opening_of_container() {
+            self.expand(index);
+            let only_child = self[index].first_child().unwrap();
+            if self[only_child].next_sibling.is_some() {
+                break;
+            }
+            index = only_child;
+        }
+        index
+    }
+
+    // Whether the document contains NaN, Infinity or -Infinity, which
+    // aren't valid JSON.
+    pub fn has_non_finite_numbers(&self) -> bool {
+        self.0
+            .iter()
+            .any(|row| row.value.number_type() == Some(NumberType::NonFinite))
+    }
+
+    // The opening rows of every collapsed container.
+    pub fn collapsed_containers(&self) -> Vec<Index> {
+        (0..self.0.len())
+            .filter(|&i| self[i].is_opening_of_container() && self[i].is_collapsed())
+            .collect()
+    }
+
+    // Collapse exactly the given containers (identified by their opening
+    // rows), and expand all the others.
+    pub fn set_collapsed_containers(&mut self, collapsed: &[Index]) {
+        for i in 0..self.0.len() {
+            if self[i].is_opening_of_container() {
+                self.expand(i);
+            }
+        }
+        for &i in collapsed {
+            self.collapse(i);
+        }
+    }
+
+    // The next row, after the given one, whose value is of the given type,
+    // wrapping around to the start of the document. Containers only match
+    // on their opening row.
+    pub fn next_of_type(&self, value_type: ValueType, after: Index) -> OptionIndex {
+        let len = self.0.len();
+        (1..=len)
+            .map(|offset| (after + offset) % len)
+            .find(|&i| self[i].value.is_of_type(value_type))
+            .map_or(OptionIndex::Nil, OptionIndex::Index)
+    }
+
+    // The nth child of a container, given the index of its opening row.
+    //
+    // Every row inside the container belongs to exactly one of its children,
+    // and the children appear in order, so this binary searches the rows for
+    // the first one belonging to the nth child, which is that child's own
+    // (opening) row. Finding which child a row belongs to means walking up
+    // its parents, so this takes O(depth * log(rows)) time.
+    pub fn nth_child(&self, index: Index, n: usize) -> OptionIndex {
+        let OptionIndex::Index(first_child) = self[index].first_child() else {
+            return OptionIndex::Nil;
+        };
+        let close_index = self[index].pair_index().unwrap();
+
+        let child_containing = |mut row: Index| {
+            while self[row].parent != OptionIndex::Index(index) {
+                row = self[row].parent.unwrap();
+            }
+            row
+        };
+        let (mut low, mut high) = (first_child, close_index);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self[child_containing(mid)].index_in_parent < n {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+
+        if low < close_index {
+            OptionIndex::Index(low)
+        } else {
+            OptionIndex::Nil
+        }
+    }
+
+    // Replace a primitive value with a new one, given as a JSON token, e.g.,
+    // `42` or `"text"`. The pretty printed document is rewritten, and the
+    // ranges of every later row, as well as of the containers the value is
+    // in, are shifted by the difference in length.
+    pub fn replace_value(&mut self, index: Index, token: &str) -> Result<(), String> {
+        if !self[index].is_primitive() {
+            return Err("Only primitive values can be edited".to_string());
+        }
+
+        let invalid = || {
+            format!("Invalid value: {token}; expected a string, number, boolean, null, [] or {{}}")
+        };
+        let (rows, pretty, _) = jsonparser::parse(token).map_err(|_| invalid())?;
+        if rows.len() != 1 || rows[0].is_container() {
+            return Err(invalid());
+        }
+        let value = rows.into_iter().next().unwrap().value;
+
+        let range = self[index].range.clone();
+        let shift = pretty.len() as isize - range.len() as isize;
+        let text = [&self.1[..range.start], &pretty, &self.1[range.end..]].concat();
+
+        self.0[index].range = range.start..range.start + pretty.len();
+        self.0[index].value = value;
+        for row in self.0[index + 1..].iter_mut() {
+            row.range = shift_range(&row.range, shift);
+            row.key_range = row.key_range.as_ref().map(|r| shift_range(r, shift));
+        }
+        let mut parent = self[index].parent;
+        while let OptionIndex::Index(open) = parent {
+            let open_range = &mut self.0[open].range;
+            *open_range = open_range.start..(open_range.end as isize + shift) as usize;
+            parent = self[open].parent;
+        }
+
+        // The value may no longer be identical to the rest of its run.
+        if let Some((&first, run)) = self.3.by_first.range(..=index).next_back() {
+            if index <= run.last {
+                self.3.by_first.remove(&first);
+            }
+        }
+
+        let spill = matches!(self.1, Document::Spilled(_));
+        self.1 = Document::new(text, spill);
+
+        Ok(())
+    }
+
+    // Remove a value, along with its key and all of its descendants, given
+    // the index of its opening row, and return a function that maps the old
+    // index of each row to its new index, or None if it was removed, along
+    // with what was removed, so that it can be put back by restore_value.
+    //
+    // Later elements of an Array are shifted down, so their indexes stay
+    // contiguous, and a container whose only child is removed becomes empty.
+    // Like with replace_value, the pretty printed document is rewritten.
+    pub fn delete_value(
+        &mut self,
+        index: Index,
+    ) -> Result<(impl Fn(Index) -> Option<Index> + Copy, DeletedValue), String> {
+        let row = &self[index];
+        let (parent, prev, next) = (row.parent, row.prev_sibling, row.next_sibling);
+        let index_in_parent = row.index_in_parent;
+        let end = match row.value {
+            Value::OpenContainer { close_index, .. } => close_index + 1,
+            _ => index + 1,
+        };
+        if parent.is_nil() && prev.is_nil() && next.is_nil() {
+            return Err("Can't delete the only value in the document".to_string());
+        }
+
+        // Remove the separator before the value, or, if it's the first
+        // child, the one after it. An only child takes the closing row of
+        // its container with it.
+        let start_of = |i: Index| match &self[i].key_range {
+            Some(key_range) => key_range.start,
+            None => self[i].range.start,
+        };
+        let (text_cut, removed_end) = match (prev, next) {
+            (OptionIndex::Index(prev), _) => (self[prev].range.end..row.range.end, end),
+            (OptionIndex::Nil, OptionIndex::Index(next)) => (start_of(index)..start_of(next), end),
+            (OptionIndex::Nil, OptionIndex::Nil) => {
+                let container_range = &self[parent.unwrap()].range;
+                (container_range.start + 1..container_range.end - 1, end + 1)
+            }
+        };
+        let shift = -(text_cut.len() as isize);
+        let removed_text = self.1[text_cut.clone()].to_string();
+        let text = [&self.1[..text_cut.start], &self.1[text_cut.end..]].concat();
+
+        let remap = move |i: Index| {
+            if i < index {
+                Some(i)
+            } else if i < removed_end {
+                None
+            } else {
+                Some(i - (removed_end - index))
+            }
+        };
+
+        if let OptionIndex::Index(prev) = prev {
+            self.0[prev].next_sibling = next;
+        }
+        if let OptionIndex::Index(next) = next {
+            self.0[next].prev_sibling = prev;
+        }
+        for (mut sibling, forward) in [(prev, false), (next, true)] {
+            while let OptionIndex::Index(i) = sibling {
+                if self[i].index_in_parent > index_in_parent {
+                    self.0[i].index_in_parent -= 1;
+                }
+                sibling = if forward {
+                    self[i].next_sibling
+                } else {
+                    self[i].prev_sibling
+                };
+            }
+        }
+
+        let mut parent_value = None;
+        if let OptionIndex::Index(parent) = parent {
+            let close_index = self[parent].pair_index().unwrap();
+            let container_type = match self[parent].value {
+                Value::OpenContainer { container_type, .. } => container_type,
+                _ => unreachable!(),
+            };
+            if prev.is_nil() && next.is_nil() {
+                let empty = match container_type {
+                    ContainerType::Object => Value::EmptyObject,
+                    ContainerType::Array => Value::EmptyArray,
+                };
+                parent_value = Some(std::mem::replace(&mut self.0[parent].value, empty));
+            } else {
+                if let Value::OpenContainer { first_child, .. } = &mut self.0[parent].value {
+                    if *first_child == index {
+                        *first_child = next.unwrap();
+                    }
+                }
+                if let Value::CloseContainer { last_child, .. } = &mut self.0[close_index].value {
+                    if *last_child == index {
+                        *last_child = prev.unwrap();
+                    }
+                }
+            }
+        }
+        let mut ancestor = parent;
+        while let OptionIndex::Index(open) = ancestor {
+            let open_range = &mut self.0[open].range;
+            *open_range = open_range.start..(open_range.end as isize + shift) as usize;
+            ancestor = self[open].parent;
+        }
+
+        let removed_rows = self.0.drain(index..removed_end).collect();
+        for (i, row) in self.0.iter_mut().enumerate() {
+            row.remap_indexes(|old| remap(old).unwrap());
+            if i >= index {
+                row.range = shift_range(&row.range, shift);
+                row.key_range = row.key_range.as_ref().map(|r| shift_range(r, shift));
+            }
+        }
+
+        // Runs that lost an element are unfolded.
+        self.3.by_first = std::mem::take(&mut self.3.by_first)
+            .into_iter()
+            .filter(|(first, run)| run.last < index || *first >= removed_end)
+            .map(|(first, run)| {
+                let last = remap(run.last).unwrap();
+                (remap(first).unwrap(), Run { last, ..run })
+            })
+            .collect();
+
+        let spill = matches!(self.1, Document::Spilled(_));
+        self.1 = Document::new(text, spill);
+
+        let deleted = DeletedValue {
+            index,
+            rows: removed_rows,
+            text_start: text_cut.start,
+            text: removed_text,
+            parent_value,
+        };
+        Ok((remap, deleted))
+    }
+
+    // Put back a value removed by delete_value, which must be the last
+    // change made to the document (other than appending records), and
+    // return a function that maps the old index of each row to its new
+    // index.
+    pub fn restore_value(&mut self, deleted: DeletedValue) -> impl Fn(Index) -> Index + Copy {
+        let DeletedValue {
+            index,
+            mut rows,
+            text_start,
+            text,
+            parent_value,
+        } = deleted;
+        let num_rows = rows.len();
+        let shift = text.len() as isize;
+        let remap = move |i: Index| if i < index { i } else { i + num_rows };
+
+        let (parent, prev) = (rows[0].parent, rows[0].prev_sibling);
+        let index_in_parent = rows[0].index_in_parent;
+        // The sibling after the value is looked up again, rather than kept
+        // around, in case records were appended after it was deleted.
+        let next = match (prev, parent) {
+            (OptionIndex::Index(prev), _) => self[prev].next_sibling,
+            (OptionIndex::Nil, OptionIndex::Index(parent)) => self[parent].first_child(),
+            (OptionIndex::Nil, OptionIndex::Nil) => OptionIndex::Index(index),
+        };
+
+        for (i, row) in self.0.iter_mut().enumerate() {
+            row.remap_indexes(remap);
+            if i >= index {
+                row.range = shift_range(&row.range, shift);
+                row.key_range = row.key_range.as_ref().map(|r| shift_range(r, shift));
+            }
+        }
+        let next = match next {
+            OptionIndex::Index(next) => OptionIndex::Index(remap(next)),
+            OptionIndex::Nil => OptionIndex::Nil,
+        };
+        rows[0].next_sibling = next;
+        self.0.splice(index..index, rows);
+
+        if let OptionIndex::Index(prev) = prev {
+            self.0[prev].next_sibling = OptionIndex::Index(index);
+        }
+        if let OptionIndex::Index(next) = next {
+            self.0[next].prev_sibling = OptionIndex::Index(index);
+        }
+        for (mut sibling, forward) in [(prev, false), (next, true)] {
+            while let OptionIndex::Index(i) = sibling {
+                if self[i].index_in_parent >= index_in_parent {
+                    self.0[i].index_in_parent += 1;
+                }
+                sibling = if forward {
+                    self[i].next_sibling
+                } else {
+                    self[i].prev_sibling
+                };
+            }
+        }
+
+        if let OptionIndex::Index(parent) = parent {
+            match parent_value {
+                Some(value) => self.0[parent].value = value,
+                None => {
+                    let close_index = self[parent].pair_index().unwrap();
+                    if let Value::OpenContainer { first_child, .. } = &mut self.0[parent].value {
+                        if prev.is_nil() {
+                            *first_child = index;
+                        }
+                    }
+                    if let Value::CloseContainer { last_child, .. } = &mut self.0[close_index].value
+                    {
+                        if next.is_nil() {
+                            *last_child = index;
+                        }
+                    }
+                }
+            }
+        }
+        let mut ancestor = parent;
+        while let OptionIndex::Index(open) = ancestor {
+            let open_range = &mut self.0[open].range;
+            *open_range = open_range.start..(open_range.end as isize + shift) as usize;
+            ancestor = self[open].parent;
+        }
+
+        self.3.by_first = std::mem::take(&mut self.3.by_first)
+            .into_iter()
+            .map(|(first, run)| {
+                let last = remap(run.last);
+                (remap(first), Run { last, ..run })
+            })
+            .collect();
+
+        let document = [&self.1[..text_start], &text, &self.1[text_start..]].concat();
+        let spill = matches!(self.1, Document::Spilled(_));
+        self.1 = Document::new(document, spill);
+
+        remap
+    }
+
+    // The document as JSON, but without any object entries, at any depth,
+    // whose keys match the given pattern. Top-level values are separated by
+    // newlines. Also returns the index in this document of each row that
+    // the filtered document will have, and the number of entries that were
+    // removed.
+    pub fn without_keys_matching(
+        &self,
+        pattern: &Regex,
+    ) -> Result<(String, Vec<Index>, usize), String> {
+        let mut buf = String::with_capacity(self.1.len());
+        let mut kept_rows = vec![];
+        let mut num_removed = 0;
+        // Whether the next value is the first one in its container, and so
+        // doesn't need a separator before it.
+        let mut is_first = true;
+
+        let mut index = 0;
+        while index < self.0.len() {
+            let row = &self[index];
+
+            if row.key_range.is_some() && pattern.is_match(&self.key_string(index)?) {
+                num_removed += 1;
+                index = match row.value {
+                    Value::OpenContainer { close_index, .. } => close_index + 1,
+                    _ => index + 1,
+                };
+                continue;
+            }
+
+            if let Value::CloseContainer { container_type, .. } = row.value {
+                buf.push_str(container_type.close_str());
+                // Containers whose children were all removed will just be
+                // a single row.
+                if !is_first {
+                    kept_rows.push(index);
+                }
+                is_first = false;
+                index += 1;
+                continue;
+            }
+
+            if !is_first {
+                buf.push_str(if row.parent.is_nil() { "\n" } else { ", " });
+            }
+
+            if let Some(key_range) = &row.key_range {
+                let key = &self.1[key_range.clone()];
+                if key.starts_with('"') {
+                    buf.push_str(key);
+                } else {
+                    buf.push('"');
+                    buf.push_str(&escape_json_string(&self.key_string(index)?));
+                    buf.push('"');
+                }
+                buf.push_str(": ");
+            }
+
+            kept_rows.push(index);
+            match row.value {
+                Value::OpenContainer { container_type, .. } => {
+                    buf.push_str(container_type.open_str());
+                    is_first = true;
+                }
+                _ => {
+                    buf.push_str(&self.1[row.range.clone()]);
+                    is_first = false;
+                }
+            }
+            index += 1;
+        }
+
+        Ok((buf, kept_rows, num_removed))
+    }
+
     pub fn pretty_printed(&self) -> Result<String, std::fmt::Error> {
         let mut buf = String::new();
 
@@ -301,13 +1071,68 @@ impl FlatJson {
         Ok(buf)
     }
 
+    // Write out every value in the document as a gron-style assignment,
+    // e.g., `json.items[0].name = "jless";`, so that the output can be
+    // easily grepped and diffed.
+    pub fn write_gron<W: std::io::Write>(&self, out: &mut W) -> std::io::Result<()> {
+        for (index, row) in self.0.iter().enumerate() {
+            let value = match &row.value {
+                Value::CloseContainer { .. } => continue,
+                Value::OpenContainer { container_type, .. } => match container_type {
+                    ContainerType::Object => "{}",
+                    ContainerType::Array => "[]",
+                },
+                _ => &self.1[row.range.clone()],
+            };
+
+            // This never fails for DotWithTopLevelIndex.
+            let path = self
+                .build_path_to_node(PathType::DotWithTopLevelIndex, index)
+                .unwrap();
+            writeln!(out, "json{path} = {value};")?;
+        }
+
+        Ok(())
+    }
+
     // A lot of the code here is almost identical to pretty_printed, but
     // there are some subtle enough differences, and the code isn't that
     // complicated, that I don't think it's worth it to try to have them
     // share an implementation.
+    // Pretty print the values of several rows as the elements of a single array.
+    pub fn pretty_printed_array(
+        &self,
+        indexes: impl IntoIterator<Item = Index>,
+    ) -> Result<String, std::fmt::Error> {
+        let mut elements = vec![];
+        for index in indexes {
+            let value = self.pretty_printed_value(index)?;
+            elements.push(format!("  {}", value.trim_end().replace('\n', "\n  ")));
+        }
+
+        if elements.is_empty() {
+            return Ok("[]\n".to_string());
+        }
+
+        Ok(format!("[\n{}\n]\n", elements.join(",\n")))
+    }
+
     pub fn pretty_printed_value(&self, value_index: Index) -> Result<String, std::fmt::Error> {
+        let pretty = self.pretty_printed_value_with_progress(value_index, |_, _| true)?;
+        Ok(pretty.unwrap())
+    }
+
+    // Like pretty_printed_value, but calls progress every so often with the
+    // number of rows printed so far and the total number of rows, which can
+    // stop printing a huge value by returning false, in which case this
+    // returns None.
+    pub fn pretty_printed_value_with_progress(
+        &self,
+        value_index: Index,
+        mut progress: impl FnMut(usize, usize) -> bool,
+    ) -> Result<Option<String>, std::fmt::Error> {
         if self[value_index].is_primitive() {
-            return Ok(self.1[self[value_index].range.clone()].to_string());
+            return Ok(Some(self.1[self[value_index].range.clone()].to_string()));
         }
 
         let mut buf = String::new();
@@ -322,6 +1147,13 @@ impl FlatJson {
         writeln!(buf, "{}", container_type.open_str())?;
 
         for index in start_index + 1..end_index {
+            let rows_printed = index - start_index;
+            if rows_printed % PROGRESS_INTERVAL_ROWS == 0
+                && !progress(rows_printed, end_index - start_index + 1)
+            {
+                return Ok(None);
+            }
+
             let row = &self[index];
             for _ in 0..(row.depth - depth_offset) {
                 write!(buf, "  ")?;
@@ -352,8 +1184,491 @@ impl FlatJson {
 
         writeln!(buf, "{}", container_type.close_str())?;
 
+        Ok(Some(buf))
+    }
+
+    // Serialize a value as JSON. For JSON input this is the same as
+    // pretty_printed_value, but input parsed from YAML can have keys that
+    // aren't strings, e.g., `[1, 2]`, which get converted to strings, and
+    // numbers that aren't valid JSON.
+    pub fn pretty_printed_value_as_json(&self, value_index: Index) -> Result<String, String> {
+        let row = &self[value_index];
+        if !row.is_container() {
+            return Ok(self.json_scalar(value_index));
+        }
+
+        let open_index = if row.is_opening_of_container() {
+            value_index
+        } else {
+            row.pair_index().unwrap()
+        };
+
+        let mut buf = String::new();
+        self.write_json_value(open_index, 0, &mut buf)?;
+        buf.push('\n');
         Ok(buf)
     }
+
+    fn write_json_value(
+        &self,
+        index: Index,
+        indentation: usize,
+        buf: &mut String,
+    ) -> Result<(), String> {
+        let row = &self[index];
+        let Some(container_type) = row.value.container_type() else {
+            buf.push_str(&self.json_scalar(index));
+            return Ok(());
+        };
+
+        buf.push_str(container_type.open_str());
+        buf.push('\n');
+
+        let mut next_child = row.first_child();
+        while let OptionIndex::Index(child) = next_child {
+            buf.push_str(&" ".repeat(indentation + 2));
+            if !row.is_array() {
+                buf.push('"');
+                buf.push_str(&escape_json_string(&self.key_string(child)?));
+                buf.push_str("\": ");
+            }
+            self.write_json_value(child, indentation + 2, buf)?;
+
+            next_child = self[child].next_sibling;
+            if next_child.is_some() {
+                buf.push(',');
+            }
+            buf.push('\n');
+        }
+
+        buf.push_str(&" ".repeat(indentation));
+        buf.push_str(container_type.close_str());
+        Ok(())
+    }
+
+    // Serialize a value compactly in a canonical form, with object keys
+    // sorted, numbers normalized (e.g., 1.0, 1e0 and 1 are all written as
+    // 1), and strings escaped consistently, so that values that only differ
+    // in formatting serialize the same way. Used to checksum values.
+    //
+    // Like RFC 8785 (JSON Canonicalization Scheme), keys are sorted by
+    // their UTF-16 code units, and strings only escape what JSON requires.
+    pub fn canonical_json(&self, value_index: Index) -> Result<String, String> {
+        let row = &self[value_index];
+        let index = if row.is_closing_of_container() {
+            row.pair_index().unwrap()
+        } else {
+            value_index
+        };
+
+        let mut buf = String::new();
+        self.write_canonical_json(index, &mut buf)?;
+        Ok(buf)
+    }
+
+    fn write_canonical_json(&self, index: Index, buf: &mut String) -> Result<(), String> {
+        let row = &self[index];
+        let Some(container_type) = row.value.container_type() else {
+            match row.value {
+                Value::String => {
+                    let string = &self.1[row.range.clone()];
+                    let unescaped =
+                        unescape_json_string(&string[1..string.len() - 1], ControlCharacters::Raw)
+                            .map_err(|err| format!("{err}"))?;
+                    write_canonical_string(&unescaped, buf);
+                }
+                Value::Number(_) => buf.push_str(&canonical_number(&self.json_scalar(index))),
+                _ => buf.push_str(&self.1[row.range.clone()]),
+            }
+            return Ok(());
+        };
+
+        let mut children = vec![];
+        let mut next_child = row.first_child();
+        while let OptionIndex::Index(child) = next_child {
+            let key = if row.is_array() {
+                String::new()
+            } else {
+                self.key_string(child)?
+            };
+            children.push((key, child));
+            next_child = self[child].next_sibling;
+        }
+        // Stable, so duplicate keys stay in the same order. Comparing UTF-16
+        // code units rather than chars puts characters outside of the Basic
+        // Multilingual Plane (surrogate pairs) before U+E000 to U+FFFF.
+        children.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+
+        buf.push_str(container_type.open_str());
+        for (i, (key, child)) in children.into_iter().enumerate() {
+            if i != 0 {
+                buf.push(',');
+            }
+            if !row.is_array() {
+                write_canonical_string(&key, buf);
+                buf.push(':');
+            }
+            self.write_canonical_json(child, buf)?;
+        }
+        buf.push_str(container_type.close_str());
+        Ok(())
+    }
+
+    // Convert a primitive value (or empty container) to JSON. Numbers that
+    // aren't valid JSON are normalized, and like JSON.stringify, infinities
+    // and NaN become null.
+    fn json_scalar(&self, index: Index) -> String {
+        let value = &self.1[self[index].range.clone()];
+        if !matches!(self[index].value, Value::Number(_)) || JSON_NUMBER.is_match(value) {
+            return value.to_string();
+        }
+
+        match value.parse::<f64>() {
+            Ok(n) if n.is_finite() => format!("{n:?}"),
+            Ok(_) => "null".to_string(),
+            // `.inf` and `.nan`, in various cases.
+            Err(_) => match value.trim_start_matches(['+', '-']).to_lowercase().as_str() {
+                ".inf" | ".nan" => "null".to_string(),
+                _ => format!("\"{}\"", escape_json_string(value)),
+            },
+        }
+    }
+
+    // Serialize a value as block-style YAML, preserving the order of
+    // object keys.
+    pub fn pretty_printed_value_as_yaml(&self, value_index: Index) -> Result<String, String> {
+        let row = &self[value_index];
+        if !row.is_container() {
+            return self.yaml_scalar(&self.1[row.range.clone()]);
+        }
+
+        let open_index = if row.is_opening_of_container() {
+            value_index
+        } else {
+            row.pair_index().unwrap()
+        };
+
+        let mut buf = String::new();
+        self.write_yaml_container_contents(open_index, 0, &mut buf)?;
+        Ok(buf)
+    }
+
+    fn write_yaml_container_contents(
+        &self,
+        open_index: Index,
+        indentation: usize,
+        buf: &mut String,
+    ) -> Result<(), String> {
+        let is_array = self[open_index].is_array();
+        let mut next_child = self[open_index].first_child();
+
+        while let OptionIndex::Index(child) = next_child {
+            let row = &self[child];
+            let line_start = buf.len();
+
+            if is_array && row.is_opening_of_container() {
+                // Print the nested container one level deeper, then replace
+                // the indentation of its first line with the "- ".
+                self.write_yaml_container_contents(child, indentation + 2, buf)?;
+                let dash_start = line_start + indentation;
+                buf.replace_range(dash_start..dash_start + 2, "- ");
+            } else {
+                buf.push_str(&" ".repeat(indentation));
+
+                if is_array {
+                    buf.push_str("- ");
+                } else {
+                    let key = &self.1[row.key_range.clone().unwrap()];
+                    if key.starts_with('"') {
+                        buf.push_str(&self.yaml_scalar(key)?);
+                    } else {
+                        // Non-string keys in YAML, e.g., "[1, 2]"; strip the
+                        // surrounding brackets and use the flow-style key.
+                        buf.push_str(&key[1..key.len() - 1]);
+                    }
+                    buf.push(':');
+                }
+
+                if row.is_opening_of_container() {
+                    buf.push('\n');
+                    self.write_yaml_container_contents(child, indentation + 2, buf)?;
+                } else {
+                    if !is_array {
+                        buf.push(' ');
+                    }
+                    buf.push_str(&self.yaml_scalar(&self.1[row.range.clone()])?);
+                    buf.push('\n');
+                }
+            }
+
+            next_child = row.next_sibling;
+        }
+
+        Ok(())
+    }
+
+    // Convert a primitive value (or empty container) to a YAML scalar.
+    // Strings are written without quotes when that is unambiguous; since
+    // JSON string escapes are also valid in double-quoted YAML strings,
+    // other strings can be output as is.
+    fn yaml_scalar(&self, value: &str) -> Result<String, String> {
+        if !value.starts_with('"') {
+            return Ok(value.to_string());
+        }
+
+        let unescaped = unescape_json_string(&value[1..value.len() - 1], ControlCharacters::Raw)
+            .map_err(|err| format!("{err}"))?;
+
+        if YAML_PLAIN_SCALAR.is_match(&unescaped)
+            && !YAML_NON_STRING_SCALAR.is_match(&unescaped)
+            && !unescaped.ends_with(' ')
+        {
+            Ok(unescaped)
+        } else {
+            Ok(value.to_string())
+        }
+    }
+
+    // Serialize an object as a TOML document, preserving the order of
+    // object keys where possible. Only objects can be TOML documents, and
+    // TOML has no equivalent of null.
+    pub fn pretty_printed_value_as_toml(&self, value_index: Index) -> Result<String, String> {
+        let open_index = match self[value_index].value {
+            Value::EmptyObject => return Ok(String::new()),
+            Value::OpenContainer {
+                container_type: ContainerType::Object,
+                ..
+            } => value_index,
+            Value::CloseContainer {
+                container_type: ContainerType::Object,
+                open_index,
+                ..
+            } => open_index,
+            _ => return Err("Only objects can be represented as TOML".to_string()),
+        };
+
+        let mut buf = String::new();
+        self.write_toml_table(open_index, &mut vec![], &mut buf)?;
+        Ok(buf)
+    }
+
+    // Write the key/value pairs of a table, and then its nested tables and
+    // arrays of tables, which have to come after all of the pairs, since
+    // their headers start new sections.
+    fn write_toml_table(
+        &self,
+        open_index: Index,
+        path: &mut Vec<String>,
+        buf: &mut String,
+    ) -> Result<(), String> {
+        let mut sections = vec![];
+        let mut next_child = self[open_index].first_child();
+
+        while let OptionIndex::Index(child) = next_child {
+            let key = self.toml_key(child)?;
+            if self.is_toml_table(child) || self.is_toml_array_of_tables(child) {
+                sections.push((child, key));
+            } else {
+                buf.push_str(&key);
+                buf.push_str(" = ");
+                self.write_toml_inline_value(child, buf)?;
+                buf.push('\n');
+            }
+            next_child = self[child].next_sibling;
+        }
+
+        for (child, key) in sections {
+            path.push(key);
+            let row = &self[child];
+
+            if self.is_toml_table(child) {
+                self.write_toml_header(&format!("[{}]", path.join(".")), buf);
+                if row.is_opening_of_container() {
+                    self.write_toml_table(child, path, buf)?;
+                }
+            } else {
+                let mut next_element = row.first_child();
+                while let OptionIndex::Index(element) = next_element {
+                    self.write_toml_header(&format!("[[{}]]", path.join(".")), buf);
+                    if self[element].is_opening_of_container() {
+                        self.write_toml_table(element, path, buf)?;
+                    }
+                    next_element = self[element].next_sibling;
+                }
+            }
+
+            path.pop();
+        }
+
+        Ok(())
+    }
+
+    fn write_toml_header(&self, header: &str, buf: &mut String) {
+        if !buf.is_empty() {
+            buf.push('\n');
+        }
+        buf.push_str(header);
+        buf.push('\n');
+    }
+
+    fn write_toml_inline_value(&self, index: Index, buf: &mut String) -> Result<(), String> {
+        let row = &self[index];
+        match row.value {
+            Value::Null => return Err("null can't be represented in TOML".to_string()),
+            Value::String => {
+                let value = &self.1[row.range.clone()];
+                let unescaped =
+                    unescape_json_string(&value[1..value.len() - 1], ControlCharacters::Raw)
+                        .map_err(|err| format!("{err}"))?;
+                buf.push_str(&toml_string(&unescaped));
+            }
+            Value::Boolean | Value::Number(_) | Value::EmptyArray | Value::EmptyObject => {
+                buf.push_str(&self.1[row.range.clone()]);
+            }
+            Value::OpenContainer { container_type, .. } => {
+                let is_object = matches!(container_type, ContainerType::Object);
+                buf.push_str(if is_object { "{ " } else { "[" });
+
+                let mut next_child = row.first_child();
+                while let OptionIndex::Index(child) = next_child {
+                    if is_object {
+                        buf.push_str(&self.toml_key(child)?);
+                        buf.push_str(" = ");
+                    }
+                    self.write_toml_inline_value(child, buf)?;
+
+                    next_child = self[child].next_sibling;
+                    if next_child.is_some() {
+                        buf.push_str(", ");
+                    }
+                }
+
+                buf.push_str(if is_object { " }" } else { "]" });
+            }
+            Value::CloseContainer { open_index, .. } => {
+                self.write_toml_inline_value(open_index, buf)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // The unescaped key of a row inside an object.
+    fn key_string(&self, index: Index) -> Result<String, String> {
+        let key = &self.1[self[index].key_range.clone().unwrap()];
+        // Non-string keys in YAML, e.g., "[1, 2]", are converted to strings.
+        if key.starts_with('"') {
+            unescape_json_string(&key[1..key.len() - 1], ControlCharacters::Raw)
+                .map_err(|err| format!("{err}"))
+        } else {
+            Ok(key[1..key.len() - 1].to_string())
+        }
+    }
+
+    fn toml_key(&self, index: Index) -> Result<String, String> {
+        let key = self.key_string(index)?;
+        if TOML_BARE_KEY.is_match(&key) {
+            Ok(key)
+        } else {
+            Ok(toml_string(&key))
+        }
+    }
+
+    fn is_toml_table(&self, index: Index) -> bool {
+        matches!(
+            self[index].value,
+            Value::EmptyObject
+                | Value::OpenContainer {
+                    container_type: ContainerType::Object,
+                    ..
+                }
+        )
+    }
+
+    fn is_toml_array_of_tables(&self, index: Index) -> bool {
+        let row = &self[index];
+        if !row.is_array() {
+            return false;
+        }
+
+        let mut next_child = row.first_child();
+        while let OptionIndex::Index(child) = next_child {
+            if !self.is_toml_table(child) {
+                return false;
+            }
+            next_child = self[child].next_sibling;
+        }
+        true
+    }
+}
+
+// Integers are kept as is, since they may be too large to represent
+// exactly as floats, but other numbers are written as integers when they
+// are whole numbers that can be represented exactly, and otherwise in their
+// shortest form that round-trips.
+fn canonical_number(number: &str) -> String {
+    const MAX_EXACT_INTEGER: f64 = (1u64 << 53) as f64;
+
+    if !number.contains(['.', 'e', 'E']) {
+        return if number == "-0" {
+            "0".to_string()
+        } else {
+            number.to_string()
+        };
+    }
+
+    match number.parse::<f64>() {
+        Ok(n) if n.fract() == 0.0 && n.abs() <= MAX_EXACT_INTEGER => format!("{}", n as i64),
+        Ok(n) if n.is_finite() => format!("{n:?}"),
+        // Not a number, e.g., null for YAML's .inf.
+        _ => number.to_string(),
+    }
+}
+
+// Write a string as JSON the way RFC 8785 does: only quotes, backslashes
+// and control characters below U+0020 are escaped, using the short forms
+// where there are any, and lowercase hex digits otherwise.
+fn write_canonical_string(s: &str, buf: &mut String) {
+    buf.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => buf.push_str("\\\""),
+            '\\' => buf.push_str("\\\\"),
+            '\u{8}' => buf.push_str("\\b"),
+            '\u{c}' => buf.push_str("\\f"),
+            '\n' => buf.push_str("\\n"),
+            '\r' => buf.push_str("\\r"),
+            '\t' => buf.push_str("\\t"),
+            '\x00'..='\x1f' => {
+                let _ = write!(buf, "\\u{:04x}", ch as u32);
+            }
+            ch => buf.push(ch),
+        }
+    }
+    buf.push('"');
+}
+
+// Convert a string to a TOML basic string. These support almost the same
+// escapes as JSON, but not "\/" or surrogate pairs, so JSON strings need
+// to be re-escaped.
+fn toml_string(s: &str) -> String {
+    let mut buf = String::with_capacity(s.len() + 2);
+    buf.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => buf.push_str("\\\""),
+            '\\' => buf.push_str("\\\\"),
+            '\n' => buf.push_str("\\n"),
+            '\r' => buf.push_str("\\r"),
+            '\t' => buf.push_str("\\t"),
+            ch if ch.is_control() => {
+                let _ = write!(buf, "\\u{:04X}", ch as u32);
+            }
+            ch => buf.push(ch),
+        }
+    }
+    buf.push('"');
+    buf
 }
 
 impl std::ops::Index<usize> for FlatJson {
@@ -370,6 +1685,18 @@ impl std::ops::IndexMut<usize> for FlatJson {
     }
 }
 
+// A value removed from the document by delete_value: its rows and text,
+// as they were before it was removed, and, if it was the only child of its
+// container, what the container was before it became empty.
+#[derive(Debug)]
+pub struct DeletedValue {
+    pub index: Index,
+    rows: Vec<Row>,
+    text_start: usize,
+    text: String,
+    parent_value: Option<Value>,
+}
+
 #[derive(Debug)]
 pub struct Row {
     pub parent: OptionIndex,
@@ -436,6 +1763,35 @@ impl Row {
     // single opening character.
     //
     // This also includes the key range for objects.
+    fn remap_indexes(&mut self, remap: impl Fn(Index) -> Index) {
+        let remap_option = |i: OptionIndex| match i {
+            OptionIndex::Index(i) => OptionIndex::Index(remap(i)),
+            OptionIndex::Nil => OptionIndex::Nil,
+        };
+        self.parent = remap_option(self.parent);
+        self.prev_sibling = remap_option(self.prev_sibling);
+        self.next_sibling = remap_option(self.next_sibling);
+        match &mut self.value {
+            Value::OpenContainer {
+                first_child,
+                close_index,
+                ..
+            } => {
+                *first_child = remap(*first_child);
+                *close_index = remap(*close_index);
+            }
+            Value::CloseContainer {
+                last_child,
+                open_index,
+                ..
+            } => {
+                *last_child = remap(*last_child);
+                *open_index = remap(*open_index);
+            }
+            _ => {}
+        }
+    }
+
     pub fn range_represented_by_row(&self) -> Range<usize> {
         let start = match &self.key_range {
             Some(key_range) => key_range.start,
@@ -452,6 +1808,10 @@ impl Row {
     }
 }
 
+fn shift_range(range: &Range<usize>, shift: isize) -> Range<usize> {
+    (range.start as isize + shift) as usize..(range.end as isize + shift) as usize
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum ContainerType {
     Object,
@@ -481,11 +1841,70 @@ impl ContainerType {
     }
 }
 
+// Whether a number was written as an integer, or with a fractional part
+// or exponent, or was one of the non-standard NaN, Infinity or -Infinity
+// literals, which are only accepted with --allow-nan.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum NumberType {
+    Integer,
+    Float,
+    NonFinite,
+}
+
+impl NumberType {
+    pub fn of_literal(number: &str) -> NumberType {
+        if number.ends_with(['N', 'y']) {
+            NumberType::NonFinite
+        } else if number.contains(['.', 'e', 'E']) {
+            NumberType::Float
+        } else {
+            NumberType::Integer
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            NumberType::Integer => "int",
+            NumberType::Float => "float",
+            NumberType::NonFinite => "non-finite",
+        }
+    }
+}
+
+// The types of values that can be searched for using :findtype.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ValueType {
+    Null,
+    Boolean,
+    Number,
+    Integer,
+    Float,
+    String,
+    Array,
+    Object,
+}
+
+impl ValueType {
+    pub fn parse(name: &str) -> Option<ValueType> {
+        match name {
+            "null" => Some(ValueType::Null),
+            "bool" | "boolean" => Some(ValueType::Boolean),
+            "number" => Some(ValueType::Number),
+            "int" | "integer" => Some(ValueType::Integer),
+            "float" => Some(ValueType::Float),
+            "string" => Some(ValueType::String),
+            "array" => Some(ValueType::Array),
+            "object" => Some(ValueType::Object),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Value {
     Null,
     Boolean,
-    Number,
+    Number(NumberType),
     String,
     EmptyObject,
     EmptyArray,
@@ -519,6 +1938,41 @@ impl Value {
         matches!(self, Value::String)
     }
 
+    pub fn number_type(&self) -> Option<NumberType> {
+        match self {
+            Value::Number(number_type) => Some(*number_type),
+            _ => None,
+        }
+    }
+
+    pub fn is_of_type(&self, value_type: ValueType) -> bool {
+        matches!(
+            (value_type, self),
+            (ValueType::Null, Value::Null)
+                | (ValueType::Boolean, Value::Boolean)
+                | (ValueType::Number, Value::Number(_))
+                | (ValueType::Integer, Value::Number(NumberType::Integer))
+                | (ValueType::Float, Value::Number(NumberType::Float))
+                | (ValueType::String, Value::String)
+                | (ValueType::Array, Value::EmptyArray)
+                | (ValueType::Object, Value::EmptyObject)
+                | (
+                    ValueType::Array,
+                    Value::OpenContainer {
+                        container_type: ContainerType::Array,
+                        ..
+                    }
+                )
+                | (
+                    ValueType::Object,
+                    Value::OpenContainer {
+                        container_type: ContainerType::Object,
+                        ..
+                    }
+                )
+        )
+    }
+
     pub fn container_type(&self) -> Option<ContainerType> {
         match self {
             Value::OpenContainer { container_type, .. } => Some(*container_type),
@@ -582,43 +2036,249 @@ impl Value {
             } => *collapsed = val,
             _ => {}
         }
-    }
+    }
+
+    fn first_child(&self) -> OptionIndex {
+        match self {
+            Value::OpenContainer { first_child, .. } => OptionIndex::Index(*first_child),
+            _ => OptionIndex::Nil,
+        }
+    }
+
+    fn last_child(&self) -> OptionIndex {
+        match self {
+            Value::CloseContainer { last_child, .. } => OptionIndex::Index(*last_child),
+            _ => OptionIndex::Nil,
+        }
+    }
+
+    fn pair_index(&self) -> OptionIndex {
+        match self {
+            Value::OpenContainer { close_index, .. } => OptionIndex::Index(*close_index),
+            Value::CloseContainer { open_index, .. } => OptionIndex::Index(*open_index),
+            _ => OptionIndex::Nil,
+        }
+    }
+}
+
+pub fn parse_top_level_json(json: String) -> Result<FlatJson, String> {
+    parse_top_level_json_allowing_nan(json, false)
+}
+
+pub fn parse_top_level_json_allowing_nan(
+    json: String,
+    allow_non_finite: bool,
+) -> Result<FlatJson, String> {
+    let (rows, document, depth) = jsonparser::parse_document(&json, allow_non_finite)?;
+    Ok(FlatJson::from_document(rows, document, depth))
+}
+
+// Append the rows of separately parsed top-level values, whose pretty
+// printed text starts at text_offset, linking them to the existing ones
+// as siblings.
+pub fn append_top_level_values(rows: &mut Vec<Row>, mut new_rows: Vec<Row>, text_offset: usize) {
+    let row_offset = rows.len();
+    let last_top_level = rows.last().map(|row| match row.value {
+        Value::CloseContainer { open_index, .. } => open_index,
+        _ => row_offset - 1,
+    });
+    let first_index_in_parent = last_top_level.map_or(0, |last| rows[last].index_in_parent + 1);
+
+    for row in new_rows.iter_mut() {
+        row.remap_indexes(|index| index + row_offset);
+        row.range = shift_range(&row.range, text_offset as isize);
+        row.key_range = row
+            .key_range
+            .as_ref()
+            .map(|range| shift_range(range, text_offset as isize));
+        if row.depth == 0 {
+            row.index_in_parent += first_index_in_parent;
+        }
+    }
+
+    if let (Some(last), false) = (last_top_level, new_rows.is_empty()) {
+        rows[last].next_sibling = OptionIndex::Index(row_offset);
+        new_rows[0].prev_sibling = OptionIndex::Index(last);
+    }
+    rows.append(&mut new_rows);
+}
+
+fn has_string_at_least(rows: &[Row], len: usize) -> bool {
+    rows.iter()
+        .any(|row| row.is_string() && row.range.len() >= len)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct LineError {
+    pub line_number: usize,
+    pub error: String,
+}
+
+// Parse JSON input, but if it fails to parse, and it looks like it is
+// newline-delimited JSON, then try parsing it line by line, skipping
+// the lines that fail to parse. This is only done with --tolerant, since
+// otherwise a file that is almost, but not quite, NDJSON would be shown
+// with some of its lines silently missing.
+//
+// We consider the input to be newline-delimited JSON if more lines
+// parse successfully than fail to parse. A pretty printed JSON file
+// with a single syntax error in it won't meet this condition, so we'll
+// return the original error instead of displaying a bunch of random
+// primitive values.
+pub fn parse_top_level_json_tolerating_bad_lines(
+    json: String,
+    allow_non_finite: bool,
+) -> Result<(FlatJson, Vec<LineError>), String> {
+    let original_err = match jsonparser::parse_document(&json, allow_non_finite) {
+        Ok((rows, document, depth)) => {
+            return Ok((FlatJson::from_document(rows, document, depth), vec![]))
+        }
+        Err(err) => err,
+    };
+
+    let parse = json_parser(allow_non_finite);
+
+    let mut valid_lines = String::new();
+    let mut num_valid_lines = 0;
+    let mut line_errors = vec![];
+
+    for (i, line) in json.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
 
-    fn first_child(&self) -> OptionIndex {
-        match self {
-            Value::OpenContainer { first_child, .. } => OptionIndex::Index(*first_child),
-            _ => OptionIndex::Nil,
+        match parse(line) {
+            Ok(_) => {
+                valid_lines.push_str(line);
+                valid_lines.push('\n');
+                num_valid_lines += 1;
+            }
+            Err(error) => line_errors.push(LineError {
+                line_number: i + 1,
+                error,
+            }),
         }
     }
 
-    fn last_child(&self) -> OptionIndex {
-        match self {
-            Value::CloseContainer { last_child, .. } => OptionIndex::Index(*last_child),
-            _ => OptionIndex::Nil,
-        }
+    if num_valid_lines <= line_errors.len() {
+        return Err(original_err);
     }
 
-    fn pair_index(&self) -> OptionIndex {
-        match self {
-            Value::OpenContainer { close_index, .. } => OptionIndex::Index(*close_index),
-            Value::CloseContainer { open_index, .. } => OptionIndex::Index(*open_index),
-            _ => OptionIndex::Nil,
+    let (rows, pretty, depth) = parse(&valid_lines)?;
+    Ok((FlatJson::new(rows, pretty, depth), line_errors))
+}
+
+// The function to parse JSON with, depending on whether NaN and Infinity
+// are allowed (via --allow-nan).
+pub fn json_parser(allow_non_finite: bool) -> JsonParseFn {
+    if allow_non_finite {
+        jsonparser::parse_allowing_non_finite
+    } else {
+        jsonparser::parse
+    }
+}
+
+pub type JsonParseFn = fn(&str) -> Result<(Vec<Row>, String, usize), String>;
+
+// Parse a JSON text sequence (RFC 7464), where each value is preceded by
+// an ASCII record separator (0x1E), or values separated by NUL bytes, as
+// some tools emit. Each value becomes a separate top-level value.
+//
+// As recommended by the RFC, values that fail to parse (e.g., because
+// they were truncated) are skipped. Errors are reported using the line
+// that the value started on.
+pub fn parse_top_level_json_seq(
+    json: String,
+    allow_non_finite: bool,
+) -> Result<(FlatJson, Vec<LineError>), String> {
+    let parse = json_parser(allow_non_finite);
+    let mut valid_values = String::new();
+    let mut num_valid_values = 0;
+    let mut value_errors = vec![];
+    let mut line_number = 1;
+
+    for value in json.split(is_json_seq_separator) {
+        let value_line_number = line_number;
+        line_number += value.matches('\n').count();
+
+        let trimmed = value.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        match parse(trimmed) {
+            Ok(_) => {
+                valid_values.push_str(trimmed);
+                valid_values.push('\n');
+                num_valid_values += 1;
+            }
+            Err(error) => {
+                let leading_whitespace = &value[..value.len() - value.trim_start().len()];
+                value_errors.push(LineError {
+                    line_number: value_line_number + leading_whitespace.matches('\n').count(),
+                    error,
+                })
+            }
         }
     }
+
+    if num_valid_values == 0 && !value_errors.is_empty() {
+        return Err(value_errors.swap_remove(0).error);
+    }
+
+    let (rows, pretty, depth) = parse(&valid_values)?;
+    Ok((FlatJson::new(rows, pretty, depth), value_errors))
 }
 
-pub fn parse_top_level_json(json: String) -> Result<FlatJson, String> {
-    let (rows, pretty, depth) = jsonparser::parse(json)?;
-    Ok(FlatJson(rows, pretty, depth))
+fn is_json_seq_separator(ch: char) -> bool {
+    ch == '\x1e' || ch == '\0'
+}
+
+// Parse newline-delimited JSON (NDJSON, or JSON Lines), where each line is
+// a separate value. Lines that fail to parse are skipped.
+pub fn parse_top_level_ndjson(
+    json: String,
+    allow_non_finite: bool,
+) -> Result<(FlatJson, Vec<LineError>), String> {
+    let records = ndjson::Records::parse(&json, 1, allow_non_finite);
+    if records.rows.is_empty() {
+        return Err(match records.errors.into_iter().next() {
+            Some(line_error) => line_error.error,
+            None => "Unexpected EOF".to_string(),
+        });
+    }
+
+    Ok((
+        FlatJson::new(records.rows, records.pretty, records.depth),
+        records.errors,
+    ))
+}
+
+pub fn parse_top_level_hjson(hjson: String) -> Result<FlatJson, String> {
+    parse_top_level_json(hjsonparser::to_json(&hjson)?)
+}
+
+// Non-string keys in YAML are stored surrounded by square brackets, e.g.,
+// `[1]` or `[[1, 2]]`. Given a key including its delimiters, returns the
+// key without them if it's a boolean, number or null.
+pub fn yaml_scalar_key(key: &str) -> Option<&str> {
+    let key = key.strip_prefix('[')?.strip_suffix(']')?;
+    if key.starts_with(['[', '{']) {
+        None
+    } else {
+        Some(key)
+    }
 }
 
 pub fn parse_top_level_yaml(yaml: String) -> Result<FlatJson, String> {
     let (rows, pretty, depth) = yamlparser::parse(yaml)?;
-    Ok(FlatJson(rows, pretty, depth))
+    Ok(FlatJson::new(rows, pretty, depth))
 }
 
 #[cfg(test)]
 mod tests {
+    use indoc::indoc;
+
     use super::*;
 
     const OBJECT: &str = r#"{
@@ -761,6 +2421,38 @@ mod tests {
         assert_visited_rows(&fj, vec![NIL]);
     }
 
+    #[test]
+    fn test_fold_runs() {
+        const JSON: &str = r#"[0, 0, 0, 0, 0, 1, "a", "a", "a", "a", [0, 0, 0], 2]"#;
+        // Rows: 0 [, 1-5 0s, 6 1, 7-10 "a"s, 11 [, 12-14 0s, 15 ], 16 2, 17 ]
+        let mut fj = parse_top_level_json(JSON.to_owned()).unwrap();
+        fj.fold_runs();
+
+        assert_eq!(Some(5), fj.folded_run_length(1));
+        assert_eq!(Some(4), fj.folded_run_length(7));
+        // Too short to fold.
+        assert_eq!(None, fj.folded_run_length(12));
+        assert_eq!(1, fj.first_visible_ancestor(3));
+        assert_eq!(16, fj.last_visible_item());
+
+        assert_visited_rows(&fj, vec![1, 6, 7, 11, 12, 13, 14, 15, 16, 17, NIL]);
+        assert_visited_items(&fj, vec![1, 6, 7, 11, 12, 13, 14, 16, NIL]);
+
+        assert_eq!(Some(1), fj.toggle_run_folded(3));
+        assert_eq!(None, fj.folded_run_length(1));
+        assert_visited_rows(
+            &fj,
+            vec![1, 2, 3, 4, 5, 6, 7, 11, 12, 13, 14, 15, 16, 17, NIL],
+        );
+        assert_eq!(None, fj.toggle_run_folded(6));
+
+        fj.unfold_run_containing(10);
+        assert_visited_items(&fj, (1..=14).chain([16, NIL]).collect());
+
+        fj.unfold_runs();
+        assert_eq!(None, fj.toggle_run_folded(3));
+    }
+
     #[test]
     fn test_move_by_items_simple() {
         let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
@@ -794,7 +2486,7 @@ mod tests {
         movement_name: &'static str,
         fj: &FlatJson,
         start_index: Index,
-        expected_visited_rows: &Vec<usize>,
+        expected_visited_rows: &[usize],
         movement_fn: fn(&FlatJson, Index) -> OptionIndex,
     ) {
         let mut curr_index = start_index;
@@ -830,7 +2522,7 @@ mod tests {
         assert_prev_visited_rows(fj, start, &expected);
     }
 
-    fn assert_next_visited_rows(fj: &FlatJson, start_index: Index, expected: &Vec<usize>) {
+    fn assert_next_visited_rows(fj: &FlatJson, start_index: Index, expected: &[usize]) {
         assert_row_iter(
             "next_visible_row",
             fj,
@@ -840,7 +2532,7 @@ mod tests {
         );
     }
 
-    fn assert_prev_visited_rows(fj: &FlatJson, start_index: Index, expected: &Vec<usize>) {
+    fn assert_prev_visited_rows(fj: &FlatJson, start_index: Index, expected: &[usize]) {
         assert_row_iter(
             "prev_visible_row",
             fj,
@@ -866,11 +2558,11 @@ mod tests {
         assert_prev_visited_items(fj, start, &expected);
     }
 
-    fn assert_next_visited_items(fj: &FlatJson, start_index: Index, expected: &Vec<usize>) {
+    fn assert_next_visited_items(fj: &FlatJson, start_index: Index, expected: &[usize]) {
         assert_row_iter("next_item", fj, start_index, expected, FlatJson::next_item);
     }
 
-    fn assert_prev_visited_items(fj: &FlatJson, start_index: Index, expected: &Vec<usize>) {
+    fn assert_prev_visited_items(fj: &FlatJson, start_index: Index, expected: &[usize]) {
         assert_row_iter("prev_item", fj, start_index, expected, FlatJson::prev_item);
     }
 
@@ -940,6 +2632,81 @@ mod tests {
         assert_paths_to_node(&fj, 4, nested_paths);
     }
 
+    #[test]
+    fn test_yaml_non_string_keys_build_path_to_node() {
+        use PathType::*;
+
+        const YAML: &str = "1: a\ntrue: b\nnull: c\n1.5: d\n[1, 2]: e\nx:\n  false: f\n";
+        let fj = parse_top_level_yaml(YAML.to_owned()).unwrap();
+
+        let paths = (r#"["1"]"#, r#"["1"]"#, r#".["1"]"#, r#"["1"]"#);
+        assert_paths_to_node(&fj, 1, paths);
+        let paths = (r#"["true"]"#, r#"["true"]"#, r#".["true"]"#, r#"["true"]"#);
+        assert_paths_to_node(&fj, 2, paths);
+        let paths = (r#"["null"]"#, r#"["null"]"#, r#".["null"]"#, r#"["null"]"#);
+        assert_paths_to_node(&fj, 3, paths);
+        let paths = (r#"["1.5"]"#, r#"["1.5"]"#, r#".["1.5"]"#, r#"["1.5"]"#);
+        assert_paths_to_node(&fj, 4, paths);
+        let paths = (
+            r#".x["false"]"#,
+            r#"["x"]["false"]"#,
+            r#".x["false"]"#,
+            r#".x["false"]"#,
+        );
+        assert_paths_to_node(&fj, 7, paths);
+
+        // Other non-string keys can't be referred to once converted to JSON.
+        assert_eq!("[[1, 2]]", fj.build_path_to_node(Dot, 5).unwrap());
+        assert!(fj.build_path_to_node(Query, 5).is_err());
+        assert!(fj.build_path_to_node(Jq, 5).is_err());
+
+        assert_eq!(None, yaml_scalar_key(r#""1""#));
+        assert_eq!(Some("false"), yaml_scalar_key("[false]"));
+        assert_eq!(None, yaml_scalar_key("[{ \"a\": 1 }]"));
+    }
+
+    #[test]
+    fn test_build_relative_path() {
+        const JSON: &str = r#"{
+            "data": {
+                "items": [1, 2, {"config": {"my key": true}}]
+            },
+            "other": [3]
+        }"#;
+        // Rows: 0 {, 1 data: {, 2 items: [, 3 1, 4 2, 5 {, 6 config: {, 7 my key,
+        // 8 }, 9 }, 10 ], 11 }, 12 other: [, 13 3, 14 ], 15 }
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+
+        assert_eq!(".items[2].config", fj.build_relative_path(1, 6).unwrap());
+        // Closing rows are treated like their opening rows.
+        assert_eq!(".items[2].config", fj.build_relative_path(11, 8).unwrap());
+        assert_eq!(
+            "[2].config[\"my key\"]",
+            fj.build_relative_path(2, 7).unwrap()
+        );
+        assert_eq!(".data.items[0]", fj.build_relative_path(0, 3).unwrap());
+
+        assert_eq!(
+            Err("Focused node is the marked node".to_string()),
+            fj.build_relative_path(2, 10)
+        );
+        assert_eq!(
+            Err(
+                "Focused node isn't inside the marked node; both are inside .data.items"
+                    .to_string()
+            ),
+            fj.build_relative_path(6, 3)
+        );
+        assert_eq!(
+            Err("Focused node isn't inside the marked node".to_string()),
+            fj.build_relative_path(13, 3)
+        );
+
+        assert_eq!(OptionIndex::Index(2), fj.common_ancestor(4, 7));
+        assert_eq!(OptionIndex::Index(0), fj.common_ancestor(14, 9));
+        assert_eq!(OptionIndex::Index(5), fj.common_ancestor(5, 8));
+    }
+
     #[test]
     fn test_multi_top_level_build_path_to_node() {
         use PathType::*;
@@ -994,6 +2761,534 @@ mod tests {
         assert_paths_to_node(&fj, 10, paths);
     }
 
+    #[test]
+    fn test_write_gron() {
+        const JSON: &str = r#"{
+            "name": "jless",
+            "a b": [1, {}, null],
+            "nested": { "ok": true },
+        }
+        "second""#;
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+
+        let mut out = vec![];
+        fj.write_gron(&mut out).unwrap();
+
+        let expected = indoc! {r#"
+            json[0] = {};
+            json[0].name = "jless";
+            json[0]["a b"] = [];
+            json[0]["a b"][0] = 1;
+            json[0]["a b"][1] = {};
+            json[0]["a b"][2] = null;
+            json[0].nested = {};
+            json[0].nested.ok = true;
+            json[1] = "second";
+        "#};
+        assert_eq!(expected, String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn test_jq_path() {
+        const JSON: &str = r#"[
+            {
+                "items": [1, { "$ref": 2, "a b": 3 }],
+            },
+        ]
+        { "items": [4] }"#;
+
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+        let jq_path = |index| fj.build_path_to_node(PathType::Jq, index).unwrap();
+
+        assert_eq!(".", jq_path(0));
+        assert_eq!(".[0]", jq_path(1));
+        assert_eq!(".[0].items[1]", jq_path(4));
+        assert_eq!(r#".[0].items[1]["$ref"]"#, jq_path(5));
+        assert_eq!(r#".[0].items[1]["a b"]"#, jq_path(6));
+        // Top-level values are processed independently by jq.
+        assert_eq!(".items[0]", jq_path(13));
+    }
+
+    #[test]
+    fn test_status_bar_path_includes_array_lengths() {
+        const MULTI_TOP_LEVEL: &str = r#"{
+            "items": [
+                { "a": 1 },
+                [true, false, null],
+            ],
+        }
+        [8]"#;
+
+        let fj = parse_top_level_json(MULTI_TOP_LEVEL.to_owned()).unwrap();
+        let status_bar_path = |index| fj.build_path_to_node(PathType::StatusBar, index).unwrap();
+
+        assert_eq!("[0].items", status_bar_path(1));
+        assert_eq!("[0].items[0/2].a", status_bar_path(3));
+        assert_eq!("[0].items[1/2]", status_bar_path(5));
+        assert_eq!("[0].items[1/2][2/3]", status_bar_path(8));
+        // Closing brackets have the same path as their opening brackets.
+        assert_eq!("[0].items[1/2]", status_bar_path(9));
+        assert_eq!("[1][0/1]", status_bar_path(13));
+    }
+
+    #[test]
+    fn test_num_children_and_nth_child() {
+        const JSON: &str = r#"[
+            { "a": [1, 2] },
+            3,
+            [4, 5, 6],
+        ]"#;
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+
+        assert_eq!(3, fj.num_children(0));
+        assert_eq!(3, fj.num_children(13));
+        assert_eq!(3, fj.num_children(8));
+
+        assert_eq!(OptionIndex::Index(1), fj.nth_child(0, 0));
+        assert_eq!(OptionIndex::Index(7), fj.nth_child(0, 1));
+        assert_eq!(OptionIndex::Index(8), fj.nth_child(0, 2));
+        assert_eq!(OptionIndex::Nil, fj.nth_child(0, 3));
+        assert_eq!(OptionIndex::Index(11), fj.nth_child(8, 2));
+        assert_eq!(OptionIndex::Index(4), fj.nth_child(2, 1));
+        assert_eq!(OptionIndex::Nil, fj.nth_child(2, 2));
+
+        let fj = parse_top_level_json(r#"[[], {}, [[[0]]], 1]"#.to_owned()).unwrap();
+        let children = (0..5).map(|n| fj.nth_child(0, n)).collect::<Vec<_>>();
+        let expected = [1, 2, 3, 10].map(OptionIndex::Index);
+        assert_eq!(&expected[..], &children[..4]);
+        assert_eq!(OptionIndex::Nil, children[4]);
+    }
+
+    #[test]
+    fn test_without_keys_matching() {
+        const JSON: &str = r#"{
+            "_links": { "self": "/1" },
+            "id": 1,
+            "items": [{ "__typename": "Item", "name": "a" }, { "_": 2 }],
+        }
+        { "_id": 2 }"#;
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+        let pattern = Regex::new("^_").unwrap();
+
+        let (json, kept_rows, num_removed) = fj.without_keys_matching(&pattern).unwrap();
+        assert_eq!("{\"id\": 1, \"items\": [{\"name\": \"a\"}, {}]}\n{}", json);
+        assert_eq!(vec![0, 4, 5, 6, 8, 9, 10, 13, 14, 15], kept_rows);
+        assert_eq!(4, num_removed);
+        assert_eq!(kept_rows.len(), parse_top_level_json(json).unwrap().0.len());
+
+        let pattern = Regex::new("nothing").unwrap();
+        let (json, kept_rows, num_removed) = fj.without_keys_matching(&pattern).unwrap();
+        assert_eq!(0, num_removed);
+        assert_eq!((0..fj.0.len()).collect::<Vec<_>>(), kept_rows);
+        assert_eq!(fj.0.len(), parse_top_level_json(json).unwrap().0.len());
+    }
+
+    #[test]
+    fn test_largest_child_and_longest_string() {
+        const JSON: &str = r#"{
+            "a": "long string",
+            "b": [1, 2, 3],
+            "c": { "d": "longer string", "e": [4] },
+            "f": [],
+        }"#;
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+
+        assert_eq!(13, fj.num_descendants(0));
+        assert_eq!(3, fj.num_descendants(2));
+        assert_eq!(0, fj.num_descendants(1));
+        assert_eq!(0, fj.num_descendants(13));
+
+        assert_eq!(OptionIndex::Index(7), fj.largest_child(0));
+        assert_eq!(OptionIndex::Index(8), fj.longest_string(0));
+        assert_eq!(OptionIndex::Index(9), fj.largest_child(7));
+        assert_eq!(OptionIndex::Nil, fj.longest_string(2));
+    }
+
+    #[test]
+    fn test_expand_spine() {
+        const JSON: &str = r#"{
+            "data": {
+                "result": [
+                    { "a": 1, "b": [2] }
+                ]
+            }
+        }
+        { "c": { "d": 3 } }
+        { "e": {}, "f": [4] }"#;
+        let mut fj = parse_top_level_json(JSON.to_owned()).unwrap();
+
+        assert_eq!(3, fj.expand_spine());
+        let expanded: Vec<Index> = (0..fj.0.len())
+            .filter(|&i| fj[i].is_opening_of_container() && fj[i].is_expanded())
+            .collect();
+        // The first value's spine ends at the object with two keys; the
+        // second's at the lone number 3. The last one has two children.
+        assert_eq!(vec![0, 1, 2, 3, 12, 13, 17], expanded);
+        assert!(fj[5].is_collapsed());
+        assert!(fj[19].is_collapsed());
+    }
+
+    #[test]
+    fn test_collapsed_containers() {
+        const JSON: &str = r#"{ "a": [1, { "b": 2 }], "c": { "d": 3 } }"#;
+        let mut fj = parse_top_level_json(JSON.to_owned()).unwrap();
+        assert_eq!(Vec::<Index>::new(), fj.collapsed_containers());
+
+        fj.collapse(3);
+        fj.collapse(7);
+        let collapsed = fj.collapsed_containers();
+        assert_eq!(vec![3, 7], collapsed);
+
+        fj.collapse(0);
+        fj.expand(3);
+        assert_eq!(vec![0, 7], fj.collapsed_containers());
+
+        fj.set_collapsed_containers(&collapsed);
+        assert_eq!(collapsed, fj.collapsed_containers());
+        assert!(fj[5].is_collapsed());
+        assert!(fj[9].is_collapsed());
+    }
+
+    #[test]
+    fn test_number_types_and_next_of_type() {
+        const JSON: &str = r#"[1, 2.5, -3e2, { "a": null, "b": 7 }, [], "s"]"#;
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+
+        assert_eq!(Some(NumberType::Integer), fj[1].value.number_type());
+        assert_eq!(Some(NumberType::Float), fj[2].value.number_type());
+        assert_eq!(Some(NumberType::Float), fj[3].value.number_type());
+        assert_eq!(Some(NumberType::Integer), fj[6].value.number_type());
+        assert_eq!(None, fj[9].value.number_type());
+
+        assert_eq!(
+            OptionIndex::Index(6),
+            fj.next_of_type(ValueType::Integer, 1)
+        );
+        assert_eq!(
+            OptionIndex::Index(1),
+            fj.next_of_type(ValueType::Integer, 6)
+        );
+        assert_eq!(OptionIndex::Index(2), fj.next_of_type(ValueType::Float, 3));
+        assert_eq!(OptionIndex::Index(2), fj.next_of_type(ValueType::Number, 1));
+        assert_eq!(OptionIndex::Index(5), fj.next_of_type(ValueType::Null, 0));
+        assert_eq!(OptionIndex::Index(4), fj.next_of_type(ValueType::Object, 0));
+        assert_eq!(OptionIndex::Index(8), fj.next_of_type(ValueType::Array, 0));
+        // Containers don't match on their closing rows.
+        assert_eq!(OptionIndex::Index(0), fj.next_of_type(ValueType::Array, 8));
+        assert_eq!(OptionIndex::Index(9), fj.next_of_type(ValueType::String, 9));
+        assert_eq!(OptionIndex::Nil, fj.next_of_type(ValueType::Boolean, 0));
+
+        let fj = parse_top_level_yaml("a: 1\nb: 1.5\n".to_owned()).unwrap();
+        assert_eq!(Some(NumberType::Integer), fj[1].value.number_type());
+        assert_eq!(Some(NumberType::Float), fj[2].value.number_type());
+    }
+
+    #[test]
+    fn test_replace_value() {
+        const JSON: &str = r#"{"a": [1, 1, 1, 1], "b": {"c": null}, "d": 3}"#;
+        // Rows: 0 {, 1 [, 2-5 1s, 6 ], 7 {, 8 null, 9 }, 10 3, 11 }
+        let mut fj = parse_top_level_json(JSON.to_owned()).unwrap();
+        fj.fold_runs();
+        let row_text = |fj: &FlatJson, i: Index| fj.1[fj[i].range.clone()].to_string();
+
+        fj.replace_value(8, r#" "longer" "#).unwrap();
+        assert!(fj[8].is_string());
+        assert_eq!(r#""longer""#, row_text(&fj, 8));
+        assert_eq!(r#"{ "c": "longer" }"#, row_text(&fj, 7));
+        assert_eq!("3", row_text(&fj, 10));
+        assert_eq!(Some(r#""d""#), fj[10].key_range.clone().map(|r| &fj.1[r]));
+        assert_eq!(fj.1.len(), fj[0].range.end);
+
+        // Editing part of a run unfolds it.
+        assert_eq!(Some(4), fj.folded_run_length(2));
+        fj.replace_value(3, "2.5").unwrap();
+        assert_eq!(Some(NumberType::Float), fj[3].value.number_type());
+        assert_eq!(None, fj.folded_run_length(2));
+        assert_eq!(
+            r#"{ "a": [1, 2.5, 1, 1], "b": { "c": "longer" }, "d": 3 }"#,
+            &*fj.1
+        );
+
+        assert!(fj.replace_value(1, "1").is_err());
+        assert!(fj.replace_value(2, "[1]").is_err());
+        assert!(fj.replace_value(2, "1 2").is_err());
+        assert!(fj.replace_value(2, "nope").is_err());
+        assert_eq!("1", row_text(&fj, 2));
+    }
+
+    #[test]
+    fn test_delete_value() {
+        const JSON: &str = r#"{"a": [1, 2, 3], "b": {"c": null}, "d": 3}"#;
+        // Rows: 0 {, 1 [, 2-4 1-3, 5 ], 6 {, 7 null, 8 }, 9 3, 10 }
+        let mut fj = parse_top_level_json(JSON.to_owned()).unwrap();
+
+        // Later elements of the array are shifted down.
+        let (remap, _) = fj.delete_value(2).unwrap();
+        assert_eq!((Some(1), None, Some(2)), (remap(1), remap(2), remap(3)));
+        assert_eq!(r#"{ "a": [2, 3], "b": { "c": null }, "d": 3 }"#, &*fj.1);
+        assert_eq!(OptionIndex::Index(2), fj[1].first_child());
+        assert_eq!(OptionIndex::Nil, fj[2].prev_sibling);
+        assert_eq!((0, 1), (fj[2].index_in_parent, fj[3].index_in_parent));
+        assert_eq!("[2, 3]", &fj.1[fj[1].range.clone()]);
+
+        // Removing the only child leaves an empty container.
+        let (remap, _) = fj.delete_value(6).unwrap();
+        assert_eq!((None, None, Some(6)), (remap(6), remap(7), remap(8)));
+        assert_eq!(r#"{ "a": [2, 3], "b": {}, "d": 3 }"#, &*fj.1);
+        assert!(matches!(fj[5].value, Value::EmptyObject));
+        assert_eq!(Some(r#""d""#), fj[6].key_range.clone().map(|r| &fj.1[r]));
+
+        // As does removing a container, along with its descendants.
+        let _ = fj.delete_value(1).unwrap();
+        assert_eq!(r#"{ "b": {}, "d": 3 }"#, &*fj.1);
+        assert_eq!(4, fj.0.len());
+        assert_eq!(OptionIndex::Index(1), fj[0].first_child());
+        assert_eq!(OptionIndex::Index(2), fj[3].last_child());
+        assert_eq!(fj.1.len(), fj[0].range.end);
+
+        let _ = fj.delete_value(2).unwrap();
+        assert_eq!(r#"{ "b": {} }"#, &*fj.1);
+        assert_eq!(OptionIndex::Index(1), fj[2].last_child());
+
+        assert!(fj.delete_value(0).is_err());
+
+        let mut fj = parse_top_level_json("1\n[2]\n3".to_owned()).unwrap();
+        let _ = fj.delete_value(0).unwrap();
+        assert_eq!("[2]\n3", &*fj.1);
+        let _ = fj.delete_value(3).unwrap();
+        assert_eq!("[2]", &*fj.1);
+        assert_eq!(OptionIndex::Nil, fj[0].next_sibling);
+    }
+
+    #[test]
+    fn test_restore_value() {
+        const JSON: &str = r#"{"a": [1, 2, 3], "b": {"c": null}, "d": [[]]} 4 [5]"#;
+
+        // Deleting and then restoring any value leaves the document exactly
+        // as it was.
+        let num_rows = parse_top_level_json(JSON.to_owned()).unwrap().0.len();
+        for index in 0..num_rows {
+            let mut fj = parse_top_level_json(JSON.to_owned()).unwrap();
+            if fj[index].is_closing_of_container() {
+                continue;
+            }
+            let before = format!("{:?}", fj);
+
+            let (remap, deleted) = fj.delete_value(index).unwrap();
+            let restore_remap = fj.restore_value(deleted);
+            assert_eq!(before, format!("{:?}", fj), "restoring row {index}");
+            for i in (0..num_rows).filter(|i| remap(*i).is_some()) {
+                assert_eq!(i, restore_remap(remap(i).unwrap()));
+            }
+        }
+
+        // Records appended after a value was deleted follow it once it's
+        // restored.
+        let mut fj = parse_top_level_json("1\n2".to_owned()).unwrap();
+        let (_, deleted) = fj.delete_value(1).unwrap();
+        let (rows, pretty, depth) = jsonparser::parse("3").unwrap();
+        fj.append(rows, &pretty, depth);
+        let _ = fj.restore_value(deleted);
+        assert_eq!("1\n2\n3", &*fj.1);
+        assert_eq!(OptionIndex::Index(2), fj[1].next_sibling);
+        assert_eq!(OptionIndex::Index(1), fj[2].prev_sibling);
+        assert_eq!(2, fj[2].index_in_parent);
+    }
+
+    #[test]
+    fn test_pretty_printed_value_as_yaml() {
+        const YAML: &str = r#"
+            name: jless
+            version: "0.9"
+            tags: [json, yaml, "on", "with space ", "multi\nline"]
+            nested:
+              - a: 1
+                b: [true, null]
+              - [1, [2, 3]]
+              - {}
+            [1, 2]: complex key
+        "#;
+        let fj = parse_top_level_yaml(YAML.to_owned()).unwrap();
+
+        let expected = indoc! {r#"
+            name: jless
+            version: "0.9"
+            tags:
+              - json
+              - yaml
+              - "on"
+              - "with space "
+              - "multi\nline"
+            nested:
+              - a: 1
+                b:
+                  - true
+                  - null
+              - - 1
+                - - 2
+                  - 3
+              - {}
+            [1, 2]: complex key
+        "#};
+        assert_eq!(expected, fj.pretty_printed_value_as_yaml(0).unwrap());
+
+        // Nested values, and primitives.
+        assert_eq!(
+            "- json\n- yaml\n- \"on\"\n- \"with space \"\n- \"multi\\nline\"\n",
+            fj.pretty_printed_value_as_yaml(3).unwrap()
+        );
+        assert_eq!("jless", fj.pretty_printed_value_as_yaml(1).unwrap());
+        assert_eq!("\"0.9\"", fj.pretty_printed_value_as_yaml(2).unwrap());
+    }
+
+    #[test]
+    fn test_pretty_printed_value_as_json() {
+        const YAML: &str = indoc! {r#"
+            name: jless
+            "quoted \"key\"": [1, 1.5, .inf, -.Inf, .nan, +2.5, 1.]
+            [1, 2]: {a: null}
+            3: true
+            empty: {}
+        "#};
+        let fj = parse_top_level_yaml(YAML.to_owned()).unwrap();
+
+        let expected = indoc! {r#"
+            {
+              "name": "jless",
+              "quoted \"key\"": [
+                1,
+                1.5,
+                null,
+                null,
+                null,
+                2.5,
+                1.0
+              ],
+              "[1, 2]": {
+                "a": null
+              },
+              "3": true,
+              "empty": {}
+            }
+        "#};
+        assert_eq!(expected, fj.pretty_printed_value_as_json(0).unwrap());
+        assert_eq!("\"jless\"", fj.pretty_printed_value_as_json(1).unwrap());
+
+        // JSON input comes out the same as when pretty printed.
+        let fj = parse_top_level_json(r#"{"a": [1, {"b": "c"}], "d": []}"#.to_owned()).unwrap();
+        assert_eq!(
+            fj.pretty_printed_value(0).unwrap(),
+            fj.pretty_printed_value_as_json(0).unwrap()
+        );
+        assert_eq!(
+            fj.pretty_printed_value(2).unwrap(),
+            fj.pretty_printed_value_as_json(2).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_canonical_json() {
+        let canonical = |json: &str| {
+            parse_top_level_json(json.to_owned())
+                .unwrap()
+                .canonical_json(0)
+                .unwrap()
+        };
+
+        assert_eq!(
+            r#"{"a":[1,1.5,0,100,12345678901234567890],"b":{"c":"é\n/","d":null}}"#,
+            canonical(
+                r#"{
+                    "b": {"d": null, "c": "\u00e9\n\/"},
+                    "a": [1.0, 15e-1, -0, 1E2, 12345678901234567890]
+                }"#
+            )
+        );
+        // Duplicate keys are kept, in their original order.
+        assert_eq!(r#"{"a":2,"a":1}"#, canonical(r#"{"a": 2, "a": 1}"#));
+
+        // Keys are sorted by UTF-16 code units, so U+1F600 (a surrogate
+        // pair starting with 0xD83D) sorts before U+FB01.
+        assert_eq!(
+            "{\"\u{1F600}\":1,\"\u{FB01}\":2}",
+            canonical(r#"{"ﬁ": 2, "😀": 1}"#)
+        );
+        // Strings escape the same characters as RFC 8785.
+        assert_eq!(
+            "[\"\\b\\f\\u000b\\u001f\\\"\\\\/\u{7f}\"]",
+            canonical(r#"["\b\f\u000B\u001F\"\\\/\u007f"]"#)
+        );
+
+        let fj = parse_top_level_yaml("b: [.inf, 1.]\n[1, 2]: x\n".to_owned()).unwrap();
+        assert_eq!(
+            r#"{"[1, 2]":"x","b":[null,1]}"#,
+            fj.canonical_json(0).unwrap()
+        );
+        // Closing brackets are treated like the container.
+        assert_eq!(r#"[null,1]"#, fj.canonical_json(4).unwrap());
+    }
+
+    #[test]
+    fn test_pretty_printed_value_as_toml() {
+        const JSON: &str = r#"{
+            "name": "jless",
+            "version": 0.9,
+            "tags": ["json", "multi\nline", "\u00e9\/"],
+            "mixed": [1, {"a": true}, [], {}],
+            "my key": "value",
+            "package": {
+                "edition": 2018,
+                "empty": {},
+                "deps": {"regex": {"version": "1.5"}}
+            },
+            "bins": [{"name": "a"}, {}, {"name": "b", "test": {"x": 1}}],
+            "no_bins": []
+        }"#;
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+
+        let expected = indoc! {r#"
+            name = "jless"
+            version = 0.9
+            tags = ["json", "multi\nline", "é/"]
+            mixed = [1, { a = true }, [], {}]
+            "my key" = "value"
+            no_bins = []
+
+            [package]
+            edition = 2018
+
+            [package.empty]
+
+            [package.deps]
+
+            [package.deps.regex]
+            version = "1.5"
+
+            [[bins]]
+            name = "a"
+
+            [[bins]]
+
+            [[bins]]
+            name = "b"
+
+            [bins.test]
+            x = 1
+        "#};
+        assert_eq!(expected, fj.pretty_printed_value_as_toml(0).unwrap());
+
+        assert_eq!(
+            Err("Only objects can be represented as TOML".to_string()),
+            fj.pretty_printed_value_as_toml(3)
+        );
+        assert_eq!(
+            Err("null can't be represented in TOML".to_string()),
+            parse_top_level_json(r#"{"a": [null]}"#.to_owned())
+                .unwrap()
+                .pretty_printed_value_as_toml(0)
+        );
+    }
+
     #[test]
     fn test_build_path_to_node_yaml_non_string_key() {
         use PathType::*;
@@ -1027,6 +3322,65 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_tolerating_bad_lines() {
+        const NDJSON: &str = r#"{"a": 1}
+            {"b": 2
+
+            [3]
+            nul
+            "four""#;
+
+        let (fj, errors) =
+            parse_top_level_json_tolerating_bad_lines(NDJSON.to_owned(), false).unwrap();
+        assert_eq!("{ \"a\": 1 }\n[3]\n\"four\"", &*fj.1);
+        assert_eq!(
+            vec![2, 5],
+            errors.iter().map(|e| e.line_number).collect::<Vec<_>>()
+        );
+
+        // Valid input has no errors.
+        let (_, errors) = parse_top_level_json_tolerating_bad_lines(
+            NDJSON.replace("nul", "null").replace("2\n", "2}\n"),
+            false,
+        )
+        .unwrap();
+        assert!(errors.is_empty());
+
+        // Mostly invalid lines returns the original error.
+        const PRETTY_PRINTED: &str = r#"{
+            "a": 1,
+            "b": [true, false
+        }"#;
+        assert!(
+            parse_top_level_json_tolerating_bad_lines(PRETTY_PRINTED.to_owned(), false).is_err()
+        );
+
+        // NaN and Infinity are only accepted when they're allowed.
+        const PYTHON: &str = "{\"a\": NaN}\n{\"a\": 1.5}\n{\"a\": -Infinity}";
+        let err = parse_top_level_json_tolerating_bad_lines(PYTHON.to_owned(), false).unwrap_err();
+        assert!(err.contains("--allow-nan"), "{}", err);
+        let (fj, errors) =
+            parse_top_level_json_tolerating_bad_lines(PYTHON.to_owned(), true).unwrap();
+        assert!(errors.is_empty());
+        assert_eq!(Some(NumberType::NonFinite), fj[1].value.number_type());
+    }
+
+    #[test]
+    fn test_parse_json_seq() {
+        const JSON_SEQ: &str = "\x1e{\"a\": 1}\n\x1e[2,\n3]\n\x1e{\"b\": tru\n\x1e\"c\"\0\0null";
+
+        let (fj, errors) = parse_top_level_json_seq(JSON_SEQ.to_owned(), false).unwrap();
+        assert_eq!("{ \"a\": 1 }\n[2, 3]\n\"c\"\nnull", &*fj.1);
+        assert_eq!(
+            vec![4],
+            errors.iter().map(|e| e.line_number).collect::<Vec<_>>()
+        );
+
+        // Nothing valid at all is an error.
+        assert!(parse_top_level_json_seq("\x1e{\n\x1e[".to_owned(), false).is_err());
+    }
+
     #[test]
     fn test_pretty_print() {
         const JSON: &str = r#"{"a":1,"b":[2,{},[],false],"c":null}
@@ -1087,4 +3441,55 @@ mod tests {
         const PRETTY_NESTED_OBJ: &str = "{\n  \"8\": false\n}\n";
         assert_eq!(PRETTY_NESTED_OBJ, fj.pretty_printed_value(7).unwrap());
     }
+
+    #[test]
+    fn test_pretty_printed_value_with_progress() {
+        let json = format!("[{}]", vec!["1"; PROGRESS_INTERVAL_ROWS * 2].join(", "));
+        let fj = parse_top_level_json(json).unwrap();
+        let total_rows = PROGRESS_INTERVAL_ROWS * 2 + 2;
+
+        let mut reports = vec![];
+        let pretty = fj.pretty_printed_value_with_progress(0, |printed, total| {
+            reports.push((printed, total));
+            true
+        });
+        assert_eq!(Some(fj.pretty_printed_value(0).unwrap()), pretty.unwrap());
+        assert_eq!(
+            vec![
+                (PROGRESS_INTERVAL_ROWS, total_rows),
+                (PROGRESS_INTERVAL_ROWS * 2, total_rows)
+            ],
+            reports
+        );
+
+        // Returning false stops printing.
+        let pretty = fj.pretty_printed_value_with_progress(0, |_, _| false);
+        assert_eq!(None, pretty.unwrap());
+    }
+
+    #[test]
+    fn test_has_string_at_least() {
+        let fj = parse_top_level_json(r#"{"a": 12345678, "b": "small"}"#.to_owned()).unwrap();
+        assert!(matches!(fj.1, Document::InMemory(_)));
+        // String lengths include the quotes; numbers don't count.
+        assert!(has_string_at_least(&fj.0, 7));
+        assert!(!has_string_at_least(&fj.0, 8));
+    }
+
+    #[test]
+    fn test_pretty_printed_array() {
+        const JSON: &str = r#"[{"a": [1]}, 2, {"b": 3}]"#;
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+        const PRETTY: &str = r#"[
+  {
+    "a": [
+      1
+    ]
+  },
+  2
+]
+"#;
+        assert_eq!(PRETTY, fj.pretty_printed_array([1, 6]).unwrap());
+        assert_eq!("[]\n", fj.pretty_printed_array([]).unwrap());
+    }
 }
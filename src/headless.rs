@@ -0,0 +1,1709 @@
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::app::App;
+use crate::input::TuiEvent;
+use crate::options::{DataFormat, Opt};
+use crate::screenwriter::Output;
+use crate::types::TTYDimensions;
+
+// Run jless without a terminal: feed the App a fixed sequence of events
+// (along with any --cmd commands), then return the text that would be on
+// the screen once they've all been handled, e.g.:
+//
+//   jless --dump-screen --screen-size 100x30 --cmd 'j | :set dense' data.json
+//
+// Everything gets drawn exactly as it normally would; the escape codes
+// that get written out are interpreted by a ScreenCapture, which keeps
+// track of what text ends up in every cell of the screen.
+pub fn run_headless(
+    opt: &Opt,
+    data: String,
+    data_format: DataFormat,
+    input_filename: String,
+    events: Box<dyn Iterator<Item = io::Result<TuiEvent>>>,
+) -> Result<String, String> {
+    let capture = ScreenCapture::new(opt.screen_size);
+    let mut app = App::new(
+        opt,
+        data,
+        data_format,
+        input_filename,
+        Box::new(capture.clone()),
+    )?;
+    app.run(events, opt.screen_size);
+    Ok(capture.contents())
+}
+
+pub fn parse_screen_size(size: &str) -> Result<TTYDimensions, String> {
+    let invalid = || format!("Invalid screen size {size:?}; expected WIDTHxHEIGHT, e.g., 80x24");
+    let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+    let width = width.parse::<u16>().map_err(|_| invalid())?;
+    let height = height.parse::<u16>().map_err(|_| invalid())?;
+    if width == 0 || height == 0 {
+        return Err(invalid());
+    }
+    Ok(TTYDimensions { width, height })
+}
+
+// An Output that records what's drawn to it, instead of writing to a
+// terminal. Clones share the same screen, so one can be handed to the App
+// and another used to read the screen afterwards.
+#[derive(Clone)]
+pub struct ScreenCapture(Rc<RefCell<Screen>>);
+
+impl ScreenCapture {
+    pub fn new(dimensions: TTYDimensions) -> ScreenCapture {
+        ScreenCapture(Rc::new(RefCell::new(Screen::new(dimensions))))
+    }
+
+    pub fn contents(&self) -> String {
+        self.0.borrow().contents()
+    }
+}
+
+impl io::Write for ScreenCapture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write_bytes(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Output for ScreenCapture {
+    fn suspend_raw_mode(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn activate_raw_mode(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn is_interactive(&self) -> bool {
+        false
+    }
+}
+
+// A grid of cells, updated by interpreting the subset of ANSI escape codes
+// that jless (via AnsiTerminal and termion) actually writes out. Styles
+// are ignored; only the text is tracked.
+struct Screen {
+    width: usize,
+    height: usize,
+    // Each cell holds a single grapheme; the cell after a double width
+    // grapheme is left empty.
+    cells: Vec<Vec<String>>,
+    row: usize,
+    col: usize,
+    // Bytes of an escape code or UTF-8 character that was split across
+    // writes.
+    pending: Vec<u8>,
+}
+
+const ESC: u8 = 0x1b;
+const BEL: u8 = 0x07;
+
+impl Screen {
+    fn new(dimensions: TTYDimensions) -> Screen {
+        let width = dimensions.width as usize;
+        let height = dimensions.height as usize;
+        Screen {
+            width,
+            height,
+            cells: vec![vec![" ".to_string(); width]; height],
+            row: 0,
+            col: 0,
+            pending: vec![],
+        }
+    }
+
+    fn contents(&self) -> String {
+        let lines: Vec<String> = self
+            .cells
+            .iter()
+            .map(|row| row.concat().trim_end().to_string())
+            .collect();
+        lines.join("\n")
+    }
+
+    fn write_bytes(&mut self, buf: &[u8]) {
+        self.pending.extend_from_slice(buf);
+        let bytes = std::mem::take(&mut self.pending);
+
+        let mut i = 0;
+        while i < bytes.len() {
+            let consumed = match bytes[i] {
+                ESC => self.handle_escape(&bytes[i..]),
+                b'\r' => {
+                    self.col = 0;
+                    Some(1)
+                }
+                b'\n' => {
+                    self.row += 1;
+                    self.col = 0;
+                    Some(1)
+                }
+                byte if byte < 0x20 || byte == 0x7f => Some(1),
+                _ => self.handle_text(&bytes[i..]),
+            };
+
+            match consumed {
+                Some(n) => i += n,
+                None => break,
+            }
+        }
+
+        self.pending = bytes[i..].to_vec();
+    }
+
+    // Returns how many bytes make up the escape code at the start of bytes,
+    // or None if it's incomplete.
+    fn handle_escape(&mut self, bytes: &[u8]) -> Option<usize> {
+        match bytes.get(1)? {
+            // Control Sequence Introducer: ESC [ params final-byte
+            b'[' => {
+                let len = bytes[2..].iter().position(|b| (0x40..=0x7e).contains(b))? + 3;
+                let params = String::from_utf8_lossy(&bytes[2..len - 1]);
+                self.handle_csi(&params, bytes[len - 1]);
+                Some(len)
+            }
+            // Operating System Command, terminated by BEL or ESC \
+            b']' => {
+                let end = bytes
+                    .windows(2)
+                    .position(|w| w[0] == BEL || w == [ESC, b'\\'])?;
+                Some(if bytes[end] == BEL { end + 1 } else { end + 2 })
+            }
+            _ => Some(2),
+        }
+    }
+
+    fn handle_csi(&mut self, params: &str, command: u8) {
+        let mut args = params
+            .split(';')
+            .map(|arg| arg.parse::<usize>().unwrap_or(0));
+        let first = args.next().unwrap_or(0);
+
+        match command {
+            b'H' | b'f' => {
+                self.row = first.max(1) - 1;
+                self.col = args.next().unwrap_or(0).max(1) - 1;
+            }
+            b'G' => self.col = first.max(1) - 1,
+            b'K' => {
+                let cols = match first {
+                    0 => self.col..self.width,
+                    1 => 0..self.col + 1,
+                    _ => 0..self.width,
+                };
+                self.clear(self.row, cols);
+            }
+            b'J' => {
+                let rows = match first {
+                    0 => {
+                        self.clear(self.row, self.col..self.width);
+                        self.row + 1..self.height
+                    }
+                    1 => {
+                        self.clear(self.row, 0..self.col + 1);
+                        0..self.row
+                    }
+                    _ => 0..self.height,
+                };
+                for row in rows {
+                    self.clear(row, 0..self.width);
+                }
+            }
+            // Styles, showing and hiding the cursor, mouse tracking, etc.
+            _ => {}
+        }
+    }
+
+    fn clear(&mut self, row: usize, cols: std::ops::Range<usize>) {
+        if let Some(cells) = self.cells.get_mut(row) {
+            for cell in cells[cols.start.min(self.width)..cols.end.min(self.width)].iter_mut() {
+                *cell = " ".to_string();
+            }
+        }
+    }
+
+    // Returns how many bytes of text were written, up to the next control
+    // character, or None if the text is just an incomplete UTF-8 character.
+    fn handle_text(&mut self, bytes: &[u8]) -> Option<usize> {
+        let end = bytes
+            .iter()
+            .position(|&b| b < 0x20 || b == 0x7f)
+            .unwrap_or(bytes.len());
+
+        let text = match std::str::from_utf8(&bytes[..end]) {
+            Ok(text) => text,
+            // Wait for the rest of a character that was split across writes.
+            Err(err) if err.error_len().is_none() && err.valid_up_to() > 0 => {
+                std::str::from_utf8(&bytes[..err.valid_up_to()]).unwrap()
+            }
+            Err(err) if err.error_len().is_none() => return None,
+            Err(err) => {
+                let text = std::str::from_utf8(&bytes[..err.valid_up_to()]).unwrap();
+                self.put_text(text);
+                self.put_text("\u{fffd}");
+                return Some(err.valid_up_to() + err.error_len().unwrap());
+            }
+        };
+
+        self.put_text(text);
+        Some(text.len())
+    }
+
+    fn put_text(&mut self, text: &str) {
+        for grapheme in text.graphemes(true) {
+            let width = UnicodeWidthStr::width(grapheme);
+            if self.row >= self.height || self.col + width > self.width {
+                self.col += width;
+                continue;
+            }
+
+            let cells = &mut self.cells[self.row];
+            if width == 0 {
+                if self.col > 0 {
+                    cells[self.col - 1].push_str(grapheme);
+                }
+                continue;
+            }
+
+            cells[self.col] = grapheme.to_string();
+            for cell in cells[self.col + 1..self.col + width].iter_mut() {
+                cell.clear();
+            }
+            self.col += width;
+        }
+    }
+}
+
+// Run jless on the given JSON with the given arguments, then feed it the
+// given keys once any --cmd commands have run, and return what ends up on
+// the screen. Used by the tests of other modules, too.
+#[cfg(test)]
+pub(crate) fn run_jless(
+    args: &[&str],
+    json: &str,
+    keys: &[termion::event::Key],
+) -> Result<String, String> {
+    use clap::Parser;
+
+    let opt = Opt::parse_from([&["jless"][..], args].concat());
+    let events: Vec<io::Result<TuiEvent>> = keys
+        .iter()
+        .map(|&key| Ok(TuiEvent::KeyEvent(key)))
+        .collect();
+    run_headless(
+        &opt,
+        json.to_string(),
+        DataFormat::Json,
+        "test.json".to_string(),
+        Box::new(events.into_iter()),
+    )
+}
+
+// The screen of the given size after running the given --cmd commands.
+#[cfg(test)]
+pub(crate) fn screen_after(size: &str, json: &str, commands: &str) -> String {
+    run_jless(&["--screen-size", size, "--cmd", commands], json, &[]).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use indoc::indoc;
+    use termion::event::Key;
+
+    use crate::control::ControlCommand;
+    use crate::ndjson::Records;
+    use crate::sha256;
+
+    use super::*;
+
+    #[test]
+    fn test_screen_capture() {
+        let mut capture = ScreenCapture::new(TTYDimensions::from_size((10, 3)));
+
+        io::Write::write_all(&mut capture, b"\x1b[2J\x1b[1;1Habc\x1b[38;5;1mdef").unwrap();
+        io::Write::write_all(&mut capture, b"\x1b[2;3Hx\xe4\xb8").unwrap();
+        // Finish the escape code and the split UTF-8 character.
+        io::Write::write_all(&mut capture, b"\xad\x1b[").unwrap();
+        io::Write::write_all(&mut capture, b"0my\x1b[6G!").unwrap();
+        assert_eq!("abcdef\n  x中!\n", capture.contents());
+
+        // Text past the end of the line is dropped.
+        io::Write::write_all(&mut capture, b"\x1b[3;8Hlong line").unwrap();
+        assert_eq!("abcdef\n  x中!\n       lon", capture.contents());
+
+        io::Write::write_all(&mut capture, b"\x1b[1;3H\x1b[2K\x1b[2;4H\x1b[K").unwrap();
+        assert_eq!("\n  x\n       lon", capture.contents());
+
+        io::Write::write_all(&mut capture, b"\x1b]11;?\x07\x1b[2J").unwrap();
+        assert_eq!("\n\n", capture.contents());
+    }
+
+    #[test]
+    fn test_parse_screen_size() {
+        assert_eq!((100, 30), {
+            let size = parse_screen_size("100x30").unwrap();
+            (size.width, size.height)
+        });
+        assert!(parse_screen_size("100").is_err());
+        assert!(parse_screen_size("0x30").is_err());
+        assert!(parse_screen_size("ax30").is_err());
+    }
+
+    #[test]
+    fn test_run_headless() {
+        let opt = Opt::parse_from(["jless", "--screen-size", "40x6", "--cmd", ":set nonumber"]);
+        let events = vec![Key::Char('j'), Key::Char('c')]
+            .into_iter()
+            .map(|key| Ok(TuiEvent::KeyEvent(key)));
+
+        let screen = run_headless(
+            &opt,
+            r#"{"a": [1, 2], "b": {"c": true}}"#.to_string(),
+            DataFormat::Json,
+            "test.json".to_string(),
+            Box::new(events),
+        )
+        .unwrap();
+
+        let expected = [
+            "▽ (2) {a: […], b: {…}}",
+            "  ▶ a: (2) [1, 2]",
+            "  ▷ b: (1) {c: true}",
+            "~",
+            "input.a                        test.json",
+            ":",
+        ];
+        assert_eq!(expected.join("\n"), screen);
+    }
+
+    #[test]
+    fn test_fold_runs() {
+        let screen_after = |commands: &str| {
+            let args = ["--fold-runs", "--screen-size", "40x6", "--cmd", commands];
+            run_jless(&args, "[1, 0, 0, 0, 0, 0, 2]", &[]).unwrap()
+        };
+
+        let expected = [
+            "▽ (7) [1, 0, 0, 0, 0, 0, 2]            │",
+            "    [0]: 1                             │",
+            "    [1]: 0 ×5                          ┃",
+            "  ▶ [6]: 2                             ┃",
+            "input[6/7]                     test.json",
+            ":",
+        ];
+        assert_eq!(expected.join("\n"), screen_after(":set nonumber | jjj"));
+
+        // l (or Space) unfolds the focused run.
+        let expected = [
+            "▽ (7) [1, 0, 0, 0, 0, 0, 2]            ┃",
+            "    [0]: 1                             ┃",
+            "  ▶ [1]: 0                             │",
+            "    [2]: 0                             │",
+            "input[1/7]                     test.json",
+            ":",
+        ];
+        assert_eq!(expected.join("\n"), screen_after(":set nonumber | jjl"));
+    }
+
+    #[test]
+    fn test_key_mappings() {
+        let args = [
+            "--map",
+            "J=<Down>,j=k",
+            "--map",
+            "k=j",
+            "--screen-size",
+            "40x6",
+            "--cmd",
+            ":set nonumber | JJkJj",
+        ];
+        let screen = run_jless(&args, r#"{"a": 1, "b": 2, "c": 3, "d": 4}"#, &[]).unwrap();
+
+        let expected = [
+            "    a: 1",
+            "    b: 2",
+            "  ▶ c: 3",
+            "    d: 4",
+            "input.c                        test.json",
+            ":",
+        ];
+        assert_eq!(expected.join("\n"), screen);
+    }
+
+    #[test]
+    fn test_pick() {
+        let pick = |args: &[&str], keys: &[Key]| {
+            let opt = Opt::parse_from(args);
+            let events: Vec<io::Result<TuiEvent>> = keys
+                .iter()
+                .map(|&key| Ok(TuiEvent::KeyEvent(key)))
+                .collect();
+            let mut app = App::new(
+                &opt,
+                r#"{"a": {"b c": "x y"}, "d": [1]}"#.to_string(),
+                DataFormat::Json,
+                "test.json".to_string(),
+                Box::new(ScreenCapture::new(opt.screen_size)),
+            )
+            .unwrap();
+            app.run(Box::new(events.into_iter()), opt.screen_size);
+            app.picked().map(str::to_string)
+        };
+
+        let keys = [Key::Char('j'), Key::Char('j'), Key::Char('\n')];
+        assert_eq!(
+            Some(".a[\"b c\"]".to_string()),
+            pick(&["jless", "--pick"], &keys)
+        );
+        assert_eq!(
+            Some("x y".to_string()),
+            pick(&["jless", "--pick=value"], &keys)
+        );
+        assert_eq!(
+            Some("[\n  1\n]\n".to_string()),
+            pick(
+                &["jless", "--pick=value"],
+                &[
+                    Key::Char('j'),
+                    Key::Char('j'),
+                    Key::Char('j'),
+                    Key::Char('\n')
+                ]
+            )
+        );
+        // Without --pick, Enter just moves down.
+        assert_eq!(None, pick(&["jless"], &keys));
+        assert_eq!(None, pick(&["jless", "--pick"], &[Key::Char('q')]));
+    }
+
+    #[test]
+    fn test_incremental_search() {
+        let screen_after = |keys: &str| {
+            let keys: Vec<Key> = keys
+                .chars()
+                .map(|ch| match ch {
+                    '<' => Key::Backspace,
+                    '^' => Key::Esc,
+                    ch => Key::Char(ch),
+                })
+                .collect();
+            let args = ["--incsearch", "--screen-size", "40x5"];
+            run_jless(&args, r#"{"a": {"b": 1}, "c": {"bb": 2}}"#, &keys).unwrap()
+        };
+
+        // The first match of what's been typed is previewed.
+        let screen = screen_after("/b");
+        assert!(
+            screen.ends_with("input.a.b                      test.json\n/b"),
+            "{:?}",
+            screen
+        );
+        let screen = screen_after("/bb");
+        assert!(
+            screen.ends_with("input.c.bb                     test.json\n/bb"),
+            "{:?}",
+            screen
+        );
+        let screen = screen_after("/bb<");
+        assert!(
+            screen.ends_with("input.a.b                      test.json\n/b"),
+            "{:?}",
+            screen
+        );
+        let screen = screen_after("/b(");
+        assert!(
+            screen.ends_with("input                          test.json\n/b("),
+            "{:?}",
+            screen
+        );
+
+        // Escape goes back to where the search started.
+        let screen = screen_after("j/bb^");
+        assert!(
+            screen.ends_with("input.a                        test.json\n:"),
+            "{:?}",
+            screen
+        );
+
+        // Enter runs the search as usual.
+        let screen = screen_after("/bb\n");
+        assert!(
+            screen
+                .ends_with("input.c.bb                     test.json\n/bb       [1/1, 1 visible]"),
+            "{:?}",
+            screen
+        );
+    }
+
+    #[test]
+    fn test_split_screen() {
+        let screen_after = |args: &[&str], keys: &[Key]| {
+            let args = [
+                &["--screen-size", "40x10", "--cmd", ":set nonumber"][..],
+                args,
+            ]
+            .concat();
+            run_jless(&args, r#"{"a": [1, 2], "b": {"c": true}}"#, keys).unwrap()
+        };
+
+        // The views share which containers are collapsed by default.
+        let keys = [
+            Key::Char('j'),
+            Key::Char('c'),
+            Key::Ctrl('w'),
+            Key::Char('G'),
+        ];
+        let expected = [
+            "▽ (2) {a: […], b: {…}}",
+            "  ▶ a: (2) [1, 2]",
+            "  ▷ b: (1) {c: true}",
+            "input.a",
+            "▽ (2) {a: […], b: {…}}",
+            "  ▷ a: (2) [1, 2]",
+            "  ▶ b: (1) {c: true}",
+            "~",
+            "input.b                        test.json",
+            ":",
+        ];
+        assert_eq!(
+            expected.join("\n"),
+            screen_after(&["--cmd", ":split"], &keys)
+        );
+
+        let expected = [
+            "▽ (2) {a: […], b: {…}}",
+            "  ▶ a: (2) [1, 2]",
+            "  ▷ b: (1) {c: true}",
+            "input.a",
+            "      [0]: 1",
+            "      [1]: 2",
+            "  ▽ b: (1) {c: true}",
+            "    ▶ c: true",
+            "input.b.c                      test.json",
+            ":",
+        ];
+        assert_eq!(
+            expected.join("\n"),
+            screen_after(&["--cmd", ":set splitcollapse=independent | :split"], &keys)
+        );
+
+        // :close closes the focused view, leaving the other one.
+        let expected = [
+            "▼ (2) {a: […], b: {…}}",
+            "  ▽ a: (2) [1, 2]",
+            "      [0]: 1",
+            "      [1]: 2",
+            "  ▽ b: (1) {c: true}",
+            "      c: true",
+            "~",
+            "~",
+            "input                          test.json",
+            ":",
+        ];
+        assert_eq!(
+            expected.join("\n"),
+            screen_after(&["--cmd", ":split | j | :close"], &[])
+        );
+    }
+
+    #[test]
+    fn test_jump_back_after_search() {
+        const JSON: &str = r#"{"a": 1, "b": 2, "c": 3, "d": 4, "e": 5, "f": "x"}"#;
+
+        // Jumping off screen shows where the jump came from.
+        let screen = screen_after("40x5", JSON, "j | /x");
+        assert!(
+            screen.ends_with(
+                "input.f                        test.json\nJumped from input.a; '' to go back"
+            ),
+            "{:?}",
+            screen
+        );
+        let screen = screen_after("40x5", JSON, "j | /x | ''");
+        assert!(
+            screen.ends_with("input.a                        test.json\n:"),
+            "{:?}",
+            screen
+        );
+        // Going back again bounces to where '' was used.
+        let screen = screen_after("40x5", JSON, "j | /x | ''''");
+        assert!(
+            screen.ends_with("input.f                        test.json\n:"),
+            "{:?}",
+            screen
+        );
+        let screen = screen_after("40x5", JSON, "j | ''");
+        assert!(
+            screen.ends_with(
+                "input.a                        test.json\nNo search jump to go back from"
+            ),
+            "{:?}",
+            screen
+        );
+        // Other keys starting with ' still jump to matching keys.
+        let screen = screen_after("40x5", JSON, "j | 'c");
+        assert!(
+            screen.ends_with("input.c                        test.json\n:"),
+            "{:?}",
+            screen
+        );
+    }
+
+    #[test]
+    fn test_timeline_keys() {
+        const JSON: &str = "{\"ts\": 1, \"a\": 1}\n{\"ts\": 5, \"a\": 2}\n{\"ts\": 9, \"a\": 3}";
+
+        let screen = screen_after("40x6", JSON, ":timeline | ]t | ]t | [t");
+        assert!(screen.contains("\ninput[1] "), "{:?}", screen);
+        // ]c still jumps between changes while the timeline is shown.
+        let screen = screen_after("40x6", JSON, ":timeline | ]c");
+        assert!(
+            screen.ends_with("No changes are marked; use :reload or --"),
+            "{:?}",
+            screen
+        );
+        let screen = screen_after("40x6", JSON, "]t");
+        assert!(
+            screen.ends_with("No timeline; use :timeline"),
+            "{:?}",
+            screen
+        );
+    }
+
+    #[test]
+    fn test_frames_are_written_once() {
+        // Counts the writes made to a ScreenCapture.
+        struct CountingOutput(ScreenCapture, Rc<RefCell<usize>>);
+
+        impl io::Write for CountingOutput {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                *self.1.borrow_mut() += 1;
+                self.0.write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                self.0.flush()
+            }
+        }
+
+        impl Output for CountingOutput {
+            fn suspend_raw_mode(&self) -> io::Result<()> {
+                Ok(())
+            }
+
+            fn activate_raw_mode(&self) -> io::Result<()> {
+                Ok(())
+            }
+
+            fn is_interactive(&self) -> bool {
+                false
+            }
+        }
+
+        let writes_after = |keys: &str| {
+            let opt = Opt::parse_from(["jless", "--screen-size", "30x5"]);
+            let capture = ScreenCapture::new(opt.screen_size);
+            let writes = Rc::new(RefCell::new(0));
+            let output = CountingOutput(capture.clone(), writes.clone());
+            let mut app = App::new(
+                &opt,
+                r#"{"a": 1, "b": 2}"#.to_string(),
+                DataFormat::Json,
+                "test.json".to_string(),
+                Box::new(output),
+            )
+            .unwrap();
+            let events: Vec<io::Result<TuiEvent>> = keys
+                .chars()
+                .map(|c| Ok(TuiEvent::KeyEvent(Key::Char(c))))
+                .collect();
+            app.run(Box::new(events.into_iter()), opt.screen_size);
+            let writes = *writes.borrow();
+            (writes, capture.contents())
+        };
+
+        let (initial_writes, _) = writes_after("");
+        // A frame that changed is written with a single write.
+        let (writes, screen) = writes_after("j");
+        assert_eq!(initial_writes + 1, writes);
+        assert!(screen.contains("input.a"), "{:?}", screen);
+        // Identical frames aren't written at all.
+        let (writes, screen) = writes_after("jjjjj");
+        assert_eq!(initial_writes + 2, writes);
+        assert!(screen.contains("input.b"), "{:?}", screen);
+    }
+
+    #[test]
+    fn test_control_commands() {
+        let screen_after = |events: Vec<TuiEvent>| {
+            let opt = Opt::parse_from(["jless", "--screen-size", "40x6", "--cmd", ":set nonumber"]);
+            run_headless(
+                &opt,
+                r#"{"a": [1, 2], "b": {"c": "needle"}}"#.to_string(),
+                DataFormat::Json,
+                "test.json".to_string(),
+                Box::new(events.into_iter().map(Ok)),
+            )
+            .unwrap()
+        };
+
+        // Going to a path expands its collapsed ancestors.
+        let screen = screen_after(vec![
+            TuiEvent::KeyEvent(Key::Char('c')),
+            TuiEvent::Control(ControlCommand::Goto(".a[1]".to_string())),
+        ]);
+        assert!(screen.contains("    ▶ [1]: 2\n"), "{:?}", screen);
+
+        let screen = screen_after(vec![TuiEvent::Control(ControlCommand::Goto(
+            ".x".to_string(),
+        ))]);
+        assert!(screen.contains("No value at path .x"), "{:?}", screen);
+
+        let screen = screen_after(vec![TuiEvent::Control(ControlCommand::Search(
+            "needle".to_string(),
+        ))]);
+        assert!(screen.contains("input.b.c"), "{:?}", screen);
+
+        // A pending multi-key command is cancelled.
+        let screen = screen_after(vec![
+            TuiEvent::KeyEvent(Key::Char('z')),
+            TuiEvent::Control(ControlCommand::Goto(".b".to_string())),
+            TuiEvent::KeyEvent(Key::Char('j')),
+        ]);
+        assert!(screen.contains("input.b.c"), "{:?}", screen);
+    }
+
+    #[test]
+    fn test_outline_focus() {
+        const JSON: &str = r#"{"a": 1, "b": 2, "c": 3}"#;
+        let run = |keys: &[Key]| {
+            let args = ["--screen-size", "60x5", "--cmd", ":set nonumber"];
+            run_jless(&args, JSON, keys).unwrap()
+        };
+
+        // O opens the outline and focuses it, and Enter jumps to the
+        // selected entry.
+        let screen = run(&[
+            Key::Char('O'),
+            Key::Char('j'),
+            Key::Char('j'),
+            Key::Char('\n'),
+        ]);
+        assert!(screen.contains("\ninput.c "), "{:?}", screen);
+        // O goes back to the main view, leaving the outline open.
+        let screen = run(&[Key::Char('O'), Key::Char('O'), Key::Char('j')]);
+        assert!(screen.contains("│ a\n"), "{:?}", screen);
+        assert!(screen.contains("\ninput.a "), "{:?}", screen);
+    }
+
+    #[test]
+    fn test_startup_focus_is_shown_with_context() {
+        let path = std::env::temp_dir().join(format!("jless-test-{}.pos", std::process::id()));
+        std::fs::write(&path, "jless position v1\nfocus [40]\n").unwrap();
+
+        let numbers: Vec<String> = (0..60).map(|n| n.to_string()).collect();
+        let args = [
+            "--screen-size",
+            "30x8",
+            "--cmd",
+            ":set nonumber",
+            "--restorepos",
+            path.to_str().unwrap(),
+        ];
+        let screen = run_jless(&args, &format!("[{}]", numbers.join(", ")), &[]).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let lines: Vec<&str> = screen.lines().collect();
+        assert!(lines[2].starts_with("  ▶ [40]: 40"), "{:?}", screen);
+    }
+
+    #[test]
+    fn test_diff_files() {
+        let path = std::env::temp_dir().join(format!("jless-test-{}-old.json", std::process::id()));
+        std::fs::write(&path, r#"{"a": 1, "b": {"c": 2, "d": 3}, "e": [1, 2]}"#).unwrap();
+
+        let screen_after = |commands: &[&str]| {
+            let mut args = vec!["-N", "--screen-size", "100x12", "--diff"];
+            args.push(path.to_str().unwrap());
+            args.extend(commands);
+            let json = r#"{"a": 1, "b": {"c": 20, "d": 3}, "e": [1], "f": true}"#;
+            run_jless(&args, json, &[]).unwrap()
+        };
+        // The marker in the gutter of the row showing the given value.
+        let marker_on_line_with = |screen: &str, text: &str| {
+            let mut chars = screen
+                .lines()
+                .map(|line| line.chars())
+                .find(|chars| {
+                    chars
+                        .clone()
+                        .skip(1)
+                        .collect::<String>()
+                        .trim_start()
+                        .starts_with(text)
+                })
+                .unwrap();
+            chars.next().unwrap()
+        };
+
+        let screen = screen_after(&[]);
+        assert_eq!(' ', marker_on_line_with(&screen, "a: 1"));
+        assert_eq!('~', marker_on_line_with(&screen, "c: 20"));
+        assert_eq!(' ', marker_on_line_with(&screen, "d: 3"));
+        assert_eq!('-', marker_on_line_with(&screen, "▽ e: (1)"));
+        assert_eq!('+', marker_on_line_with(&screen, "f: true"));
+        let message = format!(
+            "Showing changes from {}; use ]c and [c to jump between them",
+            path.display()
+        );
+        assert!(screen.ends_with(&message), "{:?}", screen);
+
+        let screen = screen_after(&["--cmd", "]c"]);
+        assert!(screen.contains("input.b.c "), "{:?}", screen);
+        let screen = screen_after(&["--cmd", "]c]c]c"]);
+        assert!(screen.contains("input.f "), "{:?}", screen);
+        let screen = screen_after(&["--cmd", "]c]c]c[c"]);
+        assert!(screen.contains("input.e "), "{:?}", screen);
+        let screen = screen_after(&["--cmd", "]c]c]c]c"]);
+        assert!(screen.ends_with("No more changes below"), "{:?}", screen);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_checksum() {
+        let checksum = |json: &str| {
+            let screen = screen_after("80x4", json, ":checksum");
+            screen.lines().last().unwrap().to_string()
+        };
+
+        let expected = format!("SHA-256: {}", sha256::hex_digest(br#"{"a":[1,2],"b":"c"}"#));
+        assert_eq!(expected, checksum(r#"{"b": "c", "a": [1, 2]}"#));
+        assert_eq!(expected, checksum(r#"{"a": [1.0, 2e0], "b": "\u0063"}"#));
+    }
+
+    #[test]
+    fn test_search_matches_list() {
+        let args = [
+            "--screen-size",
+            "80x8",
+            "--cmd",
+            ":set nonumber | /name | :matches",
+        ];
+        // Jump to the second match in the list, which is the deepest one.
+        let keys = [Key::Char('j'), Key::Char('\n')];
+        let json = r#"{"a": {"b": {"name": "x"}}, "name": "top"}"#;
+        let screen = run_jless(&args, json, &keys).unwrap();
+
+        let lines: Vec<&str> = screen.lines().collect();
+        assert!(lines[0].ends_with("│ .name: \"top\""), "{:?}", screen);
+        assert!(lines[1].ends_with("│ .a.b.name: \"x\""), "{:?}", screen);
+        assert!(lines[6].starts_with("input.a.b.name "), "{:?}", screen);
+    }
+
+    #[test]
+    fn test_secure_mode() {
+        let last_line_after = |commands: &str| {
+            let args = ["--secure", "--screen-size", "60x4", "--cmd", commands];
+            let screen = run_jless(&args, r#"{"a": 1}"#, &[]).unwrap();
+            screen.lines().last().unwrap().to_string()
+        };
+
+        let path = std::env::temp_dir().join(format!("jless-secure-{}", std::process::id()));
+        let command = format!(":savepos {}", path.display());
+        assert_eq!(
+            "Saving positions is disabled by --secure",
+            last_line_after(&command)
+        );
+        assert!(!path.exists());
+        let command = format!(":w {}", path.display());
+        assert_eq!(
+            "Writing files is disabled by --secure",
+            last_line_after(&command)
+        );
+        assert!(!path.exists());
+        assert_eq!("Copying is disabled by --secure", last_line_after("y"));
+    }
+
+    #[test]
+    fn test_chunk_string() {
+        const JSON: &str = r#"{"log": "one\ntwo\nthree", "next": 1}"#;
+
+        let chunked = screen_after("50x8", JSON, ":set nonumber | j | :chunk | j | j");
+        let expected = [
+            "▽ (2) {log: […], next: 1}",
+            r#"  ▽ log: (3) ["one\n", "two\n", "three"]"#,
+            r#"      [0]: "one\n""#,
+            r#"    ▶ [1]: "two\n""#,
+            r#"      [2]: "three""#,
+            "    next: 1",
+            "input.log[1/3]                 test.json [chunked]",
+            ":",
+        ];
+        assert_eq!(expected.join("\n"), chunked);
+
+        // Goes back to the string, and rows after it stay focused.
+        let unchunked = screen_after(
+            "50x8",
+            JSON,
+            ":set nonumber | j | :chunk | j | j | :unchunk",
+        );
+        assert!(unchunked.contains("input.log "), "{:?}", unchunked);
+        let unchunked = screen_after("50x8", JSON, ":set nonumber | j | :chunk 4 | G | :unchunk");
+        assert!(unchunked.contains("input.next "), "{:?}", unchunked);
+    }
+
+    #[test]
+    fn test_edit_value() {
+        const JSON: &str = r#"{"name": "old", "count": 1}"#;
+
+        // The edited value is marked as modified in the gutter.
+        let expected = [
+            "·▽ (2) {name: \"a new name\", count: 1}",
+            "~  ▶ name: \"a new name\"",
+            "     count: 1",
+            "~",
+            "input.name                    test.json [modified]",
+            "Value updated; use u to undo",
+        ];
+        let screen = screen_after("50x6", JSON, r#":set nonumber | j | :edit "a new name""#);
+        assert_eq!(expected.join("\n"), screen);
+
+        // Searches use the updated document.
+        let screen = screen_after("50x6", JSON, r#"j | :edit "x" | /1"#);
+        assert!(screen.contains("input.count "), "{:?}", screen);
+
+        let screen = screen_after("50x6", JSON, "j | :edit nope");
+        assert!(screen.contains("Invalid value: nope;"), "{:?}", screen);
+        let screen = screen_after("50x6", JSON, ":edit 1");
+        assert!(screen.ends_with("Must be focused on a primitive value to edit it"));
+    }
+
+    #[test]
+    fn test_delete_value() {
+        const JSON: &str = r#"{"name": "old", "tags": ["a", "b"], "count": 1}"#;
+
+        // Later elements are shifted down, and the container is marked as
+        // modified in the gutter.
+        let expected = [
+            "·▽ (3) {name: \"old\", tags: […], count: 1}",
+            "     name: \"old\"",
+            "~  ▽ tags: (1) [\"b\"]",
+            "     ▶ [0]: \"b\"",
+            "     count: 1",
+            "~",
+            "input.tags[0/1]                         test.json [modified]",
+            "Deleted input.tags[0/2]; use u to undo",
+        ];
+        let screen = screen_after("60x8", JSON, ":set nonumber | j | j | j | d");
+        assert_eq!(expected.join("\n"), screen);
+
+        let screen = screen_after("60x8", JSON, "j | :delete");
+        assert!(screen.contains("{tags: […], count: 1}"), "{:?}", screen);
+        assert!(screen.contains("input.tags "), "{:?}", screen);
+
+        let screen = screen_after("60x8", JSON, "d");
+        assert!(screen.ends_with("Can't delete the only value in the document"));
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        const JSON: &str = r#"{"name": "old", "tags": ["a", "b"], "count": 1}"#;
+        let viewer_lines = |screen: &str| {
+            screen
+                .lines()
+                .take(6)
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        // Undoing every change restores the original document, without any
+        // markers in the gutter, though it's still marked as modified.
+        let original = screen_after("60x8", JSON, ":set nonumber | j | j | j");
+        let undone = screen_after(
+            "60x8",
+            JSON,
+            ":set nonumber | j | j | j | d | j | :edit 2 | u | u",
+        );
+        assert_eq!(viewer_lines(&original), viewer_lines(&undone));
+        assert!(undone.ends_with("Undid :delete; use ctrl-r to redo"));
+
+        let redone = screen_after("60x8", JSON, ":set nonumber | j | j | j | d | u | :redo");
+        assert!(redone.contains("~  ▽ tags: (1) [\"b\"]"), "{:?}", redone);
+        assert!(redone.ends_with("Redid :delete"));
+
+        // Making another change forgets the changes that were undone.
+        let screen = screen_after("60x8", JSON, "j | :edit 2 | u | :edit 3 | :redo");
+        assert!(screen.ends_with("Already at newest change"));
+        let screen = screen_after("60x8", JSON, "u");
+        assert!(screen.ends_with("Already at oldest change"));
+    }
+
+    #[test]
+    fn test_count_matches() {
+        const JSON: &str = r#"{"logs": [{"level": "error"}, {"level": "warn"}, {"level": "error"}], "status": "error"}"#;
+
+        let screen = screen_after("70x6", JSON, "j | :count error");
+        assert!(
+            screen.ends_with("2 matches in 2 rows under input.logs"),
+            "{:?}",
+            screen
+        );
+        let screen = screen_after("70x6", JSON, ":count error");
+        assert!(
+            screen.ends_with("3 matches in 3 rows under input"),
+            "{:?}",
+            screen
+        );
+
+        // The current search is left alone.
+        let screen = screen_after("70x6", JSON, "/status | gg | :count level | n");
+        assert!(screen.contains("input.status "), "{:?}", screen);
+
+        let screen = screen_after("70x6", JSON, ":count");
+        assert!(screen.ends_with("Usage: :count PATTERN"));
+    }
+
+    #[test]
+    fn test_write_document() {
+        const JSON: &str = r#"{"name": "old", "tags": ["a", "b"]}"#;
+        let path = std::env::temp_dir().join(format!("jless-test-{}-w.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let path = path.to_str().unwrap();
+
+        let screen = screen_after("90x6", JSON, r#"j | :edit "new""#);
+        assert!(screen.contains("test.json [modified]"), "{:?}", screen);
+
+        // Edits are written, and the document is no longer modified.
+        let screen = screen_after("90x6", JSON, &format!(r#"j | :edit "new" | :w {path}"#));
+        assert!(!screen.contains("[modified]"), "{:?}", screen);
+        assert!(
+            screen.ends_with(&format!("Wrote {path} (54 B)")),
+            "{:?}",
+            screen
+        );
+        let expected = indoc! {r#"
+            {
+              "name": "new",
+              "tags": [
+                "a",
+                "b"
+              ]
+            }
+        "#};
+        assert_eq!(expected, std::fs::read_to_string(path).unwrap());
+
+        // Existing files are only overwritten with :w!.
+        let screen = screen_after("90x6", JSON, &format!("j | j | d | :w {path}"));
+        assert!(screen.ends_with("already exists; use :w! to overwrite it"));
+        screen_after("90x6", JSON, &format!("j | j | d | :w! {path}"));
+        let written = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!("{\n  \"name\": \"old\"\n}\n", written);
+
+        let screen = screen_after("90x6", JSON, ":w");
+        assert!(screen.ends_with("Input was read from STDIN; use :w FILE to write it"));
+
+        // Copies of the document that are derived from the input aren't
+        // written in place of it.
+        for commands in [":hide key=name", "j | :chunk 2"] {
+            let screen = screen_after("90x6", JSON, &format!("{commands} | :w! {path}"));
+            assert!(
+                screen.ends_with("Use :unhide, :unchunk and :unfilter before writing"),
+                "{:?}",
+                screen
+            );
+            assert!(!std::path::Path::new(path).exists());
+        }
+    }
+
+    #[test]
+    fn test_open_file() {
+        const JSON: &str = r#"{"name": "old", "tags": ["a", "b"]}"#;
+        let path =
+            std::env::temp_dir().join(format!("jless-test-{}-open.yaml", std::process::id()));
+        std::fs::write(&path, "name: opened\nlist: [1, 2]\n").unwrap();
+        let filename = path.file_name().unwrap().to_str().unwrap();
+        let path = path.to_str().unwrap();
+
+        // The format of the file is determined by its extension.
+        let screen = screen_after("90x6", JSON, &format!(":open {path}"));
+        assert!(screen.contains(r#"name: "opened""#), "{:?}", screen);
+        assert!(screen.contains("list: (2) [1, 2]"), "{:?}", screen);
+        assert!(
+            screen.ends_with(&format!("Opened {filename}")),
+            "{:?}",
+            screen
+        );
+
+        // Unwritten changes are only discarded with :open!.
+        let screen = screen_after("90x6", JSON, &format!(r#"j | :edit "new" | :open {path}"#));
+        assert!(screen.contains(r#"name: "new""#), "{:?}", screen);
+        assert!(screen.ends_with("use :w to write them, or :open! to discard them"));
+        let screen = screen_after("90x6", JSON, &format!(r#"j | :edit "new" | :e! {path}"#));
+        assert!(screen.contains(r#"name: "opened""#), "{:?}", screen);
+        assert!(!screen.contains("[modified]"), "{:?}", screen);
+
+        std::fs::remove_file(path).unwrap();
+
+        let screen = screen_after("90x6", JSON, &format!(":open {path}"));
+        assert!(screen.contains(r#"name: "old""#), "{:?}", screen);
+        assert!(screen.contains("Unable to open"), "{:?}", screen);
+        let screen = screen_after("90x6", JSON, ":open");
+        assert!(screen.ends_with("Usage: :open FILE"), "{:?}", screen);
+    }
+
+    #[test]
+    fn test_table_view() {
+        const DATA: &str = r#"[{"id": 1, "name": "a"}, {"id": 2, "owner": {"x": 1}}]"#;
+
+        // Keys missing from an element leave its cell blank.
+        let screen = screen_after("90x8", DATA, "T");
+        assert!(screen.contains("  │ id │ name │ owner\n"), "{:?}", screen);
+        assert!(screen.contains("0 │ 1  │ \"a\"  │\n"), "{:?}", screen);
+        assert!(screen.contains("1 │ 2  │      │ {…}\n"), "{:?}", screen);
+        assert!(screen.ends_with("Enter jumps to the selected one, T goes back"));
+
+        // Enter goes back to the viewer, focused on the selected element.
+        let args = ["--screen-size", "90x8", "--cmd", "T | j"];
+        let screen = run_jless(&args, DATA, &[Key::Char('\n')]).unwrap();
+        assert!(!screen.contains("│ id │"), "{:?}", screen);
+        assert!(screen.contains("input[1/2] "), "{:?}", screen);
+
+        // Searches in a table only match values in a single column.
+        const PEOPLE: &str =
+            r#"[{"name": "ann", "boss": "bob"}, {"name": "bob"}, {"name": "bobby"}]"#;
+        let screen = screen_after("90x8", PEOPLE, "T | /name:bob");
+        assert!(screen.contains("1 │ \"bob\"   │\n"), "{:?}", screen);
+        assert!(screen.ends_with("Match 1 of 2 in name"), "{:?}", screen);
+        let screen = screen_after("90x8", PEOPLE, "T | /name:bob | n | n");
+        assert!(
+            screen.ends_with("Match 1 of 2 in name (wrapped)"),
+            "{:?}",
+            screen
+        );
+        let screen = screen_after("90x8", PEOPLE, "T | ?boss:bob");
+        assert!(
+            screen.ends_with("Match 1 of 1 in boss (wrapped)"),
+            "{:?}",
+            screen
+        );
+        let screen = screen_after("90x8", PEOPLE, "T | /boss:ann");
+        assert!(
+            screen.ends_with("Pattern not found in boss: ann"),
+            "{:?}",
+            screen
+        );
+
+        let screen = screen_after("90x8", "[1, 2]", "T");
+        assert!(screen.ends_with("only Arrays of Objects can be shown as a table"));
+        let screen = screen_after("90x8", r#"{"a": 1}"#, "T");
+        assert!(screen.ends_with("Must be focused on an Array to use :table"));
+    }
+
+    #[test]
+    fn test_print_pages() {
+        let numbers: Vec<String> = (0..20).map(|n| n.to_string()).collect();
+        let data = format!("[{}]", numbers.join(", "));
+        let screen_after = |keys: &[Key]| {
+            let keys = [&[Key::Char('p'), Key::Char('p')][..], keys].concat();
+            run_jless(&["--screen-size", "30x6"], &data, &keys).unwrap()
+        };
+
+        // Printed content that doesn't fit is shown a page at a time.
+        let expected = [
+            "[",
+            "  0,",
+            "  1,",
+            "  2,",
+            "  3,",
+            "-- Lines 1-5 of 22 (space: nex",
+        ];
+        assert_eq!(expected.join("\n"), screen_after(&[]));
+
+        let expected = [
+            "  4,",
+            "  5,",
+            "  6,",
+            "  7,",
+            "  8,",
+            "-- Lines 6-10 of 22 (space: ne",
+        ];
+        assert_eq!(expected.join("\n"), screen_after(&[Key::Char(' ')]));
+        let screen = screen_after(&[Key::Char(' '), Key::Char('b')]);
+        assert!(screen.starts_with("[\n  0,"), "{:?}", screen);
+
+        let screen = screen_after(&[Key::Char(' '), Key::Char('q')]);
+        assert!(screen.contains("test.json"), "{:?}", screen);
+    }
+
+    #[test]
+    fn test_print_jq_command() {
+        let print = |data: &str, data_format| {
+            let opt = Opt::parse_from(["jless", "--screen-size", "60x4", "--cmd", "j | j"]);
+            let keys = [Key::Char('p'), Key::Char('j')];
+            let events = keys.iter().map(|&key| Ok(TuiEvent::KeyEvent(key)));
+            run_headless(
+                &opt,
+                data.to_string(),
+                data_format,
+                "input".to_string(),
+                Box::new(events.collect::<Vec<_>>().into_iter()),
+            )
+            .unwrap()
+        };
+
+        let screen = print("{\"a\": {\"b\": 1}}", DataFormat::Json);
+        assert!(screen.starts_with("jq '.a.b'"), "{:?}", screen);
+        let screen = print("a:\n  b: 1\n", DataFormat::Yaml);
+        assert!(screen.starts_with("yq '.a.b'"), "{:?}", screen);
+        let screen = print("{a: {b: 1}}", DataFormat::Hjson);
+        assert!(
+            screen.ends_with("jq can't read Hjson input; use yq to copy the path"),
+            "{:?}",
+            screen
+        );
+    }
+
+    #[test]
+    fn test_smooth_scroll_pages() {
+        let numbers: Vec<String> = (0..100).map(|n| n.to_string()).collect();
+        let data = format!("[{}]", numbers.join(", "));
+        let screen_after = |args: &[&str]| {
+            let args = [&["--screen-size", "30x10"][..], args].concat();
+            let keys = [Key::PageDown, Key::PageDown, Key::PageUp];
+            run_jless(&args, &data, &keys).unwrap()
+        };
+
+        // Scrolling by pages in two steps ends up in the same place.
+        let screen = screen_after(&["--smooth-scroll"]);
+        assert_eq!(screen_after(&[]), screen);
+        assert!(screen.contains("input["), "{:?}", screen);
+    }
+
+    #[test]
+    fn test_allow_nan() {
+        let run = |args: &[&str]| {
+            let args = [&["--screen-size", "70x4"][..], args].concat();
+            run_jless(&args, r#"{"a": NaN, "b": -Infinity}"#, &[])
+        };
+
+        let err = run(&[]).unwrap_err();
+        assert!(
+            err.contains("NaN isn't valid JSON; use --allow-nan"),
+            "{}",
+            err
+        );
+
+        let screen = run(&["--allow-nan"]).unwrap();
+        assert!(
+            screen.contains("▼ (2) {a: NaN, b: -Infinity}"),
+            "{:?}",
+            screen
+        );
+        assert!(screen.ends_with("Input contains NaN or Infinity, so it isn't strictly valid JSON"));
+    }
+
+    #[test]
+    fn test_reverse() {
+        const JSON: &str = r#"{"logs": ["a", "b", "c"], "n": 1}"#;
+
+        let screen = screen_after("40x8", JSON, ":set nonumber | j | :reverse | j");
+        assert!(
+            screen.contains("logs: (3, reversed) [\"c\", \"b\", \"a\"]"),
+            "{:?}",
+            screen
+        );
+        assert!(
+            screen.contains("▶ [2]: \"c\"\n      [1]: \"b\""),
+            "{:?}",
+            screen
+        );
+        assert!(screen.contains("input.logs[2/3]"), "{:?}", screen);
+
+        // Trailing commas follow the displayed order.
+        let args = [
+            "--screen-size",
+            "40x8",
+            "--mode",
+            "line",
+            "--cmd",
+            ":set nonumber | j | :reverse | /b",
+        ];
+        let screen = run_jless(&args, JSON, &[]).unwrap();
+        assert!(
+            screen.contains("\"c\",\n▶     \"b\",\n      \"a\"\n    ],"),
+            "{:?}",
+            screen
+        );
+        assert!(screen.contains("input.logs[1/3]"), "{:?}", screen);
+    }
+
+    #[test]
+    fn test_sample_with_seed() {
+        let json = format!(
+            "[{}]",
+            (0..100)
+                .map(|n| n.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        let sample = |seed: &str| {
+            let args = [
+                "--screen-size",
+                "40x5",
+                "--seed",
+                seed,
+                "--cmd",
+                ":sample | :sample",
+            ];
+            let screen = run_jless(&args, &json, &[]).unwrap();
+            screen.lines().last().unwrap().to_string()
+        };
+
+        let message = sample("7");
+        assert!(message.starts_with("Sampled element "), "{:?}", message);
+        assert_eq!(message, sample("7"));
+        assert!(
+            (1..20).any(|seed| sample(&seed.to_string()) != message),
+            "{:?}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_tolerant() {
+        let run = |args: &[&str]| {
+            let args = [
+                &["--screen-size", "40x5", "--cmd", ":set nonumber"][..],
+                args,
+            ]
+            .concat();
+            run_jless(&args, "{\"a\": 1}\nnope\n{\"a\": 2}\n", &[])
+        };
+
+        // Plain JSON input with bad lines isn't silently re-parsed.
+        assert!(run(&[]).is_err());
+
+        let screen = run(&["--tolerant"]).unwrap();
+        assert!(screen.contains("▼ (1) {a: 1}"), "{:?}", screen);
+        assert!(screen.contains("test.json [1 error]"), "{:?}", screen);
+    }
+
+    #[test]
+    fn test_ndjson_records_are_appended() {
+        let opt = Opt::parse_from(["jless", "--screen-size", "40x8", "--cmd", ":set nonumber"]);
+        let mut more_records = Records::parse("{\"a\": 2}\nnope\n{\"a\": 3}", 2, false);
+        more_records.last = true;
+        let events = vec![
+            Ok(TuiEvent::Records(more_records)),
+            Ok(TuiEvent::KeyEvent(Key::Char('G'))),
+        ];
+        let screen = run_headless(
+            &opt,
+            "{\"a\": 1}\n".to_string(),
+            DataFormat::Ndjson,
+            "log.ndjson".to_string(),
+            Box::new(events.into_iter()),
+        )
+        .unwrap();
+
+        let expected = [
+            "▽ (1) {a: 1}",
+            "    a: 1",
+            "▽ (1) {a: 2}",
+            "    a: 2",
+            "▽ (1) {a: 3}",
+            "  ▶ a: 3",
+            "input[2].a          log.ndjson [1 error]",
+            ":",
+        ];
+        assert_eq!(expected.join("\n"), screen);
+    }
+
+    #[test]
+    fn test_result_buffers() {
+        let screen_after = |commands: &str, keys: &str| {
+            let opt = Opt::parse_from([
+                "jless",
+                "--screen-size",
+                "40x6",
+                "--cmd",
+                ":set nonumber",
+                "--cmd",
+                commands,
+            ]);
+            let mut records = Records::parse("{\"a\": 3}", 4, false);
+            records.last = true;
+            let mut events = vec![Ok(TuiEvent::Records(records))];
+            events.extend(keys.chars().map(|c| Ok(TuiEvent::KeyEvent(Key::Char(c)))));
+            run_headless(
+                &opt,
+                "{\"a\": 1}\nnope\n{\"a\": 2}\n".to_string(),
+                DataFormat::Ndjson,
+                "log.ndjson".to_string(),
+                Box::new(events.into_iter()),
+            )
+            .unwrap()
+        };
+
+        // Records that arrive while a result buffer is shown are appended
+        // once it's closed.
+        let expected = [
+            "▼ (1) {\"Line 2\": \"Parse error\"}",
+            "    \"Line 2\": \"Parse error\"",
+            "~",
+            "~",
+            "input                     [parse errors]",
+            ":",
+        ];
+        let screen = screen_after("G | :errors", "");
+        assert_eq!(expected.join("\n"), screen);
+
+        let expected = [
+            "▽ (1) {a: 1}",
+            "    a: 1",
+            "▽ (1) {a: 2}",
+            "  ▶ a: 2",
+            "input[1].a          log.ndjson [1 error]",
+            ":",
+        ];
+        assert_eq!(expected.join("\n"), screen_after("G | :errors", "q"));
+        let screen = screen_after("G | :errors", "qG");
+        assert!(screen.contains("input[2].a "), "{:?}", screen);
+
+        // Only the input is written, not a result buffer.
+        let path = std::env::temp_dir().join(format!("jless-test-{}-r.json", std::process::id()));
+        let command = format!(":errors | :w! {}", path.display());
+        let opt = Opt::parse_from(["jless", "--screen-size", "40x6", "--cmd", &command]);
+        let screen = run_headless(
+            &opt,
+            "{\"a\": 1}\nnope\n".to_string(),
+            DataFormat::Ndjson,
+            "log.ndjson".to_string(),
+            Box::new(std::iter::empty()),
+        )
+        .unwrap();
+        assert!(
+            screen.ends_with("Close result buffers before writing"),
+            "{:?}",
+            screen
+        );
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_follow_records() {
+        let screen_after = |keys: &str| {
+            let opt = Opt::parse_from([
+                "jless",
+                "--follow",
+                "--screen-size",
+                "40x5",
+                "--cmd",
+                ":set nonumber",
+            ]);
+            let mut events = vec![Ok(TuiEvent::Records(Records::parse(
+                "{\"a\": 2}",
+                2,
+                false,
+            )))];
+            events.extend(keys.chars().map(|c| Ok(TuiEvent::KeyEvent(Key::Char(c)))));
+            events.push(Ok(TuiEvent::Records(Records::parse(
+                "{\"a\": 3}",
+                3,
+                false,
+            ))));
+            run_headless(
+                &opt,
+                "{\"a\": 1}\n".to_string(),
+                DataFormat::Ndjson,
+                "log.ndjson".to_string(),
+                Box::new(events.into_iter()),
+            )
+            .unwrap()
+        };
+
+        // The last record stays focused.
+        let expected = [
+            "    a: 2",
+            "▽ (1) {a: 3}",
+            "  ▶ a: 3",
+            "input[2].a        log.ndjson [following]",
+            ":",
+        ];
+        assert_eq!(expected.join("\n"), screen_after(""));
+
+        // Until moving somewhere else.
+        let expected = [
+            "▼ (1) {a: 1}",
+            "    a: 1",
+            "▽ (1) {a: 2}",
+            "input[0]            log.ndjson [loading]",
+            ":",
+        ];
+        assert_eq!(expected.join("\n"), screen_after("g"));
+
+        // F starts following again, like in less.
+        let expected = [
+            "    a: 2",
+            "▽ (1) {a: 3}",
+            "  ▶ a: 3",
+            "input[2].a        log.ndjson [following]",
+            ":",
+        ];
+        assert_eq!(expected.join("\n"), screen_after("gF"));
+    }
+
+    #[test]
+    fn test_filter() {
+        const JSON: &str = r#"{"items": [{"id": 1}, {"id": 2}]}"#;
+        if std::process::Command::new("jq")
+            .arg("--version")
+            .output()
+            .is_err()
+        {
+            return;
+        }
+
+        let filtered = screen_after("50x4", JSON, ":set nonumber | :filter .items[].id");
+        let expected = [
+            "▶ 1",
+            "  2",
+            "input[0]           test.json [filter: .items[].id]",
+            "Filtered through .items[].id; press ^^ to switch t",
+        ];
+        assert_eq!(expected.join("\n"), filtered);
+
+        // The focused value is replaced in place.
+        let filtered = screen_after("50x4", JSON, ":set nonumber | j | :filter! map(.id)");
+        assert!(filtered.contains("▼ items: (2) [1, 2]"), "{:?}", filtered);
+
+        // :filter on its own (or ^^) switches between the original and
+        // filtered documents.
+        let original = screen_after(
+            "50x4",
+            JSON,
+            ":set nonumber | :filter .items[].id | :filter",
+        );
+        assert!(
+            original.contains("test.json [unfiltered]"),
+            "{:?}",
+            original
+        );
+        let args = ["--screen-size", "50x4", "--cmd", ":filter .items[].id"];
+        let original = run_jless(&args, JSON, &[Key::Ctrl('6')]).unwrap();
+        assert!(
+            original.contains("test.json [unfiltered]"),
+            "{:?}",
+            original
+        );
+        let filtered = run_jless(&args, JSON, &[Key::Ctrl('6'), Key::Ctrl('6')]).unwrap();
+        assert!(filtered.contains("[filter: .items[].id]"), "{:?}", filtered);
+
+        // Only the original document is written.
+        let path = std::env::temp_dir().join(format!("jless-test-{}-f.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let screen = screen_after("50x4", JSON, &format!(":filter .items[].id | :w! {path}"));
+        assert!(screen.ends_with(":unfilter before writing"), "{:?}", screen);
+        assert!(!std::path::Path::new(path).exists());
+        screen_after(
+            "50x4",
+            JSON,
+            &format!(":filter .items[].id | :filter | :w! {path}"),
+        );
+        let written = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert!(written.starts_with("{\n  \"items\": ["), "{:?}", written);
+
+        // Neither document can be changed while there's a filter.
+        let screen = screen_after("50x4", JSON, ":filter .items[].id | :edit 3");
+        assert!(
+            screen.ends_with("Use :unfilter before editing"),
+            "{:?}",
+            screen
+        );
+        let screen = screen_after("50x4", JSON, ":filter .items[].id | :filter | j | d");
+        assert!(
+            screen.ends_with("Use :unfilter before deleting"),
+            "{:?}",
+            screen
+        );
+        let screen = screen_after("50x4", JSON, "j | d | :filter .items | u");
+        assert!(
+            screen.ends_with("Use :unfilter before using undo"),
+            "{:?}",
+            screen
+        );
+
+        let unfiltered = screen_after(
+            "50x4",
+            JSON,
+            ":set nonumber | :filter .items[].id | :unfilter",
+        );
+        assert!(
+            unfiltered.starts_with("▼ (1) {items: [{…}, {…}]}"),
+            "{:?}",
+            unfiltered
+        );
+    }
+
+    #[test]
+    fn test_aliases_from_config() {
+        let args = [
+            "--alias",
+            "nu=:set number",
+            "--screen-size",
+            "50x3",
+            "--cmd",
+            ":nu | :aliases",
+        ];
+        let screen = run_jless(&args, r#"{"a": 1}"#, &[]).unwrap();
+        let expected = [
+            " 1 ▼ (1) {a: 1}",
+            "input                                    test.json",
+            "Aliases: nu = :set number",
+        ];
+        assert_eq!(expected.join("\n"), screen);
+    }
+
+    #[test]
+    fn test_horizontal_scroll_survives_layout_changes() {
+        const JSON: &str = r#"{"a": "START-abcdefghijklmnopqrstuvwx-END"}"#;
+
+        let scrolled = screen_after("45x3", JSON, "j | ;");
+        assert!(
+            scrolled.contains(r#"▶ a: "…ART-abcdefghijklmnopqrstuvwx-END""#),
+            "{:?}",
+            scrolled
+        );
+
+        // Hiding line numbers makes room for the whole string, but showing
+        // them again goes back to showing its end.
+        for commands in [
+            "j | ; | :set nonumber | :set number",
+            "j | ; | m | m",
+            "j | ; | m | :set nonumber | m | :set number",
+        ] {
+            assert_eq!(
+                scrolled,
+                screen_after("45x3", JSON, commands),
+                "{:?}",
+                commands
+            );
+        }
+    }
+}
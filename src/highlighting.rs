@@ -2,10 +2,12 @@ use std::fmt;
 use std::iter::Peekable;
 use std::ops::Range;
 
+use clap::ValueEnum;
+
 use crate::search::MatchRangeIter;
 use crate::terminal;
-use crate::terminal::{Style, Terminal};
-use crate::truncatedstrview::TruncatedStrView;
+use crate::terminal::{Color, Style, Terminal};
+use crate::truncatedstrview::{write_sanitized, TruncatedStrView};
 
 // This module is responsible for highlighting text in the
 // appropriate colors when we print it out.
@@ -14,9 +16,17 @@ use crate::truncatedstrview::TruncatedStrView;
 // as well as different shades of gray.
 //
 // We searching for text, we highlight matches in yellow.
-// In certain cases, when highlighted matches are also focused,
-// we invert the normal colors of the terminal (to handle
-// both light and dark color schemes).
+// The current match (the one n and N last jumped to) is shown
+// by inverting the normal colors of the terminal (to handle
+// both light and dark color schemes), and underlined.
+//
+// The focused line, search matches and the current match can all
+// coincide, e.g., a search for a key focuses the line with the
+// matching key, so each is shown differently regardless of the
+// others: a match in the focused key is still highlighted as a
+// regular match unless it's the current one, and the current match
+// differs from both regular matches and the focused key in more
+// than just color, since colors can be changed via --theme-colors.
 //
 //
 // These are all the different things that we print out that
@@ -39,24 +49,24 @@ use crate::truncatedstrview::TruncatedStrView;
 // - Open and close braces and brackets ("{}[]")
 // - Container previews
 
-//      Thing      |  Default Style  |  Focused Style  |      Match     |  Focused/Current Match
+//      Thing      |  Default Style  |  Focused Style  |      Match     |      Current Match
 // ----------------+-----------------+-----------------+----------------+------------------------
-//      null       |      Gray       |        X        | Yellow/Default |        Inverted
-//     boolean     |     Yellow      |        X        | Yellow/Default |        Inverted
-//     number      |     Magenta     |        X        | Yellow/Default |        Inverted
-//     string      |      Green      |        X        | Yellow/Default |        Inverted
-//  empty obj/arr  |     Default     |        X        | Yellow/Default |        Inverted
+//      null       |      Gray       |        X        | Yellow/Default | Inverted + Bold + Under
+//     boolean     |     Yellow      |        X        | Yellow/Default | Inverted + Bold + Under
+//     number      |     Magenta     |        X        | Yellow/Default | Inverted + Bold + Under
+//     string      |      Green      |        X        | Yellow/Default | Inverted + Bold + Under
+//  empty obj/arr  |     Default     |        X        | Yellow/Default | Inverted + Bold + Under
 //
 //  ^ Object values can't be focused
 //
-//   ": " and ","  |     Default     |     Default     | Yellow/Default |        Inverted
+//   ": " and ","  |     Default     |     Default     | Yellow/Default | Inverted + Bold + Under
 //
-//  Object Labels  |      Blue       |  Inverted/Blue  | Yellow/Default |        Inverted
+//  Object Labels  |      Blue       |  Inverted/Blue  | Yellow/Default | Inverted + Bold + Under
 //                                        + Bold
 //
 //   Array Labels  |      Gray       | Default + Bold  |       X        |            X
 //
-//    Container    |     Default     |      Bold       | Yellow/Default |     Inverted + Bold
+//    Container    |     Default     |      Bold       | Yellow/Default | Inverted + Bold + Under
 //    Delimiters
 //
 //    Container    |      Gray       |     Default     | Inverted Gray  |        Inverted
@@ -87,6 +97,13 @@ pub const SEARCH_MATCH_HIGHLIGHTED: Style = Style {
     ..Style::default()
 };
 
+pub const CURRENT_SEARCH_MATCH: Style = Style {
+    inverted: true,
+    bold: true,
+    underline: true,
+    ..Style::default()
+};
+
 pub const DIMMED_STYLE: Style = Style {
     dimmed: true,
     ..Style::default()
@@ -97,8 +114,44 @@ pub const CURRENT_LINE_NUMBER: Style = Style {
     ..Style::default()
 };
 
+pub const DIFF_ADDED_STYLE: Style = Style {
+    fg: terminal::GREEN,
+    bold: true,
+    ..Style::default()
+};
+
+pub const DIFF_MODIFIED_STYLE: Style = Style {
+    fg: terminal::YELLOW,
+    bold: true,
+    ..Style::default()
+};
+
+pub const DIFF_REORDERED_STYLE: Style = Style {
+    fg: terminal::CYAN,
+    bold: true,
+    ..Style::default()
+};
+
+pub const DIFF_REMOVED_STYLE: Style = Style {
+    fg: terminal::RED,
+    bold: true,
+    ..Style::default()
+};
+
+pub const SELECTED_ROW_STYLE: Style = Style {
+    fg: terminal::LIGHT_BLUE,
+    bold: true,
+    ..Style::default()
+};
+
 pub const PREVIEW_STYLES: (&Style, &Style) = (&DIMMED_STYLE, &GRAY_INVERTED_STYLE);
 
+pub const INVISIBLE_CHAR_STYLE: Style = Style {
+    fg: terminal::RED,
+    inverted: true,
+    ..Style::default()
+};
+
 pub const BLUE_STYLE: Style = Style {
     fg: terminal::LIGHT_BLUE,
     ..Style::default()
@@ -111,9 +164,421 @@ pub const INVERTED_BOLD_BLUE_STYLE: Style = Style {
     ..Style::default()
 };
 
+// The styles used for things whose appearance can be changed via --theme,
+// identified by the role they play, rather than what they look like.
+#[derive(Clone)]
+pub struct Theme {
+    // Search matches, the current search match, and the key of the focused
+    // line must all be distinguishable from each other, and the current
+    // match must differ from the others in more than its colors.
+    pub search_match: Style,
+    pub focused_search_match: Style,
+    pub key: Style,
+    pub focused_key: Style,
+
+    pub null: Color,
+    pub boolean: Color,
+    pub number: Color,
+    pub string: Color,
+
+    pub invisible_char: Style,
+    pub diff_added: Style,
+    pub diff_modified: Style,
+    pub diff_reordered: Style,
+    pub diff_removed: Style,
+    pub current_line_number: Style,
+    pub selected_row: Style,
+    // The background of the focused line with :set cursorline.
+    pub focused_line: Color,
+    // The colors of indentation guides, used for each level of nesting in
+    // turn.
+    pub indent_guides: &'static [Color],
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug, ValueEnum)]
+pub enum ThemeName {
+    Default,
+    // Avoids relying on distinguishing red and green.
+    Deuteranopia,
+    // Uses brighter colors, and bold text more liberally.
+    HighContrast,
+    // For terminals using the Solarized palette, where the "light" colors
+    // are shades of gray.
+    Solarized,
+    // Doesn't use color at all, only bold, inverted and underlined text.
+    Monochrome,
+}
+
+impl ThemeName {
+    // The default and Solarized themes have variants for light backgrounds;
+    // the others look the same on either.
+    pub fn theme(self, background: Background) -> &'static Theme {
+        match (self, background) {
+            (ThemeName::Default, Background::Dark) => &DEFAULT_THEME,
+            (ThemeName::Default, Background::Light) => &LIGHT_THEME,
+            (ThemeName::Deuteranopia, _) => &DEUTERANOPIA_THEME,
+            (ThemeName::HighContrast, _) => &HIGH_CONTRAST_THEME,
+            (ThemeName::Solarized, Background::Dark) => &SOLARIZED_DARK_THEME,
+            (ThemeName::Solarized, Background::Light) => &SOLARIZED_LIGHT_THEME,
+            (ThemeName::Monochrome, _) => &MONOCHROME_THEME,
+        }
+    }
+
+    // Whether it's worth asking the terminal for its background color.
+    pub fn adapts_to_background(self) -> bool {
+        matches!(self, ThemeName::Default | ThemeName::Solarized)
+    }
+}
+
+impl Theme {
+    // Apply the colors given via --theme-colors.
+    pub fn with_colors(&self, colors: &ThemeColors) -> Theme {
+        let mut theme = self.clone();
+        for &(role, color) in colors.0.iter() {
+            match role {
+                ThemeRole::Key => {
+                    theme.key.fg = color;
+                    theme.focused_key.bg = color;
+                }
+                ThemeRole::String => theme.string = color,
+                ThemeRole::Number => theme.number = color,
+                ThemeRole::Boolean => theme.boolean = color,
+                ThemeRole::Null => theme.null = color,
+                ThemeRole::SearchMatch => {
+                    theme.search_match = Style {
+                        fg: color,
+                        bg: terminal::DEFAULT,
+                        inverted: true,
+                        ..theme.search_match
+                    }
+                }
+                ThemeRole::CurrentSearchMatch => {
+                    theme.focused_search_match = Style {
+                        fg: color,
+                        bg: terminal::DEFAULT,
+                        inverted: true,
+                        ..theme.focused_search_match
+                    }
+                }
+                ThemeRole::FocusedLine => theme.focused_line = color,
+            }
+        }
+        theme
+    }
+}
+
+// The parts of a theme whose colors can be set via --theme-colors.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum ThemeRole {
+    Key,
+    String,
+    Number,
+    Boolean,
+    Null,
+    SearchMatch,
+    CurrentSearchMatch,
+    FocusedLine,
+}
+
+const THEME_ROLE_NAMES: [(&str, ThemeRole); 8] = [
+    ("key", ThemeRole::Key),
+    ("string", ThemeRole::String),
+    ("number", ThemeRole::Number),
+    ("boolean", ThemeRole::Boolean),
+    ("null", ThemeRole::Null),
+    ("search-match", ThemeRole::SearchMatch),
+    ("current-search-match", ThemeRole::CurrentSearchMatch),
+    ("focused-line", ThemeRole::FocusedLine),
+];
+
+// Colors for parts of the theme, e.g., "key=blue,string=light-green".
+#[derive(Clone, Debug)]
+pub struct ThemeColors(pub Vec<(ThemeRole, Color)>);
+
+pub fn parse_theme_colors(colors: &str) -> Result<ThemeColors, String> {
+    let colors = colors
+        .split(',')
+        .map(|entry| {
+            let Some((role, color)) = entry.split_once('=') else {
+                return Err(format!(
+                    "Expected ROLE=COLOR, e.g., key=blue, not {entry:?}"
+                ));
+            };
+            let role = role.trim();
+            let role = THEME_ROLE_NAMES
+                .iter()
+                .find(|(name, _)| *name == role)
+                .map(|(_, role)| *role)
+                .ok_or_else(|| {
+                    let names: Vec<&str> = THEME_ROLE_NAMES.iter().map(|(name, _)| *name).collect();
+                    format!(
+                        "Unknown theme role {role:?}; expected one of {}",
+                        names.join(", ")
+                    )
+                })?;
+            Ok((role, parse_color(color.trim())?))
+        })
+        .collect::<Result<Vec<(ThemeRole, Color)>, String>>()?;
+    Ok(ThemeColors(colors))
+}
+
+// Colors given on the command line, e.g., "red,yellow,12", either by name
+// or by their number in the 16 color palette.
+#[derive(Clone, Debug)]
+pub struct ColorList(pub Vec<Color>);
+
+const COLOR_NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+pub fn parse_color_list(colors: &str) -> Result<ColorList, String> {
+    let colors = colors
+        .split(',')
+        .map(|color| parse_color(color.trim()))
+        .collect::<Result<Vec<Color>, String>>()?;
+    Ok(ColorList(colors))
+}
+
+fn parse_color(color: &str) -> Result<Color, String> {
+    let index = match color.parse::<u8>() {
+        Ok(index) if index < 16 => Some(index),
+        Ok(_) => None,
+        Err(_) => {
+            let (light, name) = match color.strip_prefix("light-") {
+                Some(name) => (true, name),
+                None => (false, color),
+            };
+            COLOR_NAMES
+                .iter()
+                .position(|&c| c == name)
+                .map(|index| index as u8 + if light { 8 } else { 0 })
+        }
+    };
+    index.map(Color::C16).ok_or_else(|| {
+        format!("Invalid color {color:?}; expected a name like red or light-blue, or 0-15")
+    })
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug, ValueEnum)]
+pub enum Background {
+    Dark,
+    Light,
+}
+
+impl Background {
+    // Classify a background color reported by the terminal, where each
+    // component is between 0.0 and 1.0, by its relative luminance.
+    pub fn from_rgb((r, g, b): (f32, f32, f32)) -> Background {
+        if 0.2126 * r + 0.7152 * g + 0.0722 * b > 0.5 {
+            Background::Light
+        } else {
+            Background::Dark
+        }
+    }
+
+    // Some terminals (rxvt, Konsole) set $COLORFGBG to something like "15;0",
+    // where the last number is the (16 color palette) background color.
+    pub fn from_colorfgbg(colorfgbg: &str) -> Option<Background> {
+        match colorfgbg.rsplit(';').next()?.parse::<u8>().ok()? {
+            7 | 15 => Some(Background::Light),
+            0..=15 => Some(Background::Dark),
+            _ => None,
+        }
+    }
+}
+
+pub const DEFAULT_THEME: Theme = Theme {
+    search_match: SEARCH_MATCH_HIGHLIGHTED,
+    focused_search_match: CURRENT_SEARCH_MATCH,
+    key: BLUE_STYLE,
+    focused_key: INVERTED_BOLD_BLUE_STYLE,
+
+    null: terminal::LIGHT_BLACK,
+    boolean: terminal::YELLOW,
+    number: terminal::MAGENTA,
+    string: terminal::GREEN,
+
+    invisible_char: INVISIBLE_CHAR_STYLE,
+    diff_added: DIFF_ADDED_STYLE,
+    diff_modified: DIFF_MODIFIED_STYLE,
+    diff_reordered: DIFF_REORDERED_STYLE,
+    diff_removed: DIFF_REMOVED_STYLE,
+    current_line_number: CURRENT_LINE_NUMBER,
+    selected_row: SELECTED_ROW_STYLE,
+    focused_line: terminal::LIGHT_BLACK,
+    indent_guides: &[
+        terminal::YELLOW,
+        terminal::MAGENTA,
+        terminal::CYAN,
+        terminal::GREEN,
+        terminal::LIGHT_BLUE,
+        terminal::RED,
+    ],
+};
+
+pub const DEUTERANOPIA_THEME: Theme = Theme {
+    string: terminal::CYAN,
+    invisible_char: Style {
+        fg: terminal::MAGENTA,
+        inverted: true,
+        ..Style::default()
+    },
+    diff_added: Style {
+        fg: terminal::LIGHT_BLUE,
+        bold: true,
+        ..Style::default()
+    },
+    diff_removed: Style {
+        fg: terminal::MAGENTA,
+        bold: true,
+        ..Style::default()
+    },
+    indent_guides: &[
+        terminal::YELLOW,
+        terminal::LIGHT_BLUE,
+        terminal::MAGENTA,
+        terminal::CYAN,
+        terminal::WHITE,
+    ],
+    ..DEFAULT_THEME
+};
+
+// Yellow and light blue are hard to read on light backgrounds, so this uses
+// darker colors instead.
+pub const LIGHT_THEME: Theme = Theme {
+    search_match: Style {
+        bg: terminal::YELLOW,
+        ..Style::default()
+    },
+    key: Style {
+        fg: terminal::BLUE,
+        ..Style::default()
+    },
+    boolean: terminal::RED,
+    diff_modified: Style {
+        fg: terminal::MAGENTA,
+        bold: true,
+        ..Style::default()
+    },
+    diff_reordered: Style {
+        fg: terminal::BLUE,
+        bold: true,
+        ..Style::default()
+    },
+    current_line_number: BOLD_STYLE,
+    selected_row: Style {
+        fg: terminal::BLUE,
+        bold: true,
+        ..Style::default()
+    },
+    focused_line: terminal::WHITE,
+    indent_guides: &[
+        terminal::BLUE,
+        terminal::MAGENTA,
+        terminal::RED,
+        terminal::CYAN,
+        terminal::GREEN,
+    ],
+    ..DEFAULT_THEME
+};
+
+pub const HIGH_CONTRAST_THEME: Theme = Theme {
+    search_match: Style {
+        fg: terminal::YELLOW,
+        inverted: true,
+        bold: true,
+        ..Style::default()
+    },
+    focused_search_match: Style {
+        fg: terminal::LIGHT_WHITE,
+        ..CURRENT_SEARCH_MATCH
+    },
+    key: Style {
+        fg: terminal::LIGHT_BLUE,
+        bold: true,
+        ..Style::default()
+    },
+    null: terminal::WHITE,
+    current_line_number: Style {
+        fg: terminal::YELLOW,
+        bold: true,
+        ..Style::default()
+    },
+    ..DEFAULT_THEME
+};
+
+// The Solarized palette replaces most of the "light" colors with the shades
+// of gray used for text and backgrounds; black is the background of
+// highlighted lines, and light black is the background itself.
+pub const SOLARIZED_DARK_THEME: Theme = Theme {
+    key: Style {
+        fg: terminal::BLUE,
+        ..Style::default()
+    },
+    null: terminal::LIGHT_GREEN,
+    number: terminal::LIGHT_MAGENTA,
+    string: terminal::CYAN,
+    selected_row: Style {
+        fg: terminal::BLUE,
+        bold: true,
+        ..Style::default()
+    },
+    focused_line: terminal::BLACK,
+    indent_guides: &[
+        terminal::YELLOW,
+        terminal::LIGHT_RED,
+        terminal::MAGENTA,
+        terminal::LIGHT_MAGENTA,
+        terminal::BLUE,
+        terminal::CYAN,
+        terminal::GREEN,
+    ],
+    ..DEFAULT_THEME
+};
+
+pub const SOLARIZED_LIGHT_THEME: Theme = Theme {
+    null: terminal::LIGHT_CYAN,
+    focused_line: terminal::WHITE,
+    ..SOLARIZED_DARK_THEME
+};
+
+pub const MONOCHROME_THEME: Theme = Theme {
+    search_match: Style {
+        inverted: true,
+        ..Style::default()
+    },
+    focused_search_match: Style {
+        inverted: true,
+        underline: true,
+        ..Style::default()
+    },
+    key: BOLD_STYLE,
+    focused_key: BOLD_INVERTED_STYLE,
+
+    null: terminal::DEFAULT,
+    boolean: terminal::DEFAULT,
+    number: terminal::DEFAULT,
+    string: terminal::DEFAULT,
+
+    invisible_char: Style {
+        underline: true,
+        ..Style::default()
+    },
+    diff_added: BOLD_STYLE,
+    diff_modified: BOLD_STYLE,
+    diff_reordered: BOLD_STYLE,
+    diff_removed: BOLD_STYLE,
+    current_line_number: BOLD_STYLE,
+    selected_row: BOLD_STYLE,
+    // So :set cursorline has no visible effect.
+    focused_line: terminal::DEFAULT,
+    indent_guides: &[terminal::DEFAULT],
+};
+
 #[allow(clippy::too_many_arguments)]
 pub fn highlight_truncated_str_view(
     out: &mut dyn Terminal,
+    theme: &Theme,
     mut s: &str,
     str_view: &TruncatedStrView,
     mut str_range_start: Option<usize>,
@@ -121,6 +586,7 @@ pub fn highlight_truncated_str_view(
     highlight_style: &Style,
     matches_iter: &mut Option<&mut Peekable<MatchRangeIter<'_>>>,
     focused_search_match: &Range<usize>,
+    invisible_char_ranges: &[Range<usize>],
 ) -> fmt::Result {
     let mut leading_ellipsis = false;
     let mut replacement_character = false;
@@ -151,12 +617,14 @@ pub fn highlight_truncated_str_view(
     // Print actual string itself
     highlight_matches(
         out,
+        theme,
         s,
         str_range_start,
         style,
         highlight_style,
         matches_iter,
         focused_search_match,
+        invisible_char_ranges,
     )?;
 
     // Print trailing ellipsis
@@ -168,18 +636,24 @@ pub fn highlight_truncated_str_view(
     Ok(())
 }
 
+// Invisible characters (see lineprinter::find_invisible_chars) are only
+// highlighted when str_range_start is provided, and only outside of search
+// matches.
+#[allow(clippy::too_many_arguments)]
 pub fn highlight_matches(
     out: &mut dyn Terminal,
+    theme: &Theme,
     mut s: &str,
     str_range_start: Option<usize>,
     style: &Style,
     highlight_style: &Style,
     matches_iter: &mut Option<&mut Peekable<MatchRangeIter<'_>>>,
     focused_search_match: &Range<usize>,
+    invisible_char_ranges: &[Range<usize>],
 ) -> fmt::Result {
     if str_range_start.is_none() {
         out.set_style(style)?;
-        write!(out, "{s}")?;
+        write_sanitized(out, s)?;
         return Ok(());
     }
 
@@ -209,20 +683,26 @@ pub fn highlight_matches(
         // Print out stuff before the start of the match, if there's any.
         if start_index < match_start {
             let print_end = match_start - start_index;
-            out.set_style(style)?;
-            write!(out, "{}", &s[..print_end])?;
+            write_with_invisible_chars(
+                out,
+                &s[..print_end],
+                start_index,
+                style,
+                &theme.invisible_char,
+                invisible_char_ranges,
+            )?;
         }
 
         // Highlight the matching substring.
         if match_start < string_end {
             if match_is_focused_match {
-                out.set_style(&BOLD_INVERTED_STYLE)?;
+                out.set_style(&theme.focused_search_match)?;
             } else {
                 out.set_style(highlight_style)?;
             }
             let print_start = match_start - start_index;
             let print_end = match_end - start_index;
-            write!(out, "{}", &s[print_start..print_end])?;
+            write_sanitized(out, &s[print_start..print_end])?;
         }
 
         // Update start_index and s
@@ -232,3 +712,145 @@ pub fn highlight_matches(
 
     Ok(())
 }
+
+// Write out part of a string, which starts at s_start in the pretty printed
+// JSON, highlighting any portions that overlap with invisible_char_ranges.
+fn write_with_invisible_chars(
+    out: &mut dyn Terminal,
+    s: &str,
+    s_start: usize,
+    style: &Style,
+    invisible_char_style: &Style,
+    invisible_char_ranges: &[Range<usize>],
+) -> fmt::Result {
+    let s_end = s_start + s.len();
+    let mut printed_until = s_start;
+
+    out.set_style(style)?;
+
+    for range in invisible_char_ranges {
+        if range.end <= printed_until {
+            continue;
+        }
+        if range.start >= s_end {
+            break;
+        }
+
+        let start = range.start.max(printed_until);
+        let end = range.end.min(s_end);
+
+        write_sanitized(out, &s[printed_until - s_start..start - s_start])?;
+        out.set_style(invisible_char_style)?;
+        write_sanitized(out, &s[start - s_start..end - s_start])?;
+        out.set_style(style)?;
+
+        printed_until = end;
+    }
+
+    write_sanitized(out, &s[printed_until - s_start..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_search_matches_and_focus_are_distinguishable() {
+        for name in ThemeName::value_variants() {
+            for background in Background::value_variants() {
+                let theme = name.theme(*background);
+                assert_ne!(theme.search_match, theme.focused_search_match, "{:?}", name);
+                assert_ne!(theme.search_match, theme.focused_key, "{:?}", name);
+                assert_ne!(theme.focused_search_match, theme.focused_key, "{:?}", name);
+                assert_current_match_stands_out(theme);
+            }
+        }
+
+        let theme = &DEUTERANOPIA_THEME;
+        let red_or_green = |color: Color| color == terminal::RED || color == terminal::GREEN;
+        for style in [
+            &theme.search_match,
+            &theme.focused_search_match,
+            &theme.key,
+            &theme.focused_key,
+            &theme.invisible_char,
+            &theme.diff_added,
+            &theme.diff_modified,
+            &theme.diff_reordered,
+            &theme.diff_removed,
+            &theme.current_line_number,
+            &theme.selected_row,
+        ] {
+            assert!(!red_or_green(style.fg) && !red_or_green(style.bg));
+        }
+        for color in [theme.null, theme.boolean, theme.number, theme.string] {
+            assert!(!red_or_green(color));
+        }
+        assert!(!theme.indent_guides.iter().any(|&color| red_or_green(color)));
+    }
+
+    // The current search match is still distinguishable from regular matches
+    // and the focused key when they're all the same color.
+    fn assert_current_match_stands_out(theme: &Theme) {
+        let attributes = |style: &Style| (style.inverted, style.bold, style.underline);
+        let current = attributes(&theme.focused_search_match);
+        assert_ne!(attributes(&theme.search_match), current);
+        assert_ne!(attributes(&theme.focused_key), current);
+    }
+
+    #[test]
+    fn test_parse_color_list() {
+        assert_eq!(
+            vec![
+                terminal::RED,
+                terminal::LIGHT_BLUE,
+                Color::C16(0),
+                Color::C16(15)
+            ],
+            parse_color_list("red, light-blue,0,15").unwrap().0
+        );
+        assert!(parse_color_list("16").is_err());
+        assert!(parse_color_list("red,").is_err());
+        assert!(parse_color_list("light-").is_err());
+    }
+
+    #[test]
+    fn test_theme_colors() {
+        let colors = parse_theme_colors("key=red, search-match=light-blue,focused-line=0").unwrap();
+        let theme = MONOCHROME_THEME.with_colors(&colors);
+        assert_eq!(terminal::RED, theme.key.fg);
+        assert_eq!(terminal::RED, theme.focused_key.bg);
+        assert_eq!(terminal::LIGHT_BLUE, theme.search_match.fg);
+        assert_eq!(terminal::BLACK, theme.focused_line);
+        assert_eq!(terminal::DEFAULT, theme.string);
+        assert_ne!(theme.search_match, theme.focused_search_match);
+
+        let colors =
+            parse_theme_colors("key=blue,search-match=blue,current-search-match=blue").unwrap();
+        let theme = DEFAULT_THEME.with_colors(&colors);
+        assert_eq!(terminal::BLUE, theme.focused_search_match.fg);
+        assert!(theme.focused_search_match.underline);
+        assert_current_match_stands_out(&theme);
+
+        assert!(parse_theme_colors("keys=red").is_err());
+        assert!(parse_theme_colors("key").is_err());
+        assert!(parse_theme_colors("key=pink").is_err());
+    }
+
+    #[test]
+    fn test_background() {
+        assert_eq!(Background::Dark, Background::from_rgb((0.0, 0.0, 0.0)));
+        assert_eq!(Background::Dark, Background::from_rgb((0.16, 0.17, 0.2)));
+        assert_eq!(Background::Light, Background::from_rgb((1.0, 1.0, 1.0)));
+        assert_eq!(Background::Light, Background::from_rgb((0.99, 0.96, 0.89)));
+
+        assert_eq!(Some(Background::Dark), Background::from_colorfgbg("15;0"));
+        assert_eq!(Some(Background::Light), Background::from_colorfgbg("0;15"));
+        assert_eq!(
+            Some(Background::Light),
+            Background::from_colorfgbg("0;default;7")
+        );
+        assert_eq!(None, Background::from_colorfgbg("0;default"));
+        assert_eq!(None, Background::from_colorfgbg(""));
+    }
+}
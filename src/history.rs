@@ -0,0 +1,89 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// How many values to remember for each path.
+const MAX_VALUES_PER_PATH: usize = 20;
+
+// The values seen at paths in the input each time it was (re)loaded, so
+// that :history can show how a value has changed over time.
+#[derive(Default)]
+pub struct ValueHistory(HashMap<String, VecDeque<(SystemTime, String)>>);
+
+impl ValueHistory {
+    // Record the value at a path when the input was loaded at the given
+    // time. Recording the same load more than once has no effect.
+    pub fn record(&mut self, path: String, loaded_at: SystemTime, value: &str) {
+        let values = self.0.entry(path).or_default();
+        if values.back().map_or(false, |(time, _)| *time == loaded_at) {
+            return;
+        }
+
+        if values.len() == MAX_VALUES_PER_PATH {
+            values.pop_front();
+        }
+        values.push_back((loaded_at, value.to_string()));
+    }
+
+    // List the values seen at a path, oldest first, along with the time of
+    // day when they were loaded.
+    pub fn values(&self, path: &str) -> Option<Vec<(String, &str)>> {
+        let values = self.0.get(path)?;
+        Some(
+            values
+                .iter()
+                .map(|(loaded_at, value)| (format_time_of_day(*loaded_at), value.as_str()))
+                .collect(),
+        )
+    }
+}
+
+fn format_time_of_day(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()) % 86_400;
+    format!(
+        "{:02}:{:02}:{:02} UTC",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_value_history() {
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+        let mut history = ValueHistory::default();
+
+        history.record(".a".to_string(), at(3600 + 61), "1");
+        // Recording the same load twice only records it once.
+        history.record(".a".to_string(), at(3600 + 61), "1");
+        history.record(".a".to_string(), at(86_400 + 2), "1");
+        history.record(".a".to_string(), at(86_400 + 3), "1");
+        history.record(".a".to_string(), at(86_400 + 4), "[1, 2]");
+
+        assert_eq!(None, history.values(".b"));
+        assert_eq!(
+            Some(vec![
+                ("01:01:01 UTC".to_string(), "1"),
+                ("00:00:02 UTC".to_string(), "1"),
+                ("00:00:03 UTC".to_string(), "1"),
+                ("00:00:04 UTC".to_string(), "[1, 2]"),
+            ]),
+            history.values(".a")
+        );
+
+        for i in 0..MAX_VALUES_PER_PATH as u64 {
+            history.record(".a".to_string(), at(100 + i), &i.to_string());
+        }
+        let values = history.values(".a").unwrap();
+        assert_eq!(MAX_VALUES_PER_PATH, values.len());
+        assert_eq!(
+            ("00:01:59 UTC".to_string(), "19"),
+            values[MAX_VALUES_PER_PATH - 1]
+        );
+    }
+}
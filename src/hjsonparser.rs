@@ -0,0 +1,471 @@
+use crate::flatjson::JSON_NUMBER;
+use crate::jsonstringunescaper::escape_json_string;
+
+// Hjson (https://hjson.github.io) is a relaxed dialect of JSON used for
+// config files. On top of JSON, it allows:
+//
+// - Comments, starting with `#` or `//`, or between `/*` and `*/`
+// - Unquoted keys, and leaving off the braces of the top-level object
+// - Leaving out commas between values, or having trailing commas
+// - Quoteless strings, which go until the end of the line
+// - Single quoted strings, and multiline strings between `'''`s
+//
+// Rather than building rows directly, Hjson input is converted to the
+// equivalent JSON, which is then parsed as usual, so it behaves exactly
+// like JSON everywhere else.
+pub fn to_json(hjson: &str) -> Result<String, String> {
+    let mut parser = HjsonParser {
+        input: hjson,
+        pos: 0,
+        json: String::with_capacity(hjson.len()),
+    };
+    parser.parse_root()?;
+    Ok(parser.json)
+}
+
+struct HjsonParser<'a> {
+    input: &'a str,
+    pos: usize,
+    json: String,
+}
+
+impl<'a> HjsonParser<'a> {
+    fn parse_root(&mut self) -> Result<(), String> {
+        self.skip_whitespace_and_comments();
+        match self.peek() {
+            // A file with nothing but comments is an empty object.
+            None => {
+                self.json.push_str("{}");
+                return Ok(());
+            }
+            Some('{' | '[') => self.parse_value()?,
+            // Either an object without braces, or just a single value.
+            Some(_) => {
+                if let Err(object_err) = self.parse_object(false) {
+                    self.pos = 0;
+                    self.json.clear();
+                    self.skip_whitespace_and_comments();
+                    if self.parse_value().is_err() || !self.at_end() {
+                        return Err(object_err);
+                    }
+                }
+            }
+        }
+
+        if !self.at_end() {
+            return Err(self.error("Unexpected characters after the end of the input"));
+        }
+        Ok(())
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_whitespace_and_comments();
+        self.peek().is_none()
+    }
+
+    fn parse_value(&mut self) -> Result<(), String> {
+        match self.peek() {
+            None => Err(self.error("Expected a value")),
+            Some('{') => {
+                self.bump();
+                self.parse_object(true)
+            }
+            Some('[') => self.parse_array(),
+            Some('"') => {
+                let s = self.parse_quoted_string('"')?;
+                self.push_string(&s);
+                Ok(())
+            }
+            Some('\'') if self.rest().starts_with("'''") => {
+                let s = self.parse_multiline_string()?;
+                self.push_string(&s);
+                Ok(())
+            }
+            Some('\'') => {
+                let s = self.parse_quoted_string('\'')?;
+                self.push_string(&s);
+                Ok(())
+            }
+            Some(ch @ (',' | ':' | ']' | '}')) => {
+                Err(self.error(&format!("Unexpected character: {ch:?}")))
+            }
+            Some(_) => {
+                self.parse_quoteless_value();
+                Ok(())
+            }
+        }
+    }
+
+    // Parses the members of an object, after the opening brace, if any.
+    fn parse_object(&mut self, braces: bool) -> Result<(), String> {
+        self.json.push('{');
+        let mut first = true;
+
+        loop {
+            self.skip_whitespace_and_comments();
+            match self.peek() {
+                None if braces => return Err(self.error("Unterminated object")),
+                None => break,
+                Some('}') if braces => {
+                    self.bump();
+                    break;
+                }
+                _ => {}
+            }
+
+            let key = self.parse_key()?;
+            self.skip_whitespace_and_comments();
+            if self.peek() != Some(':') {
+                return Err(self.error(&format!("Expected ':' after key {key:?}")));
+            }
+            self.bump();
+            self.skip_whitespace_and_comments();
+
+            if !first {
+                self.json.push(',');
+            }
+            first = false;
+            self.push_string(&key);
+            self.json.push(':');
+            self.parse_value()?;
+
+            self.skip_whitespace_and_comments();
+            if self.peek() == Some(',') {
+                self.bump();
+            }
+        }
+
+        self.json.push('}');
+        Ok(())
+    }
+
+    fn parse_array(&mut self) -> Result<(), String> {
+        self.bump();
+        self.json.push('[');
+        let mut first = true;
+
+        loop {
+            self.skip_whitespace_and_comments();
+            match self.peek() {
+                None => return Err(self.error("Unterminated array")),
+                Some(']') => {
+                    self.bump();
+                    break;
+                }
+                _ => {}
+            }
+
+            if !first {
+                self.json.push(',');
+            }
+            first = false;
+            self.parse_value()?;
+
+            self.skip_whitespace_and_comments();
+            if self.peek() == Some(',') {
+                self.bump();
+            }
+        }
+
+        self.json.push(']');
+        Ok(())
+    }
+
+    fn parse_key(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some(quote @ ('"' | '\'')) => self.parse_quoted_string(quote),
+            _ => {
+                let start = self.pos;
+                while let Some(ch) = self.peek() {
+                    if ch.is_whitespace() || ",:[]{}".contains(ch) {
+                        break;
+                    }
+                    self.bump();
+                }
+                if start == self.pos {
+                    return Err(self.error("Expected a key"));
+                }
+                Ok(self.input[start..self.pos].to_string())
+            }
+        }
+    }
+
+    fn parse_quoted_string(&mut self, quote: char) -> Result<String, String> {
+        self.bump();
+        let mut s = String::new();
+
+        loop {
+            match self.bump() {
+                None | Some('\n') => return Err(self.error("Unterminated string")),
+                Some(ch) if ch == quote => return Ok(s),
+                Some('\\') => {
+                    let escaped = match self.bump() {
+                        Some(ch @ ('"' | '\'' | '\\' | '/')) => ch,
+                        Some('b') => '\x08',
+                        Some('f') => '\x0c',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.parse_unicode_escape()?,
+                        _ => return Err(self.error("Invalid escape sequence")),
+                    };
+                    s.push(escaped);
+                }
+                Some(ch) => s.push(ch),
+            }
+        }
+    }
+
+    // Parses the XXXX of a \uXXXX escape, along with the second half of a
+    // surrogate pair.
+    fn parse_unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.parse_hex4()?;
+        if !(0xD800..0xDC00).contains(&high) {
+            return char::from_u32(high).ok_or_else(|| self.error("Invalid unicode escape"));
+        }
+
+        if !self.rest().starts_with("\\u") {
+            return Err(self.error("Unpaired surrogate in unicode escape"));
+        }
+        self.pos += 2;
+        let low = self.parse_hex4()?;
+        if !(0xDC00..0xE000).contains(&low) {
+            return Err(self.error("Unpaired surrogate in unicode escape"));
+        }
+        char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+            .ok_or_else(|| self.error("Invalid unicode escape"))
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, String> {
+        let hex = self.rest().get(..4).unwrap_or("");
+        match u32::from_str_radix(hex, 16) {
+            Ok(n) if hex.len() == 4 => {
+                self.pos += 4;
+                Ok(n)
+            }
+            _ => Err(self.error("Invalid unicode escape")),
+        }
+    }
+
+    // Multiline strings have the indentation of the line the opening '''
+    // is on removed from each line, and don't include the last newline.
+    fn parse_multiline_string(&mut self) -> Result<String, String> {
+        let line_start = self.input[..self.pos].rfind('\n').map_or(0, |i| i + 1);
+        let indentation = self.input[line_start..self.pos].chars().count();
+        self.pos += 3;
+
+        // Skip whitespace up to and including the end of the first line.
+        while matches!(self.peek(), Some(' ' | '\t' | '\r')) {
+            self.bump();
+        }
+        if self.peek() == Some('\n') {
+            self.bump();
+            self.skip_indentation(indentation);
+        }
+
+        let mut s = String::new();
+        loop {
+            if self.rest().starts_with("'''") {
+                self.pos += 3;
+                if s.ends_with('\n') {
+                    s.pop();
+                }
+                return Ok(s);
+            }
+
+            match self.bump() {
+                None => return Err(self.error("Unterminated multiline string")),
+                Some('\r') => {}
+                Some('\n') => {
+                    s.push('\n');
+                    self.skip_indentation(indentation);
+                }
+                Some(ch) => s.push(ch),
+            }
+        }
+    }
+
+    fn skip_indentation(&mut self, indentation: usize) {
+        for _ in 0..indentation {
+            match self.peek() {
+                Some(' ' | '\t') => {
+                    self.bump();
+                }
+                _ => return,
+            }
+        }
+    }
+
+    // Quoteless values go until the end of the line, unless they're a
+    // number, true, false or null followed by a comma, closing bracket or
+    // comment, e.g., `[1, 2]`, in which case the value ends there.
+    fn parse_quoteless_value(&mut self) {
+        let start = self.pos;
+
+        loop {
+            let ends_literal = match self.peek() {
+                None | Some('\n' | '\r' | ',' | ']' | '}' | '#') => true,
+                Some('/') => self.rest().starts_with("//") || self.rest().starts_with("/*"),
+                Some(_) => false,
+            };
+
+            if ends_literal {
+                let literal = self.input[start..self.pos].trim();
+                if matches!(literal, "true" | "false" | "null") || JSON_NUMBER.is_match(literal) {
+                    self.json.push_str(literal);
+                    return;
+                }
+            }
+
+            match self.peek() {
+                None | Some('\n' | '\r') => break,
+                Some(_) => {
+                    self.bump();
+                }
+            }
+        }
+
+        let s = self.input[start..self.pos].trim_end().to_string();
+        self.push_string(&s);
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+
+            if trimmed.starts_with('#') || trimmed.starts_with("//") {
+                self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else if let Some(comment) = trimmed.strip_prefix("/*") {
+                self.pos += comment.find("*/").map_or(trimmed.len(), |end| end + 4);
+            } else {
+                return;
+            }
+        }
+    }
+
+    fn push_string(&mut self, s: &str) {
+        self.json.push('"');
+        self.json.push_str(&escape_json_string(s));
+        self.json.push('"');
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.peek()?;
+        self.pos += ch.len_utf8();
+        Some(ch)
+    }
+
+    fn error(&self, message: &str) -> String {
+        let line = self.input[..self.pos].matches('\n').count() + 1;
+        format!("Line {line}: {message}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use indoc::indoc;
+
+    use super::*;
+
+    #[test]
+    fn test_hjson_intro_example() {
+        // From the front page of hjson.github.io.
+        let hjson = indoc! {r#"
+            {
+              # specify rate in requests/second (because comments are helpful!)
+              rate: 1000
+
+              // prefer c-style comments?
+              /* feeling old fashioned? */
+
+              # did you notice that rate doesn't need quotes?
+              hey: look ma, no quotes for strings either!
+
+              # best of all
+              notice: []
+              anything: ?
+
+              # yes, commas are optional!
+            }
+        "#};
+        assert_eq!(
+            Ok(r#"{"rate":1000,"hey":"look ma, no quotes for strings either!","notice":[],"anything":"?"}"#.to_string()),
+            to_json(hjson)
+        );
+    }
+
+    #[test]
+    fn test_braceless_root_and_multiline_strings() {
+        let hjson = indoc! {r#"
+            # The braces of the root object are optional.
+            md:
+              '''
+              First line.
+              Second line.
+                This line is indented by two spaces.
+              '''
+            single: 'It\'s "quoted"'
+            "quoted key": value # not a comment
+            list: [
+              1, 2
+              3.5
+              true
+              null text
+              -1e3 // a number
+            ]
+        "#};
+        assert_eq!(
+            Ok(concat!(
+                r#"{"md":"First line.\nSecond line.\n  This line is indented by two spaces.","#,
+                r#""single":"It's \"quoted\"","quoted key":"value # not a comment","#,
+                r#""list":[1,2,3.5,true,"null text",-1e3]}"#
+            )
+            .to_string()),
+            to_json(hjson)
+        );
+    }
+
+    #[test]
+    fn test_root_values() {
+        assert_eq!(Ok("{}".to_string()), to_json("  # nothing here\n"));
+        // Quoteless strings go until the end of the line, closing brackets and all.
+        assert_eq!(Ok("[1,\"a\"]".to_string()), to_json("[1\na\n]"));
+        assert_eq!(Ok("\"just text\"".to_string()), to_json("just text"));
+        assert_eq!(Ok("12".to_string()), to_json("12 // comment"));
+        // JSON is valid Hjson.
+        assert_eq!(
+            Ok(r#"{"a":[1,{"b":"é😀"}]}"#.to_string()),
+            to_json(r#"{"a": [1, {"b": "\u00e9\ud83d\ude00"}]}"#)
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            Err("Line 2: Unterminated object".to_string()),
+            to_json("{\na: 1")
+        );
+        assert_eq!(
+            Err("Line 2: Expected ':' after key \"b\"".to_string()),
+            to_json("a: 1\nb")
+        );
+        assert_eq!(
+            Err("Line 1: Unterminated string".to_string()),
+            to_json("{a: \"b}")
+        );
+        assert_eq!(
+            Err("Line 1: Unexpected character: ','".to_string()),
+            to_json("[,]")
+        );
+    }
+}
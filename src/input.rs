@@ -1,19 +1,45 @@
 use signal_hook::consts::SIGWINCH;
 use signal_hook::low_level::pipe;
-use termion::event::{parse_event, Event, Key, MouseEvent};
+use termion::event::{Key, MouseButton, MouseEvent};
 
+use crate::control::{ControlCommand, ControlReceiver};
+use crate::ndjson::{RecordStream, Records};
+
+use std::fs::{File, OpenOptions};
 use std::io;
-use std::io::{stdin, Read, Stdin};
-use std::os::unix::io::AsRawFd;
+use std::io::{stdin, Read, Stdin, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd};
 use std::os::unix::net::UnixStream;
+use std::sync::mpsc::TryRecvError;
+use std::time::{Duration, Instant};
 
 const POLL_INFINITE_TIMEOUT: i32 = -1;
 const SIGWINCH_PIPE_INDEX: usize = 0;
+const CONTROL_PIPE_INDEX: usize = 2;
+const RECORDS_PIPE_INDEX: usize = 3;
 const BUFFER_SIZE: usize = 1024;
+// How long to wait for the rest of an escape sequence that was split
+// across multiple reads.
+const ESCAPE_SEQUENCE_TIMEOUT_MS: i32 = 50;
+// Give up on CSI sequences that go on for too long without a final byte.
+const MAX_CSI_LENGTH: usize = 64;
+// Likewise for OSC sequences without a terminator.
+const MAX_OSC_LENGTH: usize = 256;
+// How long to wait for the terminal to report its background color.
+const BACKGROUND_QUERY_TIMEOUT_MS: u64 = 100;
+
+// OSC 11 asks for the background color. Terminals that don't support it
+// just ignore it, so we follow it with a request for the primary device
+// attributes (DA1), which every terminal answers, so that we know when to
+// stop waiting without always having to wait for the full timeout.
+const BACKGROUND_COLOR_QUERY: &str = "\x1b]11;?\x1b\\\x1b[c";
+const BACKGROUND_COLOR_RESPONSE_PREFIX: &[u8] = b"\x1b]11;";
+const DEVICE_ATTRIBUTES_RESPONSE_PREFIX: &[u8] = b"\x1b[?";
 
 const ESCAPE: u8 = 0o33;
+const BELL: u8 = 0o07;
 
-pub fn remap_dev_tty_to_stdin() {
+pub fn remap_dev_tty_to_stdin() -> io::Result<()> {
     // The readline library we use, rustyline, always gets its input from STDIN.
     // If jless accepts its input from STDIN, then rustyline can't accept input.
     // To fix this, we open up /dev/tty, and remap it to STDIN, as suggested in
@@ -28,18 +54,54 @@ pub fn remap_dev_tty_to_stdin() {
         // freopen(3) docs: https://linux.die.net/man/3/freopen
         let filename = std::ffi::CString::new("/dev/tty").unwrap();
         let path = std::ffi::CString::new("r").unwrap();
-        let _ = libc::freopen(filename.as_ptr(), path.as_ptr(), libc_stdhandle::stdin());
+        // If this fails, e.g., because there's no controlling terminal,
+        // STDIN would be left closed, so report it rather than carrying on.
+        if libc::freopen(filename.as_ptr(), path.as_ptr(), libc_stdhandle::stdin()).is_null() {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+// When picking a value, the output of jless is usually captured by the
+// shell, so the viewer needs to be drawn on the terminal instead. We keep
+// a handle to the original STDOUT to print the picked value to, then remap
+// /dev/tty to STDOUT, so that everything that draws the viewer, or asks
+// for the terminal's size, uses the terminal.
+pub fn remap_dev_tty_to_stdout() -> io::Result<File> {
+    let tty = OpenOptions::new().write(true).open("/dev/tty")?;
+    unsafe {
+        let original_stdout = libc::dup(libc::STDOUT_FILENO);
+        if original_stdout < 0 || libc::dup2(tty.as_raw_fd(), libc::STDOUT_FILENO) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(File::from_raw_fd(original_stdout))
     }
 }
 
-pub fn get_input() -> impl Iterator<Item = io::Result<TuiEvent>> {
+// A handle to the original STDIN, which can still be read from after
+// /dev/tty is remapped to STDIN.
+pub fn duplicate_stdin() -> io::Result<File> {
+    unsafe {
+        let original_stdin = libc::dup(libc::STDIN_FILENO);
+        if original_stdin < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(File::from_raw_fd(original_stdin))
+    }
+}
+
+pub fn get_input(
+    control: Option<ControlReceiver>,
+    records: Option<RecordStream>,
+) -> impl Iterator<Item = io::Result<TuiEvent>> {
     let (sigwinch_read, sigwinch_write) = UnixStream::pair().unwrap();
     // NOTE: This overrides the SIGWINCH handler registered by rustyline.
     // We should maybe get a reference to the existing signal handler
     // and call it when appropriate, but it seems to only be used to handle
     // line wrapping, and it seems to work fine without it.
     pipe::register(SIGWINCH, sigwinch_write).unwrap();
-    TuiInput::new(stdin(), sigwinch_read)
+    TuiInput::new(stdin(), sigwinch_read, control, records)
 }
 
 fn read_and_retry_on_interrupt(input: &mut Stdin, buf: &mut [u8]) -> io::Result<usize> {
@@ -58,110 +120,142 @@ fn read_and_retry_on_interrupt(input: &mut Stdin, buf: &mut [u8]) -> io::Result<
     }
 }
 
-struct BufferedInput<const N: usize> {
-    input: Stdin,
-    buffer: [u8; N],
-    buffer_size: usize,
-    buffer_index: usize,
-    might_have_more_data: bool,
-}
+// Whether a key has been pressed, without waiting for one, for cancelling
+// long running operations. Whatever was typed is discarded.
+pub fn key_pressed() -> bool {
+    let mut input = stdin();
+    if unsafe { libc::isatty(input.as_raw_fd()) } == 0 {
+        return false;
+    }
 
-impl<const N: usize> BufferedInput<N> {
-    fn new(input: Stdin) -> BufferedInput<N> {
-        BufferedInput {
-            input,
-            buffer: [0; N],
-            buffer_size: 0,
-            buffer_index: 0,
-            might_have_more_data: false,
-        }
+    let mut poll_fd = libc::pollfd {
+        fd: input.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    if unsafe { libc::poll(&mut poll_fd, 1, 0) } <= 0 {
+        return false;
     }
 
-    fn next_u8(&mut self) -> u8 {
-        if self.buffer_index >= self.buffer_size {
-            panic!("No data in buffer");
-        }
+    let mut buf = [0; BUFFER_SIZE];
+    matches!(read_and_retry_on_interrupt(&mut input, &mut buf), Ok(n) if n > 0)
+}
 
-        let val = self.buffer[self.buffer_index];
-        self.buffer_index += 1;
-        val
-    }
+// Red, green and blue components between 0.0 and 1.0.
+pub type Rgb = (f32, f32, f32);
+
+// Ask the terminal for its background color, returning None if the terminal
+// doesn't report it.
+//
+// This needs to be called in raw mode, before we start reading input events,
+// and anything typed while we're waiting for the response is discarded.
+pub fn query_background_color(out: &mut dyn Write) -> io::Result<Option<Rgb>> {
+    out.write_all(BACKGROUND_COLOR_QUERY.as_bytes())?;
+    out.flush()?;
+
+    let mut input = stdin();
+    let mut poll_fd = libc::pollfd {
+        fd: input.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let deadline = Instant::now() + Duration::from_millis(BACKGROUND_QUERY_TIMEOUT_MS);
+    let mut response = vec![];
+
+    while !has_device_attributes_response(&response) {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        if timeout.is_zero() {
+            break;
+        }
 
-    fn clear(&mut self) {
-        // Clear buffer in debug mode.
-        if cfg!(debug_assertions) {
-            for elem in self.buffer.iter_mut() {
-                *elem = 0;
+        match unsafe { libc::poll(&mut poll_fd, 1, timeout.as_millis() as i32) } {
+            -1 => {
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+                continue;
             }
+            0 => break,
+            _ => {}
         }
 
-        self.buffer_size = 0;
-        self.buffer_index = 0;
-        self.might_have_more_data = false;
-    }
-
-    fn might_have_buffered_data(&self) -> bool {
-        self.might_have_more_data || self.has_buffered_data()
-    }
-
-    fn has_buffered_data(&self) -> bool {
-        self.buffer_index < self.buffer_size
-    }
-
-    fn take_pure_escape(&mut self) -> bool {
-        if self.buffer_index == 0 && self.buffer_size == 1 && self.buffer[0] == ESCAPE {
-            // This will set self.might_have_more_data = true, which is fine,
-            // because that only gets set to true when buffer_size == N, but
-            // we just checked that it is 1 and not N.
-            self.clear();
-            return true;
+        let mut buf = [0; BUFFER_SIZE];
+        match read_and_retry_on_interrupt(&mut input, &mut buf)? {
+            0 => break,
+            bytes_read => response.extend_from_slice(&buf[..bytes_read]),
         }
-
-        false
     }
 
-    fn read_more_if_needed(&mut self) -> Option<io::Error> {
-        if self.has_buffered_data() {
-            return None;
-        }
-
-        self.clear();
+    Ok(parse_background_color_response(&response))
+}
 
-        match read_and_retry_on_interrupt(&mut self.input, &mut self.buffer) {
-            Ok(bytes_read) => {
-                self.buffer_size = bytes_read;
-                self.might_have_more_data = bytes_read == N;
-                None
-            }
-            Err(err) => Some(err),
-        }
+fn has_device_attributes_response(bytes: &[u8]) -> bool {
+    match find_subslice(bytes, DEVICE_ATTRIBUTES_RESPONSE_PREFIX) {
+        Some(start) => bytes[start..].contains(&b'c'),
+        None => false,
     }
 }
 
-impl<const N: usize> Iterator for BufferedInput<N> {
-    type Item = io::Result<u8>;
-
-    fn next(&mut self) -> Option<io::Result<u8>> {
-        if !self.has_buffered_data() {
+// Parses a response like "\x1b]11;rgb:ffff/ffff/dddd\x1b\\" (or terminated by
+// BEL), where each component has between 1 and 4 hex digits.
+fn parse_background_color_response(bytes: &[u8]) -> Option<Rgb> {
+    let start = find_subslice(bytes, BACKGROUND_COLOR_RESPONSE_PREFIX)?
+        + BACKGROUND_COLOR_RESPONSE_PREFIX.len();
+    let len = bytes[start..]
+        .iter()
+        .position(|&b| b == ESCAPE || b == BELL)?;
+    let color = std::str::from_utf8(&bytes[start..start + len]).ok()?;
+
+    let mut components = color.strip_prefix("rgb:")?.split('/').map(|hex| {
+        if hex.is_empty() || hex.len() > 4 {
             return None;
         }
-
-        Some(Ok(self.next_u8()))
+        let max = (1u32 << (4 * hex.len())) - 1;
+        Some(u32::from_str_radix(hex, 16).ok()? as f32 / max as f32)
+    });
+
+    let rgb = (
+        components.next()??,
+        components.next()??,
+        components.next()??,
+    );
+    if components.next().is_some() {
+        return None;
     }
+    Some(rgb)
+}
+
+fn find_subslice(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
 }
 
 struct TuiInput {
-    poll_fds: [libc::pollfd; 2],
+    poll_fds: [libc::pollfd; 4],
     sigwinch_pipe: UnixStream,
-    buffered_input: BufferedInput<BUFFER_SIZE>,
+    input: Stdin,
+    control: Option<ControlReceiver>,
+    // Only present while newline-delimited JSON input is still being read.
+    records: Option<RecordStream>,
+    parser: EventParser,
 }
 
 impl TuiInput {
-    fn new(input: Stdin, sigwinch_pipe: UnixStream) -> TuiInput {
+    fn new(
+        input: Stdin,
+        sigwinch_pipe: UnixStream,
+        control: Option<ControlReceiver>,
+        records: Option<RecordStream>,
+    ) -> TuiInput {
         let sigwinch_fd = sigwinch_pipe.as_raw_fd();
         let stdin_fd = input.as_raw_fd();
+        // poll ignores negative file descriptors.
+        let control_fd = control.as_ref().map_or(-1, |control| control.as_raw_fd());
+        let records_fd = records.as_ref().map_or(-1, |records| records.as_raw_fd());
 
-        let poll_fds: [libc::pollfd; 2] = [
+        let poll_fds: [libc::pollfd; 4] = [
             libc::pollfd {
                 fd: sigwinch_fd,
                 events: libc::POLLIN,
@@ -172,78 +266,489 @@ impl TuiInput {
                 events: libc::POLLIN,
                 revents: 0,
             },
+            libc::pollfd {
+                fd: control_fd,
+                events: libc::POLLIN,
+                revents: 0,
+            },
+            libc::pollfd {
+                fd: records_fd,
+                events: libc::POLLIN,
+                revents: 0,
+            },
         ];
 
         TuiInput {
             poll_fds,
             sigwinch_pipe,
-            buffered_input: BufferedInput::new(input),
+            input,
+            control,
+            records,
+            parser: EventParser::new(),
         }
     }
 
-    fn get_event_from_buffered_input(&mut self) -> Option<io::Result<TuiEvent>> {
-        if !self.buffered_input.has_buffered_data() {
-            if let Some(err) = self.buffered_input.read_more_if_needed() {
-                return Some(Err(err));
-            }
+    fn stop_receiving_records(&mut self) {
+        self.records = None;
+        // poll ignores negative file descriptors.
+        self.poll_fds[RECORDS_PIPE_INDEX].fd = -1;
+    }
+
+    // Wait until there's input to read or the window is resized, returning
+    // None if we timed out first.
+    fn poll(&mut self, timeout: i32) -> io::Result<Option<PollResult>> {
+        loop {
+            match unsafe { libc::poll(self.poll_fds.as_mut_ptr(), 4, timeout) } {
+                -1 => {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err);
+                    }
+                    // Try poll again.
+                }
+                0 => return Ok(None),
+                _ => break,
+            };
         }
 
-        if self.buffered_input.take_pure_escape() {
-            return Some(Ok(TuiEvent::KeyEvent(Key::Esc)));
+        if self.poll_fds[SIGWINCH_PIPE_INDEX].revents & libc::POLLIN != 0 {
+            // Just make this big enough to absorb a bunch of unacknowledged SIGWINCHes.
+            let mut buf = [0; 32];
+            let _ = self.sigwinch_pipe.read(&mut buf);
+            return Ok(Some(PollResult::WinCh));
         }
 
-        match self.buffered_input.next() {
-            Some(Ok(byte)) => match parse_event(byte, &mut self.buffered_input) {
-                Ok(Event::Key(k)) => Some(Ok(TuiEvent::KeyEvent(k))),
-                Ok(Event::Mouse(m)) => Some(Ok(TuiEvent::MouseEvent(m))),
-                Ok(Event::Unsupported(bytes)) => Some(Ok(TuiEvent::Unknown(bytes))),
-                Err(err) => Some(Err(err)),
-            },
-            Some(Err(err)) => Some(Err(err)),
-            None => None,
+        if self.poll_fds[CONTROL_PIPE_INDEX].revents & libc::POLLIN != 0 {
+            if let Some(control) = &mut self.control {
+                control.clear_wake_pipe();
+            }
+            return Ok(Some(PollResult::Control));
+        }
+
+        // The pipe is closed (POLLHUP) once all the records have been read.
+        if self.poll_fds[RECORDS_PIPE_INDEX].revents & (libc::POLLIN | libc::POLLHUP) != 0 {
+            if let Some(records) = &mut self.records {
+                records.clear_wake_pipe();
+            }
+            return Ok(Some(PollResult::Records));
         }
+
+        Ok(Some(PollResult::Input))
     }
 }
 
+enum PollResult {
+    WinCh,
+    Input,
+    // A command arrived on the control socket.
+    Control,
+    // More records of newline-delimited JSON input were read.
+    Records,
+}
+
 impl Iterator for TuiInput {
     type Item = io::Result<TuiEvent>;
 
     fn next(&mut self) -> Option<io::Result<TuiEvent>> {
-        if self.buffered_input.might_have_buffered_data() {
-            return self.get_event_from_buffered_input();
-        }
+        loop {
+            if let Some(event) = self.parser.next_event(false) {
+                return Some(Ok(event));
+            }
 
-        let poll_res: Option<io::Error>;
+            if let Some(command) = self.control.as_ref().and_then(|control| control.try_recv()) {
+                return Some(Ok(TuiEvent::Control(command)));
+            }
 
-        loop {
-            match unsafe { libc::poll(self.poll_fds.as_mut_ptr(), 2, POLL_INFINITE_TIMEOUT) } {
-                -1 => {
-                    let err = io::Error::last_os_error();
-                    if err.kind() != io::ErrorKind::Interrupted {
-                        poll_res = Some(err);
-                        break;
+            if let Some(stream) = &self.records {
+                match stream.try_recv() {
+                    Ok(records) => {
+                        if records.last {
+                            self.stop_receiving_records();
+                        }
+                        return Some(Ok(TuiEvent::Records(records)));
                     }
-                    // Try poll again.
+                    Err(TryRecvError::Disconnected) => self.stop_receiving_records(),
+                    Err(TryRecvError::Empty) => {}
                 }
-                _ => {
-                    poll_res = None;
-                    break;
+            }
+
+            // If we have part of an escape sequence, only wait a little while
+            // for the rest of it.
+            let timeout = if self.parser.has_pending_input() {
+                ESCAPE_SEQUENCE_TIMEOUT_MS
+            } else {
+                POLL_INFINITE_TIMEOUT
+            };
+
+            match self.poll(timeout) {
+                Err(err) => return Some(Err(err)),
+                Ok(Some(PollResult::WinCh)) => return Some(Ok(TuiEvent::WinChEvent)),
+                // Picked up at the top of the loop.
+                Ok(Some(PollResult::Control)) | Ok(Some(PollResult::Records)) => {}
+                Ok(Some(PollResult::Input)) => {
+                    let mut buf = [0; BUFFER_SIZE];
+                    match read_and_retry_on_interrupt(&mut self.input, &mut buf) {
+                        // EOF; emit whatever is left over, then stop.
+                        Ok(0) => return self.parser.next_event(true).map(Ok),
+                        Ok(bytes_read) => {
+                            // If the user hit the escape key by itself we'll
+                            // read just that, and we don't want to wait to see
+                            // if it's the start of an escape sequence.
+                            if !self.parser.has_pending_input() && buf[..bytes_read] == [ESCAPE] {
+                                return Some(Ok(TuiEvent::KeyEvent(Key::Esc)));
+                            }
+                            self.parser.push(&buf[..bytes_read]);
+                        }
+                        Err(err) => return Some(Err(err)),
+                    }
                 }
+                // Timed out waiting for the rest of an escape sequence.
+                Ok(None) => return self.parser.next_event(true).map(Ok),
+            }
+        }
+    }
+}
+
+// Converts a stream of bytes from the terminal into events. Input may
+// arrive in arbitrary chunks (an escape sequence may be split across two
+// reads), so bytes that form an incomplete sequence are kept until more
+// input arrives.
+//
+// Parsing never fails: any bytes that aren't recognized are returned in
+// a TuiEvent::Unknown, and parsing resumes right after them, so unexpected
+// input can never cause later input to be misinterpreted.
+struct EventParser {
+    pending: Vec<u8>,
+}
+
+impl EventParser {
+    fn new() -> EventParser {
+        EventParser { pending: vec![] }
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        self.pending.extend_from_slice(bytes);
+    }
+
+    fn has_pending_input(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    // Returns the next event, or None if more input is needed. When flush
+    // is true, we won't get any more input to complete a partial sequence,
+    // so whatever is pending is returned as is.
+    fn next_event(&mut self, flush: bool) -> Option<TuiEvent> {
+        if self.pending.is_empty() {
+            return None;
+        }
+
+        let (event, len) = match parse_event(&self.pending) {
+            Parsed::Event(event, len) => (event, len),
+            Parsed::Incomplete if !flush => return None,
+            Parsed::Incomplete if self.pending == [ESCAPE] => (TuiEvent::KeyEvent(Key::Esc), 1),
+            Parsed::Incomplete if self.pending == [ESCAPE, b']'] => {
+                (TuiEvent::KeyEvent(Key::Alt(']')), 2)
+            }
+            Parsed::Incomplete => (TuiEvent::Unknown(self.pending.clone()), self.pending.len()),
+        };
+
+        self.pending.drain(..len);
+        Some(event)
+    }
+}
+
+enum Parsed {
+    // An event, and the number of bytes it consumed (always at least one).
+    Event(TuiEvent, usize),
+    // The bytes are a prefix of a sequence we might recognize.
+    Incomplete,
+}
+
+fn key(key: Key, len: usize) -> Parsed {
+    Parsed::Event(TuiEvent::KeyEvent(key), len)
+}
+
+fn mouse(mouse_event: MouseEvent, len: usize) -> Parsed {
+    Parsed::Event(TuiEvent::MouseEvent(mouse_event), len)
+}
+
+fn unknown(bytes: &[u8]) -> Parsed {
+    Parsed::Event(TuiEvent::Unknown(bytes.to_vec()), bytes.len())
+}
+
+// Parse a single event from the start of a non-empty slice of bytes.
+//
+// This mirrors the parsing done by termion::event::parse_event, which
+// panics on some malformed input, and consumes bytes from the input even
+// when it can't parse them.
+fn parse_event(bytes: &[u8]) -> Parsed {
+    match bytes[0] {
+        ESCAPE => parse_escape_sequence(bytes),
+        b'\n' | b'\r' => key(Key::Char('\n'), 1),
+        b'\t' => key(Key::Char('\t'), 1),
+        b'\x7F' => key(Key::Backspace, 1),
+        c @ b'\x01'..=b'\x1A' => key(Key::Ctrl((c - 0x1 + b'a') as char), 1),
+        c @ b'\x1C'..=b'\x1F' => key(Key::Ctrl((c - 0x1C + b'4') as char), 1),
+        b'\0' => key(Key::Null, 1),
+        _ => match parse_utf8_char(bytes) {
+            Utf8::Char(ch, len) => key(Key::Char(ch), len),
+            Utf8::Incomplete => Parsed::Incomplete,
+            Utf8::Invalid(len) => unknown(&bytes[..len]),
+        },
+    }
+}
+
+fn parse_escape_sequence(bytes: &[u8]) -> Parsed {
+    match bytes.get(1) {
+        None => Parsed::Incomplete,
+        Some(b'[') => parse_csi(bytes),
+        Some(b']') => parse_osc(bytes),
+        Some(b'O') => match bytes.get(2) {
+            None => Parsed::Incomplete,
+            Some(val @ b'P'..=b'S') => key(Key::F(1 + val - b'P'), 3),
+            // Sent for the arrow keys, Home and End when the terminal is in
+            // "application cursor keys" mode, which another program may have
+            // left enabled.
+            Some(b'A') => key(Key::Up, 3),
+            Some(b'B') => key(Key::Down, 3),
+            Some(b'C') => key(Key::Right, 3),
+            Some(b'D') => key(Key::Left, 3),
+            Some(b'H') => key(Key::Home, 3),
+            Some(b'F') => key(Key::End, 3),
+            Some(_) => unknown(&bytes[..3]),
+        },
+        Some(_) => match parse_utf8_char(&bytes[1..]) {
+            Utf8::Char(ch, len) => key(Key::Alt(ch), 1 + len),
+            Utf8::Incomplete => Parsed::Incomplete,
+            Utf8::Invalid(len) => unknown(&bytes[..1 + len]),
+        },
+    }
+}
+
+// Operating System Command sequences look like ESC ], followed by a
+// number, a ';' and some text, terminated by BEL or ESC \. Terminals only
+// send them in reply to a query, e.g., for the background color, which may
+// arrive after we've stopped waiting for it, so they're dropped. ESC ]
+// followed by anything other than a number is just Alt+].
+fn parse_osc(bytes: &[u8]) -> Parsed {
+    match bytes.get(2) {
+        None => return Parsed::Incomplete,
+        Some(b'0'..=b'9') => {}
+        Some(_) => return key(Key::Alt(']'), 2),
+    }
+
+    for i in 3..bytes.len() {
+        if i >= MAX_OSC_LENGTH {
+            return unknown(&bytes[..i]);
+        }
+        match (bytes[i], bytes.get(i + 1)) {
+            (BELL, _) => return unknown(&bytes[..=i]),
+            (ESCAPE, Some(b'\\')) => return unknown(&bytes[..i + 2]),
+            (ESCAPE, None) => return Parsed::Incomplete,
+            // Not terminated properly; start parsing again from the ESC.
+            (ESCAPE, Some(_)) => return unknown(&bytes[..i]),
+            _ => {}
+        }
+    }
+
+    Parsed::Incomplete
+}
+
+// Control Sequence Introducer sequences look like ESC [, followed by any
+// number of "parameter" bytes (0x30-0x3F), then "intermediate" bytes
+// (0x20-0x2F), then a single "final" byte (0x40-0x7E).
+fn parse_csi(bytes: &[u8]) -> Parsed {
+    match bytes.get(2) {
+        None => return Parsed::Incomplete,
+        // Function keys in the Linux console: ESC [ [ A-E
+        Some(b'[') => {
+            return match bytes.get(3) {
+                None => Parsed::Incomplete,
+                Some(val @ b'A'..=b'E') => key(Key::F(1 + val - b'A'), 4),
+                Some(_) => unknown(&bytes[..4]),
+            }
+        }
+        // X10 mouse encoding: ESC [ M Cb Cx Cy, where Cb, Cx and Cy are
+        // raw bytes.
+        Some(b'M') => {
+            if bytes.len() < 6 {
+                return Parsed::Incomplete;
+            }
+            return match parse_x10_mouse(bytes[3], bytes[4], bytes[5]) {
+                Some(mouse_event) => mouse(mouse_event, 6),
+                None => unknown(&bytes[..6]),
             };
         }
+        Some(_) => {}
+    }
 
-        if let Some(poll_err) = poll_res {
-            return Some(Err(poll_err));
+    let mut final_index = 2;
+    loop {
+        if final_index >= MAX_CSI_LENGTH {
+            return unknown(&bytes[..final_index]);
         }
 
-        if self.poll_fds[SIGWINCH_PIPE_INDEX].revents & libc::POLLIN != 0 {
-            // Just make this big enough to absorb a bunch of unacknowledged SIGWINCHes.
-            let mut buf = [0; 32];
-            let _ = self.sigwinch_pipe.read(&mut buf);
-            return Some(Ok(TuiEvent::WinChEvent));
+        match bytes.get(final_index) {
+            None => return Parsed::Incomplete,
+            Some(0x20..=0x3F) => final_index += 1,
+            Some(0x40..=0x7E) => break,
+            // Not a valid CSI sequence; report what we have so far, and
+            // start parsing again from the invalid byte.
+            Some(_) => return unknown(&bytes[..final_index]),
         }
+    }
+
+    let sequence = &bytes[..=final_index];
+    let params = &bytes[2..final_index];
+    let final_byte = bytes[final_index];
+
+    let event = match (params, final_byte) {
+        (b"", b'A') => Some(TuiEvent::KeyEvent(Key::Up)),
+        (b"", b'B') => Some(TuiEvent::KeyEvent(Key::Down)),
+        (b"", b'C') => Some(TuiEvent::KeyEvent(Key::Right)),
+        (b"", b'D') => Some(TuiEvent::KeyEvent(Key::Left)),
+        (b"", b'H') => Some(TuiEvent::KeyEvent(Key::Home)),
+        (b"", b'F') => Some(TuiEvent::KeyEvent(Key::End)),
+        (b"", b'Z') => Some(TuiEvent::KeyEvent(Key::BackTab)),
+        // SGR mouse encoding: ESC [ < Cb ; Cx ; Cy (M or m)
+        ([b'<', rest @ ..], b'M' | b'm') => parse_numeric_params(rest)
+            .and_then(|nums| parse_sgr_mouse(&nums, final_byte == b'M'))
+            .map(TuiEvent::MouseEvent),
+        // rxvt mouse encoding: ESC [ Cb ; Cx ; Cy M
+        (_, b'M') => parse_numeric_params(params)
+            .and_then(|nums| parse_rxvt_mouse(&nums))
+            .map(TuiEvent::MouseEvent),
+        (_, b'~') => parse_numeric_params(params)
+            .and_then(|nums| parse_special_key(&nums))
+            .map(TuiEvent::KeyEvent),
+        // Everything else, including focus events (ESC [ I and ESC [ O),
+        // and keys with modifiers, e.g., ESC [ 1 ; 5 A.
+        _ => None,
+    };
+
+    match event {
+        Some(event) => Parsed::Event(event, sequence.len()),
+        None => unknown(sequence),
+    }
+}
+
+fn parse_numeric_params(params: &[u8]) -> Option<Vec<u16>> {
+    std::str::from_utf8(params)
+        .ok()?
+        .split(';')
+        .map(|n| n.parse::<u16>().ok())
+        .collect()
+}
+
+fn parse_x10_mouse(cb: u8, cx: u8, cy: u8) -> Option<MouseEvent> {
+    let cb = cb.wrapping_sub(32);
+    // (1, 1) are the coords for upper left.
+    let cx = cx.saturating_sub(32) as u16;
+    let cy = cy.saturating_sub(32) as u16;
+
+    Some(match cb & 0b11 {
+        0 if cb & 0x40 != 0 => MouseEvent::Press(MouseButton::WheelUp, cx, cy),
+        0 => MouseEvent::Press(MouseButton::Left, cx, cy),
+        1 if cb & 0x40 != 0 => MouseEvent::Press(MouseButton::WheelDown, cx, cy),
+        1 => MouseEvent::Press(MouseButton::Middle, cx, cy),
+        2 => MouseEvent::Press(MouseButton::Right, cx, cy),
+        _ => MouseEvent::Release(cx, cy),
+    })
+}
+
+fn parse_sgr_mouse(nums: &[u16], pressed: bool) -> Option<MouseEvent> {
+    let [cb, cx, cy] = *nums else {
+        return None;
+    };
+
+    let button = match cb {
+        0 => MouseButton::Left,
+        1 => MouseButton::Middle,
+        2 => MouseButton::Right,
+        64 => MouseButton::WheelUp,
+        65 => MouseButton::WheelDown,
+        32 => return Some(MouseEvent::Hold(cx, cy)),
+        3 => return Some(MouseEvent::Release(cx, cy)),
+        _ => return None,
+    };
+
+    if pressed {
+        Some(MouseEvent::Press(button, cx, cy))
+    } else {
+        Some(MouseEvent::Release(cx, cy))
+    }
+}
+
+fn parse_rxvt_mouse(nums: &[u16]) -> Option<MouseEvent> {
+    let [cb, cx, cy] = *nums else {
+        return None;
+    };
+
+    Some(match cb {
+        32 => MouseEvent::Press(MouseButton::Left, cx, cy),
+        33 => MouseEvent::Press(MouseButton::Middle, cx, cy),
+        34 => MouseEvent::Press(MouseButton::Right, cx, cy),
+        35 => MouseEvent::Release(cx, cy),
+        64 => MouseEvent::Hold(cx, cy),
+        96 | 97 => MouseEvent::Press(MouseButton::WheelUp, cx, cy),
+        _ => return None,
+    })
+}
+
+fn parse_special_key(nums: &[u16]) -> Option<Key> {
+    // Multiple values are used for key modifiers, e.g., [3, 2] means
+    // Shift+Delete, which we don't handle.
+    let [num] = *nums else {
+        return None;
+    };
+
+    Some(match num {
+        1 | 7 => Key::Home,
+        2 => Key::Insert,
+        3 => Key::Delete,
+        4 | 8 => Key::End,
+        5 => Key::PageUp,
+        6 => Key::PageDown,
+        v @ 11..=15 => Key::F((v - 10) as u8),
+        v @ 17..=21 => Key::F((v - 11) as u8),
+        v @ 23..=24 => Key::F((v - 12) as u8),
+        _ => return None,
+    })
+}
+
+enum Utf8 {
+    // A character and its length in bytes.
+    Char(char, usize),
+    Incomplete,
+    // The number of bytes that are part of the invalid sequence.
+    Invalid(usize),
+}
 
-        self.get_event_from_buffered_input()
+fn parse_utf8_char(bytes: &[u8]) -> Utf8 {
+    let len = match bytes[0] {
+        0x00..=0x7F => return Utf8::Char(bytes[0] as char, 1),
+        0xC2..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF4 => 4,
+        _ => return Utf8::Invalid(1),
+    };
+
+    let available = &bytes[..len.min(bytes.len())];
+    if let Some(i) = available[1..]
+        .iter()
+        .position(|b| !(0x80..=0xBF).contains(b))
+    {
+        return Utf8::Invalid(1 + i);
+    }
+
+    if available.len() < len {
+        return Utf8::Incomplete;
+    }
+
+    match std::str::from_utf8(available) {
+        Ok(s) => Utf8::Char(s.chars().next().unwrap(), len),
+        // Overlong encodings and surrogates.
+        Err(_) => Utf8::Invalid(len),
     }
 }
 
@@ -253,4 +758,233 @@ pub enum TuiEvent {
     KeyEvent(Key),
     MouseEvent(MouseEvent),
     Unknown(Vec<u8>),
+    Control(ControlCommand),
+    // More records of newline-delimited JSON input.
+    Records(Records),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::Rng;
+
+    fn parse_chunks(chunks: &[&[u8]]) -> Vec<String> {
+        let mut parser = EventParser::new();
+        let mut events = vec![];
+
+        for chunk in chunks {
+            parser.push(chunk);
+            while let Some(event) = parser.next_event(false) {
+                events.push(format!("{event:?}"));
+            }
+        }
+        while let Some(event) = parser.next_event(true) {
+            events.push(format!("{event:?}"));
+        }
+
+        events
+    }
+
+    fn parse_bytes(bytes: &[u8]) -> Vec<String> {
+        parse_chunks(&[bytes])
+    }
+
+    #[test]
+    fn test_parse_keys_and_mouse_events() {
+        assert_eq!(
+            vec![
+                "KeyEvent(Char('j'))",
+                "KeyEvent(Char('é'))",
+                "KeyEvent(Ctrl('d'))",
+                "KeyEvent(Char('\\n'))",
+                "KeyEvent(Up)",
+                "KeyEvent(Left)",
+                "KeyEvent(PageDown)",
+                "KeyEvent(F(1))",
+                "KeyEvent(Alt('x'))",
+                "MouseEvent(Press(Left, 10, 20))",
+                "MouseEvent(Press(WheelDown, 1, 1))",
+                "MouseEvent(Press(Left, 3, 4))",
+                "KeyEvent(Esc)",
+            ],
+            parse_bytes(
+                b"j\xc3\xa9\x04\r\x1b[A\x1bOD\x1b[6~\x1bOP\x1bx\
+                  \x1b[<0;10;20M\x1b[<65;1;1M\x1b[M #$\x1b"
+            ),
+        );
+    }
+
+    #[test]
+    fn test_unrecognized_sequences_are_unknown() {
+        let cases: &[(&[u8], &[&str])] = &[
+            // Focus events
+            (
+                b"\x1b[I\x1b[O",
+                &["Unknown([27, 91, 73])", "Unknown([27, 91, 79])"],
+            ),
+            // Malformed SGR mouse events
+            (
+                b"\x1b[<0;;5Mj",
+                &[
+                    "Unknown([27, 91, 60, 48, 59, 59, 53, 77])",
+                    "KeyEvent(Char('j'))",
+                ],
+            ),
+            (b"\x1b[<0;1M", &["Unknown([27, 91, 60, 48, 59, 49, 77])"]),
+            // Any-motion mouse tracking
+            (
+                b"\x1b[<35;1;2M",
+                &["Unknown([27, 91, 60, 51, 53, 59, 49, 59, 50, 77])"],
+            ),
+            // Numbers that are too big
+            (
+                b"\x1b[99999~",
+                &["Unknown([27, 91, 57, 57, 57, 57, 57, 126])"],
+            ),
+            // A CSI sequence interrupted by a control character
+            (
+                b"\x1b[12\x04",
+                &["Unknown([27, 91, 49, 50])", "KeyEvent(Ctrl('d'))"],
+            ),
+            // Invalid UTF-8
+            (
+                b"\xff\xc3(",
+                &["Unknown([255])", "Unknown([195])", "KeyEvent(Char('('))"],
+            ),
+            // A reply to the background color query that arrived late
+            (
+                b"\x1b]11;rgb:0/0/0\x07j",
+                &[
+                    "Unknown([27, 93, 49, 49, 59, 114, 103, 98, 58, 48, 47, 48, 47, 48, 7])",
+                    "KeyEvent(Char('j'))",
+                ],
+            ),
+            (
+                b"\x1b]11;rgb:0/0/0\x1b\\\x1b]11;\x1b[A",
+                &[
+                    "Unknown([27, 93, 49, 49, 59, 114, 103, 98, 58, 48, 47, 48, 47, 48, 27, 92])",
+                    "Unknown([27, 93, 49, 49, 59])",
+                    "KeyEvent(Up)",
+                ],
+            ),
+            // Alt+] isn't an OSC sequence.
+            (
+                b"\x1b]j\x1b]",
+                &[
+                    "KeyEvent(Alt(']'))",
+                    "KeyEvent(Char('j'))",
+                    "KeyEvent(Alt(']'))",
+                ],
+            ),
+            // Incomplete sequences at the end of the input
+            (b"\x1b[1;", &["Unknown([27, 91, 49, 59])"]),
+            (b"\xe2\x82", &["Unknown([226, 130])"]),
+        ];
+
+        for (bytes, expected) in cases {
+            assert_eq!(expected.to_vec(), parse_bytes(bytes), "Input: {bytes:?}");
+        }
+    }
+
+    #[test]
+    fn test_sequences_split_across_reads() {
+        let input: &[u8] = b"\x1b[<64;5;6M\x1b[5~\xe2\x82\xackq\x1b]11;rgb:0/0/0\x07\x1b[M !!\x1bO";
+        let expected = parse_bytes(input);
+
+        for split in 0..=input.len() {
+            let (first, second) = input.split_at(split);
+            assert_eq!(expected, parse_chunks(&[first, second]), "Split at {split}");
+        }
+
+        let one_at_a_time: Vec<&[u8]> = input.chunks(1).collect();
+        assert_eq!(expected, parse_chunks(&one_at_a_time));
+    }
+
+    #[test]
+    fn test_parse_background_color_response() {
+        let cases: Vec<(&[u8], Option<Rgb>)> = vec![
+            (b"\x1b]11;rgb:ffff/ffff/ffff\x1b\\", Some((1.0, 1.0, 1.0))),
+            (
+                b"\x1b]11;rgb:0000/8080/ffff\x07",
+                Some((0.0, 32896.0 / 65535.0, 1.0)),
+            ),
+            (
+                b"\x1b]11;rgb:f/00/fff\x07\x1b[?62;22c",
+                Some((1.0, 0.0, 1.0)),
+            ),
+            // Keys pressed before the response arrived are ignored.
+            (b"jk\x1b]11;rgb:00/00/00\x07", Some((0.0, 0.0, 0.0))),
+            // Only the device attributes were reported.
+            (b"\x1b[?62;22c", None),
+            // Unterminated, or malformed.
+            (b"\x1b]11;rgb:ffff/ffff/ffff", None),
+            (b"\x1b]11;rgb:ffff/ffff\x07", None),
+            (b"\x1b]11;rgb:fffff/ffff/ffff\x07", None),
+            (b"\x1b]11;rgb:ffff/ffff/ffff/ffff\x07", None),
+            (b"\x1b]11;#ffffff\x07", None),
+        ];
+
+        for (bytes, expected) in cases {
+            assert_eq!(
+                expected,
+                parse_background_color_response(bytes),
+                "Input: {bytes:?}"
+            );
+        }
+
+        assert!(has_device_attributes_response(
+            b"\x1b]11;rgb:0/0/0\x07\x1b[?1;2c"
+        ));
+        assert!(!has_device_attributes_response(
+            b"\x1b]11;rgb:0/0/0\x07\x1b[?1;2"
+        ));
+    }
+
+    // Generate bytes that are likely to look like (parts of) escape sequences.
+    fn random_bytes(rng: &mut Rng) -> Vec<u8> {
+        const INTERESTING: &[u8] =
+            b"\x1b\x1b\x1b[[[]<<;;;OMMm~0123456789IA\\\x07\x00\x7f\xc3\xa9\xe2\xff";
+        let len = rng.below(40);
+        (0..len)
+            .map(|_| {
+                if rng.below(4) == 0 {
+                    rng.below(256) as u8
+                } else {
+                    INTERESTING[rng.below(INTERESTING.len())]
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_random_bytes() {
+        // A fixed seed, so that failures are reproducible.
+        let mut rng = Rng::new(0x2545_f491_4f6c_dd1d);
+
+        for _ in 0..5000 {
+            let bytes = random_bytes(&mut rng);
+
+            // Every event consumes at least one byte, and never more than
+            // are available.
+            let mut remaining = &bytes[..];
+            while !remaining.is_empty() {
+                match parse_event(remaining) {
+                    Parsed::Event(_, len) => {
+                        assert!(len >= 1 && len <= remaining.len(), "Input: {:?}", bytes);
+                        remaining = &remaining[len..];
+                    }
+                    Parsed::Incomplete => break,
+                }
+            }
+
+            // How the input is split up doesn't affect how it's parsed.
+            let split = rng.below(bytes.len() + 1);
+            let (first, second) = bytes.split_at(split);
+            assert_eq!(
+                parse_bytes(&bytes),
+                parse_chunks(&[first, second]),
+                "Input: {bytes:?}, split at {split}"
+            );
+        }
+    }
 }
@@ -0,0 +1,124 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+
+// Run JSON input (one or more values) through a jq filter, returning the
+// resulting values, one per line.
+pub fn run(filter: &str, input: String) -> Result<Vec<String>, String> {
+    let mut child = Command::new("jq")
+        .arg("--compact-output")
+        .arg(filter)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("Unable to run jq: {err}"))?;
+
+    // Write the input on another thread, so that jq can't get stuck
+    // writing output that isn't being read while we're still writing.
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child
+        .wait_with_output()
+        .map_err(|err| format!("Unable to run jq: {err}"))?;
+    let _ = writer.join();
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(match stderr.lines().next() {
+            Some(error) => error.trim().to_string(),
+            None => format!("jq failed ({})", output.status),
+        });
+    }
+
+    let output = String::from_utf8(output.stdout).map_err(|err| err.to_string())?;
+    // With --compact-output, every value is on its own line.
+    Ok(output.lines().map(str::to_string).collect())
+}
+
+// Parse the definition of a named filter, NAME=EXPRESSION, which is run
+// with `:filter @NAME`. They're usually defined in the config file, as
+// `filters.NAME = "EXPRESSION"`.
+pub fn parse_named_filter(definition: &str) -> Result<(String, String), String> {
+    let Some((name, expression)) = definition.split_once('=') else {
+        return Err("Expected NAME=EXPRESSION".to_string());
+    };
+    let name = name.trim();
+    let is_name_char = |ch: char| ch.is_alphanumeric() || ch == '_' || ch == '-';
+    if name.is_empty() || !name.chars().all(is_name_char) {
+        return Err(format!("Invalid filter name: {name:?}"));
+    }
+    let expression = expression.trim();
+    if expression.is_empty() {
+        return Err(format!("Missing expression for filter {name}"));
+    }
+    Ok((name.to_string(), expression.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::run_jless;
+
+    fn jq_installed() -> bool {
+        Command::new("jq").arg("--version").output().is_ok()
+    }
+
+    #[test]
+    fn test_run() {
+        if !jq_installed() {
+            return;
+        }
+
+        let input = "{\"a\": [1, 2]}\n{\"a\": [3]}".to_string();
+        assert_eq!(
+            Ok(vec!["1".to_string(), "2".to_string(), "3".to_string()]),
+            run(".a[]", input.clone())
+        );
+        assert_eq!(Ok(vec![]), run("empty", input.clone()));
+
+        let err = run(".a[", input).unwrap_err();
+        assert!(err.starts_with("jq: error"), "{:?}", err);
+    }
+
+    #[test]
+    fn test_parse_named_filter() {
+        assert_eq!(
+            Ok(("k8s_images".to_string(), ".items[] | .image".to_string())),
+            parse_named_filter("k8s_images = .items[] | .image")
+        );
+        // Only the first '=' separates the name from the expression.
+        assert_eq!(
+            Ok(("ok".to_string(), "select(.a == 1)".to_string())),
+            parse_named_filter("ok=select(.a == 1)")
+        );
+        assert!(parse_named_filter(".items[]").is_err());
+        assert!(parse_named_filter("a b=.").is_err());
+        assert!(parse_named_filter("a=").is_err());
+    }
+
+    #[test]
+    fn test_named_filters() {
+        if !jq_installed() {
+            return;
+        }
+
+        // Filters defined in the config file are run by name.
+        const JSON: &str = r#"{"items": [{"id": 1}, {"id": 2}]}"#;
+        let screen_after = |commands: &str| {
+            let args = [
+                "--define-filter",
+                "ids=.items[].id",
+                "--screen-size",
+                "50x4",
+                "--cmd",
+                commands,
+            ];
+            run_jless(&args, JSON, &[]).unwrap()
+        };
+        let filtered = screen_after(":set nonumber | :filter @ids");
+        assert!(filtered.contains("[filter: .items[].id]"), "{:?}", filtered);
+        let screen = screen_after(":filter @nope");
+        assert!(screen.ends_with("No filter named nope"), "{:?}", screen);
+    }
+}
@@ -1,31 +1,64 @@
 use logos::{Lexer, Logos};
 
-use crate::flatjson::{ContainerType, Index, OptionIndex, Row, Value};
+use crate::document::{Document, DocumentWriter};
+use crate::flatjson::{ContainerType, Index, NumberType, OptionIndex, Row, Value};
 use crate::jsontokenizer::JsonToken;
 
 struct JsonParser<'a> {
     tokenizer: Lexer<'a, JsonToken>,
     parents: Vec<Index>,
     rows: Vec<Row>,
-    pretty_printed: String,
+    pretty_printed: DocumentWriter,
     max_depth: usize,
+    // Whether to accept NaN, Infinity and -Infinity as numbers.
+    allow_non_finite: bool,
 
     peeked_token: Option<Option<JsonToken>>,
 }
 
-pub fn parse(json: String) -> Result<(Vec<Row>, String, usize), String> {
+pub fn parse(json: &str) -> Result<(Vec<Row>, String, usize), String> {
+    let (rows, pretty, depth) = parse_with(json, false, false)?;
+    Ok((rows, pretty.into_string(), depth))
+}
+
+// Like parse, but also accepts the non-standard NaN, Infinity and -Infinity
+// literals that, e.g., Python's json.dumps emits by default.
+pub fn parse_allowing_non_finite(json: &str) -> Result<(Vec<Row>, String, usize), String> {
+    let (rows, pretty, depth) = parse_with(json, true, false)?;
+    Ok((rows, pretty.into_string(), depth))
+}
+
+// Like parse, but pretty prints straight into a Document, which is spilled
+// to a file as it's written if the input contains giant strings.
+pub fn parse_document(
+    json: &str,
+    allow_non_finite: bool,
+) -> Result<(Vec<Row>, Document, usize), String> {
+    parse_with(json, allow_non_finite, true)
+}
+
+fn parse_with(
+    json: &str,
+    allow_non_finite: bool,
+    spill: bool,
+) -> Result<(Vec<Row>, Document, usize), String> {
     let mut parser = JsonParser {
-        tokenizer: JsonToken::lexer(&json),
+        tokenizer: JsonToken::lexer(json),
         parents: vec![],
         rows: vec![],
-        pretty_printed: String::new(),
+        pretty_printed: DocumentWriter::new(spill),
         max_depth: 0,
+        allow_non_finite,
         peeked_token: None,
     };
 
     parser.parse_top_level_json()?;
 
-    Ok((parser.rows, parser.pretty_printed, parser.max_depth))
+    let pretty = parser
+        .pretty_printed
+        .finish()
+        .map_err(|err| format!("Unable to write spill file: {err}"))?;
+    Ok((parser.rows, pretty, parser.max_depth))
 }
 
 impl<'a> JsonParser<'a> {
@@ -100,44 +133,29 @@ impl<'a> JsonParser<'a> {
 
         self.max_depth = self.max_depth.max(self.parents.len());
 
-        loop {
-            match self.peek_token()? {
-                JsonToken::OpenCurly => {
-                    return self.parse_object();
-                }
-                JsonToken::OpenSquare => {
-                    return self.parse_array();
-                }
-                JsonToken::Null => {
-                    return self.parse_null();
-                }
-                JsonToken::True => {
-                    return self.parse_bool(true);
-                }
-                JsonToken::False => {
-                    return self.parse_bool(false);
-                }
-                JsonToken::Number => {
-                    return self.parse_number();
-                }
-                JsonToken::String => {
-                    return self.parse_string();
-                }
-
-                JsonToken::Whitespace | JsonToken::Newline => {
-                    panic!("Should have just consumed whitespace");
-                }
-
-                JsonToken::Error => {
-                    return Err("Parse error".to_string());
-                }
-                JsonToken::CloseCurly
-                | JsonToken::CloseSquare
-                | JsonToken::Colon
-                | JsonToken::Comma => {
-                    return Err(format!("Unexpected character: {:?}", self.tokenizer.span()));
-                }
+        match self.peek_token()? {
+            JsonToken::OpenCurly => self.parse_object(),
+            JsonToken::OpenSquare => self.parse_array(),
+            JsonToken::Null => self.parse_null(),
+            JsonToken::True => self.parse_bool(true),
+            JsonToken::False => self.parse_bool(false),
+            JsonToken::Number => self.parse_number(),
+            JsonToken::NonFiniteNumber if self.allow_non_finite => self.parse_number(),
+            JsonToken::NonFiniteNumber => Err(format!(
+                "{} isn't valid JSON; use --allow-nan to accept it",
+                self.tokenizer.slice()
+            )),
+            JsonToken::String => self.parse_string(),
+
+            JsonToken::Whitespace | JsonToken::Newline => {
+                panic!("Should have just consumed whitespace");
             }
+
+            JsonToken::Error => Err("Parse error".to_string()),
+            JsonToken::CloseCurly
+            | JsonToken::CloseSquare
+            | JsonToken::Colon
+            | JsonToken::Comma => Err(format!("Unexpected character: {:?}", self.tokenizer.span())),
         }
     }
 
@@ -389,7 +407,8 @@ impl<'a> JsonParser<'a> {
     }
 
     fn parse_number(&mut self) -> Result<usize, String> {
-        let row_index = self.create_row(Value::Number);
+        let number_type = NumberType::of_literal(self.tokenizer.slice());
+        let row_index = self.create_row(Value::Number(number_type));
         self.pretty_printed.push_str(self.tokenizer.slice());
 
         self.rows[row_index].range.end =
@@ -456,7 +475,7 @@ mod tests {
     fn test_row_ranges() {
         //            0 2    7  10   15    21   26    32     39 42
         let json = r#"{ "a": 1, "b": true, "c": null, "ddd": [] }"#.to_owned();
-        let (rows, _, _) = parse(json).unwrap();
+        let (rows, _, _) = parse(&json).unwrap();
 
         assert_eq!(rows[0].range, 0..43); // Object
         assert_eq!(rows[1].key_range, Some(2..5)); // "a": 1
@@ -470,7 +489,7 @@ mod tests {
 
         //            01   5        14     21 23
         let json = r#"[14, "apple", false, {}]"#.to_owned();
-        let (rows, _, _) = parse(json).unwrap();
+        let (rows, _, _) = parse(&json).unwrap();
 
         assert_eq!(rows[0].range, 0..24); // Array
         assert_eq!(rows[1].range, 1..3); // 14
@@ -481,7 +500,7 @@ mod tests {
 
         //            01 3      10     17    23  27   32   37 40    46   51
         let json = r#"[{ "abc": "str", "de": 14, "f": null }, true, false]"#.to_owned();
-        let (rows, _, _) = parse(json).unwrap();
+        let (rows, _, _) = parse(&json).unwrap();
 
         assert_eq!(rows[0].range, 0..52); // Array
         assert_eq!(rows[1].range, 1..38); // Object
@@ -496,4 +515,23 @@ mod tests {
         assert_eq!(rows[7].range, 46..51); // false
         assert_eq!(rows[8].range, 51..52); // ]
     }
+
+    #[test]
+    fn test_non_finite_numbers() {
+        let json = r#"{"a": NaN, "b": [Infinity, -Infinity]}"#;
+        assert!(parse(json).unwrap_err().contains("NaN isn't valid JSON"));
+
+        let (rows, pretty, _) = parse_allowing_non_finite(json).unwrap();
+        assert_eq!(r#"{ "a": NaN, "b": [Infinity, -Infinity] }"#, pretty);
+        for i in [1, 3, 4] {
+            assert!(matches!(
+                rows[i].value,
+                Value::Number(NumberType::NonFinite)
+            ));
+        }
+
+        // They're still not valid strings or keys.
+        assert!(parse_allowing_non_finite("[Nan]").is_err());
+        assert!(parse_allowing_non_finite("{NaN: 1}").is_err());
+    }
 }
@@ -1,6 +1,8 @@
 use std::fmt;
 use std::fmt::Write;
 
+use crate::truncatedstrview::is_control;
+
 pub struct UnescapeError {
     index: usize,
     codepoint_chars: [u8; 4],
@@ -34,8 +36,20 @@ enum DecodedCodepoint {
     HighSurrogate(u16),
 }
 
+// How to handle control characters when unescaping a string.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ControlCharacters {
+    // Leave control characters (other than \f, \n, \r and \t) escaped, so
+    // that the unescaped string is safe to print to a terminal.
+    Escaped,
+    // Unescape everything, including control characters. Unpaired
+    // surrogates, which can't be represented in UTF-8, are replaced with
+    // U+FFFD REPLACEMENT CHARACTER instead of causing an error.
+    Raw,
+}
+
 // Unescapes a syntactically valid JSON string into a valid UTF-8 string,
-// except for Unicode control characters.
+// except for Unicode control characters when using ControlCharacters::Escaped.
 //
 // This makes the assumption that the only characters following a '\' are:
 // - single character escapes: "\/bfnrt
@@ -50,7 +64,11 @@ enum DecodedCodepoint {
 // For more information, and a walkthrough of how to convert the surrogate pairs
 // back into an actual char, see:
 // https://en.wikipedia.org/wiki/UTF-16#Code_points_from_U+010000_to_U+10FFFF
-pub fn unescape_json_string(s: &str) -> Result<String, UnescapeError> {
+pub fn unescape_json_string(
+    s: &str,
+    control_characters: ControlCharacters,
+) -> Result<String, UnescapeError> {
+    let raw = control_characters == ControlCharacters::Raw;
     let mut chars = s.chars();
     let mut unescaped = String::with_capacity(s.len());
     let mut index = 1;
@@ -58,7 +76,7 @@ pub fn unescape_json_string(s: &str) -> Result<String, UnescapeError> {
     while let Some(ch) = chars.next() {
         index += 1;
         if ch != '\\' {
-            if is_control(ch) {
+            if is_control(ch) && !raw {
                 unescaped.push_str("\\u00");
                 write!(unescaped, "{:02X}", ch as u32).unwrap();
             } else {
@@ -75,6 +93,7 @@ pub fn unescape_json_string(s: &str) -> Result<String, UnescapeError> {
             '\\' => unescaped.push('\\'),
             '/' => unescaped.push('/'),
             // '\b' is backspace, a control character.
+            'b' if raw => unescaped.push('\x08'),
             'b' => unescaped.push_str("\\b"),
             'f' => unescaped.push('\x0c'),
             'n' => unescaped.push('\n'),
@@ -86,7 +105,7 @@ pub fn unescape_json_string(s: &str) -> Result<String, UnescapeError> {
 
                 match decode_codepoint(codepoint) {
                     DecodedCodepoint::Char(ch) => {
-                        if is_control(ch) {
+                        if is_control(ch) && !raw {
                             unescaped.push_str("\\u");
                             unescaped.push(codepoint_chars[0] as char);
                             unescaped.push(codepoint_chars[1] as char);
@@ -96,6 +115,7 @@ pub fn unescape_json_string(s: &str) -> Result<String, UnescapeError> {
                             unescaped.push(ch)
                         }
                     }
+                    DecodedCodepoint::LowSurrogate(_) if raw => unescaped.push('\u{FFFD}'),
                     DecodedCodepoint::LowSurrogate(_) => {
                         return Err(UnescapeError {
                             index: index - 6,
@@ -103,34 +123,40 @@ pub fn unescape_json_string(s: &str) -> Result<String, UnescapeError> {
                             error: UnicodeError::UnexpectedLowSurrogate,
                         });
                     }
-                    DecodedCodepoint::HighSurrogate(hs) => match (chars.next(), chars.next()) {
-                        (Some('\\'), Some('u')) => {
-                            index += 2;
-                            let (codepoint, _) = parse_codepoint_from_chars(&mut chars);
-                            index += 4;
-
-                            match decode_codepoint(codepoint) {
-                                DecodedCodepoint::LowSurrogate(ls) => {
-                                    let codepoint = (hs as u32) * 0x400 + (ls as u32) + 0x10000;
-                                    unescaped.push(char::from_u32(codepoint).unwrap());
-                                }
-                                _ => {
-                                    return Err(UnescapeError {
-                                        index,
-                                        codepoint_chars,
-                                        error: UnicodeError::UnmatchedHighSurrogate,
-                                    });
+                    DecodedCodepoint::HighSurrogate(hs) => {
+                        // Look ahead for the low surrogate without consuming
+                        // anything, so that if it's missing when unescaping
+                        // raw strings we don't drop the following chars.
+                        let mut lookahead = chars.clone();
+                        let (low_surrogate, lookahead_len) =
+                            match (lookahead.next(), lookahead.next()) {
+                                (Some('\\'), Some('u')) => {
+                                    let (codepoint, _) = parse_codepoint_from_chars(&mut lookahead);
+                                    match decode_codepoint(codepoint) {
+                                        DecodedCodepoint::LowSurrogate(ls) => (Some(ls), 6),
+                                        _ => (None, 6),
+                                    }
                                 }
+                                _ => (None, 0),
+                            };
+
+                        match low_surrogate {
+                            Some(ls) => {
+                                chars = lookahead;
+                                index += lookahead_len;
+                                let codepoint = (hs as u32) * 0x400 + (ls as u32) + 0x10000;
+                                unescaped.push(char::from_u32(codepoint).unwrap());
+                            }
+                            None if raw => unescaped.push('\u{FFFD}'),
+                            None => {
+                                return Err(UnescapeError {
+                                    index: index + lookahead_len,
+                                    codepoint_chars,
+                                    error: UnicodeError::UnmatchedHighSurrogate,
+                                });
                             }
                         }
-                        _ => {
-                            return Err(UnescapeError {
-                                index,
-                                codepoint_chars,
-                                error: UnicodeError::UnmatchedHighSurrogate,
-                            });
-                        }
-                    },
+                    }
                 }
             }
             _ => panic!("Unexpected escape character in JSON string: {}", ch),
@@ -140,8 +166,22 @@ pub fn unescape_json_string(s: &str) -> Result<String, UnescapeError> {
     Ok(unescaped)
 }
 
-fn is_control(ch: char) -> bool {
-    matches!(ch as u32, 0x00..=0x1F | 0x7F..=0x9F)
+// Escape a string so that it can be put between quotes in JSON, e.g.,
+// when converting YAML or Hjson strings, or creating new strings.
+pub fn escape_json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for ch in s.chars() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\x00'..='\x1f' => escaped.push_str(&format!("\\u{:04x}", ch as u32)),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
 }
 
 // Consumes four hex characters from a Chars iterator, and converts it to a u16.
@@ -194,7 +234,21 @@ mod tests {
 
     #[track_caller]
     fn check(escaped: &str, expected_unescaped: &str) {
-        let unescaped = match unescape_json_string(escaped) {
+        check_with_control_characters(escaped, ControlCharacters::Escaped, expected_unescaped);
+    }
+
+    #[track_caller]
+    fn check_raw(escaped: &str, expected_unescaped: &str) {
+        check_with_control_characters(escaped, ControlCharacters::Raw, expected_unescaped);
+    }
+
+    #[track_caller]
+    fn check_with_control_characters(
+        escaped: &str,
+        control_characters: ControlCharacters,
+        expected_unescaped: &str,
+    ) {
+        let unescaped = match unescape_json_string(escaped, control_characters) {
             Ok(s) => s,
             Err(err) => format!("ERR: {err}"),
         };
@@ -237,4 +291,31 @@ mod tests {
             "ERR: unescaping error at char 20: unexpected low surrogate \"\\uDC37\"",
         );
     }
+
+    #[test]
+    fn test_escape_json_string() {
+        assert_eq!("abc", escape_json_string("abc"));
+        assert_eq!("\\\"a\\\\b\\\"", escape_json_string("\"a\\b\""));
+        assert_eq!("\\n\\t\\u001b é", escape_json_string("\n\t\x1b é"));
+
+        let escaped = escape_json_string("a\n\u{0}\"");
+        assert_eq!(
+            Ok("a\n\u{0}\"".to_string()),
+            unescape_json_string(&escaped, ControlCharacters::Raw).map_err(|err| err.to_string())
+        );
+    }
+
+    #[test]
+    fn test_unescape_json_string_raw() {
+        check_raw("abc \\n \\\"", "abc \n \"");
+        check_raw("12x\\b34", "12x\x0834");
+        check_raw("\\u0000 | \\u001b | \\u009F", "\x00 | \x1b | \u{9f}");
+        check_raw("12 \u{0080} 34", "12 \u{0080} 34");
+        check_raw("𐐷 \\uD801\\uDC37", "𐐷 \u{10437}");
+
+        // Unpaired surrogates are replaced, without losing following chars.
+        check_raw("a \\uD801b", "a \u{FFFD}b");
+        check_raw("a \\uD801\\u0041", "a \u{FFFD}A");
+        check_raw("a \\uDC37 b", "a \u{FFFD} b");
+    }
 }
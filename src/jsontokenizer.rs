@@ -25,6 +25,9 @@ pub enum JsonToken {
     False,
     #[regex(r"-?(0|([1-9][0-9]*))(\.[0-9]+)?([eE][-+]?[0-9]+)?")]
     Number,
+    // Not valid JSON, but emitted by, e.g., Python's json.dumps.
+    #[regex("NaN|-?Infinity")]
+    NonFiniteNumber,
     // I get an error when I do [0-9a-fA-F]{4}.
     #[regex("\"((\\\\([\"\\\\/bfnrt]|u[0-9a-fA-F][0-9a-fA-F][0-9a-fA-F][0-9a-fA-F]))|[^\"\\\\\x00-\x1F])*\"")]
     String,
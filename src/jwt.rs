@@ -0,0 +1,142 @@
+use crate::flatjson::{ContainerType, Value};
+use crate::jsonparser;
+
+// Decode a JSON Web Token into a JSON object containing its decoded header
+// and payload, e.g.:
+//
+//   {"header": {"alg": "HS256"}, "payload": {"sub": "1"}, "signature": "..."}
+//
+// The signature is left as is, since it's just opaque bytes.
+pub fn decode_jwt(token: &str) -> Result<String, String> {
+    let token = token.strip_prefix("Bearer ").unwrap_or(token).trim();
+
+    let segments: Vec<&str> = token.split('.').collect();
+    match segments.len() {
+        3 => {}
+        5 => return Err("Encrypted JWTs (JWEs) can't be decoded".to_string()),
+        _ => return Err("String is not a JWT".to_string()),
+    }
+
+    let header = decode_json_segment(segments[0], "header")?;
+    let payload = decode_json_segment(segments[1], "payload")?;
+    let signature = segments[2];
+    if !signature.bytes().all(is_base64url_byte) {
+        return Err("JWT signature is not valid base64".to_string());
+    }
+
+    Ok(format!(
+        "{{\"header\": {header}, \"payload\": {payload}, \"signature\": \"{signature}\"}}"
+    ))
+}
+
+fn decode_json_segment(segment: &str, name: &str) -> Result<String, String> {
+    let bytes = decode_base64url(segment).map_err(|err| format!("JWT {name} {err}"))?;
+    let json = String::from_utf8(bytes).map_err(|_| format!("JWT {name} is not valid UTF-8"))?;
+
+    let is_object = match jsonparser::parse(&json) {
+        Ok((rows, _, _)) => {
+            rows[0].next_sibling.is_nil()
+                && matches!(
+                    rows[0].value,
+                    Value::EmptyObject
+                        | Value::OpenContainer {
+                            container_type: ContainerType::Object,
+                            ..
+                        }
+                )
+        }
+        Err(_) => false,
+    };
+
+    if is_object {
+        Ok(json)
+    } else {
+        Err(format!("JWT {name} is not a JSON object"))
+    }
+}
+
+fn is_base64url_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_'
+}
+
+// Decode base64url (RFC 4648 §5), which JWTs use, but also accept the
+// standard alphabet, and optional padding.
+fn decode_base64url(s: &str) -> Result<Vec<u8>, String> {
+    let s = s.trim_end_matches('=');
+    if s.len() % 4 == 1 {
+        return Err("is not valid base64".to_string());
+    }
+
+    let mut bytes = Vec::with_capacity(s.len() * 3 / 4);
+    let mut buffer: u32 = 0;
+    let mut num_bits = 0;
+
+    for byte in s.bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'-' | b'+' => 62,
+            b'_' | b'/' => 63,
+            _ => return Err("is not valid base64".to_string()),
+        };
+
+        buffer = (buffer << 6) | value as u32;
+        num_bits += 6;
+        if num_bits >= 8 {
+            num_bits -= 8;
+            bytes.push((buffer >> num_bits) as u8);
+            buffer &= (1 << num_bits) - 1;
+        }
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JWT: &str = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.\
+                       eyJzdWIiOiIxMjM0NTY3ODkwIiwibmFtZSI6IkpvaG4gRG9lIiwiaWF0IjoxNTE2MjM5MDIyfQ.\
+                       SflKxwRJSMeKKF2QT4fwpMeJf36POk6yJV_adQssw5c";
+
+    #[test]
+    fn test_decode_jwt() {
+        let expected = r#"{"header": {"alg":"HS256","typ":"JWT"}, "payload": {"sub":"1234567890","name":"John Doe","iat":1516239022}, "signature": "SflKxwRJSMeKKF2QT4fwpMeJf36POk6yJV_adQssw5c"}"#;
+        assert_eq!(Ok(expected.to_string()), decode_jwt(JWT));
+        assert_eq!(
+            Ok(expected.to_string()),
+            decode_jwt(&format!("Bearer {JWT}"))
+        );
+
+        assert_eq!(
+            Err("String is not a JWT".to_string()),
+            decode_jwt("hello world")
+        );
+        assert_eq!(
+            Err("JWT header is not valid base64".to_string()),
+            decode_jwt("a!b.c.d")
+        );
+        // "WzFd" is [1], which is JSON, but not an object.
+        assert_eq!(
+            Err("JWT payload is not a JSON object".to_string()),
+            decode_jwt("eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9.WzFd.sig")
+        );
+        assert_eq!(
+            Err("Encrypted JWTs (JWEs) can't be decoded".to_string()),
+            decode_jwt("a.b.c.d.e")
+        );
+    }
+
+    #[test]
+    fn test_decode_base64url() {
+        assert_eq!(Ok(b"".to_vec()), decode_base64url(""));
+        assert_eq!(Ok(b"f".to_vec()), decode_base64url("Zg"));
+        assert_eq!(Ok(b"fo".to_vec()), decode_base64url("Zm8="));
+        assert_eq!(Ok(b"foo".to_vec()), decode_base64url("Zm9v"));
+        assert_eq!(Ok(vec![0xfb, 0xff]), decode_base64url("-_8"));
+        assert_eq!(Ok(vec![0xfb, 0xff]), decode_base64url("+/8"));
+        assert!(decode_base64url("Zm9vY").is_err());
+    }
+}
@@ -0,0 +1,294 @@
+use std::path::Path;
+
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+
+use crate::flatjson::FlatJson;
+
+// Provides tab-completion of object keys at the search prompt. The
+// completions are drawn from every key that appears in the document,
+// which are collected once when the input is parsed (and again as more
+// newline-delimited JSON records are read).
+//
+// At the command prompt, the paths of files are completed instead, for
+// commands that take one, like :open, along with the names of filters
+// defined in the config file after `:filter @`.
+pub struct KeyCompleter {
+    // Sorted and deduplicated, so that all the keys starting with a
+    // given prefix are adjacent.
+    keys: Vec<String>,
+    // Names of the filters defined in the config file, in sorted order.
+    pub filter_names: Vec<String>,
+    // What's being completed at the prompt that's currently being read.
+    pub completion: Completion,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Completion {
+    Nothing,
+    // Object keys, when entering a search.
+    Keys,
+    // Paths of files, in the arguments of commands.
+    CommandPaths,
+}
+
+// Commands whose argument is the path of a file.
+const PATH_COMMANDS: [&str; 7] = ["open ", "open! ", "e ", "e! ", "w ", "w! ", "savepos "];
+
+impl KeyCompleter {
+    pub fn new(flatjson: &FlatJson) -> KeyCompleter {
+        let mut completer = KeyCompleter {
+            keys: vec![],
+            filter_names: vec![],
+            completion: Completion::Nothing,
+        };
+        completer.add_keys(flatjson, 0);
+        completer
+    }
+
+    // Add the keys in the rows starting at first_row.
+    pub fn add_keys(&mut self, flatjson: &FlatJson, first_row: usize) {
+        self.keys.extend(
+            flatjson.0[first_row..]
+                .iter()
+                .filter_map(|row| row.key_range.as_ref())
+                // key_range includes the quotes around the key.
+                .map(|range| flatjson.1[range.start + 1..range.end - 1].to_string())
+                .filter(|key| !key.is_empty()),
+        );
+        self.keys.sort_unstable();
+        self.keys.dedup();
+    }
+
+    fn keys_with_prefix(&self, prefix: &str) -> &[String] {
+        let start = self.keys.partition_point(|key| key.as_str() < prefix);
+        let len = self.keys[start..]
+            .iter()
+            .take_while(|key| key.starts_with(prefix))
+            .count();
+        &self.keys[start..start + len]
+    }
+}
+
+// Keys are completed starting after the last character in the search term
+// that couldn't appear in a typical identifier-like key, so that completing
+// "bar.foo" still works.
+fn is_key_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_' || ch == '-' || ch == '$'
+}
+
+// Complete the last component of the path being typed as the argument of
+// a command. Relative paths are relative to the directory jless was
+// started in. Directories are completed with a trailing slash, so that
+// their contents can be completed next.
+fn complete_command_path(line: &str, pos: usize) -> (usize, Vec<String>) {
+    let Some(command) = PATH_COMMANDS
+        .iter()
+        .find(|command| line.starts_with(*command) && pos >= command.len())
+    else {
+        return (pos, vec![]);
+    };
+
+    let partial = &line[command.len()..pos];
+    let (dir, prefix) = match partial.rfind('/') {
+        Some(i) => partial.split_at(i + 1),
+        None => ("", partial),
+    };
+    let start = command.len() + dir.len();
+
+    let dir = if dir.is_empty() { "." } else { dir };
+    let Ok(entries) = std::fs::read_dir(Path::new(dir)) else {
+        return (start, vec![]);
+    };
+
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name().into_string().ok()?;
+            // Hidden files are only completed once a '.' is typed.
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            // Follows symlinks, unlike DirEntry::file_type.
+            if entry.path().is_dir() {
+                Some(name + "/")
+            } else {
+                Some(name)
+            }
+        })
+        .collect();
+    names.sort_unstable();
+
+    (start, names)
+}
+
+// Complete the name of a filter being typed after `:filter @`.
+fn complete_filter_name(
+    line: &str,
+    pos: usize,
+    filter_names: &[String],
+) -> Option<(usize, Vec<String>)> {
+    let start = ["filter @", "filter! @"]
+        .iter()
+        .find(|command| line.starts_with(*command) && pos >= command.len())?
+        .len();
+    let prefix = &line[start..pos];
+    let names = filter_names
+        .iter()
+        .filter(|name| name.starts_with(prefix))
+        .cloned()
+        .collect();
+    Some((start, names))
+}
+
+impl Completer for KeyCompleter {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        match self.completion {
+            Completion::Nothing => return Ok((pos, vec![])),
+            Completion::CommandPaths => {
+                return Ok(complete_filter_name(line, pos, &self.filter_names)
+                    .unwrap_or_else(|| complete_command_path(line, pos)));
+            }
+            Completion::Keys => {}
+        }
+
+        // Skip the \V prefix that makes a search use a fixed string.
+        let term_start = if line.starts_with("\\V") { 2 } else { 0 };
+        let start = line[..pos]
+            .char_indices()
+            .rev()
+            .find(|(_, ch)| !is_key_char(*ch))
+            .map(|(i, ch)| i + ch.len_utf8())
+            .unwrap_or(0)
+            .max(term_start);
+
+        // Don't list every single key in the document.
+        if start == pos {
+            return Ok((pos, vec![]));
+        }
+
+        Ok((start, self.keys_with_prefix(&line[start..pos]).to_vec()))
+    }
+}
+
+impl Hinter for KeyCompleter {
+    type Hint = String;
+}
+
+impl Highlighter for KeyCompleter {}
+
+impl Validator for KeyCompleter {}
+
+impl Helper for KeyCompleter {}
+
+#[cfg(test)]
+mod tests {
+    use rustyline::history::History;
+
+    use super::*;
+    use crate::flatjson::parse_top_level_json;
+
+    #[test]
+    fn test_complete_keys() {
+        let fj = parse_top_level_json(
+            r#"{"id": 1, "items": [{"id": 2, "item_name": "a"}], "name": {"": null}}"#.to_owned(),
+        )
+        .unwrap();
+        let mut completer = KeyCompleter::new(&fj);
+        assert_eq!(vec!["id", "item_name", "items", "name"], completer.keys);
+
+        let history = History::new();
+        let ctx = Context::new(&history);
+        let complete = |completer: &KeyCompleter, line: &str| {
+            completer.complete(line, line.len(), &ctx).unwrap()
+        };
+
+        // Nothing is completed unless enabled.
+        assert_eq!((2, vec![]), complete(&completer, "it"));
+
+        completer.completion = Completion::Keys;
+        assert_eq!(
+            (0, vec!["item_name".to_string(), "items".to_string()]),
+            complete(&completer, "it"),
+        );
+        assert_eq!((2, vec!["name".to_string()]), complete(&completer, "\\Vna"));
+        assert_eq!((4, vec!["id".to_string()]), complete(&completer, "foo.id"));
+        assert_eq!((0, vec![]), complete(&completer, "xyz"));
+        assert_eq!((4, vec![]), complete(&completer, "foo "));
+    }
+
+    #[test]
+    fn test_complete_command_paths() {
+        let dir = std::env::temp_dir().join(format!("jless-test-{}-complete", std::process::id()));
+        std::fs::create_dir_all(dir.join("data")).unwrap();
+        for file in ["data.json", "data/a.json", "notes.yaml", ".hidden.json"] {
+            std::fs::write(dir.join(file), "{}").unwrap();
+        }
+        let dir_path = dir.to_str().unwrap();
+
+        let fj = parse_top_level_json("{}".to_owned()).unwrap();
+        let mut completer = KeyCompleter::new(&fj);
+        completer.completion = Completion::CommandPaths;
+
+        let history = History::new();
+        let ctx = Context::new(&history);
+        let complete = |line: String| completer.complete(&line, line.len(), &ctx).unwrap();
+
+        // Where the file name starts after the given command.
+        let start = |command: &str| command.len() + dir_path.len() + 1;
+        assert_eq!(
+            (
+                start("open "),
+                vec!["data.json".to_string(), "data/".to_string()]
+            ),
+            complete(format!("open {dir_path}/da")),
+        );
+        assert_eq!(
+            (start("e! ") + "data/".len(), vec!["a.json".to_string()]),
+            complete(format!("e! {dir_path}/data/")),
+        );
+        // Hidden files are only completed when asked for.
+        assert_eq!(
+            (
+                start("open "),
+                vec![
+                    "data.json".to_string(),
+                    "data/".to_string(),
+                    "notes.yaml".to_string()
+                ]
+            ),
+            complete(format!("open {dir_path}/")),
+        );
+        assert_eq!(
+            (start("w "), vec![".hidden.json".to_string()]),
+            complete(format!("w {dir_path}/.")),
+        );
+        // Names of filters are completed after @.
+        completer.filter_names = vec!["ids".to_string(), "images".to_string(), "x".to_string()];
+        let complete = |line: String| completer.complete(&line, line.len(), &ctx).unwrap();
+        assert_eq!(
+            (8, vec!["ids".to_string(), "images".to_string()]),
+            complete("filter @i".to_string())
+        );
+        assert_eq!(
+            (9, vec!["x".to_string()]),
+            complete("filter! @x".to_string())
+        );
+
+        // Only the arguments of commands that take a path are completed.
+        assert_eq!((10, vec![]), complete("filter ./d".to_string()));
+        assert_eq!((4, vec![]), complete("open".to_string()));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::HashMap;
+
+use termion::event::Key;
+
+// Keys can be remapped via --map (or `map` in the config file), e.g.,
+//
+//     --map 'J=<C-d>,K=<C-u>,h=l,l=h'
+//
+// Each mapping makes pressing the first key act like pressing the second
+// one. Like vim's `noremap`, the result isn't remapped again, so keys can
+// be swapped. Mappings only apply to the first key of a command, so they
+// don't affect prompts or the second key of commands like `yy`.
+//
+// Keys are either a single character, or a name in angle brackets:
+// <C-x> for Ctrl-x, <M-x> for Alt-x, <Up>, <Down>, <Left>, <Right>,
+// <Home>, <End>, <PageUp>, <PageDown>, <BS>, <Del>, <Tab>, <Enter> (or
+// <CR>), <Esc>, <Space>, <lt> (for '<'), and <F1> through <F12>.
+
+// The mappings from a single --map option.
+#[derive(Clone, Debug)]
+pub struct KeyMappings(pub Vec<(Key, Key)>);
+
+pub struct Keymap {
+    bindings: HashMap<Key, Key>,
+}
+
+impl Keymap {
+    // Later mappings override earlier ones, so that mappings given on the
+    // command line take precedence over those in the config file.
+    pub fn new(mappings: &[KeyMappings]) -> Keymap {
+        Keymap {
+            bindings: mappings
+                .iter()
+                .flat_map(|mappings| mappings.0.iter().copied())
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, key: Key) -> Key {
+        self.bindings.get(&key).copied().unwrap_or(key)
+    }
+}
+
+// Parse mappings like "J=<C-d>,K=<C-u>". Since ',' and '=' can be mapped
+// themselves, e.g., ",=;", keys are parsed one at a time rather than by
+// splitting on the separators.
+pub fn parse_key_mappings(mappings: &str) -> Result<KeyMappings, String> {
+    let mut rest = mappings.trim();
+    let mut parsed = vec![];
+
+    while !rest.is_empty() {
+        let (from, after_from) = parse_key(rest)?;
+        let Some(after_equals) = after_from.strip_prefix('=') else {
+            return Err(format!("Expected '=' after key in mapping: {mappings:?}"));
+        };
+        let (to, after_to) = parse_key(after_equals)?;
+        parsed.push((from, to));
+
+        rest = match after_to.strip_prefix(',') {
+            Some(next) => next.trim_start(),
+            None if after_to.trim().is_empty() => "",
+            None => return Err(format!("Expected ',' between mappings: {mappings:?}")),
+        };
+    }
+
+    Ok(KeyMappings(parsed))
+}
+
+// Parse a single key from the start of the input, returning the rest.
+fn parse_key(input: &str) -> Result<(Key, &str), String> {
+    let Some(ch) = input.chars().next() else {
+        return Err("Expected a key".to_string());
+    };
+
+    // Like in vim, a '<' that doesn't start a key name is just a '<', as in
+    // "<=<F2>", but catch typos in names.
+    if ch == '<' {
+        if let Some(end) = input.find('>') {
+            let name = &input[1..end];
+            if let Some(key) = parse_key_name(name) {
+                return Ok((key, &input[end + 1..]));
+            }
+            let looks_like_name = end > 1
+                && name
+                    .chars()
+                    .all(|ch| ch.is_ascii_alphanumeric() || ch == '-');
+            if looks_like_name {
+                return Err(format!("Unknown key: <{name}>"));
+            }
+        }
+    }
+
+    Ok((Key::Char(ch), &input[ch.len_utf8()..]))
+}
+
+fn parse_key_name(name: &str) -> Option<Key> {
+    let single_char = |s: &str| {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(ch), None) => Some(ch),
+            _ => None,
+        }
+    };
+
+    if let Some(ch) = name.strip_prefix("C-").and_then(single_char) {
+        return Some(Key::Ctrl(ch.to_ascii_lowercase()));
+    }
+    if let Some(ch) = name.strip_prefix("M-").and_then(single_char) {
+        return Some(Key::Alt(ch));
+    }
+    if let Some(n) = name.strip_prefix('F').and_then(|n| n.parse::<u8>().ok()) {
+        return (1..=12).contains(&n).then_some(Key::F(n));
+    }
+
+    let key = match name.to_ascii_lowercase().as_str() {
+        "up" => Key::Up,
+        "down" => Key::Down,
+        "left" => Key::Left,
+        "right" => Key::Right,
+        "home" => Key::Home,
+        "end" => Key::End,
+        "pageup" => Key::PageUp,
+        "pagedown" => Key::PageDown,
+        "bs" | "backspace" => Key::Backspace,
+        "del" => Key::Delete,
+        "tab" => Key::Char('\t'),
+        "enter" | "cr" => Key::Char('\n'),
+        "esc" => Key::Esc,
+        "space" => Key::Char(' '),
+        "lt" => Key::Char('<'),
+        _ => return None,
+    };
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key_mappings() {
+        let mappings = parse_key_mappings("J=<C-d>, K=<C-u>,h=l,l=h").unwrap();
+        assert_eq!(
+            vec![
+                (Key::Char('J'), Key::Ctrl('d')),
+                (Key::Char('K'), Key::Ctrl('u')),
+                (Key::Char('h'), Key::Char('l')),
+                (Key::Char('l'), Key::Char('h')),
+            ],
+            mappings.0
+        );
+
+        let mappings = parse_key_mappings(",=;,==<Space>,<lt>=<PageDown>,<=<F2>").unwrap();
+        assert_eq!(
+            vec![
+                (Key::Char(','), Key::Char(';')),
+                (Key::Char('='), Key::Char(' ')),
+                (Key::Char('<'), Key::PageDown),
+                (Key::Char('<'), Key::F(2)),
+            ],
+            mappings.0
+        );
+
+        assert!(parse_key_mappings("J").is_err());
+        assert!(parse_key_mappings("J=<C-d>K=<C-u>").is_err());
+        assert_eq!(
+            Err("Unknown key: <Foo>".to_string()),
+            parse_key_mappings("J=<Foo>").map(|m| m.0)
+        );
+    }
+
+    #[test]
+    fn test_keymap_does_not_remap_recursively() {
+        let keymap = Keymap::new(&[
+            parse_key_mappings("h=l,l=h,J=j").unwrap(),
+            parse_key_mappings("J=<C-d>").unwrap(),
+        ]);
+        assert_eq!(Key::Char('l'), keymap.get(Key::Char('h')));
+        assert_eq!(Key::Char('h'), keymap.get(Key::Char('l')));
+        assert_eq!(Key::Ctrl('d'), keymap.get(Key::Char('J')));
+        assert_eq!(Key::Char('k'), keymap.get(Key::Char('k')));
+    }
+}
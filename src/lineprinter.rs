@@ -4,15 +4,21 @@ use std::fmt::Write;
 use std::iter::Peekable;
 use std::ops::Range;
 
+use clap::ValueEnum;
 use regex::Regex;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-use crate::flatjson::{FlatJson, OptionIndex, Row, Value};
+use crate::dates::DateDisplay;
+use crate::diff::DiffMarker;
+use crate::flatjson;
+use crate::flatjson::{FlatJson, Index, NumberType, OptionIndex, Row, Value};
 use crate::highlighting;
+use crate::highlighting::Theme;
 use crate::search::MatchRangeIter;
 use crate::terminal;
 use crate::terminal::{Color, Style, Terminal};
-use crate::truncatedstrview::TruncatedStrView;
-use crate::viewer::Mode;
+use crate::truncatedstrview::{is_control, AnchoredStrView, TruncatedStrView};
+use crate::viewer::{Mode, SubFocus};
 
 // This module is responsible for printing single lines of JSON to
 // the screen, complete with syntax highlighting and highlighting
@@ -85,6 +91,7 @@ const FOCUSED_EXPANDED_CONTAINER: &str = "▼ ";
 const COLLAPSED_CONTAINER: &str = "▷ ";
 const EXPANDED_CONTAINER: &str = "▽ ";
 const INDICATOR_WIDTH: isize = 2;
+const INDENT_GUIDE: char = '│';
 const NO_FOCUSED_MATCH: Range<usize> = 0..0;
 
 lazy_static::lazy_static! {
@@ -128,6 +135,61 @@ impl DelimiterPair {
     }
 }
 
+// What the "absolute" line number represents: either the line the row
+// would appear on in the pretty printed input, or the index of the row
+// within its parent container.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NumberMode {
+    Line,
+    Index,
+}
+
+// The order entries are shown in in the previews of Objects and Arrays.
+// This only affects what's displayed, not the document itself.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PreviewOrder {
+    // The order they appear in the input.
+    Document,
+    // Object entries sorted by key; arrays stay in document order.
+    Key,
+    // Smallest values first, so that short fields like ids and names
+    // tend to fit in the preview before large nested values.
+    Size,
+}
+
+// The base that integers are additionally shown in, e.g., `255 (0xFF)`,
+// which is handy for flags and permissions. Copying a value still copies
+// it as it appears in the input.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum NumberBase {
+    // Just show integers as they are.
+    Decimal,
+    Hex,
+    Binary,
+}
+
+impl NumberBase {
+    // The note shown after an integer literal, or None if the literal
+    // isn't a plain decimal integer that fits in 128 bits.
+    pub fn note(&self, literal: &str) -> Option<String> {
+        if !literal.bytes().all(|b| b.is_ascii_digit() || b == b'-') {
+            return None;
+        }
+        let n: i128 = literal.parse().ok()?;
+        let sign = if n < 0 { "-" } else { "" };
+        let magnitude = n.unsigned_abs();
+        match self {
+            NumberBase::Decimal => None,
+            NumberBase::Hex => Some(format!(" ({sign}0x{magnitude:X})")),
+            NumberBase::Binary => Some(format!(" ({sign}0b{magnitude:b})")),
+        }
+    }
+}
+
+// Sorting the entries of huge containers for every preview would be too
+// slow, so they're always previewed in document order.
+const MAX_SORTED_PREVIEW_ENTRIES: usize = 10_000;
+
 // What line number should be displayed
 #[derive(Copy, Clone)]
 pub struct LineNumber {
@@ -136,28 +198,121 @@ pub struct LineNumber {
     pub max_width: isize,
 }
 
+// How values that don't fit on a line are truncated.
+#[derive(Copy, Clone)]
+pub struct TruncationSettings {
+    // Values in container previews are elided entirely, i.e., shown as
+    // just "…", unless at least this many characters of them are visible.
+    pub min_visible_chars: isize,
+    // Initially show the end of long strings instead of the start, which
+    // is usually the more interesting part of URLs and file paths.
+    pub prefer_string_ends: bool,
+}
+
+impl Default for TruncationSettings {
+    fn default() -> Self {
+        TruncationSettings {
+            min_visible_chars: 1,
+            prefer_string_ends: false,
+        }
+    }
+}
+
+// The output of a container preview that was already printed, which can
+// be written out again instead of walking through all of the container's
+// children on every frame, as long as it has the same space to fit into.
+pub struct CachedPreview {
+    available_space: isize,
+    always_quote_string_object_keys: bool,
+    reversed: bool,
+    output: String,
+    used_space: isize,
+}
+
 pub struct LinePrinter<'a, 'b> {
     pub mode: Mode,
+    // Whether to hide array indexes in data mode.
+    pub dense: bool,
     pub terminal: &'a mut dyn Terminal,
 
     // The entire FlatJson data structure and the specific line
     // we're printing out.
     pub flatjson: &'a FlatJson,
     pub row: &'a Row,
+    // If the row stands for a folded run of identical elements, how many
+    // there are.
+    pub folded_run_length: Option<usize>,
     pub line_number: LineNumber,
 
+    // Whether to print a gutter showing which lines changed since the
+    // input was last reloaded, and the marker for this line.
+    pub show_diff_gutter: bool,
+    pub diff_marker: Option<DiffMarker>,
+
+    // Whether to print a gutter showing which rows are selected for batch
+    // operations, and whether this row is one of them.
+    pub show_selection_gutter: bool,
+    pub selected: bool,
+
+    // Whether the row is an array whose elements are displayed in reverse
+    // order.
+    pub reversed: bool,
+
+    // Whether to print the contents of containers in their previews,
+    // or just their sizes.
+    pub show_previews: bool,
+    pub preview_order: PreviewOrder,
+
+    // Whether to highlight leading and trailing whitespace and invisible
+    // characters in strings.
+    pub show_invisibles: bool,
+
+    // Whether to show how many bytes of the input each container spans.
+    pub show_sizes: bool,
+
+    // Whether to note whether numbers are integers or floats.
+    pub show_number_types: bool,
+
+    // The base integers are additionally shown in.
+    pub number_base: NumberBase,
+
+    // How to show timestamps as dates, if they're being shown.
+    pub date_display: Option<&'a DateDisplay>,
+
+    // Whether to give the focused line a background color across the full
+    // width of the screen.
+    pub highlight_focused_line: bool,
+
+    // In line mode, the width of the widest key in the object containing
+    // this row, which shorter keys are padded to so that the colons of all
+    // the keys in the object line up.
+    pub aligned_key_width: Option<isize>,
+
+    // The colors of the guides drawn in the indentation for each level of
+    // nesting, used in turn by depth; empty when not drawing guides.
+    pub indent_guide_colors: &'a [Color],
+
+    pub theme: &'a Theme,
+
+    pub truncation: TruncationSettings,
+
     // Width of the terminal and how much we should indent the line.
     pub width: isize,
     pub indentation: isize,
 
     // Line-by-line formatting options
     pub focused: bool,
+    // Whether only the key or the value of the focused row is highlighted.
+    pub sub_focus: Option<SubFocus>,
     pub focused_because_matching_container_pair: bool,
     pub trailing_comma: bool,
 
     // For highlighting
     pub search_matches: Option<Peekable<MatchRangeIter<'b>>>,
     pub focused_search_match: &'a Range<usize>,
+    // The part of the focused search match to scroll into view when it's
+    // in a value that's too long to show entirely.
+    pub search_match_to_show: &'a Range<usize>,
 
     // It's unfortunate that this has to be exposed publicly; it's only
     // used internally to disable the special syntax highlighting for
@@ -165,15 +320,27 @@ pub struct LinePrinter<'a, 'b> {
     pub emphasize_focused_search_match: bool,
 
     // For remembering horizontal scroll positions of long lines.
-    pub cached_truncated_value: Option<Entry<'a, usize, TruncatedStrView>>,
+    pub cached_truncated_value: Option<Entry<'a, usize, AnchoredStrView>>,
+
+    // For reusing the preview of a collapsed container.
+    pub cached_preview: Option<Entry<'a, usize, CachedPreview>>,
 }
 
 impl<'a, 'b> LinePrinter<'a, 'b> {
     pub fn print_line(&mut self) -> fmt::Result {
+        if self.is_highlighted_line() {
+            self.terminal.set_line_bg(self.theme.focused_line)?;
+        }
         self.terminal.reset_style()?;
 
         let mut available_space = self.width;
 
+        let space_used_for_selection_gutter = self.print_selection_gutter(available_space)?;
+        available_space -= space_used_for_selection_gutter;
+
+        let space_used_for_diff_gutter = self.print_diff_gutter(available_space)?;
+        available_space -= space_used_for_diff_gutter;
+
         let space_used_for_line_number = self.print_line_number(available_space)?;
         available_space -= space_used_for_line_number;
 
@@ -188,21 +355,81 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             available_space -= space_used_for_label;
 
             if self.has_label() && space_used_for_label == 0 {
-                self.print_truncated_indicator()?;
+                self.print_truncated_indicator(available_space)?;
             } else {
                 let space_used_for_value = self.fill_in_value(available_space)?;
 
                 if space_used_for_value == 0 {
-                    self.print_truncated_indicator()?;
+                    self.print_truncated_indicator(available_space)?;
+                } else {
+                    self.fill_rest_of_highlighted_line(available_space - space_used_for_value)?;
                 }
             }
         } else {
-            self.print_truncated_indicator()?;
+            self.print_truncated_indicator(available_space - space_used_for_indicators)?;
+        }
+
+        if self.is_highlighted_line() {
+            self.terminal.set_line_bg(terminal::DEFAULT)?;
         }
 
         Ok(())
     }
 
+    fn is_highlighted_line(&self) -> bool {
+        self.highlight_focused_line && self.focused
+    }
+
+    // Pad out the focused line with spaces, so that the line background
+    // spans the full width of the screen.
+    fn fill_rest_of_highlighted_line(&mut self, space: isize) -> fmt::Result {
+        if self.is_highlighted_line() && space > 0 {
+            self.terminal.reset_style()?;
+            write!(self.terminal, "{:1$}", "", space as usize)?;
+        }
+        Ok(())
+    }
+
+    fn print_selection_gutter(&mut self, available_space: isize) -> Result<isize, fmt::Error> {
+        if !self.show_selection_gutter || available_space <= 1 {
+            return Ok(0);
+        }
+
+        if self.selected {
+            self.terminal.set_style(&self.theme.selected_row)?;
+            self.terminal.write_char('●')?;
+            self.terminal.reset_style()?;
+        } else {
+            self.terminal.write_char(' ')?;
+        }
+
+        Ok(1)
+    }
+
+    fn print_diff_gutter(&mut self, available_space: isize) -> Result<isize, fmt::Error> {
+        if !self.show_diff_gutter || available_space <= 1 {
+            return Ok(0);
+        }
+
+        match self.diff_marker {
+            Some(marker) => {
+                let style = match marker {
+                    DiffMarker::Added => &self.theme.diff_added,
+                    DiffMarker::Modified => &self.theme.diff_modified,
+                    DiffMarker::Reordered => &self.theme.diff_reordered,
+                    DiffMarker::ChildRemoved => &self.theme.diff_removed,
+                    DiffMarker::DescendantChanged => &highlighting::DIMMED_STYLE,
+                };
+                self.terminal.set_style(style)?;
+                self.terminal.write_char(marker.gutter_char())?;
+                self.terminal.reset_style()?;
+            }
+            None => self.terminal.write_char(' ')?,
+        }
+
+        Ok(1)
+    }
+
     // Absolute | Relative | Focused | Format
     // ---------+----------+---------+--------
     //     N    |     N    |    -    | Nothing
@@ -231,9 +458,9 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
                 (n, &highlighting::DIMMED_STYLE, true)
             }
             (Some(n), None, true) | (None, Some(n), true) => {
-                (n, &highlighting::CURRENT_LINE_NUMBER, true)
+                (n, &self.theme.current_line_number, true)
             }
-            (Some(n), Some(_), true) => (n, &highlighting::CURRENT_LINE_NUMBER, false),
+            (Some(n), Some(_), true) => (n, &self.theme.current_line_number, false),
         };
 
         self.terminal.set_style(style)?;
@@ -268,14 +495,14 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
 
                     let space_available_for_indentation = self.indentation.min(available_space - 1);
                     used_space += space_available_for_indentation;
-                    self.print_n_spaces(space_available_for_indentation)?;
+                    self.print_indentation(space_available_for_indentation)?;
                 }
             }
             Mode::Data => {
                 let space_available_for_indentation =
                     self.indentation.min(available_space - 1 - INDICATOR_WIDTH);
                 used_space += space_available_for_indentation;
-                self.print_n_spaces(space_available_for_indentation)?;
+                self.print_indentation(space_available_for_indentation)?;
 
                 if space_available_for_indentation == self.indentation {
                     if self.row.is_primitive() {
@@ -295,6 +522,38 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
         Ok(used_space)
     }
 
+    // Print up to n characters of the line's indentation, with a guide at
+    // the start of each level when indentation guides are enabled. Guides
+    // are colored by the depth of the container they're in, so they keep
+    // their color when the indentation is decreased with '<'.
+    fn print_indentation(&mut self, n: isize) -> fmt::Result {
+        if self.indent_guide_colors.is_empty() || self.indentation == 0 {
+            return self.print_n_spaces(n);
+        }
+
+        let tab_size = if self.dense && self.mode == Mode::Data {
+            1
+        } else {
+            2
+        };
+        let levels = self.indentation / tab_size;
+        let first_depth = self.row.depth - levels as usize;
+
+        for i in 0..n {
+            if i % tab_size == 0 {
+                let depth = first_depth + (i / tab_size) as usize;
+                let color = self.indent_guide_colors[depth % self.indent_guide_colors.len()];
+                self.terminal.set_fg(color)?;
+                write!(self.terminal, "{INDENT_GUIDE}")?;
+                self.terminal.set_fg(terminal::DEFAULT)?;
+            } else {
+                write!(self.terminal, " ")?;
+            }
+        }
+
+        Ok(())
+    }
+
     fn print_n_spaces(&mut self, n: isize) -> fmt::Result {
         for _ in 0..n {
             write!(self.terminal, " ")?;
@@ -323,6 +582,9 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             return Ok(0);
         }
 
+        // Don't let padding keys take up more than half of the line.
+        let max_aligned_key_width = available_space / 2;
+
         let mut index_label_buffer = String::new();
         let (label_ref, label_range, delimiter) =
             self.get_label_range_and_delimiter(&mut index_label_buffer, &self.flatjson.1);
@@ -331,6 +593,8 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
         let mut dummy_search_matches = None;
 
         let (style, highlighted_style) = self.get_label_styles();
+        let invisible_char_ranges =
+            self.invisible_char_ranges(label_ref, label_range.as_ref().map(|r| r.start + 1));
         let matches_iter = if self.row.key_range.is_some() {
             &mut self.search_matches
         } else {
@@ -373,17 +637,20 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
         // Print out start of label
         highlighting::highlight_matches(
             self.terminal,
+            self.theme,
             delimiter.left(),
             label_open_delimiter_range_start,
             style,
             highlighted_style,
             &mut matches,
             self.focused_search_match,
+            &[],
         )?;
 
         // Print out the label itself
         highlighting::highlight_truncated_str_view(
             self.terminal,
+            self.theme,
             label_ref,
             &truncated_view,
             label_range_start,
@@ -391,28 +658,48 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             highlighted_style,
             &mut matches,
             self.focused_search_match,
+            &invisible_char_ranges,
         )?;
 
         // Print out end of label
         highlighting::highlight_matches(
             self.terminal,
+            self.theme,
             delimiter.right(),
             label_close_delimiter_range_start,
             style,
             highlighted_style,
             &mut matches,
             self.focused_search_match,
+            &[],
         )?;
 
+        // Pad out short keys so that their colons line up, as long as the
+        // value still has room.
+        let label_width = delimiter.width() + space_used_for_label;
+        let padding = match self.aligned_key_width {
+            Some(aligned_width) => (aligned_width.min(max_aligned_key_width) - label_width)
+                .min(available_space - space_used_for_label)
+                .max(0),
+            None => 0,
+        };
+        if padding > 0 {
+            self.terminal.reset_style()?;
+            write!(self.terminal, "{:1$}", "", padding as usize)?;
+            used_space += padding;
+        }
+
         // Print out separator between label and value
         highlighting::highlight_matches(
             self.terminal,
+            self.theme,
             ": ",
             object_separator_range_start,
             &highlighting::DEFAULT_STYLE,
-            &highlighting::SEARCH_MATCH_HIGHLIGHTED,
+            &self.theme.search_match,
             &mut matches,
             self.focused_search_match,
+            &[],
         )?;
 
         used_space += delimiter.width();
@@ -422,9 +709,10 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
     }
 
     // Check if a line has a label. A line has a label if it has
-    // a key, or if we are in data mode and we have a parent.
+    // a key, or if we are in (non-dense) data mode and we have a parent.
     fn has_label(&self) -> bool {
-        self.row.key_range.is_some() || (self.mode == Mode::Data && self.row.parent.is_some())
+        self.row.key_range.is_some()
+            || (self.mode == Mode::Data && !self.dense && self.row.parent.is_some())
     }
 
     // Get the type of a label, either Key or Index.
@@ -451,7 +739,12 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
 
             let mut delimiter = DelimiterPair::None;
 
-            if key_open_delimiter == "[" {
+            // Scalar YAML keys are shown as they would be written in YAML,
+            // e.g., `1: ` or `null: `, which can't be confused with string
+            // keys, since those would be quoted.
+            if flatjson::yaml_scalar_key(&pretty_printed[key_range.clone()]).is_some() {
+                delimiter = DelimiterPair::None;
+            } else if key_open_delimiter == "[" {
                 delimiter = DelimiterPair::Square;
             } else if self.mode == Mode::Line || !JS_IDENTIFIER.is_match(key_without_delimiter) {
                 delimiter = DelimiterPair::Quote;
@@ -468,23 +761,19 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
         }
     }
 
-    fn get_label_styles(&self) -> (&'static Style, &'static Style) {
+    fn get_label_styles(&self) -> (&'a Style, &'a Style) {
+        let focused = self.focused && self.sub_focus != Some(SubFocus::Value);
+
         match self.label_type() {
             LabelType::Key => {
-                if self.focused {
-                    (
-                        &highlighting::INVERTED_BOLD_BLUE_STYLE,
-                        &highlighting::BOLD_INVERTED_STYLE,
-                    )
+                if focused {
+                    (&self.theme.focused_key, &self.theme.search_match)
                 } else {
-                    (
-                        &highlighting::BLUE_STYLE,
-                        &highlighting::SEARCH_MATCH_HIGHLIGHTED,
-                    )
+                    (&self.theme.key, &self.theme.search_match)
                 }
             }
             LabelType::Index => {
-                let style = if self.focused {
+                let style = if focused {
                     &highlighting::BOLD_INVERTED_STYLE
                 } else {
                     &highlighting::DIMMED_STYLE
@@ -505,7 +794,7 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
 
         let mut value_ref = &self.flatjson.1[self.row.range.clone()];
         let mut quoted = false;
-        let color = Self::color_for_value_type(&self.row.value);
+        let color = self.color_for_value_type(&self.row.value);
 
         // Strip quotes from strings.
         if self.row.is_string() {
@@ -523,6 +812,35 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             available_space -= 1;
         }
 
+        // Only note the length of a folded run, the type (or other base)
+        // of a number, or the date a timestamp stands for if there's room
+        // for the whole value.
+        let number_type = self.row.value.number_type();
+        let mut notes = String::new();
+        if let Some(run_length) = self.folded_run_length {
+            notes.push_str(&format!(" ×{run_length}"));
+        }
+        if let Some(number_type) = number_type.filter(|_| self.show_number_types) {
+            notes.push_str(&format!(" ({})", number_type.name()));
+        }
+        if number_type == Some(NumberType::Integer) {
+            notes.extend(self.number_base.note(value_ref));
+        }
+        if let Some(dates) = self.date_display {
+            let key = self
+                .row
+                .key_range
+                .as_ref()
+                .map(|range| &self.flatjson.1[range.start + 1..range.end - 1]);
+            notes.extend(dates.note(key, &self.flatjson.1[self.row.range.clone()]));
+        }
+        let value_notes = Some(notes)
+            .filter(|notes| !notes.is_empty())
+            .filter(|notes| value_ref.len() + notes.width() <= available_space.max(0) as usize);
+        if let Some(notes) = &value_notes {
+            available_space -= notes.width() as isize;
+        }
+
         let truncated_view = self.initialize_value_truncated_view_or_update_cached(available_space);
 
         let space_used_for_value = truncated_view.used_space();
@@ -539,8 +857,13 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
         }
 
         // Print out the value.
+        let value_focused = self.focused && self.sub_focus == Some(SubFocus::Value);
         let style = Style {
             fg: color,
+            inverted: value_focused,
+            bold: value_focused,
+            // Make values that aren't valid JSON stand out.
+            underline: number_type == Some(NumberType::NonFinite),
             ..Style::default()
         };
 
@@ -559,7 +882,7 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             value_ref,
             &truncated_view,
             Some(self.row.range.clone()),
-            (&style, &highlighting::SEARCH_MATCH_HIGHLIGHTED),
+            (&style, &self.theme.search_match),
         )?;
 
         if self.trailing_comma {
@@ -567,13 +890,16 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             self.highlight_str(
                 ",",
                 Some(self.row.range.end),
-                (
-                    &highlighting::DEFAULT_STYLE,
-                    &highlighting::SEARCH_MATCH_HIGHLIGHTED,
-                ),
+                (&highlighting::DEFAULT_STYLE, &self.theme.search_match),
             )?;
         }
 
+        if let Some(notes) = value_notes {
+            used_space += notes.width() as isize;
+            self.terminal.set_fg(terminal::LIGHT_BLACK)?;
+            write!(self.terminal, "{notes}")?;
+        }
+
         Ok(used_space)
     }
 
@@ -595,7 +921,8 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
     // on the search result.
     //
     // If we've already rendered a line, the available space for the
-    // line may have updated, so, we will resize the TruncatedStrView.
+    // line may have updated, so, we will fit the remembered scroll
+    // position into the space now available.
     fn initialize_value_truncated_view_or_update_cached(
         &mut self,
         available_space: isize,
@@ -615,17 +942,17 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
         self.cached_truncated_value
             .take()
             .map(|entry| {
-                *entry
-                    .and_modify(|tsv| {
-                        *tsv = tsv.resize(value_ref, available_space);
+                entry
+                    .and_modify(|cached| {
+                        cached.resize(value_ref, available_space);
                     })
                     .or_insert_with(|| {
-                        let tsv = TruncatedStrView::init_start(value_ref, available_space);
+                        let tsv = self.init_value_truncated_view(value_ref, available_space);
 
                         // If we're showing a line for the first time, we might
                         // need to focus on a search match that we just jumped to.
-                        let no_overlap = self.focused_search_match.end <= value_range.start
-                            || value_range.end <= self.focused_search_match.start;
+                        let no_overlap = self.search_match_to_show.end <= value_range.start
+                            || value_range.end <= self.search_match_to_show.start;
 
                         // NOTE: If the focused search match starts at the closing
                         // quote of a string, maybe we should use init_back so that
@@ -634,32 +961,45 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
                         // match.
 
                         if no_overlap {
-                            return tsv;
+                            return AnchoredStrView::new(tsv);
                         }
 
                         let offset_focused_range = Range {
                             start: self
-                                .focused_search_match
+                                .search_match_to_show
                                 .start
                                 .saturating_sub(value_range.start),
-                            end: (self.focused_search_match.end - value_range.start)
+                            end: (self.search_match_to_show.end - value_range.start)
                                 .min(value_ref.len()),
                         };
 
-                        tsv.focus(value_ref, &offset_focused_range)
+                        AnchoredStrView::new(tsv.focus(value_ref, &offset_focused_range))
                     })
+                    .view()
             })
-            .unwrap_or_else(|| TruncatedStrView::init_start(value_ref, available_space))
+            .unwrap_or_else(|| self.init_value_truncated_view(value_ref, available_space))
     }
 
-    fn color_for_value_type(value: &Value) -> Color {
+    fn init_value_truncated_view(
+        &self,
+        value_ref: &str,
+        available_space: isize,
+    ) -> TruncatedStrView {
+        if self.truncation.prefer_string_ends && self.row.is_string() {
+            TruncatedStrView::init_back(value_ref, available_space)
+        } else {
+            TruncatedStrView::init_start(value_ref, available_space)
+        }
+    }
+
+    fn color_for_value_type(&self, value: &Value) -> Color {
         debug_assert!(value.is_primitive());
 
         match value {
-            Value::Null => terminal::LIGHT_BLACK,
-            Value::Boolean => terminal::YELLOW,
-            Value::Number => terminal::MAGENTA,
-            Value::String => terminal::GREEN,
+            Value::Null => self.theme.null,
+            Value::Boolean => self.theme.boolean,
+            Value::Number(_) => self.theme.number,
+            Value::String => self.theme.string,
             Value::EmptyObject => terminal::WHITE,
             Value::EmptyArray => terminal::WHITE,
             _ => unreachable!(),
@@ -741,9 +1081,18 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             self.highlight_str(
                 row.value.container_type().unwrap().open_str(),
                 Some(self.row.range.start),
-                (style, &highlighting::SEARCH_MATCH_HIGHLIGHTED),
+                (style, &self.theme.search_match),
             )?;
 
+            // Expanded containers don't show their size, so we show any
+            // other notes about them on their own.
+            let notes = self.container_notes(row);
+            if !notes.is_empty() && available_space > notes.len() as isize + 3 {
+                self.terminal.set_fg(terminal::LIGHT_BLACK)?;
+                write!(self.terminal, " ({notes})")?;
+                return Ok(notes.len() as isize + 4);
+            }
+
             Ok(1)
         } else {
             Ok(0)
@@ -767,17 +1116,14 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             self.highlight_str(
                 row.value.container_type().unwrap().close_str(),
                 Some(self.row.range.start),
-                (style, &highlighting::SEARCH_MATCH_HIGHLIGHTED),
+                (style, &self.theme.search_match),
             )?;
 
             if self.trailing_comma {
                 self.highlight_str(
                     ",",
                     Some(self.row.range.end),
-                    (
-                        &highlighting::DEFAULT_STYLE,
-                        &highlighting::SEARCH_MATCH_HIGHLIGHTED,
-                    ),
+                    (&highlighting::DEFAULT_STYLE, &self.theme.search_match),
                 )?;
             }
 
@@ -797,13 +1143,21 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
         }
 
         let always_quote_string_object_keys = self.mode == Mode::Line;
-        let is_nested = false;
-        let mut used_space = self.generate_container_preview(
-            row,
-            available_space,
-            is_nested,
-            always_quote_string_object_keys,
-        )?;
+        let mut used_space = if self.can_use_cached_preview(row) {
+            self.print_cached_container_preview(
+                row,
+                available_space,
+                always_quote_string_object_keys,
+            )?
+        } else {
+            let is_nested = false;
+            self.generate_container_preview(
+                row,
+                available_space,
+                is_nested,
+                always_quote_string_object_keys,
+            )?
+        };
 
         if self.trailing_comma {
             used_space += 1;
@@ -811,10 +1165,7 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
                 self.highlight_str(
                     ",",
                     Some(self.row.range.end),
-                    (
-                        &highlighting::DEFAULT_STYLE,
-                        &highlighting::SEARCH_MATCH_HIGHLIGHTED,
-                    ),
+                    (&highlighting::DEFAULT_STYLE, &self.theme.search_match),
                 )?;
             }
         }
@@ -822,12 +1173,91 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
         Ok(used_space)
     }
 
-    fn size_of_container_and_num_digits_required(&self, row: &Row) -> (isize, isize) {
-        let container_size = {
-            let close_container = &self.flatjson[row.pair_index().unwrap()];
-            let last_child_index = close_container.last_child().unwrap();
-            (self.flatjson[last_child_index].index_in_parent as isize) + 1
+    // Previews only depend on the container and the settings they're
+    // printed with, unless they're on the focused line or contain a
+    // search match that needs to be highlighted.
+    fn can_use_cached_preview(&mut self, row: &Row) -> bool {
+        if self.cached_preview.is_none()
+            || self.focused
+            || self.focused_because_matching_container_pair
+        {
+            return false;
+        }
+
+        match &mut self.search_matches {
+            Some(matches) => !matches!(matches.peek(), Some(m) if m.start < row.range.end),
+            None => true,
+        }
+    }
+
+    // Write out the cached preview of the container if it was printed with
+    // the same amount of space, or else generate it and cache it. The style
+    // is reset before and after the preview, so that its output doesn't
+    // depend on anything printed before it.
+    fn print_cached_container_preview(
+        &mut self,
+        row: &Row,
+        available_space: isize,
+        always_quote_string_object_keys: bool,
+    ) -> Result<isize, fmt::Error> {
+        let entry = self.cached_preview.take().unwrap();
+        self.terminal.reset_style()?;
+
+        if let Entry::Occupied(cached) = &entry {
+            let cached = cached.get();
+            if cached.available_space == available_space
+                && cached.always_quote_string_object_keys == always_quote_string_object_keys
+                && cached.reversed == self.reversed
+            {
+                self.terminal.write_str(&cached.output)?;
+                return Ok(cached.used_space);
+            }
+        }
+
+        let output_start = self.terminal.output().len();
+        let is_nested = false;
+        let used_space = self.generate_container_preview(
+            row,
+            available_space,
+            is_nested,
+            always_quote_string_object_keys,
+        )?;
+        self.terminal.reset_style()?;
+
+        let preview = CachedPreview {
+            available_space,
+            always_quote_string_object_keys,
+            reversed: self.reversed,
+            output: self.terminal.output()[output_start..].to_string(),
+            used_space,
         };
+        match entry {
+            Entry::Occupied(mut entry) => {
+                entry.insert(preview);
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(preview);
+            }
+        }
+
+        Ok(used_space)
+    }
+
+    // Extra information about the container on this row shown alongside
+    // its size, e.g., "4.2 KB, reversed".
+    fn container_notes(&self, row: &Row) -> String {
+        let mut notes = vec![];
+        if self.show_sizes {
+            notes.push(format_size(row.range.len()));
+        }
+        if self.reversed {
+            notes.push("reversed".to_string());
+        }
+        notes.join(", ")
+    }
+
+    fn size_of_container_and_num_digits_required(&self, row: &Row) -> (isize, isize) {
+        let container_size = self.flatjson.num_children(row.pair_index().unwrap()) as isize;
 
         // We are assuming container_size is never 0.
         let space_needed_for_size = (isize::ilog10(container_size) as isize) + 1;
@@ -844,11 +1274,20 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
     ) -> Result<isize, fmt::Error> {
         debug_assert!(row.is_opening_of_container());
 
-        let (container_size, space_needed_for_container_size) =
+        let (container_size, mut space_needed_for_container_size) =
             self.size_of_container_and_num_digits_required(row);
+        let notes = if is_nested {
+            String::new()
+        } else {
+            self.container_notes(row)
+        };
+        if !notes.is_empty() {
+            space_needed_for_container_size += notes.len() as isize + 2;
+        }
 
         // Minimum amount of space required:
         // - top level: (123) […]
+        // - top level with notes: (123, 4.2 KB, reversed) […]
         // - nested: […]
         let mut min_space_needed = 3;
         if !is_nested {
@@ -863,7 +1302,11 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
 
         if !is_nested {
             self.terminal.set_fg(terminal::LIGHT_BLACK)?;
-            write!(self.terminal, "({container_size}) ")?;
+            if !notes.is_empty() {
+                write!(self.terminal, "({container_size}, {notes}) ")?;
+            } else {
+                write!(self.terminal, "({container_size}) ")?;
+            }
             available_space -= 3 + space_needed_for_container_size;
             num_printed += 3 + space_needed_for_container_size;
         }
@@ -874,6 +1317,33 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
         // Create a copy of self.search_matches
         let original_search_matches = self.search_matches.clone();
 
+        let flatjson = self.flatjson;
+        let to_option = |index: OptionIndex| match index {
+            OptionIndex::Index(index) => Some(index),
+            OptionIndex::Nil => None,
+        };
+        let mut children: Box<dyn Iterator<Item = Index>> = match self.sorted_children(row) {
+            Some(sorted_children) => {
+                // Search matches are highlighted by walking through them in
+                // document order, so they can't be highlighted out of order.
+                self.search_matches = None;
+                Box::new(sorted_children.into_iter())
+            }
+            None if !is_nested && self.reversed => {
+                // Likewise for the elements of reversed arrays.
+                self.search_matches = None;
+                let last_child = flatjson[row.pair_index().unwrap()].last_child();
+                Box::new(std::iter::successors(
+                    to_option(last_child),
+                    move |&child| to_option(flatjson[child].prev_sibling),
+                ))
+            }
+            None => Box::new(std::iter::successors(
+                to_option(row.first_child()),
+                move |&child| to_option(flatjson[child].next_sibling),
+            )),
+        };
+
         self.highlight_str(
             container_type.open_str(),
             Some(self.row.range.start),
@@ -882,15 +1352,23 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
 
         num_printed += 1;
 
-        let mut next_sibling = row.first_child();
         let mut is_first_child = true;
-        while let OptionIndex::Index(child) = next_sibling {
-            next_sibling = self.flatjson[child].next_sibling;
+
+        if !self.show_previews {
+            // Don't bother walking through the children at all.
+            self.highlight_str("…", None, highlighting::PREVIEW_STYLES)?;
+            num_printed += 1;
+            children = Box::new(std::iter::empty());
+        }
+
+        let mut children = children.peekable();
+        while let Some(child) = children.next() {
+            let has_more_children = children.peek().is_some();
 
             // If there are still more elements, we'll print out ", …" at the end,
-            let space_needed_at_end_of_container = if next_sibling.is_some() { 3 } else { 0 };
+            let space_needed_at_end_of_container = if has_more_children { 3 } else { 0 };
             let space_available_for_elem = available_space - space_needed_at_end_of_container;
-            let is_only_child = is_first_child && next_sibling.is_nil();
+            let is_only_child = is_first_child && !has_more_children;
 
             let used_space = self.fill_in_container_elem_preview(
                 &self.flatjson[child],
@@ -915,7 +1393,7 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
                 break;
             } else {
                 // Successfully printed elem out, let's print a separator.
-                if next_sibling.is_some() {
+                if has_more_children {
                     self.highlight_str(
                         ", ",
                         Some(self.flatjson[child].range.end),
@@ -944,6 +1422,38 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
         Ok(num_printed)
     }
 
+    // The children of a container in the order they should be previewed
+    // in, or None if they should be previewed in document order.
+    fn sorted_children(&self, row: &Row) -> Option<Vec<Index>> {
+        let sort_by_key = match self.preview_order {
+            PreviewOrder::Document => return None,
+            PreviewOrder::Key if row.is_array() => return None,
+            PreviewOrder::Key => true,
+            PreviewOrder::Size => false,
+        };
+
+        let mut children = vec![];
+        let mut next_child = row.first_child();
+        while let OptionIndex::Index(child) = next_child {
+            if children.len() == MAX_SORTED_PREVIEW_ENTRIES {
+                return None;
+            }
+            children.push(child);
+            next_child = self.flatjson[child].next_sibling;
+        }
+
+        let flatjson = self.flatjson;
+        if sort_by_key {
+            children.sort_by_key(|&child| match &flatjson[child].key_range {
+                Some(key_range) => &flatjson.1[key_range.clone()],
+                None => "",
+            });
+        } else {
+            children.sort_by_key(|&child| flatjson[child].range.len());
+        }
+        Some(children)
+    }
+
     // {a…: …, …}
     //
     // [a, …]
@@ -963,7 +1473,9 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             let key_open_delimiter = &self.flatjson.1[key_range.start..key_range.start + 1];
             let mut delimiter = DelimiterPair::None;
 
-            if key_open_delimiter == "[" {
+            if flatjson::yaml_scalar_key(&self.flatjson.1[key_range.clone()]).is_some() {
+                delimiter = DelimiterPair::None;
+            } else if key_open_delimiter == "[" {
                 delimiter = DelimiterPair::Square;
             } else if always_quote_string_object_keys || !JS_IDENTIFIER.is_match(key_ref) {
                 delimiter = DelimiterPair::Quote;
@@ -1054,15 +1566,29 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
 
         let space_used_for_quotes = if quoted { 2 } else { 0 };
 
-        let truncated_view = TruncatedStrView::init_start(value_ref, available_space);
+        let truncated_view = if self.truncation.prefer_string_ends && row.is_string() {
+            TruncatedStrView::init_back(value_ref, available_space)
+        } else {
+            TruncatedStrView::init_start(value_ref, available_space)
+        };
         let space_used_for_value = truncated_view.used_space();
 
         if space_used_for_value.is_none() || truncated_view.is_completely_elided() {
             return Ok(0);
         }
 
-        if !can_be_truncated && truncated_view.range.unwrap().is_truncated(value_ref) {
-            return Ok(0);
+        let truncated_range = truncated_view.range.unwrap();
+        if truncated_range.is_truncated(value_ref) {
+            if !can_be_truncated {
+                return Ok(0);
+            }
+
+            let num_ellipses = truncated_range.print_leading_ellipsis() as isize
+                + truncated_range.print_trailing_ellipsis(value_ref) as isize;
+            let visible_chars = space_used_for_value.unwrap() - num_ellipses;
+            if visible_chars < self.truncation.min_visible_chars {
+                return Ok(0);
+            }
         }
 
         let value_open_quote_range_start = row.range.start;
@@ -1084,8 +1610,15 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             &NO_FOCUSED_MATCH
         };
 
+        let invisible_char_ranges = if showing_collapsed_preview || !quoted {
+            vec![]
+        } else {
+            self.invisible_char_ranges(value_ref, Some(value_range_start))
+        };
+
         highlighting::highlight_truncated_str_view(
             self.terminal,
+            self.theme,
             value_ref,
             &truncated_view,
             // Technically could try to highlight open and close delimiters
@@ -1099,6 +1632,7 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             &highlighting::GRAY_INVERTED_STYLE,
             &mut self.search_matches.as_mut(),
             focused_search_match,
+            &invisible_char_ranges,
         )?;
 
         if quoted {
@@ -1112,7 +1646,8 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
         Ok(space_used_for_quotes + space_used_for_value.unwrap())
     }
 
-    fn print_truncated_indicator(&mut self) -> fmt::Result {
+    fn print_truncated_indicator(&mut self, available_space: isize) -> fmt::Result {
+        self.fill_rest_of_highlighted_line(available_space - 1)?;
         self.terminal.position_cursor_col(self.width as u16)?;
         if self.focused {
             self.terminal.reset_style()?;
@@ -1148,6 +1683,12 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
 
         self.highlight_str(delimiter.left(), str_open_delimiter_range_start, styles)?;
 
+        let invisible_char_ranges = if delimiter == DelimiterPair::Quote {
+            self.invisible_char_ranges(s, str_range_start)
+        } else {
+            vec![]
+        };
+
         let focused_search_match = if self.emphasize_focused_search_match {
             self.focused_search_match
         } else {
@@ -1156,6 +1697,7 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
 
         highlighting::highlight_truncated_str_view(
             self.terminal,
+            self.theme,
             s,
             truncated_view,
             str_range_start,
@@ -1163,6 +1705,7 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
             styles.1,
             &mut self.search_matches.as_mut(),
             focused_search_match,
+            &invisible_char_ranges,
         )?;
 
         self.highlight_str(delimiter.right(), str_close_delimiter_range_start, styles)?;
@@ -1185,20 +1728,145 @@ impl<'a, 'b> LinePrinter<'a, 'b> {
 
         highlighting::highlight_matches(
             self.terminal,
+            self.theme,
             s,
             str_range_start,
             styles.0,
             styles.1,
             &mut self.search_matches.as_mut(),
             focused_search_match,
+            &[],
         )
     }
+
+    // The ranges of invisible characters in a string, relative to the
+    // pretty printed JSON, that should be highlighted.
+    fn invisible_char_ranges(&self, s: &str, str_range_start: Option<usize>) -> Vec<Range<usize>> {
+        match str_range_start {
+            Some(start) if self.show_invisibles => find_invisible_chars(s)
+                .into_iter()
+                .map(|range| range.start + start..range.end + start)
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+// Format a number of bytes in a human readable way, e.g., "4.2 KB".
+pub fn format_size(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+// Characters that are (nearly) impossible to spot when printed, even in
+// the middle of a string. We intentionally don't include the zero width
+// joiner (U+200D), which appears in many emoji.
+fn is_invisible_char(ch: char) -> bool {
+    matches!(
+        ch,
+        '\t' | '\u{00A0}' | '\u{00AD}' | '\u{1680}' | '\u{180E}' | '\u{2000}'
+            ..='\u{200C}' | '\u{202F}' | '\u{205F}' | '\u{2060}' | '\u{3000}' | '\u{FEFF}'
+    ) || (ch.is_control() && ch != '\n')
+}
+
+// Find the byte ranges of the contents of a JSON string (i.e., with escapes
+// still present) that contain leading or trailing whitespace, or invisible
+// characters anywhere in the string.
+//
+// Escape sequences are treated as a single character, so in "a\u00a0" the
+// whole "\u00a0" is highlighted. Invisible characters that aren't escaped
+// don't take up any space when printed, so the range is extended to also
+// include the preceding (or following) character.
+pub fn find_invisible_chars(s: &str) -> Vec<Range<usize>> {
+    // Each character in the string, with its range, and whether it was
+    // escaped.
+    let mut chars: Vec<(Range<usize>, char, bool)> = vec![];
+
+    let mut char_indices = s.char_indices().peekable();
+    while let Some((start, ch)) = char_indices.next() {
+        if ch != '\\' {
+            chars.push((start..start + ch.len_utf8(), ch, false));
+            continue;
+        }
+
+        let Some((_, escaped)) = char_indices.next() else {
+            break;
+        };
+
+        let (end, decoded) = match escaped {
+            'u' => {
+                let hex = s.get(start + 2..start + 6).unwrap_or("");
+                for _ in 0..hex.len() {
+                    char_indices.next();
+                }
+                let decoded = u32::from_str_radix(hex, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .unwrap_or(char::REPLACEMENT_CHARACTER);
+                (start + 2 + hex.len(), decoded)
+            }
+            't' => (start + 2, '\t'),
+            'n' => (start + 2, '\n'),
+            'r' => (start + 2, '\r'),
+            'b' => (start + 2, '\u{0008}'),
+            'f' => (start + 2, '\u{000C}'),
+            other => (start + 1 + other.len_utf8(), other),
+        };
+        chars.push((start..end, decoded, true));
+    }
+
+    let is_blank = |ch: char| ch.is_whitespace() || is_invisible_char(ch);
+    let leading = chars.iter().take_while(|(_, ch, _)| is_blank(*ch)).count();
+    let trailing = chars[leading..]
+        .iter()
+        .rev()
+        .take_while(|(_, ch, _)| is_blank(*ch))
+        .count();
+
+    let mut ranges: Vec<Range<usize>> = vec![];
+
+    for (i, (range, ch, escaped)) in chars.iter().enumerate() {
+        let in_leading_or_trailing = i < leading || i >= chars.len() - trailing;
+        if !in_leading_or_trailing && !is_invisible_char(*ch) {
+            continue;
+        }
+
+        let mut range = range.clone();
+        let zero_width = !escaped && !is_control(*ch) && ch.width().unwrap_or(0) == 0;
+        if zero_width {
+            if i > 0 {
+                range.start = chars[i - 1].0.start;
+            } else if let Some((next, _, _)) = chars.get(1) {
+                range.end = next.end;
+            }
+        }
+
+        match ranges.last_mut() {
+            Some(last) if last.end >= range.start => last.end = last.end.max(range.end),
+            _ => ranges.push(range),
+        }
+    }
+
+    ranges
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
     use unicode_width::UnicodeWidthStr;
 
+    use crate::dates::DateZone;
     use crate::flatjson::{parse_top_level_json, parse_top_level_yaml};
     use crate::terminal::test::{TextOnlyTerminal, VisibleEscapesTerminal};
     use crate::terminal::{BLUE, LIGHT_BLUE};
@@ -1214,23 +1882,45 @@ mod tests {
     ) -> LinePrinter<'a, 'a> {
         LinePrinter {
             mode: Mode::Data,
+            dense: false,
             terminal,
             flatjson,
             row: &flatjson[index],
+            folded_run_length: None,
             line_number: LineNumber {
                 absolute: None,
                 relative: None,
                 max_width: 4,
             },
+            show_diff_gutter: false,
+            diff_marker: None,
+            show_selection_gutter: false,
+            selected: false,
+            reversed: false,
+            show_previews: true,
+            preview_order: PreviewOrder::Document,
+            show_invisibles: false,
+            show_sizes: false,
+            show_number_types: false,
+            number_base: NumberBase::Decimal,
+            date_display: None,
+            highlight_focused_line: false,
+            aligned_key_width: None,
+            indent_guide_colors: &[],
+            theme: &highlighting::DEFAULT_THEME,
+            truncation: TruncationSettings::default(),
             indentation: 0,
             width: 100,
             focused: false,
+            sub_focus: None,
             focused_because_matching_container_pair: false,
             trailing_comma: false,
             search_matches: None,
             focused_search_match: &DUMMY_RANGE,
+            search_match_to_show: &DUMMY_RANGE,
             emphasize_focused_search_match: true,
             cached_truncated_value: None,
+            cached_preview: None,
         }
     }
 
@@ -1308,35 +1998,98 @@ mod tests {
     }
 
     #[test]
-    fn test_print_line_tracks_available_space() -> std::fmt::Result {
-        const JSON: &str = r#"{
-            "hello": 1,
-            "key_2": {
-                "key_3": "value",
-                "key_4": "value2",
-            },
-        }"#;
-        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
-
+    fn test_print_line_highlight_focused_line() -> std::fmt::Result {
+        let fj = parse_top_level_json(r#"{"a": 1, "long_key": 2}"#.to_owned()).unwrap();
         let mut term = VisibleEscapesTerminal::new(true, false);
-        // ### __> key_2: (2) {key_3: "value", key_4: "value2"}
-        // 1234567890123456789012345678901234567890123456789012
-        let mut line: LinePrinter = default_line_printer(&mut term, &fj, 2);
+        let mut line: LinePrinter = default_line_printer(&mut term, &fj, 1);
         line.indentation = 2;
-        line.line_number.max_width = 3;
+        line.width = 12;
+        line.highlight_focused_line = true;
 
-        line.width = 48;
+        // Only the focused line is highlighted.
         line.print_line()?;
-        assert_eq!(
-            format!(r#"  {EXPANDED_CONTAINER}key_2: (2) {{key_3: "value", key_4: "value2"}}"#),
-            line.terminal.output(),
-        );
+        assert_eq!("    a: 1", line.terminal.output());
         line.terminal.clear_output();
 
-        line.width = 47;
+        line.focused = true;
         line.print_line()?;
-        assert_eq!(
-            format!(r#"  {EXPANDED_CONTAINER}key_2: (2) {{key_3: "value", key_4: "valu…"}}"#),
+        assert_eq!("  ▶ a: 1    ", line.terminal.output());
+        line.terminal.clear_output();
+
+        // The line is filled up to the truncation indicator.
+        line.row = &line.flatjson[2];
+        line.width = 7;
+        line.print_line()?;
+        assert_eq!("  ▶   _C(7)_>", line.terminal.output());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_print_line_indent_guides() -> std::fmt::Result {
+        let fj = parse_top_level_json(r#"{"a": {"b": [1]}}"#.to_owned()).unwrap();
+        let mut term = VisibleEscapesTerminal::new(false, true);
+        let mut line: LinePrinter = LinePrinter {
+            indent_guide_colors: &[terminal::RED, terminal::BLUE],
+            ..default_line_printer(&mut term, &fj, 3)
+        };
+        line.indentation = 6;
+
+        line.print_indentation(6)?;
+        assert_eq!(
+            "_FG(Red)_│_FG(Default)_ _FG(Blue)_│_FG(Default)_ _FG(Red)_│_FG(Default)_ ",
+            line.terminal.output()
+        );
+        line.terminal.clear_output();
+
+        // Truncated, and with the first level of indentation removed.
+        line.indentation = 4;
+        line.print_indentation(3)?;
+        assert_eq!(
+            "_FG(Blue)_│_FG(Default)_ _FG(Red)_│",
+            line.terminal.output()
+        );
+        line.terminal.clear_output();
+
+        // Dense mode only uses one column per level.
+        line.dense = true;
+        line.indentation = 3;
+        line.print_indentation(3)?;
+        assert_eq!("_FG(Red)_│_FG(Blue)_│_FG(Red)_│", line.terminal.output());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_print_line_tracks_available_space() -> std::fmt::Result {
+        const JSON: &str = r#"{
+            "hello": 1,
+            "key_2": {
+                "key_3": "value",
+                "key_4": "value2",
+            },
+        }"#;
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+
+        let mut term = VisibleEscapesTerminal::new(true, false);
+        // ### __> key_2: (2) {key_3: "value", key_4: "value2"}
+        // 1234567890123456789012345678901234567890123456789012
+        let mut line: LinePrinter = default_line_printer(&mut term, &fj, 2);
+        line.indentation = 2;
+        line.line_number.max_width = 3;
+
+        line.width = 48;
+        line.print_line()?;
+        assert_eq!(
+            format!(r#"  {EXPANDED_CONTAINER}key_2: (2) {{key_3: "value", key_4: "value2"}}"#),
+            line.terminal.output(),
+        );
+        line.terminal.clear_output();
+
+        line.width = 47;
+        line.print_line()?;
+        assert_eq!(
+            format!(r#"  {EXPANDED_CONTAINER}key_2: (2) {{key_3: "value", key_4: "valu…"}}"#),
             line.terminal.output(),
         );
         line.terminal.clear_output();
@@ -1455,6 +2208,34 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_fill_key_label_with_search_matches() -> std::fmt::Result {
+        let fj = parse_top_level_json(r#"{"hello": 1}"#.to_owned()).unwrap();
+        let key_start = fj[1].key_range.as_ref().unwrap().start;
+        // Both "l"s match, and the second one is the current match.
+        let matches = [key_start + 3..key_start + 4, key_start + 4..key_start + 5];
+
+        let mut term = VisibleEscapesTerminal::new(false, true);
+        let mut line: LinePrinter = LinePrinter {
+            focused: true,
+            search_matches: Some(matches.iter().peekable()),
+            focused_search_match: &matches[1],
+            ..default_line_printer(&mut term, &fj, 1)
+        };
+        line.fill_in_label(100)?;
+
+        // Other matches in the focused key aren't shown as the current one.
+        assert_eq!(
+            format!(
+                "_BG({BLUE})__INV__B_he_FG({yellow})__BG(Default)__!B_l_FG(Default)__B__U_l_BG({BLUE})__!U_o_BG(Default)__!INV__!B_: ",
+                yellow = terminal::YELLOW,
+            ),
+            line.terminal.output(),
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_fill_key_label_basic() -> std::fmt::Result {
         const JSON: &str = r#"{
@@ -1529,6 +2310,49 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_fill_aligned_key_label() -> std::fmt::Result {
+        const JSON: &str = r#"{
+            "a": 1,
+            "french fry": 2,
+        }"#;
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+
+        let mut term = TextOnlyTerminal::new();
+        let mut line: LinePrinter = LinePrinter {
+            mode: Mode::Line,
+            aligned_key_width: Some(12),
+            ..default_line_printer(&mut term, &fj, 1)
+        };
+
+        let used_space = line.fill_in_label(100)?;
+        assert_eq!("\"a\"         : ", line.terminal.output());
+        assert_eq!(14, used_space);
+
+        // The widest key isn't padded.
+        line.row = &line.flatjson[2];
+        line.terminal.clear_output();
+        let used_space = line.fill_in_label(100)?;
+        assert_eq!("\"french fry\": ", line.terminal.output());
+        assert_eq!(14, used_space);
+
+        // Keys are only padded to half of the available space, ...
+        line.row = &line.flatjson[1];
+        line.terminal.clear_output();
+        let used_space = line.fill_in_label(20)?;
+        assert_eq!("\"a\"       : ", line.terminal.output());
+        assert_eq!(12, used_space);
+
+        // ... and don't cause long keys to get truncated.
+        line.row = &line.flatjson[2];
+        line.terminal.clear_output();
+        let used_space = line.fill_in_label(10)?;
+        assert_eq!("\"fren…\": ", line.terminal.output());
+        assert_eq!(9, used_space);
+
+        Ok(())
+    }
+
     // Currently we incorrectly print quotes around all of these.
     #[test]
     fn test_fill_key_non_scalar_keys() -> std::fmt::Result {
@@ -1572,16 +2396,16 @@ mod tests {
         line.terminal.clear_output();
         let used_space = line.fill_in_label(100)?;
 
-        assert_eq!(r#"[3]: "#, line.terminal.output());
-        assert_eq!(5, used_space);
+        assert_eq!(r#"3: "#, line.terminal.output());
+        assert_eq!(3, used_space);
 
         line.row = &line.flatjson[4];
 
         line.terminal.clear_output();
         let used_space = line.fill_in_label(100)?;
 
-        assert_eq!(r#"[null]: "#, line.terminal.output());
-        assert_eq!(8, used_space);
+        assert_eq!(r#"null: "#, line.terminal.output());
+        assert_eq!(6, used_space);
 
         Ok(())
     }
@@ -1610,6 +2434,14 @@ mod tests {
         assert_eq!("_INV__B_[12345]_!INV__!B_: ", line.terminal.output());
         assert_eq!(9, used_space);
 
+        // Index labels are hidden in dense data mode.
+        line.dense = true;
+        line.terminal.clear_output();
+        let used_space = line.fill_in_label(100)?;
+
+        assert_eq!("", line.terminal.output());
+        assert_eq!(0, used_space);
+
         Ok(())
     }
 
@@ -1725,6 +2557,204 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_fill_value_sub_focus() -> std::fmt::Result {
+        let fj = parse_top_level_json(r#"{"a": 1}"#.to_owned()).unwrap();
+        let mut term = VisibleEscapesTerminal::new(false, true);
+        let mut line: LinePrinter = default_line_printer(&mut term, &fj, 1);
+        line.focused = true;
+        line.sub_focus = Some(SubFocus::Value);
+
+        line.fill_in_label(100)?;
+        assert_eq!("_FG(LightBlue)_a_FG(Default)_: ", line.terminal.output());
+
+        line.terminal.clear_output();
+        line.fill_in_value(100)?;
+        assert_eq!("_FG(Magenta)__INV__B_1", line.terminal.output());
+
+        line.sub_focus = Some(SubFocus::Key);
+        line.terminal.clear_output();
+        line.fill_in_label(100)?;
+        line.fill_in_value(100)?;
+        assert_eq!(
+            "_BG(Blue)__INV__B_a_BG(Default)__!INV__!B_: _FG(Magenta)_1",
+            line.terminal.output()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_invisible_chars() {
+        assert_eq!(Vec::<Range<usize>>::new(), find_invisible_chars("a b"));
+        assert_eq!(Vec::<Range<usize>>::new(), find_invisible_chars(""));
+        assert_eq!(vec![0..2, 3..5], find_invisible_chars("  a  "));
+        // Escapes are a single character.
+        assert_eq!(vec![0..2, 3..7], find_invisible_chars("\\ta\\n\\r"));
+        assert_eq!(vec![1..7], find_invisible_chars("a\\u00a0b"));
+        assert_eq!(vec![0..2], find_invisible_chars("\\t\\\\a"));
+        // Non-breaking space in the middle of a string.
+        assert_eq!(vec![1..3], find_invisible_chars("a\u{00A0}b"));
+        // Zero width characters include the preceding character, or the
+        // following one if at the start.
+        assert_eq!(vec![1..5], find_invisible_chars("ab\u{200B}c"));
+        assert_eq!(vec![0..4], find_invisible_chars("\u{FEFF}ab"));
+        // Regular whitespace in the middle is fine.
+        assert_eq!(Vec::<Range<usize>>::new(), find_invisible_chars("a b\\nc"));
+        // Zero width joiners aren't flagged.
+        assert_eq!(
+            Vec::<Range<usize>>::new(),
+            find_invisible_chars("\u{1F469}\u{200D}\u{1F4BB}")
+        );
+    }
+
+    #[test]
+    fn test_fill_value_show_invisibles() -> std::fmt::Result {
+        let fj = parse_top_level_json(r#"["a b ", "\ta"]"#.to_owned()).unwrap();
+        let mut term = VisibleEscapesTerminal::new(true, true);
+        let mut line: LinePrinter = default_line_printer(&mut term, &fj, 1);
+
+        line.fill_in_value(100)?;
+        assert_eq!("_FG(Green)_\"a b \"", line.terminal.output());
+
+        line.show_invisibles = true;
+        line.terminal.clear_output();
+        line.fill_in_value(100)?;
+        assert_eq!(
+            "_FG(Green)_\"a b_FG(Red)__INV_ _FG(Green)__!INV_\"",
+            line.terminal.output()
+        );
+
+        line.row = &line.flatjson[2];
+        line.terminal.clear_output();
+        line.fill_in_value(100)?;
+        assert_eq!(
+            "_FG(Green)_\"_FG(Red)__INV_\\t_FG(Green)__!INV_a\"",
+            line.terminal.output()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fill_value_show_number_types() -> std::fmt::Result {
+        let fj = parse_top_level_json("[12, 1.5]".to_owned()).unwrap();
+        let mut term = TextOnlyTerminal::new();
+        let mut line: LinePrinter = default_line_printer(&mut term, &fj, 1);
+        line.show_number_types = true;
+
+        let used_space = line.fill_in_value(100)?;
+        assert_eq!("12 (int)", line.terminal.output());
+        assert_eq!(8, used_space);
+
+        // The note is left out if there's not enough room for it.
+        line.terminal.clear_output();
+        let used_space = line.fill_in_value(7)?;
+        assert_eq!("12", line.terminal.output());
+        assert_eq!(2, used_space);
+
+        line.row = &line.flatjson[2];
+        line.terminal.clear_output();
+        line.fill_in_value(100)?;
+        assert_eq!("1.5 (float)", line.terminal.output());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fill_value_number_base() -> std::fmt::Result {
+        let fj = parse_top_level_json("[255, -10, 1.5, 1e3]".to_owned()).unwrap();
+        let mut term = TextOnlyTerminal::new();
+        let mut line: LinePrinter = default_line_printer(&mut term, &fj, 1);
+        line.number_base = NumberBase::Hex;
+
+        line.fill_in_value(100)?;
+        assert_eq!("255 (0xFF)", line.terminal.output());
+
+        line.number_base = NumberBase::Binary;
+        line.row = &line.flatjson[2];
+        line.terminal.clear_output();
+        line.fill_in_value(100)?;
+        assert_eq!("-10 (-0b1010)", line.terminal.output());
+
+        // Shown after the type of the number.
+        line.show_number_types = true;
+        line.terminal.clear_output();
+        line.fill_in_value(100)?;
+        assert_eq!("-10 (int) (-0b1010)", line.terminal.output());
+
+        // Floats, even whole ones, are left alone.
+        line.show_number_types = false;
+        for index in [3, 4] {
+            line.row = &line.flatjson[index];
+            line.terminal.clear_output();
+            line.fill_in_value(100)?;
+            assert!(!line.terminal.output().contains('('), "{:?}", index);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fill_value_non_finite() -> std::fmt::Result {
+        let (rows, pretty, depth) =
+            crate::jsonparser::parse_allowing_non_finite("[NaN, 1.5]").unwrap();
+        let fj = FlatJson::new(rows, pretty, depth);
+        let mut term = VisibleEscapesTerminal::new(false, true);
+        let mut line: LinePrinter = default_line_printer(&mut term, &fj, 1);
+
+        line.fill_in_value(100)?;
+        assert!(line.terminal.output().contains("_U_NaN"));
+
+        line.row = &line.flatjson[2];
+        line.terminal.clear_output();
+        line.fill_in_value(100)?;
+        assert!(!line.terminal.output().contains("_U_"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fill_value_dates() -> std::fmt::Result {
+        let fj = parse_top_level_json(
+            r#"{"created_at": 1680674828, "id": 1680674828, "updated_at": "2023-04-05T10:00:00Z"}"#
+                .to_owned(),
+        )
+        .unwrap();
+        let dates = DateDisplay {
+            format: "%F".to_owned(),
+            zone: DateZone::Utc,
+            keys: Regex::new("_at$").unwrap(),
+        };
+        let mut term = TextOnlyTerminal::new();
+        let mut line: LinePrinter = default_line_printer(&mut term, &fj, 1);
+        line.date_display = Some(&dates);
+
+        line.fill_in_value(100)?;
+        assert_eq!("1680674828 (2023-04-05)", line.terminal.output());
+
+        // Only values of the date keys are treated as timestamps.
+        line.row = &line.flatjson[2];
+        line.terminal.clear_output();
+        line.fill_in_value(100)?;
+        assert_eq!("1680674828", line.terminal.output());
+
+        line.row = &line.flatjson[3];
+        line.terminal.clear_output();
+        line.fill_in_value(100)?;
+        assert_eq!(
+            "\"2023-04-05T10:00:00Z\" (2023-04-05)",
+            line.terminal.output()
+        );
+
+        // Not noted if the whole value won't fit.
+        line.terminal.clear_output();
+        line.fill_in_value(30)?;
+        assert_eq!("\"2023-04-05T10:00:00Z\"", line.terminal.output());
+
+        Ok(())
+    }
+
     #[test]
     fn test_fill_value_not_enough_space() -> std::fmt::Result {
         let fj = parse_top_level_json(r#"["hello", "", true]"#.to_owned()).unwrap();
@@ -1906,6 +2936,107 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_generate_container_preview_notes() -> fmt::Result {
+        let json = r#"[1, [2, 3], "hello"]"#;
+        let fj = parse_top_level_json(json.to_owned()).unwrap();
+
+        let mut term = TextOnlyTerminal::new();
+        let mut line: LinePrinter = default_line_printer(&mut term, &fj, 0);
+        line.reversed = true;
+
+        for (available_space, used_space, expected) in vec![
+            (40, 31, r#"(3, reversed) ["hello", […], 1]"#),
+            (24, 24, r#"(3, reversed) ["he…", …]"#),
+            (17, 17, r#"(3, reversed) […]"#),
+            (16, 0, r#""#),
+        ]
+        .into_iter()
+        {
+            let is_nested = false;
+            let always_quote_string_object_keys = false;
+            let used = line.generate_container_preview(
+                &line.flatjson[0],
+                available_space,
+                is_nested,
+                always_quote_string_object_keys,
+            )?;
+            assert_eq!(expected, line.terminal.output());
+            assert_eq!(used_space, used);
+
+            line.terminal.clear_output();
+        }
+
+        line.show_sizes = true;
+        line.generate_container_preview(&line.flatjson[0], 40, false, false)?;
+        assert_eq!(
+            r#"(3, 20 B, reversed) ["hello", […], 1]"#,
+            line.terminal.output()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cached_container_preview() -> fmt::Result {
+        let fj = parse_top_level_json(r#"{"a": [1, 2], "b": "hello"}"#.to_owned()).unwrap();
+        let mut previews = HashMap::new();
+
+        let mut term = TextOnlyTerminal::new();
+        let mut line: LinePrinter = default_line_printer(&mut term, &fj, 0);
+        line.cached_preview = Some(previews.entry(0));
+        line.print_line()?;
+        let uncached = line.terminal.output().to_owned();
+        assert!(
+            uncached.contains(r#"(2) {a: […], b: "hello"}"#),
+            "{}",
+            uncached
+        );
+
+        // The same output is written from the cache.
+        let mut term = TextOnlyTerminal::new();
+        let mut line: LinePrinter = default_line_printer(&mut term, &fj, 0);
+        previews.get_mut(&0).unwrap().output = "cached".to_owned();
+        line.cached_preview = Some(previews.entry(0));
+        line.print_line()?;
+        assert!(line.terminal.output().contains("cached"));
+
+        // But not when there's a different amount of space.
+        let mut term = TextOnlyTerminal::new();
+        let mut line: LinePrinter = default_line_printer(&mut term, &fj, 0);
+        line.width = 20;
+        line.cached_preview = Some(previews.entry(0));
+        line.print_line()?;
+        assert!(
+            line.terminal.output().contains("(2) {a: […], b: …}"),
+            "{}",
+            line.terminal.output()
+        );
+        assert_eq!(20 - INDICATOR_WIDTH, previews[&0].available_space);
+
+        // Or when the row is focused.
+        let mut term = TextOnlyTerminal::new();
+        let mut line: LinePrinter = default_line_printer(&mut term, &fj, 0);
+        line.width = 20;
+        line.focused = true;
+        previews.get_mut(&0).unwrap().output = "cached".to_owned();
+        line.cached_preview = Some(previews.entry(0));
+        line.print_line()?;
+        assert!(!line.terminal.output().contains("cached"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!("0 B", format_size(0));
+        assert_eq!("1023 B", format_size(1023));
+        assert_eq!("1.0 KB", format_size(1024));
+        assert_eq!("4.2 KB", format_size(4300));
+        assert_eq!("32.0 MB", format_size(32 * 1024 * 1024));
+        assert_eq!("1.5 GB", format_size(3 * 512 * 1024 * 1024));
+    }
+
     #[test]
     fn test_generate_container_preview_single_container_child() -> fmt::Result {
         let json = r#"{"a": [1, {"x": true}, null, "hello", true]}"#;
@@ -1970,7 +3101,7 @@ mod tests {
         let mut term = TextOnlyTerminal::new();
         let mut line: LinePrinter = default_line_printer(&mut term, &fj, 0);
 
-        let expected = r#"{[true]: 1, [["t", "w", "o"]]: 2, [3]: 3, [null]: 4}"#;
+        let expected = r#"{true: 1, [["t", "w", "o"]]: 2, 3: 3, null: 4}"#;
 
         let _ = line.generate_container_preview(&line.flatjson[0], 100, true, true)?;
         assert_eq!(expected, line.terminal.output());
@@ -1981,4 +3112,91 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_generate_container_preview_without_previews() -> fmt::Result {
+        let json = r#"{"a": [1, {"x": true}, null, "hello", true]}"#;
+        let fj = parse_top_level_json(json.to_owned()).unwrap();
+
+        let mut term = TextOnlyTerminal::new();
+        let mut line: LinePrinter = default_line_printer(&mut term, &fj, 0);
+        line.show_previews = false;
+
+        let used = line.generate_container_preview(&line.flatjson[0], 38, false, false)?;
+        assert_eq!("(1) {…}", line.terminal.output());
+        assert_eq!(7, used);
+
+        line.terminal.clear_output();
+        let used = line.generate_container_preview(&line.flatjson[1], 38, false, false)?;
+        assert_eq!("(5) […]", line.terminal.output());
+        assert_eq!(7, used);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_container_preview_order() -> fmt::Result {
+        let json = r#"{"zeta": {"big": [1, 2]}, "name": "bob", "id": 7, "list": [3, 22, 1]}"#;
+        let fj = parse_top_level_json(json.to_owned()).unwrap();
+
+        let mut term = TextOnlyTerminal::new();
+        let mut line: LinePrinter = default_line_printer(&mut term, &fj, 0);
+
+        line.preview_order = PreviewOrder::Key;
+        line.generate_container_preview(&line.flatjson[0], 60, false, false)?;
+        assert_eq!(
+            r#"(4) {id: 7, list: […], name: "bob", zeta: {…}}"#,
+            line.terminal.output()
+        );
+
+        line.preview_order = PreviewOrder::Size;
+        line.terminal.clear_output();
+        line.generate_container_preview(&line.flatjson[0], 60, false, false)?;
+        assert_eq!(
+            r#"(4) {id: 7, name: "bob", list: […], zeta: {…}}"#,
+            line.terminal.output()
+        );
+
+        // The shortest values fit, even when the rest are elided.
+        line.terminal.clear_output();
+        line.generate_container_preview(&line.flatjson[0], 28, false, false)?;
+        assert_eq!(r#"(4) {id: 7, name: "bob", …}"#, line.terminal.output());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_container_preview_truncation_settings() -> fmt::Result {
+        let json = r#"{"id": 12345, "url": "https://example.com/a/b"}"#;
+        let fj = parse_top_level_json(json.to_owned()).unwrap();
+
+        let mut term = TextOnlyTerminal::new();
+        let mut line: LinePrinter = default_line_printer(&mut term, &fj, 0);
+
+        let preview = |line: &mut LinePrinter, available_space| -> Result<String, fmt::Error> {
+            line.terminal.clear_output();
+            line.generate_container_preview(&line.flatjson[0], available_space, false, false)?;
+            Ok(line.terminal.output().to_string())
+        };
+
+        assert_eq!(
+            r#"(2) {id: 12345, url: "https://ex…"}"#,
+            preview(&mut line, 35)?
+        );
+        assert_eq!(r#"(2) {id: 12…, …}"#, preview(&mut line, 16)?);
+
+        line.truncation.prefer_string_ends = true;
+        assert_eq!(
+            r#"(2) {id: 12345, url: "…le.com/a/b"}"#,
+            preview(&mut line, 35)?
+        );
+        // Only strings are truncated from the start.
+        assert_eq!(r#"(2) {id: 12…, …}"#, preview(&mut line, 16)?);
+
+        line.truncation.min_visible_chars = 3;
+        assert_eq!(r#"(2) {id: …, …}"#, preview(&mut line, 16)?);
+        assert_eq!(r#"(2) {id: 123…, …}"#, preview(&mut line, 17)?);
+
+        Ok(())
+    }
 }
@@ -7,10 +7,9 @@
 extern crate lazy_static;
 extern crate libc_stdhandle;
 
-use std::fs::File;
+use std::fs::{File, OpenOptions};
 use std::io;
-use std::io::Read;
-use std::path::PathBuf;
+use std::io::{BufRead, BufReader, Read, Write};
 
 use clap::Parser;
 use termion::cursor::HideCursor;
@@ -18,30 +17,100 @@ use termion::input::MouseTerminal;
 use termion::raw::IntoRawMode;
 use termion::screen::AlternateScreen;
 
+mod aliases;
 mod app;
+mod capabilities;
+mod chunks;
+mod config;
+mod control;
+mod dates;
+mod diff;
+mod document;
 mod flatjson;
+mod headless;
 mod highlighting;
+mod history;
+mod hjsonparser;
 mod input;
+mod jq;
 mod jsonparser;
 mod jsonstringunescaper;
 mod jsontokenizer;
+mod jwt;
+mod keycompleter;
+mod keymap;
 mod lineprinter;
+mod ndjson;
 mod options;
+mod outline;
+mod pager;
+mod position;
+mod resultbuffer;
+mod rng;
 mod screenwriter;
+mod script;
 mod search;
+mod sha256;
+mod smoothscroll;
+mod table;
 mod terminal;
+mod timeline;
 mod truncatedstrview;
+mod tutor;
 mod types;
+mod undo;
+mod unicodefold;
 mod viewer;
 mod yamlparser;
 
 use app::App;
+use capabilities::Capabilities;
+use highlighting::Background;
 use options::{DataFormat, Opt};
+use types::TTYDimensions;
+
+const MB: u64 = 1024 * 1024;
+// Very roughly how much memory jless uses per byte of input: a pretty
+// printed copy of the input, plus a Row for every value in it.
+const ESTIMATED_MEMORY_PER_INPUT_BYTE: u64 = 4;
+
+#[derive(Debug, PartialEq, Eq)]
+enum LargeInputChoice {
+    LoadAll,
+    // Show the first records of newline-delimited JSON right away, and
+    // read the rest in the background.
+    Stream,
+    LoadHead,
+    Quit,
+}
 
 fn main() {
-    let opt = Opt::parse();
+    let config_path = config::config_file_path();
+    let args = match config::args_with_config(config_path.as_deref()) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+    let mut opt = Opt::parse_from(args);
+
+    let capabilities = Capabilities::from_options(&opt);
+    if !capabilities.write_files {
+        document::disable_spilling();
+    }
+    if let Some(dir) = &opt.spill_dir {
+        document::set_spill_dir(dir.clone());
+    }
+    if opt.control.is_some() {
+        if let Err(err) = Capabilities::check(capabilities.control_socket, "--control") {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    }
 
-    let (input_string, input_filename) = match get_input_and_filename(&opt) {
+    let (input_string, input_filename, input_warning, records) = match get_input_and_filename(&opt)
+    {
         Ok(input_and_filename) => input_and_filename,
         Err(err) => {
             eprintln!("Unable to get input: {err}");
@@ -49,10 +118,54 @@ fn main() {
         }
     };
 
+    // The viewer shows the warning itself; otherwise there's nowhere else
+    // to report it.
+    let interactive = isatty::stdout_isatty() || opt.prints_on_exit();
+    if let Some(warning) = &input_warning {
+        if opt.gron || opt.dump_screen || !interactive {
+            eprintln!("{warning}");
+        }
+    }
+
     let data_format = determine_data_format(opt.data_format(), &input_filename);
+    if opt.follow && data_format != DataFormat::Ndjson {
+        eprintln!("--follow is only supported for newline-delimited JSON (--format ndjson)");
+        std::process::exit(1);
+    }
+
+    if opt.gron {
+        print_gron_input(input_string, data_format, &opt);
+        std::process::exit(0);
+    }
+
+    if opt.dump_screen {
+        let events = Box::new(std::iter::empty());
+        match headless::run_headless(&opt, input_string, data_format, input_filename, events) {
+            Ok(screen) => println!("{screen}"),
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+        std::process::exit(0);
+    }
+
+    // When picking, the picked value gets printed to the original STDOUT
+    // once jless exits, and the viewer is drawn on the terminal. The same
+    // goes for the focused path with --on-exit-print-path.
+    let mut exit_output = None;
+    if opt.prints_on_exit() {
+        match input::remap_dev_tty_to_stdout() {
+            Ok(original_stdout) => exit_output = Some(original_stdout),
+            Err(err) => {
+                eprintln!("Unable to open terminal: {err}");
+                std::process::exit(1);
+            }
+        }
+    }
 
     if !isatty::stdout_isatty() {
-        print_pretty_printed_input(input_string, data_format);
+        print_pretty_printed_input(input_string, data_format, &opt);
         std::process::exit(0);
     }
 
@@ -60,45 +173,221 @@ fn main() {
     // JSON input is provided via STDIN. rustyline gets initialized when we
     // create the App, so by putting this before creating the app, we make
     // sure rustyline gets the /dev/tty input.
-    input::remap_dev_tty_to_stdin();
+    if let Err(err) = input::remap_dev_tty_to_stdin() {
+        eprintln!("Unable to open terminal: {err}");
+        std::process::exit(1);
+    }
+
+    // Start listening before taking over the terminal, so that errors can
+    // be reported normally. The socket is removed when this is dropped.
+    // The same channel is used to report changes to the config file.
+    let (control_socket, control) = if opt.control.is_some() || config_path.is_some() {
+        let (sender, receiver) = match control::channel() {
+            Ok(channel) => channel,
+            Err(err) => {
+                eprintln!("Unable to create control channel: {err}");
+                std::process::exit(1);
+            }
+        };
+        if let Some(config_path) = &config_path {
+            match sender.try_clone() {
+                Ok(sender) => config::watch(config_path.clone(), sender),
+                Err(err) => eprintln!("Unable to watch config file: {err}"),
+            }
+        }
+        let socket = opt.control.as_ref().map(|path| {
+            control::listen(path, sender).unwrap_or_else(|err| {
+                eprintln!("{err}");
+                std::process::exit(1);
+            })
+        });
+        (socket, Some(receiver))
+    } else {
+        (None, None)
+    };
 
     let stdout = Box::new(MouseTerminal::from(HideCursor::from(
         AlternateScreen::from(io::stdout()),
     ))) as Box<dyn std::io::Write>;
-    let raw_stdout = stdout.into_raw_mode().unwrap();
+    let mut raw_stdout = stdout.into_raw_mode().unwrap();
 
+    if opt.background.is_none() && opt.theme.adapts_to_background() {
+        opt.background = detect_background(&mut raw_stdout);
+    }
+
+    let raw_stdout = Box::new(raw_stdout);
     let mut app = match App::new(&opt, input_string, data_format, input_filename, raw_stdout) {
         Ok(jl) => jl,
         Err(err) => {
-            eprintln!("{err}");
+            // The warning was already printed if the output isn't interactive.
+            let warning = input_warning.as_deref().filter(|_| interactive);
+            eprintln!("{}", startup_error(&err, warning));
             std::process::exit(1);
         }
     };
+    if let Some(warning) = input_warning {
+        app.set_warning_message(warning);
+    }
+    if records.is_some() {
+        app.start_loading_records();
+    }
+    if let Some(config_path) = config_path {
+        app.set_config_path(config_path);
+    }
+
+    let dimensions = TTYDimensions::from_size(termion::terminal_size().unwrap());
+    app.run(Box::new(input::get_input(control, records)), dimensions);
+
+    if let Some(destination) = opt.on_exit_path_destination() {
+        let path = app.focused_path();
+        // Restore the terminal before printing the path.
+        drop(app);
+        let result = if destination.as_os_str() == "-" {
+            match &mut exit_output {
+                Some(exit_output) => writeln!(exit_output, "{path}"),
+                None => writeln!(io::stdout(), "{path}"),
+            }
+            .map_err(|err| err.to_string())
+        } else {
+            Capabilities::check(capabilities.write_files, "--on-exit-print-path").and_then(|_| {
+                std::fs::write(&destination, format!("{path}\n")).map_err(|err| {
+                    format!("Unable to write path to {}: {err}", destination.display())
+                })
+            })
+        };
+        if let Err(err) = result {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(mut pick_output) = exit_output {
+        let picked = app.picked().map(str::to_string);
+        // Restore the terminal before printing what was picked.
+        drop(app);
+        // Exiting skips destructors, so remove the socket now.
+        drop(control_socket);
+        match picked {
+            Some(picked) => {
+                // Pretty printed values already end with a newline.
+                let newline = if picked.ends_with('\n') { "" } else { "\n" };
+                let _ = write!(pick_output, "{picked}{newline}");
+                std::process::exit(0);
+            }
+            None => std::process::exit(1),
+        }
+    }
+}
 
-    app.run(Box::new(input::get_input()));
+fn detect_background(out: &mut dyn Write) -> Option<Background> {
+    match input::query_background_color(out) {
+        Ok(Some(rgb)) => Some(Background::from_rgb(rgb)),
+        _ => std::env::var("COLORFGBG")
+            .ok()
+            .and_then(|colorfgbg| Background::from_colorfgbg(&colorfgbg)),
+    }
 }
 
-fn print_pretty_printed_input(input: String, data_format: DataFormat) {
+fn print_pretty_printed_input(input: String, data_format: DataFormat, opt: &Opt) {
     // Don't try to pretty print YAML input; just pass it through.
     if data_format == DataFormat::Yaml {
         print!("{input}");
         return;
     }
 
-    let flatjson = match flatjson::parse_top_level_json(input) {
-        Ok(flatjson) => flatjson,
-        Err(err) => {
-            eprintln!("Unable to parse input: {err:?}");
+    // Hjson is printed as the equivalent JSON.
+    let flatjson = match data_format {
+        DataFormat::Hjson => parse_hjson_for_printing(input),
+        _ => parse_json_for_printing(input, data_format, opt),
+    };
+    print!("{}", flatjson.pretty_printed().unwrap());
+}
+
+fn print_gron_input(input: String, data_format: DataFormat, opt: &Opt) {
+    let flatjson = match data_format {
+        DataFormat::Json | DataFormat::JsonSeq | DataFormat::Ndjson => {
+            parse_json_for_printing(input, data_format, opt)
+        }
+        DataFormat::Hjson => parse_hjson_for_printing(input),
+        DataFormat::Yaml => match flatjson::parse_top_level_yaml(input) {
+            Ok(flatjson) => flatjson,
+            Err(err) => {
+                eprintln!("Unable to parse input: {err:?}");
+                std::process::exit(1);
+            }
+        },
+    };
+
+    let mut stdout = io::BufWriter::new(io::stdout().lock());
+    let result = flatjson
+        .write_gron(&mut stdout)
+        .and_then(|_| stdout.flush());
+    // Don't complain if the output was piped to something like `head`.
+    if let Err(err) = result {
+        if err.kind() != io::ErrorKind::BrokenPipe {
+            eprintln!("Unable to print input: {err}");
             std::process::exit(1);
         }
+    }
+}
+
+fn parse_json_for_printing(
+    input: String,
+    data_format: DataFormat,
+    opt: &Opt,
+) -> flatjson::FlatJson {
+    let allow_nan = opt.allow_nan;
+    let result = match data_format {
+        DataFormat::JsonSeq => flatjson::parse_top_level_json_seq(input, allow_nan),
+        DataFormat::Ndjson => flatjson::parse_top_level_ndjson(input, allow_nan),
+        _ if opt.tolerant => flatjson::parse_top_level_json_tolerating_bad_lines(input, allow_nan),
+        _ => flatjson::parse_top_level_json_allowing_nan(input, allow_nan)
+            .map(|flatjson| (flatjson, vec![])),
     };
 
-    print!("{}", flatjson.pretty_printed().unwrap());
+    match result {
+        Ok((flatjson, line_errors)) => {
+            for line_error in line_errors {
+                eprintln!(
+                    "Skipping line {} that failed to parse: {}",
+                    line_error.line_number, line_error.error
+                );
+            }
+            flatjson
+        }
+        Err(err) => {
+            eprintln!("Unable to parse input: {err:?}");
+            std::process::exit(1);
+        }
+    }
 }
 
-fn get_input_and_filename(opt: &Opt) -> io::Result<(String, String)> {
-    let mut input_string = String::new();
+// The input, the name to show for it, a warning if only part of the input
+// could be read, and, for newline-delimited JSON, the records that are
+// still being read.
+type Input = (String, String, Option<String>, Option<ndjson::RecordStream>);
+
+fn get_input_and_filename(opt: &Opt) -> io::Result<Input> {
+    if opt.tutor {
+        return Ok((
+            tutor::TUTORIAL.to_string(),
+            tutor::TUTORIAL_FILENAME.to_string(),
+            None,
+            None,
+        ));
+    }
+
+    // Newline-delimited JSON is shown as soon as its first records have been
+    // read, while the rest of it is read in the background.
+    let interactive =
+        (isatty::stdout_isatty() || opt.prints_on_exit()) && !opt.gron && !opt.dump_screen;
+    let stream_records = |filename: &str| {
+        interactive && determine_data_format(opt.data_format(), filename) == DataFormat::Ndjson
+    };
+
     let filename;
+    let input;
 
     match &opt.input {
         None => {
@@ -107,20 +396,187 @@ fn get_input_and_filename(opt: &Opt) -> io::Result<(String, String)> {
                 std::process::exit(1);
             }
             filename = "STDIN".to_string();
-            io::stdin().read_to_string(&mut input_string)?;
+            if stream_records(&filename) {
+                return read_start_of_stdin(filename, opt.allow_nan);
+            }
+            input = read_input(io::stdin())?;
         }
         Some(path) => {
-            if *path == PathBuf::from("-") {
+            if path.as_os_str() == "-" {
                 filename = "STDIN".to_string();
-                io::stdin().read_to_string(&mut input_string)?;
+                if stream_records(&filename) {
+                    return read_start_of_stdin(filename, opt.allow_nan);
+                }
+                input = read_input(io::stdin())?;
             } else {
-                File::open(path)?.read_to_string(&mut input_string)?;
+                let file = File::open(path)?;
                 filename = String::from(path.file_name().unwrap().to_string_lossy());
+
+                // Only newline-delimited JSON can be streamed, or cut off
+                // after any line and still be parsed.
+                let is_ndjson =
+                    determine_data_format(opt.data_format(), &filename) == DataFormat::Ndjson;
+                let size = file.metadata()?.len();
+                let threshold = opt.large_input_threshold.saturating_mul(MB);
+                let mut stream = stream_records(&filename);
+                if threshold > 0 && size > threshold && interactive {
+                    let head_bytes = opt.large_input_head.saturating_mul(MB);
+                    match confirm_large_input(&filename, size, is_ndjson, head_bytes)? {
+                        LargeInputChoice::LoadAll => stream = false,
+                        LargeInputChoice::Stream => {}
+                        LargeInputChoice::LoadHead => {
+                            let head = read_head_of_input(file, head_bytes)?;
+                            let filename = format!(
+                                "{filename} (first {})",
+                                lineprinter::format_size(head_bytes as usize)
+                            );
+                            return Ok((head, filename, None, None));
+                        }
+                        LargeInputChoice::Quit => std::process::exit(0),
+                    }
+                }
+
+                if stream {
+                    let is_file = file.metadata()?.is_file();
+                    let (start, records) = ndjson::read_start_and_stream_rest(
+                        file,
+                        is_file,
+                        opt.follow,
+                        opt.allow_nan,
+                    )?;
+                    return Ok((start, filename, None, records));
+                }
+
+                // Files may also be pipes, e.g., when using process
+                // substitution: jless <(curl ...)
+                input = read_input(file)?;
+            }
+        }
+    }
+
+    let (input_string, warning) = input;
+    Ok((input_string, filename, warning, None))
+}
+
+// STDIN gets remapped to the terminal before the viewer starts, so the
+// rest of the input is read from a duplicate of it.
+fn read_start_of_stdin(filename: String, allow_nan: bool) -> io::Result<Input> {
+    let stdin = input::duplicate_stdin()?;
+    let (start, records) = ndjson::read_start_and_stream_rest(stdin, false, false, allow_nan)?;
+    Ok((start, filename, None, records))
+}
+
+// Read all of the input. When reading from a pipe, the writer may go away
+// before finishing, e.g., if a process substitution gets killed, which can
+// result in an EIO or unexpected EOF error. In that case, we treat the
+// error as the end of the input, so that whatever was read can still be
+// viewed, and return a warning saying that the input may be incomplete.
+fn read_input(mut input: impl Read) -> io::Result<(String, Option<String>)> {
+    let mut bytes = vec![];
+    let warning = match input.read_to_end(&mut bytes) {
+        Ok(_) => None,
+        Err(err) if is_premature_end_of_input(&err) => {
+            // The input may have been cut off in the middle of a character.
+            if let Err(utf8_err) = std::str::from_utf8(&bytes) {
+                if utf8_err.error_len().is_none() {
+                    bytes.truncate(utf8_err.valid_up_to());
+                }
             }
+            Some(format!(
+                "Input ended unexpectedly ({err}); only the first {} were read",
+                lineprinter::format_size(bytes.len())
+            ))
         }
+        Err(err) => return Err(err),
+    };
+
+    let input =
+        String::from_utf8(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    Ok((input, warning))
+}
+
+// The message printed when the input can't be shown at all. Input that
+// ended unexpectedly usually can't be parsed either, so the warning about
+// it comes first, since it's the real reason.
+fn startup_error(err: &str, input_warning: Option<&str>) -> String {
+    match input_warning {
+        Some(warning) => format!("{warning}\n{err}"),
+        None => err.to_string(),
+    }
+}
+
+fn is_premature_end_of_input(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::UnexpectedEof || err.raw_os_error() == Some(libc::EIO)
+}
+
+// Ask whether to really load a huge input file, since it may take a long
+// time, and use a lot of memory. We haven't entered raw mode yet, so we
+// can just read a line from the terminal.
+fn confirm_large_input(
+    filename: &str,
+    size: u64,
+    is_ndjson: bool,
+    head_bytes: u64,
+) -> io::Result<LargeInputChoice> {
+    let mut tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+    write!(
+        tty,
+        "{filename} is {}; loading it will use roughly {} of memory.\n{}",
+        lineprinter::format_size(size as usize),
+        lineprinter::format_size(size.saturating_mul(ESTIMATED_MEMORY_PER_INPUT_BYTE) as usize),
+        large_input_question(is_ndjson, head_bytes),
+    )?;
+    tty.flush()?;
+
+    let mut answer = String::new();
+    BufReader::new(tty).read_line(&mut answer)?;
+    Ok(parse_large_input_choice(&answer, is_ndjson))
+}
+
+fn large_input_question(is_ndjson: bool, head_bytes: u64) -> String {
+    if is_ndjson {
+        format!(
+            "[s]tream it, showing the first records right away, load [a]ll of it first, \
+             load only the [f]irst {} of lines, or [q]uit? ",
+            lineprinter::format_size(head_bytes as usize),
+        )
+    } else {
+        "Load [a]ll of it, or [q]uit? ".to_string()
+    }
+}
+
+// Streaming and loading only the first lines are only offered for
+// newline-delimited JSON; anything else quits.
+fn parse_large_input_choice(answer: &str, is_ndjson: bool) -> LargeInputChoice {
+    match answer.trim().to_lowercase().as_str() {
+        "a" | "all" => LargeInputChoice::LoadAll,
+        "s" | "stream" if is_ndjson => LargeInputChoice::Stream,
+        "f" | "first" if is_ndjson => LargeInputChoice::LoadHead,
+        _ => LargeInputChoice::Quit,
     }
+}
 
-    Ok((input_string, filename))
+// Read up to max_bytes of input, cut off after the last complete line, so
+// that newline-delimited JSON can still be parsed.
+fn read_head_of_input(input: impl Read, max_bytes: u64) -> io::Result<String> {
+    let mut bytes = vec![];
+    input.take(max_bytes).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 == max_bytes {
+        let end_of_last_line = bytes.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+        bytes.truncate(end_of_last_line);
+    }
+
+    String::from_utf8(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+fn parse_hjson_for_printing(input: String) -> flatjson::FlatJson {
+    match flatjson::parse_top_level_hjson(input) {
+        Ok(flatjson) => flatjson,
+        Err(err) => {
+            eprintln!("Unable to parse input: {err:?}");
+            std::process::exit(1);
+        }
+    }
 }
 
 fn determine_data_format(format: Option<DataFormat>, filename: &str) -> DataFormat {
@@ -130,7 +586,162 @@ fn determine_data_format(format: Option<DataFormat>, filename: &str) -> DataForm
             .and_then(std::ffi::OsStr::to_str)
         {
             Some("yml") | Some("yaml") => DataFormat::Yaml,
+            Some("hjson") => DataFormat::Hjson,
+            Some("ndjson") | Some("jsonl") => DataFormat::Ndjson,
             _ => DataFormat::Json,
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn test_large_input_helpers() {
+        assert_eq!(
+            LargeInputChoice::LoadAll,
+            parse_large_input_choice("a\n", true)
+        );
+        assert_eq!(
+            LargeInputChoice::Stream,
+            parse_large_input_choice("S\n", true)
+        );
+        assert_eq!(
+            LargeInputChoice::LoadHead,
+            parse_large_input_choice(" First\n", true)
+        );
+        assert_eq!(LargeInputChoice::Quit, parse_large_input_choice("\n", true));
+        assert_eq!(
+            LargeInputChoice::Quit,
+            parse_large_input_choice("yes\n", true)
+        );
+
+        // Other inputs can't be streamed or cut off.
+        assert_eq!(
+            LargeInputChoice::LoadAll,
+            parse_large_input_choice("all\n", false)
+        );
+        assert_eq!(
+            LargeInputChoice::Quit,
+            parse_large_input_choice("s\n", false)
+        );
+        assert_eq!(
+            LargeInputChoice::Quit,
+            parse_large_input_choice("f\n", false)
+        );
+        assert_eq!(
+            "Load [a]ll of it, or [q]uit? ",
+            large_input_question(false, 100 * MB)
+        );
+        assert!(large_input_question(true, 5 * MB).contains("[f]irst 5.0 MB of lines"));
+
+        let input = "{\"a\": 1}\n{\"b\": 2}\n{\"c\": 3}\n";
+        assert_eq!(
+            "{\"a\": 1}\n{\"b\": 2}\n",
+            read_head_of_input(input.as_bytes(), 25).unwrap()
+        );
+        // Inputs smaller than the limit are read entirely, even without a
+        // trailing newline.
+        assert_eq!("[1, 2]", read_head_of_input(&b"[1, 2]"[..], 25).unwrap());
+    }
+
+    #[test]
+    fn test_on_exit_print_path() {
+        let opt = Opt::parse_from(["jless", "--on-exit-print-path"]);
+        assert!(opt.prints_on_exit());
+        assert_eq!(Some(PathBuf::from("-")), opt.on_exit_print_path);
+
+        // Only printing to stdout takes it over.
+        let opt = Opt::parse_from(["jless", "--on-exit-print-path=path.txt"]);
+        assert!(!opt.prints_on_exit());
+        assert_eq!(
+            Some(PathBuf::from("path.txt")),
+            opt.on_exit_path_destination()
+        );
+
+        assert!(Opt::try_parse_from(["jless", "--on-exit-print-path", "--pick"]).is_err());
+    }
+
+    // A reader that returns some data, then fails, like a pipe whose writer
+    // was killed.
+    struct FailingReader {
+        data: &'static [u8],
+        error: fn() -> io::Error,
+    }
+
+    impl Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.data.is_empty() {
+                return Err((self.error)());
+            }
+            let n = self.data.len().min(buf.len()).min(4);
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_read_input() {
+        assert_eq!(
+            ("[1, 2]".to_string(), None),
+            read_input(&b"[1, 2]"[..]).unwrap()
+        );
+
+        let (input, warning) = read_input(FailingReader {
+            data: b"{\"a\": 1}\n{\"b\"",
+            error: || io::Error::from_raw_os_error(libc::EIO),
+        })
+        .unwrap();
+        assert_eq!("{\"a\": 1}\n{\"b\"", input);
+        assert!(
+            warning
+                .as_ref()
+                .unwrap()
+                .starts_with("Input ended unexpectedly"),
+            "{:?}",
+            warning
+        );
+
+        // A character cut off partway through is dropped.
+        let (input, warning) = read_input(FailingReader {
+            data: "\"中".as_bytes().split_at(3).0,
+            error: || io::Error::from(io::ErrorKind::UnexpectedEof),
+        })
+        .unwrap();
+        assert_eq!("\"", input);
+        assert!(warning.is_some());
+
+        // Other errors aren't treated as the end of the input.
+        assert!(read_input(FailingReader {
+            data: b"[1",
+            error: || io::Error::from(io::ErrorKind::PermissionDenied),
+        })
+        .is_err());
+        assert!(read_input(&b"\xff"[..]).is_err());
+    }
+
+    #[test]
+    fn test_truncated_input_that_does_not_parse() {
+        let (input, warning) = read_input(FailingReader {
+            data: b"{\"a\": [1, 2",
+            error: || io::Error::from_raw_os_error(libc::EIO),
+        })
+        .unwrap();
+        let err = flatjson::parse_top_level_json(input).unwrap_err();
+
+        let message = startup_error(&err, warning.as_deref());
+        let (first_line, rest) = message.split_once('\n').unwrap();
+        assert!(
+            first_line.starts_with("Input ended unexpectedly"),
+            "{:?}",
+            message
+        );
+        assert_eq!(err, rest);
+
+        assert_eq!(err, startup_error(&err, None));
+    }
+}
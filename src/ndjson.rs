@@ -0,0 +1,357 @@
+use std::io;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use crate::flatjson::{self, JsonParseFn, LineError, Row};
+
+// Newline-delimited JSON (NDJSON, or JSON Lines) is parsed one record, i.e.,
+// one line, at a time. That way huge log files can be shown as soon as their
+// first records have been read, while the rest of the input is read in the
+// background, and appended to the document as it arrives.
+
+// How much of the input to read before showing it.
+const INITIAL_BYTES: usize = 1024 * 1024;
+// How much of a file to read before sending the records parsed so far to
+// the viewer. Input from pipes is sent whenever no more is available.
+const BATCH_BYTES: usize = 16 * 1024 * 1024;
+const READ_BUFFER_BYTES: usize = 1024 * 1024;
+// How long to wait before checking whether more has been appended to a
+// file that's being followed.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(250);
+
+// Records parsed from consecutive lines of the input, as top-level values
+// that can be appended to a FlatJson.
+#[derive(Debug)]
+pub struct Records {
+    pub rows: Vec<Row>,
+    pub pretty: String,
+    pub depth: usize,
+    // Lines that failed to parse and were skipped.
+    pub errors: Vec<LineError>,
+    // Whether these are the last records in the input.
+    pub last: bool,
+    // Set if the rest of the input couldn't be read.
+    pub read_error: Option<String>,
+}
+
+impl Records {
+    fn new() -> Records {
+        Records {
+            rows: vec![],
+            pretty: String::new(),
+            depth: 0,
+            errors: vec![],
+            last: false,
+            read_error: None,
+        }
+    }
+
+    // Parse each line as a separate record, skipping those that fail to
+    // parse. Errors refer to lines counting from first_line_number.
+    pub fn parse(text: &str, first_line_number: usize, allow_non_finite: bool) -> Records {
+        let parse = flatjson::json_parser(allow_non_finite);
+        let mut records = Records::new();
+        for (i, line) in text.lines().enumerate() {
+            records.parse_line(line, first_line_number + i, parse);
+        }
+        records
+    }
+
+    fn parse_line(&mut self, line: &str, line_number: usize, parse: JsonParseFn) {
+        if line.trim().is_empty() {
+            return;
+        }
+
+        match parse(line) {
+            Ok((rows, pretty, depth)) => {
+                if !self.rows.is_empty() {
+                    self.pretty.push('\n');
+                }
+                flatjson::append_top_level_values(&mut self.rows, rows, self.pretty.len());
+                self.pretty.push_str(&pretty);
+                self.depth = self.depth.max(depth);
+            }
+            Err(error) => self.errors.push(LineError { line_number, error }),
+        }
+    }
+
+    fn parse_line_bytes(&mut self, line: &[u8], line_number: usize, parse: JsonParseFn) {
+        match std::str::from_utf8(line) {
+            Ok(line) => self.parse_line(line, line_number, parse),
+            Err(err) => self.errors.push(LineError {
+                line_number,
+                error: format!("Invalid UTF-8: {err}"),
+            }),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.rows.is_empty() && self.errors.is_empty()
+    }
+}
+
+// Records read from the rest of the input by a background thread. Each
+// batch is also signalled by writing a byte to a wake-up pipe, so that it
+// can be polled along with STDIN, like commands from the control socket.
+pub struct RecordStream {
+    records: Receiver<Records>,
+    wake_pipe: UnixStream,
+}
+
+impl RecordStream {
+    pub fn try_recv(&self) -> Result<Records, TryRecvError> {
+        self.records.try_recv()
+    }
+
+    pub fn clear_wake_pipe(&mut self) {
+        // Just make this big enough to absorb a bunch of batches at once.
+        let mut buf = [0; 32];
+        let _ = self.wake_pipe.read(&mut buf);
+    }
+}
+
+impl AsRawFd for RecordStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.wake_pipe.as_raw_fd()
+    }
+}
+
+// Read the first complete lines of the input that are available (up to
+// INITIAL_BYTES), and return them, along with a stream of the records in
+// the rest of the input, if there is any more. When following a file,
+// the stream keeps waiting for records to be appended to it, like
+// `tail -f`.
+pub fn read_start_and_stream_rest(
+    input: impl Read + Send + 'static,
+    is_file: bool,
+    follow: bool,
+    allow_non_finite: bool,
+) -> io::Result<(String, Option<RecordStream>)> {
+    let follow = follow && is_file;
+    let mut reader = BufReader::with_capacity(READ_BUFFER_BYTES, input);
+    let mut start = vec![];
+    let mut line_number = 1;
+    let mut has_record = false;
+
+    while !has_record || (start.len() < INITIAL_BYTES && !reader.buffer().is_empty()) {
+        let line_start = start.len();
+        if reader.read_until(b'\n', &mut start)? == 0 {
+            if follow {
+                break;
+            }
+            let start = String::from_utf8(start)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            return Ok((start, None));
+        }
+        has_record = has_record || start[line_start..].iter().any(|b| !b.is_ascii_whitespace());
+        line_number += 1;
+    }
+
+    // The last line of a followed file may still be being written, so
+    // it's read again along with the rest of it.
+    let mut partial_line = vec![];
+    if follow && !start.ends_with(b"\n") {
+        let line_start = start.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+        partial_line = start.split_off(line_start);
+    }
+
+    let start =
+        String::from_utf8(start).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    // Checking whether there's more input to read could block if it's a pipe.
+    if is_file && !follow && reader.fill_buf()?.is_empty() {
+        return Ok((start, None));
+    }
+    let parse = flatjson::json_parser(allow_non_finite);
+    let stream = stream_records(reader, partial_line, line_number, is_file, follow, parse)?;
+    Ok((start, Some(stream)))
+}
+
+fn stream_records(
+    mut reader: BufReader<impl Read + Send + 'static>,
+    mut line: Vec<u8>,
+    mut line_number: usize,
+    is_file: bool,
+    follow: bool,
+    parse: JsonParseFn,
+) -> io::Result<RecordStream> {
+    let (wake_pipe, mut wake_pipe_write) = UnixStream::pair()?;
+    let (sender, receiver) = channel();
+
+    thread::spawn(move || {
+        let mut records = Records::new();
+        let mut batch_bytes = 0;
+
+        loop {
+            let mut caught_up = false;
+            match reader.read_until(b'\n', &mut line) {
+                // Wait for more to be appended to the file.
+                Ok(0) if follow => caught_up = true,
+                Ok(0) => records.last = true,
+                // The rest of the line hasn't been written yet.
+                Ok(bytes_read) if follow && !line.ends_with(b"\n") => batch_bytes += bytes_read,
+                Ok(bytes_read) => {
+                    records.parse_line_bytes(&line, line_number, parse);
+                    line.clear();
+                    line_number += 1;
+                    batch_bytes += bytes_read;
+                }
+                Err(err) => {
+                    records.last = true;
+                    records.read_error =
+                        Some(format!("Unable to read the rest of the input: {err}"));
+                }
+            }
+
+            let batch_ready = records.last
+                || batch_bytes >= BATCH_BYTES
+                || ((!is_file || caught_up) && reader.buffer().is_empty() && !records.is_empty());
+            if batch_ready {
+                let last = records.last;
+                // The viewer has exited.
+                if sender
+                    .send(std::mem::replace(&mut records, Records::new()))
+                    .is_err()
+                {
+                    return;
+                }
+                let _ = wake_pipe_write.write_all(&[0]);
+                batch_bytes = 0;
+
+                if last {
+                    return;
+                }
+            }
+
+            if caught_up {
+                thread::sleep(FOLLOW_INTERVAL);
+            }
+        }
+    });
+
+    Ok(RecordStream {
+        records: receiver,
+        wake_pipe,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_records() {
+        let records = Records::parse("{\"a\": 1}\n\n[2, 3]\nnope\r\n\"four\"\r\n", 10, false);
+        assert_eq!("{ \"a\": 1 }\n[2, 3]\n\"four\"", records.pretty);
+        assert_eq!(8, records.rows.len());
+        assert_eq!(
+            vec![13],
+            records
+                .errors
+                .iter()
+                .map(|e| e.line_number)
+                .collect::<Vec<_>>()
+        );
+
+        // The records are siblings.
+        assert_eq!(
+            flatjson::OptionIndex::Index(3),
+            records.rows[0].next_sibling
+        );
+        assert_eq!(
+            flatjson::OptionIndex::Index(7),
+            records.rows[3].next_sibling
+        );
+        assert_eq!(
+            flatjson::OptionIndex::Index(3),
+            records.rows[7].prev_sibling
+        );
+        assert_eq!(2, records.rows[7].index_in_parent);
+        assert_eq!(12..13, records.rows[4].range);
+
+        // NaN and Infinity are only accepted when they're allowed.
+        const PYTHON: &str = "{\"a\": NaN}\n{\"a\": -Infinity}";
+        let records = Records::parse(PYTHON, 1, false);
+        assert_eq!(2, records.errors.len());
+        let records = Records::parse(PYTHON, 1, true);
+        assert!(records.errors.is_empty(), "{:?}", records.errors);
+        assert_eq!("{ \"a\": NaN }\n{ \"a\": -Infinity }", records.pretty);
+    }
+
+    #[test]
+    fn test_stream_records() {
+        let input: &'static [u8] = b"{\"a\": 1}\n{\"a\": 2}\nbad\n{\"a\": 3}";
+        let (start, stream) = read_start_and_stream_rest(input, true, false, false).unwrap();
+        // Everything fits in the initial read.
+        assert_eq!(std::str::from_utf8(input).unwrap(), start);
+        assert!(stream.is_none());
+
+        let reader = BufReader::new(&b"{\"a\": 2}\nbad\n{\"a\": 3}"[..]);
+        let stream = stream_records(
+            reader,
+            vec![],
+            2,
+            false,
+            false,
+            flatjson::json_parser(false),
+        )
+        .unwrap();
+        let mut all_records = vec![];
+        loop {
+            match stream.records.recv() {
+                Ok(records) if records.last => {
+                    all_records.push(records);
+                    break;
+                }
+                Ok(records) => all_records.push(records),
+                Err(err) => panic!("{:?}", err),
+            }
+        }
+
+        let pretty: Vec<&str> = all_records
+            .iter()
+            .map(|records| records.pretty.as_str())
+            .filter(|pretty| !pretty.is_empty())
+            .collect();
+        assert_eq!(vec!["{ \"a\": 2 }\n{ \"a\": 3 }"], pretty);
+        let errors: Vec<usize> = all_records
+            .iter()
+            .flat_map(|records| records.errors.iter().map(|e| e.line_number))
+            .collect();
+        assert_eq!(vec![3], errors);
+    }
+
+    #[test]
+    fn test_follow_records() {
+        use std::fs::{self, File, OpenOptions};
+        use std::time::Duration;
+
+        let path = std::env::temp_dir().join(format!("jless-test-{}.ndjson", std::process::id()));
+        // The last line is still being written.
+        fs::write(&path, "{\"a\": 1}\n{\"a\": 2").unwrap();
+
+        let file = File::open(&path).unwrap();
+        let (start, stream) = read_start_and_stream_rest(file, true, true, false).unwrap();
+        assert_eq!("{\"a\": 1}\n", start);
+        let stream = stream.unwrap();
+
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"}\n{\"a\": 3}\n").unwrap();
+
+        let mut pretty = vec![];
+        while pretty.len() < 2 {
+            let records = stream.records.recv_timeout(Duration::from_secs(5)).unwrap();
+            assert!(!records.last);
+            assert!(records.errors.is_empty(), "{:?}", records.errors);
+            if !records.pretty.is_empty() {
+                pretty.extend(records.pretty.lines().map(str::to_string));
+            }
+        }
+        assert_eq!(vec!["{ \"a\": 2 }", "{ \"a\": 3 }"], pretty);
+
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -1,24 +1,52 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use clap::{ArgAction, Parser, ValueEnum};
+use regex::Regex;
 
+use crate::aliases::parse_alias;
+use crate::dates::{parse_date_format, parse_date_keys, DateZone, DEFAULT_DATE_FORMAT};
+use crate::headless::parse_screen_size;
+use crate::highlighting::{
+    parse_color_list, parse_theme_colors, Background, ColorList, ThemeColors, ThemeName,
+};
+use crate::jq::parse_named_filter;
+use crate::keymap::{parse_key_mappings, KeyMappings};
+use crate::lineprinter::{NumberBase, PreviewOrder};
+use crate::types::TTYDimensions;
 use crate::viewer::Mode;
 
 #[derive(PartialEq, Eq, Copy, Clone, Debug, ValueEnum)]
 pub enum DataFormat {
     Json,
     Yaml,
+    /// JSON values separated by ASCII record separators (RFC 7464) or NULs
+    JsonSeq,
+    /// Newline-delimited JSON (JSON Lines), shown while it's still being
+    /// read
+    Ndjson,
+    /// Relaxed JSON for config files, with comments and optional quotes
+    /// and commas (https://hjson.github.io)
+    Hjson,
+}
+
+// What --pick prints when Enter is pressed.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, ValueEnum)]
+pub enum PickTarget {
+    Path,
+    Value,
 }
 
 /// A pager for JSON (or YAML) data
 #[derive(Debug, Parser)]
-#[command(name = "jless", version)]
+// Options can also be set in a config file (see config.rs), which are
+// overridden by the ones given on the command line.
+#[command(name = "jless", version, args_override_self = true)]
 pub struct Opt {
     /// Input file. jless will read from stdin if no input file is
     /// provided, or '-' is specified. If a filename is provided, jless
     /// will check the extension to determine what the input format is,
     /// and by default will assume JSON. Can specify input format
-    /// explicitly using --json or --yaml.
+    /// explicitly using --json, --yaml, or --format.
     pub input: Option<PathBuf>,
 
     /// Initial viewing mode. In line mode (--mode line), opening
@@ -71,6 +99,343 @@ pub struct Opt {
     #[arg(long = "scrolloff", default_value_t = 3)]
     pub scrolloff: u16,
 
+    /// Don't show previews of the contents of Objects and Arrays; just
+    /// show their sizes. Generating previews for every line can be slow
+    /// on very wide terminals when viewing huge containers.
+    #[arg(long = "no-previews", action = ArgAction::SetFalse)]
+    pub show_previews: bool,
+
+    /// The order to show entries in in previews of Objects and Arrays:
+    /// document (default), key (Object entries sorted by key), or size
+    /// (smallest values first, so short fields like ids and names are
+    /// more likely to fit). This only changes what's displayed.
+    #[arg(
+        long = "preview-order",
+        value_enum,
+        hide_possible_values = true,
+        default_value_t = PreviewOrder::Document
+    )]
+    pub preview_order: PreviewOrder,
+
+    /// Also show integers in another base: hex, e.g., `255 (0xFF)`, or
+    /// binary. Useful for flags and permissions; copying a value still
+    /// copies it as it appears in the input.
+    #[arg(
+        long = "number-base",
+        value_enum,
+        hide_possible_values = true,
+        default_value_t = NumberBase::Decimal
+    )]
+    pub number_base: NumberBase,
+
+    /// Show timestamps as dates, e.g., `1680674828 (2023-04-05 06:07:08
+    /// UTC)`, using strftime-style specifiers like %Y, %m, %d, %H, %M, %S,
+    /// %b and %Z. Defaults to "%Y-%m-%d %H:%M:%S %Z". Only values of keys
+    /// matching --date-keys are treated as timestamps.
+    #[arg(
+        long = "date-format",
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = DEFAULT_DATE_FORMAT,
+        value_parser = parse_date_format
+    )]
+    pub date_format: Option<String>,
+
+    /// The time zone to show dates in: local (default) or utc.
+    #[arg(
+        long = "date-timezone",
+        value_enum,
+        hide_possible_values = true,
+        default_value_t = DateZone::Local
+    )]
+    pub date_timezone: DateZone,
+
+    /// A regex matching the keys whose values are shown as dates. By
+    /// default, keys like `time`, `createdAt`, `updated_at`, `date` and
+    /// `timestamp`.
+    #[arg(long = "date-keys", value_name = "REGEX", value_parser = parse_date_keys)]
+    pub date_keys: Option<Regex>,
+
+    /// When a value in a preview of an Object or Array has to be
+    /// truncated, only show it if at least this many of its characters
+    /// are visible; otherwise elide it entirely.
+    #[arg(
+        long = "min-visible-chars",
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u16).range(1..)
+    )]
+    pub min_visible_chars: u16,
+
+    /// When a string is too long to fit on the screen, initially show
+    /// the end of it rather than the start. Useful for documents with
+    /// lots of long URLs or file paths.
+    #[arg(long = "prefer-string-ends")]
+    pub prefer_string_ends: bool,
+
+    /// The colors used to highlight the input: default, deuteranopia
+    /// (which doesn't rely on telling red and green apart), high-contrast,
+    /// solarized (for terminals using the Solarized palette), or
+    /// monochrome.
+    #[arg(long = "theme", value_enum, hide_possible_values = true, default_value_t = ThemeName::Default)]
+    pub theme: ThemeName,
+
+    /// Whether the terminal has a dark or light background, which the
+    /// default and solarized themes adapt to. If not specified, jless asks the terminal
+    /// for its background color, falling back to $COLORFGBG, and then to
+    /// dark.
+    #[arg(long = "background", value_enum, hide_possible_values = true)]
+    pub background: Option<Background>,
+
+    /// Override colors of the theme, e.g., key=blue,string=light-green.
+    /// The parts that can be set are key, string, number, boolean, null,
+    /// search-match, current-search-match (the match last jumped to), and
+    /// focused-line (the background with --cursorline).
+    #[arg(long = "theme-colors", value_name = "COLORS", value_parser = parse_theme_colors)]
+    pub theme_colors: Option<ThemeColors>,
+
+    /// Use a denser variant of data mode, which hides array indexes and
+    /// indents nested values by a single space.
+    #[arg(long = "dense")]
+    pub dense: bool,
+
+    /// Fold runs of identical values in Arrays (e.g., 500 zeros in a row)
+    /// into a single row, shown as `0 ×500`. Press Space on one to unfold
+    /// it.
+    #[arg(long = "fold-runs")]
+    pub fold_runs: bool,
+
+    /// Highlight the focused line across the full width of the screen,
+    /// which makes it easier to follow on large monitors.
+    #[arg(long = "cursorline")]
+    pub cursorline: bool,
+
+    /// Preview the first match of a search while it's being typed, as
+    /// vim does with 'incsearch'. Enter runs the search, and Escape
+    /// returns to where the search started.
+    #[arg(long = "incsearch")]
+    pub incsearch: bool,
+
+    /// Only match whole words in every search, as if /w were appended.
+    #[arg(long = "whole-word")]
+    pub whole_word: bool,
+
+    /// Search for fixed strings rather than regular expressions in every
+    /// search, as if it started with \V.
+    #[arg(long = "fixed-strings")]
+    pub fixed_strings: bool,
+
+    /// Scroll smoothly: each notch of the mouse wheel scrolls a single
+    /// line, speeding up as the wheel keeps turning, and scrolling by
+    /// pages is shown in two steps.
+    #[arg(long = "smooth-scroll")]
+    pub smooth_scroll: bool,
+
+    /// With --smooth-scroll, how many milliseconds the first step of
+    /// scrolling by a page is shown for.
+    #[arg(long = "scroll-frame-delay", value_name = "MS", default_value_t = 15)]
+    pub scroll_frame_delay: u64,
+
+    /// How many lines each notch of the mouse wheel scrolls, or, with
+    /// --smooth-scroll, the most it scrolls once it has sped up.
+    #[arg(
+        long = "wheel-lines",
+        value_name = "N",
+        default_value_t = 3,
+        value_parser = clap::value_parser!(u16).range(1..)
+    )]
+    pub wheel_lines: u16,
+
+    /// In line mode, pad short Object keys so that the colons of all the
+    /// keys in an Object line up.
+    #[arg(long = "align-keys")]
+    pub align_keys: bool,
+
+    /// Draw a guide in the indentation for each level of nesting, in a
+    /// different color for each level, which makes it easier to see which
+    /// closing brace matches which opening brace.
+    #[arg(long = "indent-guides")]
+    pub indent_guides: bool,
+
+    /// The colors to use for indentation guides, one for each level of
+    /// nesting in turn, e.g., red,yellow,light-blue. Colors can also be
+    /// given by their number (0-15) in the terminal's 16 color palette.
+    /// Defaults to colors from the theme.
+    #[arg(long = "indent-guide-colors", value_name = "COLORS", value_parser = parse_color_list)]
+    pub indent_guide_colors: Option<ColorList>,
+
+    /// Collapse the siblings of an Object or Array whenever it is
+    /// expanded, so that only one branch of the input is expanded at a
+    /// time.
+    #[arg(long = "accordion")]
+    pub accordion: bool,
+
+    /// Start with everything collapsed except the chain of Objects and
+    /// Arrays that only have a single child (e.g., `data.result.response`),
+    /// and focus the first value that has more than one, so deeply wrapped
+    /// payloads open straight to their contents.
+    #[arg(long = "expand-spine")]
+    pub expand_spine: bool,
+
+    /// Open the input focused on the same node, and with the same Objects
+    /// and Arrays collapsed, as when `:savepos FILE` was run.
+    #[arg(long = "restorepos", value_name = "FILE")]
+    pub restore_position: Option<PathBuf>,
+
+    /// Compare the input to an older version of it, e.g.,
+    /// `jless --diff old.json new.json`, marking values that were added
+    /// (+), changed (~) or removed (-, on the Object or Array they were
+    /// removed from) in the gutter. Press ]c and [c to jump between them.
+    #[arg(
+        long = "diff",
+        value_name = "OLD",
+        conflicts_with_all = ["gron", "follow", "tutor"]
+    )]
+    pub diff: Option<PathBuf>,
+
+    /// Listen for commands from other processes on a Unix socket at this
+    /// path. Each line sent to it is one of `goto PATH`, `search PATTERN`
+    /// or `reload`.
+    #[arg(long = "control", value_name = "SOCKET")]
+    pub control: Option<PathBuf>,
+
+    /// For inspecting untrusted documents: disable the clipboard, running
+    /// other programs (e.g., the pager for :help), --control and writing
+    /// files, and escape control characters in printed values.
+    #[arg(long = "secure")]
+    pub secure: bool,
+
+    /// Directory to write inputs containing giant (1 MB or longer) strings
+    /// to, so that they don't have to be kept in memory. Defaults to the
+    /// system's temporary directory ($TMPDIR or /tmp).
+    #[arg(long = "spill-dir", value_name = "DIR")]
+    pub spill_dir: Option<PathBuf>,
+
+    /// Index the words in the input when it's loaded, so that repeated
+    /// case insensitive searches for words, or parts of words, in huge
+    /// inputs are near instant. The index can take more memory than the
+    /// input itself. Other searches still scan the whole input.
+    #[arg(long = "search-index")]
+    pub search_index: bool,
+
+    /// When moving into an expanded Object or Array, focus the child
+    /// that was last focused within it, rather than its first child.
+    #[arg(long = "remember-child")]
+    pub remember_last_child: bool,
+
+    /// Ask for confirmation before loading input files larger than this
+    /// many megabytes, offering to stream newline-delimited JSON or load
+    /// just the start of it instead. Set to 0 to never ask.
+    #[arg(
+        long = "large-input-threshold",
+        value_name = "MB",
+        default_value_t = 1024
+    )]
+    pub large_input_threshold: u64,
+
+    /// How many megabytes of lines to load when choosing to load only the
+    /// start of a large newline-delimited JSON file.
+    #[arg(long = "large-input-head", value_name = "MB", default_value_t = 100)]
+    pub large_input_head: u64,
+
+    /// Seed the random number generator used by :sample, so that the same
+    /// elements are sampled every time.
+    #[arg(long = "seed", value_name = "N")]
+    pub seed: Option<u64>,
+
+    /// Instead of opening the viewer, print every value in the input as
+    /// a separate `path = value;` assignment (the format used by gron),
+    /// which makes the output easy to grep and diff.
+    #[arg(long = "gron", conflicts_with = "tutor")]
+    pub gron: bool,
+
+    /// Run commands after loading the input, e.g.,
+    /// --cmd ':set dense | /error | n'. Commands are separated by " | ";
+    /// commands starting with ':', '/' or '?' are entered at the
+    /// corresponding prompt, and anything else is treated as key presses.
+    /// May be specified multiple times.
+    #[arg(long = "cmd", value_name = "COMMANDS")]
+    pub startup_commands: Vec<String>,
+
+    /// Remap keys, e.g., --map 'J=<C-d>,K=<C-u>', so that pressing the
+    /// first key of each pair acts like pressing the second one. Keys are
+    /// single characters, or names like <C-x>, <Up>, <Enter>, or <Space>.
+    /// May be specified multiple times.
+    #[arg(long = "map", value_name = "MAPPINGS", value_parser = parse_key_mappings)]
+    pub key_mappings: Vec<KeyMappings>,
+
+    /// Name a jq filter, e.g., --define-filter 'images=.items[].image', so
+    /// that it can be run with `:filter @images`. Usually set in the config
+    /// file, as `filters.images = ".items[].image"`. May be specified
+    /// multiple times.
+    #[arg(
+        long = "define-filter",
+        value_name = "NAME=EXPRESSION",
+        value_parser = parse_named_filter
+    )]
+    pub named_filters: Vec<(String, String)>,
+
+    /// Define an alias for a command, as with :alias, e.g.,
+    /// --alias 'tl=timeline'. Usually set in the config file, as
+    /// `aliases.tl = "timeline"`. May be specified multiple times.
+    #[arg(long = "alias", value_name = "NAME=COMMAND", value_parser = parse_alias)]
+    pub aliases: Vec<(String, String)>,
+
+    /// Act as a picker for shell pipelines: pressing Enter prints the path
+    /// of the focused value (or, with --pick=value, the value itself) to
+    /// stdout and exits, e.g., KEY=$(jless --pick config.json). Exits with
+    /// status 1 if jless is quit without picking anything.
+    #[arg(
+        long = "pick",
+        value_enum,
+        value_name = "TARGET",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "path",
+        conflicts_with = "gron"
+    )]
+    pub pick: Option<PickTarget>,
+
+    /// When quitting, print the path of the focused value (e.g.,
+    /// .items[3].name) to stdout, or write it to FILE, so that shell
+    /// functions can pick up where you left off, e.g.,
+    /// P=$(jless --on-exit-print-path data.json). Can also be set with the
+    /// JLESS_ON_EXIT_PRINT_PATH environment variable, where "-" is stdout.
+    #[arg(
+        long = "on-exit-print-path",
+        value_name = "FILE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "-",
+        conflicts_with_all = ["pick", "gron"]
+    )]
+    pub on_exit_print_path: Option<PathBuf>,
+
+    /// Keep reading newline-delimited JSON as it's appended to the input
+    /// file, like `tail -f` or less's F command, with the last record
+    /// focused until you move. Use :follow to start following it again.
+    #[arg(long = "follow", conflicts_with_all = ["gron", "dump_screen"])]
+    pub follow: bool,
+
+    /// Instead of opening the viewer, run the --cmd commands, then print
+    /// the text that would be on the screen afterwards. Useful for
+    /// scripting and testing.
+    #[arg(long = "dump-screen", conflicts_with = "gron")]
+    pub dump_screen: bool,
+
+    /// The size of the screen rendered by --dump-screen.
+    #[arg(
+        long = "screen-size",
+        value_name = "WIDTHxHEIGHT",
+        default_value = "80x24",
+        value_parser = parse_screen_size
+    )]
+    pub screen_size: TTYDimensions,
+
+    /// Open an interactive tutorial that teaches how to navigate in jless.
+    #[arg(long = "tutor", conflicts_with = "input")]
+    pub tutor: bool,
+
     /// Parse input as JSON, regardless of file extension.
     #[arg(long = "json", group = "data-format", display_order = 1000)]
     pub json: bool,
@@ -78,11 +443,58 @@ pub struct Opt {
     /// Parse input as YAML, regardless of file extension.
     #[arg(long = "yaml", group = "data-format", display_order = 1000)]
     pub yaml: bool,
+
+    /// Parse input using the given format, regardless of file extension.
+    /// Use json-seq for JSON values separated by ASCII record separators
+    /// (0x1E; RFC 7464) or NUL bytes, ndjson for newline-delimited JSON,
+    /// which is shown before all of it has been read, and hjson for Hjson
+    /// config files.
+    #[arg(
+        long = "format",
+        value_enum,
+        group = "data-format",
+        display_order = 1000
+    )]
+    pub format: Option<DataFormat>,
+
+    /// Accept the non-standard NaN, Infinity and -Infinity literals in JSON
+    /// input, which Python's json.dumps emits by default. They're
+    /// underlined, and a warning is shown that the input isn't strictly
+    /// valid JSON.
+    #[arg(long = "allow-nan", display_order = 1000)]
+    pub allow_nan: bool,
+
+    /// If JSON input fails to parse, view the lines that do parse as
+    /// newline-delimited JSON, skipping the rest, which are listed by
+    /// :errors. This is always done for --format ndjson, and .ndjson and
+    /// .jsonl files.
+    #[arg(long = "tolerant", display_order = 1000)]
+    pub tolerant: bool,
 }
 
 impl Opt {
+    // Where to print the focused path when quitting, if anywhere; "-" is
+    // stdout.
+    pub fn on_exit_path_destination(&self) -> Option<PathBuf> {
+        self.on_exit_print_path.clone().or_else(|| {
+            std::env::var_os("JLESS_ON_EXIT_PRINT_PATH")
+                .filter(|destination| !destination.is_empty())
+                .map(PathBuf::from)
+        })
+    }
+
+    // Whether stdout is reserved for what gets printed when jless exits, in
+    // which case the viewer is drawn on the terminal, even when stdout isn't
+    // one. (The environment variable form of --on-exit-print-path doesn't
+    // count, so that it doesn't affect piping jless's output elsewhere.)
+    pub fn prints_on_exit(&self) -> bool {
+        self.pick.is_some() || self.on_exit_print_path.as_deref() == Some(Path::new("-"))
+    }
+
     pub fn data_format(&self) -> Option<DataFormat> {
-        if self.json {
+        if self.format.is_some() {
+            self.format
+        } else if self.json {
             Some(DataFormat::Json)
         } else if self.yaml {
             Some(DataFormat::Yaml)
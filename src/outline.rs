@@ -0,0 +1,256 @@
+use crate::flatjson::{FlatJson, Index, PathType};
+use crate::search::SearchState;
+
+// How many levels of the document are listed in the outline.
+const OUTLINE_DEPTH: usize = 2;
+
+// What's listed in the outline panel.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OutlineKind {
+    // The top levels of the document.
+    Document,
+    // The rows containing matches of the current search, opened with
+    // `:matches`.
+    SearchMatches,
+}
+
+// The outline panel, which lists the top levels of the document (or the
+// rows with search matches) and can be used to quickly jump between them.
+pub struct Outline {
+    pub kind: OutlineKind,
+    // Indexes of the rows in the outline, in document order, except for
+    // search matches, which are ranked by depth.
+    pub entries: Vec<Index>,
+    pub selected: usize,
+    // The first entry that is visible in the panel.
+    pub top: usize,
+    // Whether key presses are handled by the outline or the main viewer.
+    pub focused: bool,
+}
+
+impl Outline {
+    pub fn new(flatjson: &FlatJson) -> Outline {
+        Outline {
+            kind: OutlineKind::Document,
+            entries: Outline::document_entries(flatjson, 0).collect(),
+            selected: 0,
+            top: 0,
+            focused: true,
+        }
+    }
+
+    // Add the entries for rows appended to the document, e.g., records
+    // that were read after the outline was opened.
+    pub fn add_rows(&mut self, flatjson: &FlatJson, first_row: Index) {
+        if self.kind == OutlineKind::Document {
+            self.entries
+                .extend(Outline::document_entries(flatjson, first_row));
+        }
+    }
+
+    fn document_entries(flatjson: &FlatJson, first_row: Index) -> impl Iterator<Item = Index> + '_ {
+        flatjson.0[first_row..]
+            .iter()
+            .enumerate()
+            .filter(|(_, row)| {
+                (1..=OUTLINE_DEPTH).contains(&row.depth) && !row.is_closing_of_container()
+            })
+            .map(move |(offset, _)| first_row + offset)
+    }
+
+    // List each row containing a search match once. Shallower matches are
+    // listed first, since they're usually the more interesting ones, e.g.,
+    // a matching key at the top level rather than a string buried deep
+    // inside of some array; matches at the same depth are in document
+    // order.
+    pub fn search_matches(flatjson: &FlatJson, search_state: &SearchState) -> Outline {
+        let mut entries = search_state.rows_with_matches(flatjson);
+        entries.sort_by_key(|index| flatjson[*index].depth);
+
+        Outline {
+            kind: OutlineKind::SearchMatches,
+            entries,
+            selected: 0,
+            top: 0,
+            focused: true,
+        }
+    }
+
+    pub fn selected_row(&self) -> Option<Index> {
+        self.entries.get(self.selected).copied()
+    }
+
+    // Select the last entry at or before the given row, i.e., the entry
+    // that contains it. Search matches aren't in document order, so they
+    // only select an entry for exactly that row.
+    pub fn select_entry_containing(&mut self, row: Index) {
+        self.selected = match self.kind {
+            OutlineKind::Document => self
+                .entries
+                .partition_point(|entry| *entry <= row)
+                .saturating_sub(1),
+            OutlineKind::SearchMatches => self
+                .entries
+                .iter()
+                .position(|entry| *entry == row)
+                .unwrap_or(self.selected),
+        };
+    }
+
+    pub fn move_selection_down(&mut self, n: usize) {
+        let last = self.entries.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add(n).min(last);
+    }
+
+    pub fn move_selection_up(&mut self, n: usize) {
+        self.selected = self.selected.saturating_sub(n);
+    }
+
+    pub fn select_first(&mut self) {
+        self.selected = 0;
+    }
+
+    pub fn select_last(&mut self) {
+        self.selected = self.entries.len().saturating_sub(1);
+    }
+
+    // Update the top entry so that the selected entry is visible in a
+    // panel of the given height.
+    pub fn ensure_selected_is_visible(&mut self, height: usize) {
+        if self.selected < self.top {
+            self.top = self.selected;
+        } else if height > 0 && self.selected >= self.top + height {
+            self.top = self.selected + 1 - height;
+        }
+    }
+
+    // The text displayed for an entry: its object key or array index,
+    // indented by its depth, or for search matches, the path to the row
+    // and a snippet of its value.
+    pub fn entry_label(&self, flatjson: &FlatJson, index: Index) -> String {
+        match self.kind {
+            OutlineKind::Document => Outline::document_entry_label(flatjson, index),
+            OutlineKind::SearchMatches => Outline::search_match_label(flatjson, index),
+        }
+    }
+
+    fn document_entry_label(flatjson: &FlatJson, index: Index) -> String {
+        let row = &flatjson[index];
+        let indentation = "  ".repeat(row.depth - 1);
+        match &row.key_range {
+            Some(key_range) => format!(
+                "{indentation}{}",
+                &flatjson.1[key_range.start + 1..key_range.end - 1]
+            ),
+            None => format!("{indentation}[{}]", row.index_in_parent),
+        }
+    }
+
+    fn search_match_label(flatjson: &FlatJson, index: Index) -> String {
+        let row = &flatjson[index];
+        let path = flatjson
+            .build_path_to_node(PathType::Jq, index)
+            .unwrap_or_default();
+        let snippet = if row.is_array() {
+            "[…]"
+        } else if row.is_container() {
+            "{…}"
+        } else {
+            &flatjson.1[row.range.clone()]
+        };
+        format!("{path}: {snippet}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flatjson::parse_top_level_json;
+    use crate::jsonparser;
+    use crate::search::{SearchDefaults, SearchDirection};
+
+    #[test]
+    fn test_outline() {
+        const JSON: &str = r#"{
+            "name": "jless",
+            "authors": [
+                { "name": "Paul" },
+            ],
+            "deps": {
+                "clap": { "version": "4" },
+            },
+        }"#;
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+        let mut outline = Outline::new(&fj);
+
+        let labels: Vec<String> = outline
+            .entries
+            .iter()
+            .map(|index| outline.entry_label(&fj, *index))
+            .collect();
+        assert_eq!(vec!["name", "authors", "  [0]", "deps", "  clap"], labels);
+
+        // Inside of "authors"[0].
+        outline.select_entry_containing(4);
+        assert_eq!(Some(3), outline.selected_row());
+        outline.select_entry_containing(0);
+        assert_eq!(Some(1), outline.selected_row());
+
+        outline.move_selection_down(10);
+        assert_eq!(4, outline.selected);
+        outline.ensure_selected_is_visible(2);
+        assert_eq!(3, outline.top);
+        outline.move_selection_up(2);
+        outline.ensure_selected_is_visible(2);
+        assert_eq!(2, outline.top);
+    }
+
+    #[test]
+    fn test_add_rows() {
+        let mut fj = parse_top_level_json(r#"{ "a": [1] }"#.to_owned()).unwrap();
+        let mut outline = Outline::new(&fj);
+        outline.select_last();
+
+        let (rows, pretty, depth) = jsonparser::parse(r#"{ "b": 2 }"#).unwrap();
+        fj.append(rows, &pretty, depth);
+        outline.add_rows(&fj, 5);
+
+        assert_eq!(vec![1, 2, 6], outline.entries);
+        assert_eq!(1, outline.selected);
+    }
+
+    #[test]
+    fn test_search_matches() {
+        const JSON: &str = r#"{
+            "a": { "b": "match", "c": ["match match"] },
+            "match": 1,
+        }"#;
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+        let search_state = SearchState::initialize_search(
+            "match".to_owned(),
+            &fj.1,
+            SearchDirection::Forward,
+            SearchDefaults::default(),
+        )
+        .unwrap();
+        let mut outline = Outline::search_matches(&fj, &search_state);
+
+        // Shallowest first, and each row listed once.
+        assert_eq!(vec![7, 2, 4], outline.entries);
+        let labels: Vec<String> = outline
+            .entries
+            .iter()
+            .map(|index| outline.entry_label(&fj, *index))
+            .collect();
+        assert_eq!(
+            vec![".match: 1", r#".a.b: "match""#, r#".a.c[0]: "match match""#],
+            labels
+        );
+
+        outline.select_entry_containing(4);
+        assert_eq!(2, outline.selected);
+        // Rows without matches leave the selection alone.
+        outline.select_entry_containing(3);
+        assert_eq!(2, outline.selected);
+    }
+}
@@ -0,0 +1,152 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::types::TTYDimensions;
+
+// Content printed with p that doesn't fit on the screen is shown a page at
+// a time, like in more, so that the start of it isn't scrolled out of view
+// on small terminals. Lines are wrapped to the width of the screen up
+// front, so that pages line up exactly with the screen.
+pub struct Pager {
+    rows: Vec<String>,
+    // The first row that's shown.
+    top: usize,
+    // How many rows are shown at a time, leaving room for the prompt.
+    height: usize,
+    width: usize,
+}
+
+impl Pager {
+    // Returns None if the content fits on the screen, along with the blank
+    // line and the prompt printed after it.
+    pub fn new(content: &str, dimensions: TTYDimensions) -> Option<Pager> {
+        let width = dimensions.width as usize;
+        let height = dimensions.height as usize;
+
+        let rows: Vec<String> = content
+            .lines()
+            .flat_map(|line| wrap_line(line, width))
+            .collect();
+        if rows.len() + 2 <= height {
+            return None;
+        }
+
+        Some(Pager {
+            rows,
+            top: 0,
+            height: height.saturating_sub(1).max(1),
+            width,
+        })
+    }
+
+    pub fn page(&self) -> &[String] {
+        let end = (self.top + self.height).min(self.rows.len());
+        &self.rows[self.top..end]
+    }
+
+    pub fn is_last_page(&self) -> bool {
+        self.top + self.height >= self.rows.len()
+    }
+
+    // The last page is always a full page, so it may overlap the one
+    // before it.
+    pub fn next_page(&mut self) {
+        let last_top = self.rows.len().saturating_sub(self.height);
+        self.top = (self.top + self.height).min(last_top);
+    }
+
+    pub fn prev_page(&mut self) {
+        self.top = self.top.saturating_sub(self.height);
+    }
+
+    // Cut off to the width of the screen, so that it doesn't wrap and
+    // scroll the page up.
+    pub fn prompt(&self) -> String {
+        let end = (self.top + self.height).min(self.rows.len());
+        let keys = if self.is_last_page() {
+            "b: back, any other key: done"
+        } else {
+            "space: next page, b: back, q: done"
+        };
+        let prompt = format!(
+            "-- Lines {}-{end} of {} ({keys}) --",
+            self.top + 1,
+            self.rows.len()
+        );
+        wrap_line(&prompt, self.width).swap_remove(0)
+    }
+}
+
+// Split a line into rows that are each at most width columns wide.
+fn wrap_line(line: &str, width: usize) -> Vec<String> {
+    let mut rows = vec![];
+    let mut row = String::new();
+    let mut row_width = 0;
+
+    for grapheme in line.graphemes(true) {
+        let grapheme_width = UnicodeWidthStr::width(grapheme);
+        if row_width + grapheme_width > width && !row.is_empty() {
+            rows.push(std::mem::take(&mut row));
+            row_width = 0;
+        }
+        row.push_str(grapheme);
+        row_width += grapheme_width;
+    }
+
+    rows.push(row);
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dimensions(width: u16, height: u16) -> TTYDimensions {
+        TTYDimensions { width, height }
+    }
+
+    #[test]
+    fn test_paging() {
+        // Content that fits, along with the prompt after it, isn't paged.
+        assert!(Pager::new("1\n2\n3", dimensions(10, 5)).is_none());
+
+        let content: Vec<String> = (1..=10).map(|n| n.to_string()).collect();
+        let mut pager = Pager::new(&content.join("\n"), dimensions(80, 5)).unwrap();
+        assert_eq!(["1", "2", "3", "4"], pager.page());
+        assert!(pager.prompt().starts_with("-- Lines 1-4 of 10 (space"));
+
+        pager.prev_page();
+        assert_eq!("1", pager.page()[0]);
+
+        pager.next_page();
+        assert_eq!(["5", "6", "7", "8"], pager.page());
+        assert!(!pager.is_last_page());
+
+        // The last page is a full page.
+        pager.next_page();
+        assert_eq!(["7", "8", "9", "10"], pager.page());
+        assert!(pager.is_last_page());
+        assert_eq!(
+            "-- Lines 7-10 of 10 (b: back, any other key: done) --",
+            pager.prompt()
+        );
+        pager.next_page();
+        assert_eq!("7", pager.page()[0]);
+
+        pager.prev_page();
+        pager.prev_page();
+        assert_eq!(["1", "2", "3", "4"], pager.page());
+
+        let pager = Pager::new(&content.join("\n"), dimensions(10, 5)).unwrap();
+        assert_eq!("-- Lines 1", pager.prompt());
+    }
+
+    #[test]
+    fn test_wrap_line() {
+        assert_eq!(vec!["abc", "def", "g"], wrap_line("abcdefg", 3));
+        assert_eq!(vec![""], wrap_line("", 3));
+        // Wide characters aren't split across rows.
+        assert_eq!(vec!["a", "文", "字"], wrap_line("a文字", 2));
+        assert_eq!(vec!["e\u{301}"], wrap_line("e\u{301}", 1));
+    }
+}
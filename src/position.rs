@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::flatjson::{FlatJson, Index, OptionIndex, PathType};
+
+// A position in a document, saved via `:savepos FILE` and restored via
+// `--restorepos FILE`, so that someone else can open the same document
+// focused on the same node, with the same containers collapsed. Nodes are
+// identified by their paths, rather than by row indexes, so a position can
+// still (mostly) be restored in a slightly different version of the input.
+//
+// Files are plain text, with one path per line:
+//
+//   jless position v1
+//   focus .data.items[3].id
+//   collapsed .data.meta
+//   collapsed .data.items[0]
+#[derive(Debug, PartialEq)]
+pub struct SavedPosition {
+    focus: String,
+    collapsed: Vec<String>,
+}
+
+const HEADER: &str = "jless position v1";
+const FOCUS: &str = "focus";
+const COLLAPSED: &str = "collapsed";
+
+impl SavedPosition {
+    pub fn of(flatjson: &FlatJson, focused_row: Index) -> SavedPosition {
+        SavedPosition {
+            focus: path_to_node(flatjson, focused_row),
+            collapsed: flatjson
+                .collapsed_containers()
+                .into_iter()
+                .map(|index| path_to_node(flatjson, index))
+                .collect(),
+        }
+    }
+
+    pub fn parse(contents: &str) -> Result<SavedPosition, String> {
+        let mut lines = contents.lines();
+        if lines.next() != Some(HEADER) {
+            return Err("Not a jless position file".to_string());
+        }
+
+        let mut focus = None;
+        let mut collapsed = vec![];
+        for line in lines.filter(|line| !line.is_empty()) {
+            let (field, path) = line.split_once(' ').unwrap_or((line, ""));
+            match field {
+                FOCUS => focus = Some(path.to_string()),
+                COLLAPSED => collapsed.push(path.to_string()),
+                _ => return Err(format!("Invalid line in position file: {line}")),
+            }
+        }
+
+        match focus {
+            Some(focus) => Ok(SavedPosition { focus, collapsed }),
+            None => Err("Position file doesn't say what to focus".to_string()),
+        }
+    }
+
+    // Collapse the saved containers, expanding all the others, and return
+    // the row that should be focused, along with how many of the saved
+    // paths don't exist in the document.
+    pub fn restore(&self, flatjson: &mut FlatJson) -> (Index, usize) {
+        let mut rows_by_path = HashMap::new();
+        for index in 0..flatjson.0.len() {
+            if !flatjson[index].is_closing_of_container() {
+                rows_by_path.insert(path_to_node(flatjson, index), index);
+            }
+        }
+
+        let mut num_missing = 0;
+        let mut collapsed = vec![];
+        for path in &self.collapsed {
+            match rows_by_path.get(path) {
+                Some(&index) if flatjson[index].is_opening_of_container() => collapsed.push(index),
+                _ => num_missing += 1,
+            }
+        }
+        flatjson.set_collapsed_containers(&collapsed);
+
+        let focused_row = match rows_by_path.get(&self.focus) {
+            Some(&index) => index,
+            None => {
+                num_missing += 1;
+                0
+            }
+        };
+
+        (focused_row, num_missing)
+    }
+}
+
+impl fmt::Display for SavedPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{HEADER}")?;
+        writeln!(f, "{FOCUS} {}", self.focus)?;
+        for path in &self.collapsed {
+            writeln!(f, "{COLLAPSED} {path}")?;
+        }
+        Ok(())
+    }
+}
+
+// Find the node with the given path, in the same format as the paths in
+// position files. `.` can be used for the root of a single value.
+pub fn find_node(flatjson: &FlatJson, path: &str) -> Option<Index> {
+    let path = if path == "." { "" } else { path };
+
+    let mut index = 0;
+    while index < flatjson.0.len() {
+        let row = &flatjson[index];
+        if row.is_closing_of_container() {
+            index += 1;
+            continue;
+        }
+
+        let node_path = path_to_node(flatjson, index);
+        if node_path == path {
+            return Some(index);
+        }
+
+        // Only look inside containers that could contain the path.
+        index = match (row.first_child(), row.pair_index()) {
+            (OptionIndex::Index(first_child), _) if path.starts_with(&node_path) => first_child,
+            (_, OptionIndex::Index(close_index)) => close_index + 1,
+            _ => index + 1,
+        };
+    }
+
+    None
+}
+
+fn path_to_node(flatjson: &FlatJson, index: Index) -> String {
+    // This never fails for DotWithTopLevelIndex.
+    flatjson
+        .build_path_to_node(PathType::DotWithTopLevelIndex, index)
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::flatjson::parse_top_level_json;
+
+    use super::*;
+
+    const JSON: &str = r#"{ "a": [1, { "b c": 2 }], "d": { "e": 3 } }"#;
+
+    #[test]
+    fn test_save_and_restore_position() {
+        let mut fj = parse_top_level_json(JSON.to_owned()).unwrap();
+        fj.collapse(3);
+        fj.collapse(7);
+
+        let position = SavedPosition::of(&fj, 2);
+        let contents = position.to_string();
+        assert_eq!(
+            "jless position v1\n\
+             focus .a[0]\n\
+             collapsed .a[1]\n\
+             collapsed .d\n",
+            contents
+        );
+        assert_eq!(Ok(&position), SavedPosition::parse(&contents).as_ref());
+
+        let mut fj = parse_top_level_json(JSON.to_owned()).unwrap();
+        fj.collapse(1);
+        assert_eq!((2, 0), position.restore(&mut fj));
+        assert_eq!(vec![3, 7], fj.collapsed_containers());
+
+        // The root of a single top-level value has an empty path.
+        let position = SavedPosition::of(&fj, 0);
+        assert_eq!(
+            Ok(&position),
+            SavedPosition::parse(&position.to_string()).as_ref()
+        );
+        assert_eq!((0, 0), position.restore(&mut fj));
+    }
+
+    #[test]
+    fn test_restore_missing_paths() {
+        let position = SavedPosition::parse(
+            "jless position v1\n\
+             focus .x\n\
+             collapsed .a\n\
+             collapsed .a[0]\n\
+             collapsed .y\n",
+        )
+        .unwrap();
+
+        let mut fj = parse_top_level_json(JSON.to_owned()).unwrap();
+        // .a[0] isn't a container, so it can't be collapsed.
+        assert_eq!((0, 3), position.restore(&mut fj));
+        assert_eq!(vec![1], fj.collapsed_containers());
+    }
+
+    #[test]
+    fn test_find_node() {
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+        assert_eq!(Some(0), find_node(&fj, ""));
+        assert_eq!(Some(0), find_node(&fj, "."));
+        assert_eq!(Some(3), find_node(&fj, ".a[1]"));
+        assert_eq!(Some(4), find_node(&fj, r#".a[1]["b c"]"#));
+        assert_eq!(Some(8), find_node(&fj, ".d.e"));
+        assert_eq!(None, find_node(&fj, ".a[2]"));
+        assert_eq!(None, find_node(&fj, ".x"));
+
+        let fj = parse_top_level_json("[1] [2, 3]".to_owned()).unwrap();
+        assert_eq!(Some(5), find_node(&fj, "[1][1]"));
+    }
+
+    #[test]
+    fn test_parse_invalid_position() {
+        assert!(SavedPosition::parse("").is_err());
+        assert!(SavedPosition::parse("focus .a\n").is_err());
+        assert!(SavedPosition::parse("jless position v1\ncollapsed .a\n").is_err());
+        assert!(SavedPosition::parse("jless position v1\nfocus .a\nfold .b\n").is_err());
+    }
+}
@@ -0,0 +1,104 @@
+use crate::flatjson::{self, FlatJson, Index, OptionIndex};
+use crate::jsonparser;
+use crate::jsonstringunescaper::escape_json_string;
+
+// The output of an analysis command, like :errors, which is shown in place
+// of the document, so that it can be navigated, searched, and copied from
+// like any other input. Each entry of the output is a key and value of a
+// top-level Object, which may refer to a row of the document the command
+// was run on; pressing Enter on an entry jumps to that row, and q goes
+// back to the document.
+pub struct ResultBuffer {
+    // Shown in the status bar in place of the input filename.
+    pub name: String,
+    entries: Vec<Entry>,
+}
+
+struct Entry {
+    key: String,
+    // JSON text.
+    value: String,
+    target: Option<Index>,
+}
+
+impl ResultBuffer {
+    pub fn new(name: String) -> ResultBuffer {
+        ResultBuffer {
+            name,
+            entries: vec![],
+        }
+    }
+
+    pub fn push_text(&mut self, key: String, text: &str, target: Option<Index>) {
+        let value = format!("\"{}\"", escape_json_string(text));
+        self.entries.push(Entry { key, value, target });
+    }
+
+    // Add the text of a value from a document, which is shown as a string
+    // if it isn't a single JSON value, e.g., if it has a complex YAML key.
+    pub fn push_value(&mut self, key: String, value: &str, target: Option<Index>) {
+        let is_single_value = jsonparser::parse(value).map_or(false, |(rows, _, _)| {
+            rows.first().map_or(false, |row| row.next_sibling.is_nil())
+        });
+        if is_single_value {
+            let value = value.to_string();
+            self.entries.push(Entry { key, value, target });
+        } else {
+            self.push_text(key, value, target);
+        }
+    }
+
+    pub fn to_flatjson(&self) -> Result<FlatJson, String> {
+        let entries: Vec<String> = self
+            .entries
+            .iter()
+            .map(|entry| format!("\"{}\": {}", escape_json_string(&entry.key), entry.value))
+            .collect();
+        flatjson::parse_top_level_json(format!("{{{}}}", entries.join(", ")))
+    }
+
+    // The row of the original document that the entry containing a row of
+    // the buffer refers to.
+    pub fn target(&self, flatjson: &FlatJson, mut row: Index) -> Option<Index> {
+        if flatjson[row].is_closing_of_container() {
+            row = flatjson[row].pair_index().unwrap();
+        }
+        while flatjson[row].depth > 1 {
+            row = match flatjson[row].parent {
+                OptionIndex::Index(parent) => parent,
+                OptionIndex::Nil => return None,
+            };
+        }
+        if flatjson[row].depth != 1 {
+            return None;
+        }
+        self.entries.get(flatjson[row].index_in_parent)?.target
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_result_buffer() {
+        let mut buffer = ResultBuffer::new("[test]".to_string());
+        buffer.push_text("Line 3".to_string(), "Unexpected \"}\"", None);
+        buffer.push_value("a".to_string(), "[1, {\"b\": 2}]", Some(7));
+        buffer.push_value("c".to_string(), "[[1]]: 2", Some(9));
+
+        let flatjson = buffer.to_flatjson().unwrap();
+        assert_eq!(10, flatjson.0.len());
+        assert!(flatjson[1].is_string());
+        assert!(flatjson[8].is_string());
+
+        // The rows of the buffer are: {, "Line 3", a: [, 1, {, b, }, ], c, }.
+        assert_eq!(None, buffer.target(&flatjson, 0));
+        assert_eq!(None, buffer.target(&flatjson, 1));
+        for row in 2..=7 {
+            assert_eq!(Some(7), buffer.target(&flatjson, row));
+        }
+        assert_eq!(Some(9), buffer.target(&flatjson, 8));
+        assert_eq!(None, buffer.target(&flatjson, 9));
+    }
+}
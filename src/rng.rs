@@ -0,0 +1,79 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// A small, deterministic pseudo-random number generator (xorshift). It's
+// seeded explicitly, so that the same seed always produces the same
+// numbers, e.g., when :sample is used with --seed.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // xorshift gets stuck on 0, so use an arbitrary non-zero seed
+        // instead.
+        match seed {
+            0 => Rng(0x2545_f491_4f6c_dd1d),
+            seed => Rng(seed),
+        }
+    }
+
+    // Seeded from the current time, for when no seed was given.
+    pub fn from_time() -> Rng {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_nanos() as u64);
+        Rng::new(nanos ^ std::process::id() as u64)
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    // A number less than n, which must be positive, using Lemire's
+    // multiply-shift method: the high 64 bits of a random number times n
+    // are below n, and are uniformly distributed once the few products
+    // whose low 64 bits are under 2^64 % n are rejected. (Just taking the
+    // random number % n would favor small numbers.)
+    pub fn below(&mut self, n: usize) -> usize {
+        let n = n as u64;
+        let threshold = n.wrapping_neg() % n;
+        loop {
+            let product = self.next() as u128 * n as u128;
+            if product as u64 >= threshold {
+                return (product >> 64) as usize;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rng_is_deterministic() {
+        let numbers = |seed| {
+            let mut rng = Rng::new(seed);
+            (0..5).map(|_| rng.below(1000)).collect::<Vec<_>>()
+        };
+
+        assert_eq!(numbers(42), numbers(42));
+        assert_ne!(numbers(42), numbers(43));
+        assert!(numbers(0).iter().any(|n| *n != 0));
+    }
+
+    #[test]
+    fn test_below() {
+        let mut rng = Rng::new(42);
+        assert!((0..100).all(|_| rng.below(1) == 0));
+
+        // Two thirds of the way to the largest number; taking the remainder
+        // would land in the lower half of the range two thirds of the time.
+        let n = (u64::MAX / 3 * 2) as usize;
+        let numbers = (0..1000).map(|_| rng.below(n)).collect::<Vec<_>>();
+        assert!(numbers.iter().all(|&number| number < n));
+        let lower_half = numbers.iter().filter(|&&number| number < n / 2).count();
+        assert!((400..600).contains(&lower_half), "{}", lower_half);
+    }
+}
@@ -6,31 +6,105 @@ use std::ops::Range;
 use rustyline::Editor;
 use termion::raw::RawTerminal;
 use unicode_segmentation::UnicodeSegmentation;
-use unicode_width::UnicodeWidthStr;
 
 use crate::app::MAX_BUFFER_SIZE;
-use crate::flatjson::{Index, OptionIndex, PathType, Row, Value};
+use crate::dates::DateDisplay;
+use crate::diff::DiffMarker;
+use crate::flatjson::{FlatJson, Index, OptionIndex, PathType, Row, Value};
+use crate::highlighting::{self, Background, Theme};
+use crate::keycompleter::{Completion, KeyCompleter};
 use crate::lineprinter as lp;
-use crate::lineprinter::LineNumber;
+use crate::lineprinter::{LineNumber, NumberBase, NumberMode, PreviewOrder, TruncationSettings};
 use crate::options::Opt;
+use crate::outline::Outline;
 use crate::search::{MatchRangeIter, SearchState};
+use crate::table::{self, Table};
 use crate::terminal;
-use crate::terminal::{AnsiTerminal, Terminal};
-use crate::truncatedstrview::{TruncatedStrSlice, TruncatedStrView};
-use crate::types::TTYDimensions;
+use crate::terminal::{AnsiTerminal, Color, Style, Terminal};
+use crate::timeline::Timeline;
+use crate::truncatedstrview::{
+    display_width, AnchoredStrView, TruncatedStrSlice, TruncatedStrView,
+};
+use crate::types::{TTYDimensions, STATUS_BAR_HEIGHT};
 use crate::viewer::{JsonViewer, Mode};
 
+// Where the screen gets drawn: normally the terminal, in raw mode, but
+// when running headlessly (--dump-screen) it's a ScreenCapture instead.
+pub trait Output: std::io::Write {
+    fn suspend_raw_mode(&self) -> std::io::Result<()>;
+    fn activate_raw_mode(&self) -> std::io::Result<()>;
+
+    // Whether there's a user who can be prompted for input.
+    fn is_interactive(&self) -> bool {
+        true
+    }
+}
+
+impl<W: std::io::Write> Output for RawTerminal<W> {
+    fn suspend_raw_mode(&self) -> std::io::Result<()> {
+        RawTerminal::suspend_raw_mode(self)
+    }
+
+    fn activate_raw_mode(&self) -> std::io::Result<()> {
+        RawTerminal::activate_raw_mode(self)
+    }
+}
+
 pub struct ScreenWriter {
-    pub stdout: RawTerminal<Box<dyn std::io::Write>>,
-    pub command_editor: Editor<()>,
+    pub stdout: Box<dyn Output>,
+    pub command_editor: Editor<KeyCompleter>,
     pub dimensions: TTYDimensions,
     pub terminal: AnsiTerminal,
 
     pub show_line_numbers: bool,
     pub show_relative_line_numbers: bool,
+    pub number_mode: NumberMode,
+    pub show_previews: bool,
+    pub preview_order: PreviewOrder,
+    pub show_invisibles: bool,
+    pub show_sizes: bool,
+    pub show_number_types: bool,
+    pub number_base: NumberBase,
+    pub show_dates: bool,
+    pub date_display: DateDisplay,
+    pub highlight_focused_line: bool,
+    pub align_keys: bool,
+    pub show_indent_guides: bool,
+    pub indent_guide_colors: Vec<Color>,
+    pub show_container_gauge: bool,
+    pub theme: Theme,
+    pub truncation: TruncationSettings,
+
+    // The search being typed, when incsearch is enabled, which is shown
+    // in place of the usual message line.
+    pub search_prompt: Option<String>,
+
+    // Rows that changed the last time the input was reloaded.
+    pub diff_markers: HashMap<Index, DiffMarker>,
+
+    // The screen row (0-based) that the viewer starts on. This is only
+    // non-zero when the screen is split and the bottom view is being drawn.
+    pub viewer_top: u16,
 
     indentation_reduction: u16,
-    truncated_row_value_views: HashMap<Index, TruncatedStrView>,
+    // Width of the outline panel on the right side of the screen, if
+    // it's being shown, including the separator.
+    outline_width: u16,
+    // Width of the gauge showing the position of the focused row within its
+    // parent container, drawn just to the left of the outline panel.
+    gauge_width: u16,
+    truncated_row_value_views: HashMap<Index, AnchoredStrView>,
+    // The width of the widest key in each object, computed the first time
+    // one of its keys is printed with align_keys set.
+    widest_keys: HashMap<Index, isize>,
+    // The output of the previews of collapsed containers, which are only
+    // valid for the preview settings they were printed with.
+    container_previews: HashMap<Index, lp::CachedPreview>,
+    container_preview_settings: (bool, PreviewOrder, bool, bool),
+
+    // The contents of the last full frame written to the terminal, so that
+    // we can skip writing out identical frames.
+    last_frame: String,
 }
 
 pub enum MessageSeverity {
@@ -50,28 +124,113 @@ impl MessageSeverity {
 }
 
 const TAB_SIZE: isize = 2;
+const MAX_OUTLINE_WIDTH: u16 = 40;
+const MIN_OUTLINE_WIDTH: u16 = 10;
+const DENSE_TAB_SIZE: isize = 1;
 const PATH_BASE: &str = "input";
 const SPACE_BETWEEN_PATH_AND_FILENAME: isize = 3;
 
 impl ScreenWriter {
     pub fn init(
         options: &Opt,
-        stdout: RawTerminal<Box<dyn std::io::Write>>,
-        command_editor: Editor<()>,
+        stdout: Box<dyn Output>,
+        command_editor: Editor<KeyCompleter>,
         dimensions: TTYDimensions,
     ) -> Self {
-        ScreenWriter {
+        let mut screen_writer = ScreenWriter {
             stdout,
             command_editor,
             dimensions,
             terminal: AnsiTerminal::new(String::new()),
-            show_line_numbers: options.show_line_numbers,
-            show_relative_line_numbers: options.show_relative_line_numbers,
+            show_line_numbers: false,
+            show_relative_line_numbers: false,
+            number_mode: NumberMode::Line,
+            show_previews: false,
+            preview_order: options.preview_order,
+            show_invisibles: false,
+            show_sizes: false,
+            show_number_types: false,
+            number_base: options.number_base,
+            show_dates: false,
+            date_display: DateDisplay::from_options(options),
+            highlight_focused_line: false,
+            align_keys: false,
+            show_indent_guides: false,
+            indent_guide_colors: vec![],
+            show_container_gauge: true,
+            theme: options.theme.theme(Background::Dark).clone(),
+            truncation: TruncationSettings {
+                min_visible_chars: 0,
+                prefer_string_ends: false,
+            },
+            search_prompt: None,
+            diff_markers: HashMap::new(),
+            viewer_top: 0,
             indentation_reduction: 0,
+            outline_width: 0,
+            gauge_width: 0,
             truncated_row_value_views: HashMap::new(),
-        }
+            widest_keys: HashMap::new(),
+            container_previews: HashMap::new(),
+            container_preview_settings: (false, options.preview_order, false, false),
+            last_frame: String::new(),
+        };
+        screen_writer.apply_options(options);
+        screen_writer
     }
 
+    // Set everything that's configurable via command line options, which
+    // also happens when the config file is reloaded.
+    pub fn apply_options(&mut self, options: &Opt) {
+        let theme = options
+            .theme
+            .theme(options.background.unwrap_or(Background::Dark));
+        self.theme = match &options.theme_colors {
+            Some(colors) => theme.with_colors(colors),
+            None => theme.clone(),
+        };
+        self.indent_guide_colors = match &options.indent_guide_colors {
+            Some(colors) => colors.0.clone(),
+            None => self.theme.indent_guides.to_vec(),
+        };
+        self.show_line_numbers = options.show_line_numbers;
+        self.show_relative_line_numbers = options.show_relative_line_numbers;
+        self.show_previews = options.show_previews;
+        self.preview_order = options.preview_order;
+        self.number_base = options.number_base;
+        self.show_dates = options.date_format.is_some();
+        self.date_display = DateDisplay::from_options(options);
+        self.highlight_focused_line = options.cursorline;
+        self.align_keys = options.align_keys;
+        self.show_indent_guides = options.indent_guides;
+        self.truncation = TruncationSettings {
+            min_visible_chars: options.min_visible_chars as isize,
+            prefer_string_ends: options.prefer_string_ends,
+        };
+        self.truncated_row_value_views.clear();
+        self.widest_keys.clear();
+        self.container_previews.clear();
+        self.invalidate_last_frame();
+    }
+
+    // Force the next frame to be written out, even if it is identical to
+    // the previous one. This should be called whenever something else may
+    // have modified the screen, e.g., after switching back to the alternate
+    // screen.
+    pub fn invalidate_last_frame(&mut self) {
+        self.last_frame.clear();
+    }
+
+    // Clear any state tied to the row indexes of the previous document
+    // after the input has been reloaded.
+    pub fn reset_for_new_document(&mut self, diff_markers: HashMap<Index, DiffMarker>) {
+        self.diff_markers = diff_markers;
+        self.truncated_row_value_views.clear();
+        self.widest_keys.clear();
+        self.container_previews.clear();
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn print(
         &mut self,
         viewer: &JsonViewer,
@@ -79,25 +238,104 @@ impl ScreenWriter {
         input_filename: &str,
         search_state: &SearchState,
         message: &Option<(String, MessageSeverity)>,
+        outline: Option<&mut Outline>,
+        timeline: Option<&Timeline>,
+        table: Option<&mut Table>,
+        pinned_rows: &[Index],
     ) {
-        self.print_viewer(viewer, search_state);
-        self.print_status_bar(viewer, input_buffer, input_filename, search_state, message);
+        let preview_settings = (
+            self.show_previews,
+            self.preview_order,
+            self.show_sizes,
+            self.show_invisibles,
+        );
+        if preview_settings != self.container_preview_settings {
+            self.container_previews.clear();
+            self.container_preview_settings = preview_settings;
+        }
+
+        // On tiny screens, only draw as much of the viewer as fits.
+        if self.dimensions.is_below_minimum_layout() {
+            self.outline_width = 0;
+            self.gauge_width = 0;
+            let result = match table {
+                Some(table) => self.print_table(viewer, table),
+                None => self.print_screen_impl(viewer, search_state),
+            };
+            self.finish_frame(result);
+            return;
+        }
+
+        // The table is drawn in place of the viewer, and the outline and
+        // gauge, which refer to the viewer, are hidden while it's shown.
+        self.outline_width = match outline {
+            Some(_) if table.is_none() => Self::outline_width(self.dimensions.width),
+            _ => 0,
+        };
+
+        let container_gauge = match table {
+            Some(_) => None,
+            None => self.container_gauge(viewer),
+        };
+        self.gauge_width = if container_gauge.is_some() { 1 } else { 0 };
+
+        // Render the entire frame before writing anything out, so that it
+        // can be written with a single write. Writing out the viewer and the
+        // status bar separately causes visible tearing over slow connections.
+        let result = match table {
+            Some(table) => self.print_table(viewer, table),
+            None => self.print_screen_impl(viewer, search_state),
+        };
+        let result = result
+            .and_then(|_| match container_gauge {
+                Some((position, len)) => self.print_container_gauge(viewer, position, len),
+                None => Ok(()),
+            })
+            .and_then(|_| match outline {
+                Some(outline) if self.outline_width > 0 => self.print_outline(viewer, outline),
+                _ => Ok(()),
+            })
+            .and_then(|_| match timeline {
+                Some(timeline) => self.print_timeline(viewer, timeline, pinned_rows.len()),
+                None => Ok(()),
+            })
+            .and_then(|_| self.print_pinned_rows(viewer, pinned_rows))
+            .and_then(|_| {
+                self.print_status_bar_impl(
+                    viewer,
+                    input_buffer,
+                    input_filename,
+                    search_state,
+                    message,
+                )
+            });
+        self.finish_frame(result);
     }
 
-    pub fn print_viewer(&mut self, viewer: &JsonViewer, search_state: &SearchState) {
-        match self.print_screen_impl(viewer, search_state) {
-            Ok(_) => match self.terminal.flush_contents(&mut self.stdout) {
+    fn finish_frame(&mut self, result: std::fmt::Result) {
+        match result {
+            Ok(_) => match self.flush_frame() {
                 Ok(_) => {}
                 Err(e) => {
-                    eprintln!("Error while printing viewer: {e}");
+                    eprintln!("Error while printing screen: {e}");
                 }
             },
             Err(e) => {
-                eprintln!("Error while printing viewer: {e}");
+                eprintln!("Error while printing screen: {e}");
             }
         }
     }
 
+    fn flush_frame(&mut self) -> std::io::Result<()> {
+        if self.terminal.output != self.last_frame {
+            self.stdout.write_all(self.terminal.output.as_bytes())?;
+            self.stdout.flush()?;
+            std::mem::swap(&mut self.terminal.output, &mut self.last_frame);
+        }
+        self.terminal.output.clear();
+        Ok(())
+    }
+
     pub fn print_status_bar(
         &mut self,
         viewer: &JsonViewer,
@@ -106,6 +344,10 @@ impl ScreenWriter {
         search_state: &SearchState,
         message: &Option<(String, MessageSeverity)>,
     ) {
+        if self.dimensions.is_below_minimum_layout() {
+            return;
+        }
+
         match self.print_status_bar_impl(
             viewer,
             input_buffer,
@@ -114,7 +356,7 @@ impl ScreenWriter {
             message,
         ) {
             Ok(_) => match self.terminal.flush_contents(&mut self.stdout) {
-                Ok(_) => {}
+                Ok(_) => self.invalidate_last_frame(),
                 Err(e) => {
                     eprintln!("Error while printing status bar: {e}");
                 }
@@ -125,6 +367,64 @@ impl ScreenWriter {
         }
     }
 
+    // When the screen is split, draw the view that isn't focused. This only
+    // renders it into the current frame, which gets written out by the
+    // next call to print.
+    pub fn print_unfocused_view(&mut self, viewer: &JsonViewer, search_state: &SearchState) {
+        if let Err(e) = self.print_screen_impl(viewer, search_state) {
+            eprintln!("Error while printing screen: {e}");
+        }
+    }
+
+    // The line between the two views of a split screen shows the path to
+    // the focused row of the top view, and is highlighted when the top
+    // view is the focused one.
+    pub fn print_split_separator(&mut self, viewer: &JsonViewer, row: u16, focused: bool) {
+        if let Err(e) = self.print_split_separator_impl(viewer, row, focused) {
+            eprintln!("Error while printing screen: {e}");
+        }
+    }
+
+    fn print_split_separator_impl(
+        &mut self,
+        viewer: &JsonViewer,
+        row: u16,
+        focused: bool,
+    ) -> std::fmt::Result {
+        let path_to_node = viewer
+            .flatjson
+            .build_path_to_node(PathType::StatusBar, viewer.focused_row)
+            .unwrap();
+        let label = format!("{PATH_BASE}{path_to_node}");
+        let width = self.dimensions.width as isize;
+        let truncated_label = TruncatedStrView::init_back(&label, width);
+
+        self.terminal.position_cursor(1, row + 1)?;
+        self.terminal.clear_line()?;
+        self.terminal.set_style(&terminal::Style {
+            fg: if focused {
+                terminal::DEFAULT
+            } else {
+                terminal::LIGHT_BLACK
+            },
+            inverted: true,
+            ..terminal::Style::default()
+        })?;
+        write!(
+            self.terminal,
+            "{}",
+            TruncatedStrSlice {
+                s: &label,
+                truncated_view: &truncated_label,
+            }
+        )?;
+        for _ in truncated_label.used_space().unwrap_or(0)..width {
+            self.terminal.write_char(' ')?;
+        }
+
+        self.terminal.reset_style()
+    }
+
     fn print_screen_impl(
         &mut self,
         viewer: &JsonViewer,
@@ -134,30 +434,40 @@ impl ScreenWriter {
         let mut search_matches = search_state
             .matches_iter(viewer.flatjson[line.unwrap()].range.start)
             .peekable();
-        let current_match = search_state.current_match_range();
+        let mut prev_index = viewer.top_row;
 
         let mut delta_to_focused_row = viewer.index_of_focused_row_on_screen() as isize;
 
         for row_index in 0..viewer.dimensions.height {
             match line {
                 OptionIndex::Nil => {
-                    self.terminal.position_cursor(1, row_index + 1)?;
+                    self.terminal
+                        .position_cursor(1, self.viewer_top + row_index + 1)?;
                     self.terminal.clear_line()?;
                     self.terminal.set_fg(terminal::LIGHT_BLACK)?;
                     self.terminal.write_char('~')?;
                 }
                 OptionIndex::Index(index) => {
+                    // The elements of reversed arrays are displayed before
+                    // rows that come earlier in the document, whose search
+                    // matches have already been passed over.
+                    if index < prev_index {
+                        let start = viewer.flatjson[index].range.start;
+                        search_matches = search_state.matches_iter(start).peekable();
+                    }
+                    prev_index = index;
+
                     self.print_line(
                         viewer,
                         row_index,
                         index,
                         delta_to_focused_row,
                         &mut search_matches,
-                        &current_match,
+                        search_state,
                     )?;
                     line = match viewer.mode {
-                        Mode::Line => viewer.flatjson.next_visible_row(index),
-                        Mode::Data => viewer.flatjson.next_item(index),
+                        Mode::Line => viewer.next_visible_row(index),
+                        Mode::Data => viewer.next_item(index),
                     };
                 }
             }
@@ -168,12 +478,24 @@ impl ScreenWriter {
         Ok(())
     }
 
-    pub fn get_command(&mut self, prompt: &str) -> rustyline::Result<String> {
+    // Prompt for input, with the given text already entered.
+    pub fn get_command(
+        &mut self,
+        prompt: &str,
+        initial: &str,
+        completion: Completion,
+    ) -> rustyline::Result<String> {
+        self.invalidate_last_frame();
+        if let Some(key_completer) = self.command_editor.helper_mut() {
+            key_completer.completion = completion;
+        }
         write!(self.stdout, "{}", termion::cursor::Show)?;
         let _ = self.terminal.position_cursor(1, self.dimensions.height);
         self.terminal.flush_contents(&mut self.stdout)?;
 
-        let result = self.command_editor.readline(prompt);
+        let result = self
+            .command_editor
+            .readline_with_initial(prompt, (initial, ""));
         write!(self.stdout, "{}", termion::cursor::Hide)?;
 
         let _ = self.terminal.position_cursor(1, self.dimensions.height);
@@ -183,6 +505,303 @@ impl ScreenWriter {
         result
     }
 
+    // The outline takes up a third of the screen, up to a limit, and is
+    // hidden entirely on very narrow screens.
+    fn outline_width(screen_width: u16) -> u16 {
+        let width = (screen_width / 3).min(MAX_OUTLINE_WIDTH);
+        if width < MIN_OUTLINE_WIDTH {
+            0
+        } else {
+            width
+        }
+    }
+
+    fn print_outline(&mut self, viewer: &JsonViewer, outline: &mut Outline) -> std::fmt::Result {
+        let height = viewer.dimensions.height;
+        outline.ensure_selected_is_visible(height as usize);
+
+        let separator_col = self.dimensions.width - self.outline_width + 1;
+        // Leave a space after the separator.
+        let available_space = self.outline_width as isize - 2;
+
+        for row_index in 0..height {
+            self.terminal
+                .position_cursor(separator_col, self.viewer_top + row_index + 1)?;
+            self.terminal.set_fg(terminal::LIGHT_BLACK)?;
+            write!(self.terminal, "│ ")?;
+            self.terminal.reset_style()?;
+
+            let entry = outline.top + row_index as usize;
+            let Some(index) = outline.entries.get(entry) else {
+                continue;
+            };
+
+            if entry == outline.selected {
+                self.terminal.set_style(&terminal::Style {
+                    inverted: outline.focused,
+                    bold: true,
+                    ..terminal::Style::default()
+                })?;
+            } else if viewer.flatjson[*index].is_container() {
+                self.terminal.set_fg(terminal::LIGHT_BLUE)?;
+            }
+
+            let label = outline.entry_label(&viewer.flatjson, *index);
+            let truncated_label = TruncatedStrSlice {
+                s: &label,
+                truncated_view: &TruncatedStrView::init_start(&label, available_space),
+            };
+            write!(self.terminal, "{truncated_label}")?;
+            self.terminal.reset_style()?;
+        }
+
+        Ok(())
+    }
+
+    // The table is drawn in place of the viewer, with the keys of the
+    // columns on the first line, and an element of the Array on each line
+    // after it, with its index on the left.
+    fn print_table(&mut self, viewer: &JsonViewer, table: &mut Table) -> std::fmt::Result {
+        let flatjson = &viewer.flatjson;
+        let height = viewer.dimensions.height as usize;
+        table.ensure_selected_is_visible(height.saturating_sub(1));
+        let columns = table.visible_columns(self.dimensions.width as usize);
+
+        for row_index in 0..height {
+            self.terminal
+                .position_cursor(1, self.viewer_top + row_index as u16 + 1)?;
+            self.terminal.clear_line()?;
+
+            if row_index == 0 {
+                self.print_table_cell("", table.index_width, &Style::default())?;
+                for (column, width) in columns.iter() {
+                    self.print_table_separator(false)?;
+                    let key = &table.columns[*column].key;
+                    self.print_table_cell(key, *width, &highlighting::BOLD_STYLE)?;
+                }
+                continue;
+            }
+
+            let n = table.top + row_index - 1;
+            if n >= table.num_elements() {
+                self.terminal.set_fg(terminal::LIGHT_BLACK)?;
+                self.terminal.write_char('~')?;
+                continue;
+            }
+
+            let selected = n == table.selected;
+            let index_label = format!(
+                "{:>width$}",
+                flatjson[table.element(n)].index_in_parent,
+                width = table.index_width
+            );
+            let index_style = Style {
+                fg: terminal::LIGHT_BLACK,
+                inverted: selected,
+                ..Style::default()
+            };
+            self.print_table_cell(&index_label, table.index_width, &index_style)?;
+
+            for (column, width) in columns.iter() {
+                self.print_table_separator(selected)?;
+                let (text, fg) = match table.cell(n, *column) {
+                    Some(index) => {
+                        let fg = match flatjson[index].value {
+                            Value::Null => self.theme.null,
+                            Value::Boolean => self.theme.boolean,
+                            Value::Number(_) => self.theme.number,
+                            Value::String => self.theme.string,
+                            _ => terminal::DEFAULT,
+                        };
+                        (table::cell_text(flatjson, index), fg)
+                    }
+                    None => ("", terminal::DEFAULT),
+                };
+                let style = Style {
+                    fg,
+                    inverted: selected,
+                    ..Style::default()
+                };
+                self.print_table_cell(text, *width, &style)?;
+            }
+        }
+
+        self.terminal.reset_style()
+    }
+
+    // Print text truncated or padded to fill the width of a table column.
+    fn print_table_cell(&mut self, text: &str, width: usize, style: &Style) -> std::fmt::Result {
+        self.terminal.set_style(style)?;
+        let truncated_view = TruncatedStrView::init_start(text, width as isize);
+        write!(
+            self.terminal,
+            "{}",
+            TruncatedStrSlice {
+                s: text,
+                truncated_view: &truncated_view,
+            }
+        )?;
+        let used_space = truncated_view.used_space().unwrap_or(0) as usize;
+        write!(self.terminal, "{:1$}", "", width.saturating_sub(used_space))
+    }
+
+    fn print_table_separator(&mut self, selected: bool) -> std::fmt::Result {
+        self.terminal.set_style(&Style {
+            fg: terminal::LIGHT_BLACK,
+            inverted: selected,
+            ..Style::default()
+        })?;
+        write!(self.terminal, "{}", table::COLUMN_SEPARATOR)
+    }
+
+    // The timeline is drawn on the line above the status bar (and any pinned
+    // rows), with the column containing the focused record highlighted.
+    fn print_timeline(
+        &mut self,
+        viewer: &JsonViewer,
+        timeline: &Timeline,
+        num_pinned_rows: usize,
+    ) -> std::fmt::Result {
+        let width = self.dimensions.width as usize;
+        let focused_column = timeline.column_of_row(&viewer.flatjson, viewer.focused_row, width);
+
+        self.terminal.position_cursor(
+            1,
+            self.dimensions
+                .height
+                .saturating_sub(STATUS_BAR_HEIGHT + num_pinned_rows as u16),
+        )?;
+        self.terminal.clear_line()?;
+
+        for (column, ch) in timeline.density_chars(width).into_iter().enumerate() {
+            if Some(column) == focused_column {
+                self.terminal.set_style(&Style {
+                    fg: terminal::YELLOW,
+                    inverted: true,
+                    ..Style::default()
+                })?;
+            } else {
+                self.terminal.set_style(&Style {
+                    fg: terminal::LIGHT_BLUE,
+                    ..Style::default()
+                })?;
+            }
+            self.terminal.write_char(ch)?;
+        }
+
+        self.terminal.reset_style()
+    }
+
+    // Pinned rows are drawn on the lines right above the status bar, as
+    // "path: value", so that they stay visible while navigating elsewhere.
+    fn print_pinned_rows(
+        &mut self,
+        viewer: &JsonViewer,
+        pinned_rows: &[Index],
+    ) -> std::fmt::Result {
+        let first_line = self
+            .dimensions
+            .height
+            .saturating_sub(STATUS_BAR_HEIGHT + pinned_rows.len() as u16)
+            + 1;
+        let width = self.dimensions.width as isize;
+
+        for (i, index) in pinned_rows.iter().enumerate() {
+            self.terminal.position_cursor(1, first_line + i as u16)?;
+            self.terminal.clear_line()?;
+
+            let row = &viewer.flatjson[*index];
+            let path = viewer
+                .flatjson
+                .build_path_to_node(PathType::Dot, *index)
+                .unwrap();
+            let path = format!("{path}: ");
+            let truncated_path = TruncatedStrView::init_start(&path, width);
+            self.terminal.set_style(&self.theme.key)?;
+            write!(
+                self.terminal,
+                "{}",
+                TruncatedStrSlice {
+                    s: &path,
+                    truncated_view: &truncated_path,
+                }
+            )?;
+
+            let available_space = width - truncated_path.used_space().unwrap_or(width);
+            let value = &viewer.flatjson.1[row.range.clone()];
+            let truncated_value = TruncatedStrView::init_start(value, available_space);
+            self.terminal.reset_style()?;
+            self.terminal.set_fg(match row.value {
+                Value::Null => self.theme.null,
+                Value::Boolean => self.theme.boolean,
+                Value::Number(_) => self.theme.number,
+                Value::String => self.theme.string,
+                _ => terminal::DEFAULT,
+            })?;
+            write!(
+                self.terminal,
+                "{}",
+                TruncatedStrSlice {
+                    s: value,
+                    truncated_view: &truncated_value,
+                }
+            )?;
+        }
+
+        self.terminal.reset_style()
+    }
+
+    // When the focused row is inside a container that is too big to fit on
+    // the screen, returns the index of the focused row in the container
+    // and the number of children it has.
+    fn container_gauge(&self, viewer: &JsonViewer) -> Option<(usize, usize)> {
+        if !self.show_container_gauge {
+            return None;
+        }
+
+        let row = &viewer.flatjson[viewer.focused_row];
+        let OptionIndex::Index(parent) = row.parent else {
+            return None;
+        };
+        let num_children = viewer.flatjson.num_children(parent);
+        if num_children <= viewer.dimensions.height as usize {
+            return None;
+        }
+
+        // The closing brace of a container is part of the container itself.
+        let position = if row.is_closing_of_container() {
+            viewer.flatjson[row.pair_index().unwrap()].index_in_parent
+        } else {
+            row.index_in_parent
+        };
+
+        Some((position, num_children))
+    }
+
+    fn print_container_gauge(
+        &mut self,
+        viewer: &JsonViewer,
+        position: usize,
+        len: usize,
+    ) -> std::fmt::Result {
+        let height = viewer.dimensions.height;
+        let col = self.dimensions.width - self.outline_width;
+        let thumb = gauge_thumb(position, len, height);
+
+        for row_index in 0..height {
+            self.terminal
+                .position_cursor(col, self.viewer_top + row_index + 1)?;
+            if thumb.contains(&row_index) {
+                self.terminal.reset_style()?;
+                self.terminal.write_char('┃')?;
+            } else {
+                self.terminal.set_fg(terminal::LIGHT_BLACK)?;
+                self.terminal.write_char('│')?;
+            }
+        }
+        self.terminal.reset_style()
+    }
+
     fn print_line(
         &mut self,
         viewer: &JsonViewer,
@@ -190,18 +809,23 @@ impl ScreenWriter {
         index: Index,
         delta_to_focused_row: isize,
         search_matches: &mut Peekable<MatchRangeIter>,
-        focused_search_match: &Range<usize>,
+        search_state: &SearchState,
     ) -> std::fmt::Result {
+        let focused_search_match = search_state.current_match_range();
+        let search_match_to_show = search_state.current_match_range_to_show();
         let is_focused = index == viewer.focused_row;
 
-        self.terminal.position_cursor(1, screen_index + 1)?;
+        self.terminal
+            .position_cursor(1, self.viewer_top + screen_index + 1)?;
         self.terminal.clear_line()?;
         let row = &viewer.flatjson[index];
 
         let indentation_level =
             row.depth
                 .saturating_sub(self.indentation_reduction as usize) as isize;
-        let indentation = indentation_level * TAB_SIZE;
+        let dense = viewer.dense && viewer.mode == Mode::Data;
+        let tab_size = if dense { DENSE_TAB_SIZE } else { TAB_SIZE };
+        let indentation = indentation_level * tab_size;
 
         let focused = is_focused;
 
@@ -220,14 +844,16 @@ impl ScreenWriter {
             // we need to get the OpenContainer row before we check if a row
             // is the last row in a container, and thus whether we should
             // print a trailing comma or not.
-            let row_root = if row.is_closing_of_container() {
-                &viewer.flatjson[row.pair_index().unwrap()]
+            let root_index = if row.is_closing_of_container() {
+                row.pair_index().unwrap()
             } else {
-                row
+                index
             };
 
             // Don't print trailing commas after top level elements.
-            if row_root.parent.is_some() && row_root.next_sibling.is_some() {
+            if viewer.flatjson[root_index].parent.is_some()
+                && viewer.next_sibling(root_index).is_some()
+            {
                 if row.is_opening_of_container() && row.is_expanded() {
                     // Don't print trailing commas after { or [, but
                     // if it's collapsed, we do print one after the } or ].
@@ -247,37 +873,96 @@ impl ScreenWriter {
         );
 
         if self.show_line_numbers {
-            absolute_line_number = Some(index + 1);
+            absolute_line_number = match self.number_mode {
+                NumberMode::Line => Some(index + 1),
+                // Closing delimiters show the same index as their opening pair.
+                NumberMode::Index if row.is_closing_of_container() => {
+                    Some(viewer.flatjson[row.pair_index().unwrap()].index_in_parent)
+                }
+                NumberMode::Index => Some(row.index_in_parent),
+            };
         }
         if self.show_relative_line_numbers {
             relative_line_number = Some(delta_to_focused_row.unsigned_abs());
         }
 
+        let aligned_key_width = match row.parent {
+            OptionIndex::Index(parent)
+                if self.align_keys && viewer.mode == Mode::Line && row.key_range.is_some() =>
+            {
+                let widest_key = self
+                    .widest_keys
+                    .entry(parent)
+                    .or_insert_with(|| Self::widest_key(&viewer.flatjson, parent));
+                Some(*widest_key)
+            }
+            _ => None,
+        };
+
         let mut line = lp::LinePrinter {
             mode: viewer.mode,
+            dense,
             terminal: &mut self.terminal,
 
             flatjson: &viewer.flatjson,
             row,
+            folded_run_length: viewer.flatjson.folded_run_length(index),
             line_number: LineNumber {
                 absolute: absolute_line_number,
                 relative: relative_line_number,
                 max_width: max_line_number_width,
             },
 
-            width: self.dimensions.width as isize,
+            show_diff_gutter: !self.diff_markers.is_empty(),
+            diff_marker: self.diff_markers.get(&index).copied(),
+
+            show_selection_gutter: !viewer.selected_rows.is_empty(),
+            selected: viewer
+                .selected_rows
+                .contains(&if row.is_closing_of_container() {
+                    row.pair_index().unwrap()
+                } else {
+                    index
+                }),
+
+            reversed: viewer.is_reversed(index),
+            show_previews: self.show_previews,
+            preview_order: self.preview_order,
+            show_invisibles: self.show_invisibles,
+            show_sizes: self.show_sizes,
+            show_number_types: self.show_number_types,
+            number_base: self.number_base,
+            date_display: if self.show_dates {
+                Some(&self.date_display)
+            } else {
+                None
+            },
+            highlight_focused_line: self.highlight_focused_line,
+            aligned_key_width,
+            indent_guide_colors: if self.show_indent_guides {
+                &self.indent_guide_colors
+            } else {
+                &[]
+            },
+            theme: &self.theme,
+            truncation: self.truncation,
+
+            width: (self.dimensions.width - self.outline_width - self.gauge_width) as isize,
             indentation,
 
             focused,
+            sub_focus: if focused { viewer.sub_focus } else { None },
             focused_because_matching_container_pair,
             trailing_comma,
 
             search_matches: Some(search_matches_copy),
-            focused_search_match,
+            focused_search_match: &focused_search_match,
+            search_match_to_show: &search_match_to_show,
             // This is only used internally and really shouldn't be exposed.
             emphasize_focused_search_match: true,
 
             cached_truncated_value: Some(self.truncated_row_value_views.entry(index)),
+            cached_preview: Some(self.container_previews.entry(index)),
         };
 
         // TODO: Handle error here? Or is never an error because writes
@@ -289,6 +974,20 @@ impl ScreenWriter {
         Ok(())
     }
 
+    // The display width of the widest key, including its quotes, in the
+    // object with the given opening row.
+    fn widest_key(flatjson: &FlatJson, object: Index) -> isize {
+        let mut widest = 0;
+        let mut child = flatjson[object].first_child();
+        while let OptionIndex::Index(i) = child {
+            if let Some(key_range) = &flatjson[i].key_range {
+                widest = widest.max(display_width(&flatjson.1[key_range.clone()]));
+            }
+            child = flatjson[i].next_sibling;
+        }
+        widest
+    }
+
     fn line_primitive_value_ref<'a, 'b>(
         &'a self,
         row: &'a Row,
@@ -331,7 +1030,7 @@ impl ScreenWriter {
 
         let path_to_node = viewer
             .flatjson
-            .build_path_to_node(PathType::DotWithTopLevelIndex, viewer.focused_row)
+            .build_path_to_node(PathType::StatusBar, viewer.focused_row)
             .unwrap();
         self.print_path_to_node_and_file_name(
             &path_to_node,
@@ -342,7 +1041,9 @@ impl ScreenWriter {
         self.terminal.position_cursor(1, self.dimensions.height)?;
         self.terminal.clear_line()?;
 
-        if let Some((contents, severity)) = message {
+        if let Some(prompt) = &self.search_prompt {
+            self.terminal.write_str(prompt)?;
+        } else if let Some((contents, severity)) = message {
             self.terminal.set_style(&terminal::Style {
                 fg: severity.color(),
                 ..terminal::Style::default()
@@ -354,24 +1055,35 @@ impl ScreenWriter {
             self.terminal.write_str(&search_state.search_term)?;
 
             if let Some((match_num, just_wrapped)) = search_state.active_search_state() {
-                // Print out which match we're on:
-                let match_tracker = format!("[{}/{}]", match_num + 1, search_state.num_matches());
-                self.terminal.position_cursor(
-                    self.dimensions.width
-                        - (1 + MAX_BUFFER_SIZE as u16)
-                        - (3 + match_tracker.len() as u16 + 3),
-                    self.dimensions.height,
-                )?;
-
-                let wrapped_char = if just_wrapped { 'W' } else { ' ' };
-                write!(self.terminal, " {wrapped_char} {match_tracker}")?;
+                // Print out which match we're on, and how many matches aren't
+                // hidden inside collapsed containers:
+                let match_tracker = format!(
+                    "[{}/{}, {} visible]",
+                    match_num + 1,
+                    search_state.num_matches(),
+                    search_state.num_visible_matches(&viewer.flatjson),
+                );
+                // It's left out entirely on narrow screens.
+                let tracker_col = self
+                    .dimensions
+                    .width
+                    .checked_sub(1 + MAX_BUFFER_SIZE as u16)
+                    .and_then(|col| col.checked_sub(3 + match_tracker.len() as u16 + 3))
+                    .filter(|&col| col > 0);
+                if let Some(tracker_col) = tracker_col {
+                    self.terminal
+                        .position_cursor(tracker_col, self.dimensions.height)?;
+
+                    let wrapped_char = if just_wrapped { 'W' } else { ' ' };
+                    write!(self.terminal, " {wrapped_char} {match_tracker}")?;
+                }
             }
         } else {
             write!(self.terminal, ":")?;
         }
 
         self.terminal.position_cursor(
-            // TODO: This can overflow on very skinny screens (2-3 columns).
+            // The status bar isn't drawn on screens narrower than this.
             self.dimensions.width - (1 + MAX_BUFFER_SIZE as u16),
             self.dimensions.height,
         )?;
@@ -379,7 +1091,13 @@ impl ScreenWriter {
             .write_str(std::str::from_utf8(input_buffer).unwrap())?;
 
         // Position the cursor better for random debugging prints. (2 so it's after ':')
-        self.terminal.position_cursor(2, self.dimensions.height)?;
+        // When typing a search, put it after the search input instead.
+        let cursor_col = match &self.search_prompt {
+            Some(prompt) => 1 + display_width(prompt) as u16,
+            None => 2,
+        };
+        self.terminal
+            .position_cursor(cursor_col, self.dimensions.height)?;
 
         Ok(())
     }
@@ -395,7 +1113,7 @@ impl ScreenWriter {
         width: isize,
     ) -> std::fmt::Result {
         let base_len = PATH_BASE.len() as isize;
-        let path_display_width = UnicodeWidthStr::width(path_to_node) as isize;
+        let path_display_width = display_width(path_to_node);
         let row = self.dimensions.height - 1;
 
         let space_available_for_filename =
@@ -484,14 +1202,15 @@ impl ScreenWriter {
 
     pub fn scroll_focused_line(&mut self, viewer: &JsonViewer, count: usize, to_right: bool) {
         let row = viewer.focused_row;
-        let tsv = self.truncated_row_value_views.get(&row);
-        if let Some(tsv) = tsv {
+        let tsv = self
+            .truncated_row_value_views
+            .get(&row)
+            .map(|cached| cached.view());
+        if let Some(mut tsv) = tsv {
             if tsv.range.is_none() {
                 return;
             }
 
-            // Make tsv not a reference.
-            let mut tsv = *tsv;
             let value_ref = self
                 .line_primitive_value_ref(&viewer.flatjson[row], viewer)
                 .unwrap();
@@ -500,27 +1219,29 @@ impl ScreenWriter {
             } else {
                 tsv = tsv.scroll_left(value_ref, count);
             }
+            // Scrolling moves the anchor to what's shown now.
             self.truncated_row_value_views
-                .insert(viewer.focused_row, tsv);
+                .insert(viewer.focused_row, AnchoredStrView::new(tsv));
         }
     }
 
     pub fn scroll_focused_line_to_an_end(&mut self, viewer: &JsonViewer) {
         let row = viewer.focused_row;
-        let tsv = self.truncated_row_value_views.get(&row);
-        if let Some(tsv) = tsv {
+        let tsv = self
+            .truncated_row_value_views
+            .get(&row)
+            .map(|cached| cached.view());
+        if let Some(mut tsv) = tsv {
             if tsv.range.is_none() {
                 return;
             }
 
-            // Make tsv not a reference.
-            let mut tsv = *tsv;
             let value_ref = self
                 .line_primitive_value_ref(&viewer.flatjson[row], viewer)
                 .unwrap();
             tsv = tsv.jump_to_an_end(value_ref);
             self.truncated_row_value_views
-                .insert(viewer.focused_row, tsv);
+                .insert(viewer.focused_row, AnchoredStrView::new(tsv));
         }
     }
 
@@ -530,10 +1251,11 @@ impl ScreenWriter {
         focused_search_range: Range<usize>,
     ) {
         let row = viewer.focused_row;
-        let tsv = self.truncated_row_value_views.get(&row);
-        if let Some(tsv) = tsv {
-            // Make tsv not a reference.
-            let mut tsv = *tsv;
+        let tsv = self
+            .truncated_row_value_views
+            .get(&row)
+            .map(|cached| cached.view());
+        if let Some(mut tsv) = tsv {
             if tsv.range.is_none() {
                 return;
             }
@@ -566,7 +1288,137 @@ impl ScreenWriter {
             tsv = tsv.focus(value_ref, &offset_focused_range);
 
             self.truncated_row_value_views
-                .insert(viewer.focused_row, tsv);
+                .insert(viewer.focused_row, AnchoredStrView::new(tsv));
+        }
+    }
+}
+
+// The rows of a gauge of the given height that represent the portion of a
+// container with len children that is around the child at position, in the
+// same way as the thumb of a scrollbar.
+fn gauge_thumb(position: usize, len: usize, height: u16) -> Range<u16> {
+    let height = height as usize;
+    let thumb_height = (height * height / len.max(1)).clamp(1, height);
+    let last_position = len.saturating_sub(1).max(1);
+    let start =
+        (position.min(last_position) * (height - thumb_height) + last_position / 2) / last_position;
+    start as u16..(start + thumb_height) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headless::{run_jless, screen_after};
+    use crate::rng::Rng;
+
+    #[test]
+    fn test_gauge_thumb() {
+        assert_eq!(0..1, gauge_thumb(0, 1000, 20));
+        assert_eq!(19..20, gauge_thumb(999, 1000, 20));
+        assert_eq!(10..11, gauge_thumb(500, 1000, 20));
+        assert_eq!(0..10, gauge_thumb(0, 40, 20));
+        assert_eq!(5..15, gauge_thumb(20, 40, 20));
+        assert_eq!(10..20, gauge_thumb(39, 40, 20));
+    }
+
+    #[test]
+    fn test_number_mode_index() {
+        const JSON: &str = r#"{"a": [10, 20, {"b": true}], "c": "x"}"#;
+        let screen_after = |commands: &str| {
+            let commands = format!(":set nogauge | :set numbermode=index | {commands}");
+            screen_after("40x9", JSON, &commands)
+        };
+
+        // Each node is numbered by its index in its parent.
+        let expected = [
+            " 0 ▽ (2) {a: […], c: \"x\"}",
+            " 0   ▽ a: (3) [10, 20, {…}]",
+            " 0       [0]: 10",
+            " 1     ▶ [1]: 20",
+            " 2     ▽ [2]: (1) {b: true}",
+            " 0         b: true",
+            " 1     c: \"x\"",
+            "input.a[1/3]                   test.json",
+            ":",
+        ];
+        assert_eq!(expected.join("\n"), screen_after("3j"));
+
+        // In line mode, closing brackets get the index of their container.
+        let screen = screen_after("m");
+        assert!(
+            screen.contains(" 2       {\n 0         \"b\": true\n 2       }\n"),
+            "{:?}",
+            screen
+        );
+
+        // Line numbers are shown again with numbermode=line.
+        let screen = screen_after(":set numbermode=line");
+        assert!(screen.contains(" 9     c: \"x\"\n"), "{:?}", screen);
+    }
+
+    #[test]
+    fn test_dense_mode() {
+        const JSON: &str = r#"{"a": [10, 20, {"b": true}], "c": "x"}"#;
+        let run = |args: &[&str], commands: &str| {
+            let commands = format!(":set nogauge | :set nonumber | {commands}");
+            let args = [&["--screen-size", "40x9", "--cmd", &commands][..], args].concat();
+            run_jless(&args, JSON, &[]).unwrap()
+        };
+
+        // Array indexes are hidden, and nested values indented by one space.
+        let expected = [
+            "▼ (2) {a: […], c: \"x\"}",
+            " ▽ a: (3) [10, 20, {…}]",
+            "    10",
+            "    20",
+            "  ▽ (1) {b: true}",
+            "     b: true",
+            "   c: \"x\"",
+            "input                          test.json",
+        ];
+        let dense = expected.join("\n") + "\n:";
+        assert_eq!(dense, run(&["--dense"], ""));
+        assert_eq!(dense, run(&[], ":set dense"));
+        assert_eq!(dense, run(&[], ":set dense!"));
+
+        let screen = run(&["--dense"], ":set nodense");
+        assert!(screen.contains("      [0]: 10\n"), "{:?}", screen);
+
+        // Line mode is unaffected.
+        assert_eq!(run(&[], "m"), run(&["--dense"], "m"));
+    }
+
+    #[test]
+    fn test_tiny_screens_dont_panic() {
+        const JSON: &str = r#"{"a": [1, 2, {"b": "hello world"}], "c": {"d": null, "e": 1.5}}"#;
+        const SCRIPTS: [&str; 6] = [
+            "j | c | /l | n | N",
+            "o | G | k | o",
+            "j | :pin | j | :pin | :timeline | G",
+            ":set alignkeys | :set indentguides | :set relativenumber | :set sizes | 3j",
+            "m | :set dense | :set gauge | l | x | j | x",
+            ":split | G | :only | :set incsearch | /b",
+        ];
+
+        let check = |width: u16, height: u16| {
+            for script in SCRIPTS {
+                let size = format!("{width}x{height}");
+                let screen = screen_after(&size, JSON, script);
+                assert_eq!(height as usize, screen.lines().count(), "{:?}", size);
+            }
+        };
+
+        for width in 1..=24 {
+            for height in 1..=6 {
+                check(width, height);
+            }
+        }
+
+        // And some larger sizes, picked with a fixed seed so that they're
+        // the same every time.
+        let mut rng = Rng::new(42);
+        for _ in 0..50 {
+            check(1 + rng.below(120) as u16, 1 + rng.below(30) as u16);
         }
     }
 }
@@ -0,0 +1,72 @@
+use std::collections::VecDeque;
+
+use termion::event::Key;
+
+use crate::input::TuiEvent;
+
+// Commands passed via `--cmd` that are run after the input is loaded,
+// before handling any user input, e.g.:
+//
+//   jless --cmd ':set dense | /error | n' data.json
+//
+// Commands are separated by " | ". A command starting with ':' is run
+// as if typed at the command prompt, and one starting with '/' or '?'
+// starts a search. Anything else is treated as a sequence of key presses.
+//
+// A script is run by feeding its key presses through the normal event
+// loop, so they behave exactly as if they had been typed. The text that
+// would have been entered at the prompt for ':', '/' and '?' is returned
+// by the App's readline instead of prompting the user.
+pub struct Script {
+    pub events: Vec<TuiEvent>,
+    pub prompt_input: VecDeque<String>,
+}
+
+const COMMAND_SEPARATOR: &str = " | ";
+
+impl Script {
+    pub fn parse<'a>(scripts: impl IntoIterator<Item = &'a String>) -> Script {
+        let mut events = vec![];
+        let mut prompt_input = VecDeque::new();
+
+        for command in scripts
+            .into_iter()
+            .flat_map(|script| script.split(COMMAND_SEPARATOR))
+        {
+            let command = command.trim();
+
+            match command.chars().next() {
+                Some(prompt @ (':' | '/' | '?')) => {
+                    events.push(TuiEvent::KeyEvent(Key::Char(prompt)));
+                    prompt_input.push_back(command[1..].to_string());
+                }
+                _ => {
+                    events.extend(command.chars().map(|ch| TuiEvent::KeyEvent(Key::Char(ch))));
+                }
+            }
+        }
+
+        Script {
+            events,
+            prompt_input,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_script() {
+        let scripts = vec![":set dense | /a|b | 3n".to_string(), " ?error ".to_string()];
+        let script = Script::parse(&scripts);
+
+        assert_eq!(
+            "[KeyEvent(Char(':')), KeyEvent(Char('/')), KeyEvent(Char('3')), \
+             KeyEvent(Char('n')), KeyEvent(Char('?'))]",
+            format!("{:?}", script.events),
+        );
+        assert_eq!(script.prompt_input, vec!["set dense", "a|b", "error"]);
+    }
+}
@@ -1,9 +1,11 @@
 use std::borrow::Cow;
+use std::collections::HashMap;
 use std::ops::Range;
 
 use regex::{Captures, Regex, RegexBuilder};
 
 use crate::flatjson::{FlatJson, Index};
+use crate::unicodefold::{self, FoldedText, Folding};
 
 #[derive(PartialEq, Eq, Debug, Copy, Clone)]
 pub enum SearchDirection {
@@ -26,12 +28,43 @@ pub enum JumpDirection {
     Prev,
 }
 
+// What to do when jumping to a match inside a collapsed container.
+#[derive(PartialEq, Eq, Debug, Copy, Clone)]
+pub enum HiddenMatches {
+    // Focus the outermost collapsed container containing the match.
+    Collapsed,
+    // Expand all the containers containing the match, and focus it.
+    Expand,
+    // Skip past the match, only jumping to visible matches.
+    Skip,
+}
+
+// Where to end up after jumping to a match, specified after any flags,
+// e.g., `/error/e` or `/error/+2`.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub enum SearchOffset {
+    #[default]
+    Start,
+    // Scroll long values so that the end of the match is shown.
+    End,
+    // Focus the line this many lines after (or before, if negative) the
+    // line containing the match.
+    Lines(isize),
+}
+
 pub struct SearchState {
     pub direction: SearchDirection,
 
     pub search_term: String,
+    pub offset: SearchOffset,
+    // What to do with matches inside of collapsed containers, set from
+    // `:set hiddenmatches`.
+    pub hidden_matches: HiddenMatches,
 
     matches: Vec<Range<usize>>,
+    // The range of the first capture group of each match, if the
+    // pattern has any capture groups; empty otherwise.
+    captures: Vec<Option<Range<usize>>>,
 
     immediate_state: ImmediateSearchState,
     pub ever_searched: bool,
@@ -47,6 +80,26 @@ pub enum ImmediateSearchState {
     },
 }
 
+// Options that can be specified by appending flags to the end of a
+// search, e.g., `/abc/sw`, or with a prefix in the case of fixed_string.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+struct SearchFlags {
+    case_sensitive: bool,
+    fixed_string: bool,
+    whole_word: bool,
+    folding: Option<Folding>,
+    offset: SearchOffset,
+}
+
+// Defaults for the flags of every search, so that, e.g., /id matches
+// whole words without having to type /id/w. Set with --whole-word and
+// --fixed-strings, or at runtime with :set wholeword and :set fixedstrings.
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Default)]
+pub struct SearchDefaults {
+    pub whole_word: bool,
+    pub fixed_string: bool,
+}
+
 pub type MatchRangeIter<'a> = std::slice::Iter<'a, Range<usize>>;
 const STATIC_EMPTY_SLICE: &[Range<usize>] = &[];
 
@@ -58,40 +111,118 @@ lazy_static::lazy_static! {
     static ref UPPER_CASE: Regex = Regex::new("[[:upper:]]").unwrap();
 }
 
+lazy_static::lazy_static! {
+    // Offsets from less and vim that aren't supported: unsigned line
+    // offsets, and character offsets from the end of a match.
+    static ref UNSUPPORTED_OFFSET: Regex = Regex::new(r"^e?[+-]?[0-9]+$").unwrap();
+}
+
 impl SearchState {
     pub fn empty() -> SearchState {
         SearchState {
             direction: SearchDirection::Forward,
             search_term: "".to_owned(),
+            offset: SearchOffset::Start,
+            hidden_matches: HiddenMatches::Collapsed,
             matches: vec![],
+            captures: vec![],
             immediate_state: ImmediateSearchState::NotSearching,
             ever_searched: false,
         }
     }
 
-    fn extract_search_term_and_case_sensitivity(search_input: &str) -> (&str, bool) {
-        let regex_input;
+    // Splits the search input into the actual search term and any flags
+    // specified after a trailing '/':
+    //
+    // - s: force a case sensitive search
+    // - w: only match whole words
+    // - W: match anywhere, even if whole words are matched by default
+    // - n: match regardless of Unicode normalization form
+    // - d: like n, but also ignore diacritics
+    //
+    // Followed by an optional offset (like in less and vim):
+    //
+    // - e: show the end of the match
+    // - +N or -N: focus the line N lines after or before the match
+    //
+    // The sign of a line offset is required, and other offsets from less
+    // and vim, like `/pattern/2` or `/pattern/e+1`, aren't supported. These
+    // are rejected, rather than searched for as part of the pattern, so to
+    // search for a path or date ending in a number, like `/api/v1/42` or
+    // `2023/10/16`, add another '/' to the end.
+    //
+    // A search term starting with `\V` (like Vim's "very nomagic" mode) will
+    // be searched for literally, rather than interpreted as a regex, and one
+    // starting with `\v` (like Vim's "very magic" mode) will be interpreted
+    // as a regex, even if fixed strings are searched for by default.
+    //
+    // Flags that aren't given fall back to the defaults.
+    fn extract_search_term_and_flags(
+        search_input: &str,
+        defaults: SearchDefaults,
+    ) -> Result<(&str, SearchFlags), String> {
+        let mut regex_input = search_input;
+        let mut flags = SearchFlags {
+            whole_word: defaults.whole_word,
+            fixed_string: defaults.fixed_string,
+            ..SearchFlags::default()
+        };
         let mut case_sensitive_specified = false;
 
-        if let Some(stripped_of_slash) = search_input.strip_suffix('/') {
-            regex_input = stripped_of_slash;
-        } else if let Some(stripped_of_slash_s) = search_input.strip_suffix("/s") {
-            regex_input = stripped_of_slash_s;
-            case_sensitive_specified = true;
-        } else {
-            regex_input = search_input;
+        if let Some((term, suffix)) = search_input.rsplit_once('/') {
+            let offset_start = suffix
+                .find(|ch| !"swWnd".contains(ch))
+                .unwrap_or(suffix.len());
+            let (flag_chars, offset) = suffix.split_at(offset_start);
+
+            if let Some(offset) = Self::parse_offset(offset)? {
+                regex_input = term;
+                case_sensitive_specified = flag_chars.contains('s');
+                if flag_chars.contains('W') {
+                    flags.whole_word = false;
+                } else if flag_chars.contains('w') {
+                    flags.whole_word = true;
+                }
+                if flag_chars.contains('d') {
+                    flags.folding = Some(Folding::StripDiacritics);
+                } else if flag_chars.contains('n') {
+                    flags.folding = Some(Folding::Normalize);
+                }
+                flags.offset = offset;
+            }
         }
 
-        let case_sensitive = if case_sensitive_specified {
-            true
-        } else {
-            UPPER_CASE.is_match(regex_input)
-        };
+        if let Some(fixed_string) = regex_input.strip_prefix("\\V") {
+            regex_input = fixed_string;
+            flags.fixed_string = true;
+        } else if let Some(regex) = regex_input.strip_prefix("\\v") {
+            regex_input = regex;
+            flags.fixed_string = false;
+        }
+
+        flags.case_sensitive = case_sensitive_specified || UPPER_CASE.is_match(regex_input);
 
-        (regex_input, case_sensitive)
+        Ok((regex_input, flags))
+    }
+
+    // Parses the offset at the end of a search, if the end of the search
+    // is an offset, rather than part of the pattern. Offsets that aren't
+    // supported are an error.
+    fn parse_offset(offset: &str) -> Result<Option<SearchOffset>, String> {
+        match offset {
+            "" => Ok(Some(SearchOffset::Start)),
+            "e" => Ok(Some(SearchOffset::End)),
+            _ => match offset.parse::<isize>() {
+                Ok(lines) if offset.starts_with(['+', '-']) => Ok(Some(SearchOffset::Lines(lines))),
+                _ if UNSUPPORTED_OFFSET.is_match(offset) => Err(format!(
+                    "Invalid search offset: {offset}; use e, +N or -N, or end the pattern with '/'"
+                )),
+                _ => Ok(None),
+            },
+        }
     }
 
-    fn invert_square_and_curly_bracket_escaping(regex: &str) -> Cow<str> {
+    fn invert_square_and_curly_bracket_escaping(regex: &str) -> Cow<'_, str> {
         SQUARE_AND_CURLY_BRACKETS.replace_all(regex, |caps: &Captures| match &caps[0] {
             "\\[" => "[".to_owned(),
             "[" => "\\[".to_owned(),
@@ -105,38 +236,139 @@ impl SearchState {
         })
     }
 
+    // Like initialize_search, but finds the matches of case insensitive
+    // searches for words (or parts of words) using an index, if there is
+    // one, rather than scanning the whole document.
+    pub fn initialize_search_using_index(
+        search_input: String,
+        haystack: &str,
+        index: Option<&SearchIndex>,
+        direction: SearchDirection,
+        defaults: SearchDefaults,
+    ) -> Result<SearchState, String> {
+        let (search_term, flags) = Self::extract_search_term_and_flags(&search_input, defaults)?;
+        let is_literal = flags.fixed_string || regex::escape(search_term) == search_term;
+        let can_use_index =
+            is_literal && !flags.case_sensitive && !flags.whole_word && flags.folding.is_none();
+
+        match index
+            .filter(|_| can_use_index)
+            .and_then(|index| index.find(search_term))
+        {
+            Some(matches) => Ok(SearchState {
+                direction,
+                search_term: search_term.to_owned(),
+                offset: flags.offset,
+                hidden_matches: HiddenMatches::Collapsed,
+                matches,
+                captures: vec![],
+                immediate_state: ImmediateSearchState::NotSearching,
+                ever_searched: true,
+            }),
+            None => Self::initialize_search(search_input, haystack, direction, defaults),
+        }
+    }
+
     pub fn initialize_search(
         search_input: String,
         haystack: &str,
         direction: SearchDirection,
+        defaults: SearchDefaults,
     ) -> Result<SearchState, String> {
-        let (regex_input, case_sensitive) =
-            Self::extract_search_term_and_case_sensitivity(&search_input);
+        let (regex_input, flags) = Self::extract_search_term_and_flags(&search_input, defaults)?;
 
         if regex_input.is_empty() {
             return Ok(Self::empty());
         }
 
+        // The pattern is folded the same way as the document, so that it
+        // matches the folded copy of it.
+        let folded_input = match flags.folding {
+            Some(folding) => Cow::Owned(unicodefold::fold_str(regex_input, folding)),
+            None => Cow::Borrowed(regex_input),
+        };
+
+        let mut pattern = if flags.fixed_string {
+            Cow::Owned(regex::escape(&folded_input))
+        } else {
+            Self::invert_square_and_curly_bracket_escaping(&folded_input)
+        };
+
+        if flags.whole_word {
+            pattern = Cow::Owned(format!(r"\b(?:{pattern})\b"));
+        }
+
         // The default Display implementation for these errors spills
         // onto multiple lines.
-        let inverted = Self::invert_square_and_curly_bracket_escaping(regex_input);
-
-        let regex = RegexBuilder::new(&inverted)
-            .case_insensitive(!case_sensitive)
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(!flags.case_sensitive)
             .build()
             .map_err(|e| format!("{e}").replace('\n', " "))?;
 
-        let matches: Vec<Range<usize>> = regex.find_iter(haystack).map(|m| m.range()).collect();
+        let folded = flags
+            .folding
+            .map(|folding| FoldedText::new(haystack, folding));
+        let searched_text = folded.as_ref().map_or(haystack, |folded| &folded.text);
+
+        // Finding capture groups is slower, so only do it when necessary.
+        let (mut matches, mut captures): (Vec<_>, Vec<_>) = if regex.captures_len() > 1 {
+            regex
+                .captures_iter(searched_text)
+                .map(|caps| (caps.get(0).unwrap().range(), caps.get(1).map(|m| m.range())))
+                .unzip()
+        } else {
+            let matches = regex.find_iter(searched_text).map(|m| m.range()).collect();
+            (matches, vec![])
+        };
+
+        // Map matches in the folded copy of the document back to the
+        // document itself.
+        if let Some(folded) = &folded {
+            for range in matches.iter_mut().chain(captures.iter_mut().flatten()) {
+                *range = folded.original_range(range.clone());
+            }
+        }
 
         Ok(SearchState {
             direction,
             search_term: regex_input.to_owned(),
+            offset: flags.offset,
+            hidden_matches: HiddenMatches::Collapsed,
             matches,
+            captures,
             immediate_state: ImmediateSearchState::NotSearching,
             ever_searched: true,
         })
     }
 
+    // Count the matches of a search within a single value, along with its
+    // key and all of its descendants, given the index of its opening row.
+    // This doesn't affect the current search. Returns the number of
+    // matches, and the number of rows they're in.
+    pub fn count_matches_in_subtree(
+        search_input: String,
+        flatjson: &FlatJson,
+        index: Index,
+        defaults: SearchDefaults,
+    ) -> Result<(usize, usize), String> {
+        let row = &flatjson[index];
+        let start = row.range_represented_by_row().start;
+        let end = row.range.end;
+
+        let mut search = Self::initialize_search(
+            search_input,
+            &flatjson.1[start..end],
+            SearchDirection::Forward,
+            defaults,
+        )?;
+        for range in search.matches.iter_mut() {
+            *range = range.start + start..range.end + start;
+        }
+
+        let num_rows = search.rows_with_matches(flatjson).len();
+        Ok((search.num_matches(), num_rows))
+    }
+
     pub fn showing_matches(&self) -> bool {
         match self.immediate_state {
             ImmediateSearchState::NotSearching => false,
@@ -164,6 +396,33 @@ impl SearchState {
         !self.matches.is_empty()
     }
 
+    // Whether any match starts within the given range of the document.
+    pub fn any_match_in(&self, range: &Range<usize>) -> bool {
+        let first = self
+            .matches
+            .partition_point(|match_range| match_range.start < range.start);
+        self.matches
+            .get(first)
+            .map_or(false, |match_range| match_range.start < range.end)
+    }
+
+    // The number of matches that aren't inside a collapsed container.
+    pub fn num_visible_matches(&self, flatjson: &FlatJson) -> usize {
+        (0..self.matches.len())
+            .filter(|&match_index| self.is_match_visible(flatjson, match_index))
+            .count()
+    }
+
+    // The rows containing matches, in document order, with each row only
+    // listed once, even if it has multiple matches.
+    pub fn rows_with_matches(&self, flatjson: &FlatJson) -> Vec<Index> {
+        let mut rows: Vec<Index> = (0..self.matches.len())
+            .map(|match_index| self.compute_destination_row(flatjson, match_index))
+            .collect();
+        rows.dedup();
+        rows
+    }
+
     pub fn no_matches_message(&self) -> String {
         format!("Pattern not found: {}", self.search_term)
     }
@@ -191,11 +450,24 @@ impl SearchState {
 
         let true_direction = self.true_direction(jump_direction);
 
-        let next_match_index = self.get_next_match(focused_row, flatjson, true_direction, jumps);
+        let next_match_index = match self.hidden_matches {
+            HiddenMatches::Skip => {
+                self.get_next_visible_match(focused_row, flatjson, true_direction, jumps)
+            }
+            HiddenMatches::Collapsed | HiddenMatches::Expand => {
+                self.get_next_match(focused_row, flatjson, true_direction, jumps)
+            }
+        };
         let row_containing_match = self.compute_destination_row(flatjson, next_match_index);
 
-        // If search takes inside a collapsed object, we will show the first visible ancestor.
-        let next_focused_row = flatjson.first_visible_ancestor(row_containing_match);
+        // If search takes inside a collapsed object, we will show the first visible
+        // ancestor, unless we're going to expand the containers to reveal the match.
+        let next_focused_row = match self.hidden_matches {
+            HiddenMatches::Expand => row_containing_match,
+            HiddenMatches::Collapsed | HiddenMatches::Skip => {
+                flatjson.first_visible_ancestor(row_containing_match)
+            }
+        };
 
         let wrapped = if focused_row == next_focused_row {
             // Usually, if we end up the same place we started, that means that we
@@ -233,7 +505,7 @@ impl SearchState {
     /// Return an iterator over all the stored matches. We pass in a
     /// start index that will be used to efficiently skip any matches
     /// before that index.
-    pub fn matches_iter(&self, range_start: usize) -> MatchRangeIter {
+    pub fn matches_iter(&self, range_start: usize) -> MatchRangeIter<'_> {
         match self.immediate_state {
             ImmediateSearchState::NotSearching => STATIC_EMPTY_SLICE.iter(),
             ImmediateSearchState::MatchesVisible
@@ -262,6 +534,50 @@ impl SearchState {
         }
     }
 
+    /// Returns the part of the currently focused match that should be
+    /// scrolled into view when it's in a long value.
+    pub fn current_match_range_to_show(&self) -> Range<usize> {
+        let range = self.current_match_range();
+        if self.offset == SearchOffset::End && !range.is_empty() {
+            range.end - 1..range.end
+        } else {
+            range
+        }
+    }
+
+    pub fn has_capture_groups(&self) -> bool {
+        !self.captures.is_empty()
+    }
+
+    /// Returns the range of the first capture group of the currently
+    /// focused match, or, if not actively searching, of the first match
+    /// within the given range (i.e., the range of the focused row).
+    pub fn focused_match_capture(&self, range: &Range<usize>) -> Option<Range<usize>> {
+        let match_index = match self.immediate_state {
+            ImmediateSearchState::ActivelySearching {
+                last_match_jumped_to,
+                ..
+            } => last_match_jumped_to,
+            ImmediateSearchState::NotSearching | ImmediateSearchState::MatchesVisible => {
+                let index = self
+                    .matches
+                    .partition_point(|match_range| match_range.start < range.start);
+                if self.matches.get(index)?.end > range.end {
+                    return None;
+                }
+                index
+            }
+        };
+
+        self.captures.get(match_index)?.clone()
+    }
+
+    /// Returns the ranges of the first capture group of every match,
+    /// skipping matches where the group didn't participate.
+    pub fn all_match_captures(&self) -> impl Iterator<Item = &Range<usize>> {
+        self.captures.iter().flatten()
+    }
+
     fn true_direction(&self, jump_direction: JumpDirection) -> SearchDirection {
         match (self.direction, jump_direction) {
             (SearchDirection::Forward, JumpDirection::Next) => SearchDirection::Forward,
@@ -366,6 +682,52 @@ impl SearchState {
         }
     }
 
+    // Like get_next_match, but only counts matches that aren't inside a collapsed
+    // container. If there are no visible matches at all, this returns the match
+    // get_next_match would.
+    fn get_next_visible_match(
+        &mut self,
+        focused_row: Index,
+        flatjson: &FlatJson,
+        true_direction: SearchDirection,
+        jumps: usize,
+    ) -> usize {
+        // Jumping all the way around the visible matches ends up back at
+        // the same one, so huge counts don't have to go around many times.
+        let num_visible_matches = self.num_visible_matches(flatjson);
+        let jumps = match num_visible_matches {
+            0 => 1,
+            _ => (jumps - 1) % num_visible_matches + 1,
+        };
+
+        let first_match = self.get_next_match(focused_row, flatjson, true_direction, 1);
+        let delta: isize = match true_direction {
+            SearchDirection::Forward => 1,
+            SearchDirection::Reverse => -1,
+        };
+
+        let mut next_match = first_match;
+        let mut remaining_jumps = jumps;
+        loop {
+            if self.is_match_visible(flatjson, next_match) {
+                remaining_jumps -= 1;
+                if remaining_jumps == 0 {
+                    return next_match;
+                }
+            }
+
+            next_match = self.cycle_match(next_match, delta);
+            if next_match == first_match && remaining_jumps == jumps {
+                return first_match;
+            }
+        }
+    }
+
+    fn is_match_visible(&self, flatjson: &FlatJson, match_index: usize) -> bool {
+        let row_containing_match = self.compute_destination_row(flatjson, match_index);
+        flatjson.first_visible_ancestor(row_containing_match) == row_containing_match
+    }
+
     // Helper for modifying a match_index that handles wrapping around the start or end of the
     // matches.
     fn cycle_match(&self, match_index: usize, delta: isize) -> usize {
@@ -383,13 +745,85 @@ impl SearchState {
     }
 }
 
+// An index of the positions of every word in a document, which makes
+// repeated case insensitive searches for words, or parts of them, near
+// instant in huge documents, at the cost of the memory used by the index.
+//
+// Words are runs of ASCII letters, digits and underscores, lowercased. A
+// search term made up only of those characters can't match across the
+// boundary of a word, so its matches can be found by looking for it in
+// each distinct word, rather than in the whole document.
+pub struct SearchIndex {
+    word_positions: HashMap<String, Vec<usize>>,
+}
+
+impl SearchIndex {
+    pub fn new(document: &str) -> SearchIndex {
+        let mut index = SearchIndex {
+            word_positions: HashMap::new(),
+        };
+        index.add(document, 0);
+        index
+    }
+
+    // Index text that starts at offset in the document, e.g., more
+    // newline-delimited JSON records that were appended to it.
+    pub fn add(&mut self, text: &str, offset: usize) {
+        let bytes = text.as_bytes();
+
+        let mut i = 0;
+        while i < bytes.len() {
+            if !Self::is_word_byte(bytes[i]) {
+                i += 1;
+                continue;
+            }
+
+            let start = i;
+            while i < bytes.len() && Self::is_word_byte(bytes[i]) {
+                i += 1;
+            }
+            self.word_positions
+                .entry(text[start..i].to_ascii_lowercase())
+                .or_default()
+                .push(offset + start);
+        }
+    }
+
+    fn is_word_byte(b: u8) -> bool {
+        b.is_ascii_alphanumeric() || b == b'_'
+    }
+
+    // The ranges of all case insensitive matches of the search term, in
+    // order, or None if the term can't be looked up in the index.
+    pub fn find(&self, search_term: &str) -> Option<Vec<Range<usize>>> {
+        if search_term.is_empty() || !search_term.bytes().all(Self::is_word_byte) {
+            return None;
+        }
+
+        let search_term = search_term.to_ascii_lowercase();
+        let mut matches = vec![];
+        for (word, positions) in self.word_positions.iter() {
+            for (offset, _) in word.match_indices(&search_term) {
+                for position in positions {
+                    let start = position + offset;
+                    matches.push(start..start + search_term.len());
+                }
+            }
+        }
+        matches.sort_unstable_by_key(|range| range.start);
+        Some(matches)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::flatjson::parse_top_level_json;
+    use crate::headless::run_jless;
 
+    use super::HiddenMatches::*;
     use super::JumpDirection::*;
     use super::SearchDirection::*;
-    use super::SearchState;
+    use super::{Folding, SearchDefaults, SearchFlags, SearchIndex, SearchOffset, SearchState};
 
     const SEARCHABLE: &str = r#"{
         "1": "aaa",
@@ -406,21 +840,215 @@ mod tests {
     }"#;
 
     #[test]
-    fn test_extract_search_term_and_case_sensitivity() {
+    fn test_extract_search_term_and_flags() {
+        let flags = |case_sensitive, fixed_string, whole_word| SearchFlags {
+            case_sensitive,
+            fixed_string,
+            whole_word,
+            folding: None,
+            offset: SearchOffset::Start,
+        };
+        let with_offset = |offset| SearchFlags {
+            offset,
+            ..SearchFlags::default()
+        };
+
         let tests = vec![
-            ("abc", ("abc", false)),
-            ("Abc", ("Abc", true)),
-            ("abc/", ("abc", false)),
-            ("abc/s", ("abc", true)),
-            ("abc/s/", ("abc/s", false)),
+            ("abc", ("abc", flags(false, false, false))),
+            ("Abc", ("Abc", flags(true, false, false))),
+            ("abc/", ("abc", flags(false, false, false))),
+            ("abc/s", ("abc", flags(true, false, false))),
+            ("abc/s/", ("abc/s", flags(false, false, false))),
+            ("abc/w", ("abc", flags(false, false, true))),
+            ("abc/ws", ("abc", flags(true, false, true))),
+            ("abc/x", ("abc/x", flags(false, false, false))),
+            ("\\Va.c", ("a.c", flags(false, true, false))),
+            ("\\VA.c/w", ("A.c", flags(true, true, true))),
+            ("abc/e", ("abc", with_offset(SearchOffset::End))),
+            ("abc/+2", ("abc", with_offset(SearchOffset::Lines(2)))),
+            ("abc/-1", ("abc", with_offset(SearchOffset::Lines(-1)))),
+            ("2023/10/16/", ("2023/10/16", flags(false, false, false))),
+            ("api/v1", ("api/v1", flags(false, false, false))),
+            (
+                "api/v1/42/+1",
+                ("api/v1/42", with_offset(SearchOffset::Lines(1))),
+            ),
+            (
+                "abc/sw+1",
+                (
+                    "abc",
+                    SearchFlags {
+                        offset: SearchOffset::Lines(1),
+                        ..flags(true, false, true)
+                    },
+                ),
+            ),
+            ("c/+x", ("c/+x", flags(false, false, false))),
+            (
+                "abc/n",
+                (
+                    "abc",
+                    SearchFlags {
+                        folding: Some(Folding::Normalize),
+                        ..flags(false, false, false)
+                    },
+                ),
+            ),
+            (
+                "abc/ndw",
+                (
+                    "abc",
+                    SearchFlags {
+                        folding: Some(Folding::StripDiacritics),
+                        ..flags(false, false, true)
+                    },
+                ),
+            ),
+            ("a/b-1", ("a/b-1", flags(false, false, false))),
         ];
 
-        for (input, search_term_and_case_sensitivity) in tests.into_iter() {
+        for (input, search_term_and_flags) in tests.into_iter() {
             assert_eq!(
-                search_term_and_case_sensitivity,
-                SearchState::extract_search_term_and_case_sensitivity(input),
+                Ok(search_term_and_flags),
+                SearchState::extract_search_term_and_flags(input, SearchDefaults::default()),
+            );
+        }
+
+        // Offsets from less and vim that aren't supported are rejected,
+        // rather than searched for.
+        for input in [
+            "abc/3",
+            "api/v1/42",
+            "2023/10/16",
+            "abc/e+1",
+            "abc/se-2",
+            "abc/+9999999999999999999",
+        ] {
+            let result =
+                SearchState::extract_search_term_and_flags(input, SearchDefaults::default());
+            assert!(
+                result.unwrap_err().starts_with("Invalid search offset"),
+                "{:?}",
+                input
             );
         }
+
+        // Defaults apply unless a search overrides them.
+        let defaults = SearchDefaults {
+            whole_word: true,
+            fixed_string: true,
+        };
+        let tests = vec![
+            ("a.c", ("a.c", flags(false, true, true))),
+            ("a.c/s", ("a.c", flags(true, true, true))),
+            ("\\Va.c/w", ("a.c", flags(false, true, true))),
+            ("a.c/W", ("a.c", flags(false, true, false))),
+            ("\\va.c", ("a.c", flags(false, false, true))),
+            ("\\va.c/Ws", ("a.c", flags(true, false, false))),
+        ];
+        for (input, search_term_and_flags) in tests.into_iter() {
+            assert_eq!(
+                Ok(search_term_and_flags),
+                SearchState::extract_search_term_and_flags(input, defaults),
+            );
+        }
+    }
+
+    #[test]
+    fn test_unicode_folding_search() {
+        // "Café" in NFC, and "cafe" with a combining accent (NFD).
+        let haystack = "{ \"a\": \"Café\", \"b\": \"cafe\u{301}\", \"c\": \"cafe\" }";
+        let matches = |search_input: &str| {
+            SearchState::initialize_search(
+                search_input.to_owned(),
+                haystack,
+                Forward,
+                SearchDefaults::default(),
+            )
+            .unwrap()
+            .matches
+        };
+
+        assert_eq!(1, matches("café").len());
+        assert_eq!(vec![8..13, 22..28], matches("café/n"));
+        assert_eq!(matches("café/n"), matches("cafe\u{301}/n"));
+        assert_eq!(vec![8..13, 22..28, 37..41], matches("cafe/d"));
+        // Just the accented letter.
+        assert_eq!(vec![11..13, 25..28], matches("é/n"));
+    }
+
+    #[test]
+    fn test_search_index() {
+        let haystack = r#"{ "Error": "error_code ERR", "errors": ["a.b", "aaaa", "über"] }"#;
+        let index = SearchIndex::new(haystack);
+
+        // The index finds exactly the same matches as scanning.
+        for search_input in [
+            "err", "error", "ERR/s", "e", "aa", "a.b", "\\Va.b", "err/w", "ber", "x",
+        ] {
+            let scanned = SearchState::initialize_search(
+                search_input.to_owned(),
+                haystack,
+                Forward,
+                SearchDefaults::default(),
+            )
+            .unwrap();
+            let indexed = SearchState::initialize_search_using_index(
+                search_input.to_owned(),
+                haystack,
+                Some(&index),
+                Forward,
+                SearchDefaults::default(),
+            )
+            .unwrap();
+            assert_eq!(scanned.matches, indexed.matches, "{:?}", search_input);
+            assert_eq!(scanned.search_term, indexed.search_term);
+        }
+
+        // Only words, or parts of them, are looked up.
+        assert_eq!(4, index.find("err").unwrap().len());
+        assert_eq!(None, index.find("a.b"));
+        assert_eq!(None, index.find("über"));
+    }
+
+    #[test]
+    fn test_fixed_string_and_whole_word_search() {
+        let haystack = r#"{ "id": 1, "uuid": 2, "identifier": "a.c abc" }"#;
+        let num_matches = |search_input: &str| {
+            SearchState::initialize_search(
+                search_input.to_owned(),
+                haystack,
+                Forward,
+                SearchDefaults::default(),
+            )
+            .unwrap()
+            .num_matches()
+        };
+
+        assert_eq!(3, num_matches("id"));
+        assert_eq!(1, num_matches("id/w"));
+        assert_eq!(2, num_matches("a.c"));
+        assert_eq!(1, num_matches("\\Va.c"));
+        assert_eq!(1, num_matches("\\Va.c/w"));
+        assert_eq!(0, num_matches("\\V[a-z]"));
+
+        // A search can opt out of the defaults.
+        let defaults = SearchDefaults {
+            whole_word: true,
+            fixed_string: true,
+        };
+        let num_matches = |search_input: &str| {
+            SearchState::initialize_search(search_input.to_owned(), haystack, Forward, defaults)
+                .unwrap()
+                .num_matches()
+        };
+
+        assert_eq!(1, num_matches("id"));
+        assert_eq!(3, num_matches("id/W"));
+        assert_eq!(1, num_matches("a.c/W"));
+        assert_eq!(2, num_matches("\\va.c"));
+        assert_eq!(2, num_matches("\\vid|uuid"));
+        assert_eq!(3, num_matches("\\vid|uuid/W"));
     }
 
     #[test]
@@ -444,7 +1072,13 @@ mod tests {
     #[test]
     fn test_basic_search_forward() {
         let fj = parse_top_level_json(SEARCHABLE.to_owned()).unwrap();
-        let mut search = SearchState::initialize_search("aaa".to_owned(), &fj.1, Forward).unwrap();
+        let mut search = SearchState::initialize_search(
+            "aaa".to_owned(),
+            &fj.1,
+            Forward,
+            SearchDefaults::default(),
+        )
+        .unwrap();
         assert_eq!(search.jump_to_match(0, &fj, Next, 1), 1);
         assert_eq!(search.jump_to_match(1, &fj, Next, 1), 4);
         assert_eq!(search.jump_to_match(4, &fj, Next, 1), 7);
@@ -460,7 +1094,13 @@ mod tests {
         assert_eq!(search.jump_to_match(4, &fj, Prev, 1), 1);
         assert_eq!(search.jump_to_match(1, &fj, Prev, 1), 7);
 
-        let mut search = SearchState::initialize_search("aaa".to_owned(), &fj.1, Forward).unwrap();
+        let mut search = SearchState::initialize_search(
+            "aaa".to_owned(),
+            &fj.1,
+            Forward,
+            SearchDefaults::default(),
+        )
+        .unwrap();
         assert_eq!(search.jump_to_match(0, &fj, Next, 4), 7);
         assert_eq!(search.jump_to_match(1, &fj, Next, 2), 4);
         assert_eq!(search.jump_to_match(4, &fj, Next, 3), 1);
@@ -472,10 +1112,64 @@ mod tests {
         assert_eq!(search.jump_to_match(4, &fj, Prev, 4_000_000_001), 1);
     }
 
+    #[test]
+    fn test_capture_groups() {
+        let fj = parse_top_level_json(r#"["id-12", "x", "id-345/ab"]"#.to_owned()).unwrap();
+
+        let search = SearchState::initialize_search(
+            "id".to_owned(),
+            &fj.1,
+            Forward,
+            SearchDefaults::default(),
+        )
+        .unwrap();
+        assert!(!search.has_capture_groups());
+
+        let mut search = SearchState::initialize_search(
+            r"id-(\d+)|(?:ab)".to_owned(),
+            &fj.1,
+            Forward,
+            SearchDefaults::default(),
+        )
+        .unwrap();
+        assert!(search.has_capture_groups());
+        let captures: Vec<&str> = search
+            .all_match_captures()
+            .map(|range| &fj.1[range.clone()])
+            .collect();
+        assert_eq!(vec!["12", "345"], captures);
+
+        let capture = |search: &SearchState, row: usize| {
+            search
+                .focused_match_capture(&fj[row].range_represented_by_row())
+                .map(|range| &fj.1[range])
+        };
+
+        // Not actively searching; uses the first match on the row.
+        assert_eq!(Some("12"), capture(&search, 1));
+        assert_eq!(None, capture(&search, 2));
+        assert_eq!(Some("345"), capture(&search, 3));
+
+        // When actively searching, uses the focused match.
+        search.jump_to_match(2, &fj, Next, 1);
+        assert_eq!(Some(1), search.active_search_state().map(|(i, _)| i));
+        assert_eq!(Some("345"), capture(&search, 3));
+        // The "ab" match doesn't have a capture.
+        search.jump_to_match(3, &fj, Next, 1);
+        assert_eq!(Some(2), search.active_search_state().map(|(i, _)| i));
+        assert_eq!(None, capture(&search, 3));
+    }
+
     #[test]
     fn test_basic_search_backwards() {
         let fj = parse_top_level_json(SEARCHABLE.to_owned()).unwrap();
-        let mut search = SearchState::initialize_search("aaa".to_owned(), &fj.1, Reverse).unwrap();
+        let mut search = SearchState::initialize_search(
+            "aaa".to_owned(),
+            &fj.1,
+            Reverse,
+            SearchDefaults::default(),
+        )
+        .unwrap();
         assert_eq!(search.jump_to_match(0, &fj, Next, 1), 7);
         assert_wrapped_state(&search, true);
         assert_eq!(search.jump_to_match(7, &fj, Next, 1), 7);
@@ -490,7 +1184,13 @@ mod tests {
         assert_eq!(search.jump_to_match(1, &fj, Prev, 1), 4);
         assert_wrapped_state(&search, false);
 
-        let mut search = SearchState::initialize_search("aaa".to_owned(), &fj.1, Reverse).unwrap();
+        let mut search = SearchState::initialize_search(
+            "aaa".to_owned(),
+            &fj.1,
+            Reverse,
+            SearchDefaults::default(),
+        )
+        .unwrap();
         assert_eq!(search.jump_to_match(0, &fj, Next, 4), 1);
         assert_eq!(search.jump_to_match(1, &fj, Next, 3), 4);
         assert_eq!(search.jump_to_match(4, &fj, Next, 2), 7);
@@ -501,7 +1201,13 @@ mod tests {
     #[test]
     fn test_search_collapsed_forward() {
         let mut fj = parse_top_level_json(SEARCHABLE.to_owned()).unwrap();
-        let mut search = SearchState::initialize_search("aaa".to_owned(), &fj.1, Forward).unwrap();
+        let mut search = SearchState::initialize_search(
+            "aaa".to_owned(),
+            &fj.1,
+            Forward,
+            SearchDefaults::default(),
+        )
+        .unwrap();
         fj.collapse(6);
         assert_eq!(search.jump_to_match(0, &fj, Next, 1), 1);
         assert_eq!(search.jump_to_match(1, &fj, Next, 1), 4);
@@ -512,7 +1218,13 @@ mod tests {
         assert_eq!(search.jump_to_match(1, &fj, Prev, 1), 6);
         assert_eq!(search.jump_to_match(6, &fj, Prev, 1), 4);
 
-        let mut search = SearchState::initialize_search("aaa".to_owned(), &fj.1, Forward).unwrap();
+        let mut search = SearchState::initialize_search(
+            "aaa".to_owned(),
+            &fj.1,
+            Forward,
+            SearchDefaults::default(),
+        )
+        .unwrap();
         fj.collapse(6);
         assert_eq!(search.jump_to_match(0, &fj, Next, 4), 6);
         assert_eq!(search.jump_to_match(6, &fj, Next, 1), 1);
@@ -527,7 +1239,13 @@ mod tests {
     #[test]
     fn test_search_collapsed_backwards() {
         let mut fj = parse_top_level_json(SEARCHABLE.to_owned()).unwrap();
-        let mut search = SearchState::initialize_search("aaa".to_owned(), &fj.1, Reverse).unwrap();
+        let mut search = SearchState::initialize_search(
+            "aaa".to_owned(),
+            &fj.1,
+            Reverse,
+            SearchDefaults::default(),
+        )
+        .unwrap();
         fj.collapse(6);
         assert_eq!(search.jump_to_match(0, &fj, Next, 1), 6);
         assert_eq!(search.jump_to_match(6, &fj, Next, 1), 4);
@@ -538,7 +1256,13 @@ mod tests {
         assert_eq!(search.jump_to_match(4, &fj, Prev, 1), 6);
         assert_eq!(search.jump_to_match(6, &fj, Prev, 1), 1);
 
-        let mut search = SearchState::initialize_search("aaa".to_owned(), &fj.1, Reverse).unwrap();
+        let mut search = SearchState::initialize_search(
+            "aaa".to_owned(),
+            &fj.1,
+            Reverse,
+            SearchDefaults::default(),
+        )
+        .unwrap();
         fj.collapse(6);
         assert_eq!(search.jump_to_match(0, &fj, Prev, 4), 6);
         assert_eq!(search.jump_to_match(6, &fj, Prev, 1), 1);
@@ -550,6 +1274,73 @@ mod tests {
         assert_eq!(search.jump_to_match(1, &fj, Next, 3), 4);
     }
 
+    #[test]
+    fn test_search_hidden_matches() {
+        let mut fj = parse_top_level_json(SEARCHABLE.to_owned()).unwrap();
+        let mut search = SearchState::initialize_search(
+            "aaa".to_owned(),
+            &fj.1,
+            Forward,
+            SearchDefaults::default(),
+        )
+        .unwrap();
+        assert_eq!(search.num_visible_matches(&fj), 4);
+        fj.collapse(2);
+        fj.collapse(6);
+        assert_eq!(search.num_visible_matches(&fj), 1);
+
+        // Skipping only ever lands on the single visible match.
+        search.hidden_matches = Skip;
+        assert_eq!(search.jump_to_match(0, &fj, Next, 1), 1);
+        assert_eq!(search.jump_to_match(1, &fj, Next, 1), 1);
+        assert_wrapped_state(&search, true);
+        assert_eq!(search.jump_to_match(1, &fj, Prev, 3), 1);
+
+        // Expanding focuses the rows containing the matches themselves.
+        let mut search = SearchState::initialize_search(
+            "aaa".to_owned(),
+            &fj.1,
+            Forward,
+            SearchDefaults::default(),
+        )
+        .unwrap();
+        search.hidden_matches = Expand;
+        assert_eq!(search.jump_to_match(0, &fj, Next, 1), 1);
+        assert_eq!(search.jump_to_match(1, &fj, Next, 1), 4);
+        assert_eq!(search.jump_to_match(4, &fj, Next, 1), 7);
+        assert_eq!(search.jump_to_match(7, &fj, Next, 1), 7);
+        assert_eq!(search.jump_to_match(7, &fj, Prev, 2), 4);
+
+        // With no visible matches at all, skipping behaves like Collapsed.
+        fj.collapse(0);
+        assert_eq!(search.num_visible_matches(&fj), 0);
+        let mut search = SearchState::initialize_search(
+            "aaa".to_owned(),
+            &fj.1,
+            Forward,
+            SearchDefaults::default(),
+        )
+        .unwrap();
+        search.hidden_matches = Skip;
+        assert_eq!(search.jump_to_match(0, &fj, Next, 1), 0);
+
+        // Huge counts wrap around the visible matches.
+        fj.expand(0);
+        fj.expand(2);
+        assert_eq!(search.num_visible_matches(&fj), 2);
+        let mut search = SearchState::initialize_search(
+            "aaa".to_owned(),
+            &fj.1,
+            Forward,
+            SearchDefaults::default(),
+        )
+        .unwrap();
+        search.hidden_matches = Skip;
+        assert_eq!(search.jump_to_match(0, &fj, Next, 99_999_999), 1);
+        assert_eq!(search.jump_to_match(1, &fj, Next, 4_000_000_001), 4);
+        assert_eq!(search.jump_to_match(4, &fj, Prev, 4_000_000_001), 1);
+    }
+
     #[test]
     fn test_no_wrap_when_opening_of_collapsed_container_and_contents_match_search() {
         const TEST: &str = r#"{
@@ -559,7 +1350,13 @@ mod tests {
             "key": "term"
         }"#;
         let mut fj = parse_top_level_json(TEST.to_owned()).unwrap();
-        let mut search = SearchState::initialize_search("term".to_owned(), &fj.1, Forward).unwrap();
+        let mut search = SearchState::initialize_search(
+            "term".to_owned(),
+            &fj.1,
+            Forward,
+            SearchDefaults::default(),
+        )
+        .unwrap();
         fj.collapse(1);
         assert_eq!(search.jump_to_match(0, &fj, Next, 1), 1);
         assert_wrapped_state(&search, false);
@@ -571,12 +1368,66 @@ mod tests {
         assert_wrapped_state(&search, true);
     }
 
+    #[test]
+    fn test_count_matches_in_subtree() {
+        let fj = parse_top_level_json(SEARCHABLE.to_owned()).unwrap();
+        let count = |pattern: &str, index| {
+            SearchState::count_matches_in_subtree(
+                pattern.to_owned(),
+                &fj,
+                index,
+                SearchDefaults::default(),
+            )
+            .unwrap()
+        };
+
+        assert_eq!((4, 3), count("aaa", 0));
+        assert_eq!((1, 1), count("aaa", 2));
+        assert_eq!((2, 1), count("aaa", 6));
+        assert_eq!((0, 0), count("bbb", 6));
+        // The key of the value is included, but not its siblings.
+        assert_eq!((1, 1), count(r#""6""#, 6));
+        assert_eq!((3, 1), count("a", 1));
+        // As are flags.
+        assert_eq!((0, 0), count("AAA/s", 0));
+
+        let defaults = SearchDefaults::default();
+        assert!(SearchState::count_matches_in_subtree("(".to_owned(), &fj, 0, defaults).is_err());
+    }
+
     #[track_caller]
     fn assert_wrapped_state(search: &SearchState, expected: bool) {
         if let Some((_, wrapped)) = search.active_search_state() {
             assert_eq!(wrapped, expected);
         } else {
-            assert!(false, "Not in an active search state");
+            panic!("Not in an active search state");
         }
     }
+
+    #[test]
+    fn test_search_defaults() {
+        let focused_path = |args: &[&str], json: &str, commands: &str| {
+            let args = [&["--screen-size", "40x3", "--cmd", commands][..], args].concat();
+            let screen = run_jless(&args, json, &[]).unwrap();
+            let status_bar = screen.lines().nth(1).unwrap().to_string();
+            status_bar.split(' ').next().unwrap().to_string()
+        };
+
+        const WORDS: &str = r#"{"uuid": 1, "id": 2}"#;
+        assert_eq!("input.uuid", focused_path(&[], WORDS, "/id"));
+        assert_eq!("input.id", focused_path(&["--whole-word"], WORDS, "/id"));
+        assert_eq!("input.id", focused_path(&[], WORDS, ":set wholeword | /id"));
+        let path = focused_path(&["--whole-word"], WORDS, ":set wholeword! | /id");
+        assert_eq!("input.uuid", path);
+
+        const DOTS: &str = r#"{"a": "abc", "b": "a.c"}"#;
+        assert_eq!("input.a", focused_path(&[], DOTS, "/a.c"));
+        assert_eq!("input.b", focused_path(&["--fixed-strings"], DOTS, "/a.c"));
+        assert_eq!(
+            "input.b",
+            focused_path(&[], DOTS, ":set fixedstrings | /a.c")
+        );
+        let path = focused_path(&["--fixed-strings"], DOTS, ":set nofixedstrings | /a.c");
+        assert_eq!("input.a", path);
+    }
 }
@@ -0,0 +1,180 @@
+use std::time::{Duration, Instant};
+
+use crate::options::Opt;
+use crate::viewer::Action;
+
+// With --smooth-scroll, each notch of the mouse wheel scrolls a single
+// line, but notches that arrive in quick succession build up momentum, up
+// to --wheel-lines lines each, so spinning the wheel still moves quickly.
+// Paging up and down is shown in two steps, so it's easier to keep track
+// of where things moved to in huge documents.
+
+// How soon after the previous notch of the wheel one has to arrive to
+// keep building momentum.
+const MOMENTUM_WINDOW: Duration = Duration::from_millis(80);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WheelDirection {
+    Up,
+    Down,
+}
+
+pub struct SmoothScroll {
+    pub enabled: bool,
+    // How long each step of an animated page scroll is shown for.
+    pub frame_delay: Duration,
+    // How many lines a notch of the wheel scrolls at full momentum, or
+    // always when smooth scrolling is disabled.
+    pub wheel_lines: usize,
+    // When the wheel last scrolled, in which direction, and by how many
+    // lines.
+    last_wheel: Option<(Instant, WheelDirection, usize)>,
+}
+
+impl SmoothScroll {
+    pub fn new(opt: &Opt) -> SmoothScroll {
+        let mut smooth_scroll = SmoothScroll {
+            enabled: false,
+            frame_delay: Duration::ZERO,
+            wheel_lines: 0,
+            last_wheel: None,
+        };
+        smooth_scroll.apply_options(opt);
+        smooth_scroll
+    }
+
+    pub fn apply_options(&mut self, opt: &Opt) {
+        self.enabled = opt.smooth_scroll;
+        self.frame_delay = Duration::from_millis(opt.scroll_frame_delay);
+        self.wheel_lines = opt.wheel_lines as usize;
+    }
+
+    // The action to perform for a notch of the mouse wheel received at
+    // the given time.
+    pub fn wheel(&mut self, direction: WheelDirection, now: Instant) -> Action {
+        let lines = if !self.enabled {
+            self.wheel_lines
+        } else {
+            match self.last_wheel {
+                Some((last, last_direction, last_lines))
+                    if last_direction == direction
+                        && now.saturating_duration_since(last) < MOMENTUM_WINDOW =>
+                {
+                    (last_lines + 1).min(self.wheel_lines)
+                }
+                _ => 1,
+            }
+        };
+        self.last_wheel = Some((now, direction, lines));
+
+        match direction {
+            WheelDirection::Up => Action::ScrollUp(lines),
+            WheelDirection::Down => Action::ScrollDown(lines),
+        }
+    }
+
+    // Split scrolling by pages into two steps, given the height of the
+    // viewer, where the first step should be shown before the second one
+    // is performed. Other actions aren't animated.
+    pub fn page_steps(&self, action: Action, height: usize) -> Option<(Action, Action)> {
+        if !self.enabled {
+            return None;
+        }
+        match action {
+            Action::PageUp(n) => {
+                let lines = height * n;
+                Some((
+                    Action::ScrollUp(lines / 2),
+                    Action::ScrollUp(lines - lines / 2),
+                ))
+            }
+            Action::PageDown(n) => {
+                let lines = height * n;
+                Some((
+                    Action::ScrollDown(lines / 2),
+                    Action::ScrollDown(lines - lines / 2),
+                ))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    fn smooth_scroll(args: &[&str]) -> SmoothScroll {
+        SmoothScroll::new(&Opt::parse_from([&["jless"][..], args].concat()))
+    }
+
+    #[test]
+    fn test_wheel_momentum() {
+        let mut scroll = smooth_scroll(&["--smooth-scroll"]);
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+
+        let mut lines = vec![];
+        for millis in [0, 20, 40, 60, 80, 100] {
+            lines.push(scroll.wheel(WheelDirection::Down, at(millis)));
+        }
+        assert_eq!(
+            "[ScrollDown(1), ScrollDown(2), ScrollDown(3), ScrollDown(3), ScrollDown(3), ScrollDown(3)]",
+            format!("{lines:?}")
+        );
+
+        // Momentum is lost after a pause, or when changing direction.
+        assert!(matches!(
+            scroll.wheel(WheelDirection::Down, at(300)),
+            Action::ScrollDown(1)
+        ));
+        assert!(matches!(
+            scroll.wheel(WheelDirection::Down, at(310)),
+            Action::ScrollDown(2)
+        ));
+        assert!(matches!(
+            scroll.wheel(WheelDirection::Up, at(320)),
+            Action::ScrollUp(1)
+        ));
+
+        let mut scroll = smooth_scroll(&["--smooth-scroll", "--wheel-lines", "5"]);
+        for millis in [0, 10, 20, 30] {
+            scroll.wheel(WheelDirection::Up, at(millis));
+        }
+        assert!(matches!(
+            scroll.wheel(WheelDirection::Up, at(40)),
+            Action::ScrollUp(5)
+        ));
+
+        // Without smooth scrolling, every notch scrolls the same amount.
+        let mut scroll = smooth_scroll(&[]);
+        assert!(matches!(
+            scroll.wheel(WheelDirection::Down, at(0)),
+            Action::ScrollDown(3)
+        ));
+        assert!(matches!(
+            scroll.wheel(WheelDirection::Down, at(500)),
+            Action::ScrollDown(3)
+        ));
+    }
+
+    #[test]
+    fn test_page_steps() {
+        let scroll = smooth_scroll(&["--smooth-scroll"]);
+        assert!(matches!(
+            scroll.page_steps(Action::PageDown(1), 25),
+            Some((Action::ScrollDown(12), Action::ScrollDown(13)))
+        ));
+        assert!(matches!(
+            scroll.page_steps(Action::PageUp(2), 10),
+            Some((Action::ScrollUp(10), Action::ScrollUp(10)))
+        ));
+        assert!(scroll.page_steps(Action::ScrollDown(3), 10).is_none());
+
+        assert!(smooth_scroll(&[])
+            .page_steps(Action::PageDown(1), 10)
+            .is_none());
+    }
+}
@@ -0,0 +1,427 @@
+use std::collections::HashMap;
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::flatjson::{FlatJson, Index, OptionIndex, ValueType};
+use crate::search::{JumpDirection, SearchDirection, SearchState};
+
+// Columns are cut off at this width, so that a single long value doesn't
+// push all the other columns off of the screen.
+const MAX_COLUMN_WIDTH: usize = 30;
+
+// Drawn between the columns of the table.
+pub const COLUMN_SEPARATOR: &str = " │ ";
+const COLUMN_SEPARATOR_WIDTH: usize = 3;
+
+// An Array of Objects, e.g., the items of an API list response, shown in
+// place of the viewer as a table with a line for each element and a column
+// for each key, toggled with T. Keys that are missing from an element
+// leave its cell blank, and nested Objects and Arrays are abbreviated.
+pub struct Table {
+    // The opening row of the Array.
+    pub array: Index,
+    pub columns: Vec<Column>,
+    // The opening rows of the elements of the Array.
+    elements: Vec<Index>,
+    // The value of each column in each element, if it has one. Columns are
+    // added as new keys are found, so earlier elements may have fewer.
+    cells: Vec<Vec<Option<Index>>>,
+    // Width of the column of element indexes on the left.
+    pub index_width: usize,
+    pub selected: usize,
+    // The first element that is visible.
+    pub top: usize,
+    // The first column that is visible; the element indexes always are.
+    pub left: usize,
+    pub search: Option<ColumnSearch>,
+}
+
+pub struct Column {
+    pub key: String,
+    pub width: usize,
+}
+
+// A search scoped to a single column, e.g., `/name:foo`, which only matches
+// the values in that column; n and N move between the elements that have
+// a match in it.
+pub struct ColumnSearch {
+    pub column: usize,
+    direction: SearchDirection,
+    // The elements whose value in the column has a match, in order.
+    matching_elements: Vec<usize>,
+}
+
+impl Table {
+    pub fn new(flatjson: &FlatJson, array: Index) -> Result<Table, String> {
+        let mut elements = vec![];
+        let mut child = flatjson[array].first_child();
+        while let OptionIndex::Index(index) = child {
+            let row = &flatjson[index];
+            if !row.value.is_of_type(ValueType::Object) {
+                return Err(format!(
+                    "Element {} is not an Object; only Arrays of Objects can be shown as a table",
+                    row.index_in_parent
+                ));
+            }
+            elements.push(index);
+            child = row.next_sibling;
+        }
+
+        let mut columns: Vec<Column> = vec![];
+        let mut column_indexes: HashMap<&str, usize> = HashMap::new();
+        let mut cells = vec![];
+
+        for element in elements.iter() {
+            let mut element_cells = vec![];
+            let mut child = match flatjson[*element].is_opening_of_container() {
+                true => flatjson[*element].first_child(),
+                false => OptionIndex::Nil,
+            };
+
+            while let OptionIndex::Index(index) = child {
+                let row = &flatjson[index];
+                child = row.next_sibling;
+                // key_range includes the quotes around the key.
+                let Some(key_range) = &row.key_range else {
+                    continue;
+                };
+                let key = &flatjson.1[key_range.start + 1..key_range.end - 1];
+
+                let column = *column_indexes.entry(key).or_insert_with(|| {
+                    columns.push(Column {
+                        key: key.to_string(),
+                        width: UnicodeWidthStr::width(key).min(MAX_COLUMN_WIDTH),
+                    });
+                    columns.len() - 1
+                });
+                if element_cells.len() <= column {
+                    element_cells.resize(column + 1, None);
+                }
+                element_cells[column] = Some(index);
+
+                let width = UnicodeWidthStr::width(cell_text(flatjson, index));
+                let column = &mut columns[column];
+                column.width = column.width.max(width.min(MAX_COLUMN_WIDTH));
+            }
+
+            cells.push(element_cells);
+        }
+
+        let max_index = elements
+            .last()
+            .map_or(0, |element| flatjson[*element].index_in_parent);
+
+        Ok(Table {
+            array,
+            columns,
+            elements,
+            cells,
+            index_width: max_index.to_string().len(),
+            selected: 0,
+            top: 0,
+            left: 0,
+            search: None,
+        })
+    }
+
+    pub fn num_elements(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn element(&self, n: usize) -> Index {
+        self.elements[n]
+    }
+
+    pub fn selected_element(&self) -> Option<Index> {
+        self.elements.get(self.selected).copied()
+    }
+
+    // The value of the given column in the nth element, if it has one.
+    pub fn cell(&self, n: usize, column: usize) -> Option<Index> {
+        self.cells.get(n)?.get(column).copied().flatten()
+    }
+
+    pub fn column_named(&self, key: &str) -> Option<usize> {
+        self.columns.iter().position(|column| column.key == key)
+    }
+
+    // Search the values in a single column, given the matches of a search
+    // of the whole document, and return how many elements have a match.
+    pub fn search_column(
+        &mut self,
+        flatjson: &FlatJson,
+        column: usize,
+        search_state: &SearchState,
+    ) -> usize {
+        let matching_elements: Vec<usize> = (0..self.elements.len())
+            .filter(|n| {
+                self.cell(*n, column).map_or(false, |index| {
+                    search_state.any_match_in(&flatjson[index].range)
+                })
+            })
+            .collect();
+
+        let num_matching = matching_elements.len();
+        self.search = Some(ColumnSearch {
+            column,
+            direction: search_state.direction,
+            matching_elements,
+        });
+        num_matching
+    }
+
+    // Select the next (or previous) element with a match in the searched
+    // column, wrapping around the ends of the table. Returns which of the
+    // matching elements it is, and whether the search wrapped, or None if
+    // there are no matches to jump to.
+    pub fn jump_to_match(&mut self, jump_direction: JumpDirection) -> Option<(usize, bool)> {
+        let search = self.search.as_ref()?;
+        let matching = &search.matching_elements;
+        if matching.is_empty() {
+            return None;
+        }
+
+        let forward = (search.direction == SearchDirection::Forward)
+            == (jump_direction == JumpDirection::Next);
+        let (position, wrapped) = if forward {
+            let next = matching.partition_point(|n| *n <= self.selected);
+            match next == matching.len() {
+                true => (0, true),
+                false => (next, false),
+            }
+        } else {
+            match matching.partition_point(|n| *n < self.selected) {
+                0 => (matching.len() - 1, true),
+                prev => (prev - 1, false),
+            }
+        };
+
+        self.selected = matching[position];
+        Some((position, wrapped))
+    }
+
+    pub fn num_matching_elements(&self) -> usize {
+        self.search
+            .as_ref()
+            .map_or(0, |search| search.matching_elements.len())
+    }
+
+    // Select the element that the given row is in, if it's in one.
+    pub fn select_element_containing(&mut self, flatjson: &FlatJson, mut row: Index) {
+        if flatjson[row].is_closing_of_container() {
+            row = flatjson[row].pair_index().unwrap();
+        }
+        loop {
+            match flatjson[row].parent {
+                OptionIndex::Index(parent) if parent == self.array => break,
+                OptionIndex::Index(parent) => row = parent,
+                OptionIndex::Nil => return,
+            }
+        }
+        if let Some(n) = self.elements.iter().position(|element| *element == row) {
+            self.selected = n;
+        }
+    }
+
+    pub fn move_selection_down(&mut self, n: usize) {
+        let last = self.elements.len().saturating_sub(1);
+        self.selected = self.selected.saturating_add(n).min(last);
+    }
+
+    pub fn move_selection_up(&mut self, n: usize) {
+        self.selected = self.selected.saturating_sub(n);
+    }
+
+    pub fn select_first(&mut self) {
+        self.selected = 0;
+    }
+
+    pub fn select_last(&mut self) {
+        self.selected = self.elements.len().saturating_sub(1);
+    }
+
+    pub fn scroll_right(&mut self, n: usize) {
+        let last = self.columns.len().saturating_sub(1);
+        self.left = self.left.saturating_add(n).min(last);
+    }
+
+    pub fn scroll_left(&mut self, n: usize) {
+        self.left = self.left.saturating_sub(n);
+    }
+
+    pub fn scroll_to_last_column(&mut self) {
+        self.left = self.columns.len().saturating_sub(1);
+    }
+
+    // Update the top element so that the selected element is visible when
+    // the given number of elements fit on the screen.
+    pub fn ensure_selected_is_visible(&mut self, height: usize) {
+        if self.selected < self.top {
+            self.top = self.selected;
+        } else if height > 0 && self.selected >= self.top + height {
+            self.top = self.selected + 1 - height;
+        }
+    }
+
+    // The columns that fit on a screen of the given width, starting with
+    // the leftmost visible one, along with how wide each is shown. The
+    // last one may be cut off.
+    pub fn visible_columns(&self, width: usize) -> Vec<(usize, usize)> {
+        let mut available_space = width.saturating_sub(self.index_width);
+        let mut visible = vec![];
+
+        for (n, column) in self.columns.iter().enumerate().skip(self.left) {
+            if available_space <= COLUMN_SEPARATOR_WIDTH {
+                break;
+            }
+            available_space -= COLUMN_SEPARATOR_WIDTH;
+            let column_width = column.width.min(available_space);
+            visible.push((n, column_width));
+            available_space -= column_width;
+        }
+
+        visible
+    }
+}
+
+// The text shown for a value in a cell, which is what's shown in the
+// viewer for primitives; Objects and Arrays with anything in them are
+// abbreviated, since there isn't room to show their contents.
+pub fn cell_text(flatjson: &FlatJson, index: Index) -> &str {
+    let row = &flatjson[index];
+    if row.is_array() {
+        "[…]"
+    } else if row.is_container() {
+        "{…}"
+    } else {
+        &flatjson.1[row.range.clone()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flatjson::parse_top_level_json;
+    use crate::search::SearchDefaults;
+
+    #[test]
+    fn test_table() {
+        const JSON: &str = r#"[
+            { "id": 1, "name": "first", "tags": ["a"] },
+            { "id": 22, "owner": { "name": "Paul" } },
+            {},
+        ]"#;
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+        let mut table = Table::new(&fj, 0).unwrap();
+
+        let keys: Vec<&str> = table.columns.iter().map(|c| c.key.as_str()).collect();
+        assert_eq!(vec!["id", "name", "tags", "owner"], keys);
+        let widths: Vec<usize> = table.columns.iter().map(|c| c.width).collect();
+        assert_eq!(vec![2, 7, 4, 5], widths);
+        assert_eq!(3, table.num_elements());
+        assert_eq!(1, table.index_width);
+
+        let cells: Vec<&str> = (0..table.columns.len())
+            .map(|column| {
+                table
+                    .cell(1, column)
+                    .map_or("", |index| cell_text(&fj, index))
+            })
+            .collect();
+        assert_eq!(vec!["22", "", "", "{…}"], cells);
+        assert_eq!(
+            Some("[…]"),
+            table.cell(0, 2).map(|index| cell_text(&fj, index))
+        );
+        assert_eq!(None, table.cell(2, 0));
+
+        // Inside of [1].owner.
+        table.select_element_containing(&fj, 10);
+        assert_eq!(1, table.selected);
+        assert_eq!(Some(table.element(1)), table.selected_element());
+        table.select_element_containing(&fj, 0);
+        assert_eq!(1, table.selected);
+
+        table.move_selection_down(10);
+        assert_eq!(2, table.selected);
+        table.ensure_selected_is_visible(2);
+        assert_eq!(1, table.top);
+        table.select_first();
+        table.ensure_selected_is_visible(2);
+        assert_eq!(0, table.top);
+    }
+
+    #[test]
+    fn test_visible_columns() {
+        let fj = parse_top_level_json(
+            r#"[{ "a": "aaaa", "b": "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb", "c": 1 }]"#
+                .to_owned(),
+        )
+        .unwrap();
+        let mut table = Table::new(&fj, 0).unwrap();
+
+        // Long values are cut off at the maximum width.
+        assert_eq!(MAX_COLUMN_WIDTH, table.columns[1].width);
+        // The index column, and then a separator before each column.
+        assert_eq!(vec![(0, 6), (1, 30), (2, 1)], table.visible_columns(100));
+        assert_eq!(vec![(0, 6), (1, 10)], table.visible_columns(23));
+        assert_eq!(vec![(0, 6)], table.visible_columns(13));
+
+        table.scroll_right(10);
+        assert_eq!(2, table.left);
+        assert_eq!(vec![(2, 1)], table.visible_columns(100));
+        table.scroll_left(1);
+        assert_eq!(vec![(1, 30), (2, 1)], table.visible_columns(100));
+    }
+
+    #[test]
+    fn test_search_column() {
+        const JSON: &str = r#"[
+            { "name": "apple", "note": "banana" },
+            { "name": "banana" },
+            { "note": "apple" },
+            { "name": ["banana", "cherry"] },
+        ]"#;
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+        let mut table = Table::new(&fj, 0).unwrap();
+        assert_eq!(None, table.jump_to_match(JumpDirection::Next));
+
+        let name = table.column_named("name").unwrap();
+        let search_state = SearchState::initialize_search(
+            "banana".to_owned(),
+            &fj.1,
+            SearchDirection::Forward,
+            SearchDefaults::default(),
+        )
+        .unwrap();
+        // Only the values in the column are searched, including nested ones.
+        assert_eq!(2, table.search_column(&fj, name, &search_state));
+
+        assert_eq!(Some((0, false)), table.jump_to_match(JumpDirection::Next));
+        assert_eq!(1, table.selected);
+        assert_eq!(Some((1, false)), table.jump_to_match(JumpDirection::Next));
+        assert_eq!(3, table.selected);
+        assert_eq!(Some((0, true)), table.jump_to_match(JumpDirection::Next));
+        assert_eq!(1, table.selected);
+        assert_eq!(Some((1, true)), table.jump_to_match(JumpDirection::Prev));
+        assert_eq!(3, table.selected);
+
+        let search_state = SearchState::initialize_search(
+            "cherry".to_owned(),
+            &fj.1,
+            SearchDirection::Forward,
+            SearchDefaults::default(),
+        )
+        .unwrap();
+        let note = table.column_named("note").unwrap();
+        assert_eq!(0, table.search_column(&fj, note, &search_state));
+        assert_eq!(None, table.jump_to_match(JumpDirection::Next));
+    }
+
+    #[test]
+    fn test_table_requires_objects() {
+        let fj = parse_top_level_json(r#"[{ "a": 1 }, 2]"#.to_owned()).unwrap();
+        let err = Table::new(&fj, 0).err().unwrap();
+        assert!(err.starts_with("Element 1 is not an Object"), "{}", err);
+    }
+}
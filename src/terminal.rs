@@ -7,32 +7,32 @@ pub enum Color {
 }
 
 // Commented out colors are unused.
-// #[cfg(test)]
-// pub const BLACK: Color = Color::C16(0);
+pub const BLACK: Color = Color::C16(0);
 pub const RED: Color = Color::C16(1);
 pub const GREEN: Color = Color::C16(2);
 pub const YELLOW: Color = Color::C16(3);
 pub const BLUE: Color = Color::C16(4);
 pub const MAGENTA: Color = Color::C16(5);
-// pub const CYAN: Color = Color::C16(6);
+pub const CYAN: Color = Color::C16(6);
 pub const WHITE: Color = Color::C16(7);
 pub const LIGHT_BLACK: Color = Color::C16(8);
-// pub const LIGHT_RED: Color = Color::C16(9);
-// pub const LIGHT_GREEN: Color = Color::C16(10);
+pub const LIGHT_RED: Color = Color::C16(9);
+pub const LIGHT_GREEN: Color = Color::C16(10);
 // pub const LIGHT_YELLOW: Color = Color::C16(11);
 pub const LIGHT_BLUE: Color = Color::C16(12);
-// pub const LIGHT_MAGENTA: Color = Color::C16(13);
-// pub const LIGHT_CYAN: Color = Color::C16(14);
-// pub const LIGHT_WHITE: Color = Color::C16(15);
+pub const LIGHT_MAGENTA: Color = Color::C16(13);
+pub const LIGHT_CYAN: Color = Color::C16(14);
+pub const LIGHT_WHITE: Color = Color::C16(15);
 pub const DEFAULT: Color = Color::Default;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct Style {
     pub fg: Color,
     pub bg: Color,
     pub inverted: bool,
     pub bold: bool,
     pub dimmed: bool,
+    pub underline: bool,
 }
 
 impl Style {
@@ -43,6 +43,7 @@ impl Style {
             inverted: false,
             bold: false,
             dimmed: false,
+            underline: false,
         }
     }
 }
@@ -54,6 +55,7 @@ impl Default for Style {
 }
 
 pub trait Terminal: Write {
+    #[allow(dead_code)]
     fn clear_screen(&mut self) -> Result;
     fn clear_line(&mut self) -> Result;
 
@@ -68,16 +70,24 @@ pub trait Terminal: Write {
     fn set_inverted(&mut self, inverted: bool) -> Result;
     fn set_bold(&mut self, bold: bool) -> Result;
     fn set_dimmed(&mut self, dimmed: bool) -> Result;
+    fn set_underline(&mut self, underline: bool) -> Result;
+
+    // Use a background color in place of the default one until it's set
+    // back to Color::Default, e.g., to highlight an entire line.
+    fn set_line_bg(&mut self, color: Color) -> Result;
 
+    #[allow(dead_code)]
     fn output(&self) -> &str;
 
     // Only used for testing.
+    #[allow(dead_code)]
     fn clear_output(&mut self);
 }
 
 pub struct AnsiTerminal {
     pub output: String,
     pub style: Style,
+    pub line_bg: Color,
 }
 
 impl AnsiTerminal {
@@ -85,14 +95,15 @@ impl AnsiTerminal {
         AnsiTerminal {
             output,
             style: Style::default(),
+            line_bg: Color::Default,
         }
     }
 
-    pub fn flush_contents<W: std::io::Write>(&mut self, out: &mut W) -> std::io::Result<usize> {
-        let bytes = out.write(self.output.as_bytes())?;
+    pub fn flush_contents<W: std::io::Write>(&mut self, out: &mut W) -> std::io::Result<()> {
+        out.write_all(self.output.as_bytes())?;
         out.flush()?;
         self.output.clear();
-        Ok(bytes)
+        Ok(())
     }
 }
 
@@ -122,20 +133,31 @@ impl Terminal for AnsiTerminal {
     }
 
     fn set_style(&mut self, style: &Style) -> Result {
+        // The colors depend on whether the style is inverted.
+        self.set_inverted(style.inverted)?;
         self.set_fg(style.fg)?;
         self.set_bg(style.bg)?;
-        self.set_inverted(style.inverted)?;
         self.set_bold(style.bold)?;
         self.set_dimmed(style.dimmed)?;
+        self.set_underline(style.underline)?;
         Ok(())
     }
 
     fn reset_style(&mut self) -> Result {
         self.style = Style::default();
-        write!(self, "\x1b[0m")
+        write!(self, "\x1b[0m")?;
+        self.set_bg(Color::Default)
     }
 
     fn set_fg(&mut self, color: Color) -> Result {
+        // Don't draw text that would be invisible against the line background.
+        // Inverted text is drawn on top of its fg color instead, e.g., a search
+        // match, so it keeps its color even when it's the same as the line's.
+        let color = if color == self.line_bg && !self.style.inverted {
+            Color::Default
+        } else {
+            color
+        };
         if self.style.fg != color {
             match color {
                 Color::C16(c) => write!(self, "\x1b[38;5;{c}m")?,
@@ -147,6 +169,15 @@ impl Terminal for AnsiTerminal {
     }
 
     fn set_bg(&mut self, color: Color) -> Result {
+        // Inverted text whose fg matches the line background would be drawn
+        // in the line background color on top of it, so use the actual
+        // default background for its text instead.
+        let text_matches_line_bg = self.style.inverted && self.style.fg == self.line_bg;
+        let color = if color == Color::Default && !text_matches_line_bg {
+            self.line_bg
+        } else {
+            color
+        };
         if self.style.bg != color {
             match color {
                 Color::C16(c) => write!(self, "\x1b[48;5;{c}m")?,
@@ -201,6 +232,23 @@ impl Terminal for AnsiTerminal {
         Ok(())
     }
 
+    fn set_underline(&mut self, underline: bool) -> Result {
+        if self.style.underline != underline {
+            if underline {
+                write!(self, "\x1b[4m")?;
+            } else {
+                write!(self, "\x1b[24m")?;
+            }
+            self.style.underline = underline;
+        }
+        Ok(())
+    }
+
+    fn set_line_bg(&mut self, color: Color) -> Result {
+        self.line_bg = color;
+        self.set_bg(Color::Default)
+    }
+
     fn output(&self) -> &str {
         &self.output
     }
@@ -273,6 +321,8 @@ pub mod test {
         fn set_inverted(&mut self, _inverted: bool) -> Result { Ok(()) }
         fn set_bold(&mut self, _bold: bool) -> Result { Ok(()) }
         fn set_dimmed(&mut self, _bold: bool) -> Result { Ok(()) }
+        fn set_underline(&mut self, _underline: bool) -> Result { Ok(()) }
+        fn set_line_bg(&mut self, _color: Color) -> Result { Ok(()) }
         fn output(&self) -> &str { &self.output }
         fn clear_output(&mut self) { self.output.clear() }
     }
@@ -327,6 +377,13 @@ pub mod test {
                         write!(self.output, "_!D_")?;
                     }
                 }
+                if self.style.underline != self.pending_style.underline {
+                    if self.pending_style.underline {
+                        write!(self.output, "_U_")?;
+                    } else {
+                        write!(self.output, "_!U_")?;
+                    }
+                }
             }
 
             self.style = self.pending_style;
@@ -406,6 +463,18 @@ pub mod test {
             Ok(())
         }
 
+        fn set_underline(&mut self, underline: bool) -> Result {
+            self.pending_style.underline = underline;
+            Ok(())
+        }
+
+        fn set_line_bg(&mut self, color: Color) -> Result {
+            if self.show_style {
+                write!(self.output, "_LINEBG({color})_")?;
+            }
+            Ok(())
+        }
+
         fn output(&self) -> &str {
             &self.output
         }
@@ -0,0 +1,437 @@
+use crate::flatjson::{FlatJson, Index, OptionIndex};
+
+// The fields that are checked for timestamps, in order, when a field
+// isn't specified explicitly.
+const DEFAULT_TIMESTAMP_FIELDS: [&str; 5] = ["timestamp", "@timestamp", "time", "ts", "date"];
+
+// Characters used to show how many records fall in each column of the
+// timeline, from least to most.
+const DENSITY_CHARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+// A timeline of the top-level records of the input (e.g., newline-delimited
+// log entries), based on a timestamp field in each record. It's rendered as
+// a density bar that can be used to jump to records by time.
+pub struct Timeline {
+    pub field: String,
+    // (Milliseconds since the epoch, index of the record), sorted by time.
+    records_by_time: Vec<(i64, Index)>,
+    // The same, but sorted by index, to find the time of a record.
+    records_by_index: Vec<(Index, i64)>,
+}
+
+impl Timeline {
+    pub fn new(flatjson: &FlatJson, field: Option<&str>) -> Result<Timeline, String> {
+        let candidate_fields = match field {
+            Some(field) => vec![field],
+            None => DEFAULT_TIMESTAMP_FIELDS.to_vec(),
+        };
+
+        for field in candidate_fields.iter() {
+            let records_by_index = Self::record_timestamps(flatjson, field, 0);
+            if records_by_index.is_empty() {
+                continue;
+            }
+
+            let mut records_by_time: Vec<(i64, Index)> = records_by_index
+                .iter()
+                .map(|(index, time)| (*time, *index))
+                .collect();
+            records_by_time.sort();
+
+            return Ok(Timeline {
+                field: field.to_string(),
+                records_by_time,
+                records_by_index,
+            });
+        }
+
+        match field {
+            Some(field) => Err(format!("No records have a timestamp in \"{field}\"")),
+            None => Err(format!(
+                "No records have a timestamp in any of: {}",
+                DEFAULT_TIMESTAMP_FIELDS.join(", ")
+            )),
+        }
+    }
+
+    // Add the records appended to the document, starting with the one at
+    // the given row, e.g., log entries that were read after the timeline
+    // was shown.
+    pub fn add_records(&mut self, flatjson: &FlatJson, first_record: Index) {
+        let new_records = Self::record_timestamps(flatjson, &self.field, first_record);
+        self.records_by_time
+            .extend(new_records.iter().map(|(index, time)| (*time, *index)));
+        // The new records are sorted by index, and usually by time too, so
+        // this is mostly merging two sorted runs.
+        self.records_by_time.sort();
+        self.records_by_index.extend(new_records);
+    }
+
+    fn record_timestamps(
+        flatjson: &FlatJson,
+        field: &str,
+        first_record: Index,
+    ) -> Vec<(Index, i64)> {
+        let mut timestamps = vec![];
+
+        let mut record = OptionIndex::Index(first_record);
+        while let OptionIndex::Index(index) = record {
+            let mut child = match flatjson[index].is_opening_of_container() {
+                true => flatjson[index].first_child(),
+                false => OptionIndex::Nil,
+            };
+
+            while let OptionIndex::Index(child_index) = child {
+                let row = &flatjson[child_index];
+                // key_range includes the quotes around the key.
+                let is_field = row.key_range.as_ref().map_or(false, |key_range| {
+                    &flatjson.1[key_range.start + 1..key_range.end - 1] == field
+                });
+
+                if is_field {
+                    if let Some(time) = parse_timestamp(&flatjson.1[row.range.clone()]) {
+                        timestamps.push((index, time));
+                    }
+                    break;
+                }
+
+                child = row.next_sibling;
+            }
+
+            record = flatjson[index].next_sibling;
+        }
+
+        timestamps
+    }
+
+    fn first_time(&self) -> i64 {
+        self.records_by_time.first().unwrap().0
+    }
+
+    fn last_time(&self) -> i64 {
+        self.records_by_time.last().unwrap().0
+    }
+
+    // Timestamps can be anywhere in the range of an i64, so the distance
+    // between them is computed in an i128 so that it can't overflow.
+    fn column_for_time(&self, time: i64, width: usize) -> usize {
+        let span = self.last_time() as i128 - self.first_time() as i128;
+        if span == 0 || width == 0 {
+            return 0;
+        }
+
+        let offset = time as i128 - self.first_time() as i128;
+        let column = offset * (width - 1) as i128 / span;
+        column.clamp(0, (width - 1) as i128) as usize
+    }
+
+    fn time_for_column(&self, column: usize, width: usize) -> i64 {
+        if width <= 1 {
+            return self.first_time();
+        }
+
+        let column = column.min(width - 1);
+        let span = self.last_time() as i128 - self.first_time() as i128;
+        (self.first_time() as i128 + span * column as i128 / (width - 1) as i128) as i64
+    }
+
+    // The number of records in each column of a timeline of the given width.
+    fn densities(&self, width: usize) -> Vec<usize> {
+        let mut densities = vec![0; width];
+        if width > 0 {
+            for (time, _) in self.records_by_time.iter() {
+                densities[self.column_for_time(*time, width)] += 1;
+            }
+        }
+        densities
+    }
+
+    // The characters used to draw a timeline of the given width.
+    pub fn density_chars(&self, width: usize) -> Vec<char> {
+        let densities = self.densities(width);
+        let max_density = densities.iter().copied().max().unwrap_or(0).max(1);
+
+        densities
+            .iter()
+            .map(|density| match density {
+                0 => ' ',
+                n => DENSITY_CHARS[(n * DENSITY_CHARS.len() - 1) / max_density],
+            })
+            .collect()
+    }
+
+    // The column of the record containing the given row, if it has a timestamp.
+    pub fn column_of_row(&self, flatjson: &FlatJson, row: Index, width: usize) -> Option<usize> {
+        let record = top_level_record(flatjson, row);
+        let position = self
+            .records_by_index
+            .binary_search_by_key(&record, |(index, _)| *index)
+            .ok()?;
+
+        Some(self.column_for_time(self.records_by_index[position].1, width))
+    }
+
+    // The record whose timestamp is closest to the time represented by the
+    // given column.
+    pub fn record_nearest_column(&self, column: usize, width: usize) -> Index {
+        let time = self.time_for_column(column, width);
+        let after = self.records_by_time.partition_point(|(t, _)| *t < time);
+
+        if after == self.records_by_time.len() {
+            return self.records_by_time[after - 1].1;
+        }
+        let distance = |other: i64| (time as i128 - other as i128).abs();
+        if after > 0
+            && distance(self.records_by_time[after - 1].0) < distance(self.records_by_time[after].0)
+        {
+            return self.records_by_time[after - 1].1;
+        }
+        self.records_by_time[after].1
+    }
+
+    // Move along the timeline from the record containing the given row to
+    // the closest record in the next (or previous) column that has any.
+    pub fn step(
+        &self,
+        flatjson: &FlatJson,
+        row: Index,
+        forward: bool,
+        width: usize,
+    ) -> Option<Index> {
+        let column = match self.column_of_row(flatjson, row, width) {
+            Some(column) => column,
+            None if forward => return Some(self.records_by_time[0].1),
+            None => return Some(self.records_by_time.last().unwrap().1),
+        };
+
+        if forward {
+            let next = self
+                .records_by_time
+                .partition_point(|(time, _)| self.column_for_time(*time, width) <= column);
+            self.records_by_time.get(next).map(|(_, index)| *index)
+        } else {
+            let prev = self
+                .records_by_time
+                .partition_point(|(time, _)| self.column_for_time(*time, width) < column);
+            prev.checked_sub(1).map(|prev| self.records_by_time[prev].1)
+        }
+    }
+}
+
+fn top_level_record(flatjson: &FlatJson, mut row: Index) -> Index {
+    while let OptionIndex::Index(parent) = flatjson[row].parent {
+        row = parent;
+    }
+    if flatjson[row].is_closing_of_container() {
+        row = flatjson[row].pair_index().unwrap();
+    }
+    row
+}
+
+// Parse a timestamp into milliseconds since the epoch. Timestamps can be
+// numbers (in seconds, milliseconds, or microseconds since the epoch,
+// depending on their magnitude), or strings formatted like
+// "2023-04-05T06:07:08.123Z" (RFC 3339), where the time, fractional
+// seconds, and time zone are optional.
+pub fn parse_timestamp(value: &str) -> Option<i64> {
+    if let Some(s) = value.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+        return parse_date_time(s);
+    }
+
+    let n: f64 = value.parse().ok()?;
+    let millis = if n.abs() < 1e11 {
+        n * 1e3
+    } else if n.abs() < 1e14 {
+        n
+    } else {
+        n / 1e3
+    };
+    Some(millis as i64)
+}
+
+fn parse_date_time(s: &str) -> Option<i64> {
+    let bytes = s.as_bytes();
+    let number = |range: std::ops::Range<usize>| -> Option<i64> {
+        let digits = s.get(range)?;
+        if digits.bytes().all(|b| b.is_ascii_digit()) {
+            digits.parse().ok()
+        } else {
+            None
+        }
+    };
+
+    if bytes.get(4) != Some(&b'-') || bytes.get(7) != Some(&b'-') {
+        return None;
+    }
+    let year = number(0..4)?;
+    let month = number(5..7)?;
+    let day = number(8..10)?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let mut millis = days_from_civil(year, month, day) * 86_400_000;
+    let mut rest = &s[10..];
+
+    if let Some(time) = rest.strip_prefix(|c: char| c == 'T' || c == 't' || c == ' ') {
+        let hours = number(11..13)?;
+        if time.as_bytes().get(2) != Some(&b':') {
+            return None;
+        }
+        let minutes = number(14..16)?;
+        millis += (hours * 60 + minutes) * 60_000;
+        rest = &s[16..];
+
+        if let Some(seconds) = rest.strip_prefix(':') {
+            millis += number(17..19)? * 1000;
+            rest = &seconds[2..];
+
+            if let Some(fraction) = rest.strip_prefix('.') {
+                let len = fraction.bytes().take_while(u8::is_ascii_digit).count();
+                if len == 0 {
+                    return None;
+                }
+                let digits = format!("{:0<3}", &fraction[..len.min(3)]);
+                millis += digits.parse::<i64>().ok()?;
+                rest = &fraction[len..];
+            }
+        }
+    }
+
+    match rest {
+        "" | "Z" | "z" => {}
+        offset => {
+            let sign = match offset.as_bytes()[0] {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return None,
+            };
+            let offset = offset[1..].replace(':', "");
+            if offset.len() != 4 || !offset.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            let hours: i64 = offset[..2].parse().ok()?;
+            let minutes: i64 = offset[2..].parse().ok()?;
+            millis -= sign * (hours * 60 + minutes) * 60_000;
+        }
+    }
+
+    Some(millis)
+}
+
+// The number of days between 1970-01-01 and the given date in the
+// proleptic Gregorian calendar.
+pub fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flatjson::parse_top_level_json;
+    use crate::jsonparser;
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(Some(0), parse_timestamp("\"1970-01-01\""));
+        assert_eq!(
+            Some(1_680_674_828_000),
+            parse_timestamp("\"2023-04-05T06:07:08Z\"")
+        );
+        assert_eq!(
+            Some(1_680_674_828_120),
+            parse_timestamp("\"2023-04-05 06:07:08.12\"")
+        );
+        assert_eq!(
+            Some(1_680_674_828_123),
+            parse_timestamp("\"2023-04-05T08:07:08.123456+02:00\"")
+        );
+        assert_eq!(
+            Some(1_680_674_820_000),
+            parse_timestamp("\"2023-04-05T06:07\"")
+        );
+        assert_eq!(Some(1_680_674_828_000), parse_timestamp("1680674828"));
+        assert_eq!(Some(1_680_674_828_500), parse_timestamp("1680674828500"));
+        assert_eq!(Some(1_680_674_828_500), parse_timestamp("1680674828.5"));
+
+        assert_eq!(None, parse_timestamp("\"2023-04-05T\""));
+        assert_eq!(None, parse_timestamp("\"2023-13-05\""));
+        assert_eq!(None, parse_timestamp("\"yesterday\""));
+        assert_eq!(None, parse_timestamp("true"));
+    }
+
+    #[test]
+    fn test_timeline() {
+        const NDJSON: &str = r#"
+            {"ts": "2023-01-01T00:00:00Z", "msg": "a"}
+            {"ts": "2023-01-01T00:00:01Z", "msg": "b"}
+            {"msg": "no timestamp"}
+            {"ts": "2023-01-01T00:00:09Z", "msg": "c"}
+        "#;
+        let fj = parse_top_level_json(NDJSON.to_owned()).unwrap();
+
+        assert!(Timeline::new(&fj, Some("time")).is_err());
+        let timeline = Timeline::new(&fj, None).unwrap();
+        assert_eq!("ts", timeline.field);
+
+        // Rows: 0 {, 1 ts, 2 msg, 3 }, 4 {, ..., 8 {, 9 msg, 10 }, 11 {, 12 ts
+        assert_eq!(vec!['█', ' ', ' ', ' ', '▄'], timeline.density_chars(5));
+        assert_eq!(Some(0), timeline.column_of_row(&fj, 2, 10));
+        assert_eq!(Some(9), timeline.column_of_row(&fj, 12, 10));
+        assert_eq!(None, timeline.column_of_row(&fj, 9, 10));
+
+        assert_eq!(4, timeline.record_nearest_column(1, 10));
+        assert_eq!(4, timeline.record_nearest_column(4, 10));
+        assert_eq!(11, timeline.record_nearest_column(6, 10));
+
+        assert_eq!(Some(4), timeline.step(&fj, 0, true, 10));
+        assert_eq!(Some(11), timeline.step(&fj, 4, true, 10));
+        assert_eq!(None, timeline.step(&fj, 11, true, 10));
+        assert_eq!(Some(4), timeline.step(&fj, 12, false, 10));
+        assert_eq!(Some(0), timeline.step(&fj, 9, true, 10));
+    }
+
+    #[test]
+    fn test_add_records() {
+        let mut fj = parse_top_level_json("{\"ts\": 10}\n{\"ts\": 30}".to_owned()).unwrap();
+        let mut timeline = Timeline::new(&fj, None).unwrap();
+
+        let (rows, pretty, depth) = jsonparser::parse("{\"ts\": 20}\n{\"msg\": 1}").unwrap();
+        fj.append(rows, &pretty, depth);
+        timeline.add_records(&fj, 6);
+
+        assert_eq!(
+            vec![(10000, 0), (20000, 6), (30000, 3)],
+            timeline.records_by_time
+        );
+        assert_eq!(
+            vec![(0, 10000), (3, 30000), (6, 20000)],
+            timeline.records_by_index
+        );
+        assert_eq!(Some(4), timeline.column_of_row(&fj, 7, 9));
+    }
+
+    #[test]
+    fn test_timeline_extreme_timestamps() {
+        let fj = parse_top_level_json("{\"ts\": 0}\n{\"ts\": 1}".to_owned()).unwrap();
+        let mut timeline = Timeline::new(&fj, None).unwrap();
+        // Timestamps spanning more than an i64 can represent.
+        timeline.records_by_time = vec![(i64::MIN, 0), (0, 3), (i64::MAX, 6)];
+
+        assert_eq!(0, timeline.column_for_time(i64::MIN, 10));
+        assert_eq!(4, timeline.column_for_time(0, 10));
+        assert_eq!(9, timeline.column_for_time(i64::MAX, 10));
+        assert_eq!(vec![1, 0, 0, 0, 1, 0, 0, 0, 0, 1], timeline.densities(10));
+
+        assert_eq!(i64::MIN, timeline.time_for_column(0, 10));
+        assert_eq!(i64::MAX, timeline.time_for_column(9, 10));
+        // Columns past the end of the timeline are the last one.
+        assert_eq!(i64::MAX, timeline.time_for_column(20, 10));
+        assert_eq!(6, timeline.record_nearest_column(20, 10));
+    }
+}
@@ -3,11 +3,54 @@ use std::fmt;
 use std::ops::Range;
 
 use unicode_segmentation::UnicodeSegmentation;
-use unicode_width::UnicodeWidthStr;
+use unicode_width::UnicodeWidthChar;
+
+// This module provides functionality for truncating strings,
+// displaying them, and manipulating which portion of the string
+// is visible.
+
+// Control characters would be interpreted by the terminal rather than
+// displayed, which can corrupt the screen (e.g., an ESC starting an ANSI
+// escape sequence), so we display them using the corresponding symbols
+// from the Unicode "Control Pictures" block instead, e.g., '␛' for ESC.
+// There are no pictures for the C1 control characters, so those are
+// displayed as a replacement character.
+//
+// Each replacement is one column wide, so control characters are also
+// considered to be one column wide when computing display widths.
+pub fn is_control(ch: char) -> bool {
+    matches!(ch as u32, 0x00..=0x1F | 0x7F..=0x9F)
+}
+
+fn control_picture(ch: char) -> char {
+    match ch as u32 {
+        c @ 0x00..=0x1F => char::from_u32(0x2400 + c).unwrap(),
+        0x7F => '\u{2421}',
+        _ => '\u{FFFD}',
+    }
+}
 
-/// This module provides functionality for truncating strings,
-/// displaying them, and manipulating which portion of the string
-/// is visible.
+pub fn write_sanitized<W: fmt::Write + ?Sized>(out: &mut W, mut s: &str) -> fmt::Result {
+    while let Some(index) = s.find(is_control) {
+        let ch = s[index..].chars().next().unwrap();
+        out.write_str(&s[..index])?;
+        out.write_char(control_picture(ch))?;
+        s = &s[index + ch.len_utf8()..];
+    }
+    out.write_str(s)
+}
+
+pub fn display_width(s: &str) -> isize {
+    s.chars()
+        .map(|ch| {
+            if is_control(ch) {
+                1
+            } else {
+                UnicodeWidthChar::width(ch).unwrap_or(0) as isize
+            }
+        })
+        .sum()
+}
 
 /// A TruncatedStrView represents an attempt to fit a string within
 /// a given amount of available space. When `range` is None, it
@@ -415,6 +458,36 @@ impl TruncatedStrView {
     }
 }
 
+/// The horizontal scroll position of a value that is remembered across
+/// renders. The `anchor` is the view as the user last left it: when it
+/// was first shown, or last scrolled. Each render fits the anchor into
+/// the space available at the time, rather than resizing the previously
+/// rendered view, so that temporarily having less (or more) space, e.g.,
+/// after resizing the window, toggling modes or showing line numbers,
+/// doesn't lose the scroll position once the space is restored.
+#[derive(Debug, Copy, Clone)]
+pub struct AnchoredStrView {
+    anchor: TruncatedStrView,
+    view: TruncatedStrView,
+}
+
+impl AnchoredStrView {
+    pub fn new(view: TruncatedStrView) -> AnchoredStrView {
+        AnchoredStrView { anchor: view, view }
+    }
+
+    /// The view as it was last rendered.
+    pub fn view(&self) -> TruncatedStrView {
+        self.view
+    }
+
+    /// Fit the anchored view into the available space.
+    pub fn resize(&mut self, s: &str, available_space: isize) -> TruncatedStrView {
+        self.view = self.anchor.resize(s, available_space);
+        self.view
+    }
+}
+
 impl<'a> RangeAdjuster<'a> {
     /// Initialize a RangeAdjuster at the beginning of a string, but is
     /// not showing any part of the string.
@@ -456,7 +529,7 @@ impl<'a> RangeAdjuster<'a> {
         for _ in 0..count {
             if let Some(grapheme) = right_graphemes.next() {
                 self.end += grapheme.len();
-                self.used_space += UnicodeWidthStr::width(grapheme) as isize;
+                self.used_space += display_width(grapheme);
                 if self.end == self.s.len() {
                     // No more trailing ellipsis.
                     self.used_space -= 1;
@@ -473,7 +546,7 @@ impl<'a> RangeAdjuster<'a> {
         for _ in 0..count {
             if let Some(grapheme) = left_graphemes.next_back() {
                 self.start -= grapheme.len();
-                self.used_space += UnicodeWidthStr::width(grapheme) as isize;
+                self.used_space += display_width(grapheme);
                 if self.start == 0 {
                     // No more leading ellipsis.
                     self.used_space -= 1;
@@ -504,7 +577,7 @@ impl<'a> RangeAdjuster<'a> {
     // Adds a grapheme to the right side of a view if it will fit.
     fn add_grapheme_to_right_if_it_will_fit(&mut self, grapheme: &str) -> bool {
         let new_end = self.end + grapheme.len();
-        let mut new_used_space = self.used_space + UnicodeWidthStr::width(grapheme) as isize;
+        let mut new_used_space = self.used_space + display_width(grapheme);
 
         if new_end == self.s.len() {
             // No more trailing ellipsis.
@@ -541,7 +614,7 @@ impl<'a> RangeAdjuster<'a> {
     // Adds a grapheme to the left side of a view if it will fit.
     fn add_grapheme_to_left_if_it_will_fit(&mut self, grapheme: &str) -> bool {
         let new_start = self.start - grapheme.len();
-        let mut new_used_space = self.used_space + UnicodeWidthStr::width(grapheme) as isize;
+        let mut new_used_space = self.used_space + display_width(grapheme);
 
         if new_start == 0 {
             // No more leading ellipsis.
@@ -625,7 +698,7 @@ impl<'a> RangeAdjuster<'a> {
                 self.used_space += 1;
             }
             self.end -= rightmost_grapheme.len();
-            self.used_space -= UnicodeWidthStr::width(rightmost_grapheme) as isize;
+            self.used_space -= display_width(rightmost_grapheme);
         }
     }
 
@@ -641,7 +714,7 @@ impl<'a> RangeAdjuster<'a> {
                 self.used_space += 1;
             }
             self.start += leftmost_grapheme.len();
-            self.used_space -= UnicodeWidthStr::width(leftmost_grapheme) as isize;
+            self.used_space -= display_width(leftmost_grapheme);
         }
     }
 
@@ -704,7 +777,7 @@ impl<'a, 'b> fmt::Display for TruncatedStrSlice<'a, 'b> {
             f.write_str("�")?;
         }
 
-        f.write_str(&self.s[start..end])?;
+        write_sanitized(f, &self.s[start..end])?;
 
         if end != self.s.len() {
             f.write_str("…")?;
@@ -1103,6 +1176,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_control_characters_are_displayed_safely() {
+        let string = "a\x1b[31mb\x7fc\u{9b}d";
+
+        assert_eq!(11, display_width(string));
+
+        let mut sanitized = String::new();
+        write_sanitized(&mut sanitized, string).unwrap();
+        assert_eq!("a␛[31mb␡c�d", sanitized);
+
+        let truncated_view = TruncatedStrView::init_start(string, 5);
+        assert_eq!("a␛[3…", rendered(string, &truncated_view));
+        assert_eq!(Some(5), truncated_view.used_space());
+    }
+
     #[track_caller]
     fn assert_focuses(
         string: &str,
@@ -1122,4 +1210,29 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_anchored_view_keeps_scroll_position() {
+        let s = "abcdefghij";
+        let mut cached = AnchoredStrView::new(
+            TruncatedStrView::init_start(s, 5)
+                .scroll_right(s, 1)
+                .scroll_right(s, 1),
+        );
+        assert_eq!("…def…", rendered(s, &cached.view()));
+
+        // Resizing so small that nothing can be shown, or so large that the
+        // whole string fits, and then back, returns to the same position.
+        for space in [0, 2, 3, 4, 20] {
+            cached.resize(s, space);
+            assert_eq!("…def…", rendered(s, &cached.resize(s, 5)), "{:?}", space);
+        }
+        assert_eq!("abcdefghij", rendered(s, &cached.resize(s, 10)));
+        assert_eq!("…defg…", rendered(s, &cached.resize(s, 6)));
+        assert_eq!("…def…", rendered(s, &cached.resize(s, 5)));
+
+        let mut cached = AnchoredStrView::new(TruncatedStrView::init_back(s, 5));
+        cached.resize(s, 20);
+        assert_eq!("…ghij", rendered(s, &cached.resize(s, 5)));
+    }
 }
@@ -0,0 +1,174 @@
+use crate::viewer::Action;
+
+// The document displayed by `jless --tutor`. Each lesson in the document
+// has a corresponding entry in LESSONS below, which determines which
+// actions complete it.
+pub const TUTORIAL: &str = std::include_str!("./tutor.json");
+pub const TUTORIAL_FILENAME: &str = "tutor.json";
+
+struct Lesson {
+    title: &'static str,
+    completed_by: fn(&Action) -> bool,
+}
+
+const LESSONS: &[Lesson] = &[
+    Lesson {
+        title: "Moving down",
+        completed_by: |action| matches!(action, Action::MoveDown(_)),
+    },
+    Lesson {
+        title: "Moving up",
+        completed_by: |action| matches!(action, Action::MoveUp(_)),
+    },
+    Lesson {
+        title: "Collapsing and expanding",
+        completed_by: |action| matches!(action, Action::ToggleCollapsed),
+    },
+    Lesson {
+        title: "Moving right",
+        completed_by: |action| matches!(action, Action::MoveRight),
+    },
+    Lesson {
+        title: "Moving left",
+        completed_by: |action| matches!(action, Action::MoveLeft),
+    },
+    Lesson {
+        title: "Jumping between siblings",
+        completed_by: |action| {
+            matches!(
+                action,
+                Action::FocusNextSibling(_) | Action::FocusPrevSibling(_)
+            )
+        },
+    },
+    Lesson {
+        title: "Jumping to the parent",
+        completed_by: |action| matches!(action, Action::FocusParent),
+    },
+    Lesson {
+        title: "Jumping to the top and bottom",
+        completed_by: |action| matches!(action, Action::FocusTop | Action::FocusBottom),
+    },
+    Lesson {
+        title: "Collapsing and expanding siblings",
+        completed_by: |action| {
+            matches!(
+                action,
+                Action::CollapseNodeAndSiblings | Action::ExpandNodeAndSiblings
+            )
+        },
+    },
+    Lesson {
+        title: "Switching modes",
+        completed_by: |action| matches!(action, Action::ToggleMode),
+    },
+    Lesson {
+        title: "Scrolling",
+        completed_by: |action| matches!(action, Action::ScrollDown(_) | Action::ScrollUp(_)),
+    },
+];
+
+// Tracks which lessons of the tutorial the user has completed.
+pub struct Tutor {
+    completed: Vec<bool>,
+}
+
+impl Tutor {
+    pub fn new() -> Tutor {
+        Tutor {
+            completed: vec![false; LESSONS.len()],
+        }
+    }
+
+    pub fn welcome_message(&self) -> String {
+        format!(
+            "Welcome to the jless tutor! Start with lesson 1: {}",
+            LESSONS[0].title
+        )
+    }
+
+    // Record that an action was performed, returning a message to display
+    // if it completed a lesson for the first time.
+    pub fn record_action(&mut self, action: &Action) -> Option<String> {
+        let lesson_index = LESSONS
+            .iter()
+            .zip(self.completed.iter())
+            .position(|(lesson, completed)| !completed && (lesson.completed_by)(action))?;
+
+        self.completed[lesson_index] = true;
+
+        let num_completed = self.completed.iter().filter(|c| **c).count();
+        let progress = format!(
+            "✓ Lesson {}: {} ({num_completed}/{})",
+            lesson_index + 1,
+            LESSONS[lesson_index].title,
+            LESSONS.len(),
+        );
+
+        match self.completed.iter().position(|c| !c) {
+            Some(next) => Some(format!(
+                "{progress}; next up, lesson {}: {}",
+                next + 1,
+                LESSONS[next].title
+            )),
+            None => Some(format!("{progress}; you've completed the tutorial!")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flatjson::parse_top_level_json;
+
+    #[test]
+    fn test_tutorial_lessons_match_document() {
+        let fj = parse_top_level_json(TUTORIAL.to_owned()).unwrap();
+        let lessons = fj.build_path_to_node(crate::flatjson::PathType::Dot, 2);
+        assert_eq!(Ok(".lessons".to_string()), lessons);
+        assert_eq!(LESSONS.len(), fj.num_children(2));
+
+        for (i, lesson) in LESSONS.iter().enumerate() {
+            let title = format!("\"title\": \"{}\"", lesson.title);
+            assert!(fj.1.contains(&title), "Lesson {} not in document", i + 1);
+        }
+    }
+
+    #[test]
+    fn test_record_action() {
+        let mut tutor = Tutor::new();
+
+        assert_eq!(
+            Some("✓ Lesson 2: Moving up (1/11); next up, lesson 1: Moving down".to_string()),
+            tutor.record_action(&Action::MoveUp(1)),
+        );
+        // Repeating an action doesn't complete anything new.
+        assert_eq!(None, tutor.record_action(&Action::MoveUp(3)));
+        assert_eq!(None, tutor.record_action(&Action::FocusMatchingPair));
+        assert_eq!(
+            Some(
+                "✓ Lesson 1: Moving down (2/11); next up, lesson 3: Collapsing and expanding"
+                    .to_string()
+            ),
+            tutor.record_action(&Action::MoveDown(1)),
+        );
+
+        for action in [
+            Action::ToggleCollapsed,
+            Action::MoveRight,
+            Action::MoveLeft,
+            Action::FocusNextSibling(1),
+            Action::FocusParent,
+            Action::FocusBottom,
+            Action::ExpandNodeAndSiblings,
+            Action::ToggleMode,
+        ] {
+            assert!(tutor.record_action(&action).is_some());
+        }
+
+        assert_eq!(
+            Some("✓ Lesson 11: Scrolling (11/11); you've completed the tutorial!".to_string()),
+            tutor.record_action(&Action::ScrollDown(1)),
+        );
+    }
+}
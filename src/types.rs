@@ -2,6 +2,12 @@ pub const DEFAULT_WIDTH: u16 = 80;
 pub const DEFAULT_HEIGHT: u16 = 24;
 pub const STATUS_BAR_HEIGHT: u16 = 2;
 
+// Screens smaller than this (as can happen briefly while resizing a tmux
+// pane) only show as much of the viewer as fits, without the status bar
+// or any of the panels around the viewer.
+pub const MIN_LAYOUT_WIDTH: u16 = 20;
+pub const MIN_LAYOUT_HEIGHT: u16 = STATUS_BAR_HEIGHT + 1;
+
 #[derive(Copy, Clone, Debug)]
 pub struct TTYDimensions {
     pub width: u16,
@@ -9,23 +15,27 @@ pub struct TTYDimensions {
 }
 
 impl TTYDimensions {
+    // Terminals can report a size of 0 while they're being resized, but
+    // there's always at least one cell to draw in.
     pub fn from_size(size: (u16, u16)) -> TTYDimensions {
         TTYDimensions {
-            width: size.0,
-            height: size.1,
+            width: size.0.max(1),
+            height: size.1.max(1),
         }
     }
 
+    // The viewer always gets at least one row, even if there's no room for
+    // it, so that it always has a focused row to show.
     pub fn without_status_bar(&self) -> TTYDimensions {
         TTYDimensions {
             width: self.width,
-            height: if self.height < STATUS_BAR_HEIGHT {
-                0
-            } else {
-                self.height - STATUS_BAR_HEIGHT
-            },
+            height: self.height.saturating_sub(STATUS_BAR_HEIGHT).max(1),
         }
     }
+
+    pub fn is_below_minimum_layout(&self) -> bool {
+        self.width < MIN_LAYOUT_WIDTH || self.height < MIN_LAYOUT_HEIGHT
+    }
 }
 
 impl Default for TTYDimensions {
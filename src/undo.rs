@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+
+use crate::diff::DiffMarker;
+use crate::flatjson::{DeletedValue, Index};
+
+// Changes made to the document with :edit and :delete are recorded, so
+// that they can be undone with u and redone with ctrl-r.
+//
+// Each change is recorded as the change that reverts it, e.g., deleting a
+// value is recorded as restoring it, and reverting a change produces the
+// change that reverts *that*, which is recorded so it can be redone.
+// Changes are always reverted in the opposite order they were made in, so
+// the rows they refer to are still where they were.
+
+// The most changes that are kept around to be undone.
+const MAX_UNDO_HISTORY: usize = 1000;
+
+pub enum Change {
+    // Replace a primitive value with a JSON token.
+    Replace { index: Index, token: String },
+    Delete { index: Index },
+    Restore(DeletedValue),
+}
+
+impl Change {
+    // The command that made the change that this one reverts.
+    pub fn command(&self) -> &'static str {
+        match self {
+            Change::Replace { .. } => "edit",
+            Change::Delete { .. } | Change::Restore(_) => "delete",
+        }
+    }
+}
+
+// Along with each change, the diff markers from before it was reverted are
+// kept, since they were computed for the document at that point.
+type Entry = (Change, HashMap<Index, DiffMarker>);
+
+#[derive(Default)]
+pub struct UndoHistory {
+    undo: Vec<Entry>,
+    redo: Vec<Entry>,
+}
+
+impl UndoHistory {
+    // Record a change that was just made, given the change that reverts
+    // it. Changes that were undone can no longer be redone.
+    pub fn record(&mut self, revert: Change, diff_markers: HashMap<Index, DiffMarker>) {
+        self.push_undo(revert, diff_markers);
+        self.redo.clear();
+    }
+
+    pub fn push_undo(&mut self, revert: Change, diff_markers: HashMap<Index, DiffMarker>) {
+        self.undo.push((revert, diff_markers));
+        if self.undo.len() > MAX_UNDO_HISTORY {
+            self.undo.remove(0);
+        }
+    }
+
+    pub fn push_redo(&mut self, change: Change, diff_markers: HashMap<Index, DiffMarker>) {
+        self.redo.push((change, diff_markers));
+    }
+
+    pub fn pop_undo(&mut self) -> Option<Entry> {
+        self.undo.pop()
+    }
+
+    pub fn pop_redo(&mut self) -> Option<Entry> {
+        self.redo.pop()
+    }
+
+    // Forget every change, e.g., when the document is replaced.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn indexes(entries: &[Entry]) -> Vec<Index> {
+        entries
+            .iter()
+            .map(|(change, _)| match change {
+                Change::Delete { index } => *index,
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_recording_clears_redo() {
+        let mut history = UndoHistory::default();
+        history.record(Change::Delete { index: 1 }, HashMap::new());
+        history.record(Change::Delete { index: 2 }, HashMap::new());
+
+        let (change, markers) = history.pop_undo().unwrap();
+        history.push_redo(change, markers);
+        assert_eq!(
+            (vec![1], vec![2]),
+            (indexes(&history.undo), indexes(&history.redo))
+        );
+
+        // Redoing doesn't forget the other changes that were undone.
+        let (change, markers) = history.pop_redo().unwrap();
+        history.push_undo(change, markers);
+        assert_eq!(vec![1, 2], indexes(&history.undo));
+
+        let (change, markers) = history.pop_undo().unwrap();
+        history.push_redo(change, markers);
+        history.record(Change::Delete { index: 3 }, HashMap::new());
+        assert_eq!(
+            (vec![1, 3], vec![]),
+            (indexes(&history.undo), indexes(&history.redo))
+        );
+        assert!(history.pop_redo().is_none());
+
+        for index in 0..MAX_UNDO_HISTORY {
+            history.record(Change::Delete { index }, HashMap::new());
+        }
+        assert_eq!(MAX_UNDO_HISTORY, history.undo.len());
+        assert_eq!(Some(&0), indexes(&history.undo).first());
+    }
+}
@@ -1,6 +1,8 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
 use clap::ValueEnum;
 
-use crate::flatjson::{FlatJson, Index, OptionIndex};
+use crate::flatjson::{DeletedValue, FlatJson, Index, OptionIndex};
 use crate::types::TTYDimensions;
 
 #[derive(PartialEq, Eq, Copy, Clone, Debug, ValueEnum)]
@@ -9,6 +11,14 @@ pub enum Mode {
     Data,
 }
 
+// Which part of the focused row is highlighted in data mode. When set,
+// a bare 'y' copies just that part of the row.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum SubFocus {
+    Key,
+    Value,
+}
+
 const DEFAULT_SCROLLOFF: u16 = 3;
 
 pub struct JsonViewer {
@@ -30,6 +40,41 @@ pub struct JsonViewer {
     // Access the functional value via .scrolloff().
     pub scrolloff_setting: u16,
     pub mode: Mode,
+
+    // When set, expanding a container will collapse all of its
+    // siblings, so that only one branch is expanded at a time.
+    pub accordion_mode: bool,
+
+    // A denser variant of data mode that hides array indexes and
+    // only indents nested values by a single space.
+    pub dense: bool,
+
+    // When set, moving into an expanded container focuses the child that
+    // was last focused within it, rather than its first child.
+    pub remember_last_child: bool,
+
+    // Whether runs of identical elements in Arrays are folded into a
+    // single row.
+    pub fold_runs: bool,
+    // The last focused child of each container, keyed by the index of the
+    // container's opening row. This is kept up to date even when
+    // remember_last_child isn't set, so it can be turned on at any time.
+    last_visited_child: HashMap<Index, Index>,
+
+    // Only set in data mode.
+    pub sub_focus: Option<SubFocus>,
+
+    // Rows marked (via 'x') for batch operations. Containers are always
+    // stored using the index of their opening row.
+    pub selected_rows: BTreeSet<Index>,
+
+    // The row marked (via 'M') as the start of relative paths.
+    pub mark: Option<Index>,
+
+    // Arrays whose elements are displayed in reverse order, keyed by the
+    // index of their opening row. This only changes the order rows are
+    // shown and moved through in; the document itself isn't modified.
+    reversed_arrays: HashSet<Index>,
 }
 
 impl JsonViewer {
@@ -43,6 +88,15 @@ impl JsonViewer {
             dimensions: TTYDimensions::default(),
             scrolloff_setting: DEFAULT_SCROLLOFF,
             mode,
+            accordion_mode: false,
+            dense: false,
+            remember_last_child: false,
+            fold_runs: false,
+            last_visited_child: HashMap::new(),
+            sub_focus: None,
+            selected_rows: BTreeSet::new(),
+            mark: None,
+            reversed_arrays: HashSet::new(),
         }
     }
 }
@@ -116,6 +170,10 @@ pub enum Action {
         line: Index,
         make_visible: bool,
     },
+    // Like JumpTo with make_visible, but if the line isn't already on the
+    // screen, show it with some context around it, rather than at the very
+    // top or bottom of the screen.
+    Reveal(Index),
 
     PageUp(usize),
     PageDown(usize),
@@ -132,7 +190,12 @@ pub enum Action {
     ExpandNodeAndSiblings,
     DeepExpandNodeAndSiblings,
 
+    ToggleSelected,
+    CollapseSelected,
+    ExpandSelected,
+
     ToggleMode,
+    ToggleSubFocus,
 
     ResizeViewerDimensions(TTYDimensions),
 }
@@ -171,6 +234,7 @@ impl JsonViewer {
             Action::JumpUp(option_n) => self.jump_up(option_n),
             Action::JumpDown(option_n) => self.jump_down(option_n),
             Action::JumpTo { line, make_visible } => self.jump_to(line, make_visible),
+            Action::Reveal(line) => self.reveal(line),
             Action::PageUp(n) => self.scroll_up(self.dimensions.height as usize * n),
             Action::PageDown(n) => self.scroll_down(self.dimensions.height as usize * n),
             Action::MoveFocusedLineToTop => self.move_focused_line_to_top(),
@@ -183,6 +247,10 @@ impl JsonViewer {
             Action::ExpandNodeAndSiblings => self.expand_node_and_siblings(),
             Action::DeepExpandNodeAndSiblings => self.deep_expand_node_and_siblings(),
             Action::ToggleMode => self.toggle_mode(),
+            Action::ToggleSubFocus => self.toggle_sub_focus(),
+            Action::ToggleSelected => self.toggle_selected(),
+            Action::CollapseSelected => self.collapse_selected(),
+            Action::ExpandSelected => self.expand_selected(),
             Action::ResizeViewerDimensions(dims) => self.dimensions = dims,
         }
 
@@ -190,6 +258,8 @@ impl JsonViewer {
             self.desired_depth = self.flatjson[self.focused_row].depth;
         }
 
+        self.remember_focused_row_in_parent();
+
         if track_window {
             self.ensure_focused_row_is_visible();
         } else if let Some(screen_index) = prev_index_of_focused_row {
@@ -221,6 +291,7 @@ impl JsonViewer {
             Action::JumpUp(_) => false,
             Action::JumpDown(_) => false,
             Action::JumpTo { .. } => true,
+            Action::Reveal(_) => false, // Window refocusing is handled in reveal.
             Action::PageUp(_) => false,
             Action::PageDown(_) => false,
             Action::MoveFocusedLineToTop => false,
@@ -232,6 +303,10 @@ impl JsonViewer {
             Action::ExpandNodeAndSiblings => false,
             Action::DeepExpandNodeAndSiblings => false,
             Action::ToggleMode => false,
+            Action::ToggleSubFocus => false,
+            Action::ToggleSelected => false,
+            Action::CollapseSelected => false,
+            Action::ExpandSelected => false,
             Action::ResizeViewerDimensions(_) => true,
             _ => false,
         }
@@ -249,6 +324,8 @@ impl JsonViewer {
                 | Action::MoveFocusedLineToCenter
                 | Action::MoveFocusedLineToBottom
                 | Action::ToggleMode
+                | Action::ToggleSubFocus
+                | Action::ToggleSelected
                 | Action::ResizeViewerDimensions(_)
         )
     }
@@ -259,7 +336,9 @@ impl JsonViewer {
             | Action::CollapseNodeAndSiblings
             | Action::DeepCollapseNodeAndSiblings
             | Action::ExpandNodeAndSiblings
-            | Action::DeepExpandNodeAndSiblings => Some(self.index_of_focused_row_on_screen()),
+            | Action::DeepExpandNodeAndSiblings
+            | Action::CollapseSelected
+            | Action::ExpandSelected => Some(self.index_of_focused_row_on_screen()),
             _ => None,
         }
     }
@@ -269,8 +348,8 @@ impl JsonViewer {
 
         for _ in 0..rows {
             let prev_row = match self.mode {
-                Mode::Line => self.flatjson.prev_visible_row(row),
-                Mode::Data => self.flatjson.prev_item(row),
+                Mode::Line => self.prev_visible_row(row),
+                Mode::Data => self.prev_item(row),
             };
 
             match prev_row {
@@ -289,8 +368,8 @@ impl JsonViewer {
 
         for _ in 0..rows {
             let next_row = match self.mode {
-                Mode::Line => self.flatjson.next_visible_row(row),
-                Mode::Data => self.flatjson.next_item(row),
+                Mode::Line => self.next_visible_row(row),
+                Mode::Data => self.next_item(row),
             };
 
             match next_row {
@@ -307,22 +386,139 @@ impl JsonViewer {
     fn move_right(&mut self) {
         let focused_row = &self.flatjson[self.focused_row];
         if focused_row.is_primitive() {
+            self.flatjson.unfold_run_containing(self.focused_row);
             return;
         }
 
         if focused_row.is_collapsed() {
             self.flatjson.expand(self.focused_row);
+            self.maybe_collapse_siblings_of_focused_row();
             return;
         }
 
         if focused_row.is_opening_of_container() {
-            self.focused_row = focused_row.first_child().unwrap();
+            let first_child = self.first_child(self.focused_row);
+            self.focused_row = if self.remember_last_child {
+                *self
+                    .last_visited_child
+                    .get(&self.focused_row)
+                    .unwrap_or(&first_child)
+            } else {
+                first_child
+            };
         } else {
             debug_assert!(
                 self.mode == Mode::Line,
                 "Can't be focused on closing char in Data mode"
             );
-            self.focused_row = self.flatjson.prev_visible_row(self.focused_row).unwrap();
+            self.focused_row = self.prev_visible_row(self.focused_row).unwrap();
+        }
+    }
+
+    // Fold or unfold all runs of identical elements in Arrays, keeping the
+    // focused and top rows visible.
+    pub fn set_fold_runs(&mut self, fold_runs: bool) {
+        self.fold_runs = fold_runs;
+        if fold_runs {
+            self.flatjson.fold_runs();
+            self.focused_row = self.flatjson.first_visible_ancestor(self.focused_row);
+            self.ensure_top_row_is_visible();
+        } else {
+            self.flatjson.unfold_runs();
+        }
+    }
+
+    pub fn is_reversed(&self, index: Index) -> bool {
+        self.reversed_arrays.contains(&index)
+    }
+
+    // Display the elements of an array in reverse order, or in their
+    // original order again, given its opening row. Returns whether the
+    // array is now reversed.
+    pub fn toggle_reversed(&mut self, index: Index) -> bool {
+        let reversed = !self.reversed_arrays.remove(&index);
+        if reversed {
+            self.reversed_arrays.insert(index);
+        }
+        reversed
+    }
+
+    // Delete a value, given the index of its opening row, focusing on the
+    // value displayed after it, or the one before it if it was the last
+    // one, or else its parent.
+    pub fn delete_value(
+        &mut self,
+        index: Index,
+    ) -> Result<(impl Fn(Index) -> Option<Index> + Copy, DeletedValue), String> {
+        let row = &self.flatjson[index];
+        let (next_sibling, prev_sibling) = if self.is_in_reversed_array(index) {
+            (row.prev_sibling, row.next_sibling)
+        } else {
+            (row.next_sibling, row.prev_sibling)
+        };
+        let focused_row = match (next_sibling, prev_sibling, row.parent) {
+            (OptionIndex::Index(next), _, _) => next,
+            (_, OptionIndex::Index(prev), _) => prev,
+            (_, _, OptionIndex::Index(parent)) => parent,
+            // The only value can't be deleted.
+            (_, _, OptionIndex::Nil) => index,
+        };
+
+        let (remap, deleted) = self.flatjson.delete_value(index)?;
+        self.focused_row = remap(focused_row).unwrap();
+        self.top_row = remap(self.top_row).unwrap_or(self.focused_row);
+        self.mark = self.mark.and_then(remap);
+        self.selected_rows = self
+            .selected_rows
+            .iter()
+            .filter_map(|i| remap(*i))
+            .collect();
+        self.last_visited_child = self
+            .last_visited_child
+            .iter()
+            .filter_map(|(parent, child)| Some((remap(*parent)?, remap(*child)?)))
+            .collect();
+        self.reversed_arrays = self
+            .reversed_arrays
+            .iter()
+            .filter_map(|i| remap(*i))
+            .collect();
+        Ok((remap, deleted))
+    }
+
+    // Put back a value removed by delete_value, focusing on it. Returns the
+    // function that maps the old index of each row to its new index.
+    pub fn restore_value(&mut self, deleted: DeletedValue) -> impl Fn(Index) -> Index {
+        let index = deleted.index;
+        let remap = self.flatjson.restore_value(deleted);
+        if self.fold_runs {
+            self.flatjson.fold_runs();
+        }
+        self.focused_row = index;
+        self.top_row = remap(self.top_row);
+        self.mark = self.mark.map(remap);
+        self.selected_rows = self.selected_rows.iter().map(|i| remap(*i)).collect();
+        self.last_visited_child = self
+            .last_visited_child
+            .iter()
+            .map(|(parent, child)| (remap(*parent), remap(*child)))
+            .collect();
+        self.reversed_arrays = self.reversed_arrays.iter().map(|i| remap(*i)).collect();
+        remap
+    }
+
+    fn remember_focused_row_in_parent(&mut self) {
+        let focused_row = &self.flatjson[self.focused_row];
+        // Remember containers by their opening row, even when focused on
+        // their closing row in line mode.
+        let child = if focused_row.is_closing_of_container() {
+            focused_row.pair_index().unwrap()
+        } else {
+            self.focused_row
+        };
+
+        if let OptionIndex::Index(parent) = focused_row.parent {
+            self.last_visited_child.insert(parent, child);
         }
     }
 
@@ -350,8 +546,8 @@ impl JsonViewer {
 
         loop {
             let prev_row = match self.mode {
-                Mode::Line => self.flatjson.prev_visible_row(row),
-                Mode::Data => self.flatjson.prev_item(row),
+                Mode::Line => self.prev_visible_row(row),
+                Mode::Data => self.prev_item(row),
             };
 
             match prev_row {
@@ -401,8 +597,8 @@ impl JsonViewer {
 
         loop {
             let next_row = match self.mode {
-                Mode::Line => self.flatjson.next_visible_row(row),
-                Mode::Data => self.flatjson.next_item(row),
+                Mode::Line => self.next_visible_row(row),
+                Mode::Data => self.next_item(row),
             };
 
             match next_row {
@@ -462,7 +658,7 @@ impl JsonViewer {
             {
                 let closing_brace = current_row.pair_index().unwrap();
                 self.focused_row = if self.mode == Mode::Data {
-                    match self.flatjson.next_item(closing_brace) {
+                    match self.next_item(closing_brace) {
                         // If there's no item after the closing brace, then we don't actually
                         // want to move the focus at all.
                         OptionIndex::Nil => self.focused_row,
@@ -480,7 +676,7 @@ impl JsonViewer {
     fn focus_first_sibling(&mut self) {
         match &self.flatjson[self.focused_row].parent {
             OptionIndex::Index(parent_index) => {
-                self.focused_row = self.flatjson[*parent_index].first_child().unwrap();
+                self.focused_row = self.first_child(*parent_index);
             }
             // If node has no parent, then we're at the top level and want to focus
             // the first element, which is the top of the file.
@@ -491,8 +687,7 @@ impl JsonViewer {
     fn focus_last_sibling(&mut self) {
         match &self.flatjson[self.focused_row].parent {
             OptionIndex::Index(parent_index) => {
-                let closing_parent_index = self.flatjson[*parent_index].pair_index().unwrap();
-                self.focused_row = self.flatjson[closing_parent_index].last_child().unwrap();
+                self.focused_row = self.last_child(*parent_index);
             }
             // If node has no parent, then we're at the top level and want to focus
             // the last element. If this last element is a container though, we want to
@@ -516,7 +711,7 @@ impl JsonViewer {
     fn focus_bottom(&mut self) {
         self.focused_row = match self.mode {
             Mode::Line => self.flatjson.last_visible_index(),
-            Mode::Data => self.flatjson.last_visible_item(),
+            Mode::Data => self.last_visible_item(),
         };
     }
 
@@ -546,7 +741,7 @@ impl JsonViewer {
             self.mode,
         );
 
-        if self.focused_row > max_focused_row {
+        if self.is_displayed_before(max_focused_row, self.focused_row) {
             self.focused_row = max_focused_row;
         }
     }
@@ -556,7 +751,7 @@ impl JsonViewer {
         let first_focusable_row =
             self.count_n_lines_past(self.top_row, self.scrolloff() as usize, self.mode);
 
-        if self.focused_row < first_focusable_row {
+        if self.is_displayed_before(self.focused_row, first_focusable_row) {
             self.focused_row = first_focusable_row;
         }
     }
@@ -594,15 +789,20 @@ impl JsonViewer {
 
         let last_line = match self.mode {
             Mode::Line => self.flatjson.last_visible_index(),
-            Mode::Data => self.flatjson.last_visible_item(),
+            Mode::Data => self.last_visible_item(),
         };
         let top_row_if_last_row_is_at_bottom =
             self.count_n_lines_before(last_line, self.dimensions.height as usize - 1, self.mode);
 
         // When jumping, we won't show lines past EOF, unless we already
         // are showing lines past EOF.
-        if self.top_row > top_row_if_last_row_is_at_bottom {
-            self.top_row = top_row_if_last_row_is_at_bottom.max(original_top_row);
+        if self.is_displayed_before(top_row_if_last_row_is_at_bottom, self.top_row) {
+            self.top_row =
+                if self.is_displayed_before(original_top_row, top_row_if_last_row_is_at_bottom) {
+                    top_row_if_last_row_is_at_bottom
+                } else {
+                    original_top_row
+                };
         }
 
         // If the viewing window moved at all, then keep the focused line in the
@@ -632,7 +832,17 @@ impl JsonViewer {
             Mode::Data => {
                 // Back up to a non-closing of a container.
                 while self.flatjson[self.focused_row].is_closing_of_container() {
-                    self.focused_row -= 1;
+                    let opening = self.flatjson[self.focused_row].pair_index().unwrap();
+                    self.focused_row = if self.is_reversed(opening) {
+                        // The first element is displayed last.
+                        let first = self.flatjson[opening].first_child().unwrap();
+                        match self.flatjson[first].pair_index() {
+                            OptionIndex::Index(close) => close,
+                            OptionIndex::Nil => first,
+                        }
+                    } else {
+                        self.focused_row - 1
+                    };
                 }
             }
             Mode::Line => {
@@ -655,11 +865,27 @@ impl JsonViewer {
                 self.flatjson.expand(parent);
                 curr = parent;
             }
+            self.flatjson.unfold_run_containing(self.focused_row);
         } else {
             self.focused_row = self.flatjson.first_visible_ancestor(self.focused_row);
         }
     }
 
+    fn reveal(&mut self, line: Index) {
+        let was_on_screen = self.is_row_on_screen(line);
+        self.jump_to(line, true);
+
+        if was_on_screen {
+            self.ensure_focused_row_is_visible();
+        } else {
+            // The same placement used when jumping far down the file; see
+            // ensure_focused_row_is_visible.
+            let max_padding = self.dimensions.height - self.scrolloff() - 1;
+            let bottom_padding = (self.dimensions.height * 2 / 3).min(max_padding);
+            self.place_focused_row_above_bottom(bottom_padding);
+        }
+    }
+
     // If the user provided a count to a jump command, sets that as the new
     // jump distance. Otherwise, use the stored jump distance, or if none has
     // been set yet, use the default of half a window size.
@@ -689,7 +915,9 @@ impl JsonViewer {
 
     fn click_row(&mut self, row: u16) {
         self.focused_row = self.count_n_lines_past(self.top_row, (row - 1) as usize, self.mode);
-        if self.flatjson[self.focused_row].is_opening_of_container() {
+        if self.flatjson[self.focused_row].is_opening_of_container()
+            || self.flatjson.folded_run_length(self.focused_row).is_some()
+        {
             self.toggle_collapsed();
         }
     }
@@ -697,6 +925,10 @@ impl JsonViewer {
     fn toggle_collapsed(&mut self) {
         let focused_row = &mut self.flatjson[self.focused_row];
         if focused_row.is_primitive() {
+            // Fold or unfold a run of identical elements instead.
+            if let Some(start) = self.flatjson.toggle_run_folded(self.focused_row) {
+                self.focused_row = start;
+            }
             return;
         }
 
@@ -709,6 +941,36 @@ impl JsonViewer {
         }
 
         self.flatjson.toggle_collapsed(self.focused_row);
+        if self.flatjson[self.focused_row].is_expanded() {
+            self.maybe_collapse_siblings_of_focused_row();
+        }
+    }
+
+    // Called after expanding the focused row; in accordion mode, collapse
+    // all of its siblings.
+    fn maybe_collapse_siblings_of_focused_row(&mut self) {
+        if !self.accordion_mode {
+            return;
+        }
+
+        let mut next_sibling = OptionIndex::Index(self.first_sibling_of_focused_row());
+
+        while let OptionIndex::Index(next) = next_sibling {
+            if next != self.focused_row {
+                self.flatjson.collapse(next);
+            }
+            next_sibling = self.flatjson[next].next_sibling;
+        }
+    }
+
+    fn first_sibling_of_focused_row(&self) -> Index {
+        if let OptionIndex::Index(parent) = self.flatjson[self.focused_row].parent {
+            self.flatjson[parent].first_child().unwrap()
+        } else {
+            // If we don't have parent, that means we're at the top level, so the first
+            // sibling is the very first element.
+            0
+        }
     }
 
     fn collapse_node_and_siblings(&mut self) {
@@ -731,6 +993,45 @@ impl JsonViewer {
         self.set_deep_collapse_state_on_node_and_siblings(false);
     }
 
+    fn toggle_selected(&mut self) {
+        let mut index = self.focused_row;
+        if self.flatjson[index].is_closing_of_container() {
+            index = self.flatjson[index].pair_index().unwrap();
+        }
+
+        if !self.selected_rows.remove(&index) {
+            self.selected_rows.insert(index);
+        }
+    }
+
+    fn collapse_selected(&mut self) {
+        let focused_row = &self.flatjson[self.focused_row];
+        if focused_row.is_closing_of_container()
+            && self
+                .selected_rows
+                .contains(&focused_row.pair_index().unwrap())
+        {
+            self.focused_row = focused_row.pair_index().unwrap();
+        }
+
+        for index in self.selected_rows.iter() {
+            if self.flatjson[*index].is_container() {
+                self.flatjson.collapse(*index);
+            }
+        }
+
+        // The focused row may have been inside one of the collapsed containers.
+        self.focused_row = self.flatjson.first_visible_ancestor(self.focused_row);
+    }
+
+    fn expand_selected(&mut self) {
+        for index in self.selected_rows.iter() {
+            if self.flatjson[*index].is_container() {
+                self.flatjson.expand(*index);
+            }
+        }
+    }
+
     fn switch_focus_to_opening_of_container_if_on_closing(&mut self) {
         let focused_row = &mut self.flatjson[self.focused_row];
         if focused_row.is_closing_of_container() {
@@ -743,16 +1044,7 @@ impl JsonViewer {
     }
 
     fn set_collapse_state_on_node_and_siblings(&mut self, collapsed: bool) {
-        let first_sibling =
-            if let OptionIndex::Index(parent) = self.flatjson[self.focused_row].parent {
-                self.flatjson[parent].first_child().unwrap()
-            } else {
-                // If we don't have parent, that means we're at the top level, so the first
-                // sibling is the very first element.
-                0
-            };
-
-        let mut next_sibling = OptionIndex::Index(first_sibling);
+        let mut next_sibling = OptionIndex::Index(self.first_sibling_of_focused_row());
 
         while let OptionIndex::Index(next) = next_sibling {
             if collapsed {
@@ -795,10 +1087,10 @@ impl JsonViewer {
             // By focusing the next item, and ensuring that the focus stays in the
             // same place on the screen, it will look the surrounding data is getting
             // "pulled" towards the focused line.
-            if let OptionIndex::Index(next) = self.flatjson.next_item(self.focused_row) {
+            if let OptionIndex::Index(next) = self.next_item(self.focused_row) {
                 self.focused_row = next;
             } else {
-                self.focused_row = self.flatjson.prev_item(self.focused_row).unwrap();
+                self.focused_row = self.prev_item(self.focused_row).unwrap();
             }
         }
 
@@ -807,6 +1099,68 @@ impl JsonViewer {
             Mode::Line => Mode::Data,
             Mode::Data => Mode::Line,
         };
+
+        self.sub_focus = None;
+    }
+
+    // Cycle between highlighting the key of the focused row, its value,
+    // and the whole row. Rows without keys skip straight to the value.
+    fn toggle_sub_focus(&mut self) {
+        if self.mode != Mode::Data {
+            return;
+        }
+
+        let has_key = self.flatjson[self.focused_row].key_range.is_some();
+        self.sub_focus = match self.sub_focus {
+            None if has_key => Some(SubFocus::Key),
+            None | Some(SubFocus::Key) => Some(SubFocus::Value),
+            Some(SubFocus::Value) => None,
+        };
+    }
+
+    // Find the next sibling of the focused row whose key starts with the
+    // given character (ignoring case), wrapping around to the first
+    // sibling, so that repeatedly looking up the same character cycles
+    // through all the matching keys.
+    pub fn next_sibling_with_key_starting_with(&self, ch: char) -> Option<Index> {
+        let mut focused_row = self.focused_row;
+        if self.flatjson[focused_row].is_closing_of_container() {
+            focused_row = self.flatjson[focused_row].pair_index().unwrap();
+        }
+
+        let OptionIndex::Index(parent) = self.flatjson[focused_row].parent else {
+            return None;
+        };
+
+        let key_starts_with_ch = |index: Index| match &self.flatjson[index].key_range {
+            // key_range includes the quotes around the key.
+            Some(key_range) => self.flatjson.1[key_range.start + 1..key_range.end - 1]
+                .chars()
+                .next()
+                .map_or(false, |first| first.to_lowercase().eq(ch.to_lowercase())),
+            None => false,
+        };
+
+        let mut sibling = self.flatjson[focused_row].next_sibling;
+        while let OptionIndex::Index(index) = sibling {
+            if key_starts_with_ch(index) {
+                return Some(index);
+            }
+            sibling = self.flatjson[index].next_sibling;
+        }
+
+        let mut sibling = self.flatjson[parent].first_child();
+        while let OptionIndex::Index(index) = sibling {
+            if index > focused_row {
+                break;
+            }
+            if key_starts_with_ch(index) {
+                return Some(index);
+            }
+            sibling = self.flatjson[index].next_sibling;
+        }
+
+        None
     }
 
     fn scrolloff(&self) -> u16 {
@@ -873,7 +1227,9 @@ impl JsonViewer {
         );
 
         // Handle focused line too close to or past the top of the screen.
-        if self.focused_row < self.top_row || num_visible_before_focused < scrolloff {
+        if self.is_displayed_before(self.focused_row, self.top_row)
+            || num_visible_before_focused < scrolloff
+        {
             self.top_row =
                 self.count_n_lines_before(self.focused_row, scrolloff as usize, self.mode);
         } else if num_visible_before_focused > max_padding {
@@ -892,33 +1248,35 @@ impl JsonViewer {
                 scrolloff
             };
 
-            // We need to figure out where the last line is because we won't
-            // show any empty lines past the end of the file (unless the
-            // user explicitly scrolls past the end of the file).
-            //
-            // This overrides the scrolloff setting.
-            let last_line = match self.mode {
-                Mode::Line => self.flatjson.last_visible_index(),
-                Mode::Data => self.flatjson.last_visible_item(),
-            };
-            let lines_visible_before_eof = self.count_visible_rows_before(
-                self.focused_row,
-                last_line,
-                refocus_padding + 1,
-                self.mode,
-            );
-
-            // Clamp the refocus padding at the number of lines visible before EOF
-            // so that we don't show anything past EOF.
-            let bottom_padding = refocus_padding.min(lines_visible_before_eof);
-            self.top_row = self.count_n_lines_before(
-                self.focused_row,
-                (self.dimensions.height - bottom_padding - 1) as usize,
-                self.mode,
-            );
+            self.place_focused_row_above_bottom(refocus_padding);
         }
     }
 
+    // Scroll so that there are `padding` lines between the focused row and the
+    // bottom of the screen.
+    fn place_focused_row_above_bottom(&mut self, padding: u16) {
+        // We need to figure out where the last line is because we won't
+        // show any empty lines past the end of the file (unless the
+        // user explicitly scrolls past the end of the file).
+        //
+        // This overrides the scrolloff setting.
+        let last_line = match self.mode {
+            Mode::Line => self.flatjson.last_visible_index(),
+            Mode::Data => self.last_visible_item(),
+        };
+        let lines_visible_before_eof =
+            self.count_visible_rows_before(self.focused_row, last_line, padding + 1, self.mode);
+
+        // Clamp the padding at the number of lines visible before EOF so that
+        // we don't show anything past EOF.
+        let bottom_padding = padding.min(lines_visible_before_eof);
+        self.top_row = self.count_n_lines_before(
+            self.focused_row,
+            (self.dimensions.height - bottom_padding - 1) as usize,
+            self.mode,
+        );
+    }
+
     // Makes sure that the top row is visible. If not, the top row will be updated
     // to the first visible parent of the top row.
     //
@@ -937,22 +1295,218 @@ impl JsonViewer {
             }
         }
 
-        // Now make sure all ancestors are visible.
-        let mut ancestor = self.top_row;
-        while let OptionIndex::Index(ancestor_index) = self.flatjson[ancestor].parent {
-            if self.flatjson[ancestor_index].is_collapsed() {
-                self.top_row = ancestor_index;
+        // Now make sure all ancestors are visible, and that the top row
+        // isn't hidden in a folded run.
+        self.top_row = self.flatjson.first_visible_ancestor(self.top_row);
+    }
+
+    // The rows of the document are displayed in order, except that the
+    // elements of reversed arrays are walked through from last to first.
+    // These functions only fall back to doing that when there are any
+    // reversed arrays, so that normally moving around is as cheap as
+    // stepping to the row before or after.
+
+    pub fn prev_visible_row(&self, index: Index) -> OptionIndex {
+        if self.reversed_arrays.is_empty() {
+            return self.flatjson.prev_visible_row(index);
+        }
+
+        let row = &self.flatjson[index];
+        if row.is_closing_of_container() {
+            let last_child = self.last_child(row.pair_index().unwrap());
+            return OptionIndex::Index(self.last_visible_row_of(last_child));
+        }
+
+        match self.prev_sibling(index) {
+            OptionIndex::Index(prev) => OptionIndex::Index(self.last_visible_row_of(prev)),
+            OptionIndex::Nil => row.parent,
+        }
+    }
+
+    pub fn next_visible_row(&self, index: Index) -> OptionIndex {
+        if self.reversed_arrays.is_empty() {
+            return self.flatjson.next_visible_row(index);
+        }
+
+        let row = &self.flatjson[index];
+        if row.is_opening_of_container() && row.is_expanded() {
+            return OptionIndex::Index(self.first_child(index));
+        }
+
+        // After a closing row comes whatever comes after the whole container.
+        let value = if row.is_closing_of_container() {
+            row.pair_index().unwrap()
+        } else {
+            index
+        };
+        match self.next_sibling(value) {
+            OptionIndex::Index(next) => OptionIndex::Index(next),
+            OptionIndex::Nil => match self.flatjson[value].parent {
+                OptionIndex::Index(parent) => self.flatjson[parent].pair_index(),
+                OptionIndex::Nil => OptionIndex::Nil,
+            },
+        }
+    }
+
+    pub fn prev_item(&self, mut index: Index) -> OptionIndex {
+        if self.reversed_arrays.is_empty() {
+            return self.flatjson.prev_item(index);
+        }
+
+        while let OptionIndex::Index(i) = self.prev_visible_row(index) {
+            if !self.flatjson[i].is_closing_of_container() {
+                return OptionIndex::Index(i);
             }
 
-            ancestor = ancestor_index;
+            index = i;
+        }
+
+        OptionIndex::Nil
+    }
+
+    pub fn next_item(&self, mut index: Index) -> OptionIndex {
+        if self.reversed_arrays.is_empty() {
+            return self.flatjson.next_item(index);
+        }
+
+        while let OptionIndex::Index(i) = self.next_visible_row(index) {
+            if !self.flatjson[i].is_closing_of_container() {
+                return OptionIndex::Index(i);
+            }
+
+            index = i;
+        }
+
+        OptionIndex::Nil
+    }
+
+    fn last_visible_item(&self) -> Index {
+        if self.reversed_arrays.is_empty() {
+            return self.flatjson.last_visible_item();
+        }
+
+        let last_row = self.flatjson.last_visible_index();
+        if self.flatjson[last_row].is_closing_of_container() {
+            self.prev_item(last_row).unwrap()
+        } else {
+            last_row
+        }
+    }
+
+    // The sibling displayed after a value, given its opening row, treating
+    // a folded run as a single value.
+    pub fn next_sibling(&self, index: Index) -> OptionIndex {
+        if self.is_in_reversed_array(index) {
+            self.prev_sibling_in_document(index)
+        } else {
+            self.next_sibling_in_document(index)
+        }
+    }
+
+    fn prev_sibling(&self, index: Index) -> OptionIndex {
+        if self.is_in_reversed_array(index) {
+            self.next_sibling_in_document(index)
+        } else {
+            self.prev_sibling_in_document(index)
+        }
+    }
+
+    // The child of a container, given its opening row, displayed first.
+    fn first_child(&self, index: Index) -> Index {
+        if self.is_reversed(index) {
+            let close_index = self.flatjson[index].pair_index().unwrap();
+            self.visible_row(self.flatjson[close_index].last_child().unwrap())
+        } else {
+            self.flatjson[index].first_child().unwrap()
+        }
+    }
+
+    // The child of a container, given its opening row, displayed last.
+    fn last_child(&self, index: Index) -> Index {
+        if self.is_reversed(index) {
+            self.flatjson[index].first_child().unwrap()
+        } else {
+            let close_index = self.flatjson[index].pair_index().unwrap();
+            self.visible_row(self.flatjson[close_index].last_child().unwrap())
+        }
+    }
+
+    fn is_in_reversed_array(&self, index: Index) -> bool {
+        matches!(self.flatjson[index].parent, OptionIndex::Index(parent) if self.is_reversed(parent))
+    }
+
+    fn next_sibling_in_document(&self, index: Index) -> OptionIndex {
+        let last = match self.flatjson.folded_run_length(index) {
+            Some(len) => index + len - 1,
+            None => index,
+        };
+        self.flatjson[last].next_sibling
+    }
+
+    fn prev_sibling_in_document(&self, index: Index) -> OptionIndex {
+        match self.flatjson[index].prev_sibling {
+            OptionIndex::Index(prev) => OptionIndex::Index(self.visible_row(prev)),
+            OptionIndex::Nil => OptionIndex::Nil,
         }
     }
 
+    // The row that a value is shown on, which is the start of the folded
+    // run it's in, if it's in one.
+    fn visible_row(&self, index: Index) -> Index {
+        self.flatjson.start_of_folded_run(index).unwrap_or(index)
+    }
+
+    // The last row of a value that's visible, which is the closing row of
+    // an expanded container.
+    fn last_visible_row_of(&self, index: Index) -> Index {
+        let row = &self.flatjson[index];
+        if row.is_opening_of_container() && row.is_expanded() {
+            row.pair_index().unwrap()
+        } else {
+            index
+        }
+    }
+
+    // Whether one row is displayed before another one.
+    fn is_displayed_before(&self, a: Index, b: Index) -> bool {
+        if self.reversed_arrays.is_empty() {
+            return a < b;
+        }
+        self.display_position(a) < self.display_position(b)
+    }
+
+    // The position of a row in the order they're displayed in, as the
+    // position of it and each of its ancestors among their siblings,
+    // starting from the top level. Positions are compared in order, and
+    // elements of reversed arrays are counted down from the end.
+    fn display_position(&self, index: Index) -> Vec<usize> {
+        let mut position = vec![];
+        let mut value = index;
+        if self.flatjson[index].is_closing_of_container() {
+            // Closing rows come after all of the children of their container.
+            position.push(usize::MAX);
+            value = self.flatjson[index].pair_index().unwrap();
+        }
+        loop {
+            if self.is_in_reversed_array(value) {
+                position.push(usize::MAX - 1 - value);
+            } else {
+                position.push(value);
+            }
+            match self.flatjson[value].parent {
+                OptionIndex::Index(parent) => value = parent,
+                OptionIndex::Nil => break,
+            }
+        }
+        position.reverse();
+        position
+    }
+
     fn count_n_lines_before(&self, mut start: Index, mut lines: usize, mode: Mode) -> Index {
         while lines != 0 && start != 0 {
             start = match mode {
-                Mode::Line => self.flatjson.prev_visible_row(start).unwrap(),
-                Mode::Data => self.flatjson.prev_item(start).unwrap(),
+                Mode::Line => self.prev_visible_row(start).unwrap(),
+                Mode::Data => self.prev_item(start).unwrap(),
             };
             lines -= 1;
         }
@@ -962,8 +1516,8 @@ impl JsonViewer {
     fn count_n_lines_past(&self, mut start: Index, mut lines: usize, mode: Mode) -> Index {
         while lines != 0 {
             let next = match mode {
-                Mode::Line => self.flatjson.next_visible_row(start),
-                Mode::Data => self.flatjson.next_item(start),
+                Mode::Line => self.next_visible_row(start),
+                Mode::Data => self.next_item(start),
             };
 
             match next {
@@ -987,11 +1541,11 @@ impl JsonViewer {
     // we'll return max.
     fn count_visible_rows_before(&self, mut start: Index, end: Index, max: u16, mode: Mode) -> u16 {
         let mut num_visible: u16 = 0;
-        while start < end && num_visible < max {
+        while self.is_displayed_before(start, end) && num_visible < max {
             num_visible += 1;
             start = match mode {
-                Mode::Line => self.flatjson.next_visible_row(start).unwrap(),
-                Mode::Data => self.flatjson.next_item(start).unwrap(),
+                Mode::Line => self.next_visible_row(start).unwrap(),
+                Mode::Data => self.next_item(start).unwrap(),
             };
         }
         num_visible
@@ -1006,6 +1560,24 @@ impl JsonViewer {
             self.mode,
         )
     }
+
+    // Whether the given row is one of those currently in the viewing window.
+    pub fn is_row_on_screen(&self, index: Index) -> bool {
+        let mut row = OptionIndex::Index(self.top_row);
+        for _ in 0..self.dimensions.height {
+            match row {
+                OptionIndex::Index(i) if i == index => return true,
+                OptionIndex::Index(i) => {
+                    row = match self.mode {
+                        Mode::Line => self.next_visible_row(i),
+                        Mode::Data => self.next_item(i),
+                    }
+                }
+                OptionIndex::Nil => return false,
+            }
+        }
+        false
+    }
 }
 
 #[cfg(test)]
@@ -1014,10 +1586,10 @@ mod tests {
     use crate::flatjson::{parse_top_level_json, NIL};
 
     impl OptionIndex {
-        pub fn to_usize(&self) -> usize {
+        pub fn to_usize(self) -> usize {
             match self {
                 OptionIndex::Nil => NIL,
-                OptionIndex::Index(i) => *i,
+                OptionIndex::Index(i) => i,
             }
         }
     }
@@ -1208,6 +1780,33 @@ mod tests {
         assert_movements(&mut viewer, vec![(Action::MoveLeft, 0)]);
     }
 
+    #[test]
+    fn test_accordion_mode() {
+        let fj = parse_top_level_json(DATA_OBJECT.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Data);
+        viewer.accordion_mode = true;
+
+        viewer.flatjson.collapse(2);
+        assert_movements(
+            &mut viewer,
+            vec![(Action::MoveDown(2), 2), (Action::MoveRight, 2)],
+        );
+        assert!(viewer.flatjson[2].is_expanded());
+        assert!(viewer.flatjson[6].is_collapsed());
+
+        assert_movements(
+            &mut viewer,
+            vec![(Action::MoveDown(3), 6), (Action::ToggleCollapsed, 6)],
+        );
+        assert!(viewer.flatjson[2].is_collapsed());
+        assert!(viewer.flatjson[6].is_expanded());
+
+        // Collapsing a node doesn't affect its siblings.
+        viewer.flatjson.expand(2);
+        assert_movements(&mut viewer, vec![(Action::ToggleCollapsed, 6)]);
+        assert!(viewer.flatjson[2].is_expanded());
+    }
+
     #[test]
     fn test_move_up_down_until_depth_change_line_mode() {
         let fj = parse_top_level_json(OBJECT.to_owned()).unwrap();
@@ -2268,6 +2867,279 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_toggle_sub_focus() {
+        let fj = parse_top_level_json(r#"{"a": [1]}"#.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Data);
+
+        // Top-level value has no key.
+        viewer.perform_action(Action::ToggleSubFocus);
+        assert_eq!(Some(SubFocus::Value), viewer.sub_focus);
+        viewer.perform_action(Action::ToggleSubFocus);
+        assert_eq!(None, viewer.sub_focus);
+
+        viewer.focused_row = 1;
+        viewer.perform_action(Action::ToggleSubFocus);
+        assert_eq!(Some(SubFocus::Key), viewer.sub_focus);
+        viewer.perform_action(Action::ToggleSubFocus);
+        assert_eq!(Some(SubFocus::Value), viewer.sub_focus);
+
+        // Sub-focus is kept when moving, but not when changing modes.
+        viewer.perform_action(Action::MoveDown(1));
+        assert_eq!(Some(SubFocus::Value), viewer.sub_focus);
+        viewer.perform_action(Action::ToggleMode);
+        assert_eq!(None, viewer.sub_focus);
+        viewer.perform_action(Action::ToggleSubFocus);
+        assert_eq!(None, viewer.sub_focus);
+    }
+
+    #[test]
+    fn test_next_sibling_with_key_starting_with() {
+        let fj = parse_top_level_json(
+            r#"{"apple": 1, "banana": {"avocado": 2}, "Apricot": 3, "cherry": [4]}"#.to_owned(),
+        )
+        .unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+
+        // The top-level value has no siblings.
+        assert_eq!(None, viewer.next_sibling_with_key_starting_with('a'));
+
+        viewer.focused_row = 1;
+        assert_eq!(Some(5), viewer.next_sibling_with_key_starting_with('a'));
+        viewer.focused_row = 5;
+        // Wraps around to the first sibling, but not into nested objects.
+        assert_eq!(Some(1), viewer.next_sibling_with_key_starting_with('A'));
+        assert_eq!(Some(2), viewer.next_sibling_with_key_starting_with('b'));
+        assert_eq!(None, viewer.next_sibling_with_key_starting_with('z'));
+
+        // The only match is the focused row itself.
+        viewer.focused_row = 2;
+        assert_eq!(Some(2), viewer.next_sibling_with_key_starting_with('b'));
+
+        // The closing brace of a container is treated like its opening.
+        viewer.focused_row = 4;
+        assert_eq!(Some(6), viewer.next_sibling_with_key_starting_with('c'));
+    }
+
+    #[test]
+    fn test_is_row_on_screen() {
+        let fj = parse_top_level_json(r#"[1, 2, 3, [4, 5], 6]"#.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+        viewer.dimensions.height = 3;
+        viewer.top_row = 1;
+
+        assert!(!viewer.is_row_on_screen(0));
+        assert!(viewer.is_row_on_screen(1));
+        assert!(viewer.is_row_on_screen(3));
+        assert!(!viewer.is_row_on_screen(4));
+
+        viewer.flatjson.collapse(4);
+        viewer.top_row = 3;
+        // Rows inside collapsed containers aren't shown.
+        assert!(!viewer.is_row_on_screen(5));
+        assert!(viewer.is_row_on_screen(4));
+        assert!(viewer.is_row_on_screen(8));
+        // Nothing past the end of the document.
+        assert!(!viewer.is_row_on_screen(9));
+    }
+
+    #[test]
+    fn test_reveal() {
+        let numbers: Vec<String> = (1..=40).map(|n| n.to_string()).collect();
+        let fj = parse_top_level_json(format!("[{}]", numbers.join(", "))).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+        viewer.dimensions.height = 9;
+        viewer.scrolloff_setting = 3;
+
+        // Rows that aren't on the screen are shown a third of the way down,
+        // whether they're above or below the screen.
+        viewer.perform_action(Action::Reveal(30));
+        assert_eq!((30, 27), (viewer.focused_row, viewer.top_row));
+        viewer.perform_action(Action::Reveal(5));
+        assert_eq!((5, 2), (viewer.focused_row, viewer.top_row));
+
+        // Rows already on the screen just obey scrolloff.
+        viewer.perform_action(Action::Reveal(4));
+        assert_eq!((4, 1), (viewer.focused_row, viewer.top_row));
+
+        // Without showing anything past the end.
+        viewer.perform_action(Action::Reveal(39));
+        assert_eq!((39, 33), (viewer.focused_row, viewer.top_row));
+
+        let fj = parse_top_level_json("[[1, [2]], 3]".to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+        viewer.flatjson.collapse(3);
+        viewer.flatjson.collapse(1);
+        viewer.perform_action(Action::Reveal(4));
+        assert_eq!(4, viewer.focused_row);
+        assert!(viewer.flatjson[1].is_expanded());
+        assert!(viewer.flatjson[3].is_expanded());
+    }
+
+    #[test]
+    fn test_batch_collapse_selected() {
+        let fj = parse_top_level_json(r#"[{"a": 1}, {"b": 2}, {"c": 3}]"#.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+
+        // Rows: 0 [, 1 {, 2 a, 3 }, 4 {, 5 b, 6 }, 7 {, 8 c, 9 }, 10 ]
+        viewer.focused_row = 1;
+        viewer.perform_action(Action::ToggleSelected);
+        // Selecting the closing brace selects the container.
+        viewer.focused_row = 9;
+        viewer.perform_action(Action::ToggleSelected);
+        assert_eq!(
+            vec![1, 7],
+            viewer.selected_rows.iter().copied().collect::<Vec<_>>()
+        );
+
+        viewer.focused_row = 8;
+        viewer.perform_action(Action::CollapseSelected);
+        assert!(viewer.flatjson[1].is_collapsed());
+        assert!(viewer.flatjson[4].is_expanded());
+        assert!(viewer.flatjson[7].is_collapsed());
+        assert_eq!(7, viewer.focused_row);
+
+        viewer.perform_action(Action::ExpandSelected);
+        assert!(viewer.flatjson[1].is_expanded());
+        assert!(viewer.flatjson[7].is_expanded());
+
+        // Toggling again deselects the row.
+        viewer.perform_action(Action::ToggleSelected);
+        assert_eq!(
+            vec![1],
+            viewer.selected_rows.iter().copied().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_remember_last_child() {
+        let fj = parse_top_level_json(r#"{"a": [1, 2, 3], "b": {"c": [4]}}"#.to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+
+        // Rows: 0 {, 1 a: [, 2 1, 3 2, 4 3, 5 ], 6 b: {, 7 c: [, 8 4, 9 ], 10 }, 11 }
+        viewer.focused_row = 1;
+        viewer.perform_action(Action::MoveRight);
+        viewer.perform_action(Action::MoveDown(2));
+        viewer.perform_action(Action::FocusParent);
+        assert_eq!(1, viewer.focused_row);
+
+        // Disabled by default.
+        viewer.perform_action(Action::MoveRight);
+        assert_eq!(2, viewer.focused_row);
+
+        viewer.remember_last_child = true;
+        viewer.perform_action(Action::MoveDown(2));
+        viewer.perform_action(Action::FocusParent);
+        viewer.perform_action(Action::MoveRight);
+        assert_eq!(4, viewer.focused_row);
+
+        // Containers focused on their closing rows are remembered by their
+        // opening rows.
+        viewer.perform_action(Action::JumpTo {
+            line: 9,
+            make_visible: false,
+        });
+        viewer.perform_action(Action::FocusParent);
+        viewer.perform_action(Action::MoveRight);
+        assert_eq!(7, viewer.focused_row);
+
+        // Containers that were never visited focus their first child.
+        viewer.perform_action(Action::FocusTop);
+        viewer.perform_action(Action::MoveRight);
+        assert_eq!(6, viewer.focused_row);
+    }
+
+    #[test]
+    fn test_reversed_array() {
+        const JSON: &str = r#"{"a": [1, {"b": 2}, 3], "c": 4}"#;
+        let fj = parse_top_level_json(JSON.to_owned()).unwrap();
+        let document = fj.1.to_string();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+
+        // Rows: 0 {, 1 a: [, 2 1, 3 {, 4 b, 5 }, 6 3, 7 ], 8 c, 9 }
+        assert!(viewer.toggle_reversed(1));
+        assert!(viewer.is_reversed(1));
+        assert_eq!(document, &*viewer.flatjson.1);
+
+        let moves = [1, 6, 3, 4, 5, 2, 7, 8, 9, 9];
+        assert_movements(
+            &mut viewer,
+            moves
+                .iter()
+                .map(|row| (Action::MoveDown(1), *row))
+                .collect(),
+        );
+        let moves = [8, 7, 2, 5, 4, 3, 6, 1, 0, 0];
+        assert_movements(
+            &mut viewer,
+            moves.iter().map(|row| (Action::MoveUp(1), *row)).collect(),
+        );
+
+        viewer.focused_row = 3;
+        assert_movements(
+            &mut viewer,
+            vec![
+                (Action::FocusFirstSibling, 6),
+                (Action::FocusLastSibling, 2),
+                (Action::FocusParent, 1),
+                (Action::MoveRight, 6),
+            ],
+        );
+        assert!(viewer.is_displayed_before(6, 2));
+        assert!(viewer.is_displayed_before(5, 7));
+        assert!(!viewer.is_displayed_before(8, 3));
+
+        viewer.perform_action(Action::ToggleMode);
+        viewer.focused_row = 0;
+        let moves = [1, 6, 3, 4, 2, 8, 8];
+        assert_movements(
+            &mut viewer,
+            moves
+                .iter()
+                .map(|row| (Action::MoveDown(1), *row))
+                .collect(),
+        );
+        // The closing row of a reversed array backs up to its first element.
+        assert_movements(
+            &mut viewer,
+            vec![(
+                Action::JumpTo {
+                    line: 7,
+                    make_visible: false,
+                },
+                2,
+            )],
+        );
+
+        assert!(!viewer.toggle_reversed(1));
+        viewer.focused_row = 1;
+        assert_movements(&mut viewer, vec![(Action::MoveDown(1), 2)]);
+    }
+
+    #[test]
+    fn test_reversed_array_with_folded_run() {
+        let fj = parse_top_level_json("[0, 0, 0, 0, 1]".to_owned()).unwrap();
+        let mut viewer = JsonViewer::new(fj, Mode::Line);
+        viewer.set_fold_runs(true);
+        viewer.toggle_reversed(0);
+
+        // Rows: 0 [, 1-4 0, 5 1, 6 ]
+        let moves = [5, 1, 6];
+        assert_movements(
+            &mut viewer,
+            moves
+                .iter()
+                .map(|row| (Action::MoveDown(1), *row))
+                .collect(),
+        );
+        let moves = [1, 5, 0];
+        assert_movements(
+            &mut viewer,
+            moves.iter().map(|row| (Action::MoveUp(1), *row)).collect(),
+        );
+        assert_eq!(OptionIndex::Nil, viewer.next_sibling(1));
+    }
+
     #[track_caller]
     fn assert_window_tracking(
         viewer: &mut JsonViewer,
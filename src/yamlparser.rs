@@ -1,7 +1,8 @@
 use yaml_rust::yaml::{Array, Hash, Yaml};
 use yaml_rust::YamlLoader;
 
-use crate::flatjson::{ContainerType, Index, OptionIndex, Row, Value};
+use crate::flatjson::{ContainerType, Index, NumberType, OptionIndex, Row, Value};
+use crate::jsonstringunescaper::escape_json_string;
 
 struct YamlParser {
     parents: Vec<Index>,
@@ -51,8 +52,8 @@ impl YamlParser {
             Yaml::BadValue => return Err("Unknown YAML parse error".to_owned()),
             Yaml::Null => self.parse_null(),
             Yaml::Boolean(b) => self.parse_bool(b),
-            Yaml::Integer(i) => self.parse_number(i.to_string()),
-            Yaml::Real(real_str) => self.parse_number(real_str),
+            Yaml::Integer(i) => self.parse_number(i.to_string(), NumberType::Integer),
+            Yaml::Real(real_str) => self.parse_number(real_str, NumberType::Float),
             Yaml::String(s) => self.parse_string(s),
             Yaml::Array(arr) => self.parse_array(arr)?,
             Yaml::Hash(hash) => self.parse_hash(hash)?,
@@ -82,8 +83,8 @@ impl YamlParser {
         row_index
     }
 
-    fn parse_number(&mut self, num_s: String) -> usize {
-        let row_index = self.create_row(Value::Number);
+    fn parse_number(&mut self, num_s: String, number_type: NumberType) -> usize {
+        let row_index = self.create_row(Value::Number(number_type));
         self.pretty_printed.push_str(&num_s);
 
         self.rows[row_index].range.end = self.rows[row_index].range.start + num_s.len();
@@ -94,8 +95,7 @@ impl YamlParser {
     fn parse_string(&mut self, s: String) -> usize {
         let row_index = self.create_row(Value::String);
 
-        // Escape newlines.
-        let s = s.replace('\n', "\\n");
+        let s = escape_json_string(&s);
 
         self.pretty_printed.push('"');
         self.pretty_printed.push_str(&s);
@@ -289,8 +289,7 @@ impl YamlParser {
 
     fn pretty_print_key_item(&mut self, item: Yaml, is_key: bool) -> Result<(), String> {
         if let Yaml::String(s) = item {
-            // Replace newlines.
-            let s = s.replace('\n', "\\n");
+            let s = escape_json_string(&s);
             self.pretty_printed.push('"');
             self.pretty_printed.push_str(&s);
             self.pretty_printed.push('"');
@@ -306,7 +305,7 @@ impl YamlParser {
             Yaml::Null => self.pretty_printed.push_str("null"),
             Yaml::Boolean(b) => self
                 .pretty_printed
-                .push_str(if b { "true" } else { "false " }),
+                .push_str(if b { "true" } else { "false" }),
             Yaml::Integer(i) => self.pretty_printed.push_str(&i.to_string()),
             Yaml::Real(real_str) => self.pretty_printed.push_str(&real_str),
             Yaml::Array(arr) => {